@@ -1,65 +1,87 @@
 //! Benchmarks for ClickHouse Rust client
+//!
+//! Block construction, row decoding and packet encode/decode.
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use clickhouse_rs::types::{Block, Column, ColumnData, UInt8, String as ClickHouseString};
+use bytes::BytesMut;
+use clickhouse_rs::protocol::{Packet, ProtocolReader, ProtocolWriter, ServerData};
+use clickhouse_rs::types::{Block, Column, ColumnData};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 
-fn create_test_block() -> Block {
+const ROW_COUNTS: [usize; 3] = [100, 10_000, 100_000];
+
+fn create_test_block(rows: usize) -> Block {
     let mut block = Block::new();
-    
-    // Add ID column
-    let id_column = Column::new(
-        "id".to_string(),
-        ColumnData::UInt8(vec![1, 2, 3, 4, 5]),
+
+    block.add_column("id", Column::new("id", "UInt64", ColumnData::UInt64((0..rows as u64).collect())));
+    block.add_column(
+        "name",
+        Column::new("name", "String", ColumnData::String((0..rows).map(|i| format!("user_{}", i)).collect())),
     );
-    block.add_column(id_column);
-    
-    // Add name column
-    let name_column = Column::new(
-        "name".to_string(),
-        ColumnData::String(vec![
-            ClickHouseString::new("Alice"),
-            ClickHouseString::new("Bob"),
-            ClickHouseString::new("Charlie"),
-            ClickHouseString::new("Diana"),
-            ClickHouseString::new("Eve"),
-        ]),
+    block.add_column(
+        "score",
+        Column::new("score", "Float64", ColumnData::Float64((0..rows).map(|i| i as f64 * 0.5).collect())),
     );
-    block.add_column(name_column);
-    
+
     block
 }
 
 fn block_creation_benchmark(c: &mut Criterion) {
-    c.bench_function("block_creation", |b| {
-        b.iter(|| create_test_block())
-    });
+    let mut group = c.benchmark_group("block_creation");
+    for rows in ROW_COUNTS {
+        group.throughput(Throughput::Elements(rows as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &rows, |b, &rows| {
+            b.iter(|| create_test_block(black_box(rows)))
+        });
+    }
+    group.finish();
 }
 
-fn block_serialization_benchmark(c: &mut Criterion) {
-    let block = create_test_block();
-    
-    c.bench_function("block_serialization", |b| {
+fn block_decode_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("block_decode_rows");
+    for rows in ROW_COUNTS {
+        let block = create_test_block(rows);
+        group.throughput(Throughput::Elements(rows as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &block, |b, block| {
+            b.iter(|| block.rows().map(|row| row.len()).sum::<usize>())
+        });
+    }
+    group.finish();
+}
+
+fn packet_encode_benchmark(c: &mut Criterion) {
+    let packet = ServerData::new(create_test_block(ROW_COUNTS[0])).with_compression_method("lz4");
+
+    c.bench_function("packet_encode", |b| {
+        let mut out = Vec::with_capacity(64);
         b.iter(|| {
-            let _ = serde_json::to_string(&block);
+            out.clear();
+            ProtocolWriter::new(&mut out).write_packet(black_box(&packet)).unwrap();
         })
     });
 }
 
-fn block_deserialization_benchmark(c: &mut Criterion) {
-    let block = create_test_block();
-    let json = serde_json::to_string(&block).unwrap();
-    
-    c.bench_function("block_deserialization", |b| {
-        b.iter(|| {
-            let _: Block = serde_json::from_str(&json).unwrap();
-        })
+fn packet_decode_benchmark(c: &mut Criterion) {
+    let packet = ServerData::new(create_test_block(ROW_COUNTS[0])).with_compression_method("lz4");
+    let mut body = BytesMut::new();
+    packet.serialize(&mut body).unwrap();
+
+    let mut framed = Vec::new();
+    ProtocolWriter::new(&mut framed).write_packet(&packet).unwrap();
+
+    c.bench_function("packet_decode_body", |b| {
+        b.iter(|| ServerData::deserialize(&mut black_box(body.clone())).unwrap())
+    });
+
+    c.bench_function("packet_decode_framed", |b| {
+        b.iter(|| ProtocolReader::new(black_box(framed.as_slice())).read_packet().unwrap())
     });
 }
 
 criterion_group!(
     benches,
     block_creation_benchmark,
-    block_serialization_benchmark,
-    block_deserialization_benchmark
+    block_decode_benchmark,
+    packet_encode_benchmark,
+    packet_decode_benchmark
 );
 criterion_main!(benches);
@@ -0,0 +1,49 @@
+//! Compression benchmarks
+
+use clickhouse_rs::compression::{CompressedData, CompressionLevel, CompressionManager, CompressionMethod};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const PAYLOAD_SIZES: [usize; 3] = [4 * 1024, 64 * 1024, 1024 * 1024];
+
+/// Column-like payload: repetitive enough to compress, not trivially so
+fn payload(size: usize) -> Vec<u8> {
+    (0..size as u64)
+        .flat_map(|i| ((i / 7) % 1000).to_le_bytes())
+        .take(size)
+        .collect()
+}
+
+fn compress_benchmark(c: &mut Criterion) {
+    for method in [CompressionMethod::LZ4, CompressionMethod::ZSTD] {
+        let manager = CompressionManager::new(method, CompressionLevel::default(), 0).unwrap();
+        let mut group = c.benchmark_group(format!("compress_{}", method.as_str()));
+
+        for size in PAYLOAD_SIZES {
+            let data = payload(size);
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+                b.iter(|| manager.compress_if_needed(black_box(data)).unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+fn decompress_benchmark(c: &mut Criterion) {
+    for method in [CompressionMethod::LZ4, CompressionMethod::ZSTD] {
+        let manager = CompressionManager::new(method, CompressionLevel::default(), 0).unwrap();
+        let mut group = c.benchmark_group(format!("decompress_{}", method.as_str()));
+
+        for size in PAYLOAD_SIZES {
+            let compressed: CompressedData = manager.compress_if_needed(&payload(size)).unwrap();
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(size), &compressed, |b, compressed| {
+                b.iter(|| manager.decompress(black_box(compressed)).unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, compress_benchmark, decompress_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+//! End-to-end benchmarks against a local ClickHouse server
+//!
+//! Only built with the `bench-server` feature. The server address is read from
+//! `CLICKHOUSE_HOST` / `CLICKHOUSE_PORT` (default `localhost:9000`).
+//!
+//! ```bash
+//! cargo bench --features bench-server --bench end_to_end
+//! ```
+
+use clickhouse_rs::client::{Client, ClientOptions};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::time::Duration;
+
+const CONCURRENCY: [usize; 3] = [1, 8, 32];
+
+fn bench_client() -> Client {
+    let host = std::env::var("CLICKHOUSE_HOST").unwrap_or_else(|_| "localhost".to_string());
+    let port = std::env::var("CLICKHOUSE_PORT")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(9000);
+
+    let options = ClientOptions::new()
+        .host(host)
+        .port(port)
+        .max_connections(CONCURRENCY[CONCURRENCY.len() - 1])
+        .connect_timeout(Duration::from_secs(5));
+    Client::new(options).expect("Failed to create client")
+}
+
+fn pooled_query_benchmark(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = runtime.block_on(async { bench_client() });
+
+    if let Err(e) = runtime.block_on(client.ping()) {
+        eprintln!("Skipping end-to-end benchmarks, server not reachable: {}", e);
+        return;
+    }
+
+    let mut group = c.benchmark_group("pooled_query");
+    for concurrency in CONCURRENCY {
+        group.throughput(Throughput::Elements(concurrency as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(concurrency), &concurrency, |b, &concurrency| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let queries = (0..concurrency).map(|_| client.query("SELECT 1"));
+                    for result in futures::future::join_all(queries).await {
+                        result.expect("Query failed");
+                    }
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pooled_query_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+//! Value conversion benchmarks
+
+use clickhouse_rs::types::{Column, ColumnData, Row, Value};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn value_from_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("value_from");
+    group.bench_function("u64", |b| b.iter(|| Value::from(black_box(42u64))));
+    group.bench_function("f64", |b| b.iter(|| Value::from(black_box(42.5f64))));
+    group.bench_function("str", |b| b.iter(|| Value::from(black_box("clickhouse"))));
+    group.bench_function("array", |b| {
+        b.iter(|| Value::from(black_box(vec![Value::from(1u8), Value::from(2u8), Value::from(3u8)])))
+    });
+    group.finish();
+}
+
+fn value_display_benchmark(c: &mut Criterion) {
+    let values = [
+        Value::from(42u64),
+        Value::from(-7i32),
+        Value::from(3.25f64),
+        Value::from("clickhouse"),
+        Value::from(uuid::Uuid::nil()),
+    ];
+
+    c.bench_function("value_display", |b| {
+        b.iter(|| values.iter().map(|v| v.to_string().len()).sum::<usize>())
+    });
+}
+
+fn row_extract_benchmark(c: &mut Criterion) {
+    let row = Row::new(vec![Some(Value::from(42u64)), Some(Value::from("name"))]);
+
+    c.bench_function("row_extract", |b| {
+        b.iter(|| match black_box(&row).get(0) {
+            Some(Some(Value::UInt64(v))) => *v,
+            _ => 0,
+        })
+    });
+}
+
+fn column_push_get_benchmark(c: &mut Criterion) {
+    c.bench_function("column_push_1000", |b| {
+        b.iter(|| {
+            let mut column = Column::new("id", "UInt64", ColumnData::UInt64(Vec::with_capacity(1000)));
+            for i in 0..1000u64 {
+                column.push(Value::from(i)).unwrap();
+            }
+            column
+        })
+    });
+
+    let column = Column::new("id", "UInt64", ColumnData::UInt64((0..1000).collect()));
+    c.bench_function("column_get_value_1000", |b| {
+        b.iter(|| (0..column.len()).filter_map(|i| column.get_value(i)).count())
+    });
+}
+
+criterion_group!(
+    benches,
+    value_from_benchmark,
+    value_display_benchmark,
+    row_extract_benchmark,
+    column_push_get_benchmark
+);
+criterion_main!(benches);
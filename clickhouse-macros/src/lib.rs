@@ -0,0 +1,169 @@
+//! Compile-time checked queries for `clickhouse-rs`
+//!
+//! [`query!`] checks a `SELECT` against a schema snapshot while the crate
+//! compiles and generates a struct with one typed field per output column,
+//! much like the offline mode of sqlx. The snapshot is a JSON file written by
+//! `clickhouse-rs schema snapshot` (see `clickhouse_rs::schema`); it is read
+//! from `clickhouse-schema.json` in the crate root, the path in the
+//! `CLICKHOUSE_SCHEMA` environment variable, or the `schema` argument.
+//!
+//! ```rust,ignore
+//! clickhouse_macros::query! {
+//!     /// Recent events of a user
+//!     pub struct UserEvent = "SELECT id, `event time` AS at, score FROM events WHERE user = {user:String}";
+//! }
+//!
+//! let events = UserEvent::fetch_all(&client).await?;
+//! ```
+
+use clickhouse_rs::schema::{rust_type, SchemaSnapshot, SCHEMA_SNAPSHOT_FILE};
+use clickhouse_rs::types::NameCase;
+use proc_macro::TokenStream;
+use quote::quote;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Ident, LitStr, Token, Visibility};
+
+/// Environment variable overriding the snapshot path
+const SCHEMA_ENV: &str = "CLICKHOUSE_SCHEMA";
+
+/// `[attrs] [vis] struct Name = "SQL" [, schema = "path"] [;]`
+struct QueryInput {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    sql: LitStr,
+    schema: Option<LitStr>,
+}
+
+impl Parse for QueryInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let sql = input.parse()?;
+
+        let mut schema = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.peek(Token![;]) && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key != "schema" {
+                return Err(syn::Error::new(key.span(), "expected `schema = \"path\"`"));
+            }
+            input.parse::<Token![=]>()?;
+            schema = Some(input.parse()?);
+        }
+        input.parse::<Option<Token![;]>>()?;
+        Ok(Self {
+            attrs,
+            vis,
+            name,
+            sql,
+            schema,
+        })
+    }
+}
+
+/// Declare a row struct for a `SELECT` checked against the schema snapshot
+///
+/// Each output column becomes a `pub` field named after the column in
+/// `snake_case`, with the Rust type its ClickHouse type converts into.
+/// The struct derives `Debug`, `Clone` and `PartialEq` and gets:
+///
+/// - `SQL`, the query text
+/// - `from_row(&RowReader)` and `from_result(&QueryResult)`
+/// - `fetch_all(&Client)`, which runs the query and converts every row
+///
+/// Unknown tables or columns, and expressions whose type cannot be known
+/// without a server, are compile errors. Give expressions a type with
+/// `CAST(expr AS Type)` and a name with `AS`.
+#[proc_macro]
+pub fn query(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as QueryInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn snapshot_path(schema: Option<&LitStr>) -> PathBuf {
+    let root = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+    match schema {
+        Some(path) => root.join(path.value()),
+        None => root.join(std::env::var(SCHEMA_ENV).unwrap_or_else(|_| SCHEMA_SNAPSHOT_FILE.to_string())),
+    }
+}
+
+fn expand(input: QueryInput) -> syn::Result<proc_macro2::TokenStream> {
+    let QueryInput {
+        attrs,
+        vis,
+        name,
+        sql,
+        schema,
+    } = input;
+    let error = |message: String| syn::Error::new(sql.span(), message);
+
+    let path = snapshot_path(schema.as_ref());
+    let snapshot = SchemaSnapshot::load(&path).map_err(|e| error(e.to_string()))?;
+    let columns = snapshot.check_select(&sql.value()).map_err(|e| error(e.to_string()))?;
+
+    let mut seen = HashSet::new();
+    let mut fields = Vec::with_capacity(columns.len());
+    let mut reads = Vec::with_capacity(columns.len());
+    for column in &columns {
+        let field_name = NameCase::Snake.convert(&column.name);
+        let field: Ident = syn::parse_str(&field_name)
+            .or_else(|_| syn::parse_str(&format!("r#{}", field_name)))
+            .map_err(|_| error(format!("Column '{}' is not a valid field name; give it an alias", column.name)))?;
+        if !seen.insert(field_name) {
+            return Err(error(format!("Column '{}' is selected more than once", column.name)));
+        }
+        let rust_type = rust_type(&column.type_name).map_err(|e| error(e.to_string()))?;
+        let ty: syn::Type = syn::parse_str(&rust_type)?;
+        let column_name = &column.name;
+        fields.push(quote!(pub #field: #ty));
+        reads.push(quote!(#field: row.get(#column_name)?));
+    }
+    let path = path.display().to_string();
+
+    Ok(quote! {
+        #(#attrs)*
+        #[derive(Debug, Clone, PartialEq)]
+        #vis struct #name {
+            #(#fields,)*
+        }
+
+        impl #name {
+            /// Query text
+            pub const SQL: &'static str = #sql;
+
+            /// Read one row
+            pub fn from_row(
+                row: &::clickhouse_rs::client::RowReader<'_>,
+            ) -> ::clickhouse_rs::error::Result<Self> {
+                Ok(Self { #(#reads,)* })
+            }
+
+            /// Read every row of a result
+            pub fn from_result(
+                result: &::clickhouse_rs::client::QueryResult,
+            ) -> ::clickhouse_rs::error::Result<Vec<Self>> {
+                let mut rows = Vec::with_capacity(result.row_count());
+                for block in &result.blocks {
+                    for index in 0..block.row_count {
+                        rows.push(Self::from_row(&::clickhouse_rs::client::RowReader::new(block, index))?);
+                    }
+                }
+                Ok(rows)
+            }
+
+            /// Run the query and read every row
+            pub async fn fetch_all(client: &::clickhouse_rs::Client) -> ::clickhouse_rs::error::Result<Vec<Self>> {
+                Self::from_result(&client.query(Self::SQL).await?)
+            }
+        }
+
+        // Rebuild when the snapshot changes
+        const _: &[u8] = include_bytes!(#path);
+    })
+}
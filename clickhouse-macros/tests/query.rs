@@ -0,0 +1,47 @@
+use clickhouse_macros::query;
+use clickhouse_rs::client::{QueryMetadata, QueryResult, QueryStats};
+use clickhouse_rs::types::{Block, Column, ColumnData, Value};
+use std::time::Duration;
+
+query! {
+    /// Scores of recent events
+    struct EventScore = "SELECT id, user, score, `event time`, tags, count() AS n FROM events GROUP BY ALL",
+    schema = "tests/schema.json";
+}
+
+#[test]
+fn test_query_struct_from_result() {
+    let at = clickhouse_rs::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(3, 4, 5).unwrap();
+    let block = Block::with_columns(vec![
+        Column::new("id", "UInt64", ColumnData::UInt64(vec![7, 8])),
+        Column::new("user", "String", ColumnData::String(vec!["ann".into(), "bob".into()])),
+        Column::new(
+            "score",
+            "Nullable(Float64)",
+            ColumnData::Nullable(vec![Some(Value::Float64(0.5)), None]),
+        ),
+        Column::new("event time", "DateTime('UTC')", ColumnData::DateTime(vec![at, at])),
+        Column::new(
+            "tags",
+            "Array(String)",
+            ColumnData::Array(vec![vec![Value::String("a".into())], vec![]]),
+        ),
+        Column::new("n", "UInt64", ColumnData::UInt64(vec![1, 2])),
+    ]);
+    let result = QueryResult::new(QueryMetadata::new(Vec::new(), Vec::new()), vec![block], QueryStats::new(0, 0, Duration::ZERO));
+
+    let rows = EventScore::from_result(&result).unwrap();
+    assert_eq!(
+        rows[0],
+        EventScore {
+            id: 7,
+            user: "ann".to_string(),
+            score: Some(0.5),
+            event_time: at,
+            tags: vec!["a".to_string()],
+            n: 1,
+        }
+    );
+    assert_eq!(rows[1].score, None);
+    assert!(EventScore::SQL.starts_with("SELECT id, user"));
+}
@@ -0,0 +1,235 @@
+//! Load tests against a ClickHouse server or cluster
+//!
+//! ```text
+//! ch-bench [--dsn DSN] [--concurrency N] [--duration SECS] [--warmup SECS] [--query [WEIGHT:]SQL]...
+//! ```
+//!
+//! Each of the `--concurrency` workers runs queries picked at random from the
+//! mix, in proportion to their weights, for `--duration` seconds. Queries
+//! finished during the `--warmup` period are not counted. The report lists
+//! QPS, latency percentiles and rows per second, overall and per query. The
+//! DSN defaults to the `CLICKHOUSE_URL` environment variable, then
+//! `clickhouse://localhost`; the query mix defaults to `SELECT 1`.
+
+use clickhouse_rs::{Client, ClientOptions};
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const USAGE: &str =
+    "usage: ch-bench [--dsn DSN] [--concurrency N] [--duration SECS] [--warmup SECS] [--query [WEIGHT:]SQL]...";
+
+const DEFAULT_DSN: &str = "clickhouse://localhost";
+
+type CliResult<T = ()> = Result<T, Box<dyn std::error::Error>>;
+
+/// Query of the mix with its relative weight
+struct MixEntry {
+    sql: String,
+    weight: u32,
+}
+
+impl MixEntry {
+    /// Parse `WEIGHT:SQL`, or plain SQL with weight 1
+    fn parse(arg: &str) -> CliResult<Self> {
+        let (weight, sql) = match arg.split_once(':') {
+            Some((weight, sql)) if weight.trim().parse::<u32>().is_ok() => (weight.trim().parse()?, sql.trim()),
+            _ => (1, arg.trim()),
+        };
+        if weight == 0 || sql.is_empty() {
+            return Err(format!("invalid query {:?}: weight must be positive and SQL non-empty", arg).into());
+        }
+        Ok(Self {
+            sql: sql.to_string(),
+            weight,
+        })
+    }
+}
+
+struct Config {
+    dsn: String,
+    concurrency: usize,
+    duration: Duration,
+    warmup: Duration,
+    mix: Vec<MixEntry>,
+}
+
+impl Config {
+    fn parse(args: &[String]) -> CliResult<Self> {
+        let mut config = Config {
+            dsn: std::env::var("CLICKHOUSE_URL").unwrap_or_else(|_| DEFAULT_DSN.to_string()),
+            concurrency: 8,
+            duration: Duration::from_secs(30),
+            warmup: Duration::ZERO,
+            mix: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(USAGE);
+            match arg.as_str() {
+                "--dsn" => config.dsn = value()?.clone(),
+                "--concurrency" => config.concurrency = value()?.parse()?,
+                "--duration" => config.duration = Duration::from_secs_f64(value()?.parse()?),
+                "--warmup" => config.warmup = Duration::from_secs_f64(value()?.parse()?),
+                "--query" => config.mix.push(MixEntry::parse(value()?)?),
+                "--help" | "-h" => return Err(USAGE.into()),
+                other => return Err(format!("unknown argument {}\n{}", other, USAGE).into()),
+            }
+        }
+        if config.concurrency == 0 || config.duration.is_zero() {
+            return Err("concurrency and duration must be positive".into());
+        }
+        if config.mix.is_empty() {
+            config.mix.push(MixEntry::parse("SELECT 1")?);
+        }
+        Ok(config)
+    }
+
+    /// Pick a query index from the mix by weight
+    fn pick(&self, rng: &mut impl Rng) -> usize {
+        let total: u32 = self.mix.iter().map(|entry| entry.weight).sum();
+        let mut ticket = rng.gen_range(0..total);
+        for (index, entry) in self.mix.iter().enumerate() {
+            if ticket < entry.weight {
+                return index;
+            }
+            ticket -= entry.weight;
+        }
+        self.mix.len() - 1
+    }
+}
+
+/// Latencies and rows of the queries of one mix entry
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    rows: u64,
+    errors: u64,
+}
+
+impl Samples {
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.rows += other.rows;
+        self.errors += other.errors;
+    }
+
+    fn percentile(sorted: &[Duration], p: f64) -> Duration {
+        if sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    fn report(&mut self, label: &str, elapsed: Duration) {
+        self.latencies.sort_unstable();
+        let secs = elapsed.as_secs_f64();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        println!(
+            "{:<40} {:>9} {:>7} {:>10.1} {:>9.2} {:>9.2} {:>9.2} {:>12.0}",
+            label,
+            self.latencies.len(),
+            self.errors,
+            self.latencies.len() as f64 / secs,
+            ms(Self::percentile(&self.latencies, 50.0)),
+            ms(Self::percentile(&self.latencies, 99.0)),
+            ms(self.latencies.last().copied().unwrap_or_default()),
+            self.rows as f64 / secs,
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: &[String]) -> CliResult {
+    let config = Arc::new(Config::parse(args)?);
+    let options = ClientOptions::from_dsn(&config.dsn)?.max_connections(config.concurrency);
+    let client = Arc::new(Client::new(options)?);
+    client.ping().await?;
+
+    println!(
+        "Running {} queries on {} workers for {:?} (warmup {:?})",
+        config.mix.len(),
+        config.concurrency,
+        config.duration,
+        config.warmup
+    );
+    let start = Instant::now();
+    let measure_from = start + config.warmup;
+    let deadline = measure_from + config.duration;
+    let workers: Vec<_> = (0..config.concurrency)
+        .map(|_| {
+            let client = client.clone();
+            let config = config.clone();
+            tokio::spawn(async move { worker(&client, &config, measure_from, deadline).await })
+        })
+        .collect();
+
+    let mut samples: Vec<Samples> = config.mix.iter().map(|_| Samples::default()).collect();
+    for worker in workers {
+        for (total, part) in samples.iter_mut().zip(worker.await?) {
+            total.merge(part);
+        }
+    }
+    let elapsed = deadline.min(Instant::now()).saturating_duration_since(measure_from);
+
+    println!(
+        "{:<40} {:>9} {:>7} {:>10} {:>9} {:>9} {:>9} {:>12}",
+        "query", "count", "errors", "qps", "p50 ms", "p99 ms", "max ms", "rows/s"
+    );
+    let mut overall = Samples::default();
+    for (entry, mut entry_samples) in config.mix.iter().zip(samples) {
+        entry_samples.report(&truncate(&entry.sql, 40), elapsed);
+        overall.merge(entry_samples);
+    }
+    if config.mix.len() > 1 {
+        overall.report("total", elapsed);
+    }
+    Ok(())
+}
+
+async fn worker(client: &Client, config: &Config, measure_from: Instant, deadline: Instant) -> Vec<Samples> {
+    let mut samples: Vec<Samples> = config.mix.iter().map(|_| Samples::default()).collect();
+    let mut rng = rand::rngs::StdRng::from_entropy();
+    while Instant::now() < deadline {
+        let index = config.pick(&mut rng);
+        let started = Instant::now();
+        let result = client.query(&config.mix[index].sql).await;
+        if started < measure_from {
+            continue;
+        }
+        let entry = &mut samples[index];
+        match result {
+            Ok(result) => {
+                entry.latencies.push(started.elapsed());
+                entry.rows += result.row_count() as u64;
+            }
+            Err(e) => {
+                entry.errors += 1;
+                if entry.errors == 1 {
+                    eprintln!("{}: {}", truncate(&config.mix[index].sql, 40), e);
+                }
+            }
+        }
+    }
+    samples
+}
+
+/// Shorten SQL to fit a report column
+fn truncate(sql: &str, width: usize) -> String {
+    let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    if sql.chars().count() <= width {
+        return sql;
+    }
+    let mut short: String = sql.chars().take(width - 3).collect();
+    short.push_str("...");
+    short
+}
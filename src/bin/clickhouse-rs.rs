@@ -0,0 +1,58 @@
+//! Command-line tools for clickhouse-rs
+//!
+//! ```text
+//! clickhouse-rs schema snapshot [--dsn DSN] [--out FILE] TABLE...
+//! ```
+//!
+//! `schema snapshot` writes the columns of the tables to the snapshot file
+//! that `clickhouse-macros` checks queries against. The DSN defaults to the
+//! `CLICKHOUSE_URL` environment variable, then `clickhouse://localhost`.
+
+use clickhouse_rs::schema::SCHEMA_SNAPSHOT_FILE;
+use clickhouse_rs::{Client, ClientOptions};
+
+const USAGE: &str = "usage: clickhouse-rs schema snapshot [--dsn DSN] [--out FILE] TABLE...";
+
+const DEFAULT_DSN: &str = "clickhouse://localhost";
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: &[String]) -> CliResult {
+    match args {
+        [group, command, rest @ ..] if group == "schema" && command == "snapshot" => schema_snapshot(rest).await,
+        _ => Err(USAGE.into()),
+    }
+}
+
+async fn schema_snapshot(args: &[String]) -> CliResult {
+    let mut dsn = std::env::var("CLICKHOUSE_URL").unwrap_or_else(|_| DEFAULT_DSN.to_string());
+    let mut out = SCHEMA_SNAPSHOT_FILE.to_string();
+    let mut tables = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dsn" => dsn = args.next().ok_or(USAGE)?.clone(),
+            "--out" => out = args.next().ok_or(USAGE)?.clone(),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}\n{}", flag, USAGE).into()),
+            table => tables.push(table),
+        }
+    }
+    if tables.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let client = Client::new(ClientOptions::from_dsn(&dsn)?)?;
+    let snapshot = client.schema_snapshot(&tables).await?;
+    snapshot.save(&out)?;
+    println!("Wrote {} tables to {}", snapshot.tables.len(), out);
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! Blocking client
+//!
+//! [`Client`] wraps the async [`crate::Client`] and drives it on a runtime it
+//! owns, so programs without Tokio can use the crate. Calls block the current
+//! thread until they complete, which means they must not be made from async
+//! code: a blocking client used inside a Tokio runtime panics.
+//!
+//! ```rust,no_run
+//! use clickhouse_rs::blocking::Client;
+//! use clickhouse_rs::ClientOptions;
+//!
+//! let client = Client::new(ClientOptions::new().host("localhost"))?;
+//! let result = client.query("SELECT 1")?;
+//! println!("{}", result.format_table(10, 40));
+//! # Ok::<(), clickhouse_rs::error::Error>(())
+//! ```
+
+use crate::client::{InsertResult, QueryResult, QuerySettings, TableColumn};
+use crate::error::{Error, Result};
+use crate::types::{Block, Value};
+use crate::ClientOptions;
+use std::collections::HashMap;
+use std::future::Future;
+use tokio::runtime::Runtime;
+
+/// Blocking ClickHouse client
+///
+/// The runtime runs on one background thread, which also serves the pool's
+/// health checks and other background tasks between calls.
+pub struct Client {
+    inner: crate::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    /// Create a client with the specified options
+    pub fn new(options: ClientOptions) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("clickhouse-blocking")
+            .enable_all()
+            .build()
+            .map_err(|e| Error::Internal(format!("Failed to start the blocking client runtime: {}", e)))?;
+        let inner = {
+            let _guard = runtime.enter();
+            crate::Client::new(options)?
+        };
+        Ok(Self { inner, runtime })
+    }
+
+    /// Create a client and, with `fail_fast_on_startup`, check the server
+    pub fn connect(options: ClientOptions) -> Result<Self> {
+        let client = Self::new(options)?;
+        if client.inner.options().fail_fast_on_startup {
+            client.connect_check()?;
+        }
+        Ok(client)
+    }
+
+    /// Execute a query
+    pub fn query(&self, sql: &str) -> Result<QueryResult> {
+        self.block_on(self.inner.query(sql))
+    }
+
+    /// Execute a query with parameters
+    pub fn query_with_params(&self, sql: &str, params: HashMap<String, Value>) -> Result<QueryResult> {
+        self.block_on(self.inner.query_with_params(sql, params))
+    }
+
+    /// Execute a query with settings
+    pub fn query_with_settings(&self, sql: &str, settings: QuerySettings) -> Result<QueryResult> {
+        self.block_on(self.inner.query_with_settings(sql, settings))
+    }
+
+    /// Execute a statement that returns no rows
+    pub fn execute(&self, sql: &str) -> Result<()> {
+        self.block_on(self.inner.execute(sql))
+    }
+
+    /// Execute a statement with parameters
+    pub fn execute_with_params(&self, sql: &str, params: HashMap<String, Value>) -> Result<()> {
+        self.block_on(self.inner.execute_with_params(sql, params))
+    }
+
+    /// Execute a statement with settings
+    pub fn execute_with_settings(&self, sql: &str, settings: QuerySettings) -> Result<()> {
+        self.block_on(self.inner.execute_with_settings(sql, settings))
+    }
+
+    /// Insert a block into a table
+    pub fn insert(&self, table: &str, block: Block) -> Result<InsertResult> {
+        self.block_on(self.inner.insert(table, block))
+    }
+
+    /// Insert a block into a table with settings
+    pub fn insert_with_settings(&self, table: &str, block: Block, settings: QuerySettings) -> Result<InsertResult> {
+        self.block_on(self.inner.insert_with_settings(table, block, settings))
+    }
+
+    /// Ping the server
+    pub fn ping(&self) -> Result<()> {
+        self.block_on(self.inner.ping())
+    }
+
+    /// Open the initial connections and ping the server
+    pub fn connect_check(&self) -> Result<()> {
+        self.block_on(self.inner.connect_check())
+    }
+
+    /// Get the columns of a table
+    pub fn describe_table(&self, table: &str) -> Result<Vec<TableColumn>> {
+        self.block_on(self.inner.describe_table(table))
+    }
+
+    /// Get the server version
+    pub fn server_version(&self) -> Result<String> {
+        self.block_on(self.inner.server_version())
+    }
+
+    /// Get the client options
+    pub fn options(&self) -> &ClientOptions {
+        self.inner.options()
+    }
+
+    /// Get the async client, for operations without a blocking wrapper
+    pub fn inner(&self) -> &crate::Client {
+        &self.inner
+    }
+
+    /// Run a future on the client's runtime, blocking until it completes
+    ///
+    /// Use this with [`Client::inner`] for the rest of the async API.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client").field("options", self.inner.options()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_client_without_runtime() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let options = ClientOptions::new()
+            .host("127.0.0.1")
+            .port(port)
+            .lazy_connect(true)
+            .disable_retry()
+            .connect_timeout(std::time::Duration::from_millis(200));
+        let client = Client::new(options).unwrap();
+
+        assert!(client.connect_check().is_err());
+        assert_eq!(client.block_on(async { client.inner().options().port }), port);
+        assert!(Client::connect(client.options().clone().lazy_connect(false).fail_fast_on_startup(true)).is_err());
+    }
+}
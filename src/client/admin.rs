@@ -0,0 +1,197 @@
+//! Typed facade over ClickHouse `SYSTEM` statements
+//!
+//! Every command accepts an optional cluster set with [`Admin::on_cluster`],
+//! which adds `ON CLUSTER` so the statement runs on every node. Missing
+//! privileges are reported as [`Error::Authentication`] naming the command.
+
+use crate::client::Client;
+use crate::error::{server_codes, Error, Result};
+
+/// Admin operations bound to a client
+pub struct Admin<'a> {
+    client: &'a Client,
+    cluster: Option<String>,
+}
+
+impl<'a> Admin<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Self { client, cluster: None }
+    }
+
+    /// Run the commands on every node of a cluster
+    pub fn on_cluster(mut self, cluster: impl Into<String>) -> Self {
+        self.cluster = Some(cluster.into());
+        self
+    }
+
+    /// Get the target cluster
+    pub fn cluster(&self) -> Option<&str> {
+        self.cluster.as_deref()
+    }
+
+    /// Flush buffered log entries into the system log tables
+    pub async fn flush_logs(&self) -> Result<()> {
+        self.run("FLUSH LOGS", None).await
+    }
+
+    /// Reload all dictionaries
+    pub async fn reload_dictionaries(&self) -> Result<()> {
+        self.run("RELOAD DICTIONARIES", None).await
+    }
+
+    /// Reload a single dictionary
+    pub async fn reload_dictionary(&self, name: &str) -> Result<()> {
+        self.run("RELOAD DICTIONARY", Some(name)).await
+    }
+
+    /// Reload the server configuration
+    pub async fn reload_config(&self) -> Result<()> {
+        self.run("RELOAD CONFIG", None).await
+    }
+
+    /// Drop the DNS cache
+    pub async fn drop_dns_cache(&self) -> Result<()> {
+        self.run("DROP DNS CACHE", None).await
+    }
+
+    /// Drop the mark cache
+    pub async fn drop_mark_cache(&self) -> Result<()> {
+        self.run("DROP MARK CACHE", None).await
+    }
+
+    /// Drop the uncompressed data cache
+    pub async fn drop_uncompressed_cache(&self) -> Result<()> {
+        self.run("DROP UNCOMPRESSED CACHE", None).await
+    }
+
+    /// Drop the query result cache
+    pub async fn drop_query_cache(&self) -> Result<()> {
+        self.run("DROP QUERY CACHE", None).await
+    }
+
+    /// Wait until a replicated table has caught up with its replication queue
+    pub async fn sync_replica(&self, table: &str) -> Result<()> {
+        self.run("SYNC REPLICA", Some(table)).await
+    }
+
+    /// Reinitialize the ZooKeeper session of a replicated table
+    pub async fn restart_replica(&self, table: &str) -> Result<()> {
+        self.run("RESTART REPLICA", Some(table)).await
+    }
+
+    /// Reinitialize the ZooKeeper sessions of all replicated tables
+    pub async fn restart_replicas(&self) -> Result<()> {
+        self.run("RESTART REPLICAS", None).await
+    }
+
+    /// Stop background merges, for one table or all tables
+    pub async fn stop_merges(&self, table: Option<&str>) -> Result<()> {
+        self.run("STOP MERGES", table).await
+    }
+
+    /// Start background merges, for one table or all tables
+    pub async fn start_merges(&self, table: Option<&str>) -> Result<()> {
+        self.run("START MERGES", table).await
+    }
+
+    /// Stop fetching parts from other replicas, for one table or all tables
+    pub async fn stop_fetches(&self, table: Option<&str>) -> Result<()> {
+        self.run("STOP FETCHES", table).await
+    }
+
+    /// Start fetching parts from other replicas, for one table or all tables
+    pub async fn start_fetches(&self, table: Option<&str>) -> Result<()> {
+        self.run("START FETCHES", table).await
+    }
+
+    /// Build the SQL for a `SYSTEM` command
+    pub fn statement(&self, command: &str, target: Option<&str>) -> Result<String> {
+        let mut sql = format!("SYSTEM {}", command);
+        if let Some(cluster) = &self.cluster {
+            sql.push_str(" ON CLUSTER ");
+            sql.push_str(&quote_name(cluster)?);
+        }
+        if let Some(target) = target {
+            sql.push(' ');
+            sql.push_str(&quote_name(target)?);
+        }
+        Ok(sql)
+    }
+
+    async fn run(&self, command: &str, target: Option<&str>) -> Result<()> {
+        let sql = self.statement(command, target)?;
+        tracing::info!("Running {}", sql);
+        self.client
+            .execute(&sql)
+            .await
+            .map_err(|e| permission_error(command, e))
+    }
+}
+
+/// Quote a possibly database-qualified name, keeping already quoted parts
+fn quote_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::Configuration("Empty name in SYSTEM command".to_string()));
+    }
+    let parts: Vec<String> = name
+        .split('.')
+        .map(|part| {
+            if part.len() >= 2 && part.starts_with('`') && part.ends_with('`') {
+                part.to_string()
+            } else {
+                format!("`{}`", part.replace('`', "\\`"))
+            }
+        })
+        .collect();
+    Ok(parts.join("."))
+}
+
+/// Turn missing-privilege server errors into authentication errors
+fn permission_error(command: &str, error: Error) -> Error {
+    match error.server_exception() {
+        Some(e) if matches!(e.code, server_codes::ACCESS_DENIED | server_codes::READONLY) => {
+            Error::Authentication(format!("Not allowed to run SYSTEM {}: {}", command, e.message))
+        }
+        _ => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+    use crate::protocol::ServerException;
+
+    #[tokio::test]
+    async fn test_admin_statements() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        let admin = client.admin();
+        assert_eq!(admin.statement("FLUSH LOGS", None).unwrap(), "SYSTEM FLUSH LOGS");
+        assert_eq!(
+            admin.statement("SYNC REPLICA", Some("db.events")).unwrap(),
+            "SYSTEM SYNC REPLICA `db`.`events`"
+        );
+
+        let admin = client.admin().on_cluster("prod");
+        assert_eq!(
+            admin.statement("RESTART REPLICA", Some("`db`.events")).unwrap(),
+            "SYSTEM RESTART REPLICA ON CLUSTER `prod` `db`.`events`"
+        );
+        assert_eq!(
+            admin.statement("DROP MARK CACHE", None).unwrap(),
+            "SYSTEM DROP MARK CACHE ON CLUSTER `prod`"
+        );
+        assert!(admin.statement("SYNC REPLICA", Some(" ")).is_err());
+    }
+
+    #[test]
+    fn test_admin_permission_error() {
+        let denied: Error = ServerException::new("Not enough privileges", server_codes::ACCESS_DENIED, "DB::Exception").into();
+        let mapped = permission_error("FLUSH LOGS", denied);
+        assert!(matches!(&mapped, Error::Authentication(msg) if msg.contains("SYSTEM FLUSH LOGS")));
+
+        let other: Error = ServerException::new("Table doesn't exist", server_codes::UNKNOWN_TABLE, "DB::Exception").into();
+        assert!(matches!(permission_error("SYNC REPLICA", other), Error::Server(_)));
+    }
+}
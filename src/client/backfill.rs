@@ -0,0 +1,399 @@
+//! Chunked INSERT ... SELECT backfills
+//!
+//! Backfilling months of data with a single `INSERT ... SELECT` ties up the
+//! server for hours and has to start over when anything fails. [`Backfill`]
+//! splits the time range into chunks and runs the statement once per chunk,
+//! with `{from}` and `{to}` bound to the chunk's bounds:
+//!
+//! ```rust
+//! # async fn example(client: clickhouse_rs::Client) -> clickhouse_rs::error::Result<()> {
+//! use chrono::NaiveDate;
+//! use clickhouse_rs::client::Backfill;
+//! use std::time::Duration;
+//!
+//! let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+//! let to = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+//! let report = Backfill::new(
+//!     client,
+//!     "INSERT INTO daily SELECT * FROM events WHERE ts >= {from} AND ts < {to}",
+//!     from,
+//!     to,
+//!     Duration::from_secs(86_400),
+//! )
+//! .concurrency(4)
+//! .state_table("default.backfill_state")
+//! .run()
+//! .await?;
+//! assert!(report.is_complete());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A failed chunk does not stop the others; it is reported and retried on the
+//! next run. With a state table, finished chunks are recorded in ClickHouse
+//! and skipped when the backfill is run again.
+
+use crate::client::system_tables::{quote, RowReader};
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::types::Value;
+use chrono::NaiveDateTime;
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Chunks run at the same time by default
+pub const DEFAULT_BACKFILL_CONCURRENCY: usize = 1;
+
+/// Attempts per chunk by default
+pub const DEFAULT_BACKFILL_MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry of a chunk by default; it doubles with every retry
+pub const DEFAULT_BACKFILL_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest wait between retries of a chunk
+pub const MAX_BACKFILL_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Format of chunk bounds in statements and the state table
+const CHUNK_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Called after each chunk with the progress so far
+pub type BackfillProgressCallback = Box<dyn Fn(&BackfillProgress) + Send + Sync>;
+
+/// Time range of one chunk, from inclusive to exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BackfillChunk {
+    /// Start of the chunk
+    pub from: NaiveDateTime,
+    /// End of the chunk
+    pub to: NaiveDateTime,
+}
+
+/// Progress of a running backfill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackfillProgress {
+    /// Chunks of the whole range
+    pub total: usize,
+    /// Chunks finished, including those finished by earlier runs
+    pub completed: usize,
+    /// Chunks that failed in this run
+    pub failed: usize,
+}
+
+/// Outcome of a backfill run
+#[derive(Debug, Clone, Default)]
+pub struct BackfillReport {
+    /// Chunks of the whole range
+    pub total: usize,
+    /// Chunks run successfully in this run
+    pub completed: usize,
+    /// Chunks skipped because an earlier run finished them
+    pub skipped: usize,
+    /// Chunks that failed, with the last error of each
+    pub failed: Vec<(BackfillChunk, String)>,
+}
+
+impl BackfillReport {
+    /// Check if every chunk of the range is done
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.completed + self.skipped == self.total
+    }
+}
+
+/// INSERT ... SELECT run chunk by chunk over a time range
+pub struct Backfill {
+    client: Client,
+    sql: String,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    step: Duration,
+    concurrency: usize,
+    max_attempts: u32,
+    retry_backoff: Duration,
+    state_table: Option<String>,
+    job: Option<String>,
+    on_progress: Option<BackfillProgressCallback>,
+}
+
+impl Backfill {
+    /// Backfill `[from, to)` in chunks of `step`
+    ///
+    /// `sql` is run once per chunk with `{from}` and `{to}` replaced by the
+    /// chunk's bounds as DateTime literals.
+    pub fn new(
+        client: Client,
+        sql: impl Into<String>,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        step: Duration,
+    ) -> Self {
+        Self {
+            client,
+            sql: sql.into(),
+            from,
+            to,
+            step,
+            concurrency: DEFAULT_BACKFILL_CONCURRENCY,
+            max_attempts: DEFAULT_BACKFILL_MAX_ATTEMPTS,
+            retry_backoff: DEFAULT_BACKFILL_RETRY_BACKOFF,
+            state_table: None,
+            job: None,
+            on_progress: None,
+        }
+    }
+
+    /// Set how many chunks run at the same time
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set how many times a chunk is tried before it is reported as failed
+    ///
+    /// Only retryable errors are tried again.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the wait before the first retry of a chunk
+    ///
+    /// The wait doubles with every retry, up to [`MAX_BACKFILL_RETRY_BACKOFF`].
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Record finished chunks in a table, so a new run resumes where this one stopped
+    ///
+    /// The table is created if it does not exist.
+    pub fn state_table(mut self, table: impl Into<String>) -> Self {
+        self.state_table = Some(table.into());
+        self
+    }
+
+    /// Set the name the chunks are recorded under; defaults to the SQL
+    pub fn job(mut self, job: impl Into<String>) -> Self {
+        self.job = Some(job.into());
+        self
+    }
+
+    /// Set a callback called after each chunk
+    pub fn on_progress(mut self, callback: impl Fn(&BackfillProgress) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Split the range into chunks
+    ///
+    /// The last chunk ends at `to` and may be shorter than the step.
+    pub fn chunks(&self) -> Result<Vec<BackfillChunk>> {
+        let step = chrono::Duration::from_std(self.step)
+            .ok()
+            .filter(|step| step.num_seconds() > 0)
+            .ok_or_else(|| Error::Configuration("Backfill step must be at least one second".to_string()))?;
+        let mut chunks = Vec::new();
+        let mut from = self.from;
+        while from < self.to {
+            let to = (from + step).min(self.to);
+            chunks.push(BackfillChunk { from, to });
+            from = to;
+        }
+        Ok(chunks)
+    }
+
+    /// Build the statement that creates the state table
+    ///
+    /// Chunk bounds are stored in UTC, so they read back the same whatever
+    /// the server's timezone.
+    pub fn create_state_table_sql(table: &str) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (job String, chunk_from DateTime('UTC'), chunk_to DateTime('UTC'), \
+             status LowCardinality(String), error String, updated_at DateTime64(3) DEFAULT now64(3)) \
+             ENGINE = ReplacingMergeTree(updated_at) ORDER BY (job, chunk_from)",
+            table
+        )
+    }
+
+    /// Run the backfill
+    ///
+    /// Fails only when the state table cannot be read; failed chunks are
+    /// listed in the report.
+    pub async fn run(&self) -> Result<BackfillReport> {
+        let chunks = self.chunks()?;
+        let done = self.finished_chunks().await?;
+        let (finished, pending): (Vec<_>, Vec<_>) =
+            chunks.iter().partition(|chunk| done.contains(&chunk.from));
+
+        let completed = AtomicUsize::new(finished.len());
+        let failed_count = AtomicUsize::new(0);
+        let (completed_ref, failed_ref, total) = (&completed, &failed_count, chunks.len());
+        let failed: Vec<(BackfillChunk, String)> = stream::iter(pending)
+            .map(|chunk| async move {
+                let result = self.run_chunk(chunk).await;
+                let counter = if result.is_ok() { completed_ref } else { failed_ref };
+                counter.fetch_add(1, Ordering::Relaxed);
+                if let Some(callback) = &self.on_progress {
+                    callback(&BackfillProgress {
+                        total,
+                        completed: completed_ref.load(Ordering::Relaxed),
+                        failed: failed_ref.load(Ordering::Relaxed),
+                    });
+                }
+                result.err().map(|e| (*chunk, e.to_string()))
+            })
+            .buffer_unordered(self.concurrency)
+            .filter_map(|failure| async { failure })
+            .collect()
+            .await;
+
+        let report = BackfillReport {
+            total: chunks.len(),
+            completed: completed.into_inner() - finished.len(),
+            skipped: finished.len(),
+            failed,
+        };
+        if !report.failed.is_empty() {
+            tracing::warn!("Backfill left {} of {} chunks failed", report.failed.len(), report.total);
+        }
+        Ok(report)
+    }
+
+    fn job_name(&self) -> &str {
+        self.job.as_deref().unwrap_or(&self.sql)
+    }
+
+    /// Get the starts of chunks recorded as done
+    ///
+    /// The starts are read back as text, the way they were written, so that
+    /// state tables created with a server-local `DateTime` still match.
+    async fn finished_chunks(&self) -> Result<HashSet<NaiveDateTime>> {
+        let Some(table) = &self.state_table else {
+            return Ok(HashSet::new());
+        };
+        self.client.execute(&Self::create_state_table_sql(table)).await?;
+        let result = self
+            .client
+            .query(&format!(
+                "SELECT toString(chunk_from) AS chunk_from FROM {} FINAL \
+                 WHERE job = {} AND status = 'done'",
+                table,
+                quote(self.job_name())
+            ))
+            .await?;
+        let mut done = HashSet::new();
+        for block in &result.blocks {
+            for index in 0..block.row_count {
+                let from = RowReader::new(block, index).string("chunk_from")?;
+                let from = NaiveDateTime::parse_from_str(&from, CHUNK_TIME_FORMAT)
+                    .map_err(|e| Error::InvalidData(format!("Invalid backfill chunk start {}: {}", from, e)))?;
+                done.insert(from);
+            }
+        }
+        Ok(done)
+    }
+
+    async fn run_chunk(&self, chunk: &BackfillChunk) -> Result<()> {
+        let params = HashMap::from([
+            ("from".to_string(), Value::DateTime(chunk.from)),
+            ("to".to_string(), Value::DateTime(chunk.to)),
+        ]);
+        let mut attempt = 1;
+        let result = loop {
+            match self.client.execute_with_params(&self.sql, params.clone()).await {
+                Err(e) if e.is_retryable() && attempt < self.max_attempts => {
+                    let delay = self.retry_delay(attempt);
+                    tracing::debug!(
+                        "Backfill chunk {} failed on attempt {}, retrying in {:?}: {}",
+                        chunk.from,
+                        attempt,
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => break result,
+            }
+        };
+        if let Err(e) = &result {
+            tracing::warn!("Backfill chunk {} to {} failed: {}", chunk.from, chunk.to, e);
+        }
+        self.record(chunk, &result).await?;
+        result
+    }
+
+    /// Get the wait before retrying a chunk that failed `attempt` times
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        self.retry_backoff
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(MAX_BACKFILL_RETRY_BACKOFF)
+    }
+
+    async fn record(&self, chunk: &BackfillChunk, result: &Result<()>) -> Result<()> {
+        let Some(table) = &self.state_table else {
+            return Ok(());
+        };
+        let (status, error) = match result {
+            Ok(()) => ("done", String::new()),
+            Err(e) => ("failed", e.to_string()),
+        };
+        self.client.execute(&self.state_insert_sql(table, chunk, status, &error)).await
+    }
+
+    fn state_insert_sql(&self, table: &str, chunk: &BackfillChunk, status: &str, error: &str) -> String {
+        format!(
+            "INSERT INTO {} (job, chunk_from, chunk_to, status, error) VALUES ({}, '{}', '{}', '{}', {})",
+            table,
+            quote(self.job_name()),
+            chunk.from.format(CHUNK_TIME_FORMAT),
+            chunk.to.format(CHUNK_TIME_FORMAT),
+            status,
+            quote(error)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_backfill_chunks() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        let step = Duration::from_secs(12 * 3600);
+        let backfill = Backfill::new(client.clone(), "INSERT INTO t SELECT 1", at(1, 0), at(2, 6), step).job("j'1");
+        let chunks = backfill.chunks().unwrap();
+        assert_eq!(
+            chunks,
+            [
+                BackfillChunk { from: at(1, 0), to: at(1, 12) },
+                BackfillChunk { from: at(1, 12), to: at(2, 0) },
+                BackfillChunk { from: at(2, 0), to: at(2, 6) },
+            ]
+        );
+        assert_eq!(
+            backfill.state_insert_sql("s", &chunks[2], "failed", "boom"),
+            "INSERT INTO s (job, chunk_from, chunk_to, status, error) \
+             VALUES ('j\\'1', '2024-01-02 00:00:00', '2024-01-02 06:00:00', 'failed', 'boom')"
+        );
+        assert!(Backfill::new(client.clone(), "", at(1, 0), at(2, 0), Duration::from_millis(10)).chunks().is_err());
+        assert!(Backfill::create_state_table_sql("s").contains("chunk_from DateTime('UTC')"));
+
+        assert_eq!(backfill.retry_delay(1), DEFAULT_BACKFILL_RETRY_BACKOFF);
+        assert_eq!(backfill.retry_delay(3), DEFAULT_BACKFILL_RETRY_BACKOFF * 4);
+        assert_eq!(backfill.retry_delay(40), MAX_BACKFILL_RETRY_BACKOFF);
+
+        let mut report = BackfillReport { total: 3, completed: 2, skipped: 1, failed: Vec::new() };
+        assert!(report.is_complete());
+        report.failed.push((chunks[0], "timeout".to_string()));
+        assert!(!report.is_complete());
+    }
+}
@@ -0,0 +1,134 @@
+//! Result block sizes picked for a memory target
+//!
+//! ```rust
+//! # async fn example(client: clickhouse_rs::Client) -> clickhouse_rs::error::Result<()> {
+//! use clickhouse_rs::client::QuerySettings;
+//!
+//! let settings = QuerySettings::new().auto_block_size(4 * 1024 * 1024);
+//! let mut stream = client.query_stream_with_settings("SELECT * FROM events", settings);
+//! while let Some(block) = stream.next_block().await? {
+//!     println!("{} rows", block.row_count);
+//! }
+//! println!("largest block received: {} rows", stream.block_sizes().max_rows);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With a memory target, `max_block_size` is worked out from the width of the
+//! result rows: from the column types once the statement has run on the
+//! connection, and from the first received blocks in a stream. Streams also
+//! cut blocks larger than the limit into chunks, so a server ignoring the
+//! setting does not hand out oversized blocks.
+
+use crate::protocol::encoded_width;
+use crate::types::{Block, TypeDescriptor};
+
+/// Fewest rows per block the automatic size goes down to
+pub const MIN_AUTO_BLOCK_SIZE: u64 = 1_024;
+/// Most rows per block the automatic size goes up to
+pub const MAX_AUTO_BLOCK_SIZE: u64 = 1_048_576;
+/// Bytes assumed per value of a variable-width type
+pub const VARIABLE_WIDTH_ESTIMATE: usize = 32;
+
+/// Estimate the encoded bytes of a row with columns of the given types
+///
+/// Types that cannot be parsed count as variable-width.
+pub fn estimate_row_width<'a>(type_names: impl IntoIterator<Item = &'a str>) -> usize {
+    type_names
+        .into_iter()
+        .map(|type_name| TypeDescriptor::parse(type_name).map_or(VARIABLE_WIDTH_ESTIMATE, |d| value_width(&d)))
+        .sum::<usize>()
+        .max(1)
+}
+
+fn value_width(descriptor: &TypeDescriptor) -> usize {
+    if let Some(width) = encoded_width(descriptor) {
+        return width;
+    }
+    match descriptor {
+        TypeDescriptor::Nullable(inner) => 1 + value_width(inner),
+        TypeDescriptor::Tuple(elements) => elements.iter().map(|(_, element)| value_width(element)).sum(),
+        // Dictionary indices; the dictionary is shared by the block
+        TypeDescriptor::LowCardinality(_) => 4,
+        _ => VARIABLE_WIDTH_ESTIMATE,
+    }
+}
+
+/// Pick the rows per block that keep a block of `row_width` bytes per row near `target_bytes`
+pub fn auto_block_size(row_width: usize, target_bytes: usize) -> u64 {
+    ((target_bytes / row_width.max(1)) as u64).clamp(MIN_AUTO_BLOCK_SIZE, MAX_AUTO_BLOCK_SIZE)
+}
+
+/// Sizes of the blocks received from the server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockSizes {
+    /// Blocks received
+    pub blocks: u64,
+    /// Rows received
+    pub rows: u64,
+    /// Rows of the smallest block
+    pub min_rows: u64,
+    /// Rows of the largest block
+    pub max_rows: u64,
+}
+
+impl BlockSizes {
+    /// Record a received block of `rows` rows
+    pub fn record(&mut self, rows: u64) {
+        self.min_rows = if self.blocks == 0 { rows } else { self.min_rows.min(rows) };
+        self.max_rows = self.max_rows.max(rows);
+        self.blocks += 1;
+        self.rows += rows;
+    }
+
+    /// Get the average rows per block
+    pub fn mean_rows(&self) -> f64 {
+        if self.blocks == 0 {
+            0.0
+        } else {
+            self.rows as f64 / self.blocks as f64
+        }
+    }
+}
+
+/// Cut a block into chunks of at most `max_rows` rows
+pub fn chunk_block(block: Block, max_rows: usize) -> Vec<Block> {
+    if max_rows == 0 || block.row_count <= max_rows {
+        return vec![block];
+    }
+    (0..block.row_count)
+        .step_by(max_rows)
+        .map(|start| {
+            let indices: Vec<usize> = (start..(start + max_rows).min(block.row_count)).collect();
+            block.take(&indices)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Column, ColumnData};
+
+    #[test]
+    fn test_auto_block_size() {
+        assert_eq!(estimate_row_width(["UInt64", "Nullable(Int32)", "String"]), 8 + 5 + VARIABLE_WIDTH_ESTIMATE);
+        assert_eq!(estimate_row_width(["Tuple(UInt8, DateTime)", "LowCardinality(String)"]), 9);
+        assert_eq!(estimate_row_width([]), 1);
+        assert_eq!(auto_block_size(64, 4 * 1024 * 1024), 65_536);
+        assert_eq!(auto_block_size(1 << 20, 1024), MIN_AUTO_BLOCK_SIZE);
+        assert_eq!(auto_block_size(0, usize::MAX), MAX_AUTO_BLOCK_SIZE);
+    }
+
+    #[test]
+    fn test_chunk_block_and_sizes() {
+        let block = Block::with_columns(vec![Column::new("id", "UInt32", ColumnData::UInt32((0..10).collect()))]);
+        let chunks = chunk_block(block.clone(), 4);
+        let mut sizes = BlockSizes::default();
+        chunks.iter().for_each(|chunk| sizes.record(chunk.row_count as u64));
+        assert_eq!(sizes, BlockSizes { blocks: 3, rows: 10, min_rows: 2, max_rows: 4 });
+        assert_eq!(chunks[2].get_column("id").unwrap().get_value(1), Some(crate::types::Value::UInt32(9)));
+        assert_eq!(chunk_block(block, 0).len(), 1);
+        assert_eq!(BlockSizes::default().mean_rows(), 0.0);
+    }
+}
@@ -0,0 +1,121 @@
+//! Server feature detection
+//!
+//! [`ServerCapabilities`] turns the server release and the settings it knows
+//! into booleans, so callers can branch on features instead of comparing
+//! version strings. [`Client::capabilities`](crate::Client::capabilities)
+//! probes the server once and caches the result.
+
+use crate::protocol::ProtocolVersion;
+use std::collections::BTreeSet;
+
+/// Settings whose presence refines the version-based feature checks
+pub(crate) const PROBED_SETTINGS: &[&str] = &[
+    "async_insert",
+    "allow_experimental_json_type",
+    "allow_experimental_object_type",
+    "allow_experimental_variant_type",
+    "allow_experimental_dynamic_type",
+    "use_query_cache",
+    "allow_experimental_parallel_reading_from_replicas",
+    "enable_lightweight_delete",
+];
+
+/// Features supported by a server
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerCapabilities {
+    /// Server release
+    pub version: ProtocolVersion,
+    /// `DELETE FROM` without a mutation
+    pub supports_lightweight_delete: bool,
+    /// Server-side batching of small inserts
+    pub supports_async_insert: bool,
+    /// The `JSON` column type
+    pub supports_json_type: bool,
+    /// The `Variant` column type
+    pub supports_variant_type: bool,
+    /// The `Dynamic` column type
+    pub supports_dynamic_type: bool,
+    /// The query result cache
+    pub supports_query_cache: bool,
+    /// Reading one query from several replicas
+    pub supports_parallel_replicas: bool,
+    settings: Option<BTreeSet<String>>,
+}
+
+impl ServerCapabilities {
+    /// Derive capabilities from the server release alone
+    pub fn from_version(version: ProtocolVersion) -> Self {
+        let at_least = |major: u32, minor: u32| version >= ProtocolVersion::new(major, minor, 0, 0);
+        Self {
+            supports_lightweight_delete: at_least(23, 3),
+            supports_async_insert: at_least(21, 11),
+            supports_json_type: at_least(22, 3),
+            supports_variant_type: at_least(24, 1),
+            supports_dynamic_type: at_least(24, 5),
+            supports_query_cache: at_least(23, 1),
+            supports_parallel_replicas: at_least(23, 3),
+            version,
+            settings: None,
+        }
+    }
+
+    /// Derive capabilities from the release and the settings the server knows
+    ///
+    /// A feature also needs its setting to exist, which catches builds where
+    /// it was compiled out or renamed.
+    pub fn from_version_and_settings<I, S>(version: ProtocolVersion, settings: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let settings: BTreeSet<String> = settings.into_iter().map(Into::into).collect();
+        let has = |name: &str| settings.contains(name);
+        let mut capabilities = Self::from_version(version);
+        capabilities.supports_lightweight_delete &= has("enable_lightweight_delete");
+        capabilities.supports_async_insert &= has("async_insert");
+        capabilities.supports_json_type &=
+            has("allow_experimental_json_type") || has("allow_experimental_object_type");
+        capabilities.supports_variant_type &= has("allow_experimental_variant_type");
+        capabilities.supports_dynamic_type &= has("allow_experimental_dynamic_type");
+        capabilities.supports_query_cache &= has("use_query_cache");
+        capabilities.supports_parallel_replicas &= has("allow_experimental_parallel_reading_from_replicas");
+        capabilities.settings = Some(settings);
+        capabilities
+    }
+
+    /// Check whether the server release is at least `major.minor`
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        self.version >= ProtocolVersion::new(major, minor, 0, 0)
+    }
+
+    /// Check whether the server knows a probed setting
+    ///
+    /// Returns `None` if settings could not be probed or `name` is not one of
+    /// the probed settings.
+    pub fn has_setting(&self, name: &str) -> Option<bool> {
+        let settings = self.settings.as_ref()?;
+        PROBED_SETTINGS.contains(&name).then(|| settings.contains(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_from_version_and_settings() {
+        let old = ServerCapabilities::from_version(ProtocolVersion::new(22, 8, 1, 0));
+        assert!(old.supports_async_insert && old.supports_json_type);
+        assert!(!old.supports_lightweight_delete && !old.supports_variant_type);
+        assert!(old.at_least(22, 8) && !old.at_least(23, 1));
+        assert_eq!(old.has_setting("async_insert"), None);
+
+        let settings = ["async_insert", "allow_experimental_variant_type", "use_query_cache", "enable_lightweight_delete"];
+        let new = ServerCapabilities::from_version_and_settings(ProtocolVersion::new(24, 8, 0, 0), settings);
+        assert!(new.supports_lightweight_delete && new.supports_variant_type && new.supports_query_cache);
+        assert!(!new.supports_json_type && !new.supports_dynamic_type);
+        assert_eq!(new.has_setting("async_insert"), Some(true));
+        assert_eq!(new.has_setting("use_query_cache"), Some(true));
+        assert_eq!(new.has_setting("allow_experimental_dynamic_type"), Some(false));
+    }
+}
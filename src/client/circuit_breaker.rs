@@ -1,5 +1,6 @@
 //! Circuit breaker for ClickHouse client operations
 
+use crate::client::{ClientWarning, WarningChannel};
 use crate::error::{Error, Result};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -30,6 +31,8 @@ pub struct CircuitBreakerConfig {
     pub operation_timeout: Option<Duration>,
     /// Whether to enable the circuit breaker
     pub enabled: bool,
+    /// Channel told when the circuit opens
+    pub warnings: Option<WarningChannel>,
 }
 
 impl Default for CircuitBreakerConfig {
@@ -40,6 +43,7 @@ impl Default for CircuitBreakerConfig {
             success_threshold: 3,
             operation_timeout: None,
             enabled: true,
+            warnings: None,
         }
     }
 }
@@ -185,9 +189,8 @@ impl CircuitBreaker {
         }
 
         // If we're in Open state but timeout has passed, transition to HalfOpen
-        let current_state = self.state.read().await;
-        if *current_state == CircuitBreakerState::Open {
-            drop(current_state); // Release lock before calling transition
+        let is_open = *self.state.read().await == CircuitBreakerState::Open;
+        if is_open {
             self.transition_to_half_open().await;
         }
 
@@ -255,6 +258,7 @@ impl CircuitBreaker {
                 stats.last_success_time = Some(Instant::now());
                 stats.current_success_streak += 1;
                 stats.current_failure_streak = 0;
+                drop(stats); // Opening or closing the circuit takes the stats lock again
                 
                 self.record_success().await;
             }
@@ -263,6 +267,7 @@ impl CircuitBreaker {
                 stats.last_failure_time = Some(Instant::now());
                 stats.current_failure_streak += 1;
                 stats.current_success_streak = 0;
+                drop(stats); // Opening or closing the circuit takes the stats lock again
                 
                 self.record_failure().await;
             }
@@ -312,6 +317,12 @@ impl CircuitBreaker {
             *failure_count = 0;
 
             warn!("Circuit breaker opened after {} failures", self.config.failure_threshold);
+            if let Some(warnings) = &self.config.warnings {
+                warnings.emit(ClientWarning::CircuitOpened {
+                    failures: self.config.failure_threshold,
+                    open_for: self.config.open_timeout,
+                });
+            }
         }
     }
 
@@ -506,6 +517,12 @@ impl CircuitBreakerBuilder {
         self
     }
 
+    /// Report the circuit opening on a warning channel
+    pub fn warnings(mut self, warnings: WarningChannel) -> Self {
+        self.config.warnings = Some(warnings);
+        self
+    }
+
     /// Build the circuit breaker
     pub fn build(self) -> CircuitBreaker {
         CircuitBreaker::new(self.config)
@@ -614,7 +631,6 @@ mod tests {
     }
 
     #[tokio::test]
-    #[ignore = "This test can hang due to circuit breaker state transitions and timing issues"]
     async fn test_circuit_breaker_open_after_failures() {
         tokio::time::timeout(Duration::from_secs(10), async {
             let cb = CircuitBreakerBuilder::new()
@@ -670,7 +686,6 @@ mod tests {
     }
 
     #[tokio::test]
-    #[ignore = "This test can hang due to circuit breaker state transitions and timing issues"]
     async fn test_circuit_breaker_reset() {
         tokio::time::timeout(Duration::from_secs(10), async {
             let cb = CircuitBreakerBuilder::new()
@@ -1,17 +1,114 @@
 //! Connection management for ClickHouse
 
-use crate::error::{Error, Result};
-use crate::types::{Block, Value};
-use crate::client::{QueryResult, QuerySettings, QueryMetadata, QueryStats};
+use crate::compression::{self, CompressionLevel};
+use crate::error::{Error, ErrorContext, Result, ResultExt};
+use crate::types::{parse_timezone, Block, Value};
+use crate::client::{
+    deprecated_setting, describe_table_sql, estimate_row_width, is_server_pushed, ClientWarning, ServerEvent, CompressionMethod, SlowQuery, HttpSession, InsertResult, read_frame_with_keepalive, KeepAlive, MemoryWatchdog, PreparedStatement, Query, QueryResult, QuerySettings, QueryMetadata,
+    QueryStats, StatementCache, TableColumn, DEFAULT_STATEMENT_CACHE_SIZE,
+};
+use crate::client::inserter::{adapt_block, insert_schema, table_columns};
+use crate::client::session::{SessionRestorePolicy, SessionState};
+use crate::client::in_list::{rewrite_in_lists, InListStrategy, InListTable, DEFAULT_IN_LIST_THRESHOLD};
+use crate::protocol::{
+    BlockDecoder, ClientCancel, ClientData, ClientHello, ClientInfo, ClientQuery, ConnectionStats, DecodeOptions, Frame, FrameDecoder, Framing, Packet, PacketType,
+    ProtocolStats, ProtocolWriter, ServerData, ServerEndOfStream, ServerException, ServerHello, ServerPartUUIDs, ServerProgress, ServerTableColumns, ServerTimezoneUpdate,
+};
+use chrono_tz::Tz;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Instant;
 use tokio::net::TcpStream;
 use tokio::time::timeout;
-use tokio::io::AsyncWriteExt;
-use tokio_tungstenite::{connect_async, WebSocketStream, MaybeTlsStream};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_tungstenite::{connect_async_tls_with_config, Connector, WebSocketStream, MaybeTlsStream};
 
 use tungstenite::Message;
 
+/// Lifecycle state of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// No server connection
+    Disconnected,
+    /// Connected and ready for the next query
+    Idle,
+    /// Query sent, waiting for the server to respond
+    QueryInFlight,
+    /// Data blocks are being exchanged for the current query
+    Streaming,
+    /// Protocol state is unknown; the connection must be re-established
+    Broken,
+}
+
+impl ConnectionState {
+    /// Check if a transition to `next` is allowed
+    pub fn can_transition_to(self, next: ConnectionState) -> bool {
+        use ConnectionState::*;
+
+        matches!(
+            (self, next),
+            (_, Disconnected)
+                | (_, Broken)
+                | (Disconnected, Idle)
+                | (Idle, QueryInFlight)
+                | (QueryInFlight, Streaming)
+                | (QueryInFlight, Idle)
+                | (Streaming, Idle)
+        )
+    }
+
+    /// Check if the state holds a live server connection
+    pub fn is_connected(self) -> bool {
+        matches!(
+            self,
+            ConnectionState::Idle | ConnectionState::QueryInFlight | ConnectionState::Streaming
+        )
+    }
+
+    /// Get the state name
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConnectionState::Disconnected => "disconnected",
+            ConnectionState::Idle => "idle",
+            ConnectionState::QueryInFlight => "query_in_flight",
+            ConnectionState::Streaming => "streaming",
+            ConnectionState::Broken => "broken",
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Byte stream carrying native protocol packets in place of a TCP stream
+///
+/// Packets are framed as [`ProtocolWriter`] writes them, so a
+/// [`ReplayTransport`](crate::protocol::ReplayTransport) or an in-memory pipe
+/// can play the server side.
+pub trait PacketTransport: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T> PacketTransport for T where T: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+/// Response of the query in flight, read packet by packet
+#[derive(Debug)]
+struct PendingResponse {
+    /// SQL sent to the server
+    sql: String,
+    /// Decoder configured from the settings of the query
+    decoder: BlockDecoder,
+    /// Names and types of the result columns, from the first data block
+    metadata: Option<QueryMetadata>,
+    /// Rows and bytes the server reported reading
+    stats: QueryStats,
+    /// UUIDs of the data parts the query read
+    part_uuids: Vec<ServerPartUUIDs>,
+    /// When the query was sent
+    started: Instant,
+}
+
 /// Connection to a ClickHouse server
 pub struct Connection {
     /// Connection options
@@ -20,31 +117,96 @@ pub struct Connection {
     tcp_stream: Option<TcpStream>,
     /// WebSocket stream for HTTP/WebSocket interface
     websocket: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    /// Whether the connection is connected
-    connected: bool,
+    /// Transport the native protocol runs over instead of TCP, if attached
+    transport: Option<Box<dyn PacketTransport>>,
+    /// Response of the query in flight on the transport
+    response: Option<PendingResponse>,
+    /// Cuts the packets read from the transport out of the received bytes
+    frames: FrameDecoder,
+    /// Pings the server while the response of the query in flight is idle
+    keepalive: Option<KeepAlive>,
+    /// Current lifecycle state
+    state: ConnectionState,
     /// Connection ID
     id: String,
     /// Last activity timestamp
     last_activity: Instant,
+    /// ID of the query currently on the wire, if any
+    pending_query: Option<String>,
+    /// Session timezone announced by the server
+    server_timezone: Option<Tz>,
+    /// Protocol revision announced by the server
+    server_revision: Option<u64>,
+    /// Client information reported to the server
+    client_info: ClientInfo,
+    /// Settings, role and temporary tables applied in the current session
+    session: SessionState,
+    /// How result columns of the query in flight are decoded and validated
+    decode_options: DecodeOptions,
+    /// Compression of the data blocks of the query in flight
+    compression: CompressionMethod,
+    /// Server settings of the insert in flight, sent in its query
+    insert_settings: String,
+    /// Bytes, packets and compression sizes exchanged on this connection
+    stats: ProtocolStats,
+    /// Server session that HTTP requests are bound to, if enabled
+    http_session: Option<HttpSession>,
+    /// Prepared statements and table schemas seen on this connection
+    statements: StatementCache,
+    /// Columns the server reported for the target of the last insert
+    insert_columns: Option<ServerTableColumns>,
+    /// Client-side memory bound of the query in flight
+    memory_watchdog: Option<MemoryWatchdog>,
+    /// Generation of the TLS identity the connection was opened with
+    tls_generation: Option<u64>,
+    /// Last query that ran over the slow query threshold, until taken
+    slow_query: Option<SlowQuery>,
 }
 
 impl Connection {
     /// Create a new connection
     pub fn new(options: crate::client::ClientOptions) -> Self {
+        let client_info = options.resolved_client_info();
+        let compression = options.effective_compression();
+        let http_session = options.http_session.clone().map(HttpSession::new);
+        let statements = StatementCache::new(options.statement_cache_size.unwrap_or(DEFAULT_STATEMENT_CACHE_SIZE));
+        let stats = ProtocolStats::new();
+        let frames = frame_decoder(&options, &stats);
         Self {
             options,
             tcp_stream: None,
             websocket: None,
-            connected: false,
+            transport: None,
+            response: None,
+            frames,
+            keepalive: None,
+            state: ConnectionState::Disconnected,
             id: uuid::Uuid::new_v4().to_string(),
             last_activity: Instant::now(),
+            pending_query: None,
+            server_timezone: None,
+            server_revision: None,
+            client_info,
+            session: SessionState::new(),
+            decode_options: DecodeOptions::default(),
+            compression,
+            insert_settings: String::new(),
+            stats,
+            http_session,
+            statements,
+            insert_columns: None,
+            memory_watchdog: None,
+            tls_generation: None,
+            slow_query: None,
         }
     }
 
     /// Connect to the server
     pub async fn connect(&mut self) -> Result<()> {
-        if self.connected {
-            return Ok(());
+        match self.state {
+            ConnectionState::Disconnected => {}
+            ConnectionState::Broken => self.disconnect().await?,
+            _ => return Ok(()),
         }
 
         let start_time = Instant::now();
@@ -57,8 +219,9 @@ impl Connection {
             self.connect_native().await?;
         }
 
-        self.connected = true;
+        self.transition(ConnectionState::Idle)?;
         self.last_activity = Instant::now();
+        self.tls_generation = self.options.tls_reloader.as_ref().map(|tls| tls.generation());
 
         tracing::debug!(
             "Connected to {}:{} in {:?}",
@@ -67,11 +230,89 @@ impl Connection {
             start_time.elapsed()
         );
 
+        self.restore_session().await
+    }
+
+    /// Re-apply the state of the previous session according to the restore policy
+    async fn restore_session(&mut self) -> Result<()> {
+        let mut session = std::mem::take(&mut self.session);
+        if session.is_empty() {
+            return Ok(());
+        }
+
+        match self.options.session_restore {
+            SessionRestorePolicy::Discard => {
+                tracing::warn!("Connection {} reconnected without its session state: {}", self.id, session.describe());
+                Ok(())
+            }
+            SessionRestorePolicy::Fail => Err(Error::SessionLost(format!(
+                "Connection {} lost its session state on reconnect: {}",
+                self.id,
+                session.describe()
+            ))),
+            SessionRestorePolicy::Restore => {
+                // A failed replay is reported once; the state is not retried
+                self.replay_session(&session).await?;
+
+                let lost_tables = session.take_temporary_tables();
+                self.session = session;
+                if lost_tables.is_empty() {
+                    tracing::debug!("Restored session state on connection {}", self.id);
+                    Ok(())
+                } else {
+                    Err(Error::SessionLost(format!(
+                        "Connection {} lost temporary tables on reconnect: {}",
+                        self.id,
+                        lost_tables.join(", ")
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Run the statements that re-apply a session's settings and role
+    async fn replay_session(&mut self, session: &SessionState) -> Result<()> {
+        for statement in session.restore_statements() {
+            self.start_query()?;
+            let result = self.dispatch_query(&statement).await;
+            self.finish_request(result).map_err(|e| {
+                Error::SessionLost(format!("Failed to restore session on connection {}: {}", self.id, e))
+            })?;
+        }
         Ok(())
     }
 
+    /// Get the session state applied on this connection
+    pub fn session(&self) -> &SessionState {
+        &self.session
+    }
+
+    /// Forget the recorded session state, so it is not restored on reconnect
+    pub fn clear_session(&mut self) {
+        self.session = SessionState::new();
+    }
+
+    /// Start with recorded session state, applied on connect according to the restore policy
+    #[cfg(test)]
+    pub(crate) fn with_session(mut self, session: SessionState) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Run the native protocol over `transport` instead of a TCP stream
+    ///
+    /// The transport counts as connected; once the connection is closed, it
+    /// is dropped and the next connect goes over TCP again.
+    pub fn with_transport(mut self, transport: impl PacketTransport + 'static) -> Self {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
     /// Connect using native protocol
     async fn connect_native(&mut self) -> Result<()> {
+        if self.transport.is_some() {
+            return Ok(());
+        }
         let addr = format!("{}:{}", self.options.host, self.options.port);
         let stream = timeout(
             self.options.connect_timeout,
@@ -98,7 +339,7 @@ impl Connection {
 
         let (ws_stream, _) = timeout(
             self.options.connect_timeout,
-            connect_async(url)
+            connect_async_tls_with_config(url, None, false, self.tls_connector()?)
         ).await
             .map_err(|_| Error::Timeout(self.options.connect_timeout))??;
 
@@ -106,6 +347,25 @@ impl Connection {
         Ok(())
     }
 
+    /// Build the TLS connector for the current identity, if one is configured
+    fn tls_connector(&self) -> Result<Option<Connector>> {
+        match &self.options.tls_reloader {
+            #[cfg(feature = "native-tls")]
+            Some(tls) if self.options.use_tls => {
+                Ok(Some(Connector::NativeTls(tls.identity().native_connector(self.options.tls_verify)?)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Check whether the TLS identity changed since the connection was opened
+    pub fn has_stale_tls(&self) -> bool {
+        match (&self.options.tls_reloader, self.tls_generation) {
+            (Some(tls), Some(generation)) => tls.generation() != generation,
+            _ => false,
+        }
+    }
+
     /// Connect using HTTP (placeholder for future implementation)
     async fn connect_http(&mut self) -> Result<()> {
         // HTTP connection will be implemented separately
@@ -114,7 +374,7 @@ impl Connection {
 
     /// Disconnect from the server
     pub async fn disconnect(&mut self) -> Result<()> {
-        if !self.connected {
+        if self.state == ConnectionState::Disconnected {
             return Ok(());
         }
 
@@ -126,65 +386,250 @@ impl Connection {
             let _ = stream.shutdown().await;
         }
 
-        self.connected = false;
+        if let Some(mut transport) = self.transport.take() {
+            let _ = transport.shutdown().await;
+        }
+
+        self.state = ConnectionState::Disconnected;
+        self.pending_query = None;
+        self.response = None;
+        self.frames = frame_decoder(&self.options, &self.stats);
+        self.server_timezone = None;
+        self.server_revision = None;
+        // Schemas may change while we are away, and the server may be another replica
+        self.statements.clear();
+        self.insert_columns = None;
         tracing::debug!("Disconnected from {}:{}", self.options.host, self.options.port);
         Ok(())
     }
 
     /// Execute a query
     pub async fn query(&mut self, sql: &str) -> Result<QueryResult> {
-        if !self.connected {
-            self.connect().await?;
-        }
+        self.ensure_ready().await?;
+        self.run_query(sql).await
+    }
 
+    /// Execute a query on a connection known to be ready
+    async fn run_query(&mut self, sql: &str) -> Result<QueryResult> {
         let start_time = Instant::now();
+        self.slow_query = None;
         self.last_activity = Instant::now();
+        let query_id = self.start_query()?.to_string();
 
-        let result = if self.options.use_websocket {
-            self.query_websocket(sql).await?
-        } else if self.options.use_http {
-            self.query_http(sql).await?
-        } else {
-            self.query_native(sql).await?
+        let query_timeout = self.options.query_timeout;
+        let result = match timeout(query_timeout, self.dispatch_query(sql)).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::Timeout(query_timeout)),
         };
+        let mut result = self
+            .settle_query(result)
+            .await
+            .context_with(|| self.query_context(&query_id, sql))?;
+        self.session.track(sql);
+        self.statements.observe(sql);
+        if result.server_timezone.is_none() {
+            result.server_timezone = self.server_timezone;
+        }
 
         let elapsed = start_time.elapsed();
         tracing::debug!("Query executed in {:?}", elapsed);
+        if let Some(threshold) = self.options.slow_query_threshold.filter(|threshold| elapsed > *threshold) {
+            tracing::warn!(
+                "Slow query {} took {:?}, reading {} rows and {} bytes: {}",
+                query_id,
+                elapsed,
+                result.stats.rows_read,
+                result.stats.bytes_read,
+                sql
+            );
+            let slow = SlowQuery { query_id, sql: sql.to_string(), elapsed, threshold, stats: result.stats.clone() };
+            self.options.warnings.emit(ClientWarning::SlowQuery(slow.clone()));
+            self.slow_query = Some(slow);
+        }
 
         Ok(result)
     }
 
+    /// Settle the connection once a query has completed or failed
+    ///
+    /// A query that timed out or went over the client's limits is still
+    /// running on the server, so it is cancelled.
+    async fn settle_query<T>(&mut self, result: Result<T>) -> Result<T> {
+        if let Err(e @ (Error::Timeout(_) | Error::ResultTooLarge(_) | Error::MemoryLimitExceeded(_))) = result {
+            if let Err(cancel) = self.cancel_pending_query().await {
+                tracing::warn!("Failed to cancel query: {}", cancel);
+            }
+            return Err(e);
+        }
+        self.finish_request(result)
+    }
+
+    /// Send a query whose result is then read block by block with [`next_block`](Self::next_block)
+    ///
+    /// The settings stay in effect until the last block has been read.
+    pub async fn send_query(&mut self, sql: &str, settings: &QuerySettings) -> Result<()> {
+        self.ensure_ready().await?;
+        let sql = self.apply_settings(sql, settings);
+        self.last_activity = Instant::now();
+        let query_id = self.start_query()?.to_string();
+
+        let result = if self.options.use_websocket || self.options.use_http {
+            Err(Error::Unsupported("Streaming results are only read over the native protocol".to_string()))
+        } else {
+            self.send_query_native(&sql).await
+        };
+        if let Err(e) = result {
+            let result = self.settle_query(Err(e)).await;
+            self.reset_settings();
+            return result.context_with(|| self.query_context(&query_id, &sql));
+        }
+        Ok(())
+    }
+
+    /// Read the next block of the query sent with [`send_query`](Self::send_query)
+    ///
+    /// Nothing is read from the server between calls. Returns `None` once the
+    /// whole result has been read; the connection is then ready for the next
+    /// query.
+    pub async fn next_block(&mut self) -> Result<Option<Block>> {
+        let Some(response) = &self.response else {
+            return Ok(None);
+        };
+        let sql = response.sql.clone();
+        let query_id = self.pending_query.clone().unwrap_or_default();
+
+        // With keep-alive pings an idle stream is fine as long as the server
+        // answers them, so the read timeout only applies without pings
+        let result = match self.keepalive {
+            Some(_) => self.read_response_block().await,
+            None => {
+                let read_timeout = self.options.read_timeout;
+                match timeout(read_timeout, self.read_response_block()).await {
+                    Ok(result) => result,
+                    Err(_) => Err(Error::Timeout(read_timeout)),
+                }
+            }
+        };
+        match result {
+            Ok(Some(block)) => return Ok(Some(block)),
+            Ok(None) => {
+                let warnings = self.finish_response(Vec::new()).warnings;
+                if !warnings.is_empty() {
+                    tracing::warn!("Query {} decoded with warnings: {}", query_id, warnings.join("; "));
+                }
+                self.session.track(&sql);
+                self.statements.observe(&sql);
+            }
+            Err(_) => self.response = None,
+        }
+        let result = self.settle_query(result.map(|_| None)).await;
+        self.reset_settings();
+        result.context_with(|| self.query_context(&query_id, &sql))
+    }
+
+    /// Take the last query that ran over the slow query threshold
+    pub fn take_slow_query(&mut self) -> Option<SlowQuery> {
+        self.slow_query.take()
+    }
+
     /// Execute a query with parameters
+    ///
+    /// Long array parameters are rewritten as with default settings in
+    /// [`query_with_params_and_settings`](Self::query_with_params_and_settings).
     pub async fn query_with_params(
         &mut self,
         sql: &str,
         params: HashMap<String, Value>,
     ) -> Result<QueryResult> {
-        // Replace parameters in SQL
-        let mut final_sql = sql.to_string();
-        for (key, value) in params {
-            let placeholder = format!("{{{}}}", key);
-            let value_str = match value {
-                Value::String(s) => format!("'{}'", s),
-                Value::UInt8(v) => v.to_string(),
-                Value::UInt16(v) => v.to_string(),
-                Value::UInt32(v) => v.to_string(),
-                Value::UInt64(v) => v.to_string(),
-                Value::Int8(v) => v.to_string(),
-                Value::Int16(v) => v.to_string(),
-                Value::Int32(v) => v.to_string(),
-                Value::Int64(v) => v.to_string(),
-                Value::Float32(v) => v.to_string(),
-                Value::Float64(v) => v.to_string(),
-                Value::Date(d) => format!("'{}'", d.format("%Y-%m-%d")),
-                Value::DateTime(dt) => format!("'{}'", dt.format("%Y-%m-%d %H:%M:%S")),
-                Value::UUID(u) => format!("'{}'", u),
-                _ => format!("{:?}", value),
-            };
-            final_sql = final_sql.replace(&placeholder, &value_str);
+        let statement = self.prepare(sql);
+        if let Some(missing) = statement.parameters.iter().find(|p| !params.contains_key(&p.name)) {
+            return Err(Error::Configuration(format!("Missing value for query parameter {}", missing.name)));
+        }
+        let result = self.query_with_params_and_settings(sql, params, QuerySettings::default()).await?;
+        if statement.result_columns.is_none() {
+            self.statements.set_result_columns(sql, result_columns(&result.metadata));
         }
+        Ok(result)
+    }
+
+    /// Get the prepared form of a statement from the cache, parsing it on a miss
+    pub fn prepare(&mut self, sql: &str) -> Arc<PreparedStatement> {
+        self.statements.prepare(sql)
+    }
+
+    /// Get the columns of a table, reading them with `DESCRIBE TABLE` on a cache miss
+    pub async fn describe_table(&mut self, table: &str) -> Result<Vec<TableColumn>> {
+        if let Some(schema) = self.statements.table_schema(table) {
+            return Ok(schema);
+        }
+        let result = self.query(&describe_table_sql(table)).await?;
+        let schema = table_columns(&result)?;
+        self.statements.set_table_schema(table, schema.clone());
+        Ok(schema)
+    }
+
+    /// Get the prepared statements and table schemas cached on this connection
+    pub fn statement_cache(&self) -> &StatementCache {
+        &self.statements
+    }
 
-        self.query(&final_sql).await
+    /// Get the statement cache mutably, e.g. to invalidate a table changed elsewhere
+    pub fn statement_cache_mut(&mut self) -> &mut StatementCache {
+        &mut self.statements
+    }
+
+    /// Execute a query with parameters and settings
+    ///
+    /// Array parameters longer than the settings' IN-list threshold are read
+    /// from a `VALUES` table function or a temporary table instead of being
+    /// inlined into the query text.
+    pub async fn query_with_params_and_settings(
+        &mut self,
+        sql: &str,
+        params: HashMap<String, Value>,
+        settings: QuerySettings,
+    ) -> Result<QueryResult> {
+        let rewrite = rewrite_in_lists(
+            sql,
+            params,
+            settings.in_list_threshold.unwrap_or(DEFAULT_IN_LIST_THRESHOLD),
+            settings.in_list_strategy.unwrap_or(InListStrategy::Values),
+        )?;
+        let mut settings = settings;
+        if let Some(size) = rewrite.max_query_size {
+            settings = settings.custom_setting("max_query_size", size.to_string());
+        }
+        let final_sql = bind_params(&rewrite.sql, rewrite.params);
+
+        if rewrite.tables.is_empty() {
+            return self.query_with_settings(&final_sql, settings).await;
+        }
+
+        let mut result = Ok(());
+        for table in &rewrite.tables {
+            result = self.fill_in_list_table(table).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        let result = match result {
+            Ok(()) => self.query_with_settings(&final_sql, settings).await,
+            Err(e) => Err(e),
+        };
+
+        // Temporary tables live for the session, so drop them even on failure
+        for table in &rewrite.tables {
+            if let Err(e) = self.execute(&table.drop_sql()).await {
+                tracing::warn!("Failed to drop temporary table {}: {}", table.name, e);
+            }
+        }
+        result
+    }
+
+    async fn fill_in_list_table(&mut self, table: &InListTable) -> Result<()> {
+        self.execute(&table.create_sql()).await?;
+        self.insert(&table.name, table.block()?).await?;
+        Ok(())
     }
 
     /// Execute a query with settings
@@ -193,14 +638,131 @@ impl Connection {
         sql: &str,
         settings: QuerySettings,
     ) -> Result<QueryResult> {
-        let settings_str = settings.build_settings_string();
+        let final_sql = self.apply_settings(sql, &settings);
+        let result = self.query(&final_sql).await;
+        self.reset_settings();
+        if let (Ok(result), Some(_)) = (&result, settings.block_memory_target) {
+            // Later runs size their blocks from the result columns
+            if self.prepare(sql).result_columns.is_none() {
+                self.statements.set_result_columns(sql, result_columns(&result.metadata));
+            }
+        }
+        result
+    }
+
+    /// Execute queries one after another, returning their results in order
+    ///
+    /// Each query runs as with
+    /// [`query_with_params_and_settings`](Self::query_with_params_and_settings)
+    /// and its result is read in full before the next query is sent, so this
+    /// saves no round trips over calling it in a loop. The pipeline stops at
+    /// the first failure, whose error carries the index of the failed query.
+    pub async fn pipeline(&mut self, queries: &[Query]) -> Result<Vec<QueryResult>> {
+        let mut results = Vec::with_capacity(queries.len());
+        for (index, query) in queries.iter().enumerate() {
+            let result = self
+                .query_with_params_and_settings(query.sql(), query.get_params().clone(), query.get_settings().clone())
+                .await;
+            match result {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    tracing::debug!("Pipeline stopped at query {} of {}", index + 1, queries.len());
+                    let details = format!("query {} of {}", index + 1, queries.len());
+                    return Err(e.context(ErrorContext::new("pipeline").with_details(details)));
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Set up per-query state from settings and build the SQL to send
+    fn apply_settings(&mut self, sql: &str, settings: &QuerySettings) -> String {
+        let mut settings_str = settings.build_settings_string();
+        if settings.max_block_size.is_none() {
+            if let Some(rows) = settings.block_size_for(self.cached_row_width(sql)) {
+                let separator = if settings_str.is_empty() { "" } else { ", " };
+                settings_str = format!("{}{}max_block_size={}", settings_str, separator, rows);
+            }
+        }
+        let sql = settings.guard_sql(sql);
         let final_sql = if settings_str.is_empty() {
             sql.to_string()
         } else {
             format!("{} SETTINGS {}", sql, settings_str)
         };
 
-        self.query(&final_sql).await
+        self.decode_options = DecodeOptions {
+            mode: settings.decode_mode.unwrap_or_default(),
+            validation: settings.validation.unwrap_or_default(),
+            max_rows: settings.max_result_rows,
+            max_bytes: settings.max_result_bytes,
+        };
+        self.compression = settings.compression.unwrap_or_else(|| self.options.effective_compression());
+        self.memory_watchdog = settings.max_query_memory.map(MemoryWatchdog::new);
+        for name in settings.custom.keys() {
+            if let Some(setting) = deprecated_setting(name) {
+                self.options.warnings.emit(ClientWarning::DeprecatedSetting {
+                    name: setting.name.to_string(),
+                    replacement: setting.replacement.map(str::to_string),
+                });
+            }
+        }
+        final_sql
+    }
+
+    /// Estimate the row width of a statement whose result columns are cached
+    fn cached_row_width(&self, sql: &str) -> Option<usize> {
+        let columns = self.statements.get(sql)?.result_columns.clone()?;
+        Some(estimate_row_width(columns.iter().map(|column| column.type_name.as_str())))
+    }
+
+    /// Restore the per-query state set by [`apply_settings`](Self::apply_settings)
+    fn reset_settings(&mut self) {
+        self.decode_options = DecodeOptions::default();
+        self.compression = self.options.effective_compression();
+        self.memory_watchdog = None;
+    }
+
+    /// Get how result columns of the query in flight are decoded and validated
+    pub fn decode_options(&self) -> DecodeOptions {
+        self.decode_options
+    }
+
+    /// Get the compression of the data blocks of the query in flight
+    ///
+    /// This is the client's compression unless the query's settings override it.
+    pub fn compression(&self) -> CompressionMethod {
+        self.compression
+    }
+
+    /// Compress a serialized data block into a frame with a checksum
+    pub fn encode_block_frame(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let level = CompressionLevel::new(self.options.compression_level)?;
+        compression::encode_frame(data, self.compression.into(), level)
+    }
+
+    /// Decompress a received data block frame
+    ///
+    /// With [`ClientOptions::verify_checksums`] the frame's checksum is checked,
+    /// and a corrupted frame marks the connection broken so that it is
+    /// reconnected before the next query.
+    pub fn decode_block_frame(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        let result = compression::decode_frame(frame, self.options.verify_checksums);
+        if let Err(e @ Error::IntegrityCheck(_)) = &result {
+            tracing::warn!("Connection {} received a corrupted block, resetting: {}", self.id, e);
+            self.state = ConnectionState::Broken;
+        }
+        result
+    }
+
+    /// Get the keep-alive pinger of the last native query, if pings are enabled
+    pub fn keepalive(&self) -> Option<&KeepAlive> {
+        self.keepalive.as_ref()
+    }
+
+    /// Get the protocol counters accumulated over the life of this connection
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats.snapshot()
     }
 
     /// Execute a query (no result)
@@ -230,53 +792,71 @@ impl Connection {
     }
 
     /// Insert data into a table
-    pub async fn insert(&mut self, table: &str, block: Block) -> Result<()> {
-        if !self.connected {
-            self.connect().await?;
-        }
+    pub async fn insert(&mut self, table: &str, block: Block) -> Result<InsertResult> {
+        self.ensure_ready().await?;
 
         self.last_activity = Instant::now();
+        let query_id = self.start_query()?.to_string();
+        self.transition(ConnectionState::Streaming)?;
 
-        if self.options.use_websocket {
-            self.insert_websocket(table, block).await?;
+        let result = if self.options.use_websocket {
+            self.insert_websocket(table, block).await
         } else if self.options.use_http {
-            self.insert_http(table, block).await?;
+            self.insert_http(table, block).await
         } else {
-            self.insert_native(table, block).await?;
-        }
+            self.insert_native(table, block).await
+        };
+        let result = result.map(|inserted| InsertResult {
+            query_id,
+            duration: self.last_activity.elapsed(),
+            ..inserted
+        });
 
-        Ok(())
+        self.finish_request(result)
     }
 
     /// Insert data with settings
+    ///
+    /// Server-side settings go in the `SETTINGS` clause of the insert query,
+    /// as for queries; the compression setting applies to the data block.
     pub async fn insert_with_settings(
         &mut self,
         table: &str,
         block: Block,
         settings: QuerySettings,
-    ) -> Result<()> {
-        // For now, just insert without settings
-        // TODO: Implement settings support for inserts
-        self.insert(table, block).await
+    ) -> Result<InsertResult> {
+        self.compression = settings.compression.unwrap_or_else(|| self.options.effective_compression());
+        self.insert_settings = settings.build_settings_string();
+        let result = self.insert(table, block).await;
+        self.insert_settings.clear();
+        self.compression = self.options.effective_compression();
+        result
+    }
+
+    /// Build the query of an insert into `table` with the insert's settings
+    fn insert_sql(&self, table: &str) -> String {
+        if self.insert_settings.is_empty() {
+            format!("INSERT INTO {} FORMAT Native", table)
+        } else {
+            format!("INSERT INTO {} SETTINGS {} FORMAT Native", table, self.insert_settings)
+        }
     }
 
     /// Ping the server
     pub async fn ping(&mut self) -> Result<()> {
-        if !self.connected {
-            self.connect().await?;
-        }
+        self.ensure_ready().await?;
 
         self.last_activity = Instant::now();
 
-        if self.options.use_websocket {
-            self.ping_websocket().await?;
+        let result = if self.options.use_websocket {
+            self.ping_websocket().await
         } else if self.options.use_http {
-            self.ping_http().await?;
+            self.ping_http().await
         } else {
-            self.ping_native().await?;
-        }
+            self.ping_native().await
+        };
 
-        Ok(())
+        self.finish_request(result)
     }
 
     /// Get server information
@@ -303,6 +883,253 @@ impl Connection {
         Ok(info.get("version").cloned().unwrap_or_else(|| "unknown".to_string()))
     }
 
+    /// Get the current connection state
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Check if the connection is broken and must be re-established
+    pub fn is_broken(&self) -> bool {
+        self.state == ConnectionState::Broken
+    }
+
+    /// Move to the next state, rejecting invalid transitions
+    fn transition(&mut self, next: ConnectionState) -> Result<()> {
+        if !self.state.can_transition_to(next) {
+            return Err(Error::Internal(format!(
+                "Invalid connection state transition: {} -> {}",
+                self.state, next
+            )));
+        }
+
+        self.state = next;
+        Ok(())
+    }
+
+    /// Bring the connection to the idle state before sending a request
+    ///
+    /// Disconnected and broken connections are (re)connected, and a query
+    /// abandoned mid-stream is cancelled first, so an earlier failure never
+    /// leaves the connection stuck.
+    async fn ensure_ready(&mut self) -> Result<()> {
+        match self.state {
+            ConnectionState::Idle => Ok(()),
+            ConnectionState::QueryInFlight | ConnectionState::Streaming => {
+                tracing::warn!("Connection {} has an abandoned query, cancelling", self.id);
+                if let Err(e) = self.cancel_pending_query().await {
+                    tracing::warn!("Failed to cancel abandoned query: {}", e);
+                }
+                self.connect().await
+            }
+            ConnectionState::Disconnected | ConnectionState::Broken => self.connect().await,
+        }
+    }
+
+    /// Mark a query as in flight until it completes
+    ///
+    /// If the future driving the query is dropped before completion the
+    /// state stays in flight, which tells the pool the connection is left
+    /// mid-stream.
+    pub(crate) fn start_query(&mut self) -> Result<&str> {
+        self.transition(ConnectionState::QueryInFlight)?;
+        Ok(self.pending_query.insert(uuid::Uuid::new_v4().to_string()))
+    }
+
+    /// Settle the connection state once a request has completed
+    pub(crate) fn finish_request<T>(&mut self, result: Result<T>) -> Result<T> {
+        self.pending_query = None;
+        self.state = match &result {
+            Err(e) if e.poisons_connection() => {
+                tracing::warn!("Connection {} is broken: {}", self.id, e);
+                ConnectionState::Broken
+            }
+            _ => ConnectionState::Idle,
+        };
+        result
+    }
+
+    /// Check if a query was abandoned mid-stream on this connection
+    pub fn has_pending_query(&self) -> bool {
+        matches!(self.state, ConnectionState::QueryInFlight | ConnectionState::Streaming)
+    }
+
+    /// Get the HTTP session this connection's requests are bound to
+    pub fn http_session(&self) -> Option<&HttpSession> {
+        self.http_session.as_ref()
+    }
+
+    /// Get the HTTP session mutably, to record responses and reuse its connection
+    pub fn http_session_mut(&mut self) -> Option<&mut HttpSession> {
+        self.http_session.as_mut()
+    }
+
+    /// Get the client information reported to the server
+    pub fn client_info(&self) -> &ClientInfo {
+        &self.client_info
+    }
+
+    /// Build the handshake packet for this connection
+    pub fn client_hello(&self) -> ClientHello {
+        let hello = ClientHello::new(
+            self.client_info.client_name.clone().unwrap_or_default(),
+            self.options.database.as_str(),
+            self.options.username.as_str(),
+            self.options.password.as_str(),
+        )
+        .with_protocol_version(self.options.native_protocol_version as u64);
+        self.client_info.apply_to_hello(hello)
+    }
+
+    /// Write the handshake addendum due after the server hello
+    pub fn write_hello_addendum(&self, buf: &mut bytes::BytesMut) {
+        self.client_hello().serialize_addendum(buf, self.protocol_revision());
+    }
+
+    /// Build the query packet for `sql`, tagged with this client's information
+    ///
+    /// Settings are serialized for the revision both sides support.
+    pub fn client_query(&self, sql: &str, query_id: &str) -> ClientQuery {
+        self.client_info
+            .apply_to_query(ClientQuery::new(sql).with_query_id(query_id))
+            .with_revision(self.protocol_revision())
+            .with_compression(self.compression != CompressionMethod::None)
+    }
+
+    /// Get the protocol revision used with the server
+    pub fn protocol_revision(&self) -> u64 {
+        let client_revision = self.options.native_protocol_version as u64;
+        self.server_revision
+            .map_or(client_revision, |server| server.min(client_revision))
+    }
+
+    /// Get the session timezone announced by the server
+    pub fn server_timezone(&self) -> Option<Tz> {
+        self.server_timezone
+    }
+
+    /// Record the server timezone from the handshake
+    pub fn apply_server_hello(&mut self, hello: &ServerHello) -> Result<()> {
+        self.server_timezone = Some(parse_timezone(hello.timezone())?);
+        self.server_revision = Some(hello.server_revision);
+        Ok(())
+    }
+
+    /// Switch to the timezone announced mid-session
+    ///
+    /// Results decoded from here on use the new timezone.
+    pub fn apply_timezone_update(&mut self, update: &ServerTimezoneUpdate) -> Result<()> {
+        let timezone = update.tz()?;
+        let previous = self.server_timezone.replace(timezone);
+        tracing::debug!("Connection {} switched to timezone {}", self.id, update.timezone());
+        self.notify_server_event(ServerEvent::TimezoneChanged { previous, timezone });
+        Ok(())
+    }
+
+    /// Consume a packet the server pushed in the middle of a stream
+    ///
+    /// Timezone updates are applied, and they and other pushed packets such
+    /// as logs and table columns go to the server event handler. Packets
+    /// answering the query are handed back to the caller.
+    pub fn handle_server_frame(&mut self, frame: Frame) -> Result<Option<Frame>> {
+        if !is_server_pushed(frame.packet_type) {
+            return Ok(Some(frame));
+        }
+        self.last_activity = Instant::now();
+        if frame.kind() == Some(PacketType::ServerTimezoneUpdate) {
+            self.apply_timezone_update(&ServerTimezoneUpdate::from_frame_payload(&frame.payload)?)?;
+        } else {
+            tracing::trace!("Connection {} received server packet {}", self.id, frame.packet_type);
+            self.notify_server_event(ServerEvent::Packet(frame));
+        }
+        Ok(None)
+    }
+
+    fn notify_server_event(&self, event: ServerEvent) {
+        if let Some(handler) = &self.options.server_event_handler {
+            handler.notify(&event);
+        }
+    }
+
+    /// Align an insert block with the columns the server sent for the table
+    ///
+    /// The block is reordered to table order, columns the table lacks are
+    /// dropped and missing plain columns are filled with type defaults, as in
+    /// [`adapt_block`]. The columns also refresh the cached schema of the table.
+    pub fn apply_table_columns(&mut self, table: &str, packet: ServerTableColumns, block: Block) -> Result<Block> {
+        let schema = insert_schema(&packet);
+        self.statements.set_table_schema(table, schema.clone());
+        self.insert_columns = Some(packet);
+        adapt_block(block, &schema)
+    }
+
+    /// Check a progress packet of the query in flight against its memory bound
+    ///
+    /// Every progress packet of a native query goes through here. Fails with
+    /// [`Error::MemoryLimitExceeded`] once the server reports more memory use
+    /// than [`QuerySettings::max_query_memory`] allows; the query is then
+    /// cancelled instead of being left to hit the server's limit.
+    pub fn apply_progress(&mut self, progress: &ServerProgress) -> Result<()> {
+        match &mut self.memory_watchdog {
+            Some(watchdog) => watchdog.check(progress),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the memory bound of the query in flight
+    pub fn memory_watchdog(&self) -> Option<&MemoryWatchdog> {
+        self.memory_watchdog.as_ref()
+    }
+
+    /// Get the columns the server sent before the last insert
+    pub fn insert_columns(&self) -> Option<&ServerTableColumns> {
+        self.insert_columns.as_ref()
+    }
+
+    /// Build the error context for a query on this connection
+    fn query_context(&self, query_id: &str, sql: &str) -> ErrorContext {
+        ErrorContext::new("query")
+            .with_host(format!("{}:{}", self.options.host, self.options.port))
+            .with_query_id(query_id)
+            .with_sql(sql)
+    }
+
+    /// Cancel an abandoned query and drop the underlying stream
+    ///
+    /// Sends `ClientCancel` on the native protocol, over TCP or the attached
+    /// transport, then disconnects: the
+    /// remaining response packets are still on the wire, so the connection
+    /// cannot be reused until it is re-established.
+    pub async fn cancel_pending_query(&mut self) -> Result<()> {
+        if !self.has_pending_query() {
+            return Ok(());
+        }
+        // The server only knows the query by the id it was sent with, so
+        // without one there is nothing to cancel beyond dropping the stream
+        let query_id = self.pending_query.take();
+
+        let stream: Option<&mut dyn PacketTransport> = match self.tcp_stream.as_mut() {
+            Some(stream) => Some(stream),
+            None => self.transport.as_deref_mut(),
+        };
+        let mut cancel_result = Ok(());
+        if let (Some(query_id), Some(stream)) = (&query_id, stream) {
+            let mut packet = Vec::new();
+            ProtocolWriter::new(&mut packet)
+                .with_tracer(self.options.packet_tracer.clone())
+                .with_stats(self.stats.clone())
+                .write_packet(&ClientCancel::new(query_id.clone()))?;
+
+            cancel_result = match timeout(self.options.write_timeout, stream.write_all(&packet)).await {
+                Ok(result) => result.map_err(Error::from),
+                Err(_) => Err(Error::Timeout(self.options.write_timeout)),
+            };
+        }
+
+        tracing::debug!("Cancelled query {:?} on connection {}", query_id, self.id);
+        self.disconnect().await?;
+        cancel_result
+    }
+
     /// Reset the connection
     pub async fn reset(&mut self) -> Result<()> {
         self.disconnect().await?;
@@ -312,7 +1139,7 @@ impl Connection {
 
     /// Check if the connection is connected
     pub fn is_connected(&self) -> bool {
-        self.connected
+        self.state.is_connected()
     }
 
     /// Get the connection ID
@@ -320,6 +1147,11 @@ impl Connection {
         &self.id
     }
 
+    /// Get the host and port of the server this connection talks to
+    pub fn server_address(&self) -> (&str, u16) {
+        (&self.options.host, self.options.port)
+    }
+
     /// Get the last activity timestamp
     pub fn last_activity(&self) -> Instant {
         self.last_activity
@@ -330,15 +1162,206 @@ impl Connection {
         self.last_activity.elapsed() > timeout
     }
 
-    // Native protocol implementations (placeholders)
-    async fn query_native(&mut self, _sql: &str) -> Result<QueryResult> {
-        // TODO: Implement native protocol query execution
-        Err(Error::Unsupported("Native protocol not yet implemented".to_string()))
+    /// Run a query over the configured transport
+    async fn dispatch_query(&mut self, sql: &str) -> Result<QueryResult> {
+        if self.options.use_websocket {
+            self.query_websocket(sql).await
+        } else if self.options.use_http {
+            self.query_http(sql).await
+        } else {
+            self.query_native(sql).await
+        }
     }
 
-    async fn insert_native(&mut self, _table: &str, _block: Block) -> Result<()> {
-        // TODO: Implement native protocol insert
-        Err(Error::Unsupported("Native protocol not yet implemented".to_string()))
+    /// Run a query over the attached transport and read its whole result
+    async fn query_native(&mut self, sql: &str) -> Result<QueryResult> {
+        self.send_query_native(sql).await?;
+        let mut blocks = Vec::new();
+        while let Some(block) = self.read_response_block().await? {
+            blocks.push(block);
+        }
+        Ok(self.finish_response(blocks))
+    }
+
+    /// Send the query packet of the query in flight
+    ///
+    /// Result blocks are decoded with the connection's current
+    /// [`DecodeOptions`]. Only an attached [`PacketTransport`] speaks the
+    /// native protocol so far.
+    async fn send_query_native(&mut self, sql: &str) -> Result<()> {
+        // TODO: Implement native protocol query execution over TCP
+        if self.transport.is_none() {
+            return Err(Error::Unsupported("Native protocol not yet implemented".to_string()));
+        }
+        let query_id = self.pending_query.clone().unwrap_or_default();
+        let packet = self.encode_packets(&[&self.client_query(sql, &query_id)])?;
+        self.write_native(&packet).await?;
+
+        self.keepalive = self
+            .options
+            .keepalive_ping_interval
+            .map(|interval| KeepAlive::new(interval).with_framing(Framing::Headers));
+        self.response = Some(PendingResponse {
+            sql: sql.to_string(),
+            decoder: BlockDecoder::new(self.decode_options),
+            metadata: None,
+            stats: QueryStats::new(0, 0, std::time::Duration::ZERO),
+            part_uuids: Vec::new(),
+            started: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Frame packets as the transport carries them, tracing and counting them
+    fn encode_packets(&self, packets: &[&dyn Packet]) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut writer = ProtocolWriter::new(&mut bytes)
+            .with_tracer(self.options.packet_tracer.clone())
+            .with_stats(self.stats.clone());
+        for packet in packets {
+            writer.write_packet(*packet)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Write framed packets to the transport within the write timeout
+    async fn write_native(&mut self, bytes: &[u8]) -> Result<()> {
+        let write_timeout = self.options.write_timeout;
+        let transport = self
+            .transport
+            .as_mut()
+            .ok_or_else(|| Error::Protocol("No transport to write the request to".to_string()))?;
+        match timeout(write_timeout, transport.write_all(bytes)).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(Error::Timeout(write_timeout)),
+        }
+    }
+
+    /// Read the next packet of the query in flight from the transport
+    async fn read_native_frame(&mut self) -> Result<Frame> {
+        let transport = self
+            .transport
+            .as_mut()
+            .ok_or_else(|| Error::Protocol("No transport to read the response from".to_string()))?;
+
+        let frame = read_frame_with_keepalive(transport, &mut self.frames, self.keepalive.as_mut()).await?;
+        self.last_activity = Instant::now();
+        Ok(frame)
+    }
+
+    /// Read packets of the query in flight up to its next data block
+    ///
+    /// Returns `None` once the server ends the stream. Progress and pushed
+    /// packets are consumed on the way; a server exception fails the read.
+    async fn read_response_block(&mut self) -> Result<Option<Block>> {
+        loop {
+            let Frame { packet_type, mut payload } = self.read_native_frame().await?;
+            let response = self
+                .response
+                .as_mut()
+                .ok_or_else(|| Error::Protocol(format!("Packet type {} received without a query", packet_type)))?;
+
+            match PacketType::from_u64(packet_type) {
+                Some(PacketType::ServerData) => {
+                    // A block cut short in a lenient mode leaves the rest of
+                    // its columns in `payload`, which is dropped here
+                    let data = ServerData::deserialize_with_decoder(&mut payload, &mut response.decoder)?;
+                    if data.block.column_count() == 0 {
+                        continue;
+                    }
+                    response.metadata.get_or_insert_with(|| {
+                        let (names, types) =
+                            data.block.columns.iter().map(|column| (column.name.clone(), column.type_name.clone())).unzip();
+                        QueryMetadata::new(names, types)
+                    });
+                    if self.state == ConnectionState::QueryInFlight {
+                        self.transition(ConnectionState::Streaming)?;
+                    }
+                    return Ok(Some(data.block));
+                }
+                Some(PacketType::ServerProgress) => {
+                    let progress = ServerProgress::deserialize(&mut payload)?;
+                    response.stats.rows_read += progress.rows;
+                    response.stats.bytes_read += progress.bytes;
+                    self.apply_progress(&progress)?;
+                }
+                Some(PacketType::ServerException) => {
+                    return Err(ServerException::deserialize(&mut payload)?.into());
+                }
+                Some(PacketType::ServerEndOfStream) => {
+                    let end = ServerEndOfStream::deserialize(&mut payload)?;
+                    if !end.is_success() {
+                        return Err(Error::QueryExecution(format!(
+                            "Query ended early ({}): {}",
+                            end.reason().as_str(),
+                            end.message().unwrap_or_default()
+                        )));
+                    }
+                    if let Some(stats) = end.final_stats() {
+                        response.stats.rows_read = stats.total_rows_read;
+                        response.stats.bytes_read = stats.total_bytes_read;
+                    }
+                    return Ok(None);
+                }
+                Some(PacketType::ServerPartUUIDs) => {
+                    response.part_uuids.push(ServerPartUUIDs::deserialize(&mut payload)?);
+                }
+                Some(PacketType::ServerTimezoneUpdate) => {
+                    self.apply_timezone_update(&ServerTimezoneUpdate::deserialize(&mut payload)?)?;
+                }
+                Some(PacketType::ServerProfileInfo | PacketType::ServerTotals | PacketType::ServerExtremes) => {
+                    tracing::trace!("Connection {} skipped packet type {}", self.id, packet_type);
+                }
+                _ if is_server_pushed(packet_type) => {
+                    self.handle_server_frame(Frame { packet_type, payload })?;
+                }
+                _ => {
+                    return Err(Error::Protocol(format!("Unexpected packet type {} in query response", packet_type)));
+                }
+            }
+        }
+    }
+
+    /// Build the result of the query in flight from its data blocks
+    ///
+    /// Values the decoder replaced under lenient validation are reported in
+    /// [`QueryResult::warnings`].
+    fn finish_response(&mut self, blocks: Vec<Block>) -> QueryResult {
+        let Some(mut response) = self.response.take() else {
+            return QueryResult::new(QueryMetadata::new(Vec::new(), Vec::new()), blocks, QueryStats::new(0, 0, std::time::Duration::ZERO));
+        };
+        let mut stats = response.stats;
+        stats.elapsed = response.started.elapsed();
+        let metadata = response.metadata.unwrap_or_else(|| QueryMetadata::new(Vec::new(), Vec::new()));
+        let metadata = match &self.pending_query {
+            Some(query_id) => metadata.with_query_id(query_id.clone()),
+            None => metadata,
+        };
+        let mut result = QueryResult::new(metadata, blocks, stats).with_warnings(response.decoder.take_warnings());
+        for packet in &response.part_uuids {
+            result.apply_part_uuids(packet);
+        }
+        result
+    }
+
+    /// Send an insert over the attached transport and read the server's answer
+    ///
+    /// The block follows the insert query in a data packet, and an empty data
+    /// packet ends the insert.
+    async fn insert_native(&mut self, table: &str, block: Block) -> Result<InsertResult> {
+        // TODO: Implement native protocol insert over TCP
+        if self.transport.is_none() {
+            return Err(Error::Unsupported("Native protocol not yet implemented".to_string()));
+        }
+        let sql = self.insert_sql(table);
+        self.send_query_native(&sql).await?;
+
+        let rows_written = block.row_count() as u64;
+        let data = self.encode_packets(&[&ClientData::new(block), &ClientData::new(Block::new())])?;
+        self.write_native(&data).await?;
+        while self.read_response_block().await?.is_some() {}
+        let response = self.finish_response(Vec::new());
+        Ok(InsertResult { rows_written, part_uuids: response.part_uuids, ..InsertResult::default() })
     }
 
     async fn ping_native(&mut self) -> Result<()> {
@@ -352,7 +1375,7 @@ impl Connection {
         Err(Error::Unsupported("WebSocket interface not yet implemented".to_string()))
     }
 
-    async fn insert_websocket(&mut self, _table: &str, _block: Block) -> Result<()> {
+    async fn insert_websocket(&mut self, _table: &str, _block: Block) -> Result<InsertResult> {
         // TODO: Implement WebSocket insert
         Err(Error::Unsupported("WebSocket interface not yet implemented".to_string()))
     }
@@ -368,7 +1391,7 @@ impl Connection {
         Err(Error::Unsupported("HTTP interface not yet implemented".to_string()))
     }
 
-    async fn insert_http(&mut self, _table: &str, _block: Block) -> Result<()> {
+    async fn insert_http(&mut self, _table: &str, _block: Block) -> Result<InsertResult> {
         // TODO: Implement HTTP insert
         Err(Error::Unsupported("HTTP interface not yet implemented".to_string()))
     }
@@ -379,6 +1402,14 @@ impl Connection {
     }
 }
 
+/// Create the decoder for packets read from a connection's transport
+fn frame_decoder(options: &crate::client::ClientOptions, stats: &ProtocolStats) -> FrameDecoder {
+    FrameDecoder::new(options.native_protocol_version as u64)
+        .with_framing(Framing::Headers)
+        .with_tracer(options.packet_tracer.clone())
+        .with_stats(stats.clone())
+}
+
 /// Helper function to extract string value from Value
 fn extract_string(value: &Value) -> Option<std::string::String> {
     match value {
@@ -402,9 +1433,46 @@ fn extract_string(value: &Value) -> Option<std::string::String> {
     }
 }
 
+/// Substitute `{name}` placeholders with parameter values
+fn bind_params(sql: &str, params: HashMap<String, Value>) -> String {
+    let mut final_sql = sql.to_string();
+    for (key, value) in params {
+        let placeholder = format!("{{{}}}", key);
+        let value_str = match value {
+            Value::String(s) => format!("'{}'", s),
+            Value::UInt8(v) => v.to_string(),
+            Value::UInt16(v) => v.to_string(),
+            Value::UInt32(v) => v.to_string(),
+            Value::UInt64(v) => v.to_string(),
+            Value::Int8(v) => v.to_string(),
+            Value::Int16(v) => v.to_string(),
+            Value::Int32(v) => v.to_string(),
+            Value::Int64(v) => v.to_string(),
+            Value::Float32(v) => v.to_string(),
+            Value::Float64(v) => v.to_string(),
+            Value::Date(d) => format!("'{}'", d.format("%Y-%m-%d")),
+            Value::DateTime(dt) => format!("'{}'", dt.format("%Y-%m-%d %H:%M:%S")),
+            Value::UUID(u) => format!("'{}'", u),
+            _ => format!("{:?}", value),
+        };
+        final_sql = final_sql.replace(&placeholder, &value_str);
+    }
+    final_sql
+}
+
+/// Get the name and type of each result column
+fn result_columns(metadata: &QueryMetadata) -> Vec<TableColumn> {
+    metadata
+        .column_names
+        .iter()
+        .zip(&metadata.column_types)
+        .map(|(name, type_name)| TableColumn::new(name, type_name))
+        .collect()
+}
+
 impl Drop for Connection {
     fn drop(&mut self) {
-        if self.connected {
+        if self.state != ConnectionState::Disconnected {
             // Try to disconnect, but don't block
             let _ = tokio::task::spawn(async move {
                 // This is a bit of a hack, but it's the best we can do in Drop
@@ -418,9 +1486,608 @@ impl std::fmt::Debug for Connection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Connection")
             .field("options", &self.options)
-            .field("connected", &self.connected)
+            .field("state", &self.state)
             .field("id", &self.id)
             .field("last_activity", &self.last_activity)
+            .field("pending_query", &self.pending_query)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientOptions, HttpSessionOptions};
+    use crate::error::ErrorCode;
+    use crate::protocol::{ColumnDescription, EndReason};
+    use crate::types::{Column, ColumnData};
+
+    async fn local_connection() -> (Connection, tokio::net::TcpListener) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ClientOptions::new().host("127.0.0.1").port(port);
+        (Connection::new(options), listener)
+    }
+
+    /// Connect over an in-memory transport whose server side has already sent `response`
+    ///
+    /// The server half is returned so that the connection does not see the end of the stream.
+    async fn replay_connection(options: ClientOptions, response: &[u8]) -> (Connection, tokio::io::DuplexStream) {
+        let (client, mut server) = tokio::io::duplex(1 << 16);
+        server.write_all(response).await.unwrap();
+        let mut conn = Connection::new(options).with_transport(client);
+        conn.connect().await.unwrap();
+        (conn, server)
+    }
+
+    /// Frame server packets as the transport carries them
+    fn packets(packets: &[&dyn Packet]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut writer = ProtocolWriter::new(&mut bytes);
+        for packet in packets {
+            writer.write_packet(*packet).unwrap();
+        }
+        bytes
+    }
+
+    /// Read the query packet the client sent to the server side of a transport
+    async fn read_client_query(server: &mut tokio::io::DuplexStream, revision: u64) -> ClientQuery {
+        use bytes::BytesMut;
+        use tokio::io::AsyncReadExt;
+
+        let mut header = [0u8; 16];
+        server.read_exact(&mut header).await.unwrap();
+        assert_eq!(PacketType::from_u64(u64::from_le_bytes(header[0..8].try_into().unwrap())), Some(PacketType::ClientQuery));
+        let mut body = vec![0u8; u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize];
+        server.read_exact(&mut body).await.unwrap();
+        ClientQuery::deserialize_with_revision(&mut BytesMut::from(&body[..]), revision).unwrap()
+    }
+
+    /// Frame a data packet around a block written by hand
+    fn raw_data_packet(block: &[u8]) -> Vec<u8> {
+        use bytes::{BufMut, BytesMut};
+
+        let mut body = BytesMut::new();
+        body.put_u8(0);
+        body.put_i32_le(-1);
+        body.put_u8(0);
+        body.put_u64_le(0);
+        body.put_u8(0);
+        body.put_slice(block);
+        let mut packet = PacketType::ServerData.to_u64().to_le_bytes().to_vec();
+        packet.extend((body.len() as u64).to_le_bytes());
+        packet.extend(body);
+        packet
+    }
+
+    /// Block of two rows with columns of the given types and raw data
+    fn raw_block(columns: &[(&str, &str, &[u8])]) -> Vec<u8> {
+        let mut block = (columns.len() as u64).to_le_bytes().to_vec();
+        block.extend(2u64.to_le_bytes());
+        for (name, type_name, data) in columns {
+            for s in [name, type_name] {
+                block.extend((s.len() as u64).to_le_bytes());
+                block.extend(s.as_bytes());
+            }
+            block.extend(*data);
+        }
+        block
+    }
+
+    #[test]
+    fn test_state_transitions() {
+        use ConnectionState::*;
+
+        assert!(Disconnected.can_transition_to(Idle));
+        assert!(Idle.can_transition_to(QueryInFlight));
+        assert!(QueryInFlight.can_transition_to(Streaming));
+        assert!(Streaming.can_transition_to(Idle));
+        assert!(Streaming.can_transition_to(Broken));
+        assert!(Broken.can_transition_to(Disconnected));
+
+        assert!(!Disconnected.can_transition_to(QueryInFlight));
+        assert!(!QueryInFlight.can_transition_to(QueryInFlight));
+        assert!(!Broken.can_transition_to(Idle));
+        assert!(!Idle.can_transition_to(Streaming));
+    }
+
+    #[tokio::test]
+    async fn test_connection_state_lifecycle() {
+        let (mut conn, _listener) = local_connection().await;
+        assert_eq!(conn.state(), ConnectionState::Disconnected);
+
+        conn.connect().await.unwrap();
+        assert_eq!(conn.state(), ConnectionState::Idle);
+
+        // A second query cannot start while one is in flight
+        conn.start_query().unwrap();
+        assert!(conn.start_query().is_err());
+
+        // Server-side failures keep the connection usable
+        let result: Result<()> = conn.finish_request(Err(Error::QueryExecution("bad query".to_string())));
+        assert!(result.is_err());
+        assert_eq!(conn.state(), ConnectionState::Idle);
+
+        // Wire-level failures poison it
+        conn.start_query().unwrap();
+        let _ = conn.finish_request::<()>(Err(Error::Protocol("unexpected packet".to_string())));
+        assert!(conn.is_broken());
+        assert!(!conn.is_connected());
+
+        conn.disconnect().await.unwrap();
+        assert_eq!(conn.state(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn test_connection_recovers_from_broken_state() {
+        let (mut conn, _listener) = local_connection().await;
+        conn.connect().await.unwrap();
+
+        conn.start_query().unwrap();
+        let _ = conn.finish_request::<()>(Err(Error::Protocol("unexpected packet".to_string())));
+        conn.ensure_ready().await.unwrap();
+        assert_eq!(conn.state(), ConnectionState::Idle);
+
+        // An abandoned query is cancelled before the next request
+        conn.start_query().unwrap();
+        conn.ensure_ready().await.unwrap();
+        assert_eq!(conn.state(), ConnectionState::Idle);
+        assert!(!conn.has_pending_query());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_sends_pending_query_id() {
+        use tokio::io::AsyncReadExt;
+
+        let (mut conn, listener) = local_connection().await;
+        conn.connect().await.unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        let query_id = conn.start_query().unwrap().to_string();
+        conn.cancel_pending_query().await.unwrap();
+
+        let mut packet = Vec::new();
+        server.read_to_end(&mut packet).await.unwrap();
+        assert!(packet.ends_with(query_id.as_bytes()));
+        assert!(!conn.has_pending_query());
+    }
+
+    #[tokio::test]
+    async fn test_query_error_carries_context() {
+        let (mut conn, _listener) = local_connection().await;
+
+        let err = conn.query("SELECT 1").await.unwrap_err();
+        let context = err.context_info().expect("query errors carry context");
+        assert_eq!(context.operation, "query");
+        assert_eq!(context.sql.as_deref(), Some("SELECT 1"));
+        assert!(context.host.as_deref().unwrap().starts_with("127.0.0.1:"));
+        assert!(context.query_id.is_some());
+        assert!(matches!(err.root(), Error::Unsupported(_)));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_statement_cache_on_connection() {
+        let (mut conn, _listener) = local_connection().await;
+        let sql = "SELECT * FROM t WHERE id = {id:UInt64} AND name = {name:String}";
+
+        let params = HashMap::from([("id".to_string(), Value::UInt64(1))]);
+        let err = conn.query_with_params(sql, params).await.unwrap_err();
+        assert!(matches!(err, Error::Configuration(ref m) if m.contains("name")));
+        assert_eq!(conn.prepare(sql).parameters.len(), 2);
+        assert_eq!(conn.statement_cache().stats().hits, 1);
+
+        // Long IN-lists are rewritten like with explicit settings
+        let sql = "SELECT * FROM t WHERE id IN {ids:Array(UInt64)}";
+        let ids = Value::Array((0..=DEFAULT_IN_LIST_THRESHOLD as u64).map(Value::UInt64).collect());
+        let err = conn.query_with_params(sql, HashMap::from([("ids".to_string(), ids)])).await.unwrap_err();
+        let sent = err.context_info().unwrap().sql.clone().unwrap();
+        assert!(sent.starts_with("SELECT * FROM t WHERE id IN (SELECT x FROM VALUES('x UInt64', 0, 1,"), "{}", sent);
+
+        conn.statement_cache_mut().set_table_schema("t", vec![TableColumn::new("id", "UInt64")]);
+        assert_eq!(conn.describe_table("t").await.unwrap()[0].name, "id");
+        conn.connect().await.unwrap();
+        conn.disconnect().await.unwrap();
+        assert!(conn.statement_cache().is_empty());
+        assert!(conn.describe_table("t").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_server_timezone_tracking() {
+        let (mut conn, _listener) = local_connection().await;
+        assert_eq!(conn.server_timezone(), None);
+        assert_eq!(conn.protocol_revision(), 54428);
+
+        let hello = ServerHello::new("ClickHouse", 24, 3, 0, 54466, 54466, "Europe/Berlin", "local");
+        conn.apply_server_hello(&hello).unwrap();
+        assert_eq!(conn.server_timezone(), Some(chrono_tz::Europe::Berlin));
+        assert_eq!(conn.client_query("SELECT 1", "q").revision, 54428);
+
+        conn.apply_timezone_update(&ServerTimezoneUpdate::new("Asia/Tokyo")).unwrap();
+        assert_eq!(conn.server_timezone(), Some(chrono_tz::Asia::Tokyo));
+        assert!(conn.apply_timezone_update(&ServerTimezoneUpdate::new("Nowhere/Land")).is_err());
+
+        conn.connect().await.unwrap();
+        conn.disconnect().await.unwrap();
+        assert_eq!(conn.server_timezone(), None);
+    }
+
+    #[tokio::test]
+    async fn test_handle_server_frame() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let options = ClientOptions::default().on_server_event(move |event: &ServerEvent| seen.lock().unwrap().push(event.clone()));
+        let mut conn = Connection::new(options);
+        let frame = |packet_type: PacketType, payload: &[u8]| Frame { packet_type: packet_type.to_u64(), payload: payload.into() };
+
+        assert!(conn.handle_server_frame(frame(PacketType::ServerTimezoneUpdate, b"\x0dEurope/Berlin")).unwrap().is_none());
+        assert_eq!(conn.server_timezone(), Some(chrono_tz::Europe::Berlin));
+        let log = frame(PacketType::ServerLog, b"\x00");
+        assert!(conn.handle_server_frame(log.clone()).unwrap().is_none());
+        let data = frame(PacketType::ServerEndOfStream, b"");
+        assert_eq!(conn.handle_server_frame(data.clone()).unwrap(), Some(data));
+        assert!(conn.handle_server_frame(frame(PacketType::ServerTimezoneUpdate, b"\x03Foo")).is_err());
+        assert_eq!(conn.server_timezone(), Some(chrono_tz::Europe::Berlin));
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                ServerEvent::TimezoneChanged { previous: None, timezone: chrono_tz::Europe::Berlin },
+                ServerEvent::Packet(log),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_table_columns() {
+        let (mut conn, _listener) = local_connection().await;
+        let packet = ServerTableColumns::new(vec![
+            ColumnDescription::new("id", "UInt64"),
+            ColumnDescription::new("day", "Date").default_expression("MATERIALIZED", "today()"),
+            ColumnDescription::new("name", "String"),
+        ]);
+        let block = Block::with_columns(vec![
+            Column::new("name", "String", ColumnData::String(vec!["a".to_string()])),
+            Column::new("id", "UInt64", ColumnData::UInt64(vec![1])),
+        ]);
+
+        let aligned = conn.apply_table_columns("t", packet.clone(), block).unwrap();
+        assert_eq!(aligned.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["id", "name"]);
+        assert!(packet.validate_block(&aligned).is_ok());
+        assert_eq!(conn.insert_columns(), Some(&packet));
+        assert_eq!(conn.describe_table("t").await.unwrap()[1].default_kind, "MATERIALIZED");
+    }
+
+    #[tokio::test]
+    async fn test_apply_progress_memory_bound() {
+        let (mut conn, _listener) = local_connection().await;
+        let mut progress = ServerProgress::new();
+        progress.memory_usage = 1 << 30;
+        assert!(conn.apply_progress(&progress).is_ok());
+
+        conn.apply_settings("SELECT 1", &QuerySettings::new().max_query_memory(1 << 20));
+        assert_eq!(conn.memory_watchdog().map(MemoryWatchdog::limit), Some(1 << 20));
+        assert!(matches!(conn.apply_progress(&progress), Err(Error::MemoryLimitExceeded(_))));
+
+        conn.reset_settings();
+        assert!(conn.memory_watchdog().is_none());
+        assert!(conn.apply_progress(&progress).is_ok());
+    }
+
+    #[test]
+    fn test_auto_block_size_from_cached_columns() {
+        let mut conn = Connection::new(ClientOptions::default());
+        let settings = QuerySettings::new().auto_block_size(1 << 20);
+        let sql = "SELECT id, name FROM t";
+        assert_eq!(conn.apply_settings(sql, &settings), sql);
+
+        conn.prepare(sql);
+        conn.statement_cache_mut()
+            .set_result_columns(sql, vec![TableColumn::new("id", "UInt64"), TableColumn::new("name", "String")]);
+        assert_eq!(conn.apply_settings(sql, &settings), "SELECT id, name FROM t SETTINGS max_block_size=26214");
+        let explicit = settings.max_threads(2).max_block_size(100);
+        assert_eq!(conn.apply_settings(sql, &explicit), "SELECT id, name FROM t SETTINGS max_block_size=100, max_threads=2");
+    }
+
+    #[tokio::test]
+    async fn test_per_query_compression() {
+        let (mut conn, _listener) = local_connection().await;
+        assert_eq!(conn.compression(), CompressionMethod::LZ4);
+        assert!(conn.client_query("SELECT 1", "q").is_compressed());
+
+        let settings = QuerySettings::new().disable_compression();
+        assert!(conn.query_with_settings("SELECT 1", settings.clone()).await.is_err());
+        assert!(conn.insert_with_settings("t", Block::new(), settings).await.is_err());
+        assert_eq!(conn.compression(), CompressionMethod::LZ4);
+
+        let conn = Connection::new(ClientOptions::new().disable_compression());
+        assert_eq!(conn.compression(), CompressionMethod::None);
+        assert!(!conn.client_query("SELECT 1", "q").is_compressed());
+        assert_eq!(
+            QuerySettings::new().compression(CompressionMethod::ZSTD).merge(&QuerySettings::new()).compression,
+            Some(CompressionMethod::ZSTD)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_sends_server_settings() {
+        use tokio::io::AsyncReadExt;
+
+        let response = packets(&[&ServerEndOfStream::new(EndReason::Normal)]);
+        let (mut conn, mut server) = replay_connection(ClientOptions::new(), &response).await;
+        let block = Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(vec![1, 2]))]);
+        let result = conn.insert_with_settings("t", block, QuerySettings::new().max_threads(2)).await.unwrap();
+        assert_eq!(result.rows_written, 2);
+        assert!(!result.query_id.is_empty());
+        assert_eq!(conn.insert_sql("t"), "INSERT INTO t FORMAT Native");
+
+        let query = read_client_query(&mut server, conn.protocol_revision()).await;
+        assert_eq!(query.sql, "INSERT INTO t SETTINGS max_threads=2 FORMAT Native");
+
+        // The block and the empty block ending the insert follow the query
+        let mut header = [0u8; 16];
+        server.read_exact(&mut header).await.unwrap();
+        assert_eq!(PacketType::from_u64(u64::from_le_bytes(header[0..8].try_into().unwrap())), Some(PacketType::ClientData));
+    }
+
+    #[tokio::test]
+    async fn test_insert_sends_deduplication_token() {
+        use crate::client::InsertOptions;
+
+        let response = packets(&[&ServerEndOfStream::new(EndReason::Normal)]);
+        let (mut conn, mut server) = replay_connection(ClientOptions::new(), &response).await;
+        let block = Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(vec![1]))]);
+        let settings = InsertOptions::new().deduplication_token("batch-'7'").settings_for(&block).unwrap();
+        conn.insert_with_settings("t", block, settings).await.unwrap();
+
+        let query = read_client_query(&mut server, conn.protocol_revision()).await;
+        assert_eq!(query.sql, "INSERT INTO t SETTINGS insert_deduplication_token='batch-\\'7\\'' FORMAT Native");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        let (mut conn, _listener) = local_connection().await;
+        assert!(conn.pipeline(&[]).await.unwrap().is_empty());
+
+        let queries = [
+            Query::new("SELECT 1").settings(QuerySettings::new().compression(CompressionMethod::ZSTD)),
+            Query::new("SELECT 2"),
+        ];
+        let err = conn.pipeline(&queries).await.unwrap_err();
+        let context = err.context_info().unwrap();
+        assert_eq!(context.operation, "pipeline");
+        assert_eq!(context.details.as_deref(), Some("query 1 of 2"));
+        assert!(matches!(err.root(), Error::Unsupported(_)));
+        assert_eq!(conn.compression(), CompressionMethod::LZ4);
+        assert_eq!(conn.state(), ConnectionState::Idle);
+
+        // A connection left mid-stream is recovered before the query runs
+        conn.start_query().unwrap();
+        let err = conn.pipeline(&queries[1..]).await.unwrap_err();
+        assert!(matches!(err.root(), Error::Unsupported(_)));
+        assert!(!conn.has_pending_query());
+    }
+
+    #[tokio::test]
+    async fn test_block_frame_checksums() {
+        let data = b"block data".repeat(10);
+        let mut conn = Connection::new(ClientOptions::new().verify_checksums(true));
+        let mut frame = conn.encode_block_frame(&data).unwrap();
+        assert_eq!(conn.decode_block_frame(&frame).unwrap(), data);
+
+        frame[0] ^= 0xff;
+        let err = conn.decode_block_frame(&frame).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::IntegrityCheck);
+        assert!(err.is_retryable());
+        assert_eq!(conn.state(), ConnectionState::Broken);
+
+        let mut conn = Connection::new(ClientOptions::new());
+        assert_eq!(conn.decode_block_frame(&frame).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_client_info_on_packets() {
+        let options = ClientOptions::new()
+            .os_user("etl")
+            .client_hostname("loader-1")
+            .quota_key("etl-quota")
+            .enable_http();
+        let conn = Connection::new(options);
+
+        let query = conn.client_query("SELECT 1", "q1");
+        assert_eq!(query.os_user.as_deref(), Some("etl"));
+        assert_eq!(query.client_hostname.as_deref(), Some("loader-1"));
+        assert_eq!(query.interface.as_deref(), Some("HTTP"));
+        assert_eq!(query.client_version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(query.quota_key.as_deref(), Some("etl-quota"));
+
+        let hello = conn.client_hello();
+        assert_eq!(hello.client_name, crate::protocol::constants::DEFAULT_CLIENT_NAME);
+        assert_eq!(hello.client_query_info_os_user.as_deref(), Some("etl"));
+        assert_eq!(hello.client_query_info_quota_key.as_deref(), Some("etl-quota"));
+        assert!(conn.http_session().is_none());
+
+        let options = ClientOptions::new().enable_http().http_session(HttpSessionOptions::new());
+        let (first, second) = (Connection::new(options.clone()), Connection::new(options));
+        assert_ne!(first.http_session().unwrap().id(), second.http_session().unwrap().id());
+    }
+
+    #[tokio::test]
+    async fn test_session_restore_policies() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ClientOptions::new().host("127.0.0.1").port(port);
+
+        let mut conn = Connection::new(options.clone().session_restore(SessionRestorePolicy::Fail));
+        conn.session.track("SET max_threads = 4");
+        assert!(matches!(conn.connect().await, Err(Error::SessionLost(_))));
+        assert!(conn.session().is_empty());
+
+        let mut conn = Connection::new(options.clone().session_restore(SessionRestorePolicy::Discard));
+        conn.session.track("SET ROLE analyst");
+        conn.connect().await.unwrap();
+        assert!(conn.session().is_empty());
+
+        // A failed restore is reported, then the state is dropped
+        let mut conn = Connection::new(options.session_restore(SessionRestorePolicy::Restore));
+        conn.session.track("SET max_threads = 4");
+        assert!(matches!(conn.connect().await, Err(Error::SessionLost(_))));
+        assert!(conn.session().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_decodes_with_settings() {
+        use crate::protocol::DecodeMode;
+
+        // `id UInt32` and `t Time`, which the client cannot decode
+        let mut response = raw_data_packet(&raw_block(&[
+            ("id", "UInt32", &[1, 0, 0, 0, 2, 0, 0, 0]),
+            ("t", "Time", &[16, 14, 0, 0, 0, 0, 0, 0]),
+        ]));
+        response.extend(packets(&[&ServerEndOfStream::new(EndReason::Normal)]));
+
+        let (mut conn, _server) = replay_connection(ClientOptions::new(), &response).await;
+        let err = conn.query("SELECT id, t FROM t").await.unwrap_err();
+        assert!(err.root().to_string().contains("Time"), "{}", err);
+
+        let (mut conn, _server) = replay_connection(ClientOptions::new(), &response).await;
+        let settings = QuerySettings::new().decode_mode(DecodeMode::SkipUnknownColumns);
+        let result = conn.query_with_settings("SELECT id, t FROM t", settings).await.unwrap();
+        assert_eq!(result.blocks.len(), 1);
+        assert_eq!(result.metadata.column_names, ["id"]);
+        assert_eq!(result.blocks[0].get_column("id").unwrap().get_value(1), Some(Value::UInt32(2)));
+        assert_eq!(conn.decode_options(), DecodeOptions::default());
+        assert_eq!(conn.state(), ConnectionState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_query_reports_validation_warnings() {
+        use crate::protocol::ValidationMode;
+
+        // The second string is not valid UTF-8
+        let strings = [&2u64.to_le_bytes()[..], b"ok", &2u64.to_le_bytes(), &[0x66, 0xff]].concat();
+        let mut response = raw_data_packet(&raw_block(&[("s", "String", &strings)]));
+        response.extend(packets(&[&ServerEndOfStream::new(EndReason::Normal)]));
+
+        let (mut conn, _server) = replay_connection(ClientOptions::new(), &response).await;
+        let err = conn.query("SELECT s FROM t").await.unwrap_err();
+        assert!(matches!(err.root(), Error::Protocol(m) if m.contains("UTF-8")), "{}", err);
+
+        let (mut conn, _server) = replay_connection(ClientOptions::new(), &response).await;
+        let settings = QuerySettings::new().validation(ValidationMode::Lenient);
+        let result = conn.query_with_settings("SELECT s FROM t", settings).await.unwrap();
+        assert_eq!(result.blocks[0].get_column("s").unwrap().get_value(1), Some(Value::String("f\u{fffd}".to_string())));
+        assert_eq!(result.warnings(), ["Column s: replaced 1 invalid UTF-8 strings"]);
+    }
+
+    #[tokio::test]
+    async fn test_query_over_result_limit_is_cancelled() {
+        use tokio::io::AsyncReadExt;
+
+        let block = Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(vec![1, 2, 3]))]);
+        let response = packets(&[
+            &ServerData::new(block.clone()),
+            &ServerData::new(block),
+            &ServerEndOfStream::new(EndReason::Normal),
+        ]);
+
+        let (mut conn, _server) = replay_connection(ClientOptions::new(), &response).await;
+        let result = conn.query_with_settings("SELECT id FROM t", QuerySettings::new().max_result_rows(6)).await.unwrap();
+        assert_eq!(result.row_count(), 6);
+
+        let (mut conn, mut server) = replay_connection(ClientOptions::new(), &response).await;
+        let err = conn.query_with_settings("SELECT id FROM t", QuerySettings::new().max_result_rows(5)).await.unwrap_err();
+        assert!(matches!(err.root(), Error::ResultTooLarge(m) if m.contains("max_result_rows is 5")), "{}", err);
+        assert_eq!(conn.state(), ConnectionState::Disconnected);
+
+        // The server saw the query, then its cancellation
+        let mut sent = Vec::new();
+        server.read_to_end(&mut sent).await.unwrap();
+        let mut packet_types = Vec::new();
+        let mut rest = sent.as_slice();
+        while rest.len() >= 16 {
+            packet_types.push(PacketType::from_u64(u64::from_le_bytes(rest[0..8].try_into().unwrap())));
+            rest = &rest[16 + u64::from_le_bytes(rest[8..16].try_into().unwrap()) as usize..];
+        }
+        assert_eq!(packet_types, [Some(PacketType::ClientQuery), Some(PacketType::ClientCancel)]);
+        assert!(sent.ends_with(err.context_info().unwrap().query_id.as_deref().unwrap().as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_query_over_memory_bound_is_cancelled() {
+        let block = Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(vec![1]))]);
+        let response = packets(&[
+            &ServerProgress::new().with_rows(1).with_memory_usage(1 << 20),
+            &ServerData::new(block),
+            &ServerProgress::new().with_rows(1).with_memory_usage(1 << 30),
+            &ServerEndOfStream::new(EndReason::Normal),
+        ]);
+
+        let (mut conn, _server) = replay_connection(ClientOptions::new(), &response).await;
+        let result = conn.query("SELECT id FROM t").await.unwrap();
+        assert_eq!(result.stats.rows_read, 2);
+
+        let (mut conn, _server) = replay_connection(ClientOptions::new(), &response).await;
+        let settings = QuerySettings::new().max_query_memory(1 << 24);
+        let err = conn.query_with_settings("SELECT id FROM t", settings).await.unwrap_err();
+        assert!(matches!(err.root(), Error::MemoryLimitExceeded(_)), "{}", err);
+        assert_eq!(conn.state(), ConnectionState::Disconnected);
+        assert!(conn.memory_watchdog().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_query_keeps_partial_blocks() {
+        use crate::protocol::DecodeMode;
+
+        // `doc JSON` has no known size, so the block is cut short before it
+        let mut response = raw_data_packet(&raw_block(&[
+            ("id", "UInt32", &[1, 0, 0, 0, 2, 0, 0, 0]),
+            ("t", "Time", &[16, 14, 0, 0, 0, 0, 0, 0]),
+            ("doc", "JSON", &[0xde, 0xad]),
+            ("n", "UInt8", &[1, 2]),
+        ]));
+        let block = Block::with_columns(vec![Column::new("id", "UInt32", ColumnData::UInt32(vec![3]))]);
+        response.extend(packets(&[&ServerData::new(block), &ServerEndOfStream::new(EndReason::Normal)]));
+
+        let (mut conn, _server) = replay_connection(ClientOptions::new(), &response).await;
+        let settings = QuerySettings::new().decode_mode(DecodeMode::Lenient);
+        let result = conn.query_with_settings("SELECT * FROM t", settings).await.unwrap();
+        assert_eq!(result.blocks.len(), 2);
+        assert_eq!(result.blocks[0].columns().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["id", "t"]);
+        assert_eq!(
+            result.blocks[0].get_column("t").unwrap().get_value(0),
+            Some(Value::Unsupported(3600i32.to_le_bytes().to_vec(), "Time".to_string()))
+        );
+        assert_eq!(result.blocks[1].get_column("id").unwrap().get_value(0), Some(Value::UInt32(3)));
+        assert_eq!(result.warnings().len(), 2);
+        assert!(result.warnings()[1].contains("dropped the 1 after it"), "{:?}", result.warnings());
+        assert_eq!(conn.state(), ConnectionState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_send_query_reads_block_by_block() {
+        let block = |id: u64| Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(vec![id]))]);
+        let (mut conn, mut server) = replay_connection(ClientOptions::new(), &packets(&[&ServerData::new(block(1))])).await;
+        conn.send_query("SELECT id FROM t", &QuerySettings::new().max_result_rows(10)).await.unwrap();
+
+        // The first block is handed out before the server has sent the rest
+        assert_eq!(conn.next_block().await.unwrap().unwrap().get_column("id").unwrap().get_value(0), Some(Value::UInt64(1)));
+        assert_eq!(conn.state(), ConnectionState::Streaming);
+        assert_eq!(conn.decode_options().max_rows, Some(10));
+
+        server
+            .write_all(&packets(&[&ServerData::new(block(2)), &ServerEndOfStream::new(EndReason::Normal)]))
+            .await
+            .unwrap();
+        assert!(conn.next_block().await.unwrap().is_some());
+        assert!(conn.next_block().await.unwrap().is_none());
+        assert_eq!(conn.state(), ConnectionState::Idle);
+        assert_eq!(conn.decode_options(), DecodeOptions::default());
+        assert!(conn.next_block().await.unwrap().is_none());
+
+        let (mut conn, _listener) = local_connection().await;
+        let err = conn.send_query("SELECT 1", &QuerySettings::new()).await.unwrap_err();
+        assert!(matches!(err.root(), Error::Unsupported(_)));
+        assert!(!conn.has_pending_query());
+    }
+}
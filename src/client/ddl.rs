@@ -0,0 +1,489 @@
+//! `CREATE TABLE` builder with per-column codecs, and `ALTER TABLE` for TTL and storage changes
+//!
+//! ```rust
+//! use clickhouse_rs::client::{Codec, ColumnDef, CreateTable};
+//!
+//! let sql = CreateTable::new("metrics")
+//!     .column(ColumnDef::new("ts", "DateTime").codec([Codec::Delta(Some(4)), Codec::Zstd(Some(3))]))
+//!     .column(ColumnDef::new("value", "Float64").codec([Codec::Gorilla, Codec::Lz4]))
+//!     .order_by("ts")
+//!     .build()
+//!     .unwrap();
+//! assert!(sql.contains("`ts` DateTime CODEC(Delta(4), ZSTD(3))"));
+//! ```
+
+use crate::error::{Error, Result};
+use crate::protocol::ProtocolVersion;
+use std::fmt;
+
+/// Column compression codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Store data uncompressed
+    None,
+    /// LZ4 compression
+    Lz4,
+    /// LZ4 HC compression with an optional level (1-12)
+    Lz4Hc(Option<u8>),
+    /// ZSTD compression with an optional level (1-22)
+    Zstd(Option<u8>),
+    /// Delta encoding with an optional value width in bytes (1, 2, 4 or 8)
+    Delta(Option<u8>),
+    /// Delta-of-delta encoding, suited to timestamps
+    DoubleDelta,
+    /// XOR encoding for slowly changing floats
+    Gorilla,
+    /// Bit packing that crops unused high bits
+    T64,
+    /// Floating point predictive encoding
+    Fpc,
+    /// Divide values by their greatest common divisor
+    Gcd,
+}
+
+impl Codec {
+    /// Check if the codec compresses bytes rather than transforming values
+    pub fn is_general_purpose(&self) -> bool {
+        matches!(self, Codec::None | Codec::Lz4 | Codec::Lz4Hc(_) | Codec::Zstd(_))
+    }
+
+    /// Get the oldest server version that supports the codec
+    pub fn min_server_version(&self) -> ProtocolVersion {
+        match self {
+            Codec::None | Codec::Lz4 | Codec::Lz4Hc(_) | Codec::Zstd(_) => ProtocolVersion::new(1, 0, 0, 0),
+            Codec::Delta(_) | Codec::DoubleDelta | Codec::Gorilla | Codec::T64 => ProtocolVersion::new(19, 10, 0, 0),
+            Codec::Fpc => ProtocolVersion::new(22, 9, 0, 0),
+            Codec::Gcd => ProtocolVersion::new(23, 9, 0, 0),
+        }
+    }
+
+    /// Check the codec parameters
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |msg: String| Err(Error::Configuration(msg));
+        match *self {
+            Codec::Lz4Hc(Some(level)) if !(1..=12).contains(&level) => {
+                invalid(format!("LZ4HC level must be between 1 and 12, got {}", level))
+            }
+            Codec::Zstd(Some(level)) if !(1..=22).contains(&level) => {
+                invalid(format!("ZSTD level must be between 1 and 22, got {}", level))
+            }
+            Codec::Delta(Some(width)) if !matches!(width, 1 | 2 | 4 | 8) => {
+                invalid(format!("Delta width must be 1, 2, 4 or 8 bytes, got {}", width))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let with_param = |f: &mut fmt::Formatter<'_>, name: &str, param: Option<u8>| match param {
+            Some(param) => write!(f, "{}({})", name, param),
+            None => write!(f, "{}", name),
+        };
+        match *self {
+            Codec::None => write!(f, "NONE"),
+            Codec::Lz4 => write!(f, "LZ4"),
+            Codec::Lz4Hc(level) => with_param(f, "LZ4HC", level),
+            Codec::Zstd(level) => with_param(f, "ZSTD", level),
+            Codec::Delta(width) => with_param(f, "Delta", width),
+            Codec::DoubleDelta => write!(f, "DoubleDelta"),
+            Codec::Gorilla => write!(f, "Gorilla"),
+            Codec::T64 => write!(f, "T64"),
+            Codec::Fpc => write!(f, "FPC"),
+            Codec::Gcd => write!(f, "GCD"),
+        }
+    }
+}
+
+/// Validate a codec chain as ClickHouse would
+///
+/// Value transforms must come before general purpose compression, and `NONE`
+/// cannot be combined with other codecs.
+pub fn validate_codecs(codecs: &[Codec]) -> Result<()> {
+    for codec in codecs {
+        codec.validate()?;
+    }
+    if codecs.len() > 1 && codecs.contains(&Codec::None) {
+        return Err(Error::Configuration("Codec NONE cannot be combined with other codecs".to_string()));
+    }
+    if let Some(pos) = codecs.iter().position(|c| c.is_general_purpose()) {
+        if let Some(transform) = codecs[pos..].iter().find(|c| !c.is_general_purpose()) {
+            return Err(Error::Configuration(format!(
+                "Codec {} must come before general purpose codec {}",
+                transform, codecs[pos]
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Column definition in a `CREATE TABLE` statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    /// Column name
+    pub name: String,
+    /// ClickHouse type
+    pub type_name: String,
+    /// DEFAULT expression
+    pub default: Option<String>,
+    /// Compression codecs, applied in order
+    pub codecs: Vec<Codec>,
+    /// Column comment
+    pub comment: Option<String>,
+}
+
+impl ColumnDef {
+    /// Create a column definition
+    pub fn new(name: impl Into<String>, type_name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            type_name: type_name.into(),
+            default: None,
+            codecs: Vec::new(),
+            comment: None,
+        }
+    }
+
+    /// Set the DEFAULT expression
+    pub fn default_expr(mut self, expr: impl Into<String>) -> Self {
+        self.default = Some(expr.into());
+        self
+    }
+
+    /// Set the compression codecs
+    pub fn codec(mut self, codecs: impl IntoIterator<Item = Codec>) -> Self {
+        self.codecs = codecs.into_iter().collect();
+        self
+    }
+
+    /// Set the column comment
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Render the column definition
+    pub fn to_sql(&self) -> Result<String> {
+        validate_codecs(&self.codecs)?;
+        let mut sql = format!("{} {}", quote_identifier(&self.name), self.type_name);
+        if let Some(default) = &self.default {
+            sql.push_str(&format!(" DEFAULT {}", default));
+        }
+        if !self.codecs.is_empty() {
+            let codecs: Vec<String> = self.codecs.iter().map(|c| c.to_string()).collect();
+            sql.push_str(&format!(" CODEC({})", codecs.join(", ")));
+        }
+        if let Some(comment) = &self.comment {
+            sql.push_str(&format!(" COMMENT '{}'", comment.replace('\\', "\\\\").replace('\'', "\\'")));
+        }
+        Ok(sql)
+    }
+}
+
+/// `CREATE TABLE` statement builder
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTable {
+    table: String,
+    if_not_exists: bool,
+    cluster: Option<String>,
+    columns: Vec<ColumnDef>,
+    engine: String,
+    order_by: Option<String>,
+    partition_by: Option<String>,
+    ttl: Option<String>,
+    settings: Vec<(String, String)>,
+}
+
+impl CreateTable {
+    /// Start a `MergeTree` table definition
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            if_not_exists: false,
+            cluster: None,
+            columns: Vec::new(),
+            engine: "MergeTree".to_string(),
+            order_by: None,
+            partition_by: None,
+            ttl: None,
+            settings: Vec::new(),
+        }
+    }
+
+    /// Add `IF NOT EXISTS`
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+
+    /// Create the table on every node of a cluster
+    pub fn on_cluster(mut self, cluster: impl Into<String>) -> Self {
+        self.cluster = Some(cluster.into());
+        self
+    }
+
+    /// Add a column
+    pub fn column(mut self, column: ColumnDef) -> Self {
+        self.columns.push(column);
+        self
+    }
+
+    /// Set the table engine, e.g. `ReplacingMergeTree(version)`
+    pub fn engine(mut self, engine: impl Into<String>) -> Self {
+        self.engine = engine.into();
+        self
+    }
+
+    /// Set the ORDER BY expression
+    pub fn order_by(mut self, expr: impl Into<String>) -> Self {
+        self.order_by = Some(expr.into());
+        self
+    }
+
+    /// Set the PARTITION BY expression
+    pub fn partition_by(mut self, expr: impl Into<String>) -> Self {
+        self.partition_by = Some(expr.into());
+        self
+    }
+
+    /// Set the table TTL expression
+    pub fn ttl(mut self, expr: impl Into<String>) -> Self {
+        self.ttl = Some(expr.into());
+        self
+    }
+
+    /// Add a table setting
+    pub fn setting(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.push((key.into(), value.into()));
+        self
+    }
+
+    /// Get the column definitions
+    pub fn columns(&self) -> &[ColumnDef] {
+        &self.columns
+    }
+
+    /// Check that every codec is supported by the given server version
+    pub fn validate_for(&self, server_version: &ProtocolVersion) -> Result<()> {
+        for column in &self.columns {
+            for codec in &column.codecs {
+                let required = codec.min_server_version();
+                if required > *server_version {
+                    return Err(Error::Unsupported(format!(
+                        "Codec {} on column '{}' requires server {}, connected to {}",
+                        codec,
+                        column.name,
+                        required.to_string(),
+                        server_version.to_string()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the statement
+    pub fn build(&self) -> Result<String> {
+        if self.columns.is_empty() {
+            return Err(Error::Configuration(format!("Table {} has no columns", self.table)));
+        }
+
+        let mut sql = String::from("CREATE TABLE ");
+        if self.if_not_exists {
+            sql.push_str("IF NOT EXISTS ");
+        }
+        sql.push_str(&quote_identifier(&self.table));
+        if let Some(cluster) = &self.cluster {
+            sql.push_str(&format!(" ON CLUSTER {}", quote_identifier(cluster)));
+        }
+
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| c.to_sql().map(|s| format!("    {}", s)))
+            .collect::<Result<Vec<_>>>()?;
+        sql.push_str(&format!("\n(\n{}\n)\nENGINE = {}", columns.join(",\n"), self.engine));
+
+        if let Some(partition_by) = &self.partition_by {
+            sql.push_str(&format!("\nPARTITION BY {}", partition_by));
+        }
+        let order_by = self.order_by.as_deref().unwrap_or("tuple()");
+        if self.engine.contains("MergeTree") {
+            sql.push_str(&format!("\nORDER BY {}", order_by));
+        }
+        if let Some(ttl) = &self.ttl {
+            sql.push_str(&format!("\nTTL {}", ttl));
+        }
+        if !self.settings.is_empty() {
+            let settings: Vec<String> = self.settings.iter().map(|(k, v)| format!("{} = {}", k, v)).collect();
+            sql.push_str(&format!("\nSETTINGS {}", settings.join(", ")));
+        }
+        Ok(sql)
+    }
+}
+
+/// `ALTER TABLE` builder for TTL and storage changes
+///
+/// ```rust
+/// use clickhouse_rs::client::AlterTable;
+///
+/// let sql = AlterTable::new("events")
+///     .modify_ttl("ts + INTERVAL 30 DAY DELETE")
+///     .modify_setting("storage_policy", "'tiered'")
+///     .build()
+///     .unwrap();
+/// assert_eq!(
+///     sql,
+///     "ALTER TABLE `events` MODIFY TTL ts + INTERVAL 30 DAY DELETE, MODIFY SETTING storage_policy = 'tiered'"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlterTable {
+    table: String,
+    cluster: Option<String>,
+    commands: Vec<String>,
+}
+
+impl AlterTable {
+    /// Start an `ALTER TABLE` statement
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            cluster: None,
+            commands: Vec::new(),
+        }
+    }
+
+    /// Alter the table on every node of a cluster
+    pub fn on_cluster(mut self, cluster: impl Into<String>) -> Self {
+        self.cluster = Some(cluster.into());
+        self
+    }
+
+    /// Replace the table TTL, e.g. `ts + INTERVAL 1 MONTH TO VOLUME 'cold'`
+    pub fn modify_ttl(self, expr: impl Into<String>) -> Self {
+        self.command(format!("MODIFY TTL {}", expr.into()))
+    }
+
+    /// Remove the table TTL
+    pub fn remove_ttl(self) -> Self {
+        self.command("REMOVE TTL".to_string())
+    }
+
+    /// Replace the TTL of a column
+    pub fn modify_column_ttl(self, column: &str, expr: impl Into<String>) -> Self {
+        self.command(format!("MODIFY COLUMN {} TTL {}", quote_identifier(column), expr.into()))
+    }
+
+    /// Remove the TTL of a column
+    pub fn remove_column_ttl(self, column: &str) -> Self {
+        self.command(format!("MODIFY COLUMN {} REMOVE TTL", quote_identifier(column)))
+    }
+
+    /// Apply the current TTL to existing parts, optionally of one partition only
+    pub fn materialize_ttl(self, partition: Option<&str>) -> Self {
+        match partition {
+            Some(partition) => self.command(format!("MATERIALIZE TTL IN PARTITION {}", partition)),
+            None => self.command("MATERIALIZE TTL".to_string()),
+        }
+    }
+
+    /// Change a table setting, such as `storage_policy`
+    pub fn modify_setting(self, key: &str, value: impl Into<String>) -> Self {
+        self.command(format!("MODIFY SETTING {} = {}", key, value.into()))
+    }
+
+    fn command(mut self, command: String) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Render the statement
+    pub fn build(&self) -> Result<String> {
+        if self.commands.is_empty() {
+            return Err(Error::Configuration(format!("ALTER TABLE {} has no commands", self.table)));
+        }
+        let mut sql = format!("ALTER TABLE {}", quote_identifier(&self.table));
+        if let Some(cluster) = &self.cluster {
+            sql.push_str(&format!(" ON CLUSTER {}", quote_identifier(cluster)));
+        }
+        sql.push(' ');
+        sql.push_str(&self.commands.join(", "));
+        Ok(sql)
+    }
+}
+
+/// Quote a possibly database-qualified identifier
+pub(crate) fn quote_identifier(name: &str) -> String {
+    name.split('.')
+        .map(|part| format!("`{}`", part.trim_matches('`').replace('`', "\\`")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_table_with_codecs() {
+        let sql = CreateTable::new("db.metrics")
+            .if_not_exists()
+            .on_cluster("prod")
+            .column(ColumnDef::new("ts", "DateTime").codec([Codec::DoubleDelta, Codec::Zstd(Some(3))]))
+            .column(ColumnDef::new("value", "Float64").codec([Codec::Gorilla]).comment("it's a gauge"))
+            .column(ColumnDef::new("host", "LowCardinality(String)").default_expr("''"))
+            .partition_by("toYYYYMM(ts)")
+            .order_by("(host, ts)")
+            .ttl("ts + INTERVAL 30 DAY")
+            .setting("index_granularity", "8192")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            sql,
+            "CREATE TABLE IF NOT EXISTS `db`.`metrics` ON CLUSTER `prod`\n(\n    \
+             `ts` DateTime CODEC(DoubleDelta, ZSTD(3)),\n    \
+             `value` Float64 CODEC(Gorilla) COMMENT 'it\\'s a gauge',\n    \
+             `host` LowCardinality(String) DEFAULT ''\n)\n\
+             ENGINE = MergeTree\nPARTITION BY toYYYYMM(ts)\nORDER BY (host, ts)\n\
+             TTL ts + INTERVAL 30 DAY\nSETTINGS index_granularity = 8192"
+        );
+    }
+
+    #[test]
+    fn test_codec_chain_validation() {
+        assert!(validate_codecs(&[Codec::Delta(Some(8)), Codec::Lz4]).is_ok());
+        assert!(validate_codecs(&[Codec::Lz4, Codec::Delta(None)]).is_err());
+        assert!(validate_codecs(&[Codec::None, Codec::Lz4]).is_err());
+        assert!(validate_codecs(&[Codec::Zstd(Some(30))]).is_err());
+        assert!(validate_codecs(&[Codec::Delta(Some(3))]).is_err());
+        assert!(CreateTable::new("t").build().is_err());
+    }
+
+    #[test]
+    fn test_codec_server_version() {
+        let table = CreateTable::new("t").column(ColumnDef::new("n", "UInt64").codec([Codec::Gcd, Codec::Lz4]));
+        assert!(table.validate_for(&ProtocolVersion::new(23, 8, 1, 1)).is_err());
+        assert!(table.validate_for(&ProtocolVersion::new(23, 9, 1, 1)).is_ok());
+    }
+
+    #[test]
+    fn test_alter_table_ttl() {
+        let sql = AlterTable::new("db.events")
+            .on_cluster("main")
+            .modify_column_ttl("payload", "ts + INTERVAL 7 DAY")
+            .remove_column_ttl("debug")
+            .materialize_ttl(Some("202405"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "ALTER TABLE `db`.`events` ON CLUSTER `main` MODIFY COLUMN `payload` TTL ts + INTERVAL 7 DAY, \
+             MODIFY COLUMN `debug` REMOVE TTL, MATERIALIZE TTL IN PARTITION 202405"
+        );
+        assert_eq!(AlterTable::new("t").remove_ttl().build().unwrap(), "ALTER TABLE `t` REMOVE TTL");
+        assert!(AlterTable::new("t").build().is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Query draining for graceful shutdown and rolling restarts
+
+use crate::error::{Error, Result};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Tracks in-flight operations and refuses new ones while draining
+#[derive(Debug, Default)]
+pub struct DrainController {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+impl DrainController {
+    /// Create a controller that accepts operations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new operation, failing with [`Error::Draining`] while draining
+    pub fn enter(self: &Arc<Self>) -> Result<InFlightGuard> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        if self.draining.load(Ordering::SeqCst) {
+            self.leave();
+            return Err(Error::Draining);
+        }
+        Ok(InFlightGuard {
+            controller: Arc::clone(self),
+        })
+    }
+
+    /// Stop accepting new operations
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    /// Accept new operations again
+    pub fn resume(&self) {
+        self.draining.store(false, Ordering::SeqCst);
+    }
+
+    /// Check if new operations are being refused
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Get the number of operations still running
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Wait until no operations are running
+    pub async fn wait_idle(&self, timeout: Duration) -> Result<()> {
+        let wait = async {
+            loop {
+                let idle = self.idle.notified();
+                if self.in_flight() == 0 {
+                    return;
+                }
+                idle.await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.map_err(|_| {
+            tracing::warn!("{} operations still running after {:?}", self.in_flight(), timeout);
+            Error::Timeout(timeout)
+        })
+    }
+
+    fn leave(&self) {
+        if self.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.idle.notify_waiters();
+        }
+    }
+}
+
+/// Marks an operation as in flight until dropped
+#[derive(Debug)]
+pub struct InFlightGuard {
+    controller: Arc<DrainController>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.controller.leave();
+    }
+}
+
+/// Resolve when the process is asked to stop (SIGTERM, SIGINT or Ctrl-C)
+///
+/// Pass this to [`Client::shutdown_on`](crate::Client::shutdown_on) to drain
+/// queries during a rolling restart.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Failed to install SIGTERM handler: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_refuses_new_operations() {
+        let controller = Arc::new(DrainController::new());
+        let guard = controller.enter().unwrap();
+        assert_eq!(controller.in_flight(), 1);
+
+        controller.start_draining();
+        assert!(matches!(controller.enter(), Err(Error::Draining)));
+        assert_eq!(controller.in_flight(), 1);
+
+        drop(guard);
+        assert_eq!(controller.in_flight(), 0);
+
+        controller.resume();
+        assert!(controller.enter().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_idle_waits_for_in_flight() {
+        let controller = Arc::new(DrainController::new());
+        let guard = controller.enter().unwrap();
+        controller.start_draining();
+
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(guard);
+        });
+
+        controller.wait_idle(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(controller.in_flight(), 0);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_idle_timeout() {
+        let controller = Arc::new(DrainController::new());
+        let _guard = controller.enter().unwrap();
+
+        let result = controller.wait_idle(Duration::from_millis(10)).await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+    }
+}
@@ -0,0 +1,287 @@
+//! Connection strings with failover groups
+//!
+//! [`ClientOptions::from_dsn`] accepts two forms:
+//!
+//! - a URL, `clickhouse://user:pass@a:9000,b:9000/db?fallback=c:9000`
+//! - key-value pairs, `host=a:9000,b:9000;fallback=c:9000;database=db`
+//!
+//! `host` (or the URL authority) lists the primary servers. Each `fallback`
+//! adds a group that is only used while every server of the groups before it
+//! is unavailable. Groups map to [`ServerInfo::priority`], 0 being the
+//! primaries.
+
+use crate::client::options::{CompressionMethod, LoadBalancingStrategy, ServerInfo};
+use crate::client::ClientOptions;
+use crate::error::{Error, Result};
+use std::str::FromStr;
+
+/// Default ports by scheme
+const NATIVE_PORT: u16 = 9000;
+const HTTP_PORT: u16 = 8123;
+const HTTPS_PORT: u16 = 8443;
+
+impl ClientOptions {
+    /// Parse a connection string into client options
+    ///
+    /// Hosts without a port use the default port of the scheme. With more than
+    /// one host, load balancing and failover are enabled.
+    pub fn from_dsn(dsn: &str) -> Result<Self> {
+        let mut parser = DsnParser::new();
+        match dsn.split_once("://") {
+            Some((scheme, rest)) => parser.parse_url(scheme, rest)?,
+            None => {
+                for pair in dsn.split(';').map(str::trim).filter(|pair| !pair.is_empty()) {
+                    let (key, value) = pair
+                        .split_once('=')
+                        .ok_or_else(|| Error::Configuration(format!("Expected key=value in DSN, got '{}'", pair)))?;
+                    parser.param(key.trim(), &percent_decode(value.trim())?)?;
+                }
+            }
+        }
+        parser.finish()
+    }
+}
+
+impl FromStr for ClientOptions {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_dsn(s)
+    }
+}
+
+struct DsnParser {
+    options: ClientOptions,
+    default_port: u16,
+    groups: Vec<Vec<String>>,
+}
+
+impl DsnParser {
+    fn new() -> Self {
+        Self {
+            options: ClientOptions::new(),
+            default_port: NATIVE_PORT,
+            groups: vec![Vec::new()],
+        }
+    }
+
+    fn parse_url(&mut self, scheme: &str, rest: &str) -> Result<()> {
+        match scheme.to_ascii_lowercase().as_str() {
+            "clickhouse" | "tcp" => {}
+            "http" => {
+                self.options.use_http = true;
+                self.default_port = HTTP_PORT;
+            }
+            "https" => {
+                self.options.use_http = true;
+                self.options.use_tls = true;
+                self.default_port = HTTPS_PORT;
+            }
+            other => return Err(Error::Configuration(format!("Unsupported DSN scheme '{}'", other))),
+        }
+
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+        let (authority, database) = match rest.split_once('/') {
+            Some((authority, database)) => (authority, database.trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        let hosts = match authority.rsplit_once('@') {
+            Some((userinfo, hosts)) => {
+                let (user, password) = match userinfo.split_once(':') {
+                    Some((user, password)) => (user, Some(password)),
+                    None => (userinfo, None),
+                };
+                self.param("user", &percent_decode(user)?)?;
+                if let Some(password) = password {
+                    self.param("password", &percent_decode(password)?)?;
+                }
+                hosts
+            }
+            None => authority,
+        };
+        if !hosts.is_empty() {
+            self.param("host", hosts)?;
+        }
+        if !database.is_empty() {
+            self.param("database", &percent_decode(database)?)?;
+        }
+
+        for (key, value) in url::form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            self.param(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    fn param(&mut self, key: &str, value: &str) -> Result<()> {
+        let hosts = || value.split(',').map(str::trim).filter(|h| !h.is_empty()).map(str::to_string);
+        match key {
+            "host" | "hosts" => self.groups[0].extend(hosts()),
+            "fallback" => self.groups.push(hosts().collect()),
+            "database" | "db" => self.options.database = value.to_string(),
+            "user" | "username" => self.options.username = value.to_string(),
+            "password" => self.options.password = value.to_string().into(),
+            "secure" => self.options.use_tls = parse_bool(key, value)?,
+            "compression" => {
+                let method = [
+                    CompressionMethod::None,
+                    CompressionMethod::LZ4,
+                    CompressionMethod::ZSTD,
+                    CompressionMethod::GZIP,
+                    CompressionMethod::BZIP2,
+                    CompressionMethod::XZ,
+                ]
+                .into_iter()
+                .find(|method| method.as_str().eq_ignore_ascii_case(value))
+                .ok_or_else(|| Error::Configuration(format!("Unknown compression method '{}'", value)))?;
+                self.options.use_compression = method != CompressionMethod::None;
+                self.options.compression = method;
+            }
+            "strategy" => {
+                self.options.load_balancing_strategy = [
+                    LoadBalancingStrategy::RoundRobin,
+                    LoadBalancingStrategy::WeightedRoundRobin,
+                    LoadBalancingStrategy::LeastConnections,
+                    LoadBalancingStrategy::Random,
+                    LoadBalancingStrategy::LatencyWeighted,
+                    LoadBalancingStrategy::ZoneAware,
+                ]
+                .into_iter()
+                .find(|strategy| strategy.as_str() == value)
+                .ok_or_else(|| Error::Configuration(format!("Unknown load balancing strategy '{}'", value)))?;
+            }
+            "zone" => self.options.local_zone = Some(value.to_string()),
+            _ => return Err(Error::Configuration(format!("Unknown DSN parameter '{}'", key))),
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<ClientOptions> {
+        if self.groups.iter().skip(1).any(Vec::is_empty) {
+            return Err(Error::Configuration("Fallback group has no hosts".to_string()));
+        }
+        if self.groups[0].is_empty() {
+            if self.groups.len() > 1 {
+                return Err(Error::Configuration("Fallback hosts require a primary host".to_string()));
+            }
+            self.groups[0].push(self.options.host.clone());
+        }
+
+        let mut servers = Vec::new();
+        for (priority, group) in self.groups.iter().enumerate() {
+            for host in group {
+                let (host, port) = split_host_port(host, self.default_port)?;
+                servers.push(ServerInfo::new(host, port).priority(priority as u32));
+            }
+        }
+
+        let mut options = self.options;
+        options.host = servers[0].host.clone();
+        options.port = servers[0].port;
+        if servers.len() > 1 {
+            options.servers = servers;
+            options.use_load_balancing = true;
+            options.use_failover = true;
+        }
+        Ok(options)
+    }
+}
+
+/// Split `host[:port]`, accepting bracketed IPv6 addresses
+fn split_host_port(address: &str, default_port: u16) -> Result<(String, u16)> {
+    let invalid = || Error::Configuration(format!("Invalid host '{}' in DSN", address));
+    let (host, port) = if let Some(rest) = address.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+        match rest {
+            "" => (host, None),
+            _ => (host, Some(rest.strip_prefix(':').ok_or_else(invalid)?)),
+        }
+    } else {
+        match address.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        }
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    let port = match port {
+        Some(port) => port.parse().map_err(|_| invalid())?,
+        None => default_port,
+    };
+    Ok((host.to_string(), port))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(Error::Configuration(format!("Invalid boolean '{}' for DSN parameter '{}'", value, key))),
+    }
+}
+
+/// Decode `%XX` escapes in a DSN component
+fn percent_decode(value: &str) -> Result<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| Error::Configuration(format!("Invalid percent escape in '{}'", value)))?;
+            decoded.push(hex);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| Error::Configuration(format!("Invalid UTF-8 in '{}'", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dsn_url_with_fallback_groups() {
+        let options = ClientOptions::from_dsn(
+            "clickhouse://bob:p%40ss@a:9001,b/analytics?fallback=c:9000&fallback=[::1]&compression=zstd",
+        )
+        .unwrap();
+        assert_eq!((options.host.as_str(), options.port), ("a", 9001));
+        assert_eq!(options.username, "bob");
+        assert_eq!(options.password.expose(), "p@ss");
+        assert_eq!(options.database, "analytics");
+        assert_eq!(options.compression, CompressionMethod::ZSTD);
+        assert!(options.use_load_balancing && options.use_failover);
+        let servers: Vec<_> = options
+            .servers
+            .iter()
+            .map(|s| (s.host.as_str(), s.port, s.priority))
+            .collect();
+        assert_eq!(servers, [("a", 9001, 0), ("b", 9000, 0), ("c", 9000, 1), ("::1", 9000, 2)]);
+
+        let single: ClientOptions = "https://example.com/".parse().unwrap();
+        assert!(single.use_http && single.use_tls && single.servers.is_empty());
+        assert_eq!(single.port, 8443);
+    }
+
+    #[test]
+    fn test_dsn_key_value_form() {
+        let options = ClientOptions::from_dsn("host=a:9000,b:9000;fallback=c:9000;database=db;secure=1").unwrap();
+        assert!(options.use_tls);
+        assert_eq!(options.database, "db");
+        let priorities: Vec<_> = options.servers.iter().map(|s| s.priority).collect();
+        assert_eq!(priorities, [0, 0, 1]);
+
+        assert!(ClientOptions::from_dsn("fallback=c:9000").is_err());
+        assert!(ClientOptions::from_dsn("host=a;colour=red").is_err());
+        assert!(ClientOptions::from_dsn("host=a:port").is_err());
+        assert!(ClientOptions::from_dsn("redis://a").is_err());
+    }
+}
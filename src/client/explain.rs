@@ -0,0 +1,276 @@
+//! Structured `EXPLAIN` output
+//!
+//! ClickHouse renders plans, pipelines and ASTs as indented text. The parser
+//! here turns that text into a tree of [`PlanNode`]s, and the tabular output
+//! of `EXPLAIN ESTIMATE` into one node per table with its estimated rows.
+
+use crate::client::system_tables::RowReader;
+use crate::client::QueryResult;
+use crate::error::{Error, Result};
+use crate::protocol::ServerQueryPlan;
+use crate::types::Value;
+
+/// Kind of `EXPLAIN` query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainKind {
+    /// Query plan steps
+    Plan,
+    /// Processor pipeline
+    Pipeline,
+    /// Rows, marks and parts to be read per table
+    Estimate,
+    /// Abstract syntax tree
+    Ast,
+}
+
+impl ExplainKind {
+    /// Get the `EXPLAIN` keyword for the kind
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ExplainKind::Plan => "PLAN",
+            ExplainKind::Pipeline => "PIPELINE",
+            ExplainKind::Estimate => "ESTIMATE",
+            ExplainKind::Ast => "AST",
+        }
+    }
+
+    /// Build the `EXPLAIN` statement for a query
+    pub fn statement(&self, sql: &str) -> String {
+        format!("EXPLAIN {} {}", self.keyword(), sql.trim().trim_end_matches(';'))
+    }
+}
+
+/// A step in an explained query
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PlanNode {
+    /// Step name, e.g. `ReadFromMergeTree` or `ExpressionTransform`
+    pub name: String,
+    /// Text in parentheses after the name
+    pub description: Option<String>,
+    /// Estimated rows to read, when the server reports it
+    pub estimated_rows: Option<u64>,
+    /// Estimated marks to read, when the server reports it
+    pub estimated_marks: Option<u64>,
+    /// Estimated parts to read, when the server reports it
+    pub estimated_parts: Option<u64>,
+    /// Nested steps
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    /// Create a node with the given name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Visit this node and all descendants depth first
+    pub fn walk(&self) -> Vec<&PlanNode> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.walk());
+        }
+        nodes
+    }
+
+    fn from_line(text: &str) -> Self {
+        let text = text.trim();
+        if let Some(inner) = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            return Self::new(inner);
+        }
+        match text.split_once(" (") {
+            Some((name, rest)) if rest.ends_with(')') => Self {
+                description: Some(rest[..rest.len() - 1].to_string()),
+                ..Self::new(name)
+            },
+            _ => Self::new(text),
+        }
+    }
+}
+
+/// Parsed `EXPLAIN` output
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    /// Kind of explanation
+    pub kind: ExplainKind,
+    /// Top-level steps
+    pub nodes: Vec<PlanNode>,
+    /// Unparsed output lines
+    pub raw: Vec<String>,
+}
+
+impl QueryPlan {
+    /// Parse indented `EXPLAIN` text lines into a tree
+    pub fn from_lines(kind: ExplainKind, lines: Vec<String>) -> Self {
+        let parsed: Vec<(usize, &str)> = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| (line.len() - line.trim_start().len(), line.as_str()))
+            .collect();
+        let mut pos = 0;
+        let nodes = build_tree(&parsed, &mut pos, 0);
+        Self { kind, nodes, raw: lines }
+    }
+
+    /// Parse a plan sent by the server in a query plan packet
+    pub fn from_packet(packet: &ServerQueryPlan) -> Self {
+        Self::from_lines(ExplainKind::Plan, packet.lines.clone())
+    }
+
+    /// Parse the result of an `EXPLAIN` query
+    pub fn from_result(kind: ExplainKind, result: &QueryResult) -> Result<Self> {
+        if kind == ExplainKind::Estimate {
+            return Self::from_estimate(result);
+        }
+
+        let mut lines = Vec::with_capacity(result.row_count());
+        for row in result.rows() {
+            match row.get(0) {
+                Some(Some(Value::String(line))) => lines.push(line.clone()),
+                _ => return Err(Error::InvalidData("EXPLAIN output is not a single string column".to_string())),
+            }
+        }
+        Ok(Self::from_lines(kind, lines))
+    }
+
+    fn from_estimate(result: &QueryResult) -> Result<Self> {
+        let mut nodes = Vec::with_capacity(result.row_count());
+        let mut raw = Vec::with_capacity(result.row_count());
+        for block in &result.blocks {
+            for index in 0..block.row_count {
+                let row = RowReader::new(block, index);
+                let table = format!("{}.{}", row.string("database")?, row.string("table")?);
+                let node = PlanNode {
+                    estimated_rows: Some(row.u64("rows")?),
+                    estimated_marks: Some(row.u64("marks")?),
+                    estimated_parts: Some(row.u64("parts")?),
+                    ..PlanNode::new(table)
+                };
+                raw.push(format!(
+                    "{}\t{}\t{}\t{}",
+                    node.name,
+                    node.estimated_parts.unwrap_or_default(),
+                    node.estimated_rows.unwrap_or_default(),
+                    node.estimated_marks.unwrap_or_default()
+                ));
+                nodes.push(node);
+            }
+        }
+        Ok(Self {
+            kind: ExplainKind::Estimate,
+            nodes,
+            raw,
+        })
+    }
+
+    /// Get every step in the plan, depth first
+    pub fn steps(&self) -> Vec<&PlanNode> {
+        self.nodes.iter().flat_map(|node| node.walk()).collect()
+    }
+
+    /// Find the first step with the given name
+    pub fn find(&self, name: &str) -> Option<&PlanNode> {
+        self.steps().into_iter().find(|node| node.name == name)
+    }
+
+    /// Sum the estimated rows over all steps that report them
+    pub fn total_estimated_rows(&self) -> Option<u64> {
+        self.steps()
+            .iter()
+            .filter_map(|node| node.estimated_rows)
+            .reduce(|a, b| a + b)
+    }
+}
+
+fn build_tree(lines: &[(usize, &str)], pos: &mut usize, indent: usize) -> Vec<PlanNode> {
+    let mut nodes = Vec::new();
+    while let Some(&(line_indent, text)) = lines.get(*pos) {
+        if line_indent < indent {
+            break;
+        }
+        *pos += 1;
+        let mut node = PlanNode::from_line(text);
+        if let Some(&(child_indent, _)) = lines.get(*pos) {
+            if child_indent > line_indent {
+                node.children = build_tree(lines, pos, child_indent);
+            }
+        }
+        nodes.push(node);
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{QueryMetadata, QueryStats};
+    use crate::types::{Block, Column, ColumnData};
+    use std::time::Duration;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_plan_tree() {
+        let plan = QueryPlan::from_lines(
+            ExplainKind::Plan,
+            lines(
+                "Expression ((Projection + Before ORDER BY))\n  \
+                 Sorting (Sorting for ORDER BY)\n    \
+                 Filter (WHERE)\n      \
+                 ReadFromMergeTree (default.events)\n  \
+                 Limit",
+            ),
+        );
+
+        assert_eq!(plan.nodes.len(), 1);
+        let root = &plan.nodes[0];
+        assert_eq!(root.name, "Expression");
+        assert_eq!(root.description.as_deref(), Some("(Projection + Before ORDER BY)"));
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.children[1].name, "Limit");
+        assert_eq!(plan.steps().len(), 5);
+        assert_eq!(plan.find("ReadFromMergeTree").unwrap().description.as_deref(), Some("default.events"));
+    }
+
+    #[test]
+    fn test_parse_pipeline_and_ast() {
+        let pipeline = QueryPlan::from_lines(ExplainKind::Pipeline, lines("(Expression)\nExpressionTransform\n  (ReadFromMergeTree)"));
+        assert_eq!(pipeline.nodes[0].name, "Expression");
+        assert_eq!(pipeline.nodes[1].children[0].name, "ReadFromMergeTree");
+
+        let ast = QueryPlan::from_lines(ExplainKind::Ast, lines("SelectWithUnionQuery (children 1)\n ExpressionList (children 1)"));
+        assert_eq!(ast.nodes[0].description.as_deref(), Some("children 1"));
+        assert_eq!(ast.nodes[0].children[0].name, "ExpressionList");
+    }
+
+    #[test]
+    fn test_parse_estimate() {
+        let mut block = Block::new();
+        let strings = |v: &str| ColumnData::String(vec![v.to_string()]);
+        block.add_column("database", Column::new("database", "String", strings("default")));
+        block.add_column("table", Column::new("table", "String", strings("events")));
+        block.add_column("parts", Column::new("parts", "UInt64", ColumnData::UInt64(vec![2])));
+        block.add_column("rows", Column::new("rows", "UInt64", ColumnData::UInt64(vec![16384])));
+        block.add_column("marks", Column::new("marks", "UInt64", ColumnData::UInt64(vec![2])));
+        let result = QueryResult::new(
+            QueryMetadata::new(Vec::new(), Vec::new()),
+            vec![block],
+            QueryStats::new(0, 0, Duration::ZERO),
+        );
+
+        let plan = QueryPlan::from_result(ExplainKind::Estimate, &result).unwrap();
+        assert_eq!(plan.nodes[0].name, "default.events");
+        assert_eq!(plan.total_estimated_rows(), Some(16384));
+    }
+
+    #[test]
+    fn test_explain_statement() {
+        assert_eq!(ExplainKind::Pipeline.statement("SELECT 1;"), "EXPLAIN PIPELINE SELECT 1");
+        let packet = ServerQueryPlan::new(lines("ReadFromStorage (SystemNumbers)"));
+        assert_eq!(QueryPlan::from_packet(&packet).nodes[0].name, "ReadFromStorage");
+    }
+}
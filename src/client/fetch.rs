@@ -0,0 +1,120 @@
+//! Typed fetches into scalars and tuples
+//!
+//! ```rust
+//! # async fn example(client: clickhouse_rs::Client) -> clickhouse_rs::error::Result<()> {
+//! let users: Vec<(u64, String, f64)> = client.fetch_all("SELECT id, name, score FROM users").await?;
+//! let name: Option<String> = client.fetch_optional("SELECT name FROM users WHERE id = 42").await?;
+//! let total = client.fetch_scalar::<u64>("SELECT count() FROM users").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each element is converted with [`FromValue`], so `Option<T>` takes
+//! nullable columns and `Vec<T>` takes arrays.
+
+use crate::client::{Client, QueryResult};
+use crate::error::{Error, Result};
+use crate::types::{FromRow, FromValue};
+
+impl QueryResult {
+    /// Convert every row into a scalar or a tuple
+    pub fn decode_rows<T: FromRow>(&self) -> Result<Vec<T>> {
+        self.check_width::<T>()?;
+        self.rows()
+            .enumerate()
+            .map(|(index, row)| row.decode().map_err(|e| Error::TypeConversion(format!("Row {}: {}", index, e))))
+            .collect()
+    }
+
+    /// Convert the first row, if any, into a scalar or a tuple
+    pub fn decode_first<T: FromRow>(&self) -> Result<Option<T>> {
+        self.check_width::<T>()?;
+        self.rows()
+            .next()
+            .map(|row| row.decode().map_err(|e| Error::TypeConversion(format!("Row 0: {}", e))))
+            .transpose()
+    }
+
+    /// Convert the first column of the first row
+    pub fn scalar<T: FromValue>(&self) -> Result<T> {
+        let row = self
+            .rows()
+            .next()
+            .ok_or_else(|| Error::InvalidData("Query returned no rows".to_string()))?;
+        let value = row.values.into_iter().next().flatten();
+        let value = value.ok_or_else(|| Error::InvalidData("Query returned no columns".to_string()))?;
+        T::from_value(value).map_err(Error::TypeConversion)
+    }
+
+    fn check_width<T: FromRow>(&self) -> Result<()> {
+        match self.column_count() {
+            0 => Ok(()),
+            width if width == T::WIDTH => Ok(()),
+            width => Err(Error::TypeConversion(format!(
+                "Expected {} columns, the query returned {}",
+                T::WIDTH,
+                width
+            ))),
+        }
+    }
+}
+
+impl Client {
+    /// Run a query and convert every row into a scalar or a tuple
+    pub async fn fetch_all<T: FromRow>(&self, sql: &str) -> Result<Vec<T>> {
+        self.query(sql).await?.decode_rows()
+    }
+
+    /// Run a query and convert its first row, failing if there is none
+    pub async fn fetch_one<T: FromRow>(&self, sql: &str) -> Result<T> {
+        self.fetch_optional(sql)
+            .await?
+            .ok_or_else(|| Error::InvalidData("Query returned no rows".to_string()))
+    }
+
+    /// Run a query and convert its first row, if any
+    pub async fn fetch_optional<T: FromRow>(&self, sql: &str) -> Result<Option<T>> {
+        self.query(sql).await?.decode_first()
+    }
+
+    /// Run a query and convert the first column of its first row
+    pub async fn fetch_scalar<T: FromValue>(&self, sql: &str) -> Result<T> {
+        self.query(sql).await?.scalar()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{QueryMetadata, QueryStats};
+    use crate::types::{Block, Column, ColumnData, Value};
+    use std::time::Duration;
+
+    fn query_result(blocks: Vec<Block>) -> QueryResult {
+        QueryResult::new(QueryMetadata::new(Vec::new(), Vec::new()), blocks, QueryStats::new(0, 0, Duration::ZERO))
+    }
+
+    #[test]
+    fn test_typed_rows() {
+        let block = Block::with_columns(vec![
+            Column::new("id", "UInt64", ColumnData::UInt64(vec![1, 2])),
+            Column::new("name", "Nullable(String)", ColumnData::Nullable(vec![Some(Value::String("a".into())), None])),
+        ]);
+        let result = query_result(vec![block.clone(), block]);
+
+        let rows: Vec<(u64, Option<String>)> = result.decode_rows().unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1], (2, None));
+        assert_eq!(result.decode_first::<(u8, Option<String>)>().unwrap(), Some((1, Some("a".to_string()))));
+        assert_eq!(result.scalar::<i32>().unwrap(), 1);
+
+        assert!(matches!(result.decode_rows::<u64>(), Err(Error::TypeConversion(_))));
+        let err = result.decode_rows::<(u64, String)>().unwrap_err();
+        assert!(err.to_string().contains("Row 1: Column 1"), "{}", err);
+
+        let empty = query_result(Vec::new());
+        assert_eq!(empty.decode_first::<(u64, String)>().unwrap(), None);
+        assert!(empty.decode_rows::<u64>().unwrap().is_empty());
+        assert!(matches!(empty.scalar::<u64>(), Err(Error::InvalidData(_))));
+    }
+}
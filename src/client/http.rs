@@ -0,0 +1,483 @@
+//! Reading HTTP interface responses
+//!
+//! ClickHouse streams HTTP results with chunked transfer encoding. With
+//! `send_progress_in_http_headers = 1` it also sends `X-ClickHouse-Progress`
+//! headers with cumulative counters, and it reports the final counters in an
+//! `X-ClickHouse-Summary` header or trailer. [`HttpResponse`] turns the
+//! progress headers into the same increments the native transport delivers
+//! in progress packets, and exposes the summary like a profile packet.
+
+use crate::client::InsertResult;
+use crate::error::{Error, Result};
+use crate::protocol::ServerProgress;
+use bytes::{Buf, Bytes, BytesMut};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Header carrying cumulative query progress
+pub const PROGRESS_HEADER: &str = "X-ClickHouse-Progress";
+
+/// Header or trailer carrying the final query counters
+pub const SUMMARY_HEADER: &str = "X-ClickHouse-Summary";
+
+/// Most headers read from a response head
+const MAX_HEADERS: usize = 128;
+
+/// Callback invoked with each progress increment
+pub type ProgressCallback = Arc<dyn Fn(&ServerProgress) + Send + Sync>;
+
+/// Parse the JSON of a progress or summary header
+///
+/// The server writes counters as strings (`{"read_rows":"10",...}`); plain
+/// numbers are accepted too, and unknown fields are ignored.
+pub fn parse_progress(value: &str) -> Result<ServerProgress> {
+    let json: serde_json::Value = serde_json::from_str(value)
+        .map_err(|e| Error::Protocol(format!("Invalid progress header {}: {}", value, e)))?;
+    let counter = |name: &str| -> u64 {
+        match json.get(name) {
+            Some(serde_json::Value::String(s)) => s.parse().unwrap_or(0),
+            Some(v) => v.as_u64().unwrap_or(0),
+            None => 0,
+        }
+    };
+
+    let mut progress = ServerProgress::new();
+    progress.rows = counter("read_rows");
+    progress.bytes = counter("read_bytes");
+    progress.total_rows = counter("total_rows_to_read");
+    progress.written_rows = counter("written_rows");
+    progress.written_bytes = counter("written_bytes");
+    progress.elapsed_ns = counter("elapsed_ns");
+    progress.memory_usage = counter("memory_usage");
+    progress.peak_memory_usage = counter("peak_memory_usage");
+    Ok(progress)
+}
+
+/// Turns cumulative progress headers into increments
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    last: ServerProgress,
+}
+
+impl ProgressTracker {
+    /// Create a tracker that has seen no progress
+    pub fn new() -> Self {
+        Self { last: ServerProgress::new() }
+    }
+
+    /// Record cumulative counters and get the increment since the last call
+    pub fn update(&mut self, current: ServerProgress) -> ServerProgress {
+        let mut delta = current.clone();
+        delta.rows = current.rows.saturating_sub(self.last.rows);
+        delta.bytes = current.bytes.saturating_sub(self.last.bytes);
+        delta.total_rows = current.total_rows.saturating_sub(self.last.total_rows);
+        delta.written_rows = current.written_rows.saturating_sub(self.last.written_rows);
+        delta.written_bytes = current.written_bytes.saturating_sub(self.last.written_bytes);
+        self.last = current;
+        delta
+    }
+
+    /// Get the latest cumulative counters
+    pub fn total(&self) -> &ServerProgress {
+        &self.last
+    }
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkState {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+/// Incremental decoder for `Transfer-Encoding: chunked` bodies
+#[derive(Debug)]
+pub struct ChunkedDecoder {
+    buffer: BytesMut,
+    state: ChunkState,
+    trailers: Vec<(String, String)>,
+}
+
+impl ChunkedDecoder {
+    /// Create a decoder at the start of a body
+    pub fn new() -> Self {
+        Self {
+            buffer: BytesMut::new(),
+            state: ChunkState::Size,
+            trailers: Vec::new(),
+        }
+    }
+
+    /// Feed bytes read from the connection
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Check whether the last chunk and trailers have been read
+    pub fn is_done(&self) -> bool {
+        self.state == ChunkState::Done
+    }
+
+    /// Get the trailers sent after the last chunk
+    pub fn trailers(&self) -> &[(String, String)] {
+        &self.trailers
+    }
+
+    /// Take the next piece of body data
+    ///
+    /// Returns `None` when more input is needed or the body is complete.
+    pub fn next_chunk(&mut self) -> Result<Option<Bytes>> {
+        loop {
+            match self.state {
+                ChunkState::Size => {
+                    let Some(line) = self.take_line()? else { return Ok(None) };
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    let size = usize::from_str_radix(size, 16)
+                        .map_err(|_| Error::Protocol(format!("Invalid chunk size line: {}", line)))?;
+                    self.state = if size == 0 { ChunkState::Trailers } else { ChunkState::Data(size) };
+                }
+                ChunkState::Data(remaining) => {
+                    if self.buffer.is_empty() {
+                        return Ok(None);
+                    }
+                    let n = remaining.min(self.buffer.len());
+                    let data = self.buffer.split_to(n).freeze();
+                    self.state = if n == remaining { ChunkState::DataEnd } else { ChunkState::Data(remaining - n) };
+                    return Ok(Some(data));
+                }
+                ChunkState::DataEnd => {
+                    let Some(line) = self.take_line()? else { return Ok(None) };
+                    if !line.is_empty() {
+                        return Err(Error::Protocol("Missing CRLF after chunk data".to_string()));
+                    }
+                    self.state = ChunkState::Size;
+                }
+                ChunkState::Trailers => {
+                    let Some(line) = self.take_line()? else { return Ok(None) };
+                    if line.is_empty() {
+                        self.state = ChunkState::Done;
+                        continue;
+                    }
+                    let (name, value) = line
+                        .split_once(':')
+                        .ok_or_else(|| Error::Protocol(format!("Invalid trailer line: {}", line)))?;
+                    self.trailers.push((name.trim().to_string(), value.trim().to_string()));
+                }
+                ChunkState::Done => return Ok(None),
+            }
+        }
+    }
+
+    /// Take a CRLF-terminated line from the buffer, without the CRLF
+    fn take_line(&mut self) -> Result<Option<String>> {
+        let Some(end) = self.buffer.windows(2).position(|w| w == b"\r\n") else {
+            return Ok(None);
+        };
+        let line = self.buffer.split_to(end);
+        self.buffer.advance(2);
+        String::from_utf8(line.to_vec())
+            .map(Some)
+            .map_err(|e| Error::Protocol(format!("Invalid chunk framing: {}", e)))
+    }
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+enum Body {
+    Chunked(ChunkedDecoder),
+    Length(usize),
+    UntilClose,
+}
+
+/// A streamed HTTP response from the ClickHouse HTTP interface
+pub struct HttpResponse<R> {
+    reader: R,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Body,
+    pending: BytesMut,
+    finished: bool,
+    progress: ProgressTracker,
+    on_progress: Option<ProgressCallback>,
+    summary: Option<ServerProgress>,
+}
+
+impl<R: AsyncRead + Unpin> HttpResponse<R> {
+    /// Read the response head, reporting progress headers to `on_progress`
+    ///
+    /// Error statuses are turned into [`Error::Http`] with the response body
+    /// as the message.
+    pub async fn read(mut reader: R, on_progress: Option<ProgressCallback>) -> Result<Self> {
+        let mut buffer = BytesMut::new();
+        let (status, headers, head_len) = loop {
+            if reader.read_buf(&mut buffer).await? == 0 {
+                return Err(Error::Protocol("Connection closed before the HTTP response head".to_string()));
+            }
+            let mut slots = [httparse::EMPTY_HEADER; MAX_HEADERS];
+            let mut parsed = httparse::Response::new(&mut slots);
+            match parsed.parse(&buffer) {
+                Ok(httparse::Status::Complete(len)) => {
+                    let headers = parsed
+                        .headers
+                        .iter()
+                        .map(|h| (h.name.to_string(), String::from_utf8_lossy(h.value).into_owned()))
+                        .collect::<Vec<_>>();
+                    break (parsed.code.unwrap_or(0), headers, len);
+                }
+                Ok(httparse::Status::Partial) => continue,
+                Err(e) => return Err(Error::Protocol(format!("Invalid HTTP response: {}", e))),
+            }
+        };
+        buffer.advance(head_len);
+
+        let header = |name: &str| headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str());
+        let body = if header("Transfer-Encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+            Body::Chunked(ChunkedDecoder::new())
+        } else if let Some(length) = header("Content-Length") {
+            Body::Length(length.trim().parse().map_err(|_| Error::Protocol(format!("Invalid Content-Length: {}", length)))?)
+        } else {
+            Body::UntilClose
+        };
+
+        let mut response = Self {
+            reader,
+            status,
+            headers,
+            body,
+            pending: buffer,
+            finished: false,
+            progress: ProgressTracker::new(),
+            on_progress,
+            summary: None,
+        };
+        let progress_values: Vec<String> = response.headers_named(PROGRESS_HEADER).map(str::to_string).collect();
+        for value in progress_values {
+            response.report_progress(&value)?;
+        }
+        if let Some(summary) = response.header(SUMMARY_HEADER) {
+            response.summary = Some(parse_progress(summary)?);
+        }
+
+        if !(200..300).contains(&status) {
+            let body = response.read_to_end().await?;
+            return Err(Error::Http {
+                status,
+                message: String::from_utf8_lossy(&body).trim().to_string(),
+            });
+        }
+        Ok(response)
+    }
+
+    /// Get the status code
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Get all headers in the order they were received
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Get the first header with the given name, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    fn headers_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get the cumulative progress reported so far
+    pub fn progress(&self) -> &ServerProgress {
+        self.progress.total()
+    }
+
+    /// Get the final counters from the summary header or trailer
+    pub fn summary(&self) -> Option<&ServerProgress> {
+        self.summary.as_ref()
+    }
+
+    /// Build the result of an insert from the summary
+    pub fn insert_result(&self, query_id: impl Into<String>) -> InsertResult {
+        let mut result = InsertResult::new(query_id);
+        if let Some(summary) = &self.summary {
+            result.add_progress(summary);
+        }
+        result
+    }
+
+    /// Read the next piece of the body, or `None` at its end
+    pub async fn next_chunk(&mut self) -> Result<Option<Bytes>> {
+        loop {
+            if self.finished {
+                return Ok(None);
+            }
+            if let Some(chunk) = self.decode_pending()? {
+                return Ok(Some(chunk));
+            }
+            if self.finished {
+                return Ok(None);
+            }
+
+            let mut data = BytesMut::new();
+            if self.reader.read_buf(&mut data).await? == 0 {
+                return match self.body {
+                    Body::UntilClose => {
+                        self.finished = true;
+                        Ok(None)
+                    }
+                    _ => Err(Error::Protocol("Connection closed in the middle of the HTTP body".to_string())),
+                };
+            }
+            match &mut self.body {
+                Body::Chunked(decoder) => decoder.push(&data),
+                _ => self.pending.extend_from_slice(&data),
+            }
+        }
+    }
+
+    /// Read the rest of the body
+    pub async fn read_to_end(&mut self) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        while let Some(chunk) = self.next_chunk().await? {
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    fn decode_pending(&mut self) -> Result<Option<Bytes>> {
+        match &mut self.body {
+            Body::Chunked(decoder) => {
+                if !self.pending.is_empty() {
+                    decoder.push(&self.pending.split());
+                }
+                let chunk = decoder.next_chunk()?;
+                if decoder.is_done() {
+                    self.finished = true;
+                    self.apply_trailers()?;
+                }
+                Ok(chunk)
+            }
+            Body::Length(remaining) => {
+                if *remaining == 0 {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                if self.pending.is_empty() {
+                    return Ok(None);
+                }
+                let n = (*remaining).min(self.pending.len());
+                *remaining -= n;
+                Ok(Some(self.pending.split_to(n).freeze()))
+            }
+            Body::UntilClose => Ok((!self.pending.is_empty()).then(|| self.pending.split().freeze())),
+        }
+    }
+
+    fn apply_trailers(&mut self) -> Result<()> {
+        let Body::Chunked(decoder) = &self.body else { return Ok(()) };
+        let trailers = decoder.trailers().to_vec();
+        for (name, value) in trailers {
+            if name.eq_ignore_ascii_case(PROGRESS_HEADER) {
+                self.report_progress(&value)?;
+            } else if name.eq_ignore_ascii_case(SUMMARY_HEADER) {
+                self.summary = Some(parse_progress(&value)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn report_progress(&mut self, value: &str) -> Result<()> {
+        let delta = self.progress.update(parse_progress(value)?);
+        if let Some(callback) = &self.on_progress {
+            callback(&delta);
+        }
+        Ok(())
+    }
+}
+
+impl<R> std::fmt::Debug for HttpResponse<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("finished", &self.finished)
+            .field("summary", &self.summary)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_chunked_decoder_split_input() {
+        let wire = b"5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-ClickHouse-Summary: {}\r\n\r\n";
+        let mut decoder = ChunkedDecoder::new();
+        let mut body = Vec::new();
+        for byte in wire.iter() {
+            decoder.push(&[*byte]);
+            while let Some(chunk) = decoder.next_chunk().unwrap() {
+                body.extend_from_slice(&chunk);
+            }
+        }
+        assert_eq!(body, b"hello world");
+        assert!(decoder.is_done());
+        assert_eq!(decoder.trailers(), &[("X-ClickHouse-Summary".to_string(), "{}".to_string())]);
+
+        let mut bad = ChunkedDecoder::new();
+        bad.push(b"zz\r\n");
+        assert!(bad.next_chunk().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http_response_progress_and_trailers() {
+        let wire: &[u8] = b"HTTP/1.1 200 OK\r\n\
+            Transfer-Encoding: chunked\r\n\
+            X-ClickHouse-Progress: {\"read_rows\":\"10\",\"read_bytes\":\"80\",\"total_rows_to_read\":\"100\"}\r\n\
+            X-ClickHouse-Progress: {\"read_rows\":\"25\",\"read_bytes\":\"200\",\"total_rows_to_read\":\"100\"}\r\n\
+            \r\n\
+            4\r\n1\n2\n\r\n0\r\n\
+            X-ClickHouse-Summary: {\"read_rows\":\"100\",\"written_rows\":\"7\",\"written_bytes\":\"56\"}\r\n\r\n";
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let callback: ProgressCallback = {
+            let seen = seen.clone();
+            Arc::new(move |p: &ServerProgress| seen.lock().unwrap().push(p.rows))
+        };
+        let mut response = HttpResponse::read(wire, Some(callback)).await.unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![10, 15]);
+        assert_eq!(response.progress().rows, 25);
+        assert!(response.summary().is_none());
+
+        assert_eq!(response.read_to_end().await.unwrap(), b"1\n2\n");
+        assert_eq!(response.summary().unwrap().rows, 100);
+        let insert = response.insert_result("q1");
+        assert_eq!((insert.rows_written, insert.bytes_written), (7, 56));
+    }
+
+    #[tokio::test]
+    async fn test_http_response_error_status() {
+        let wire: &[u8] = b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 13\r\n\r\nCode: 60. Bad";
+        let err = HttpResponse::read(wire, None).await.unwrap_err();
+        assert!(matches!(err, Error::Http { status: 500, ref message } if message == "Code: 60. Bad"));
+    }
+}
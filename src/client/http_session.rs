@@ -0,0 +1,233 @@
+//! Sticky HTTP sessions
+//!
+//! Over HTTP every request is independent unless it names a server session
+//! with the `session_id` parameter. Behind a proxy or load balancer such as
+//! chproxy the request must also reach the replica holding that session,
+//! which proxies arrange through sticky cookies or a reused keep-alive
+//! connection. An [`HttpSession`] keeps all three for one connection, so
+//! `SET` statements and temporary tables work over HTTP.
+
+use crate::client::HttpResponse;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::io::AsyncRead;
+use tokio::net::TcpStream;
+
+/// How HTTP requests are tied to a server session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpSessionOptions {
+    /// Fixed session ID; each connection generates its own if unset
+    pub session_id: Option<String>,
+    /// Idle time after which the server closes the session
+    pub timeout: Option<Duration>,
+    /// Ask the server to fail requests whose session has expired
+    pub check: bool,
+    /// Store cookies set by proxies and send them back
+    pub sticky_cookies: bool,
+}
+
+impl HttpSessionOptions {
+    /// Create options with a generated session ID and sticky cookies
+    pub fn new() -> Self {
+        Self {
+            sticky_cookies: true,
+            ..Self::default()
+        }
+    }
+
+    /// Use a fixed session ID
+    pub fn session_id(mut self, id: impl Into<String>) -> Self {
+        self.session_id = Some(id.into());
+        self
+    }
+
+    /// Set the session idle timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set whether expired sessions fail requests instead of starting afresh
+    pub fn check(mut self, check: bool) -> Self {
+        self.check = check;
+        self
+    }
+
+    /// Set whether proxy cookies are sent back
+    pub fn sticky_cookies(mut self, sticky: bool) -> Self {
+        self.sticky_cookies = sticky;
+        self
+    }
+}
+
+/// Session ID, cookies and keep-alive connection of one HTTP client connection
+#[derive(Debug)]
+pub struct HttpSession {
+    id: String,
+    options: HttpSessionOptions,
+    started: bool,
+    cookies: BTreeMap<String, String>,
+    idle: Option<TcpStream>,
+}
+
+impl HttpSession {
+    /// Create a session, generating an ID unless the options fix one
+    pub fn new(options: HttpSessionOptions) -> Self {
+        let id = options
+            .session_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        Self {
+            id,
+            options,
+            started: false,
+            cookies: BTreeMap::new(),
+            idle: None,
+        }
+    }
+
+    /// Get the session ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Check whether the server has answered a request in this session
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Get the query parameters that bind a request to the session
+    ///
+    /// `session_check` is only sent once the session exists on the server,
+    /// since the first request is the one that creates it.
+    pub fn query_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![("session_id", self.id.clone())];
+        if let Some(timeout) = self.options.timeout {
+            params.push(("session_timeout", timeout.as_secs().to_string()));
+        }
+        if self.options.check && self.started {
+            params.push(("session_check", "1".to_string()));
+        }
+        params
+    }
+
+    /// Build a request target for `path` with the session parameters and `params`
+    pub fn request_target(&self, path: &str, params: &[(&str, &str)]) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        for (name, value) in self.query_params() {
+            query.append_pair(name, &value);
+        }
+        for (name, value) in params {
+            query.append_pair(name, value);
+        }
+        format!("{}?{}", path, query.finish())
+    }
+
+    /// Get the `Cookie` header value to send, if any cookies are stored
+    pub fn cookie_header(&self) -> Option<String> {
+        if !self.options.sticky_cookies || self.cookies.is_empty() {
+            return None;
+        }
+        let pairs: Vec<String> = self.cookies.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Store cookies from `Set-Cookie` headers
+    ///
+    /// Cookie attributes are ignored, except that `Max-Age=0` removes the cookie.
+    pub fn store_cookies<'a>(&mut self, headers: impl IntoIterator<Item = (&'a str, &'a str)>) {
+        if !self.options.sticky_cookies {
+            return;
+        }
+        for (name, value) in headers {
+            if !name.eq_ignore_ascii_case("Set-Cookie") {
+                continue;
+            }
+            let mut parts = value.split(';').map(str::trim);
+            let Some((cookie, cookie_value)) = parts.next().and_then(|pair| pair.split_once('=')) else {
+                continue;
+            };
+            let expired = parts.any(|attribute| attribute.eq_ignore_ascii_case("Max-Age=0"));
+            if expired {
+                self.cookies.remove(cookie.trim());
+            } else {
+                self.cookies.insert(cookie.trim().to_string(), cookie_value.trim().to_string());
+            }
+        }
+    }
+
+    /// Record a successful response in the session
+    pub fn observe<R: AsyncRead + Unpin>(&mut self, response: &HttpResponse<R>) {
+        self.started = true;
+        self.store_cookies(response.headers().iter().map(|(n, v)| (n.as_str(), v.as_str())));
+    }
+
+    /// Take the kept-alive connection, or open a new one to `host:port`
+    pub async fn checkout(&mut self, host: &str, port: u16) -> Result<TcpStream> {
+        if let Some(stream) = self.idle.take() {
+            return Ok(stream);
+        }
+        Ok(TcpStream::connect((host, port)).await?)
+    }
+
+    /// Keep a connection whose response was fully read for the next request
+    pub fn checkin(&mut self, stream: TcpStream) {
+        self.idle = Some(stream);
+    }
+
+    /// Forget the server session, cookies and kept-alive connection
+    pub fn reset(&mut self) {
+        self.started = false;
+        self.cookies.clear();
+        self.idle = None;
+        if self.options.session_id.is_none() {
+            self.id = uuid::Uuid::new_v4().to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_http_session_stickiness() {
+        let options = HttpSessionOptions::new()
+            .session_id("s 1")
+            .timeout(Duration::from_secs(120))
+            .check(true);
+        let mut session = HttpSession::new(options);
+        assert_eq!(
+            session.request_target("/", &[("query", "SET max_threads = 2")]),
+            "/?session_id=s+1&session_timeout=120&query=SET+max_threads+%3D+2"
+        );
+        assert_eq!(session.cookie_header(), None);
+
+        let response = HttpResponse::read(
+            &b"HTTP/1.1 200 OK\r\nSet-Cookie: route=r2; Path=/\r\nSet-Cookie: lb=a\r\nContent-Length: 0\r\n\r\n"[..],
+            None,
+        )
+        .await
+        .unwrap();
+        session.observe(&response);
+        assert_eq!(session.cookie_header().as_deref(), Some("lb=a; route=r2"));
+        assert!(session.request_target("/", &[]).ends_with("&session_check=1"));
+
+        session.store_cookies([("set-cookie", "lb=; Max-Age=0")]);
+        assert_eq!(session.cookie_header().as_deref(), Some("route=r2"));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = session.checkout("127.0.0.1", port).await.unwrap();
+        let local = stream.local_addr().unwrap();
+        session.checkin(stream);
+        assert_eq!(session.checkout("127.0.0.1", port).await.unwrap().local_addr().unwrap(), local);
+
+        session.reset();
+        assert_eq!(session.id(), "s 1");
+        assert!(!session.is_started());
+        assert_eq!(session.cookie_header(), None);
+    }
+}
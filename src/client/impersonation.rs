@@ -0,0 +1,276 @@
+//! Per-request impersonation of other users
+//!
+//! A [`UserHandle`] runs queries as a different ClickHouse user, so the row
+//! policies, quotas and grants of that user apply. Each user gets its own
+//! small connection pool, created on first use and shared by later handles.
+//! At most [`USER_POOL_MAX_USERS`] pools are kept, and pools unused for
+//! [`USER_POOL_IDLE_TIMEOUT`] are dropped.
+
+use crate::client::{Client, ClientOptions, ConnectionPool, InsertResult, QueryResult, QuerySettings};
+use crate::error::{Error, Result};
+use crate::protocol::constants::JWT_AUTHENTICATION_MARKER;
+use crate::types::{Block, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Most connections kept for a single impersonated user
+pub const USER_POOL_MAX_CONNECTIONS: usize = 4;
+
+/// Most impersonated users with a connection pool at the same time
+pub const USER_POOL_MAX_USERS: usize = 64;
+
+/// How long the pool of an impersonated user is kept without being used
+pub const USER_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Credentials for an impersonated user
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum UserCredential {
+    /// Authenticate without a password
+    None,
+    /// Authenticate with a password
+    Password(String),
+    /// Authenticate with a JSON Web Token; the user is taken from the token
+    Jwt(String),
+}
+
+impl UserCredential {
+    fn apply(&self, username: &str, options: ClientOptions) -> ClientOptions {
+        match self {
+            UserCredential::None => options.username(username).password(""),
+            UserCredential::Password(password) => options.username(username).password(password.as_str()),
+            UserCredential::Jwt(token) => options.username(JWT_AUTHENTICATION_MARKER).password(token.as_str()),
+        }
+    }
+}
+
+impl std::fmt::Debug for UserCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserCredential::None => write!(f, "None"),
+            UserCredential::Password(_) => write!(f, "Password(***)"),
+            UserCredential::Jwt(_) => write!(f, "Jwt(***)"),
+        }
+    }
+}
+
+impl From<&str> for UserCredential {
+    fn from(password: &str) -> Self {
+        UserCredential::Password(password.to_string())
+    }
+}
+
+impl From<String> for UserCredential {
+    fn from(password: String) -> Self {
+        UserCredential::Password(password)
+    }
+}
+
+/// Connection pool of one impersonated user
+struct UserPool {
+    credential: UserCredential,
+    pool: Arc<ConnectionPool>,
+    last_used: Instant,
+}
+
+/// Connection pools keyed by impersonated user
+///
+/// A user has one pool at a time: asking for it with another credential
+/// replaces it. Pools unused for longer than the idle timeout are dropped, as
+/// is the least recently used one when the registry is full.
+pub(crate) struct UserPools {
+    pools: Mutex<HashMap<String, UserPool>>,
+    max_users: usize,
+    idle_timeout: Duration,
+}
+
+impl Default for UserPools {
+    fn default() -> Self {
+        Self::with_limits(USER_POOL_MAX_USERS, USER_POOL_IDLE_TIMEOUT)
+    }
+}
+
+impl UserPools {
+    pub(crate) fn with_limits(max_users: usize, idle_timeout: Duration) -> Self {
+        Self {
+            pools: Mutex::new(HashMap::new()),
+            max_users: max_users.max(1),
+            idle_timeout,
+        }
+    }
+
+    fn get_or_create(&self, base: &ClientOptions, username: &str, credential: &UserCredential) -> Result<Arc<ConnectionPool>> {
+        let mut pools = self
+            .pools
+            .lock()
+            .map_err(|_| Error::Internal("User pool registry lock poisoned".to_string()))?;
+        let now = Instant::now();
+        pools.retain(|user, entry| {
+            let keep = user == username || now.duration_since(entry.last_used) < self.idle_timeout;
+            if !keep {
+                tracing::debug!("Dropping idle connection pool of impersonated user {}", user);
+            }
+            keep
+        });
+        if let Some(entry) = pools.get_mut(username).filter(|entry| entry.credential == *credential) {
+            entry.last_used = now;
+            return Ok(Arc::clone(&entry.pool));
+        }
+
+        if !pools.contains_key(username) && pools.len() >= self.max_users {
+            if let Some(oldest) = pools.iter().min_by_key(|(_, entry)| entry.last_used).map(|(user, _)| user.clone()) {
+                tracing::debug!("Dropping least recently used connection pool of impersonated user {}", oldest);
+                pools.remove(&oldest);
+            }
+        }
+        let options = credential
+            .apply(username, base.clone())
+            .min_connections(0)
+            .max_connections(base.max_connections.clamp(1, USER_POOL_MAX_CONNECTIONS));
+        let pool = Arc::new(ConnectionPool::new(options)?);
+        let entry = UserPool {
+            credential: credential.clone(),
+            pool: Arc::clone(&pool),
+            last_used: now,
+        };
+        pools.insert(username.to_string(), entry);
+        Ok(pool)
+    }
+
+    pub(crate) fn remove(&self, username: &str) -> Option<Arc<ConnectionPool>> {
+        let mut pools = self.pools.lock().ok()?;
+        pools.remove(username).map(|entry| entry.pool)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.pools.lock().map(|pools| pools.len()).unwrap_or_default()
+    }
+}
+
+/// Handle that runs queries as another user
+pub struct UserHandle<'a> {
+    client: &'a Client,
+    username: String,
+    pool: Arc<ConnectionPool>,
+}
+
+impl<'a> UserHandle<'a> {
+    pub(crate) fn new(client: &'a Client, username: &str, credential: Option<UserCredential>) -> Result<Self> {
+        if username.is_empty() {
+            return Err(Error::Configuration("Impersonated username cannot be empty".to_string()));
+        }
+        let credential = credential.unwrap_or(UserCredential::None);
+        let pool = client.user_pools.get_or_create(&client.options, username, &credential)?;
+        Ok(Self {
+            client,
+            username: username.to_string(),
+            pool,
+        })
+    }
+
+    /// Get the impersonated username
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Get the connection pool used for this user
+    pub fn pool(&self) -> &Arc<ConnectionPool> {
+        &self.pool
+    }
+
+    /// Execute a query as this user
+    pub async fn query(&self, sql: &str) -> Result<QueryResult> {
+        let _guard = self.client.drain.enter()?;
+        let mut connection = self.pool.get_connection().await?;
+        connection.query(sql).await
+    }
+
+    /// Execute a query with parameters as this user
+    pub async fn query_with_params(&self, sql: &str, params: HashMap<String, Value>) -> Result<QueryResult> {
+        let _guard = self.client.drain.enter()?;
+        let mut connection = self.pool.get_connection().await?;
+        connection.query_with_params(sql, params).await
+    }
+
+    /// Execute a query with settings as this user
+    pub async fn query_with_settings(&self, sql: &str, settings: QuerySettings) -> Result<QueryResult> {
+        let _guard = self.client.drain.enter()?;
+        let mut connection = self.pool.get_connection().await?;
+        connection.query_with_settings(sql, settings).await
+    }
+
+    /// Execute a statement as this user
+    pub async fn execute(&self, sql: &str) -> Result<()> {
+        let _guard = self.client.drain.enter()?;
+        let mut connection = self.pool.get_connection().await?;
+        connection.execute(sql).await
+    }
+
+    /// Insert data into a table as this user
+    pub async fn insert(&self, table: &str, block: Block) -> Result<InsertResult> {
+        let _guard = self.client.drain.enter()?;
+        let mut connection = self.pool.get_connection().await?;
+        connection.insert(table, block).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_as_user_reuses_pools() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+
+        let alice = client.as_user("alice", Some("secret".into())).unwrap();
+        let again = client.as_user("alice", Some("secret".into())).unwrap();
+        assert!(Arc::ptr_eq(alice.pool(), again.pool()));
+        assert_eq!(alice.username(), "alice");
+
+        let bob = client.as_user("bob", None).unwrap();
+        assert!(!Arc::ptr_eq(alice.pool(), bob.pool()));
+        assert_eq!(client.impersonated_users(), 2);
+
+        // Clones share the impersonation pools
+        assert_eq!(client.clone().impersonated_users(), 2);
+
+        // Another credential replaces the user's pool
+        let rotated = client.as_user("alice", Some("rotated".into())).unwrap();
+        assert!(!Arc::ptr_eq(alice.pool(), rotated.pool()));
+        assert_eq!(client.impersonated_users(), 2);
+
+        client.release_user("alice").await.unwrap();
+        assert_eq!(client.impersonated_users(), 1);
+        assert!(client.as_user("", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_user_pool_eviction() {
+        let options = ClientOptions::default().min_connections(0);
+        let pools = UserPools::with_limits(2, Duration::from_secs(600));
+        let alice = pools.get_or_create(&options, "alice", &UserCredential::None).unwrap();
+        pools.get_or_create(&options, "bob", &UserCredential::None).unwrap();
+        pools.get_or_create(&options, "alice", &UserCredential::None).unwrap();
+        pools.get_or_create(&options, "carol", &UserCredential::None).unwrap();
+        assert_eq!(pools.len(), 2);
+        assert!(pools.remove("bob").is_none());
+        assert!(Arc::ptr_eq(&alice, &pools.get_or_create(&options, "alice", &UserCredential::None).unwrap()));
+
+        let pools = UserPools::with_limits(8, Duration::ZERO);
+        pools.get_or_create(&options, "alice", &UserCredential::None).unwrap();
+        pools.get_or_create(&options, "bob", &UserCredential::None).unwrap();
+        assert_eq!(pools.len(), 1);
+    }
+
+    #[test]
+    fn test_credential_options() {
+        let options = ClientOptions::default();
+        let jwt = UserCredential::Jwt("token".to_string()).apply("ignored", options.clone());
+        assert_eq!(jwt.username, JWT_AUTHENTICATION_MARKER);
+        assert_eq!(jwt.password, "token");
+
+        let password = UserCredential::from("pw").apply("carol", options);
+        assert_eq!(password.username, "carol");
+        assert_eq!(format!("{:?}", UserCredential::from("pw")), "Password(***)");
+    }
+}
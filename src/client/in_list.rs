@@ -0,0 +1,240 @@
+//! Rewriting of large array parameters used in `IN` lists
+//!
+//! Binding a large array into `x IN {ids:Array(UInt64)}` inlines every element
+//! into the query text, which quickly exceeds `max_query_size`. Arrays longer
+//! than the configured threshold are instead read from a `VALUES` table
+//! function or from a temporary table filled with a data block.
+
+use crate::client::stream::sql_literal;
+use crate::error::{Error, Result};
+use crate::types::{Block, Column, ColumnData, Value};
+use std::collections::HashMap;
+
+/// Array length above which IN-list parameters are rewritten
+pub const DEFAULT_IN_LIST_THRESHOLD: usize = 1000;
+
+/// Server default for `max_query_size`
+const DEFAULT_MAX_QUERY_SIZE: usize = 256 * 1024;
+
+/// How large array parameters are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InListStrategy {
+    /// Inline every element into the query text
+    Inline,
+    /// Select from a `VALUES` table function, raising `max_query_size` if needed
+    Values,
+    /// Insert the elements into a temporary table and select from it
+    TemporaryTable,
+}
+
+/// Temporary table holding the elements of one array parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct InListTable {
+    /// Table name
+    pub name: String,
+    /// Element type
+    pub element_type: String,
+    /// Elements to insert
+    pub values: Vec<Value>,
+}
+
+impl InListTable {
+    /// Statement creating the table
+    pub fn create_sql(&self) -> String {
+        format!("CREATE TEMPORARY TABLE {} (x {}) ENGINE = Memory", self.name, self.element_type)
+    }
+
+    /// Statement dropping the table
+    pub fn drop_sql(&self) -> String {
+        format!("DROP TEMPORARY TABLE IF EXISTS {}", self.name)
+    }
+
+    /// Block with the elements
+    pub fn block(&self) -> Result<Block> {
+        let mut data = self
+            .values
+            .first()
+            .and_then(empty_column_data)
+            .ok_or_else(|| {
+                Error::Unsupported(format!("Cannot send {} elements through a temporary table", self.element_type))
+            })?;
+        for value in &self.values {
+            data.push(value.clone()).map_err(Error::TypeConversion)?;
+        }
+        let mut block = Block::new();
+        block.add_column("x", Column::new("x", self.element_type.clone(), data));
+        Ok(block)
+    }
+}
+
+/// Query with large array parameters rewritten
+#[derive(Debug, Clone, PartialEq)]
+pub struct InListRewrite {
+    /// Rewritten query
+    pub sql: String,
+    /// Parameters that still need to be bound
+    pub params: HashMap<String, Value>,
+    /// Temporary tables to create before running the query
+    pub tables: Vec<InListTable>,
+    /// `max_query_size` needed for the rewritten query, if above the default
+    pub max_query_size: Option<usize>,
+}
+
+/// Rewrite array parameters longer than `threshold`
+pub fn rewrite_in_lists(
+    sql: &str,
+    params: HashMap<String, Value>,
+    threshold: usize,
+    strategy: InListStrategy,
+) -> Result<InListRewrite> {
+    let mut rewrite = InListRewrite {
+        sql: sql.to_string(),
+        params: HashMap::new(),
+        tables: Vec::new(),
+        max_query_size: None,
+    };
+
+    for (name, value) in params {
+        let elements = match &value {
+            Value::Array(elements) if strategy != InListStrategy::Inline && elements.len() > threshold => elements,
+            _ => {
+                rewrite.params.insert(name, value);
+                continue;
+            }
+        };
+
+        let placeholders = find_placeholders(&rewrite.sql, &name);
+        if placeholders.is_empty() {
+            rewrite.params.insert(name, value);
+            continue;
+        }
+
+        let declared = placeholders.iter().find_map(|(_, ty)| ty.clone());
+        let element_type = declared
+            .as_deref()
+            .and_then(|ty| ty.strip_prefix("Array(").and_then(|t| t.strip_suffix(')')))
+            .map(str::to_string)
+            .unwrap_or_else(|| elements[0].type_name().to_string());
+
+        let replacement = match strategy {
+            InListStrategy::Values => {
+                let literals = elements.iter().map(sql_literal).collect::<Result<Vec<_>>>()?;
+                format!("(SELECT x FROM VALUES('x {}', {}))", element_type, literals.join(", "))
+            }
+            _ => {
+                let table = InListTable {
+                    name: format!("_in_list_{}", name),
+                    element_type,
+                    values: elements.clone(),
+                };
+                let replacement = format!("(SELECT x FROM {})", table.name);
+                rewrite.tables.push(table);
+                replacement
+            }
+        };
+
+        for (placeholder, _) in placeholders {
+            rewrite.sql = rewrite.sql.replace(&placeholder, &replacement);
+        }
+    }
+
+    if rewrite.sql.len() >= DEFAULT_MAX_QUERY_SIZE {
+        rewrite.max_query_size = Some(rewrite.sql.len() + 1);
+    }
+    Ok(rewrite)
+}
+
+/// Find `{name}` and `{name:Type}` placeholders for a parameter
+fn find_placeholders(sql: &str, name: &str) -> Vec<(String, Option<String>)> {
+    let mut found: Vec<(String, Option<String>)> = Vec::new();
+    let mut rest = sql;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let inner = &rest[start + 1..start + len];
+        let (param, ty) = match inner.split_once(':') {
+            Some((param, ty)) => (param.trim(), Some(ty.trim().to_string())),
+            None => (inner.trim(), None),
+        };
+        let placeholder = rest[start..=start + len].to_string();
+        if param == name && !found.iter().any(|(p, _)| *p == placeholder) {
+            found.push((placeholder, ty));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    found
+}
+
+fn empty_column_data(sample: &Value) -> Option<ColumnData> {
+    Some(match sample {
+        Value::UInt8(_) => ColumnData::UInt8(Vec::new()),
+        Value::UInt16(_) => ColumnData::UInt16(Vec::new()),
+        Value::UInt32(_) => ColumnData::UInt32(Vec::new()),
+        Value::UInt64(_) => ColumnData::UInt64(Vec::new()),
+        Value::Int8(_) => ColumnData::Int8(Vec::new()),
+        Value::Int16(_) => ColumnData::Int16(Vec::new()),
+        Value::Int32(_) => ColumnData::Int32(Vec::new()),
+        Value::Int64(_) => ColumnData::Int64(Vec::new()),
+        Value::Float32(_) => ColumnData::Float32(Vec::new()),
+        Value::Float64(_) => ColumnData::Float64(Vec::new()),
+        Value::String(_) => ColumnData::String(Vec::new()),
+        Value::Date(_) => ColumnData::Date(Vec::new()),
+        Value::DateTime(_) => ColumnData::DateTime(Vec::new()),
+        Value::UUID(_) => ColumnData::UUID(Vec::new()),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(n: u64) -> HashMap<String, Value> {
+        HashMap::from([
+            ("ids".to_string(), Value::Array((1..=n).map(Value::UInt64).collect())),
+            ("name".to_string(), Value::String("x".to_string())),
+        ])
+    }
+
+    #[test]
+    fn test_small_arrays_are_untouched() {
+        let sql = "SELECT * FROM t WHERE id IN {ids:Array(UInt64)}";
+        let rewrite = rewrite_in_lists(sql, ids(3), 10, InListStrategy::Values).unwrap();
+        assert_eq!(rewrite.sql, sql);
+        assert_eq!(rewrite.params.len(), 2);
+    }
+
+    #[test]
+    fn test_values_rewrite() {
+        let sql = "SELECT * FROM t WHERE id IN {ids:Array(UInt64)} AND name = {name}";
+        let rewrite = rewrite_in_lists(sql, ids(3), 2, InListStrategy::Values).unwrap();
+        assert_eq!(
+            rewrite.sql,
+            "SELECT * FROM t WHERE id IN (SELECT x FROM VALUES('x UInt64', 1, 2, 3)) AND name = {name}"
+        );
+        assert!(rewrite.params.contains_key("name"));
+        assert!(!rewrite.params.contains_key("ids"));
+        assert_eq!(rewrite.max_query_size, None);
+
+        let huge = rewrite_in_lists(sql, ids(60_000), 2, InListStrategy::Values).unwrap();
+        assert!(huge.max_query_size.unwrap() > DEFAULT_MAX_QUERY_SIZE);
+    }
+
+    #[test]
+    fn test_temporary_table_rewrite() {
+        let sql = "SELECT * FROM t WHERE id IN {ids} OR parent IN {ids}";
+        let rewrite = rewrite_in_lists(sql, ids(5), 2, InListStrategy::TemporaryTable).unwrap();
+        assert_eq!(
+            rewrite.sql,
+            "SELECT * FROM t WHERE id IN (SELECT x FROM _in_list_ids) OR parent IN (SELECT x FROM _in_list_ids)"
+        );
+
+        let table = &rewrite.tables[0];
+        assert_eq!(table.create_sql(), "CREATE TEMPORARY TABLE _in_list_ids (x UInt64) ENGINE = Memory");
+        assert_eq!(table.block().unwrap().row_count, 5);
+
+        let inline = rewrite_in_lists(sql, ids(5), 2, InListStrategy::Inline).unwrap();
+        assert_eq!(inline.sql, sql);
+    }
+}
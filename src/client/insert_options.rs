@@ -0,0 +1,134 @@
+//! Insert options and deduplication tokens
+//!
+//! Replicated tables, and MergeTree tables with a
+//! `non_replicated_deduplication_window`, skip an insert whose deduplication
+//! token was seen recently. Sending the same token when a batch is retried
+//! after an unknown outcome turns an at-least-once pipeline into an
+//! effectively exactly-once one. [`InsertOptions`] either sends a fixed token
+//! or derives one from the content of each block, which stays the same however
+//! the server splits the insert into parts.
+
+use crate::client::{Client, InsertResult, QuerySettings};
+use crate::error::Result;
+use crate::protocol::write_block;
+use crate::types::Block;
+use bytes::BytesMut;
+
+/// Server setting carrying the token
+pub const DEDUPLICATION_TOKEN_SETTING: &str = "insert_deduplication_token";
+
+/// FNV-1a 128-bit parameters
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum Deduplication {
+    #[default]
+    Server,
+    Token(String),
+    Content,
+}
+
+/// Options for an insert
+#[derive(Debug, Clone, Default)]
+pub struct InsertOptions {
+    settings: QuerySettings,
+    deduplication: Deduplication,
+}
+
+impl InsertOptions {
+    /// Create options that leave deduplication to the server
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the settings sent with the insert
+    pub fn settings(mut self, settings: QuerySettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Send a fixed deduplication token, such as a batch or offset id
+    pub fn deduplication_token(mut self, token: impl Into<String>) -> Self {
+        self.deduplication = Deduplication::Token(token.into());
+        self
+    }
+
+    /// Derive the deduplication token from the content of each block
+    ///
+    /// The token is a hash of the block's column names, types and values, so
+    /// an identical retry is dropped by the server.
+    pub fn deduplicate_by_content(mut self) -> Self {
+        self.deduplication = Deduplication::Content;
+        self
+    }
+
+    /// Get the token sent with a block, if any
+    pub fn token_for(&self, block: &Block) -> Result<Option<String>> {
+        match &self.deduplication {
+            Deduplication::Server => Ok(None),
+            Deduplication::Token(token) => Ok(Some(token.clone())),
+            Deduplication::Content => content_token(block).map(Some),
+        }
+    }
+
+    /// Get the settings sent with a block, including its token
+    pub fn settings_for(&self, block: &Block) -> Result<QuerySettings> {
+        let settings = self.settings.clone();
+        Ok(match self.token_for(block)? {
+            Some(token) => settings.custom_setting(
+                DEDUPLICATION_TOKEN_SETTING,
+                format!("'{}'", token.replace('\\', "\\\\").replace('\'', "\\'")),
+            ),
+            None => settings,
+        })
+    }
+}
+
+/// Hash the content of a block into a deduplication token
+///
+/// The hash covers the block as it is sent on the wire, so equal blocks give
+/// equal tokens across processes and client versions.
+pub fn content_token(block: &Block) -> Result<String> {
+    let mut buf = BytesMut::new();
+    write_block(&mut buf, block)?;
+    let hash = buf
+        .iter()
+        .fold(FNV_OFFSET, |hash, &byte| (hash ^ byte as u128).wrapping_mul(FNV_PRIME));
+    Ok(format!("{:032x}", hash))
+}
+
+impl Client {
+    /// Insert a block with insert options
+    pub async fn insert_with_options(&self, table: &str, block: Block, options: &InsertOptions) -> Result<InsertResult> {
+        let settings = options.settings_for(&block)?;
+        self.insert_with_settings(table, block, settings).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Column, ColumnData};
+
+    fn block(ids: Vec<u64>) -> Block {
+        Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(ids))])
+    }
+
+    #[test]
+    fn test_deduplication_tokens() {
+        let content = InsertOptions::new().deduplicate_by_content();
+        let token = content.token_for(&block(vec![1, 2])).unwrap().unwrap();
+        assert_eq!(token.len(), 32);
+        assert_eq!(content.token_for(&block(vec![1, 2])).unwrap(), Some(token.clone()));
+        assert_ne!(content.token_for(&block(vec![2, 1])).unwrap(), Some(token));
+
+        assert_eq!(InsertOptions::new().token_for(&block(vec![1])).unwrap(), None);
+        let fixed = InsertOptions::new()
+            .settings(QuerySettings::new().max_threads(2))
+            .deduplication_token("batch-'7'");
+        let settings = fixed.settings_for(&block(vec![1])).unwrap().build_settings_string();
+        assert!(settings.contains("max_threads=2"));
+        assert!(settings.contains(r"insert_deduplication_token='batch-\'7\''"));
+    }
+}
@@ -0,0 +1,411 @@
+//! Buffered inserts that follow online schema changes
+//!
+//! A long-running [`Inserter`] periodically re-reads the target table with
+//! `DESCRIBE TABLE`. When columns were added or dropped since the last check,
+//! it reports a [`SchemaDrift`] to the registered callback and adapts each
+//! buffered block before sending it: columns the table no longer has are
+//! dropped, and new columns without a `DEFAULT` expression are filled with the
+//! default value of their type. Columns the client cannot produce a default
+//! for are left out, so the server fills them in.
+
+use crate::client::system_tables::RowReader;
+use crate::client::{Client, ClientWarning, InsertOptions, QueryResult};
+use crate::error::{Error, Result};
+use crate::protocol::{ColumnDescription, ServerTableColumns};
+use crate::types::{
+    Block, Column, ColumnData, Decimal128, Decimal32, Decimal64, FixedString, Int256, TypeDescriptor, UInt256,
+};
+use std::time::{Duration, Instant};
+
+/// Default number of buffered rows that triggers a flush
+pub const DEFAULT_INSERTER_MAX_ROWS: usize = 100_000;
+
+/// Default interval between schema checks
+pub const DEFAULT_SCHEMA_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Column of a table as reported by `DESCRIBE TABLE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableColumn {
+    /// Column name
+    pub name: String,
+    /// Column type
+    pub type_name: String,
+    /// `DEFAULT`, `MATERIALIZED`, `ALIAS` or `EPHEMERAL`; empty for plain columns
+    pub default_kind: String,
+}
+
+impl TableColumn {
+    /// Create a plain column
+    pub fn new(name: impl Into<String>, type_name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            type_name: type_name.into(),
+            default_kind: String::new(),
+        }
+    }
+
+    /// Set the kind of default expression
+    pub fn default_kind(mut self, kind: impl Into<String>) -> Self {
+        self.default_kind = kind.into();
+        self
+    }
+
+    /// Check if inserts may supply a value for the column
+    pub fn is_insertable(&self) -> bool {
+        !matches!(self.default_kind.as_str(), "MATERIALIZED" | "ALIAS")
+    }
+}
+
+impl From<&ColumnDescription> for TableColumn {
+    fn from(column: &ColumnDescription) -> Self {
+        TableColumn::new(&column.name, &column.type_name).default_kind(&column.default_kind)
+    }
+}
+
+/// Get the table schema sent by the server before an insert
+pub fn insert_schema(packet: &ServerTableColumns) -> Vec<TableColumn> {
+    packet.columns().iter().map(TableColumn::from).collect()
+}
+
+/// Columns added to and dropped from a table between two schema checks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaDrift {
+    /// Columns the table gained
+    pub added: Vec<TableColumn>,
+    /// Names of the columns the table lost
+    pub removed: Vec<String>,
+}
+
+impl SchemaDrift {
+    /// Compare two schemas
+    pub fn between(old: &[TableColumn], new: &[TableColumn]) -> Self {
+        Self {
+            added: new
+                .iter()
+                .filter(|column| !old.iter().any(|c| c.name == column.name))
+                .cloned()
+                .collect(),
+            removed: old
+                .iter()
+                .filter(|column| !new.iter().any(|c| c.name == column.name))
+                .map(|column| column.name.clone())
+                .collect(),
+        }
+    }
+
+    /// Check if the schema is unchanged
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Build the query that reads a table's schema
+pub fn describe_table_sql(table: &str) -> String {
+    format!("DESCRIBE TABLE {}", table)
+}
+
+/// Read the columns from the result of [`describe_table_sql`]
+pub(crate) fn table_columns(result: &QueryResult) -> Result<Vec<TableColumn>> {
+    let mut schema = Vec::with_capacity(result.row_count());
+    for block in &result.blocks {
+        for index in 0..block.row_count {
+            let row = RowReader::new(block, index);
+            schema.push(
+                TableColumn::new(row.string("name")?, row.string("type")?).default_kind(row.string("default_type")?),
+            );
+        }
+    }
+    Ok(schema)
+}
+
+/// Reshape a block to the insertable columns of a table, in table order
+///
+/// Columns missing from the table are dropped. Missing plain columns are
+/// filled with their type's default value when the client can produce it;
+/// other missing columns are left to the server.
+pub fn adapt_block(mut block: Block, schema: &[TableColumn]) -> Result<Block> {
+    let rows = block.row_count;
+    let mut columns = Vec::with_capacity(schema.len());
+    for table_column in schema.iter().filter(|column| column.is_insertable()) {
+        if let Some(index) = block.columns.iter().position(|c| c.name == table_column.name) {
+            columns.push(block.columns.swap_remove(index));
+        } else if table_column.default_kind.is_empty() {
+            let descriptor = TypeDescriptor::parse(&table_column.type_name)?;
+            match default_column_data(&descriptor, rows) {
+                Some(data) => columns.push(Column::new(&table_column.name, &table_column.type_name, data)),
+                None => tracing::debug!(
+                    "Leaving column {} of type {} to the server default",
+                    table_column.name,
+                    table_column.type_name
+                ),
+            }
+        }
+    }
+    if columns.is_empty() {
+        return Err(Error::InvalidData(
+            "Block has no columns left after adapting it to the table schema".to_string(),
+        ));
+    }
+    block.columns = columns;
+    Ok(block)
+}
+
+/// Build a column holding the default value of a type in every row
+pub fn default_column_data(descriptor: &TypeDescriptor, rows: usize) -> Option<ColumnData> {
+    let data = match descriptor {
+        TypeDescriptor::Simple(name) => match name.as_str() {
+            "UInt8" | "Bool" => ColumnData::UInt8(vec![0; rows]),
+            "UInt16" => ColumnData::UInt16(vec![0; rows]),
+            "UInt32" => ColumnData::UInt32(vec![0; rows]),
+            "UInt64" => ColumnData::UInt64(vec![0; rows]),
+            "UInt128" => ColumnData::UInt128(vec![0; rows]),
+            "UInt256" => ColumnData::UInt256(vec![UInt256::ZERO; rows]),
+            "Int8" => ColumnData::Int8(vec![0; rows]),
+            "Int16" => ColumnData::Int16(vec![0; rows]),
+            "Int32" => ColumnData::Int32(vec![0; rows]),
+            "Int64" => ColumnData::Int64(vec![0; rows]),
+            "Int128" => ColumnData::Int128(vec![0; rows]),
+            "Int256" => ColumnData::Int256(vec![Int256::ZERO; rows]),
+            "Float32" => ColumnData::Float32(vec![0.0; rows]),
+            "Float64" => ColumnData::Float64(vec![0.0; rows]),
+            "String" => ColumnData::String(vec![String::new(); rows]),
+            "UUID" => ColumnData::UUID(vec![uuid::Uuid::nil(); rows]),
+            "Date" => ColumnData::Date(vec![chrono::NaiveDate::default(); rows]),
+            _ => return None,
+        },
+        TypeDescriptor::FixedString(length) => {
+            ColumnData::FixedString(vec![FixedString::from_bytes(&vec![0; *length], *length); rows])
+        }
+        TypeDescriptor::DateTime { .. } => ColumnData::DateTime(vec![chrono::NaiveDateTime::default(); rows]),
+        TypeDescriptor::DateTime64 { .. } => ColumnData::DateTime64(vec![chrono::NaiveDateTime::default(); rows]),
+        TypeDescriptor::Decimal { precision, scale } => match precision {
+            0..=9 => ColumnData::Decimal32(vec![Decimal32::new(0, *scale); rows]),
+            10..=18 => ColumnData::Decimal64(vec![Decimal64::new(0, *scale); rows]),
+            19..=38 => ColumnData::Decimal128(vec![Decimal128::new(0, *scale); rows]),
+            _ => return None,
+        },
+        TypeDescriptor::Nullable(_) => ColumnData::Nullable(vec![None; rows]),
+        TypeDescriptor::Array(_) => ColumnData::Array(vec![Vec::new(); rows]),
+        _ => return None,
+    };
+    Some(data)
+}
+
+/// Callback invoked with the changes found by a schema check
+pub type SchemaChangeCallback<'a> = Box<dyn Fn(&SchemaDrift) + Send + Sync + 'a>;
+
+/// Buffered inserter that detects schema drift of its target table
+pub struct Inserter<'a> {
+    client: &'a Client,
+    table: String,
+    max_rows: usize,
+    schema_check_interval: Duration,
+    schema: Option<Vec<TableColumn>>,
+    last_schema_check: Option<Instant>,
+    on_schema_change: Option<SchemaChangeCallback<'a>>,
+    insert_options: InsertOptions,
+    pending: Vec<Block>,
+    pending_rows: usize,
+}
+
+impl<'a> Inserter<'a> {
+    /// Create an inserter for a table
+    pub fn new(client: &'a Client, table: impl Into<String>) -> Self {
+        Self {
+            client,
+            table: table.into(),
+            max_rows: DEFAULT_INSERTER_MAX_ROWS,
+            schema_check_interval: DEFAULT_SCHEMA_CHECK_INTERVAL,
+            schema: None,
+            last_schema_check: None,
+            on_schema_change: None,
+            insert_options: InsertOptions::new(),
+            pending: Vec::new(),
+            pending_rows: 0,
+        }
+    }
+
+    /// Set the number of buffered rows that triggers a flush
+    pub fn max_rows(mut self, rows: usize) -> Self {
+        self.max_rows = rows.max(1);
+        self
+    }
+
+    /// Set how often the table schema is re-read
+    pub fn schema_check_interval(mut self, interval: Duration) -> Self {
+        self.schema_check_interval = interval;
+        self
+    }
+
+    /// Set the callback invoked when the table schema changed
+    pub fn on_schema_change(mut self, callback: impl Fn(&SchemaDrift) + Send + Sync + 'a) -> Self {
+        self.on_schema_change = Some(Box::new(callback));
+        self
+    }
+
+    /// Set the options each block is inserted with
+    ///
+    /// With [`InsertOptions::deduplicate_by_content`], a block that is sent
+    /// again after a failed flush keeps its token, since the token is derived
+    /// before the block is adapted to the schema.
+    pub fn insert_options(mut self, options: InsertOptions) -> Self {
+        self.insert_options = options;
+        self
+    }
+
+    /// Get the target table
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Get the last known table schema
+    pub fn schema(&self) -> Option<&[TableColumn]> {
+        self.schema.as_deref()
+    }
+
+    /// Get the number of buffered rows
+    pub fn pending_rows(&self) -> usize {
+        self.pending_rows
+    }
+
+    /// Buffer a block, flushing when the row limit is reached
+    pub async fn write(&mut self, block: Block) -> Result<()> {
+        if block.is_empty() {
+            return Ok(());
+        }
+        self.pending_rows += block.row_count;
+        self.pending.push(block);
+        if self.pending_rows >= self.max_rows {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Send all buffered blocks, returning the number of rows inserted
+    ///
+    /// The schema is re-read first when the check interval has elapsed. Blocks
+    /// that fail to insert stay buffered.
+    pub async fn flush(&mut self) -> Result<usize> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+        let due = self
+            .last_schema_check
+            .is_none_or(|checked| checked.elapsed() >= self.schema_check_interval);
+        if due {
+            self.refresh_schema().await?;
+        }
+        let schema = self.schema.clone().unwrap_or_default();
+
+        let mut inserted = 0;
+        while let Some(block) = self.pending.first() {
+            let rows = block.row_count;
+            let settings = self.insert_options.settings_for(block)?;
+            let block = adapt_block(block.clone(), &schema)?;
+            self.client.insert_with_settings(&self.table, block, settings).await?;
+            self.pending.remove(0);
+            self.pending_rows -= rows;
+            inserted += rows;
+        }
+        Ok(inserted)
+    }
+
+    /// Re-read the table schema now
+    ///
+    /// Returns the changes since the previous check, if there were any. The
+    /// first check only records the schema.
+    pub async fn refresh_schema(&mut self) -> Result<Option<SchemaDrift>> {
+        let result = self.client.query(&describe_table_sql(&self.table)).await?;
+        let schema = table_columns(&result)?;
+        self.last_schema_check = Some(Instant::now());
+        Ok(self.update_schema(schema))
+    }
+
+    fn update_schema(&mut self, schema: Vec<TableColumn>) -> Option<SchemaDrift> {
+        let drift = self
+            .schema
+            .as_deref()
+            .map(|old| SchemaDrift::between(old, &schema))
+            .filter(|drift| !drift.is_empty());
+        if let Some(drift) = &drift {
+            tracing::info!(
+                "Schema of {} changed: added {:?}, removed {:?}",
+                self.table,
+                drift.added.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+                drift.removed
+            );
+            if let Some(callback) = &self.on_schema_change {
+                callback(drift);
+            }
+            self.client.options().warnings.emit(ClientWarning::SchemaDrift {
+                table: self.table.clone(),
+                drift: drift.clone(),
+            });
+        }
+        self.schema = Some(schema);
+        drift
+    }
+}
+
+impl Client {
+    /// Create a buffered inserter for a table
+    pub fn inserter(&self, table: impl Into<String>) -> Inserter<'_> {
+        Inserter::new(self, table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+    use crate::types::Value;
+    use std::sync::{Arc, Mutex};
+
+    fn block() -> Block {
+        Block::with_columns(vec![
+            Column::new("id", "UInt64", ColumnData::UInt64(vec![1, 2])),
+            Column::new("legacy", "String", ColumnData::String(vec!["a".into(), "b".into()])),
+        ])
+    }
+
+    #[test]
+    fn test_adapt_block() {
+        let schema = vec![
+            TableColumn::new("score", "Nullable(Float64)"),
+            TableColumn::new("id", "UInt64"),
+            TableColumn::new("tags", "Array(String)"),
+            TableColumn::new("created", "DateTime").default_kind("DEFAULT"),
+            TableColumn::new("day", "Date").default_kind("MATERIALIZED"),
+            TableColumn::new("geo", "Point"),
+        ];
+        let adapted = adapt_block(block(), &schema).unwrap();
+        let names: Vec<&str> = adapted.columns().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["score", "id", "tags"]);
+        assert_eq!(adapted.row_count, 2);
+        assert_eq!(adapted.get_column("score").unwrap().get_value(1), Some(Value::Nullable(None)));
+        assert_eq!(adapted.get_column("tags").unwrap().get_value(0), Some(Value::Array(Vec::new())));
+
+        assert!(adapt_block(block(), &[TableColumn::new("other", "Point")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schema_drift_callback() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = seen.clone();
+        let mut inserter = client
+            .inserter("events")
+            .on_schema_change(move |drift| recorded.lock().unwrap().push(drift.clone()));
+
+        let v1 = vec![TableColumn::new("id", "UInt64"), TableColumn::new("legacy", "String")];
+        assert_eq!(inserter.update_schema(v1.clone()), None);
+        assert_eq!(inserter.update_schema(v1), None);
+
+        let v2 = vec![TableColumn::new("id", "UInt64"), TableColumn::new("score", "Float64")];
+        let drift = inserter.update_schema(v2).unwrap();
+        assert_eq!(drift.added, [TableColumn::new("score", "Float64")]);
+        assert_eq!(drift.removed, ["legacy"]);
+        assert_eq!(*seen.lock().unwrap(), [drift]);
+        assert_eq!(inserter.schema().unwrap().len(), 2);
+    }
+}
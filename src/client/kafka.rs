@@ -0,0 +1,349 @@
+//! Kafka engine ingestion pipelines and consumer monitoring
+//!
+//! A Kafka engine table is a queue: reading from it consumes messages. The
+//! usual pipeline pairs it with a materialized view that moves every consumed
+//! batch into a `MergeTree` target table. Consumer offsets are exposed by
+//! `system.kafka_consumers` (server 23.8 and later).
+//!
+//! ```rust
+//! use clickhouse_rs::client::{ColumnDef, KafkaPipeline};
+//!
+//! let statements = KafkaPipeline::new("events_queue", "events")
+//!     .broker("kafka:9092")
+//!     .topic("events")
+//!     .group("clickhouse-events")
+//!     .column(ColumnDef::new("id", "UInt64"))
+//!     .column(ColumnDef::new("payload", "String"))
+//!     .statements()
+//!     .unwrap();
+//! assert!(statements[1].starts_with("CREATE MATERIALIZED VIEW IF NOT EXISTS `events_queue_mv` TO `events`"));
+//! ```
+
+use crate::client::ddl::{quote_identifier, ColumnDef, CreateTable};
+use crate::client::system_tables::{RowReader, SystemTableRow};
+use crate::error::{Error, Result};
+use crate::types::Value;
+use chrono::NaiveDateTime;
+
+/// Kafka engine table feeding a target table through a materialized view
+#[derive(Debug, Clone, PartialEq)]
+pub struct KafkaPipeline {
+    queue: String,
+    target: String,
+    brokers: Vec<String>,
+    topics: Vec<String>,
+    group: Option<String>,
+    format: String,
+    columns: Vec<ColumnDef>,
+    select: Option<String>,
+    settings: Vec<(String, String)>,
+}
+
+impl KafkaPipeline {
+    /// Start a pipeline from the `queue` Kafka table into `target`
+    pub fn new(queue: impl Into<String>, target: impl Into<String>) -> Self {
+        Self {
+            queue: queue.into(),
+            target: target.into(),
+            brokers: Vec::new(),
+            topics: Vec::new(),
+            group: None,
+            format: "JSONEachRow".to_string(),
+            columns: Vec::new(),
+            select: None,
+            settings: Vec::new(),
+        }
+    }
+
+    /// Add a broker address
+    pub fn broker(mut self, broker: impl Into<String>) -> Self {
+        self.brokers.push(broker.into());
+        self
+    }
+
+    /// Add a topic to consume
+    pub fn topic(mut self, topic: impl Into<String>) -> Self {
+        self.topics.push(topic.into());
+        self
+    }
+
+    /// Set the consumer group
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Set the message format, `JSONEachRow` by default
+    pub fn format(mut self, format: impl Into<String>) -> Self {
+        self.format = format.into();
+        self
+    }
+
+    /// Add a column of the queue table
+    pub fn column(mut self, column: ColumnDef) -> Self {
+        self.columns.push(column);
+        self
+    }
+
+    /// Set the select list of the materialized view, `*` by default
+    pub fn select(mut self, select: impl Into<String>) -> Self {
+        self.select = Some(select.into());
+        self
+    }
+
+    /// Set the number of consumers, at most the number of partitions
+    pub fn num_consumers(self, consumers: u32) -> Self {
+        self.setting("kafka_num_consumers", consumers.to_string())
+    }
+
+    /// Set the number of unparsable messages skipped per block
+    pub fn skip_broken_messages(self, messages: u64) -> Self {
+        self.setting("kafka_skip_broken_messages", messages.to_string())
+    }
+
+    /// Add a Kafka engine setting
+    pub fn setting(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.push((key.into(), value.into()));
+        self
+    }
+
+    /// Get the materialized view name
+    pub fn view_name(&self) -> String {
+        format!("{}_mv", self.queue)
+    }
+
+    /// Render the queue table and materialized view statements
+    pub fn statements(&self) -> Result<Vec<String>> {
+        let group = self
+            .group
+            .as_deref()
+            .ok_or_else(|| Error::Configuration(format!("Kafka table {} needs a consumer group", self.queue)))?;
+        if self.brokers.is_empty() || self.topics.is_empty() {
+            return Err(Error::Configuration(format!(
+                "Kafka table {} needs at least one broker and topic",
+                self.queue
+            )));
+        }
+
+        let quote = |s: &str| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+        let mut queue = CreateTable::new(&self.queue)
+            .if_not_exists()
+            .engine("Kafka")
+            .setting("kafka_broker_list", quote(&self.brokers.join(",")))
+            .setting("kafka_topic_list", quote(&self.topics.join(",")))
+            .setting("kafka_group_name", quote(group))
+            .setting("kafka_format", quote(&self.format));
+        for column in &self.columns {
+            queue = queue.column(column.clone());
+        }
+        for (key, value) in &self.settings {
+            queue = queue.setting(key, value);
+        }
+
+        let view = format!(
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS {} TO {} AS SELECT {} FROM {}",
+            quote_identifier(&self.view_name()),
+            quote_identifier(&self.target),
+            self.select.as_deref().unwrap_or("*"),
+            quote_identifier(&self.queue)
+        );
+        Ok(vec![queue.build()?, view])
+    }
+
+    /// Statements removing the pipeline, view first so consumption stops
+    pub fn drop_statements(&self) -> Vec<String> {
+        vec![
+            format!("DROP VIEW IF EXISTS {}", quote_identifier(&self.view_name())),
+            format!("DROP TABLE IF EXISTS {}", quote_identifier(&self.queue)),
+        ]
+    }
+}
+
+/// Partition assigned to a Kafka consumer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KafkaAssignment {
+    /// Topic name
+    pub topic: String,
+    /// Partition number
+    pub partition: i32,
+    /// Next offset to consume, negative if none was committed yet
+    pub current_offset: i64,
+}
+
+impl KafkaAssignment {
+    /// Messages behind the partition's high watermark, if the offset is known
+    pub fn lag(&self, high_watermark: i64) -> Option<i64> {
+        (self.current_offset >= 0).then(|| (high_watermark - self.current_offset).max(0))
+    }
+}
+
+/// Kafka consumer from `system.kafka_consumers`
+#[derive(Debug, Clone, PartialEq)]
+pub struct KafkaConsumerInfo {
+    /// Database name
+    pub database: String,
+    /// Kafka table name
+    pub table: String,
+    /// Consumer ID
+    pub consumer_id: String,
+    /// Assigned partitions and their offsets
+    pub assignments: Vec<KafkaAssignment>,
+    /// Most recent consumer error, if any
+    pub last_exception: Option<String>,
+    /// Time of the last poll
+    pub last_poll_time: NaiveDateTime,
+    /// Messages read by the consumer
+    pub num_messages_read: u64,
+    /// Offset commits made by the consumer
+    pub num_commits: u64,
+    /// Whether the consumer is in use
+    pub is_currently_used: bool,
+}
+
+impl KafkaConsumerInfo {
+    /// Sum the lag of all partitions, given each partition's high watermark
+    ///
+    /// Partitions without a known offset or watermark are left out.
+    pub fn total_lag(&self, high_watermark: impl Fn(&str, i32) -> Option<i64>) -> i64 {
+        self.assignments
+            .iter()
+            .filter_map(|a| high_watermark(&a.topic, a.partition).and_then(|hw| a.lag(hw)))
+            .sum()
+    }
+}
+
+impl SystemTableRow for KafkaConsumerInfo {
+    const TABLE: &'static str = "system.kafka_consumers";
+    const COLUMNS: &'static [&'static str] = &[
+        "database",
+        "table",
+        "consumer_id",
+        "assignments.topic",
+        "assignments.partition_id",
+        "assignments.current_offset",
+        "exceptions.text",
+        "last_poll_time",
+        "num_messages_read",
+        "num_commits",
+        "is_currently_used",
+    ];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        let topics = row.array("assignments.topic")?;
+        let partitions = row.array("assignments.partition_id")?;
+        let offsets = row.array("assignments.current_offset")?;
+        let assignments = topics
+            .into_iter()
+            .zip(partitions)
+            .zip(offsets)
+            .map(|((topic, partition), offset)| {
+                Ok(KafkaAssignment {
+                    topic: topic.to_string(),
+                    partition: i32::try_from(integer(&partition)?)
+                        .map_err(|_| Error::TypeConversion(format!("Invalid Kafka partition {}", partition)))?,
+                    current_offset: integer(&offset)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            database: row.string("database")?,
+            table: row.string("table")?,
+            consumer_id: row.string("consumer_id")?,
+            assignments,
+            last_exception: row.array("exceptions.text")?.last().map(|text| text.to_string()),
+            last_poll_time: row.datetime("last_poll_time")?,
+            num_messages_read: row.u64("num_messages_read")?,
+            num_commits: row.u64("num_commits")?,
+            is_currently_used: row.bool("is_currently_used")?,
+        })
+    }
+}
+
+fn integer(value: &Value) -> Result<i64> {
+    match value {
+        Value::Int32(v) => Ok(*v as i64),
+        Value::Int64(v) => Ok(*v),
+        Value::UInt32(v) => Ok(*v as i64),
+        Value::UInt64(v) => i64::try_from(*v).map_err(|e| Error::TypeConversion(e.to_string())),
+        other => Err(Error::TypeConversion(format!("Expected an integer, got {}", other.type_name()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Block, Column, ColumnData};
+
+    #[test]
+    fn test_pipeline_statements() {
+        let pipeline = KafkaPipeline::new("db.events_queue", "db.events")
+            .broker("k1:9092")
+            .broker("k2:9092")
+            .topic("events")
+            .group("ch")
+            .format("Avro")
+            .column(ColumnDef::new("id", "UInt64"))
+            .select("id, now() AS received_at")
+            .num_consumers(2);
+        let statements = pipeline.statements().unwrap();
+        assert_eq!(
+            statements[0],
+            "CREATE TABLE IF NOT EXISTS `db`.`events_queue`\n(\n    `id` UInt64\n)\nENGINE = Kafka\n\
+             SETTINGS kafka_broker_list = 'k1:9092,k2:9092', kafka_topic_list = 'events', \
+             kafka_group_name = 'ch', kafka_format = 'Avro', kafka_num_consumers = 2"
+        );
+        assert_eq!(
+            statements[1],
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS `db`.`events_queue_mv` TO `db`.`events` \
+             AS SELECT id, now() AS received_at FROM `db`.`events_queue`"
+        );
+        assert_eq!(pipeline.drop_statements()[0], "DROP VIEW IF EXISTS `db`.`events_queue_mv`");
+        assert!(KafkaPipeline::new("q", "t").broker("k:9092").topic("t").statements().is_err());
+    }
+
+    #[test]
+    fn test_consumer_from_row() {
+        let mut block = Block::new();
+        let string = |s: &str| ColumnData::String(vec![s.to_string()]);
+        let array = |v: Vec<Value>| ColumnData::Array(vec![v]);
+        block.add_column("database", Column::new("database", "String", string("db")));
+        block.add_column("table", Column::new("table", "String", string("events_queue")));
+        block.add_column("consumer_id", Column::new("consumer_id", "String", string("c1")));
+        block.add_column(
+            "assignments.topic",
+            Column::new(
+                "assignments.topic",
+                "Array(String)",
+                array(vec![Value::String("events".to_string()), Value::String("events".to_string())]),
+            ),
+        );
+        block.add_column(
+            "assignments.partition_id",
+            Column::new("assignments.partition_id", "Array(Int32)", array(vec![Value::Int32(0), Value::Int32(1)])),
+        );
+        block.add_column(
+            "assignments.current_offset",
+            Column::new(
+                "assignments.current_offset",
+                "Array(Int64)",
+                array(vec![Value::Int64(90), Value::Int64(-1001)]),
+            ),
+        );
+        block.add_column("exceptions.text", Column::new("exceptions.text", "Array(String)", array(Vec::new())));
+        block.add_column(
+            "last_poll_time",
+            Column::new("last_poll_time", "DateTime", ColumnData::DateTime(vec![NaiveDateTime::default()])),
+        );
+        block.add_column("num_messages_read", Column::new("num_messages_read", "UInt64", ColumnData::UInt64(vec![90])));
+        block.add_column("num_commits", Column::new("num_commits", "UInt64", ColumnData::UInt64(vec![3])));
+        block.add_column("is_currently_used", Column::new("is_currently_used", "UInt8", ColumnData::UInt8(vec![1])));
+
+        let consumer = KafkaConsumerInfo::from_row(&RowReader::new(&block, 0)).unwrap();
+        assert_eq!(consumer.assignments.len(), 2);
+        assert_eq!(consumer.assignments[0].lag(100), Some(10));
+        assert_eq!(consumer.assignments[1].lag(100), None);
+        assert_eq!(consumer.last_exception, None);
+        assert_eq!(consumer.total_lag(|_, _| Some(120)), 30);
+    }
+}
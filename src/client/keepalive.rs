@@ -0,0 +1,195 @@
+//! Keep-alive pings during long idle streams
+//!
+//! WATCH queries and other long-running streams can go minutes without a
+//! packet. NAT gateways and firewalls drop such idle TCP flows without telling
+//! either side, so the stream hangs forever. [`KeepAlive`] sends a ping after
+//! each idle interval and consumes the server's pong, so the caller only sees
+//! the packets of its query. A server that stops answering pings is reported
+//! as a network error instead of a hang. Connections read the responses of
+//! native queries through [`read_frame_with_keepalive`].
+
+use crate::error::{Error, Result};
+use crate::protocol::{Frame, FrameDecoder, Framing, PacketType};
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
+
+/// Idle time after which a ping is sent
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Pings left unanswered before the connection is considered dead
+pub const DEFAULT_KEEPALIVE_MAX_MISSED: u32 = 3;
+
+/// Size of each read from the stream
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Ping scheduling and pong bookkeeping for one connection
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    interval: Duration,
+    max_missed: u32,
+    last_activity: Instant,
+    outstanding: u32,
+    pings_sent: u64,
+    pongs_received: u64,
+    framing: Framing,
+}
+
+impl KeepAlive {
+    /// Ping after `interval` without traffic
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            max_missed: DEFAULT_KEEPALIVE_MAX_MISSED,
+            last_activity: Instant::now(),
+            outstanding: 0,
+            pings_sent: 0,
+            pongs_received: 0,
+            framing: Framing::Native,
+        }
+    }
+
+    /// Encode pings for the framing of the connection
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Set how many pings may go unanswered before the connection is dead
+    pub fn max_missed(mut self, max_missed: u32) -> Self {
+        self.max_missed = max_missed;
+        self
+    }
+
+    /// Get the ping interval
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Get when the next ping is due
+    pub fn deadline(&self) -> Instant {
+        self.last_activity + self.interval
+    }
+
+    /// Record a packet from the server
+    ///
+    /// Returns `false` for pongs, which are consumed here.
+    pub fn on_frame(&mut self, frame: &Frame) -> bool {
+        self.last_activity = Instant::now();
+        self.outstanding = 0;
+        if frame.kind() == Some(PacketType::ServerPong) {
+            self.pongs_received += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Get the ping to send if one is due at `now`
+    ///
+    /// Fails once `max_missed` pings in a row were not answered.
+    pub fn poll(&mut self, now: Instant) -> Result<Option<Vec<u8>>> {
+        if now < self.deadline() {
+            return Ok(None);
+        }
+        if self.outstanding >= self.max_missed {
+            return Err(Error::Network(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Server did not answer {} keep-alive pings", self.outstanding),
+            )));
+        }
+        self.outstanding += 1;
+        self.pings_sent += 1;
+        self.last_activity = now;
+        Ok(Some(self.framing.ping()))
+    }
+
+    /// Get the number of pings sent
+    pub fn pings_sent(&self) -> u64 {
+        self.pings_sent
+    }
+
+    /// Get the number of pongs received
+    pub fn pongs_received(&self) -> u64 {
+        self.pongs_received
+    }
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEEPALIVE_INTERVAL)
+    }
+}
+
+/// Read the next server packet, pinging the server while the stream is idle
+///
+/// Pongs are handled here and never returned. Without a keep-alive this only
+/// reads.
+pub async fn read_frame_with_keepalive<S>(
+    stream: &mut S,
+    decoder: &mut FrameDecoder,
+    mut keepalive: Option<&mut KeepAlive>,
+) -> Result<Frame>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+    loop {
+        if let Some(frame) = decoder.decode()? {
+            if keepalive.as_deref_mut().is_none_or(|keepalive| keepalive.on_frame(&frame)) {
+                return Ok(frame);
+            }
+            continue;
+        }
+        let deadline = keepalive.as_ref().map(|keepalive| keepalive.deadline());
+        tokio::select! {
+            read = stream.read(&mut chunk) => match read? {
+                0 => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed mid-packet").into())
+                }
+                n => decoder.extend(&chunk[..n]),
+            },
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                let Some(keepalive) = keepalive.as_deref_mut() else {
+                    continue;
+                };
+                if let Some(ping) = keepalive.poll(Instant::now())? {
+                    tracing::trace!("Sending keep-alive ping");
+                    stream.write_all(&ping).await?;
+                    stream.flush().await?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::constants::DEFAULT_PROTOCOL_VERSION;
+
+    #[tokio::test]
+    async fn test_pings_idle_stream_and_consumes_pongs() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let server = tokio::spawn(async move {
+            let mut byte = [0u8; 1];
+            server.read_exact(&mut byte).await.unwrap();
+            assert_eq!(byte[0] as u64, PacketType::ClientPing.to_u64());
+            let reply = [PacketType::ServerPong.to_u64() as u8, PacketType::ServerEndOfStream.to_u64() as u8];
+            server.write_all(&reply).await.unwrap();
+            server
+        });
+
+        let mut decoder = FrameDecoder::new(DEFAULT_PROTOCOL_VERSION);
+        let mut keepalive = KeepAlive::new(Duration::from_millis(20));
+        let frame = read_frame_with_keepalive(&mut client, &mut decoder, Some(&mut keepalive)).await.unwrap();
+        assert_eq!(frame.kind(), Some(PacketType::ServerEndOfStream));
+        assert_eq!(keepalive.pings_sent(), 1);
+        assert_eq!(keepalive.pongs_received(), 1);
+        let _server = server.await.unwrap();
+
+        let mut silent = KeepAlive::new(Duration::ZERO).max_missed(1);
+        assert!(silent.poll(Instant::now()).unwrap().is_some());
+        assert!(matches!(silent.poll(Instant::now()), Err(Error::Network(_))));
+    }
+}
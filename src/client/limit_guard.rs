@@ -0,0 +1,177 @@
+//! Automatic LIMIT for interactive queries
+//!
+//! Ad-hoc tools that let users type SQL can be brought down by a single
+//! `SELECT * FROM events`. With [`QuerySettings::interactive_safe`] a SELECT
+//! without a LIMIT gets one appended. The SQL is scanned token by token, so
+//! `LIMIT` inside strings, comments or subqueries, and `LIMIT n BY`, do not
+//! count as a limit on the result.
+//!
+//! [`QuerySettings::interactive_safe`]: crate::client::QuerySettings::interactive_safe
+
+/// Rows returned by interactive queries without a LIMIT
+pub const DEFAULT_INTERACTIVE_LIMIT: u64 = 1000;
+
+/// Clauses that follow LIMIT at the end of a statement
+const TAIL_CLAUSES: [&str; 3] = ["SETTINGS", "FORMAT", "INTO"];
+
+/// Word outside quotes, comments and parentheses
+struct Word<'a> {
+    text: &'a str,
+    /// End of the last token before the word, ignoring whitespace and comments
+    after_previous: usize,
+}
+
+impl Word<'_> {
+    fn is(&self, keyword: &str) -> bool {
+        self.text.eq_ignore_ascii_case(keyword)
+    }
+}
+
+/// Append `LIMIT limit` to a SELECT that does not limit its result
+///
+/// Returns `None` when the statement is not a SELECT or already has a LIMIT,
+/// `TOP` or `FETCH`. The LIMIT goes before any trailing SETTINGS, FORMAT or
+/// INTO OUTFILE clause. Set operations are wrapped in a subquery, since a LIMIT
+/// after the last SELECT of a UNION only applies to that SELECT.
+pub fn inject_limit(sql: &str, limit: u64) -> Option<String> {
+    let (words, end) = scan(sql);
+    let first = words.first()?;
+    if !first.is("SELECT") && !first.is("WITH") {
+        return None;
+    }
+
+    let at = words
+        .iter()
+        .find(|w| TAIL_CLAUSES.iter().any(|clause| w.is(clause)))
+        .map_or(end, |w| w.after_previous);
+    let body: Vec<&Word> = words.iter().filter(|w| w.after_previous < at).collect();
+
+    let set_operation = body.iter().enumerate().any(|(i, w)| {
+        w.is("UNION")
+            || w.is("INTERSECT")
+            // `SELECT * EXCEPT (column)` is a column transformer, not a set operation
+            || (w.is("EXCEPT")
+                && body
+                    .get(i + 1)
+                    .is_some_and(|next| next.is("SELECT") || next.is("ALL") || next.is("DISTINCT")))
+    });
+    if set_operation {
+        return Some(format!("SELECT * FROM ({}) LIMIT {}{}", &sql[..at], limit, &sql[at..]));
+    }
+
+    if has_limit(&body) {
+        return None;
+    }
+    Some(format!("{} LIMIT {}{}", &sql[..at], limit, &sql[at..]))
+}
+
+fn has_limit(words: &[&Word]) -> bool {
+    words.iter().enumerate().any(|(i, w)| {
+        if w.is("FETCH") {
+            return true;
+        }
+        if w.is("TOP") {
+            return i > 0 && (words[i - 1].is("SELECT") || words[i - 1].is("DISTINCT"));
+        }
+        // `LIMIT n BY columns` limits rows per group, not the result
+        w.is("LIMIT") && !words[i + 1..].iter().take_while(|next| !next.is("LIMIT")).any(|next| next.is("BY"))
+    })
+}
+
+/// Split SQL into top-level words and find the end of its last token
+fn scan(sql: &str) -> (Vec<Word<'_>>, usize) {
+    let bytes = sql.as_bytes();
+    let mut words = Vec::new();
+    let mut depth = 0usize;
+    let mut end = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = sql[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = sql[i + 2..].find("*/").map_or(bytes.len(), |n| i + n + 4);
+                continue;
+            }
+            b'\'' | b'"' | b'`' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(bytes.len());
+                end = i;
+                continue;
+            }
+            b';' => {
+                i += 1;
+                continue;
+            }
+            _ if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            _ if b.is_ascii_alphanumeric() || b == b'_' => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                if depth == 0 {
+                    words.push(Word { text: &sql[start..i], after_previous: end });
+                }
+                end = i;
+                continue;
+            }
+            b'(' | b'[' => depth += 1,
+            b')' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        i += 1;
+        end = i;
+    }
+    (words, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_limit() {
+        let limit = |sql: &str| inject_limit(sql, 100);
+        assert_eq!(limit("SELECT * FROM t").unwrap(), "SELECT * FROM t LIMIT 100");
+        assert_eq!(limit("select * from t;").unwrap(), "select * from t LIMIT 100;");
+        assert_eq!(
+            limit("SELECT * FROM t -- LIMIT 5").unwrap(),
+            "SELECT * FROM t LIMIT 100 -- LIMIT 5"
+        );
+        assert_eq!(
+            limit("SELECT 'LIMIT 1', (SELECT x FROM u LIMIT 1) FROM t FORMAT JSON").unwrap(),
+            "SELECT 'LIMIT 1', (SELECT x FROM u LIMIT 1) FROM t LIMIT 100 FORMAT JSON"
+        );
+        assert_eq!(
+            limit("SELECT * FROM t LIMIT 1 BY user SETTINGS max_threads = 1").unwrap(),
+            "SELECT * FROM t LIMIT 1 BY user LIMIT 100 SETTINGS max_threads = 1"
+        );
+        assert_eq!(
+            limit("SELECT 1 UNION ALL SELECT 2 LIMIT 1").unwrap(),
+            "SELECT * FROM (SELECT 1 UNION ALL SELECT 2 LIMIT 1) LIMIT 100"
+        );
+        assert_eq!(
+            limit("SELECT * EXCEPT (id) FROM t").unwrap(),
+            "SELECT * EXCEPT (id) FROM t LIMIT 100"
+        );
+
+        assert_eq!(limit("SELECT * FROM t LIMIT 10"), None);
+        assert_eq!(limit("SELECT * FROM t LIMIT 1 BY user LIMIT 10 OFFSET 5"), None);
+        assert_eq!(limit("WITH x AS (SELECT 1) SELECT * FROM x LIMIT 5, 10"), None);
+        assert_eq!(limit("SELECT TOP 5 * FROM t"), None);
+        assert_eq!(limit("INSERT INTO t SELECT * FROM u"), None);
+        assert_eq!(limit("SHOW TABLES"), None);
+    }
+}
@@ -2,7 +2,11 @@
 
 use crate::error::{Error, Result};
 use crate::client::ClientOptions;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
 use tokio::sync::RwLock;
 use std::time::{Duration, Instant};
 use tracing::{debug, warn};
@@ -26,6 +30,20 @@ pub struct ServerInfo {
     pub active_connections: usize,
     /// Maximum connections allowed
     pub max_connections: usize,
+    /// Zone or datacenter the server is located in
+    pub zone: Option<String>,
+    /// Failover group; lower values are preferred while any of their servers is available
+    pub priority: u32,
+    /// Exponentially weighted moving average of observed latencies
+    pub latency_ewma: Option<Duration>,
+    /// Health check overriding the load balancer default for this server
+    pub health_check: Option<HealthCheckKind>,
+    /// Number of consecutive failed health checks
+    pub consecutive_failures: usize,
+    /// Number of consecutive successful health checks
+    pub consecutive_successes: usize,
+    /// Earliest time the next health check should run (used for backoff)
+    pub next_health_check: Option<Instant>,
 }
 
 impl ServerInfo {
@@ -40,6 +58,13 @@ impl ServerInfo {
             response_time: None,
             active_connections: 0,
             max_connections: 100,
+            zone: None,
+            priority: 0,
+            latency_ewma: None,
+            health_check: None,
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            next_health_check: None,
         }
     }
 
@@ -55,6 +80,29 @@ impl ServerInfo {
         self
     }
 
+    /// Set the zone or datacenter of the server
+    pub fn zone(mut self, zone: impl Into<String>) -> Self {
+        self.zone = Some(zone.into());
+        self
+    }
+
+    /// Set the failover priority of the server (0 is the primary group)
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set a health check for this server, overriding the default
+    pub fn health_check(mut self, health_check: HealthCheckKind) -> Self {
+        self.health_check = Some(health_check);
+        self
+    }
+
+    /// Check if the server is located in the given zone
+    pub fn in_zone(&self, zone: &str) -> bool {
+        self.zone.as_deref() == Some(zone)
+    }
+
     /// Check if server can accept new connections
     pub fn can_accept_connections(&self) -> bool {
         self.healthy && self.active_connections < self.max_connections
@@ -80,6 +128,45 @@ impl ServerInfo {
         self.response_time = Some(response_time);
     }
 
+    /// Fold a latency sample into the EWMA using the given smoothing factor
+    pub fn record_latency(&mut self, latency: Duration, alpha: f64) {
+        let alpha = alpha.clamp(0.0, 1.0);
+        self.latency_ewma = Some(match self.latency_ewma {
+            Some(current) => {
+                let smoothed = alpha * latency.as_secs_f64() + (1.0 - alpha) * current.as_secs_f64();
+                Duration::from_secs_f64(smoothed)
+            }
+            None => latency,
+        });
+    }
+
+    /// Record the outcome of a health check, applying thresholds and backoff
+    pub fn record_health_check(&mut self, passed: bool, config: &HealthCheckConfig) {
+        let now = Instant::now();
+        self.last_health_check = Some(now);
+
+        if passed {
+            self.consecutive_failures = 0;
+            self.consecutive_successes = self.consecutive_successes.saturating_add(1);
+            if !self.healthy && self.consecutive_successes >= config.success_threshold {
+                self.healthy = true;
+            }
+            self.next_health_check = None;
+        } else {
+            self.consecutive_successes = 0;
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+            if self.healthy && self.consecutive_failures >= config.failure_threshold {
+                self.healthy = false;
+            }
+            self.next_health_check = Some(now + config.backoff_delay(self.consecutive_failures));
+        }
+    }
+
+    /// Check if a health check is due for this server
+    pub fn health_check_due(&self) -> bool {
+        !matches!(self.next_health_check, Some(at) if Instant::now() < at)
+    }
+
     /// Increment active connections
     pub fn increment_connections(&mut self) {
         self.active_connections = self.active_connections.saturating_add(1);
@@ -103,6 +190,15 @@ pub enum LoadBalancingStrategy {
     FastestResponse,
     /// Random: randomly select a server
     Random,
+    /// Latency-weighted: randomly select a server with probability inversely
+    /// proportional to its EWMA latency
+    LatencyWeighted,
+    /// Zone-aware: prefer servers in the local zone, spilling to remote
+    /// zones only when no local server is available
+    ZoneAware {
+        /// Zone the client is running in
+        local_zone: String,
+    },
     /// Custom strategy
     Custom(Box<dyn Fn(&[ServerInfo]) -> Option<usize> + Send + Sync>),
 }
@@ -121,6 +217,10 @@ impl Clone for LoadBalancingStrategy {
             LoadBalancingStrategy::LeastConnections => LoadBalancingStrategy::LeastConnections,
             LoadBalancingStrategy::FastestResponse => LoadBalancingStrategy::FastestResponse,
             LoadBalancingStrategy::Random => LoadBalancingStrategy::Random,
+            LoadBalancingStrategy::LatencyWeighted => LoadBalancingStrategy::LatencyWeighted,
+            LoadBalancingStrategy::ZoneAware { local_zone } => LoadBalancingStrategy::ZoneAware {
+                local_zone: local_zone.clone(),
+            },
             LoadBalancingStrategy::Custom(_) => LoadBalancingStrategy::RoundRobin, // Can't clone custom functions
         }
     }
@@ -134,10 +234,68 @@ pub struct LoadBalancer {
     strategy: LoadBalancingStrategy,
     /// Current round-robin index
     round_robin_index: Arc<RwLock<usize>>,
+    /// Connection options used as a template for SQL health probes
+    probe_options: Option<ClientOptions>,
     /// Health check configuration
     health_check_config: HealthCheckConfig,
     /// Health check background task handle
     health_check_handle: Option<tokio::task::JoinHandle<()>>,
+    /// Smoothing factor for per-server latency EWMA (0.0 to 1.0)
+    latency_alpha: f64,
+}
+
+/// Default smoothing factor for per-server latency EWMA
+pub const DEFAULT_LATENCY_ALPHA: f64 = 0.3;
+
+/// Future returned by a custom health check
+pub type HealthCheckFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// Health check probe performed against a server
+#[derive(Clone, Default)]
+pub enum HealthCheckKind {
+    /// Open a TCP connection to the server port
+    #[default]
+    TcpConnect,
+    /// Run a SQL probe query (e.g. `SELECT 1`) over a fresh connection
+    SqlProbe {
+        /// Query to execute
+        query: String,
+    },
+    /// Issue an HTTP GET against the server's `/ping` style endpoint
+    HttpPing {
+        /// HTTP port of the server
+        port: u16,
+        /// Request path
+        path: String,
+    },
+    /// Custom health check function
+    Custom(Arc<dyn Fn(&ServerInfo) -> HealthCheckFuture + Send + Sync>),
+}
+
+impl HealthCheckKind {
+    /// SQL probe running `SELECT 1`
+    pub fn select_one() -> Self {
+        HealthCheckKind::SqlProbe { query: "SELECT 1".to_string() }
+    }
+
+    /// HTTP probe against `/ping` on the default HTTP port
+    pub fn http_ping() -> Self {
+        HealthCheckKind::HttpPing { port: 8123, path: "/ping".to_string() }
+    }
+}
+
+impl std::fmt::Debug for HealthCheckKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthCheckKind::TcpConnect => write!(f, "TcpConnect"),
+            HealthCheckKind::SqlProbe { query } => f.debug_struct("SqlProbe").field("query", query).finish(),
+            HealthCheckKind::HttpPing { port, path } => f.debug_struct("HttpPing")
+                .field("port", port)
+                .field("path", path)
+                .finish(),
+            HealthCheckKind::Custom(_) => write!(f, "Custom"),
+        }
+    }
 }
 
 /// Health check configuration
@@ -145,50 +303,164 @@ pub struct LoadBalancer {
 pub struct HealthCheckConfig {
     /// Whether to enable health checks
     pub enabled: bool,
+    /// Default health check probe
+    pub kind: HealthCheckKind,
     /// Health check interval
     pub interval: Duration,
+    /// Maximum random jitter added to each interval
+    pub jitter: Duration,
     /// Health check timeout
     pub timeout: Duration,
     /// Number of consecutive failures before marking server as unhealthy
     pub failure_threshold: usize,
     /// Number of consecutive successes before marking server as healthy
     pub success_threshold: usize,
+    /// Backoff multiplier applied per consecutive failure
+    pub backoff_multiplier: f64,
+    /// Maximum delay between checks of a failing server
+    pub max_backoff: Duration,
 }
 
 impl Default for HealthCheckConfig {
     fn default() -> Self {
         Self {
             enabled: false, // Disable by default to prevent hanging in tests
+            kind: HealthCheckKind::default(),
             interval: Duration::from_secs(30),
+            jitter: Duration::from_secs(0),
             timeout: Duration::from_secs(5),
             failure_threshold: 3,
             success_threshold: 2,
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(300),
         }
     }
 }
 
+impl HealthCheckConfig {
+    /// Create a new health check configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable health checks
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Set the default health check probe
+    pub fn kind(mut self, kind: HealthCheckKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Set the health check interval
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the maximum jitter added to the interval
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the health check timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the failure threshold
+    pub fn failure_threshold(mut self, threshold: usize) -> Self {
+        self.failure_threshold = threshold;
+        self
+    }
+
+    /// Set the success threshold
+    pub fn success_threshold(mut self, threshold: usize) -> Self {
+        self.success_threshold = threshold;
+        self
+    }
+
+    /// Set the backoff multiplier and cap for failing servers
+    ///
+    /// # Panics
+    ///
+    /// Panics if `multiplier` is not finite.
+    pub fn backoff(mut self, multiplier: f64, max_backoff: Duration) -> Self {
+        assert!(multiplier.is_finite(), "Health check backoff multiplier must be finite, got {}", multiplier);
+        self.backoff_multiplier = multiplier;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Delay before re-checking a server after the given number of consecutive failures
+    pub fn backoff_delay(&self, consecutive_failures: usize) -> Duration {
+        let exponent = consecutive_failures.saturating_sub(1).min(32) as i32;
+        let cap = self.max_backoff.max(self.interval);
+        // Capped in seconds first, as the uncapped delay can overflow a `Duration`
+        let seconds = self.interval.as_secs_f64() * self.backoff_multiplier.max(1.0).powi(exponent);
+        Duration::try_from_secs_f64(seconds.min(cap.as_secs_f64())).unwrap_or(cap)
+    }
+
+    /// Interval until the next health check round, including jitter
+    fn next_interval(&self) -> Duration {
+        if self.jitter.is_zero() {
+            return self.interval;
+        }
+        use rand::Rng;
+        let jitter_nanos = rand::thread_rng().gen_range(0..=self.jitter.as_nanos() as u64);
+        self.interval + Duration::from_nanos(jitter_nanos)
+    }
+}
+
 impl LoadBalancer {
     /// Create a new load balancer
     pub fn new(servers: Vec<ServerInfo>, strategy: LoadBalancingStrategy) -> Self {
-        let health_check_config = HealthCheckConfig::default();
+        Self::with_health_check_config(servers, strategy, HealthCheckConfig::default())
+    }
+
+    /// Create a new load balancer with a custom health check configuration
+    pub fn with_health_check_config(
+        servers: Vec<ServerInfo>,
+        strategy: LoadBalancingStrategy,
+        health_check_config: HealthCheckConfig,
+    ) -> Self {
+        Self::build(servers, strategy, health_check_config, None)
+    }
+
+    /// Create a load balancer and start its health checks
+    fn build(
+        servers: Vec<ServerInfo>,
+        strategy: LoadBalancingStrategy,
+        health_check_config: HealthCheckConfig,
+        probe_options: Option<ClientOptions>,
+    ) -> Self {
         let mut load_balancer = Self {
             servers: Arc::new(RwLock::new(servers)),
             strategy,
             round_robin_index: Arc::new(RwLock::new(0)),
-            health_check_config: health_check_config.clone(),
+            probe_options,
+            health_check_config,
             health_check_handle: None,
+            latency_alpha: DEFAULT_LATENCY_ALPHA,
         };
+        load_balancer.start_health_checks();
+        load_balancer
+    }
 
-        // Start health check background task if enabled
-        if health_check_config.enabled {
-            let lb = load_balancer.clone();
+    /// Start the health check background task if enabled
+    fn start_health_checks(&mut self) {
+        if self.health_check_config.enabled {
+            let lb = self.clone();
             let handle = tokio::spawn(async move {
                 lb.run_health_checks().await;
             });
-            load_balancer.health_check_handle = Some(handle);
+            self.health_check_handle = Some(handle);
         }
-
-        load_balancer
     }
 
     /// Create a new load balancer from client options
@@ -198,7 +470,13 @@ impl LoadBalancer {
         }
 
         let servers = options.servers.iter()
-            .map(|server| ServerInfo::new(server.host.clone(), server.port))
+            .map(|server| {
+                let mut info = ServerInfo::new(server.host.clone(), server.port)
+                    .weight(server.weight)
+                    .priority(server.priority);
+                info.zone = server.zone.clone();
+                info
+            })
             .collect();
 
         // Convert from options::LoadBalancingStrategy to load_balancer::LoadBalancingStrategy
@@ -207,9 +485,27 @@ impl LoadBalancer {
             crate::client::options::LoadBalancingStrategy::WeightedRoundRobin => LoadBalancingStrategy::WeightedRoundRobin,
             crate::client::options::LoadBalancingStrategy::LeastConnections => LoadBalancingStrategy::LeastConnections,
             crate::client::options::LoadBalancingStrategy::Random => LoadBalancingStrategy::Random,
+            crate::client::options::LoadBalancingStrategy::LatencyWeighted => LoadBalancingStrategy::LatencyWeighted,
+            crate::client::options::LoadBalancingStrategy::ZoneAware => {
+                let local_zone = options.local_zone.clone().ok_or_else(|| {
+                    Error::Configuration("Zone-aware load balancing requires a local zone".to_string())
+                })?;
+                LoadBalancingStrategy::ZoneAware { local_zone }
+            }
         };
 
-        Ok(Self::new(servers, strategy))
+        let health_check_config = HealthCheckConfig::default()
+            .enabled(options.use_health_checks)
+            .interval(options.health_check_interval)
+            .timeout(options.connect_timeout);
+
+        Ok(Self::build(servers, strategy, health_check_config, Some(options.clone())))
+    }
+
+    /// Set the smoothing factor used for per-server latency EWMA
+    pub fn with_latency_alpha(mut self, alpha: f64) -> Self {
+        self.latency_alpha = alpha.clamp(0.0, 1.0);
+        self
     }
 
     /// Get the next server based on the load balancing strategy
@@ -222,17 +518,18 @@ impl LoadBalancer {
                 return Err(Error::Configuration("No servers available".to_string()));
             }
 
-            // Filter healthy servers that can accept connections
-            let available: Vec<ServerInfo> = servers.iter()
+            // Filter healthy servers that can accept connections, keeping only
+            // the most preferred priority group that still has one
+            let priority = servers.iter()
                 .filter(|s| s.can_accept_connections())
-                .cloned()
-                .collect();
+                .map(|s| s.priority)
+                .min()
+                .ok_or_else(|| Error::ConnectionPool("No healthy servers available".to_string()))?;
 
-            if available.is_empty() {
-                return Err(Error::ConnectionPool("No healthy servers available".to_string()));
-            }
-
-            available
+            servers.iter()
+                .filter(|s| s.priority == priority && s.can_accept_connections())
+                .cloned()
+                .collect::<Vec<ServerInfo>>()
         };
 
         let server_index = match &self.strategy {
@@ -256,6 +553,12 @@ impl LoadBalancer {
                 let mut rng = rand::thread_rng();
                 rng.gen_range(0..available_servers.len())
             }
+            LoadBalancingStrategy::LatencyWeighted => {
+                self.select_latency_weighted(&available_servers.iter().collect::<Vec<_>>())
+            }
+            LoadBalancingStrategy::ZoneAware { local_zone } => {
+                self.select_zone_aware(&available_servers.iter().collect::<Vec<_>>(), local_zone).await
+            }
             LoadBalancingStrategy::Custom(func) => {
                 if let Some(selected) = func(&available_servers) {
                     selected
@@ -337,61 +640,157 @@ impl LoadBalancer {
             .unwrap_or(0)
     }
 
+    /// Select server randomly, weighted by the inverse of its latency EWMA
+    ///
+    /// Servers without any latency samples are treated as being as fast as
+    /// the fastest known server so that they get probed.
+    fn select_latency_weighted(&self, servers: &[&ServerInfo]) -> usize {
+        let fastest = servers.iter()
+            .filter_map(|s| s.latency_ewma)
+            .min()
+            .unwrap_or(Duration::from_millis(1))
+            .max(Duration::from_micros(1));
+
+        let weights: Vec<f64> = servers.iter()
+            .map(|s| {
+                let latency = s.latency_ewma.unwrap_or(fastest).max(Duration::from_micros(1));
+                1.0 / latency.as_secs_f64()
+            })
+            .collect();
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 || !total.is_finite() {
+            return 0;
+        }
+
+        use rand::Rng;
+        let mut target = rand::thread_rng().gen_range(0.0..total);
+        for (i, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                return i;
+            }
+            target -= weight;
+        }
+        weights.len() - 1
+    }
+
+    /// Select server preferring the local zone, round-robin within the chosen set
+    async fn select_zone_aware(&self, servers: &[&ServerInfo], local_zone: &str) -> usize {
+        let local: Vec<usize> = servers.iter()
+            .enumerate()
+            .filter(|(_, s)| s.in_zone(local_zone))
+            .map(|(i, _)| i)
+            .collect();
+
+        let candidates: Vec<usize> = if local.is_empty() {
+            debug!("No servers available in zone {}, spilling to remote zones", local_zone);
+            (0..servers.len()).collect()
+        } else {
+            local
+        };
+
+        let mut index = self.round_robin_index.write().await;
+        let selected = candidates[*index % candidates.len()];
+        *index = index.wrapping_add(1);
+        selected
+    }
+
+    /// Record a latency sample (e.g. a query timing) for a server
+    pub async fn record_latency(&self, server: &ServerInfo, latency: Duration) {
+        self.record_server_latency(&server.host, server.port, latency).await;
+    }
+
+    /// Record a latency sample for the server at `host:port`, if it is balanced
+    pub async fn record_server_latency(&self, host: &str, port: u16, latency: Duration) {
+        let mut servers = self.servers.write().await;
+        if let Some(server_mut) = servers.iter_mut()
+            .find(|s| s.host == host && s.port == port) {
+            server_mut.record_latency(latency, self.latency_alpha);
+        }
+    }
+
     /// Run health checks on all servers
     async fn run_health_checks(&self) {
-        let mut interval = tokio::time::interval(self.health_check_config.interval);
-        
         loop {
-            interval.tick().await;
-            
+            tokio::time::sleep(self.health_check_config.next_interval()).await;
+            self.check_all_servers().await;
+        }
+    }
+
+    /// Run one round of health checks against every server that is due
+    ///
+    /// Servers that keep failing are checked less often according to the
+    /// configured exponential backoff.
+    pub async fn check_all_servers(&self) {
+        let due: Vec<ServerInfo> = {
+            let servers = self.servers.read().await;
+            servers.iter().filter(|s| s.health_check_due()).cloned().collect()
+        };
+
+        for server in due {
+            let result = self.check_server_health(&server).await;
+            if let Err(e) = &result {
+                warn!("Health check failed for {}:{} - {}", server.host, server.port, e);
+            }
+
             let mut servers = self.servers.write().await;
-            for server in servers.iter_mut() {
-                if let Err(e) = self.check_server_health(server).await {
-                    warn!("Health check failed for {}:{} - {}", server.host, server.port, e);
+            if let Some(server_mut) = servers.iter_mut()
+                .find(|s| s.host == server.host && s.port == server.port) {
+                if let Ok(response_time) = result {
+                    server_mut.update_response_time(response_time);
+                    server_mut.record_latency(response_time, self.latency_alpha);
                 }
+                server_mut.record_health_check(result.is_ok(), &self.health_check_config);
             }
         }
     }
 
-    /// Check health of a specific server
-    async fn check_server_health(&self, server: &mut ServerInfo) -> Result<()> {
+    /// Check health of a specific server, returning its response time
+    async fn check_server_health(&self, server: &ServerInfo) -> Result<Duration> {
         let start_time = Instant::now();
-        
-        // Simple ping health check
+        let kind = server.health_check.as_ref().unwrap_or(&self.health_check_config.kind);
+
         let result = tokio::time::timeout(
             self.health_check_config.timeout,
-            self.ping_server(server)
+            self.probe_server(server, kind)
         ).await;
 
         match result {
-            Ok(Ok(_)) => {
+            Ok(Ok(())) => {
                 let response_time = start_time.elapsed();
-                server.update_response_time(response_time);
-                server.update_health(true);
-                debug!("Health check passed for {}:{} - response time: {:?}", 
+                debug!("Health check passed for {}:{} - response time: {:?}",
                        server.host, server.port, response_time);
+                Ok(response_time)
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(Error::Timeout(self.health_check_config.timeout)),
+        }
+    }
+
+    /// Run a health check probe against a server
+    async fn probe_server(&self, server: &ServerInfo, kind: &HealthCheckKind) -> Result<()> {
+        match kind {
+            HealthCheckKind::TcpConnect => {
+                TcpStream::connect((server.host.as_str(), server.port)).await?;
                 Ok(())
             }
-            Ok(Err(e)) => {
-                server.update_health(false);
-                Err(e)
+            HealthCheckKind::SqlProbe { query } => {
+                let options = self.probe_options.clone()
+                    .unwrap_or_default()
+                    .host(server.host.clone())
+                    .port(server.port);
+                let mut connection = crate::client::Connection::new(options);
+                let result = connection.query(query).await.map(|_| ());
+                let _ = connection.disconnect().await;
+                result
             }
-            Err(_) => {
-                server.update_health(false);
-                Err(Error::Timeout(self.health_check_config.timeout))
+            HealthCheckKind::HttpPing { port, path } => {
+                http_ping(&server.host, *port, path).await
             }
+            HealthCheckKind::Custom(check) => check(server).await,
         }
     }
 
-    /// Ping a server to check health
-    async fn ping_server(&self, _server: &ServerInfo) -> Result<()> {
-        // This is a placeholder implementation
-        // In a real implementation, you would establish a connection and send a ping
-        // For now, we'll simulate a successful ping
-        tokio::time::sleep(Duration::from_millis(1)).await;
-        Ok(())
-    }
-
     /// Get load balancer statistics
     pub async fn get_stats(&self) -> LoadBalancerStats {
         let servers = self.servers.read().await;
@@ -433,6 +832,11 @@ impl LoadBalancer {
         servers.retain(|s| !(s.host == host && s.port == port));
     }
 
+    /// Get the health check configuration
+    pub fn health_check_config(&self) -> &HealthCheckConfig {
+        &self.health_check_config
+    }
+
     /// Stop health checks
     pub async fn stop_health_checks(&mut self) {
         if let Some(handle) = self.health_check_handle.take() {
@@ -447,10 +851,46 @@ impl Clone for LoadBalancer {
             servers: Arc::clone(&self.servers),
             strategy: self.strategy.clone(),
             round_robin_index: Arc::clone(&self.round_robin_index),
+            probe_options: self.probe_options.clone(),
             health_check_config: self.health_check_config.clone(),
             health_check_handle: None, // Don't clone the running task
+            latency_alpha: self.latency_alpha,
+        }
+    }
+}
+
+/// Issue a minimal HTTP/1.1 GET and require a 200 response
+pub(crate) async fn http_ping(host: &str, port: u16, path: &str) -> Result<()> {
+    let mut stream = TcpStream::connect((host, port)).await?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\n\r\n",
+        path, host, port
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 512];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 16];
+        let mut parsed = httparse::Response::new(&mut headers);
+        if let Ok(httparse::Status::Complete(_)) = parsed.parse(&response) {
+            return match parsed.code {
+                Some(200) => Ok(()),
+                code => Err(Error::Http {
+                    status: code.unwrap_or(0),
+                    message: format!("Health check {} failed", path),
+                }),
+            };
         }
     }
+
+    Err(Error::Protocol("Incomplete HTTP health check response".to_string()))
 }
 
 /// Load balancer statistics
@@ -545,6 +985,20 @@ mod tests {
         assert_eq!(stats.healthy_servers, 3);
     }
 
+    #[tokio::test]
+    async fn test_load_balancer_from_options() {
+        let options = ClientOptions::new()
+            .add_server(crate::client::options::ServerInfo::new("a", 9000))
+            .add_server(crate::client::options::ServerInfo::new("b", 9000))
+            .enable_health_checks();
+        let mut lb = LoadBalancer::from_options(&options).unwrap();
+
+        assert_eq!(lb.get_stats().await.total_servers, 2);
+        assert!(lb.probe_options.is_some());
+        assert!(lb.health_check_handle.is_some());
+        lb.stop_health_checks().await;
+    }
+
     #[tokio::test]
     async fn test_load_balancer_round_robin() {
         tokio::time::timeout(Duration::from_secs(10), async {
@@ -581,6 +1035,87 @@ mod tests {
         }).await.expect("Test timed out after 10 seconds");
     }
 
+    #[test]
+    fn test_server_info_latency_ewma() {
+        let mut server = ServerInfo::new("test".to_string(), 9000);
+        assert_eq!(server.latency_ewma, None);
+
+        server.record_latency(Duration::from_millis(100), 0.5);
+        assert_eq!(server.latency_ewma, Some(Duration::from_millis(100)));
+
+        server.record_latency(Duration::from_millis(200), 0.5);
+        assert_eq!(server.latency_ewma, Some(Duration::from_millis(150)));
+    }
+
+    #[tokio::test]
+    async fn test_load_balancer_latency_weighted() {
+        let servers = vec![
+            ServerInfo::new("fast".to_string(), 9000),
+            ServerInfo::new("slow".to_string(), 9001),
+        ];
+        let lb = LoadBalancer::new(servers.clone(), LoadBalancingStrategy::LatencyWeighted);
+        lb.record_latency(&servers[0], Duration::from_millis(1)).await;
+        lb.record_latency(&servers[1], Duration::from_secs(10)).await;
+        lb.record_server_latency("slow", 9001, Duration::from_secs(10)).await;
+        lb.record_server_latency("unknown", 9001, Duration::from_millis(1)).await;
+
+        let mut fast_count = 0;
+        for _ in 0..100 {
+            let server = lb.get_server().await.unwrap();
+            if server.host == "fast" {
+                fast_count += 1;
+            }
+            lb.release_server(&server).await;
+        }
+        assert!(fast_count > 90);
+    }
+
+    #[tokio::test]
+    async fn test_load_balancer_zone_aware() {
+        let servers = vec![
+            ServerInfo::new("remote".to_string(), 9000).zone("us-west"),
+            ServerInfo::new("local1".to_string(), 9001).zone("us-east"),
+            ServerInfo::new("local2".to_string(), 9002).zone("us-east"),
+        ];
+        let strategy = LoadBalancingStrategy::ZoneAware { local_zone: "us-east".to_string() };
+        let lb = LoadBalancer::new(servers, strategy);
+
+        for _ in 0..4 {
+            let server = lb.get_server().await.unwrap();
+            assert!(server.in_zone("us-east"));
+            lb.release_server(&server).await;
+        }
+
+        lb.remove_server("local1", 9001).await;
+        lb.remove_server("local2", 9002).await;
+
+        let server = lb.get_server().await.unwrap();
+        assert_eq!(server.host, "remote");
+    }
+
+    #[tokio::test]
+    async fn test_load_balancer_priority_groups() {
+        let servers = vec![
+            ServerInfo::new("fallback".to_string(), 9000).priority(1),
+            ServerInfo::new("a".to_string(), 9000),
+            ServerInfo::new("b".to_string(), 9000),
+        ];
+        let lb = LoadBalancer::new(servers, LoadBalancingStrategy::RoundRobin);
+
+        for _ in 0..4 {
+            let server = lb.get_server().await.unwrap();
+            assert_eq!(server.priority, 0);
+            lb.release_server(&server).await;
+        }
+
+        lb.remove_server("a", 9000).await;
+        assert_eq!(lb.get_server().await.unwrap().host, "b");
+        lb.remove_server("b", 9000).await;
+        assert_eq!(lb.get_server().await.unwrap().host, "fallback");
+        lb.add_server(ServerInfo::new("a".to_string(), 9000)).await;
+        assert_eq!(lb.get_server().await.unwrap().host, "a");
+    }
+
     #[test]
     fn test_health_check_config_default() {
         let config = HealthCheckConfig::default();
@@ -589,6 +1124,107 @@ mod tests {
         assert_eq!(config.timeout, Duration::from_secs(5));
         assert_eq!(config.failure_threshold, 3);
         assert_eq!(config.success_threshold, 2);
+        assert!(matches!(config.kind, HealthCheckKind::TcpConnect));
+    }
+
+    #[test]
+    fn test_health_check_backoff_delay() {
+        let config = HealthCheckConfig::new()
+            .interval(Duration::from_secs(1))
+            .backoff(2.0, Duration::from_secs(5));
+
+        assert_eq!(config.backoff_delay(1), Duration::from_secs(1));
+        assert_eq!(config.backoff_delay(2), Duration::from_secs(2));
+        assert_eq!(config.backoff_delay(3), Duration::from_secs(4));
+        assert_eq!(config.backoff_delay(10), Duration::from_secs(5));
+
+        // Large multipliers hit the cap instead of overflowing
+        let config = HealthCheckConfig::new()
+            .interval(Duration::from_secs(10))
+            .backoff(4.0, Duration::from_secs(300));
+        assert_eq!(config.backoff_delay(40), Duration::from_secs(300));
+        let config = HealthCheckConfig { backoff_multiplier: f64::NAN, ..config };
+        assert_eq!(config.backoff_delay(40), Duration::from_secs(10));
+        assert!(std::panic::catch_unwind(|| HealthCheckConfig::new().backoff(f64::INFINITY, Duration::from_secs(1))).is_err());
+    }
+
+    #[test]
+    fn test_server_info_health_thresholds() {
+        let config = HealthCheckConfig::new().failure_threshold(2).success_threshold(2);
+        let mut server = ServerInfo::new("test".to_string(), 9000);
+
+        server.record_health_check(false, &config);
+        assert!(server.healthy);
+        assert!(!server.health_check_due());
+
+        server.record_health_check(false, &config);
+        assert!(!server.healthy);
+
+        server.record_health_check(true, &config);
+        assert!(!server.healthy);
+        server.record_health_check(true, &config);
+        assert!(server.healthy);
+        assert!(server.health_check_due());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_health_check() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let config = HealthCheckConfig::new().failure_threshold(1);
+        let servers = vec![ServerInfo::new("127.0.0.1".to_string(), port)];
+        let lb = LoadBalancer::with_health_check_config(servers, LoadBalancingStrategy::RoundRobin, config);
+
+        lb.check_all_servers().await;
+        let stats = lb.get_stats().await;
+        assert_eq!(stats.healthy_servers, 1);
+
+        drop(listener);
+        lb.check_all_servers().await;
+        let stats = lb.get_stats().await;
+        assert_eq!(stats.healthy_servers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_http_ping_health_check() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nOk.\n").await;
+        });
+
+        let server = ServerInfo::new("127.0.0.1".to_string(), 9000)
+            .health_check(HealthCheckKind::HttpPing { port, path: "/ping".to_string() });
+        let config = HealthCheckConfig::new().failure_threshold(1);
+        let lb = LoadBalancer::with_health_check_config(vec![server], LoadBalancingStrategy::RoundRobin, config);
+
+        lb.check_all_servers().await;
+        let stats = lb.get_stats().await;
+        assert_eq!(stats.healthy_servers, 1);
+    }
+
+    #[tokio::test]
+    async fn test_custom_health_check() {
+        let check = HealthCheckKind::Custom(Arc::new(|server: &ServerInfo| {
+            let healthy = server.host == "good";
+            Box::pin(async move {
+                if healthy { Ok(()) } else { Err(Error::Custom("down".to_string())) }
+            })
+        }));
+        let config = HealthCheckConfig::new().kind(check).failure_threshold(1);
+        let servers = vec![
+            ServerInfo::new("good".to_string(), 9000),
+            ServerInfo::new("bad".to_string(), 9001),
+        ];
+        let lb = LoadBalancer::with_health_check_config(servers, LoadBalancingStrategy::RoundRobin, config);
+
+        lb.check_all_servers().await;
+        let server = lb.get_server().await.unwrap();
+        assert_eq!(server.host, "good");
     }
 
     #[test]
@@ -0,0 +1,209 @@
+//! Client-wide budget for buffered query results, and per-query watchdogs
+//! on the memory the server reports
+
+use crate::error::{Error, Result};
+use crate::protocol::ServerProgress;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+#[derive(Debug)]
+struct BudgetState {
+    limit: Option<usize>,
+    usage: AtomicUsize,
+    released: Notify,
+}
+
+/// Memory budget shared by every result buffered in a client
+///
+/// Streams reserve an estimate of the next block before reading it, and
+/// blocks they hold are charged to the budget until they are handed to the
+/// caller. While the total is over the limit, streams wait before reading.
+/// A single result larger than the whole budget is still let through once
+/// nothing else is buffered, so it cannot wait forever.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    state: Arc<BudgetState>,
+}
+
+impl MemoryBudget {
+    /// Create a budget; `None` tracks usage without ever pausing
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            state: Arc::new(BudgetState {
+                limit,
+                usage: AtomicUsize::new(0),
+                released: Notify::new(),
+            }),
+        }
+    }
+
+    /// Get the configured limit in bytes
+    pub fn limit(&self) -> Option<usize> {
+        self.state.limit
+    }
+
+    /// Get the bytes currently charged to the budget
+    pub fn usage(&self) -> usize {
+        self.state.usage.load(Ordering::Acquire)
+    }
+
+    /// Check whether buffered results exceed the limit
+    pub fn is_exhausted(&self) -> bool {
+        self.state.limit.is_some_and(|limit| self.usage() >= limit)
+    }
+
+    /// Wait until buffered results fit within the limit again
+    pub async fn wait_for_capacity(&self) {
+        loop {
+            let released = self.state.released.notified();
+            if !self.is_exhausted() {
+                return;
+            }
+            tracing::debug!(
+                "Result memory budget exhausted ({} bytes buffered), pausing",
+                self.usage()
+            );
+            released.await;
+        }
+    }
+
+    /// Wait for capacity, then charge `bytes` to the budget
+    pub async fn acquire(&self, bytes: usize) -> MemoryReservation {
+        self.wait_for_capacity().await;
+        self.reserve(bytes)
+    }
+
+    /// Charge `bytes` to the budget until the reservation is dropped
+    pub fn reserve(&self, bytes: usize) -> MemoryReservation {
+        self.state.usage.fetch_add(bytes, Ordering::AcqRel);
+        MemoryReservation {
+            budget: self.clone(),
+            bytes,
+        }
+    }
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Bytes charged to a [`MemoryBudget`], released on drop
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: MemoryBudget,
+    bytes: usize,
+}
+
+impl MemoryReservation {
+    /// Get the number of reserved bytes
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget.state.usage.fetch_sub(self.bytes, Ordering::AcqRel);
+        self.budget.state.released.notify_waiters();
+    }
+}
+
+/// Client-side bound on the server memory used by one query
+///
+/// Progress packets carry the query's current and peak memory use. Checking
+/// them against a bound lower than the server's `max_memory_usage` stops a
+/// runaway query within one progress interval, before it takes memory from
+/// other queries on the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWatchdog {
+    limit: u64,
+    peak: u64,
+}
+
+impl MemoryWatchdog {
+    /// Watch for memory use above `limit` bytes
+    pub fn new(limit: u64) -> Self {
+        Self { limit, peak: 0 }
+    }
+
+    /// Get the bound in bytes
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Get the highest memory use reported so far
+    pub fn peak(&self) -> u64 {
+        self.peak
+    }
+
+    /// Record a progress packet
+    ///
+    /// Fails with [`Error::MemoryLimitExceeded`] once the reported memory
+    /// use passes the bound; the caller then cancels the query.
+    pub fn check(&mut self, progress: &ServerProgress) -> Result<()> {
+        self.peak = self.peak.max(progress.memory_usage).max(progress.peak_memory_usage);
+        if self.peak > self.limit {
+            return Err(Error::MemoryLimitExceeded(format!(
+                "server reported {} bytes, bound is {}",
+                self.peak, self.limit
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_budget_pauses_until_released() {
+        let budget = MemoryBudget::new(Some(100));
+        let first = budget.reserve(60);
+        budget.wait_for_capacity().await;
+        let second = budget.reserve(60);
+        assert_eq!(budget.usage(), 120);
+        assert!(budget.is_exhausted());
+
+        let waiter = {
+            let budget = budget.clone();
+            tokio::spawn(async move { budget.wait_for_capacity().await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert_eq!(second.bytes(), 60);
+
+        // An estimate is reserved only once the budget has room
+        let third = budget.reserve(40);
+        let acquiring = {
+            let budget = budget.clone();
+            tokio::spawn(async move { budget.acquire(70).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(budget.usage(), 100);
+        drop(third);
+        let estimate = tokio::time::timeout(Duration::from_secs(1), acquiring).await.unwrap().unwrap();
+        assert_eq!(budget.usage(), 130);
+        drop((second, estimate));
+        assert_eq!(budget.usage(), 0);
+    }
+
+    #[test]
+    fn test_memory_watchdog() {
+        let mut watchdog = MemoryWatchdog::new(1000);
+        let mut progress = ServerProgress::new();
+        progress.memory_usage = 800;
+        assert!(watchdog.check(&progress).is_ok());
+        progress.memory_usage = 300;
+        progress.peak_memory_usage = 1200;
+        assert!(matches!(watchdog.check(&progress), Err(Error::MemoryLimitExceeded(_))));
+        assert_eq!(watchdog.peak(), 1200);
+    }
+}
@@ -1,10 +1,12 @@
 //! Metrics and monitoring for ClickHouse client operations
 
+use crate::client::StatementCacheStats;
 use crate::error::{Error, Result};
+use crate::protocol::ConnectionStats;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock as StdRwLock};
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
 use tracing::debug;
 
 /// Metric type
@@ -170,10 +172,207 @@ impl Metric {
     }
 }
 
+/// Number of counter shards; concurrent updates from different threads land on different cache lines
+const COUNTER_SHARDS: usize = 16;
+
+/// Number of samples kept per histogram reservoir
+pub const HISTOGRAM_RESERVOIR_SIZE: usize = 1024;
+
+/// Get the counter shard assigned to the current thread
+fn shard_index() -> usize {
+    static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % COUNTER_SHARDS;
+    }
+    SHARD.with(|shard| *shard)
+}
+
+/// Atomic padded to its own cache line to avoid false sharing
+#[derive(Default)]
+#[repr(align(64))]
+struct PaddedAtomicU64(AtomicU64);
+
+/// Counter split across per-thread shards
+struct ShardedCounter {
+    shards: [PaddedAtomicU64; COUNTER_SHARDS],
+}
+
+impl ShardedCounter {
+    fn new(initial: u64) -> Self {
+        let counter = Self { shards: Default::default() };
+        counter.add(initial);
+        counter
+    }
+
+    fn add(&self, value: u64) {
+        self.shards[shard_index()].0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn value(&self) -> u64 {
+        self.shards
+            .iter()
+            .fold(0u64, |sum, shard| sum.wrapping_add(shard.0.load(Ordering::Relaxed)))
+    }
+}
+
+/// Fixed-size ring of recent observations, written without locks
+///
+/// Under concurrent writes a snapshot may briefly miss the newest samples.
+struct Reservoir {
+    samples: Box<[AtomicU64]>,
+    count: AtomicU64,
+}
+
+impl Reservoir {
+    fn new() -> Self {
+        Self {
+            samples: (0..HISTOGRAM_RESERVOIR_SIZE).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, value: f64) {
+        let index = self.count.fetch_add(1, Ordering::Relaxed) as usize % self.samples.len();
+        self.samples[index].store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Vec<f64> {
+        let len = (self.count.load(Ordering::Relaxed) as usize).min(self.samples.len());
+        self.samples[..len]
+            .iter()
+            .map(|sample| f64::from_bits(sample.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+/// Lock-free storage behind a registered metric
+enum MetricStorage {
+    Counter(Box<ShardedCounter>),
+    Gauge(AtomicU64),
+    Histogram(Reservoir),
+    /// Quantile values as f64 bits, fixed at registration
+    Summary(Vec<(f64, AtomicU64)>),
+}
+
+/// Registered metric; updates only touch atomics
+struct MetricCell {
+    name: String,
+    metric_type: MetricType,
+    description: String,
+    labels: HashMap<String, String>,
+    unit: Option<String>,
+    storage: MetricStorage,
+    /// Creation time, the base for `updated_nanos`
+    created: Instant,
+    /// Nanoseconds after `created` of the last update
+    updated_nanos: AtomicU64,
+}
+
+impl MetricCell {
+    /// Create a cell holding the current value of `metric`
+    fn from_metric(metric: Metric) -> Self {
+        let storage = match metric.value {
+            MetricValue::Counter(value) => MetricStorage::Counter(Box::new(ShardedCounter::new(value))),
+            MetricValue::Gauge(value) => MetricStorage::Gauge(AtomicU64::new(value.to_bits())),
+            MetricValue::Histogram(buckets) => {
+                let reservoir = Reservoir::new();
+                buckets.iter().for_each(|bucket| reservoir.record(bucket.upper_bound));
+                MetricStorage::Histogram(reservoir)
+            }
+            MetricValue::Summary(quantiles) => MetricStorage::Summary(
+                quantiles
+                    .iter()
+                    .map(|quantile| (quantile.quantile, AtomicU64::new(quantile.value.to_bits())))
+                    .collect(),
+            ),
+        };
+
+        Self {
+            name: metric.name,
+            metric_type: metric.metric_type,
+            description: metric.description,
+            labels: metric.labels,
+            unit: metric.unit,
+            storage,
+            created: metric.timestamp,
+            updated_nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn touch(&self) {
+        let elapsed = self.created.elapsed().as_nanos() as u64;
+        self.updated_nanos.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    fn increment(&self, value: u64) -> Result<()> {
+        match &self.storage {
+            MetricStorage::Counter(counter) => counter.add(value),
+            _ => return Err(Error::Internal("Cannot increment non-counter metric".to_string())),
+        }
+        self.touch();
+        Ok(())
+    }
+
+    fn set_gauge(&self, value: f64) -> Result<()> {
+        match &self.storage {
+            MetricStorage::Gauge(gauge) => gauge.store(value.to_bits(), Ordering::Relaxed),
+            _ => return Err(Error::Internal("Cannot set gauge on non-gauge metric".to_string())),
+        }
+        self.touch();
+        Ok(())
+    }
+
+    fn observe_histogram(&self, value: f64) -> Result<()> {
+        match &self.storage {
+            MetricStorage::Histogram(reservoir) => reservoir.record(value),
+            _ => return Err(Error::Internal("Cannot observe histogram on non-histogram metric".to_string())),
+        }
+        self.touch();
+        Ok(())
+    }
+
+    /// Take a point-in-time copy of the metric
+    fn snapshot(&self) -> Metric {
+        let value = match &self.storage {
+            MetricStorage::Counter(counter) => MetricValue::Counter(counter.value()),
+            MetricStorage::Gauge(gauge) => MetricValue::Gauge(f64::from_bits(gauge.load(Ordering::Relaxed))),
+            MetricStorage::Histogram(reservoir) => MetricValue::Histogram(
+                reservoir
+                    .snapshot()
+                    .into_iter()
+                    .map(|value| HistogramBucket { upper_bound: value, count: 1 })
+                    .collect(),
+            ),
+            MetricStorage::Summary(quantiles) => MetricValue::Summary(
+                quantiles
+                    .iter()
+                    .map(|(quantile, value)| SummaryQuantile {
+                        quantile: *quantile,
+                        value: f64::from_bits(value.load(Ordering::Relaxed)),
+                    })
+                    .collect(),
+            ),
+        };
+
+        Metric {
+            name: self.name.clone(),
+            metric_type: self.metric_type.clone(),
+            value,
+            labels: self.labels.clone(),
+            description: self.description.clone(),
+            unit: self.unit.clone(),
+            timestamp: self.created + Duration::from_nanos(self.updated_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
 /// Metrics registry for collecting and managing metrics
+///
+/// Metric values live in sharded atomics. The name index is only write-locked
+/// when a metric is first created, and no lock is ever held across an await.
 pub struct MetricsRegistry {
     /// Registered metrics
-    metrics: Arc<RwLock<HashMap<String, Metric>>>,
+    metrics: Arc<StdRwLock<HashMap<String, Arc<MetricCell>>>>,
     /// Metrics prefix
     prefix: String,
     /// Whether metrics collection is enabled
@@ -184,7 +383,7 @@ impl MetricsRegistry {
     /// Create a new metrics registry
     pub fn new(prefix: String) -> Self {
         Self {
-            metrics: Arc::new(RwLock::new(HashMap::new())),
+            metrics: Arc::new(StdRwLock::new(HashMap::new())),
             prefix,
             enabled: true,
         }
@@ -195,6 +394,21 @@ impl MetricsRegistry {
         Self::new("clickhouse_client".to_string())
     }
 
+    /// Get a metric cell, creating it on first use
+    fn cell(&self, name: &str, create: impl FnOnce() -> Metric) -> Arc<MetricCell> {
+        let full_name = format!("{}_{}", self.prefix, name);
+
+        if let Some(cell) = self.metrics.read().unwrap_or_else(|e| e.into_inner()).get(&full_name) {
+            return cell.clone();
+        }
+
+        let mut metrics = self.metrics.write().unwrap_or_else(|e| e.into_inner());
+        metrics
+            .entry(full_name)
+            .or_insert_with(|| Arc::new(MetricCell::from_metric(create())))
+            .clone()
+    }
+
     /// Register a new metric
     pub async fn register_metric(&self, metric: Metric) -> Result<()> {
         if !self.enabled {
@@ -202,8 +416,8 @@ impl MetricsRegistry {
         }
 
         let name = format!("{}_{}", self.prefix, metric.name);
-        let mut metrics = self.metrics.write().await;
-        metrics.insert(name.clone(), metric);
+        let mut metrics = self.metrics.write().unwrap_or_else(|e| e.into_inner());
+        metrics.insert(name.clone(), Arc::new(MetricCell::from_metric(metric)));
         debug!("Registered metric: {}", name);
         Ok(())
     }
@@ -211,8 +425,8 @@ impl MetricsRegistry {
     /// Get a metric by name
     pub async fn get_metric(&self, name: &str) -> Option<Metric> {
         let full_name = format!("{}_{}", self.prefix, name);
-        let metrics = self.metrics.read().await;
-        metrics.get(&full_name).cloned()
+        let metrics = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        metrics.get(&full_name).map(|cell| cell.snapshot())
     }
 
     /// Update a counter metric
@@ -221,30 +435,14 @@ impl MetricsRegistry {
             return Ok(());
         }
 
-        let full_name = format!("{}_{}", self.prefix, name);
-        let mut metrics = self.metrics.write().await;
-        
-        if let Some(metric) = metrics.get_mut(&full_name) {
-            metric.increment(value)?;
-        } else {
-            // Auto-create metric if it doesn't exist
-            let mut metric = Metric::new(
-                name.to_string(),
-                MetricType::Counter,
-                format!("Counter metric for {}", name),
-            );
-            
-            if let Some(labels) = labels {
-                for (key, value) in labels {
-                    metric = metric.label(key, value);
-                }
-            }
-            
-            metric.increment(value)?;
-            metrics.insert(full_name, metric);
-        }
-        
-        Ok(())
+        // Auto-create metric if it doesn't exist
+        self.cell(name, || {
+            with_labels(
+                Metric::new(name.to_string(), MetricType::Counter, format!("Counter metric for {}", name)),
+                labels,
+            )
+        })
+        .increment(value)
     }
 
     /// Update a gauge metric
@@ -253,30 +451,14 @@ impl MetricsRegistry {
             return Ok(());
         }
 
-        let full_name = format!("{}_{}", self.prefix, name);
-        let mut metrics = self.metrics.write().await;
-        
-        if let Some(metric) = metrics.get_mut(&full_name) {
-            metric.set_gauge(value)?;
-        } else {
-            // Auto-create metric if it doesn't exist
-            let mut metric = Metric::new(
-                name.to_string(),
-                MetricType::Gauge,
-                format!("Gauge metric for {}", name),
-            );
-            
-            if let Some(labels) = labels {
-                for (key, value) in labels {
-                    metric = metric.label(key, value);
-                }
-            }
-            
-            metric.set_gauge(value)?;
-            metrics.insert(full_name, metric);
-        }
-        
-        Ok(())
+        // Auto-create metric if it doesn't exist
+        self.cell(name, || {
+            with_labels(
+                Metric::new(name.to_string(), MetricType::Gauge, format!("Gauge metric for {}", name)),
+                labels,
+            )
+        })
+        .set_gauge(value)
     }
 
     /// Observe a histogram value
@@ -285,36 +467,26 @@ impl MetricsRegistry {
             return Ok(());
         }
 
-        let full_name = format!("{}_{}", self.prefix, name);
-        let mut metrics = self.metrics.write().await;
-        
-        if let Some(metric) = metrics.get_mut(&full_name) {
-            metric.observe_histogram(value)?;
-        } else {
-            // Auto-create metric if it doesn't exist
-            let mut metric = Metric::new(
-                name.to_string(),
-                MetricType::Histogram,
-                format!("Histogram metric for {}", name),
-            );
-            
-            if let Some(labels) = labels {
-                for (key, value) in labels {
-                    metric = metric.label(key, value);
-                }
-            }
-            
-            metric.observe_histogram(value)?;
-            metrics.insert(full_name, metric);
-        }
-        
-        Ok(())
+        // Auto-create metric if it doesn't exist
+        self.cell(name, || {
+            with_labels(
+                Metric::new(name.to_string(), MetricType::Histogram, format!("Histogram metric for {}", name)),
+                labels,
+            )
+        })
+        .observe_histogram(value)
     }
 
     /// Get all metrics
     pub async fn get_all_metrics(&self) -> Vec<Metric> {
-        let metrics = self.metrics.read().await;
-        metrics.values().cloned().collect()
+        let cells: Vec<Arc<MetricCell>> = self
+            .metrics
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        cells.iter().map(|cell| cell.snapshot()).collect()
     }
 
     /// Export metrics in Prometheus format
@@ -360,6 +532,89 @@ impl MetricsRegistry {
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
+
+    /// Add the protocol counters of a connection to the network metrics
+    ///
+    /// Pass the delta since the last recorded snapshot (see
+    /// [`ConnectionStats::since`]) so that bytes are not counted twice.
+    pub async fn record_connection_stats(&self, stats: &ConnectionStats) -> Result<()> {
+        use metric_names::*;
+
+        let totals = [
+            (NETWORK_BYTES_SENT, stats.bytes_sent),
+            (NETWORK_BYTES_RECEIVED, stats.bytes_received),
+            (NETWORK_UNCOMPRESSED_BYTES_SENT, stats.uncompressed_bytes_sent),
+            (NETWORK_COMPRESSED_BYTES_SENT, stats.compressed_bytes_sent),
+            (NETWORK_UNCOMPRESSED_BYTES_RECEIVED, stats.uncompressed_bytes_received),
+            (NETWORK_COMPRESSED_BYTES_RECEIVED, stats.compressed_bytes_received),
+        ];
+        for (name, value) in totals {
+            if value > 0 {
+                self.increment_counter(name, value, None).await?;
+            }
+        }
+
+        for (prefix, packets) in [
+            (NETWORK_PACKETS_SENT, &stats.packets_sent),
+            (NETWORK_PACKETS_RECEIVED, &stats.packets_received),
+        ] {
+            for (packet_type, count) in packets {
+                let labels = HashMap::from([("packet_type".to_string(), packet_type.clone())]);
+                let name = format!("{}_{}", prefix, snake_case(packet_type));
+                self.increment_counter(&name, *count, Some(labels)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the counters of a connection's statement cache to the cache metrics
+    ///
+    /// As with [`record_connection_stats`](Self::record_connection_stats),
+    /// pass the delta since the last recorded snapshot.
+    pub async fn record_statement_cache_stats(&self, stats: &StatementCacheStats) -> Result<()> {
+        use metric_names::*;
+
+        let totals = [
+            (STATEMENT_CACHE_HITS, stats.hits),
+            (STATEMENT_CACHE_MISSES, stats.misses),
+            (STATEMENT_CACHE_EVICTIONS, stats.evictions),
+            (STATEMENT_CACHE_SCHEMA_HITS, stats.schema_hits),
+            (STATEMENT_CACHE_SCHEMA_MISSES, stats.schema_misses),
+            (STATEMENT_CACHE_INVALIDATIONS, stats.invalidations),
+        ];
+        for (name, value) in totals {
+            if value > 0 {
+                self.increment_counter(name, value, None).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Convert a packet type name such as `ServerData` into `server_data`
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if !out.is_empty() {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Apply optional labels to a newly created metric
+fn with_labels(mut metric: Metric, labels: Option<HashMap<String, String>>) -> Metric {
+    if let Some(labels) = labels {
+        for (key, value) in labels {
+            metric = metric.label(key, value);
+        }
+    }
+    metric
 }
 
 /// Metrics collector for specific operations
@@ -443,17 +698,35 @@ pub mod metric_names {
     pub const CONNECTION_POOL_ACTIVE: &str = "connection_pool_active";
     pub const CONNECTION_POOL_IDLE: &str = "connection_pool_idle";
     pub const CONNECTION_POOL_WAIT_TIME: &str = "connection_pool_wait_time";
+    pub const CONNECTION_POOL_ACQUIRE_WAIT: &str = "connection_pool_acquire_wait_seconds";
+    pub const CONNECTION_POOL_ACQUIRE_RETRIES: &str = "connection_pool_acquire_retries_total";
+    pub const CONNECTION_POOL_EXHAUSTED: &str = "connection_pool_exhausted_total";
     
     /// Query execution metrics
     pub const QUERY_DURATION: &str = "query_duration_seconds";
     pub const QUERY_SUCCESS_TOTAL: &str = "query_success_total";
     pub const QUERY_FAILURE_TOTAL: &str = "query_failure_total";
     pub const QUERY_ROWS_PROCESSED: &str = "query_rows_processed";
+    pub const RESULT_MEMORY_BUFFERED: &str = "result_memory_buffered_bytes";
     
     /// Network metrics
     pub const NETWORK_CONNECTIONS_TOTAL: &str = "network_connections_total";
     pub const NETWORK_BYTES_SENT: &str = "network_bytes_sent";
     pub const NETWORK_BYTES_RECEIVED: &str = "network_bytes_received";
+    pub const NETWORK_PACKETS_SENT: &str = "network_packets_sent";
+    pub const NETWORK_PACKETS_RECEIVED: &str = "network_packets_received";
+    pub const NETWORK_UNCOMPRESSED_BYTES_SENT: &str = "network_uncompressed_bytes_sent";
+    pub const NETWORK_COMPRESSED_BYTES_SENT: &str = "network_compressed_bytes_sent";
+    pub const NETWORK_UNCOMPRESSED_BYTES_RECEIVED: &str = "network_uncompressed_bytes_received";
+    pub const NETWORK_COMPRESSED_BYTES_RECEIVED: &str = "network_compressed_bytes_received";
+
+    /// Statement cache metrics
+    pub const STATEMENT_CACHE_HITS: &str = "statement_cache_hits_total";
+    pub const STATEMENT_CACHE_MISSES: &str = "statement_cache_misses_total";
+    pub const STATEMENT_CACHE_EVICTIONS: &str = "statement_cache_evictions_total";
+    pub const STATEMENT_CACHE_SCHEMA_HITS: &str = "statement_cache_schema_hits_total";
+    pub const STATEMENT_CACHE_SCHEMA_MISSES: &str = "statement_cache_schema_misses_total";
+    pub const STATEMENT_CACHE_INVALIDATIONS: &str = "statement_cache_invalidations_total";
     
     /// Load balancer metrics
     pub const LOAD_BALANCER_SERVERS_TOTAL: &str = "load_balancer_servers_total";
@@ -617,4 +890,101 @@ mod tests {
         assert!(prometheus_output.contains("42"));
         assert!(prometheus_output.contains("3.14"));
     }
+
+    #[tokio::test]
+    async fn test_metrics_registry_concurrent_increments() {
+        let registry = Arc::new(MetricsRegistry::new("test".to_string()));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let registry = registry.clone();
+                tokio::spawn(async move {
+                    for _ in 0..1000 {
+                        registry.increment_counter("hits", 1, None).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let metric = registry.get_metric("hits").await.unwrap();
+        assert!(matches!(metric.value, MetricValue::Counter(8000)));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_registry_histogram_reservoir_capped() {
+        let registry = MetricsRegistry::new("test".to_string());
+
+        for i in 0..HISTOGRAM_RESERVOIR_SIZE + 10 {
+            registry.observe_histogram("latency", i as f64, None).await.unwrap();
+        }
+
+        let metric = registry.get_metric("latency").await.unwrap();
+        match metric.value {
+            MetricValue::Histogram(buckets) => assert_eq!(buckets.len(), HISTOGRAM_RESERVOIR_SIZE),
+            other => panic!("unexpected value: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_registry_type_mismatch() {
+        let registry = MetricsRegistry::new("test".to_string());
+
+        registry.set_gauge("value", 1.0, None).await.unwrap();
+        assert!(registry.increment_counter("value", 1, None).await.is_err());
+        assert!(registry.observe_histogram("value", 1.0, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_registry_export_prometheus_format() {
+        let registry = MetricsRegistry::new("test".to_string());
+        let mut labels = HashMap::new();
+        labels.insert("host".to_string(), "a".to_string());
+
+        registry.increment_counter("queries", 5, Some(labels)).await.unwrap();
+
+        assert_eq!(
+            registry.export_prometheus().await,
+            "# HELP queries Counter metric for queries\n\
+             # TYPE queries counter\n\
+             queries{host=\"a\"} 5\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_connection_stats() {
+        use crate::protocol::{PacketDirection, PacketType, ProtocolStats};
+
+        let registry = MetricsRegistry::new("test".to_string());
+        let stats = ProtocolStats::new();
+        stats.record_packet(PacketDirection::Sent, PacketType::ClientQuery.to_u64(), 64);
+        stats.record_packet(PacketDirection::Received, PacketType::ServerData.to_u64(), 128);
+        registry.record_connection_stats(&stats.snapshot()).await.unwrap();
+        registry.record_connection_stats(&stats.snapshot()).await.unwrap();
+
+        let sent = registry.get_metric(metric_names::NETWORK_BYTES_SENT).await.unwrap();
+        assert!(matches!(sent.value, MetricValue::Counter(128)));
+        let data = registry.get_metric("network_packets_received_server_data").await.unwrap();
+        assert!(matches!(data.value, MetricValue::Counter(2)));
+        assert_eq!(data.labels.get("packet_type").map(String::as_str), Some("ServerData"));
+        assert!(registry.get_metric(metric_names::NETWORK_COMPRESSED_BYTES_SENT).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_record_statement_cache_stats() {
+        let registry = MetricsRegistry::new("test".to_string());
+        let stats = StatementCacheStats {
+            hits: 3,
+            misses: 1,
+            ..StatementCacheStats::default()
+        };
+        registry.record_statement_cache_stats(&stats).await.unwrap();
+        registry.record_statement_cache_stats(&stats).await.unwrap();
+
+        let hits = registry.get_metric(metric_names::STATEMENT_CACHE_HITS).await.unwrap();
+        assert!(matches!(hits.value, MetricValue::Counter(6)));
+        assert!(registry.get_metric(metric_names::STATEMENT_CACHE_EVICTIONS).await.is_none());
+    }
 }
@@ -9,22 +9,119 @@ mod retry;
 mod load_balancer;
 mod metrics;
 mod circuit_breaker;
+mod transaction;
+mod stream;
+mod memory;
+mod drain;
+mod admin;
+mod system_tables;
+mod explain;
+mod impersonation;
+mod ddl;
+mod mutation;
+mod in_list;
+mod session;
+mod upsert;
+mod timeseries;
+mod kafka;
+mod inserter;
+mod capabilities;
+mod http;
+mod http_session;
+mod statement_cache;
+mod dsn;
+mod pretty;
+mod insert_options;
+mod limit_guard;
+mod keepalive;
+mod backfill;
+mod block_size;
+mod optimize;
+mod pipe;
+mod tls;
+mod warnings;
+mod slow_query;
+mod fetch;
+mod server_events;
+mod multi_batch;
+mod query_registry;
 
-pub use connection::Connection;
-pub use options::ClientOptions;
-pub use pool::ConnectionPool;
-pub use query::{Query, QueryResult, QuerySettings, QueryMetadata, QueryStats};
+pub use connection::{Connection, ConnectionState, PacketTransport};
+pub use options::{ClientOptions, CompressionMethod};
+pub use pool::{ConnectionPool, DiscardReason, PoolRetryPolicy, PoolStats, WAIT_TIME_SAMPLE_SIZE};
+pub use query::{
+    ColumnSchema, InsertResult, Query, QueryResult, QuerySettings, QueryMetadata, QueryStats, BUILTIN_PROFILES,
+};
 pub use grpc::GrpcClient;
 pub use retry::{RetryConfig, RetryStrategy, with_retry, with_retry_config};
-pub use load_balancer::{LoadBalancer, LoadBalancingStrategy, ServerInfo};
+pub use load_balancer::{LoadBalancer, LoadBalancingStrategy, ServerInfo, HealthCheckConfig, HealthCheckKind};
+#[cfg(feature = "testing")]
+pub(crate) use load_balancer::http_ping;
 pub use metrics::{MetricsRegistry, MetricsCollector, Metric, MetricType, MetricValue};
 pub use circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitBreakerBuilder, CircuitBreakerState};
+pub use transaction::{Transaction, TransactionState};
+pub use stream::{QueryStream, ResumeStrategy, StreamControl};
+use stream::{BlockSource, PooledBlocks};
+pub use memory::{MemoryBudget, MemoryReservation, MemoryWatchdog};
+pub use capabilities::ServerCapabilities;
+pub use http_session::{HttpSession, HttpSessionOptions};
+pub use statement_cache::{
+    normalize_query, PreparedStatement, StatementCache, StatementCacheStats, StatementParameter,
+    DEFAULT_STATEMENT_CACHE_SIZE,
+};
+pub use http::{parse_progress, ChunkedDecoder, HttpResponse, ProgressCallback, ProgressTracker, PROGRESS_HEADER, SUMMARY_HEADER};
+pub use admin::Admin;
+pub use optimize::Optimize;
+pub use pipe::{BlockTransform, Pipe, PipeReport};
+pub use multi_batch::{MultiTableBatch, MultiTableReport, TableInsertOutcome};
+pub use query_registry::{QueryRegistry, QueryTemplate, QUERY_FILE_EXTENSION};
+pub use block_size::{
+    auto_block_size, chunk_block, estimate_row_width, BlockSizes, MAX_AUTO_BLOCK_SIZE, MIN_AUTO_BLOCK_SIZE, VARIABLE_WIDTH_ESTIMATE,
+};
+pub use server_events::{is_server_pushed, ServerEvent, ServerEventHandler};
+pub use slow_query::SlowQuery;
+pub use tls::{TlsIdentity, TlsProvider, TlsReloader};
+pub use warnings::{
+    deprecated_setting, ClientWarning, DeprecatedSetting, WarningChannel, DEFAULT_WARNING_CAPACITY, DEPRECATED_SETTINGS,
+};
+pub use impersonation::{
+    UserCredential, UserHandle, USER_POOL_IDLE_TIMEOUT, USER_POOL_MAX_CONNECTIONS, USER_POOL_MAX_USERS,
+};
+pub use ddl::{validate_codecs, AlterTable, Codec, ColumnDef, CreateTable};
+pub use mutation::{
+    lightweight_delete_sql, lightweight_delete_version, mutation_delete_sql, DeleteOutcome, MutationHandle, MutationStatus,
+};
+pub use explain::{ExplainKind, PlanNode, QueryPlan};
+pub use system_tables::{
+    query_log_filter, rows_from_result, select_sql, ColumnStorage, DiskInfo, MergeInfo, PartInfo, ProcessInfo, QueryLogEntry, ReplicaInfo,
+    RowReader, StoragePolicyVolume, SystemTableRow, SystemTables, TableStorage,
+};
+pub use drain::{shutdown_signal, DrainController, InFlightGuard};
+pub use session::{SessionRestorePolicy, SessionState};
+pub use in_list::{rewrite_in_lists, InListRewrite, InListStrategy, InListTable, DEFAULT_IN_LIST_THRESHOLD};
+pub use upsert::{next_version, optimize_final_sql, select_final_sql, with_version};
+pub use kafka::{KafkaAssignment, KafkaConsumerInfo, KafkaPipeline};
+pub use timeseries::{avg, count, max, min, sum, Aggregation, Fill, TimeSeriesQuery};
+pub use inserter::{
+    adapt_block, default_column_data, describe_table_sql, insert_schema, Inserter, SchemaChangeCallback, SchemaDrift,
+    TableColumn, DEFAULT_INSERTER_MAX_ROWS, DEFAULT_SCHEMA_CHECK_INTERVAL,
+};
+pub use insert_options::{content_token, InsertOptions, DEDUPLICATION_TOKEN_SETTING};
+pub use keepalive::{
+    read_frame_with_keepalive, KeepAlive, DEFAULT_KEEPALIVE_INTERVAL, DEFAULT_KEEPALIVE_MAX_MISSED,
+};
+pub use limit_guard::{inject_limit, DEFAULT_INTERACTIVE_LIMIT};
+pub use backfill::{
+    Backfill, BackfillChunk, BackfillProgress, BackfillProgressCallback, BackfillReport, DEFAULT_BACKFILL_CONCURRENCY,
+    DEFAULT_BACKFILL_MAX_ATTEMPTS, DEFAULT_BACKFILL_RETRY_BACKOFF, MAX_BACKFILL_RETRY_BACKOFF,
+};
 
-use crate::error::Result;
-use crate::types::{Block, Value};
+use crate::error::{Error, Result};
+use crate::protocol::{ConnectionStats, ProtocolVersion};
+use crate::types::{Block, CodecRegistry, Value};
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 /// Main ClickHouse client
 pub struct Client {
@@ -34,12 +131,32 @@ pub struct Client {
     metrics: Arc<MetricsRegistry>,
     circuit_breaker: Arc<CircuitBreaker>,
     retry_config: RetryConfig,
+    settings_profiles: Arc<RwLock<HashMap<String, QuerySettings>>>,
+    drain: Arc<DrainController>,
+    user_pools: Arc<impersonation::UserPools>,
+    memory: MemoryBudget,
+    codecs: CodecRegistry,
+    capabilities: Arc<tokio::sync::OnceCell<ServerCapabilities>>,
 }
 
 impl Client {
     /// Create a new client with the specified options
-    pub fn new(options: ClientOptions) -> Result<Self> {
-        let pool = Arc::new(ConnectionPool::new(options.clone())?);
+    pub fn new(mut options: ClientOptions) -> Result<Self> {
+        options.validate()?;
+        let has_tls_files = options.tls_cert_path.is_some() || options.tls_ca_path.is_some();
+        if options.use_tls && options.tls_reloader.is_none() && has_tls_files {
+            options.tls_reloader = Some(TlsReloader::from_files(
+                options.tls_cert_path.as_ref(),
+                options.tls_key_path.as_ref(),
+                options.tls_ca_path.as_ref(),
+            )?);
+        }
+
+        let metrics = Arc::new(MetricsRegistry::new(options.metrics_prefix.clone()));
+        let pool = Arc::new(ConnectionPool::new(options.clone())?.with_metrics(metrics.clone()));
+        if let (Some(tls), Some(interval)) = (&options.tls_reloader, options.tls_reload_interval) {
+            tls.watch(interval);
+        }
         
         let load_balancer = if options.use_load_balancing && !options.servers.is_empty() {
             Some(Arc::new(LoadBalancer::from_options(&options)?))
@@ -47,13 +164,12 @@ impl Client {
             None
         };
 
-        let metrics = Arc::new(MetricsRegistry::new(options.metrics_prefix.clone()));
-        
         let circuit_breaker = Arc::new(CircuitBreakerBuilder::new()
             .failure_threshold(options.max_retries)
             .open_timeout(Duration::from_secs(30))
             .success_threshold(3)
             .enabled(options.use_retry)
+            .warnings(options.warnings.clone())
             .build());
 
         let retry_config = RetryConfig::new()
@@ -67,6 +183,8 @@ impl Client {
             .retry_on(|e| e.is_retryable())
             .operation_timeout(options.query_timeout);
 
+        let memory = MemoryBudget::new(options.max_buffered_result_memory);
+
         Ok(Client {
             options,
             pool,
@@ -74,9 +192,34 @@ impl Client {
             metrics,
             circuit_breaker,
             retry_config,
+            settings_profiles: Arc::new(RwLock::new(HashMap::new())),
+            drain: Arc::new(DrainController::new()),
+            user_pools: Arc::new(impersonation::UserPools::default()),
+            memory,
+            codecs: CodecRegistry::new(),
+            capabilities: Arc::new(tokio::sync::OnceCell::new()),
         })
     }
 
+    /// Create a new client and, with `fail_fast_on_startup`, check the server
+    ///
+    /// [`Client::new`] opens the initial connections in the background and
+    /// only logs failures. With `fail_fast_on_startup` this runs
+    /// [`Client::connect_check`] instead, so an unreachable server or bad
+    /// credentials are reported here rather than on the first query. With
+    /// `strict_queries` every registered query template is also validated
+    /// with [`Client::validate_queries`].
+    pub async fn connect(options: ClientOptions) -> Result<Self> {
+        let client = Self::new(options)?;
+        if client.options.fail_fast_on_startup {
+            client.connect_check().await?;
+        }
+        if client.options.strict_queries {
+            client.validate_queries().await?;
+        }
+        Ok(client)
+    }
+
     /// Create a new client with default options
     pub fn default() -> Result<Self> {
         Self::new(ClientOptions::default())
@@ -84,11 +227,15 @@ impl Client {
 
     /// Execute a query and return the result with retry logic
     pub async fn query(&self, sql: &str) -> Result<QueryResult> {
+        let _guard = self.drain.enter()?;
         let collector = MetricsCollector::new(self.metrics.clone(), "query".to_string());
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.query(sql).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.query(sql).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
@@ -101,11 +248,19 @@ impl Client {
         sql: &str,
         params: HashMap<String, Value>,
     ) -> Result<QueryResult> {
+        let _guard = self.drain.enter()?;
         let collector = MetricsCollector::new(self.metrics.clone(), "query_with_params".to_string());
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.query_with_params(sql, params.clone()).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let cache_before = connection.statement_cache().stats();
+            let result = connection.query_with_params(sql, params.clone()).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            self.metrics
+                .record_statement_cache_stats(&connection.statement_cache().stats().since(&cache_before))
+                .await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
@@ -118,24 +273,148 @@ impl Client {
         sql: &str,
         settings: QuerySettings,
     ) -> Result<QueryResult> {
+        let _guard = self.drain.enter()?;
         let collector = MetricsCollector::new(self.metrics.clone(), "query_with_settings".to_string());
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.query_with_settings(sql, settings.clone()).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.query_with_settings(sql, settings.clone()).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
+        }).await;
+
+        collector.record_result(&result, None).await?;
+        result
+    }
+
+    /// Execute queries back to back on one connection, returning their results in order
+    ///
+    /// See [`Connection::pipeline`].
+    pub async fn pipeline(&self, queries: &[Query]) -> Result<Vec<QueryResult>> {
+        let _guard = self.drain.enter()?;
+        let collector = MetricsCollector::new(self.metrics.clone(), "pipeline".to_string());
+
+        let result = self.circuit_breaker.execute(|| async {
+            let mut connection = self.pool.get_connection().await?;
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.pipeline(queries).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
         result
     }
 
+    /// Execute a query with parameters and settings
+    ///
+    /// Large array parameters are rewritten according to the settings' IN-list
+    /// threshold and strategy.
+    pub async fn query_with_params_and_settings(
+        &self,
+        sql: &str,
+        params: HashMap<String, Value>,
+        settings: QuerySettings,
+    ) -> Result<QueryResult> {
+        let _guard = self.drain.enter()?;
+        let collector = MetricsCollector::new(self.metrics.clone(), "query_with_params_and_settings".to_string());
+
+        let result = self.circuit_breaker.execute(|| async {
+            let mut connection = self.pool.get_connection().await?;
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.query_with_params_and_settings(sql, params.clone(), settings.clone()).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
+        }).await;
+
+        collector.record_result(&result, None).await?;
+        result
+    }
+
+    /// Get the named query templates
+    ///
+    /// Templates registered here are shared by every clone of the client.
+    pub fn queries(&self) -> &QueryRegistry {
+        &self.options.queries
+    }
+
+    /// Run a registered query template by name
+    ///
+    /// `params` must hold a value for every placeholder of the template and
+    /// nothing else.
+    pub async fn run(&self, name: &str, params: HashMap<String, Value>) -> Result<QueryResult> {
+        let template = self
+            .options
+            .queries
+            .get(name)
+            .ok_or_else(|| Error::Configuration(format!("Unknown query: {}", name)))?;
+        template.check_params(&params)?;
+        let sql = template.bindable_sql();
+        match &template.settings {
+            Some(settings) => self.query_with_params_and_settings(&sql, params, settings.clone()).await,
+            None => self.query_with_params(&sql, params).await,
+        }
+    }
+
+    /// Have the server explain every registered query template
+    ///
+    /// Placeholders are bound to the default value of their declared type.
+    /// SELECTs are planned, other statements only parsed. All templates are
+    /// checked and the failures reported together.
+    pub async fn validate_queries(&self) -> Result<()> {
+        let mut failures = Vec::new();
+        for template in self.options.queries.templates() {
+            let kind = if template.is_plannable() { ExplainKind::Plan } else { ExplainKind::Ast };
+            if let Err(e) = self.query(&kind.statement(&template.sample_sql())).await {
+                tracing::warn!("Query template {} is invalid: {}", template.name, e);
+                failures.push(format!("{}: {}", template.name, e));
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Configuration(format!("Invalid query templates: {}", failures.join("; "))))
+        }
+    }
+
+    /// Register a named settings profile, replacing any profile with the same name
+    pub fn register_settings_profile(&self, name: impl Into<String>, settings: QuerySettings) {
+        self.settings_profiles
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.into(), settings);
+    }
+
+    /// Get a settings profile by name, checking registered profiles before built-in ones
+    pub fn settings_profile(&self, name: &str) -> Option<QuerySettings> {
+        self.settings_profiles
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+            .or_else(|| QuerySettings::profile(name))
+    }
+
+    /// Execute a query with a named settings profile
+    pub async fn query_with_profile(&self, sql: &str, profile: &str) -> Result<QueryResult> {
+        let settings = self
+            .settings_profile(profile)
+            .ok_or_else(|| Error::Configuration(format!("Unknown settings profile: {}", profile)))?;
+        self.query_with_settings(sql, settings).await
+    }
+
     /// Execute a query and return the result with retry logic
     pub async fn execute(&self, sql: &str) -> Result<()> {
+        let _guard = self.drain.enter()?;
         let collector = MetricsCollector::new(self.metrics.clone(), "execute".to_string());
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.execute(sql).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.execute(sql).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
@@ -148,11 +427,15 @@ impl Client {
         sql: &str,
         params: HashMap<String, Value>,
     ) -> Result<()> {
+        let _guard = self.drain.enter()?;
         let collector = MetricsCollector::new(self.metrics.clone(), "execute_with_params".to_string());
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.execute_with_params(sql, params.clone()).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.execute_with_params(sql, params.clone()).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
@@ -165,11 +448,15 @@ impl Client {
         sql: &str,
         settings: QuerySettings,
     ) -> Result<()> {
+        let _guard = self.drain.enter()?;
         let collector = MetricsCollector::new(self.metrics.clone(), "execute_with_settings".to_string());
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.execute_with_settings(sql, settings.clone()).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.execute_with_settings(sql, settings.clone()).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
@@ -177,12 +464,18 @@ impl Client {
     }
 
     /// Insert data into a table with retry logic
-    pub async fn insert(&self, table: &str, block: Block) -> Result<()> {
+    ///
+    /// Returns the rows and bytes the server reports as written.
+    pub async fn insert(&self, table: &str, block: Block) -> Result<InsertResult> {
+        let _guard = self.drain.enter()?;
         let collector = MetricsCollector::new(self.metrics.clone(), "insert".to_string());
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.insert(table, block.clone()).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.insert(table, block.clone()).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
@@ -195,12 +488,16 @@ impl Client {
         table: &str,
         block: Block,
         settings: QuerySettings,
-    ) -> Result<()> {
+    ) -> Result<InsertResult> {
+        let _guard = self.drain.enter()?;
         let collector = MetricsCollector::new(self.metrics.clone(), "insert_with_settings".to_string());
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.insert_with_settings(table, block.clone(), settings.clone()).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.insert_with_settings(table, block.clone(), settings.clone()).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
@@ -213,7 +510,43 @@ impl Client {
         
         let result = self.circuit_breaker.execute(|| async {
             let mut connection = self.pool.get_connection().await?;
-            connection.ping().await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.ping().await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
+        }).await;
+
+        collector.record_result(&result, None).await?;
+        result
+    }
+
+    /// Open the minimum number of pool connections and ping the server
+    ///
+    /// Unlike [`Client::ping`] this does not retry and returns the first
+    /// connection error, which makes it suitable for startup validation.
+    pub async fn connect_check(&self) -> Result<()> {
+        self.pool.initialize_pool(true).await?;
+        let mut connection = self.pool.get_connection().await?;
+        connection.ping().await
+    }
+
+    /// Get the columns of a table
+    ///
+    /// Schemas are cached per connection and re-read after DDL on the table
+    /// runs through that connection, or after it reconnects.
+    pub async fn describe_table(&self, table: &str) -> Result<Vec<TableColumn>> {
+        let collector = MetricsCollector::new(self.metrics.clone(), "describe_table".to_string());
+
+        let result = self.circuit_breaker.execute(|| async {
+            let mut connection = self.pool.get_connection().await?;
+            let (before, started) = (connection.stats(), Instant::now());
+            let cache_before = connection.statement_cache().stats();
+            let result = connection.describe_table(table).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            self.metrics
+                .record_statement_cache_stats(&connection.statement_cache().stats().since(&cache_before))
+                .await?;
+            result
         }).await;
 
         collector.record_result(&result, None).await?;
@@ -246,6 +579,22 @@ impl Client {
         result
     }
 
+    /// Begin an experimental transaction pinned to a single connection
+    ///
+    /// Requires `ClientOptions::enable_experimental_transactions`. The
+    /// transaction is rolled back automatically if dropped before commit.
+    pub async fn begin_transaction(&self) -> Result<Transaction> {
+        let guard = self.drain.enter()?;
+        if !self.options.use_experimental_transactions {
+            return Err(Error::Configuration(
+                "Experimental transactions are disabled; enable them with enable_experimental_transactions()".to_string(),
+            ));
+        }
+
+        let connection = self.pool.get_connection().await?;
+        Ok(Transaction::begin(connection).await?.with_in_flight(guard))
+    }
+
     /// Reset the connection (useful for retry logic)
     pub async fn reset_connection(&self) -> Result<()> {
         let mut connection = self.pool.get_connection().await?;
@@ -258,9 +607,13 @@ impl Client {
         sql: &str,
         retry_config: RetryConfig,
     ) -> Result<QueryResult> {
+        let _guard = self.drain.enter()?;
         with_retry_config(retry_config, || async {
             let mut connection = self.pool.get_connection().await?;
-            connection.query(sql).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.query(sql).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await
     }
 
@@ -271,12 +624,278 @@ impl Client {
         params: HashMap<String, Value>,
         retry_config: RetryConfig,
     ) -> Result<QueryResult> {
+        let _guard = self.drain.enter()?;
         with_retry_config(retry_config, || async {
             let mut connection = self.pool.get_connection().await?;
-            connection.query_with_params(sql, params.clone()).await
+            let (before, started) = (connection.stats(), Instant::now());
+            let result = connection.query_with_params(sql, params.clone()).await;
+            self.record_request(&mut connection, &before, started, &result).await?;
+            result
         }).await
     }
 
+    /// Get a handle that runs queries as another user
+    ///
+    /// Connections for each user are pooled separately and reused by later
+    /// calls with the same credential; another credential replaces the pool.
+    pub fn as_user(&self, username: &str, credential: Option<UserCredential>) -> Result<UserHandle<'_>> {
+        UserHandle::new(self, username, credential)
+    }
+
+    /// Close and forget the connection pool of an impersonated user
+    pub async fn release_user(&self, username: &str) -> Result<()> {
+        match self.user_pools.remove(username) {
+            Some(pool) => pool.close().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Get the number of impersonated users with a dedicated connection pool
+    pub fn impersonated_users(&self) -> usize {
+        self.user_pools.len()
+    }
+
+    /// Create a table, checking its column codecs against the server version first
+    pub async fn create_table(&self, table: &CreateTable) -> Result<()> {
+        let sql = table.build()?;
+        table.validate_for(&self.server_release().await?)?;
+        self.execute(&sql).await
+    }
+
+    /// Get the server version as comparable release numbers
+    pub async fn server_release(&self) -> Result<ProtocolVersion> {
+        let version = self.server_version().await?;
+        let mut parts: Vec<&str> = version.trim().split('.').take(4).collect();
+        parts.resize(4, "0");
+        ProtocolVersion::from_string(&parts.join("."))
+    }
+
+    /// Get the features supported by the server
+    ///
+    /// The server release and the relevant settings are probed on first use
+    /// and cached for the lifetime of the client. If the settings cannot be
+    /// read, capabilities are derived from the release alone.
+    pub async fn capabilities(&self) -> Result<ServerCapabilities> {
+        self.capabilities
+            .get_or_try_init(|| async {
+                let version = self.server_release().await?;
+                let names: Vec<String> = capabilities::PROBED_SETTINGS.iter().map(|s| format!("'{}'", s)).collect();
+                let sql = format!("SELECT name FROM system.settings WHERE name IN ({})", names.join(", "));
+                Ok(match self.query(&sql).await {
+                    Ok(result) => {
+                        let settings = result.rows().filter_map(|row| match row.get(0) {
+                            Some(Some(Value::String(name))) => Some(name.clone()),
+                            _ => None,
+                        });
+                        ServerCapabilities::from_version_and_settings(version, settings)
+                    }
+                    Err(e) => {
+                        tracing::debug!("Could not probe server settings, using the version only: {}", e);
+                        ServerCapabilities::from_version(version)
+                    }
+                })
+            })
+            .await
+            .cloned()
+    }
+
+    /// Delete rows matching a predicate
+    ///
+    /// Uses lightweight DELETE on servers that support it, which returns
+    /// [`DeleteOutcome::Completed`]. Older servers get an `ALTER TABLE ... DELETE`
+    /// mutation and a [`DeleteOutcome::Pending`] handle to wait on.
+    pub async fn delete(&self, table: &str, predicate: &str) -> Result<DeleteOutcome> {
+        if self.capabilities().await?.supports_lightweight_delete {
+            self.execute(&lightweight_delete_sql(table, predicate)?).await?;
+            Ok(DeleteOutcome::Completed)
+        } else {
+            self.delete_with_mutation(table, predicate).await.map(|handle| DeleteOutcome::Pending(Box::new(handle)))
+        }
+    }
+
+    /// Delete rows and wait until they are no longer visible
+    pub async fn delete_sync(&self, table: &str, predicate: &str, timeout: Duration) -> Result<()> {
+        self.delete(table, predicate).await?.wait(timeout).await
+    }
+
+    /// Delete rows with an `ALTER TABLE ... DELETE` mutation without waiting for it
+    ///
+    /// The handle tracks the mutation by the ID it got in `system.mutations`.
+    pub async fn delete_with_mutation(&self, table: &str, predicate: &str) -> Result<MutationHandle> {
+        let sql = mutation_delete_sql(table, predicate)?;
+        let handle = MutationHandle::new(self.clone(), table);
+        let known = handle.mutation_ids().await?;
+        self.execute(&sql).await?;
+        handle.track_new(&known).await
+    }
+
+    /// Insert or replace rows in a `ReplacingMergeTree(version_column)` table
+    ///
+    /// Rows without a version column get one from [`next_version`], so later
+    /// upserts of the same key win once parts merge. Use
+    /// [`Client::select_final`] to read one row per key before that.
+    pub async fn upsert(&self, table: &str, block: Block, version_column: &str) -> Result<InsertResult> {
+        let block = with_version(block, version_column, next_version())?;
+        self.insert(table, block).await
+    }
+
+    /// Build an `OPTIMIZE TABLE` statement for a table
+    pub fn optimize(&self, table: &str) -> Optimize<'_> {
+        Optimize::new(self, table)
+    }
+
+    /// Subscribe to the client's warnings
+    ///
+    /// Reports deprecated settings, schema drift seen by inserters, the
+    /// circuit breaker opening and queries over the slow query threshold.
+    pub fn warnings(&self) -> tokio::sync::broadcast::Receiver<ClientWarning> {
+        self.options.warnings.subscribe()
+    }
+
+    /// Get the reloadable TLS identity, if TLS certificates are configured
+    pub fn tls(&self) -> Option<&TlsReloader> {
+        self.options.tls_reloader.as_ref()
+    }
+
+    /// Reload the TLS identity now, returning whether it changed
+    ///
+    /// New connections use the new identity; idle connections using the old
+    /// one are closed as they are returned to the pool.
+    pub fn reload_tls(&self) -> Result<bool> {
+        match &self.options.tls_reloader {
+            Some(tls) => tls.reload(),
+            None => Err(Error::Configuration("No TLS certificates are configured".to_string())),
+        }
+    }
+
+    /// Collect blocks for several tables to insert together over one connection
+    ///
+    /// The batch is all-or-nothing when experimental transactions are enabled.
+    pub fn multi_table_batch(&self) -> MultiTableBatch<'_> {
+        MultiTableBatch::new(self)
+    }
+
+    /// Stream the blocks of a SELECT into an INSERT on another connection
+    pub fn pipe(&self, select_sql: &str, table: &str) -> Pipe<'_> {
+        Pipe::new(self, select_sql, table)
+    }
+
+    /// Force the merge that collapses replaced rows
+    pub async fn optimize_final(&self, table: &str) -> Result<()> {
+        self.execute(&optimize_final_sql(table)).await
+    }
+
+    /// Read a table with replaced rows collapsed, optionally filtered
+    pub async fn select_final(&self, table: &str, predicate: Option<&str>) -> Result<QueryResult> {
+        self.query(&select_final_sql(table, predicate)).await
+    }
+
+    /// Get the admin facade for `SYSTEM` commands
+    pub fn admin(&self) -> Admin<'_> {
+        Admin::new(self)
+    }
+
+    /// Explain a query and parse the output into a tree of steps
+    pub async fn explain(&self, sql: &str, kind: ExplainKind) -> Result<QueryPlan> {
+        let result = self.query(&kind.statement(sql)).await?;
+        QueryPlan::from_result(kind, &result)
+    }
+
+    /// Create a Kafka engine table and the materialized view feeding its target
+    pub async fn create_kafka_pipeline(&self, pipeline: &KafkaPipeline) -> Result<()> {
+        for statement in pipeline.statements()? {
+            self.execute(&statement).await?;
+        }
+        Ok(())
+    }
+
+    /// Start a bucketed time-series query over a table
+    pub fn timeseries(&self, table: &str) -> TimeSeriesQuery<'_> {
+        TimeSeriesQuery::new(self, table)
+    }
+
+    /// Get typed accessors for system tables
+    pub fn system_tables(&self) -> SystemTables<'_> {
+        SystemTables::new(self)
+    }
+
+    /// Stop accepting new queries and wait for in-flight ones to finish
+    ///
+    /// New queries fail with [`Error::Draining`] until [`Client::resume`] is
+    /// called. Returns [`Error::Timeout`] if queries are still running after
+    /// `timeout`; the client keeps refusing new queries in that case.
+    pub async fn quiesce(&self, timeout: Duration) -> Result<()> {
+        self.drain.start_draining();
+        tracing::info!("Quiescing client with {} queries in flight", self.drain.in_flight());
+        self.drain.wait_idle(timeout).await
+    }
+
+    /// Accept new queries again after [`Client::quiesce`]
+    pub fn resume(&self) {
+        self.drain.resume();
+    }
+
+    /// Check if the client is refusing new queries
+    pub fn is_quiescing(&self) -> bool {
+        self.drain.is_draining()
+    }
+
+    /// Get the number of queries currently running through this client
+    pub fn in_flight_queries(&self) -> usize {
+        self.drain.in_flight()
+    }
+
+    /// Wait for `signal`, then quiesce and close the connection pool
+    ///
+    /// Intended for rolling restarts, e.g.
+    /// `client.shutdown_on(shutdown_signal(), Duration::from_secs(30))`.
+    /// The pool is closed even if draining times out.
+    pub async fn shutdown_on<F>(&self, signal: F, timeout: Duration) -> Result<()>
+    where
+        F: std::future::Future<Output = ()>,
+    {
+        signal.await;
+        tracing::info!("Shutdown requested, draining queries");
+        let drained = self.quiesce(timeout).await;
+        self.pool.close().await?;
+        drained
+    }
+
+    /// Stream the result of a query block by block
+    pub fn query_stream(&self, sql: &str) -> QueryStream<'_> {
+        QueryStream::new(sql, None, move |sql| Box::pin(self.open_stream(sql, QuerySettings::default())))
+            .with_budget(self.memory.clone())
+    }
+
+    /// Stream the result of a query with settings, cutting blocks down to their block size
+    pub fn query_stream_with_settings(&self, sql: &str, settings: QuerySettings) -> QueryStream<'_> {
+        let stream = QueryStream::new(sql, None, {
+            let settings = settings.clone();
+            move |sql| Box::pin(self.open_stream(sql, settings.clone()))
+        });
+        stream.with_block_size(&settings).with_budget(self.memory.clone())
+    }
+
+    /// Stream a query, resuming from the last received cursor value on connection loss
+    pub fn query_stream_with_resume(&self, sql: &str, resume: ResumeStrategy) -> QueryStream<'_> {
+        QueryStream::new(sql, Some(resume), move |sql| Box::pin(self.open_stream(sql, QuerySettings::default())))
+            .with_budget(self.memory.clone())
+    }
+
+    /// Send a query for a stream on a pooled connection, which then reads its blocks
+    async fn open_stream(&self, sql: String, settings: QuerySettings) -> Result<Box<dyn BlockSource + '_>> {
+        let guard = self.drain.enter()?;
+        let connection = self
+            .circuit_breaker
+            .execute(|| async {
+                let mut connection = self.pool.get_connection().await?;
+                connection.send_query(&sql, &settings).await?;
+                Ok(connection)
+            })
+            .await?;
+        Ok(Box::new(PooledBlocks::new(connection, guard)))
+    }
+
     /// Get the client options
     pub fn options(&self) -> &ClientOptions {
         &self.options
@@ -292,6 +911,47 @@ impl Client {
         self.load_balancer.as_ref()
     }
 
+    /// Record a request that ran on a pooled connection
+    ///
+    /// Every wrapper that takes a connection from the pool ends here: the
+    /// latency goes to the load balancer, a slow query is explained and the
+    /// protocol counters of the request go to the metrics.
+    async fn record_request<T>(
+        &self,
+        connection: &mut Connection,
+        before: &ConnectionStats,
+        started: Instant,
+        result: &Result<T>,
+    ) -> Result<()> {
+        self.record_latency(connection, result, started.elapsed()).await;
+        if let Some(slow) = connection.take_slow_query() {
+            self.explain_slow_query(slow);
+        }
+        self.metrics.record_connection_stats(&connection.stats().since(before)).await
+    }
+
+    /// Feed the time a successful query took into the load balancer's latency average
+    async fn record_latency<T>(&self, connection: &Connection, result: &Result<T>, latency: Duration) {
+        if let (Some(lb), Ok(_)) = (&self.load_balancer, result) {
+            let (host, port) = connection.server_address();
+            lb.record_server_latency(host, port, latency).await;
+        }
+    }
+
+    /// Get the budget shared by the results buffered in streams
+    pub fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory
+    }
+
+    /// Get the codecs converting values into user domain types
+    ///
+    /// Codecs registered here are shared by every clone of the client. Pass
+    /// the registry to [`RowReader::with_codecs`] when reading rows and to
+    /// [`CodecRegistry::encode_into`] when building insert blocks.
+    pub fn codecs(&self) -> &CodecRegistry {
+        &self.codecs
+    }
+
     /// Get the metrics registry
     pub fn metrics(&self) -> &Arc<MetricsRegistry> {
         &self.metrics
@@ -343,6 +1003,14 @@ impl Client {
         self.metrics.set_gauge("connection_pool_active", pool_stats.active_connections as f64, None).await.ok();
         self.metrics.set_gauge("connection_pool_idle", pool_stats.idle_connections as f64, None).await.ok();
         self.metrics.observe_histogram("connection_pool_wait_time", pool_stats.average_wait_time().as_secs_f64(), None).await.ok();
+        self.metrics.set_gauge("connection_pool_waiting", pool_stats.waiting_requests as f64, None).await.ok();
+        self.metrics.set_gauge("connection_pool_wait_time_p50", pool_stats.wait_time_percentile(0.5).as_secs_f64(), None).await.ok();
+        self.metrics.set_gauge("connection_pool_wait_time_p99", pool_stats.wait_time_percentile(0.99).as_secs_f64(), None).await.ok();
+        for reason in DiscardReason::ALL {
+            let name = format!("connection_pool_discarded_{}", reason.as_str());
+            self.metrics.set_gauge(&name, pool_stats.discarded(reason) as f64, None).await.ok();
+        }
+        self.metrics.set_gauge(metrics::metric_names::RESULT_MEMORY_BUFFERED, self.memory.usage() as f64, None).await.ok();
     }
 
     /// Update load balancer metrics
@@ -364,6 +1032,7 @@ impl Client {
         let metrics = self.metrics.clone();
         let pool = self.pool.clone();
         let load_balancer = self.load_balancer.clone();
+        let memory = self.memory.clone();
         
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(30));
@@ -377,6 +1046,14 @@ impl Client {
                 metrics.set_gauge("connection_pool_active", pool_stats.active_connections as f64, None).await.ok();
                 metrics.set_gauge("connection_pool_idle", pool_stats.idle_connections as f64, None).await.ok();
                 metrics.observe_histogram("connection_pool_wait_time", pool_stats.average_wait_time().as_secs_f64(), None).await.ok();
+                metrics.set_gauge("connection_pool_waiting", pool_stats.waiting_requests as f64, None).await.ok();
+                metrics.set_gauge("connection_pool_wait_time_p50", pool_stats.wait_time_percentile(0.5).as_secs_f64(), None).await.ok();
+                metrics.set_gauge("connection_pool_wait_time_p99", pool_stats.wait_time_percentile(0.99).as_secs_f64(), None).await.ok();
+                for reason in DiscardReason::ALL {
+                    let name = format!("connection_pool_discarded_{}", reason.as_str());
+                    metrics.set_gauge(&name, pool_stats.discarded(reason) as f64, None).await.ok();
+                }
+                metrics.set_gauge(metrics::metric_names::RESULT_MEMORY_BUFFERED, memory.usage() as f64, None).await.ok();
                 
                 // Update load balancer metrics
                 if let Some(lb) = &load_balancer {
@@ -402,6 +1079,12 @@ impl Clone for Client {
             metrics: Arc::clone(&self.metrics),
             circuit_breaker: Arc::clone(&self.circuit_breaker),
             retry_config: self.retry_config.clone(),
+            settings_profiles: Arc::clone(&self.settings_profiles),
+            drain: Arc::clone(&self.drain),
+            user_pools: Arc::clone(&self.user_pools),
+            memory: self.memory.clone(),
+            codecs: self.codecs.clone(),
+            capabilities: Arc::clone(&self.capabilities),
         }
     }
 }
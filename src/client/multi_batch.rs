@@ -0,0 +1,243 @@
+//! Batches of inserts into several tables over one connection
+//!
+//! ```rust
+//! # async fn example(client: clickhouse_rs::Client, orders: clickhouse_rs::types::Block, items: clickhouse_rs::types::Block) -> clickhouse_rs::error::Result<()> {
+//! let mut batch = client.multi_table_batch();
+//! batch.add("order_items", items).add("orders", orders);
+//! let report = batch.order(["orders", "order_items"]).flush().await?;
+//! if !report.is_complete() {
+//!     for outcome in report.failed() {
+//!         println!("{} was not written", outcome.table);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The blocks of each table are inserted in turn over a single pinned
+//! connection. With experimental transactions enabled the whole batch runs in
+//! one transaction: the first failure rolls it back and is returned, so either
+//! every table is written or none is. Without them the batch is best-effort:
+//! every table is attempted and the report tells which ones were written.
+
+use crate::client::{Client, InsertResult, Transaction};
+use crate::error::{Error, ErrorContext, Result};
+use crate::types::Block;
+
+/// Outcome of the inserts into one table of a batch
+#[derive(Debug)]
+pub struct TableInsertOutcome {
+    /// Table the blocks went to
+    pub table: String,
+    /// Blocks in the batch for the table
+    pub blocks: usize,
+    /// Rows in the batch for the table
+    pub rows: u64,
+    /// Result of each inserted block, up to the first failure
+    pub results: Vec<InsertResult>,
+    /// Error that stopped the inserts into the table, if any
+    pub error: Option<Error>,
+}
+
+impl TableInsertOutcome {
+    /// Check whether every block of the table was written
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Get the rows the server reports as written
+    pub fn rows_written(&self) -> u64 {
+        self.results.iter().map(|result| result.rows_written).sum()
+    }
+}
+
+/// Outcome of a flushed [`MultiTableBatch`]
+#[derive(Debug)]
+pub struct MultiTableReport {
+    /// Whether the batch ran in a transaction
+    pub transactional: bool,
+    /// Outcome per table, in flush order
+    pub tables: Vec<TableInsertOutcome>,
+}
+
+impl MultiTableReport {
+    /// Check whether every table was written
+    pub fn is_complete(&self) -> bool {
+        self.tables.iter().all(TableInsertOutcome::is_ok)
+    }
+
+    /// Get the tables that were not completely written
+    pub fn failed(&self) -> impl Iterator<Item = &TableInsertOutcome> {
+        self.tables.iter().filter(|outcome| !outcome.is_ok())
+    }
+
+    /// Get the rows the server reports as written over all tables
+    pub fn rows_written(&self) -> u64 {
+        self.tables.iter().map(TableInsertOutcome::rows_written).sum()
+    }
+}
+
+/// Blocks for several tables, flushed together over one connection
+pub struct MultiTableBatch<'a> {
+    client: &'a Client,
+    tables: Vec<(String, Vec<Block>)>,
+}
+
+impl<'a> MultiTableBatch<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Self {
+            client,
+            tables: Vec::new(),
+        }
+    }
+
+    /// Add a block for a table
+    ///
+    /// Tables are flushed in the order they were first added unless
+    /// [`order`](Self::order) says otherwise.
+    pub fn add(&mut self, table: &str, block: Block) -> &mut Self {
+        match self.tables.iter_mut().find(|(name, _)| name == table) {
+            Some((_, blocks)) => blocks.push(block),
+            None => self.tables.push((table.to_string(), vec![block])),
+        }
+        self
+    }
+
+    /// Flush the given tables first, in the given order
+    ///
+    /// Tables not listed follow in the order they were added.
+    pub fn order<I, S>(mut self, tables: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut ordered = Vec::with_capacity(self.tables.len());
+        for table in tables {
+            if let Some(position) = self.tables.iter().position(|(name, _)| name == table.as_ref()) {
+                ordered.push(self.tables.remove(position));
+            }
+        }
+        ordered.append(&mut self.tables);
+        self.tables = ordered;
+        self
+    }
+
+    /// Get the tables in flush order
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        self.tables.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Get the number of rows in the batch
+    pub fn rows(&self) -> u64 {
+        self.tables
+            .iter()
+            .flat_map(|(_, blocks)| blocks)
+            .map(|block| block.row_count as u64)
+            .sum()
+    }
+
+    /// Check whether the batch has no blocks
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Insert every block, table by table
+    ///
+    /// In a transaction the first failure rolls back the batch and is
+    /// returned; otherwise failures are recorded in the report.
+    pub async fn flush(self) -> Result<MultiTableReport> {
+        let _guard = self.client.drain.enter()?;
+        let connection = self.client.pool.get_connection().await?;
+
+        if self.client.options.use_experimental_transactions {
+            let mut transaction = Transaction::begin(connection).await?;
+            let mut tables = Vec::with_capacity(self.tables.len());
+            for (table, blocks) in self.tables {
+                let mut outcome = outcome(&table, &blocks);
+                for block in blocks {
+                    match transaction.insert(&table, block).await {
+                        Ok(result) => outcome.results.push(result),
+                        Err(e) => {
+                            if let Err(rollback) = transaction.rollback().await {
+                                tracing::warn!("Rollback of multi-table batch failed: {}", rollback);
+                            }
+                            let context = ErrorContext::new("multi_table_batch").with_details(format!("table {}", table));
+                            return Err(e.context(context));
+                        }
+                    }
+                }
+                tables.push(outcome);
+            }
+            transaction.commit().await?;
+            return Ok(MultiTableReport { transactional: true, tables });
+        }
+
+        let mut connection = connection;
+        let mut tables = Vec::with_capacity(self.tables.len());
+        for (table, blocks) in self.tables {
+            let mut outcome = outcome(&table, &blocks);
+            for block in blocks {
+                match connection.insert(&table, block).await {
+                    Ok(result) => outcome.results.push(result),
+                    Err(e) => {
+                        tracing::warn!("Insert into {} in multi-table batch failed: {}", table, e);
+                        outcome.error = Some(e);
+                        break;
+                    }
+                }
+            }
+            tables.push(outcome);
+        }
+        Ok(MultiTableReport { transactional: false, tables })
+    }
+}
+
+fn outcome(table: &str, blocks: &[Block]) -> TableInsertOutcome {
+    TableInsertOutcome {
+        table: table.to_string(),
+        blocks: blocks.len(),
+        rows: blocks.iter().map(|block| block.row_count as u64).sum(),
+        results: Vec::new(),
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+    use crate::types::{Column, ColumnData};
+
+    fn ids(ids: Vec<u64>) -> Block {
+        Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(ids))])
+    }
+
+    #[tokio::test]
+    async fn test_multi_table_batch_order() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        let mut batch = client.multi_table_batch();
+        assert!(batch.is_empty());
+        batch.add("items", ids(vec![1, 2])).add("orders", ids(vec![1])).add("items", ids(vec![3]));
+        batch.add("audit", ids(vec![]));
+        assert_eq!(batch.rows(), 4);
+        assert_eq!(batch.tables().collect::<Vec<_>>(), ["items", "orders", "audit"]);
+
+        let batch = batch.order(["orders", "missing"]);
+        assert_eq!(batch.tables().collect::<Vec<_>>(), ["orders", "items", "audit"]);
+
+        let report = MultiTableReport {
+            transactional: false,
+            tables: vec![
+                outcome("orders", &[ids(vec![1])]),
+                TableInsertOutcome {
+                    error: Some(Error::Internal("down".to_string())),
+                    ..outcome("items", &[ids(vec![1, 2]), ids(vec![3])])
+                },
+            ],
+        };
+        assert!(!report.is_complete());
+        assert_eq!(report.failed().map(|o| o.table.as_str()).collect::<Vec<_>>(), ["items"]);
+        assert_eq!((report.tables[1].blocks, report.tables[1].rows), (2, 3));
+        assert_eq!(report.rows_written(), 0);
+    }
+}
@@ -0,0 +1,290 @@
+//! Row deletion with lightweight DELETE and mutation tracking
+//!
+//! Servers from 23.3 support lightweight `DELETE FROM ... WHERE`, which marks
+//! rows as deleted immediately. Older servers fall back to an
+//! `ALTER TABLE ... DELETE` mutation that rewrites parts in the background;
+//! the returned [`MutationHandle`] tracks it until it finishes.
+
+use crate::client::system_tables::{quote, RowReader};
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::protocol::ProtocolVersion;
+use std::time::{Duration, Instant};
+
+/// Oldest server version with lightweight DELETE enabled by default
+pub fn lightweight_delete_version() -> ProtocolVersion {
+    ProtocolVersion::new(23, 3, 0, 0)
+}
+
+/// Outcome of a delete
+#[derive(Debug)]
+pub enum DeleteOutcome {
+    /// Rows are no longer visible to queries
+    Completed,
+    /// Rows are removed by a background mutation that is still running
+    Pending(Box<MutationHandle>),
+}
+
+impl DeleteOutcome {
+    /// Check if the deleted rows are already invisible
+    pub fn is_completed(&self) -> bool {
+        matches!(self, DeleteOutcome::Completed)
+    }
+
+    /// Wait until the deleted rows are invisible
+    pub async fn wait(self, timeout: Duration) -> Result<()> {
+        match self {
+            DeleteOutcome::Completed => Ok(()),
+            DeleteOutcome::Pending(handle) => handle.wait(timeout).await,
+        }
+    }
+}
+
+/// Progress of a mutation from `system.mutations`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MutationStatus {
+    /// Mutation ID, when the mutation was found
+    pub mutation_id: Option<String>,
+    /// Whether the mutation has finished
+    pub is_done: bool,
+    /// Parts still to be mutated
+    pub parts_to_do: u64,
+    /// Reason of the last failure, empty if none
+    pub latest_fail_reason: String,
+}
+
+/// Handle to a running `ALTER TABLE ... DELETE` mutation
+#[derive(Clone)]
+pub struct MutationHandle {
+    client: Client,
+    database: Option<String>,
+    table: String,
+    mutation_id: Option<String>,
+    poll_interval: Duration,
+}
+
+impl std::fmt::Debug for MutationHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MutationHandle")
+            .field("database", &self.database)
+            .field("table", &self.table)
+            .field("mutation_id", &self.mutation_id)
+            .finish()
+    }
+}
+
+impl MutationHandle {
+    /// Track the mutations of a table
+    pub fn new(client: Client, table: &str) -> Self {
+        let (database, table) = match table.split_once('.') {
+            Some((database, table)) => (Some(unquote(database)), unquote(table)),
+            None => (None, unquote(table)),
+        };
+        Self {
+            client,
+            database,
+            table,
+            mutation_id: None,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+
+    /// Track a specific mutation
+    pub fn with_mutation_id(mut self, mutation_id: impl Into<String>) -> Self {
+        self.mutation_id = Some(mutation_id.into());
+        self
+    }
+
+    /// Set how often `wait` polls the server
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Get the tracked mutation ID
+    pub fn mutation_id(&self) -> Option<&str> {
+        self.mutation_id.as_deref()
+    }
+
+    /// Build the condition selecting the table's rows of `system.mutations`
+    fn table_filter(&self) -> String {
+        let database = self
+            .database
+            .as_deref()
+            .map(quote)
+            .unwrap_or_else(|| "currentDatabase()".to_string());
+        format!("database = {} AND table = {}", database, quote(&self.table))
+    }
+
+    /// Build the status query
+    ///
+    /// Without a mutation ID every unfinished mutation of the table is counted.
+    pub fn status_sql(&self) -> String {
+        let mut sql = format!(
+            "SELECT mutation_id, is_done, parts_to_do, latest_fail_reason FROM system.mutations WHERE {}",
+            self.table_filter()
+        );
+        match &self.mutation_id {
+            Some(id) => sql.push_str(&format!(" AND mutation_id = {}", quote(id))),
+            None => sql.push_str(" AND NOT is_done"),
+        }
+        sql.push_str(" ORDER BY create_time DESC");
+        sql
+    }
+
+    /// Build the query listing the IDs of the table's mutations, newest first
+    pub fn mutation_ids_sql(&self) -> String {
+        format!(
+            "SELECT mutation_id FROM system.mutations WHERE {} ORDER BY create_time DESC",
+            self.table_filter()
+        )
+    }
+
+    /// Fetch the IDs of the table's mutations, newest first
+    pub async fn mutation_ids(&self) -> Result<Vec<String>> {
+        let result = self.client.query(&self.mutation_ids_sql()).await?;
+        let mut ids = Vec::with_capacity(result.row_count());
+        for block in &result.blocks {
+            for index in 0..block.row_count {
+                ids.push(RowReader::new(block, index).string("mutation_id")?);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Track the newest mutation of the table that is not among `known`
+    ///
+    /// `known` is the list of IDs from before the mutation was started. If no
+    /// new mutation is listed every unfinished mutation of the table is tracked.
+    pub async fn track_new(self, known: &[String]) -> Result<Self> {
+        let ids = self.mutation_ids().await?;
+        match ids.into_iter().find(|id| !known.contains(id)) {
+            Some(id) => Ok(self.with_mutation_id(id)),
+            None => {
+                tracing::warn!("New mutation on {} not found, tracking all of its mutations", self.table);
+                Ok(self)
+            }
+        }
+    }
+
+    /// Fetch the current status
+    pub async fn status(&self) -> Result<MutationStatus> {
+        let result = self.client.query(&self.status_sql()).await?;
+        let mut status = MutationStatus {
+            mutation_id: self.mutation_id.clone(),
+            is_done: true,
+            parts_to_do: 0,
+            latest_fail_reason: String::new(),
+        };
+        for block in &result.blocks {
+            for index in 0..block.row_count {
+                let row = RowReader::new(block, index);
+                status.is_done &= row.bool("is_done")?;
+                status.parts_to_do += row.u64("parts_to_do")?;
+                let reason = row.string("latest_fail_reason")?;
+                if !reason.is_empty() {
+                    status.latest_fail_reason = reason;
+                }
+                if status.mutation_id.is_none() {
+                    status.mutation_id = Some(row.string("mutation_id")?);
+                }
+            }
+        }
+        Ok(status)
+    }
+
+    /// Wait for the mutation to finish
+    ///
+    /// Fails with [`Error::QueryExecution`] as soon as the server reports a
+    /// failure of the mutation, which it would otherwise retry indefinitely.
+    pub async fn wait(&self, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            let status = self.status().await?;
+            if status.is_done {
+                return Ok(());
+            }
+            if !status.latest_fail_reason.is_empty() {
+                return Err(Error::QueryExecution(format!(
+                    "Mutation {} on {} failed: {}",
+                    status.mutation_id.as_deref().unwrap_or("?"),
+                    self.table,
+                    status.latest_fail_reason
+                )));
+            }
+            if started.elapsed() >= timeout {
+                return Err(Error::Timeout(timeout));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+/// Build a lightweight DELETE statement
+pub fn lightweight_delete_sql(table: &str, predicate: &str) -> Result<String> {
+    Ok(format!("DELETE FROM {} WHERE {}", table, check_predicate(predicate)?))
+}
+
+/// Build an ALTER TABLE DELETE mutation statement
+pub fn mutation_delete_sql(table: &str, predicate: &str) -> Result<String> {
+    Ok(format!("ALTER TABLE {} DELETE WHERE {}", table, check_predicate(predicate)?))
+}
+
+fn check_predicate(predicate: &str) -> Result<&str> {
+    let predicate = predicate.trim().trim_end_matches(';').trim();
+    if predicate.is_empty() {
+        return Err(Error::Configuration(
+            "Delete predicate cannot be empty; use WHERE 1 to delete all rows".to_string(),
+        ));
+    }
+    Ok(predicate)
+}
+
+fn unquote(name: &str) -> String {
+    name.trim().trim_matches('`').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+
+    #[test]
+    fn test_delete_sql() {
+        assert_eq!(
+            lightweight_delete_sql("db.events", "id = 1;").unwrap(),
+            "DELETE FROM db.events WHERE id = 1"
+        );
+        assert_eq!(
+            mutation_delete_sql("events", "ts < now() - INTERVAL 1 DAY").unwrap(),
+            "ALTER TABLE events DELETE WHERE ts < now() - INTERVAL 1 DAY"
+        );
+        assert!(lightweight_delete_sql("events", "  ").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mutation_status_sql() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        let handle = MutationHandle::new(client.clone(), "`db`.events");
+        assert_eq!(
+            handle.status_sql(),
+            "SELECT mutation_id, is_done, parts_to_do, latest_fail_reason FROM system.mutations \
+             WHERE database = 'db' AND table = 'events' AND NOT is_done ORDER BY create_time DESC"
+        );
+
+        assert_eq!(
+            handle.mutation_ids_sql(),
+            "SELECT mutation_id FROM system.mutations WHERE database = 'db' AND table = 'events' \
+             ORDER BY create_time DESC"
+        );
+
+        let handle = MutationHandle::new(client, "events").with_mutation_id("mutation_3.txt");
+        assert!(handle.status_sql().contains("database = currentDatabase()"));
+        assert!(handle.status_sql().contains("mutation_id = 'mutation_3.txt'"));
+    }
+
+    #[tokio::test]
+    async fn test_completed_delete_waits_immediately() {
+        assert!(DeleteOutcome::Completed.wait(Duration::ZERO).await.is_ok());
+    }
+}
@@ -0,0 +1,247 @@
+//! `OPTIMIZE TABLE` with partition targeting and merge tracking
+//!
+//! ```rust
+//! # async fn example(client: clickhouse_rs::Client) -> clickhouse_rs::error::Result<()> {
+//! use std::time::Duration;
+//!
+//! client
+//!     .optimize("events")
+//!     .partition("2024-05")
+//!     .final_(true)
+//!     .deduplicate(true)
+//!     .wait(Duration::from_secs(600))
+//!     .execute()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Replicated tables, and any table with `alter_sync = 0`, may return from
+//! `OPTIMIZE` before the merge is done. With [`Optimize::wait`], `execute`
+//! then polls `system.merges` until the table has no merges left.
+
+use crate::client::system_tables::{quote, RowReader};
+use crate::client::Client;
+use crate::error::{Error, Result};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Partition {
+    Value(String),
+    Id(String),
+}
+
+/// Builder for an `OPTIMIZE TABLE` statement
+pub struct Optimize<'a> {
+    client: &'a Client,
+    table: String,
+    cluster: Option<String>,
+    partition: Option<Partition>,
+    final_: bool,
+    deduplicate: bool,
+    deduplicate_by: Vec<String>,
+    wait: Option<Duration>,
+    poll_interval: Duration,
+}
+
+impl<'a> Optimize<'a> {
+    pub(crate) fn new(client: &'a Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+            cluster: None,
+            partition: None,
+            final_: false,
+            deduplicate: false,
+            deduplicate_by: Vec::new(),
+            wait: None,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+
+    /// Run the statement on every node of a cluster
+    pub fn on_cluster(mut self, cluster: impl Into<String>) -> Self {
+        self.cluster = Some(cluster.into());
+        self
+    }
+
+    /// Merge only one partition, given by its partition key value
+    ///
+    /// Numbers and tuples are used as they are; anything else is quoted as a
+    /// string, so `"2024-05"` becomes `PARTITION '2024-05'`.
+    pub fn partition(mut self, value: impl Into<String>) -> Self {
+        self.partition = Some(Partition::Value(value.into()));
+        self
+    }
+
+    /// Merge only one partition, given by its ID from `system.parts`
+    pub fn partition_id(mut self, id: impl Into<String>) -> Self {
+        self.partition = Some(Partition::Id(id.into()));
+        self
+    }
+
+    /// Merge even when the partition is already a single part
+    pub fn final_(mut self, final_: bool) -> Self {
+        self.final_ = final_;
+        self
+    }
+
+    /// Remove rows that are identical in every column while merging
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// Remove rows that are identical in the given columns while merging
+    ///
+    /// The columns must include the sorting key.
+    pub fn deduplicate_by<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.deduplicate = true;
+        self.deduplicate_by = columns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Wait up to `timeout` for the table's merges to finish
+    pub fn wait(mut self, timeout: Duration) -> Self {
+        self.wait = Some(timeout);
+        self
+    }
+
+    /// Set how often the merges are polled while waiting
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Build the statement
+    pub fn sql(&self) -> Result<String> {
+        if self.table.trim().is_empty() {
+            return Err(Error::Configuration("OPTIMIZE needs a table".to_string()));
+        }
+        let mut sql = format!("OPTIMIZE TABLE {}", self.table.trim());
+        if let Some(cluster) = &self.cluster {
+            sql.push_str(&format!(" ON CLUSTER `{}`", cluster.trim_matches('`')));
+        }
+        match &self.partition {
+            Some(Partition::Value(value)) => sql.push_str(&format!(" PARTITION {}", partition_literal(value)?)),
+            Some(Partition::Id(id)) => sql.push_str(&format!(" PARTITION ID {}", quote(id))),
+            None => {}
+        }
+        if self.final_ {
+            sql.push_str(" FINAL");
+        }
+        if self.deduplicate {
+            sql.push_str(" DEDUPLICATE");
+            if !self.deduplicate_by.is_empty() {
+                sql.push_str(" BY ");
+                sql.push_str(&self.deduplicate_by.join(", "));
+            }
+        }
+        Ok(sql)
+    }
+
+    /// Build the query counting the table's running merges
+    pub fn merges_sql(&self) -> String {
+        let (database, table) = match self.table.trim().split_once('.') {
+            Some((database, table)) => (quote(database.trim_matches('`')), table),
+            None => ("currentDatabase()".to_string(), self.table.trim()),
+        };
+        let mut sql = format!(
+            "SELECT count() AS merges FROM system.merges WHERE database = {} AND table = {}",
+            database,
+            quote(table.trim_matches('`'))
+        );
+        if let Some(Partition::Id(id)) = &self.partition {
+            sql.push_str(&format!(" AND partition_id = {}", quote(id)));
+        }
+        sql
+    }
+
+    /// Run the statement, then wait for the merges if a timeout was set
+    pub async fn execute(&self) -> Result<()> {
+        let sql = self.sql()?;
+        tracing::info!("Running {}", sql);
+        self.client.execute(&sql).await?;
+        match self.wait {
+            Some(timeout) => self.wait_for_merges(timeout).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Get the number of merges running on the table
+    pub async fn running_merges(&self) -> Result<u64> {
+        let result = self.client.query(&self.merges_sql()).await?;
+        let mut merges = 0;
+        for block in &result.blocks {
+            for index in 0..block.row_count {
+                merges += RowReader::new(block, index).u64("merges")?;
+            }
+        }
+        Ok(merges)
+    }
+
+    async fn wait_for_merges(&self, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            let merges = self.running_merges().await?;
+            if merges == 0 {
+                return Ok(());
+            }
+            if started.elapsed() >= timeout {
+                return Err(Error::Timeout(timeout));
+            }
+            tracing::debug!("Waiting for {} merges on {}", merges, self.table);
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+/// Format a partition value, quoting strings
+fn partition_literal(value: &str) -> Result<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(Error::Configuration("Empty partition value".to_string()));
+    }
+    let verbatim = value.parse::<i64>().is_ok()
+        || value == "tuple()"
+        || (value.starts_with('(') && value.ends_with(')'))
+        || (value.len() >= 2 && value.starts_with('\'') && value.ends_with('\''));
+    Ok(if verbatim { value.to_string() } else { quote(value) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+
+    #[tokio::test]
+    async fn test_optimize_sql() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        let optimize = client.optimize("db.events").partition("2024-05").final_(true).deduplicate(true);
+        assert_eq!(optimize.sql().unwrap(), "OPTIMIZE TABLE db.events PARTITION '2024-05' FINAL DEDUPLICATE");
+        assert_eq!(
+            optimize.merges_sql(),
+            "SELECT count() AS merges FROM system.merges WHERE database = 'db' AND table = 'events'"
+        );
+
+        let optimize = client
+            .optimize("events")
+            .on_cluster("main")
+            .partition_id("202405")
+            .deduplicate_by(["id", "ts"]);
+        assert_eq!(
+            optimize.sql().unwrap(),
+            "OPTIMIZE TABLE events ON CLUSTER `main` PARTITION ID '202405' DEDUPLICATE BY id, ts"
+        );
+        assert!(optimize.merges_sql().ends_with("table = 'events' AND partition_id = '202405'"));
+
+        assert_eq!(client.optimize("t").partition("202405").sql().unwrap(), "OPTIMIZE TABLE t PARTITION 202405");
+        assert_eq!(client.optimize("t").partition("(1, 'a')").sql().unwrap(), "OPTIMIZE TABLE t PARTITION (1, 'a')");
+        assert!(client.optimize("t").partition(" ").sql().is_err());
+        assert!(client.optimize("").sql().is_err());
+    }
+}
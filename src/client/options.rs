@@ -1,6 +1,11 @@
 //! Client options for ClickHouse
 
+use crate::client::session::SessionRestorePolicy;
+use crate::client::keepalive::DEFAULT_KEEPALIVE_INTERVAL;
+use crate::client::{HttpSessionOptions, PoolRetryPolicy, QueryRegistry, ServerEvent, ServerEventHandler, TlsReloader, WarningChannel};
 use crate::error::{Error, Result};
+use crate::protocol::{ClientInfo, PacketTracer};
+use crate::secret::Secret;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -15,8 +20,8 @@ pub struct ClientOptions {
     pub database: String,
     /// Username
     pub username: String,
-    /// Password
-    pub password: String,
+    /// Password, or the token for JWT authentication
+    pub password: Secret,
     /// Connection timeout
     pub connect_timeout: Duration,
     /// Query timeout
@@ -27,10 +32,18 @@ pub struct ClientOptions {
     pub write_timeout: Duration,
     /// Keep alive timeout
     pub keep_alive_timeout: Duration,
+    /// Idle time after which a ping is sent during a streaming query, `None` to never ping
+    pub keepalive_ping_interval: Option<Duration>,
     /// Maximum number of connections in the pool
     pub max_connections: usize,
     /// Minimum number of connections in the pool
     pub min_connections: usize,
+    /// Open connections on first use instead of when the client is created
+    #[serde(default)]
+    pub lazy_connect: bool,
+    /// Make [`Client::connect`](crate::Client::connect) fail if the initial connections cannot be opened
+    #[serde(default)]
+    pub fail_fast_on_startup: bool,
     /// Connection idle timeout
     pub idle_timeout: Duration,
     /// Whether to use TLS/SSL
@@ -43,6 +56,12 @@ pub struct ClientOptions {
     pub tls_ca_path: Option<String>,
     /// Whether to verify TLS certificates
     pub tls_verify: bool,
+    /// Reloadable TLS identity; built from the paths above when unset
+    #[serde(skip)]
+    pub tls_reloader: Option<TlsReloader>,
+    /// How often the TLS identity is checked for changes (never if unset)
+    #[serde(default)]
+    pub tls_reload_interval: Option<Duration>,
     /// Compression method
     pub compression: CompressionMethod,
     /// Whether to use HTTP interface
@@ -69,10 +88,15 @@ pub struct ClientOptions {
     pub use_compression: bool,
     /// Compression level
     pub compression_level: u8,
+    /// Whether to verify the checksums of received compressed blocks
+    pub verify_checksums: bool,
     /// Whether to use connection pooling
     pub use_connection_pool: bool,
     /// Pool acquire timeout
     pub pool_acquire_timeout: Duration,
+    /// Retry pool acquisitions with backoff instead of queueing (off by default)
+    #[serde(default)]
+    pub pool_acquire_retry: Option<PoolRetryPolicy>,
     /// Whether to use retry logic
     pub use_retry: bool,
     /// Maximum retry attempts
@@ -85,6 +109,8 @@ pub struct ClientOptions {
     pub load_balancing_strategy: LoadBalancingStrategy,
     /// Server list for load balancing
     pub servers: Vec<ServerInfo>,
+    /// Zone or datacenter the client runs in (for zone-aware load balancing)
+    pub local_zone: Option<String>,
     /// Whether to use failover
     pub use_failover: bool,
     /// Failover timeout
@@ -101,6 +127,44 @@ pub struct ClientOptions {
     pub use_tracing: bool,
     /// Tracing level
     pub tracing_level: TracingLevel,
+    /// Whether experimental transactions (BEGIN/COMMIT/ROLLBACK) are allowed
+    pub use_experimental_transactions: bool,
+    /// Wire-level packet tracer, shared by all connections (disabled by default)
+    #[serde(skip)]
+    pub packet_tracer: PacketTracer,
+    /// Client information overrides; unset fields are detected from the environment
+    #[serde(default)]
+    pub client_info: ClientInfo,
+    /// What to do with session settings, role and temporary tables after a reconnect
+    #[serde(default)]
+    pub session_restore: SessionRestorePolicy,
+    /// Memory that streams may buffer across the client before pausing (unlimited if unset)
+    #[serde(default)]
+    pub max_buffered_result_memory: Option<usize>,
+    /// Bind HTTP requests of each connection to a server session (off by default)
+    #[serde(default)]
+    pub http_session: Option<HttpSessionOptions>,
+    /// Statements cached per connection (`DEFAULT_STATEMENT_CACHE_SIZE` if unset, 0 disables)
+    #[serde(default)]
+    pub statement_cache_size: Option<usize>,
+    /// Channel of structured warnings, shared by all connections
+    #[serde(skip)]
+    pub warnings: WarningChannel,
+    /// Duration above which a query is reported as slow (never if unset)
+    #[serde(default)]
+    pub slow_query_threshold: Option<Duration>,
+    /// Share of slow SELECTs explained in the background, from 0.0 to 1.0
+    #[serde(default)]
+    pub slow_query_explain_rate: f64,
+    /// Handler of changes the server pushes mid-session
+    #[serde(skip)]
+    pub server_event_handler: Option<ServerEventHandler>,
+    /// Named query templates, shared by every clone of the client
+    #[serde(skip)]
+    pub queries: QueryRegistry,
+    /// Have the server explain every query template on `Client::connect`
+    #[serde(default)]
+    pub strict_queries: bool,
 }
 
 impl ClientOptions {
@@ -111,20 +175,25 @@ impl ClientOptions {
             port: 9000,
             database: "default".to_string(),
             username: "default".to_string(),
-            password: "".to_string(),
+            password: Secret::default(),
             connect_timeout: Duration::from_secs(10),
             query_timeout: Duration::from_secs(300),
             read_timeout: Duration::from_secs(60),
             write_timeout: Duration::from_secs(60),
             keep_alive_timeout: Duration::from_secs(300),
+            keepalive_ping_interval: Some(DEFAULT_KEEPALIVE_INTERVAL),
             max_connections: 10,
             min_connections: 2,
+            lazy_connect: false,
+            fail_fast_on_startup: false,
             idle_timeout: Duration::from_secs(600),
             use_tls: false,
             tls_cert_path: None,
             tls_key_path: None,
             tls_ca_path: None,
             tls_verify: true,
+            tls_reloader: None,
+            tls_reload_interval: None,
             compression: CompressionMethod::LZ4,
             use_http: false,
             http_path: "/".to_string(),
@@ -138,14 +207,17 @@ impl ClientOptions {
             native_protocol_version: 54428,
             use_compression: true,
             compression_level: 3,
+            verify_checksums: false,
             use_connection_pool: true,
             pool_acquire_timeout: Duration::from_secs(30),
+            pool_acquire_retry: None,
             use_retry: true,
             max_retries: 3,
             retry_delay: Duration::from_millis(100),
             use_load_balancing: false,
             load_balancing_strategy: LoadBalancingStrategy::RoundRobin,
             servers: Vec::new(),
+            local_zone: None,
             use_failover: false,
             failover_timeout: Duration::from_secs(5),
             use_health_checks: false,
@@ -154,6 +226,19 @@ impl ClientOptions {
             metrics_prefix: "clickhouse".to_string(),
             use_tracing: false,
             tracing_level: TracingLevel::Info,
+            use_experimental_transactions: false,
+            packet_tracer: PacketTracer::new(),
+            client_info: ClientInfo::new(),
+            session_restore: SessionRestorePolicy::default(),
+            max_buffered_result_memory: None,
+            http_session: None,
+            statement_cache_size: None,
+            warnings: WarningChannel::default(),
+            slow_query_threshold: None,
+            slow_query_explain_rate: 0.0,
+            server_event_handler: None,
+            queries: QueryRegistry::default(),
+            strict_queries: false,
         }
     }
 
@@ -183,7 +268,7 @@ impl ClientOptions {
 
     /// Set the password
     pub fn password(mut self, password: impl Into<String>) -> Self {
-        self.password = password.into();
+        self.password = Secret::new(password.into());
         self
     }
 
@@ -217,6 +302,21 @@ impl ClientOptions {
         self
     }
 
+    /// Ping the server after `interval` without packets during a streaming query
+    ///
+    /// Keeps NAT gateways and firewalls from dropping sparse streams such as
+    /// WATCH queries. Pongs are consumed by the client.
+    pub fn keepalive_ping_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_ping_interval = Some(interval);
+        self
+    }
+
+    /// Never ping the server during streaming queries
+    pub fn disable_keepalive_pings(mut self) -> Self {
+        self.keepalive_ping_interval = None;
+        self
+    }
+
     /// Set the maximum number of connections
     pub fn max_connections(mut self, max_connections: usize) -> Self {
         self.max_connections = max_connections;
@@ -229,6 +329,19 @@ impl ClientOptions {
         self
     }
 
+    /// Set whether connections are opened on first use rather than up front
+    pub fn lazy_connect(mut self, lazy: bool) -> Self {
+        self.lazy_connect = lazy;
+        self
+    }
+
+    /// Set whether [`Client::connect`](crate::Client::connect) opens the
+    /// initial connections itself and returns their errors
+    pub fn fail_fast_on_startup(mut self, fail_fast: bool) -> Self {
+        self.fail_fast_on_startup = fail_fast;
+        self
+    }
+
     /// Set the idle timeout
     pub fn idle_timeout(mut self, timeout: Duration) -> Self {
         self.idle_timeout = timeout;
@@ -271,6 +384,18 @@ impl ClientOptions {
         self
     }
 
+    /// Take the TLS identity from a reloader instead of the paths
+    pub fn tls_reloader(mut self, reloader: TlsReloader) -> Self {
+        self.tls_reloader = Some(reloader);
+        self
+    }
+
+    /// Check the TLS identity for changes every `interval`
+    pub fn tls_reload_interval(mut self, interval: Duration) -> Self {
+        self.tls_reload_interval = Some(interval);
+        self
+    }
+
     /// Set compression method
     pub fn compression(mut self, method: CompressionMethod) -> Self {
         self.compression = method;
@@ -379,12 +504,31 @@ impl ClientOptions {
         self
     }
 
+    /// Get the compression method in effect, [`CompressionMethod::None`] when disabled
+    pub fn effective_compression(&self) -> CompressionMethod {
+        if self.use_compression {
+            self.compression
+        } else {
+            CompressionMethod::None
+        }
+    }
+
     /// Set compression level
     pub fn compression_level(mut self, level: u8) -> Self {
         self.compression_level = level;
         self
     }
 
+    /// Verify the checksums of received compressed blocks
+    ///
+    /// A corrupted block fails with [`Error::IntegrityCheck`] and the connection
+    /// is reset. Worth enabling on flaky networks; sent blocks always carry a
+    /// checksum.
+    pub fn verify_checksums(mut self, verify: bool) -> Self {
+        self.verify_checksums = verify;
+        self
+    }
+
     /// Enable connection pooling
     pub fn enable_connection_pool(mut self) -> Self {
         self.use_connection_pool = true;
@@ -403,6 +547,12 @@ impl ClientOptions {
         self
     }
 
+    /// Retry pool acquisitions with jittered backoff while all connections are in use
+    pub fn pool_acquire_retry(mut self, policy: PoolRetryPolicy) -> Self {
+        self.pool_acquire_retry = Some(policy);
+        self
+    }
+
     /// Enable retry logic
     pub fn enable_retry(mut self) -> Self {
         self.use_retry = true;
@@ -451,6 +601,12 @@ impl ClientOptions {
         self
     }
 
+    /// Set the local zone for zone-aware load balancing
+    pub fn local_zone(mut self, zone: impl Into<String>) -> Self {
+        self.local_zone = Some(zone.into());
+        self
+    }
+
     /// Enable failover
     pub fn enable_failover(mut self) -> Self {
         self.use_failover = true;
@@ -523,6 +679,145 @@ impl ClientOptions {
         self
     }
 
+    /// Enable experimental transactions
+    pub fn enable_experimental_transactions(mut self) -> Self {
+        self.use_experimental_transactions = true;
+        self
+    }
+
+    /// Disable experimental transactions
+    pub fn disable_experimental_transactions(mut self) -> Self {
+        self.use_experimental_transactions = false;
+        self
+    }
+
+    /// Set the packet tracer
+    pub fn packet_tracer(mut self, tracer: PacketTracer) -> Self {
+        self.packet_tracer = tracer;
+        self
+    }
+
+    /// Enable wire-level packet tracing
+    pub fn enable_packet_tracing(self) -> Self {
+        self.packet_tracer.enable();
+        self
+    }
+
+    /// Disable wire-level packet tracing
+    pub fn disable_packet_tracing(self) -> Self {
+        self.packet_tracer.disable();
+        self
+    }
+
+    /// Set the warning channel
+    pub fn warnings(mut self, warnings: WarningChannel) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Report queries running longer than `threshold` as slow
+    pub fn slow_query_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_query_threshold = Some(threshold);
+        self
+    }
+
+    /// Explain a share of slow SELECTs in the background, from 0.0 to 1.0
+    pub fn slow_query_explain_rate(mut self, rate: f64) -> Self {
+        self.slow_query_explain_rate = rate;
+        self
+    }
+
+    /// Call `callback` for every change the server pushes mid-session
+    pub fn on_server_event<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ServerEvent) + Send + Sync + 'static,
+    {
+        self.server_event_handler = Some(ServerEventHandler::new(callback));
+        self
+    }
+
+    /// Set the named query templates run with `Client::run`
+    pub fn queries(mut self, queries: QueryRegistry) -> Self {
+        self.queries = queries;
+        self
+    }
+
+    /// Fail `Client::connect` if the server cannot explain a query template
+    pub fn strict_queries(mut self, strict: bool) -> Self {
+        self.strict_queries = strict;
+        self
+    }
+
+    /// Pause result streams while they buffer more than `bytes` in total
+    pub fn max_buffered_result_memory(mut self, bytes: usize) -> Self {
+        self.max_buffered_result_memory = Some(bytes);
+        self
+    }
+
+    /// Bind HTTP requests of each connection to a sticky server session
+    pub fn http_session(mut self, options: HttpSessionOptions) -> Self {
+        self.http_session = Some(options);
+        self
+    }
+
+    /// Set how many prepared statements each connection caches (0 disables)
+    pub fn statement_cache_size(mut self, size: usize) -> Self {
+        self.statement_cache_size = Some(size);
+        self
+    }
+
+    /// Set what happens to session state when a connection reconnects
+    pub fn session_restore(mut self, policy: SessionRestorePolicy) -> Self {
+        self.session_restore = policy;
+        self
+    }
+
+    /// Set the client information overrides
+    pub fn client_info(mut self, client_info: ClientInfo) -> Self {
+        self.client_info = client_info;
+        self
+    }
+
+    /// Override the OS user reported to the server
+    pub fn os_user(mut self, os_user: impl Into<String>) -> Self {
+        self.client_info = self.client_info.os_user(os_user);
+        self
+    }
+
+    /// Override the client hostname reported to the server
+    pub fn client_hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.client_info = self.client_info.client_hostname(hostname);
+        self
+    }
+
+    /// Override the client name reported to the server
+    pub fn client_name(mut self, name: impl Into<String>) -> Self {
+        self.client_info = self.client_info.client_name(name);
+        self
+    }
+
+    /// Set the quota key sent with the handshake and every query
+    pub fn quota_key(mut self, quota_key: impl Into<String>) -> Self {
+        self.client_info = self.client_info.quota_key(quota_key);
+        self
+    }
+
+    /// Get the client information to report, with unset fields detected
+    pub fn resolved_client_info(&self) -> ClientInfo {
+        let mut client_info = self.client_info.clone();
+        if client_info.interface.is_none() {
+            let interface = if self.use_grpc {
+                "gRPC"
+            } else if self.use_http || self.use_websocket {
+                "HTTP"
+            } else {
+                "TCP"
+            };
+            client_info = client_info.interface(interface);
+        }
+        client_info.resolve()
+    }
+
     /// Build connection string
     pub fn build_connection_string(&self) -> String {
         if self.use_grpc {
@@ -560,6 +855,12 @@ impl ClientOptions {
             return Err(Error::Configuration("Username cannot be empty".to_string()));
         }
 
+        if self.lazy_connect && self.fail_fast_on_startup {
+            return Err(Error::Configuration(
+                "Lazy connect and fail fast on startup cannot both be enabled".to_string(),
+            ));
+        }
+
         if self.max_connections < self.min_connections {
             return Err(Error::Configuration(
                 "Max connections cannot be less than min connections".to_string(),
@@ -576,6 +877,22 @@ impl ClientOptions {
             }
         }
 
+        if self.tls_reload_interval == Some(Duration::ZERO) {
+            return Err(Error::Configuration("TLS reload interval cannot be zero".to_string()));
+        }
+
+        if !(0.0..=1.0).contains(&self.slow_query_explain_rate) {
+            return Err(Error::Configuration("Slow query explain rate must be between 0 and 1".to_string()));
+        }
+
+        if let Some(policy) = &self.pool_acquire_retry {
+            policy.validate()?;
+        }
+
+        if self.keepalive_ping_interval == Some(Duration::ZERO) {
+            return Err(Error::Configuration("Keep-alive ping interval cannot be zero".to_string()));
+        }
+
         if self.use_compression {
             if self.compression_level > 9 {
                 return Err(Error::Configuration(
@@ -625,6 +942,19 @@ impl CompressionMethod {
     }
 }
 
+impl From<CompressionMethod> for crate::compression::CompressionMethod {
+    fn from(method: CompressionMethod) -> Self {
+        match method {
+            CompressionMethod::None => Self::None,
+            CompressionMethod::LZ4 => Self::LZ4,
+            CompressionMethod::ZSTD => Self::ZSTD,
+            CompressionMethod::GZIP => Self::GZIP,
+            CompressionMethod::BZIP2 => Self::BZIP2,
+            CompressionMethod::XZ => Self::XZ,
+        }
+    }
+}
+
 /// Load balancing strategies
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LoadBalancingStrategy {
@@ -636,6 +966,10 @@ pub enum LoadBalancingStrategy {
     LeastConnections,
     /// Weighted round-robin load balancing
     WeightedRoundRobin,
+    /// EWMA latency-weighted load balancing
+    LatencyWeighted,
+    /// Zone-aware load balancing (requires `local_zone`)
+    ZoneAware,
 }
 
 impl LoadBalancingStrategy {
@@ -646,6 +980,8 @@ impl LoadBalancingStrategy {
             LoadBalancingStrategy::Random => "random",
             LoadBalancingStrategy::LeastConnections => "least_connections",
             LoadBalancingStrategy::WeightedRoundRobin => "weighted_round_robin",
+            LoadBalancingStrategy::LatencyWeighted => "latency_weighted",
+            LoadBalancingStrategy::ZoneAware => "zone_aware",
         }
     }
 }
@@ -663,6 +999,9 @@ pub struct ServerInfo {
     pub healthy: bool,
     /// Server priority
     pub priority: u32,
+    /// Zone or datacenter the server is located in
+    #[serde(default)]
+    pub zone: Option<String>,
 }
 
 impl ServerInfo {
@@ -674,6 +1013,7 @@ impl ServerInfo {
             weight: 1,
             healthy: true,
             priority: 0,
+            zone: None,
         }
     }
 
@@ -689,6 +1029,12 @@ impl ServerInfo {
         self
     }
 
+    /// Set server zone
+    pub fn zone(mut self, zone: impl Into<String>) -> Self {
+        self.zone = Some(zone.into());
+        self
+    }
+
     /// Mark server as healthy
     pub fn mark_healthy(&mut self) {
         self.healthy = true;
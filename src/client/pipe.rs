@@ -0,0 +1,216 @@
+//! Streaming a SELECT into an INSERT block by block
+//!
+//! ```rust
+//! # async fn example(client: clickhouse_rs::Client) -> clickhouse_rs::error::Result<()> {
+//! let report = client
+//!     .pipe("SELECT id, name, score FROM events", "events_copy")
+//!     .filter_rows(|row| row.get(2).and_then(|v| v.as_ref()).is_some())
+//!     .rename_column("score", "points")
+//!     .select_columns(["id", "points"])
+//!     .run()
+//!     .await?;
+//! println!("copied {} rows", report.rows_written);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each block of the result is transformed and inserted before the next one is
+//! read from the connection, so the pipe holds one server block at a time, or
+//! the chunks left of it when the read settings cut blocks down. Reads and
+//! writes go through separate pooled connections; the SELECT keeps its
+//! connection busy until the last insert is done.
+
+use crate::client::{Client, QuerySettings};
+use crate::error::{Error, Result};
+use crate::types::{Block, Row};
+
+/// Transform applied to every block; `None` drops the block
+pub type BlockTransform = Box<dyn FnMut(Block) -> Result<Option<Block>> + Send>;
+
+/// Totals of a finished pipe
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipeReport {
+    /// Blocks received from the SELECT
+    pub blocks_read: u64,
+    /// Rows received from the SELECT
+    pub rows_read: u64,
+    /// Blocks inserted
+    pub blocks_written: u64,
+    /// Rows the server reports as written
+    pub rows_written: u64,
+    /// Bytes the server reports as written
+    pub bytes_written: u64,
+}
+
+/// Builder streaming the result of a SELECT into a table
+pub struct Pipe<'a> {
+    client: &'a Client,
+    select_sql: String,
+    table: String,
+    transforms: Vec<BlockTransform>,
+    settings: Option<QuerySettings>,
+}
+
+impl<'a> Pipe<'a> {
+    pub(crate) fn new(client: &'a Client, select_sql: &str, table: &str) -> Self {
+        Self {
+            client,
+            select_sql: select_sql.to_string(),
+            table: table.to_string(),
+            transforms: Vec::new(),
+            settings: None,
+        }
+    }
+
+    /// Add a transform that may rewrite or drop each block
+    ///
+    /// Transforms run in the order they were added.
+    pub fn transform<F>(mut self, transform: F) -> Self
+    where
+        F: FnMut(Block) -> Result<Option<Block>> + Send + 'static,
+    {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Rewrite each block
+    pub fn map_blocks<F>(self, mut f: F) -> Self
+    where
+        F: FnMut(Block) -> Result<Block> + Send + 'static,
+    {
+        self.transform(move |block| f(block).map(Some))
+    }
+
+    /// Keep only the rows matching a predicate
+    pub fn filter_rows<F>(self, mut predicate: F) -> Self
+    where
+        F: FnMut(&Row) -> bool + Send + 'static,
+    {
+        self.transform(move |block| {
+            let keep: Vec<usize> = (0..block.row_count)
+                .filter(|&index| block.get_row(index).is_some_and(|row| predicate(&row)))
+                .collect();
+            Ok(Some(if keep.len() == block.row_count { block } else { block.take(&keep) }))
+        })
+    }
+
+    /// Keep only the given columns, in the given order
+    pub fn select_columns<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let names: Vec<String> = names.into_iter().map(Into::into).collect();
+        self.transform(move |mut block| {
+            let mut columns = Vec::with_capacity(names.len());
+            for name in &names {
+                let position = block
+                    .columns
+                    .iter()
+                    .position(|column| &column.name == name)
+                    .ok_or_else(|| Error::InvalidData(format!("Column '{}' is not in the piped result", name)))?;
+                columns.push(block.columns.swap_remove(position));
+            }
+            block.columns = columns;
+            Ok(Some(block))
+        })
+    }
+
+    /// Rename a column of every block
+    pub fn rename_column(self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        let (from, to) = (from.into(), to.into());
+        self.transform(move |mut block| {
+            if let Some(column) = block.get_column_mut(&from) {
+                column.name = to.clone();
+            }
+            Ok(Some(block))
+        })
+    }
+
+    /// Set the settings of the inserts
+    pub fn insert_settings(mut self, settings: QuerySettings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// Stream the result into the table
+    ///
+    /// Stops at the first failed read, transform or insert; blocks inserted
+    /// before that stay in the table.
+    pub async fn run(mut self) -> Result<PipeReport> {
+        let mut report = PipeReport::default();
+        let mut stream = self.client.query_stream(&self.select_sql);
+        while let Some(block) = stream.next_block().await? {
+            report.blocks_read += 1;
+            report.rows_read += block.row_count as u64;
+            let Some(block) = apply_transforms(&mut self.transforms, block)? else {
+                continue;
+            };
+            let result = match &self.settings {
+                Some(settings) => self.client.insert_with_settings(&self.table, block, settings.clone()).await?,
+                None => self.client.insert(&self.table, block).await?,
+            };
+            report.blocks_written += 1;
+            report.rows_written += result.rows_written;
+            report.bytes_written += result.bytes_written;
+        }
+        tracing::debug!(
+            "Piped {} of {} rows into {}",
+            report.rows_written,
+            report.rows_read,
+            self.table
+        );
+        Ok(report)
+    }
+}
+
+/// Run a block through the transforms; empty blocks are dropped
+fn apply_transforms(transforms: &mut [BlockTransform], block: Block) -> Result<Option<Block>> {
+    let mut block = block;
+    for transform in transforms.iter_mut() {
+        match transform(block)? {
+            Some(next) => block = next,
+            None => return Ok(None),
+        }
+    }
+    Ok((block.row_count > 0).then_some(block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+    use crate::types::{Column, ColumnData, Value};
+
+    fn sample() -> Block {
+        Block::with_columns(vec![
+            Column::new("id", "UInt64", ColumnData::UInt64(vec![1, 2, 3])),
+            Column::new("name", "String", ColumnData::String(vec!["a".into(), "b".into(), "c".into()])),
+            Column::new("score", "Float64", ColumnData::Float64(vec![0.5, 1.5, 2.5])),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_pipe_transforms() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        let mut pipe = client
+            .pipe("SELECT id, name, score FROM t", "t2")
+            .filter_rows(|row| row.get(0) != Some(&Some(Value::UInt64(2))))
+            .rename_column("score", "points")
+            .select_columns(["points", "id"]);
+
+        let block = apply_transforms(&mut pipe.transforms, sample()).unwrap().unwrap();
+        assert_eq!(block.row_count, 2);
+        assert_eq!(block.columns().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["points", "id"]);
+        assert_eq!(block.get_column("id").unwrap().get_value(1), Some(Value::UInt64(3)));
+
+        let mut pipe = client.pipe("SELECT 1", "t2").filter_rows(|_| false);
+        assert!(apply_transforms(&mut pipe.transforms, sample()).unwrap().is_none());
+
+        let mut pipe = client.pipe("SELECT 1", "t2").select_columns(["missing"]);
+        assert!(matches!(apply_transforms(&mut pipe.transforms, sample()), Err(Error::InvalidData(_))));
+
+        let mut pipe = client.pipe("SELECT 1", "t2").transform(|_| Ok(None)).map_blocks(|_| unreachable!());
+        assert!(apply_transforms(&mut pipe.transforms, sample()).unwrap().is_none());
+    }
+}
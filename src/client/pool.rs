@@ -1,11 +1,15 @@
 //! Connection pool for ClickHouse
 
 use crate::error::{Error, Result};
-use crate::client::ClientOptions;
+use crate::client::metrics::metric_names;
+use crate::client::{ClientOptions, MetricsRegistry};
 use super::Connection;
-use std::collections::VecDeque;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use tokio::sync::{Mutex, Semaphore};
+use std::time::Instant;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore, TryAcquireError};
 use tokio::time::{timeout, Duration};
 use tracing::{debug, warn, error};
 
@@ -15,10 +19,12 @@ pub struct ConnectionPool {
     options: ClientOptions,
     /// Available connections
     available: Arc<Mutex<VecDeque<Connection>>>,
-    /// Semaphore for limiting concurrent connections
+    /// Semaphore for limiting concurrent connections (fair, FIFO waiter queue)
     semaphore: Arc<Semaphore>,
     /// Pool statistics
     stats: Arc<Mutex<PoolStats>>,
+    /// Registry that acquisition wait times are reported to
+    metrics: Option<Arc<MetricsRegistry>>,
 }
 
 /// Pool statistics
@@ -36,8 +42,138 @@ pub struct PoolStats {
     pub connection_requests: usize,
     /// Number of connection timeouts
     pub connection_timeouts: usize,
+    /// Number of callers currently waiting for a connection
+    pub waiting_requests: usize,
+    /// Highest number of callers waiting at the same time
+    pub max_waiting_requests: usize,
+    /// Most recent acquisition wait times (bounded sample window)
+    pub recent_wait_times: VecDeque<Duration>,
+    /// Number of connections discarded, by reason
+    pub discarded_connections: HashMap<DiscardReason, usize>,
+    /// Number of acquisitions retried after backing off
+    pub acquire_retries: usize,
+    /// Number of acquisitions that gave up when the retry wait budget ran out
+    pub exhausted_requests: usize,
 }
 
+/// Backoff for retrying pool acquisitions while every connection is in use
+///
+/// Unlike query retries, this only covers waiting for a free connection. Each
+/// attempt takes a connection only if one is free right away, then backs off
+/// for a jittered, doubling delay, so retrying callers do not queue in FIFO
+/// order like plain waiters do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolRetryPolicy {
+    /// Total time to keep retrying before failing with [`Error::PoolExhausted`]
+    pub wait_budget: Duration,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Longest delay between retries
+    pub max_backoff: Duration,
+}
+
+impl PoolRetryPolicy {
+    /// Create a policy retrying for up to `wait_budget`
+    pub fn new(wait_budget: Duration) -> Self {
+        Self {
+            wait_budget,
+            ..Self::default()
+        }
+    }
+
+    /// Set the delay before the first retry
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Set the longest delay between retries
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Get the delay before retry number `attempt`, counting from 0
+    ///
+    /// The delay doubles with every attempt up to the maximum, and a random
+    /// half of it is taken off so that waiters spread out.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.min(31)))
+            .min(self.max_backoff);
+        let jitter = rand::thread_rng().gen_range(0.0..=0.5);
+        ceiling.mul_f64(1.0 - jitter)
+    }
+
+    /// Check the policy
+    pub fn validate(&self) -> Result<()> {
+        if self.wait_budget.is_zero() {
+            return Err(Error::Configuration("Pool retry wait budget must be greater than 0".to_string()));
+        }
+        if self.initial_backoff > self.max_backoff {
+            return Err(Error::Configuration(
+                "Pool retry initial backoff cannot exceed the maximum backoff".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for PoolRetryPolicy {
+    fn default() -> Self {
+        Self {
+            wait_budget: Duration::from_secs(10),
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Why the pool discarded a connection instead of reusing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiscardReason {
+    /// The connection hit an error that left it in an unknown state
+    Broken,
+    /// A query was abandoned mid-stream and had to be cancelled
+    QueryAbandoned,
+    /// The connection was already closed
+    Disconnected,
+    /// The connection exceeded the idle timeout
+    IdleTimeout,
+    /// The pool was already at capacity
+    PoolFull,
+    /// The connection was opened with a TLS identity that has since been reloaded
+    TlsReloaded,
+}
+
+impl DiscardReason {
+    /// All discard reasons
+    pub const ALL: [DiscardReason; 6] = [
+        DiscardReason::Broken,
+        DiscardReason::QueryAbandoned,
+        DiscardReason::Disconnected,
+        DiscardReason::IdleTimeout,
+        DiscardReason::PoolFull,
+        DiscardReason::TlsReloaded,
+    ];
+
+    /// Get the reason name
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiscardReason::Broken => "broken",
+            DiscardReason::QueryAbandoned => "query_abandoned",
+            DiscardReason::Disconnected => "disconnected",
+            DiscardReason::IdleTimeout => "idle_timeout",
+            DiscardReason::PoolFull => "pool_full",
+            DiscardReason::TlsReloaded => "tls_reloaded",
+        }
+    }
+}
+
+/// Number of recent wait times kept for percentile calculation
+pub const WAIT_TIME_SAMPLE_SIZE: usize = 1024;
+
 impl PoolStats {
     /// Create new pool stats
     pub fn new() -> Self {
@@ -48,7 +184,44 @@ impl PoolStats {
             total_wait_time: Duration::from_secs(0),
             connection_requests: 0,
             connection_timeouts: 0,
+            waiting_requests: 0,
+            max_waiting_requests: 0,
+            recent_wait_times: VecDeque::new(),
+            discarded_connections: HashMap::new(),
+            acquire_retries: 0,
+            exhausted_requests: 0,
+        }
+    }
+
+    /// Record the wait time of a successful acquisition
+    pub fn record_wait(&mut self, wait: Duration) {
+        self.total_wait_time += wait;
+        if self.recent_wait_times.len() >= WAIT_TIME_SAMPLE_SIZE {
+            self.recent_wait_times.pop_front();
         }
+        self.recent_wait_times.push_back(wait);
+    }
+
+    /// Record a discarded connection
+    pub fn record_discard(&mut self, reason: DiscardReason) {
+        *self.discarded_connections.entry(reason).or_insert(0) += 1;
+    }
+
+    /// Get the number of connections discarded for the given reason
+    pub fn discarded(&self, reason: DiscardReason) -> usize {
+        self.discarded_connections.get(&reason).copied().unwrap_or(0)
+    }
+
+    /// Get the wait time at the given quantile (0.0 to 1.0) over recent acquisitions
+    pub fn wait_time_percentile(&self, quantile: f64) -> Duration {
+        if self.recent_wait_times.is_empty() {
+            return Duration::from_secs(0);
+        }
+
+        let mut sorted: Vec<Duration> = self.recent_wait_times.iter().copied().collect();
+        sorted.sort();
+        let rank = (quantile.clamp(0.0, 1.0) * (sorted.len() - 1) as f64).round() as usize;
+        sorted[rank]
     }
 
     /// Get the average wait time
@@ -90,29 +263,40 @@ impl ConnectionPool {
             available: Arc::new(Mutex::new(VecDeque::new())),
             semaphore,
             stats: Arc::new(Mutex::new(PoolStats::new())),
+            metrics: None,
         };
 
-        // Initialize the pool with minimum connections
-        tokio::spawn({
-            let pool = pool.clone();
-            async move {
-                if let Err(e) = pool.initialize_pool().await {
-                    error!("Failed to initialize connection pool: {}", e);
+        // Initialize the pool with minimum connections, unless the caller
+        // connects on first use or opens them itself to see the errors
+        if !pool.options.lazy_connect && !pool.options.fail_fast_on_startup {
+            tokio::spawn({
+                let pool = pool.clone();
+                async move {
+                    if let Err(e) = pool.initialize_pool(false).await {
+                        error!("Failed to initialize connection pool: {}", e);
+                    }
                 }
-            }
-        });
+            });
+        }
 
         Ok(pool)
     }
 
-    /// Initialize the pool with minimum connections
-    async fn initialize_pool(&self) -> Result<()> {
-        let min_connections = self.options.min_connections;
+    /// Open connections until the pool holds the minimum number
+    ///
+    /// With `fail_fast` the first connection error is returned; otherwise it
+    /// is logged and the pool starts with the connections opened so far.
+    pub(crate) async fn initialize_pool(&self, fail_fast: bool) -> Result<()> {
+        let missing = self
+            .options
+            .min_connections
+            .saturating_sub(self.stats.lock().await.total_connections);
         let mut connections = Vec::new();
 
-        for _ in 0..min_connections {
+        for _ in 0..missing {
             match self.create_connection().await {
                 Ok(conn) => connections.push(conn),
+                Err(e) if fail_fast => return Err(e),
                 Err(e) => {
                     warn!("Failed to create initial connection: {}", e);
                     break;
@@ -129,8 +313,8 @@ impl ConnectionPool {
             drop(available);
 
             let mut stats = self.stats.lock().await;
-            stats.idle_connections = connections_len;
-            stats.total_connections = connections_len;
+            stats.idle_connections += connections_len;
+            stats.total_connections += connections_len;
             
             debug!("Initialized pool with {} connections", connections_len);
         } else {
@@ -139,71 +323,185 @@ impl ConnectionPool {
         Ok(())
     }
 
+    /// Report acquisition wait times, retries and exhaustion to a registry
+    pub(crate) fn with_metrics(mut self, metrics: Arc<MetricsRegistry>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Get a connection from the pool
+    ///
+    /// Callers are served in FIFO order and wait at most
+    /// `pool_acquire_timeout` for a connection, unless
+    /// `pool_acquire_retry` is set; see
+    /// [`get_connection_with_retry`](Self::get_connection_with_retry).
     pub async fn get_connection(&self) -> Result<PooledConnection> {
+        match self.options.pool_acquire_retry {
+            Some(policy) => self.get_connection_with_retry(&policy).await,
+            None => self.get_connection_timeout(self.options.pool_acquire_timeout).await,
+        }
+    }
+
+    /// Get a connection from the pool, backing off while none is free
+    ///
+    /// Fails with [`Error::PoolExhausted`], carrying the pool stats at that
+    /// point, once the policy's wait budget runs out.
+    pub async fn get_connection_with_retry(&self, policy: &PoolRetryPolicy) -> Result<PooledConnection> {
+        let start_time = Instant::now();
+        {
+            let mut stats = self.stats.lock().await;
+            stats.connection_requests += 1;
+        }
+
+        let mut attempt = 0;
+        let permit = loop {
+            match self.semaphore.clone().try_acquire_owned() {
+                Ok(permit) => break permit,
+                Err(TryAcquireError::Closed) => {
+                    return Err(Error::ConnectionPool("Connection pool is closed".to_string()));
+                }
+                Err(TryAcquireError::NoPermits) => {}
+            }
+
+            let remaining = policy.wait_budget.saturating_sub(start_time.elapsed());
+            if remaining.is_zero() {
+                let waited = start_time.elapsed();
+                let stats = {
+                    let mut stats = self.stats.lock().await;
+                    stats.exhausted_requests += 1;
+                    stats.clone()
+                };
+                warn!("Connection pool exhausted after {} attempts over {:?}", attempt + 1, waited);
+                self.report_acquire(waited, attempt, true).await;
+                return Err(Error::PoolExhausted {
+                    waited,
+                    attempts: attempt as usize + 1,
+                    stats: Box::new(stats),
+                });
+            }
+
+            let backoff = policy.backoff(attempt).min(remaining);
+            attempt += 1;
+            {
+                let mut stats = self.stats.lock().await;
+                stats.acquire_retries += 1;
+                stats.waiting_requests += 1;
+                stats.max_waiting_requests = stats.max_waiting_requests.max(stats.waiting_requests);
+            }
+            debug!("No pooled connection free, retrying in {:?}", backoff);
+            tokio::time::sleep(backoff).await;
+            let mut stats = self.stats.lock().await;
+            stats.waiting_requests = stats.waiting_requests.saturating_sub(1);
+        };
+
+        let waited = start_time.elapsed();
+        self.stats.lock().await.record_wait(waited);
+        self.report_acquire(waited, attempt, false).await;
+        self.checkout(permit).await
+    }
+
+    /// Report an acquisition to the metrics registry, if there is one
+    async fn report_acquire(&self, waited: Duration, retries: u32, exhausted: bool) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+        metrics
+            .observe_histogram(metric_names::CONNECTION_POOL_ACQUIRE_WAIT, waited.as_secs_f64(), None)
+            .await
+            .ok();
+        if retries > 0 {
+            metrics
+                .increment_counter(metric_names::CONNECTION_POOL_ACQUIRE_RETRIES, retries as u64, None)
+                .await
+                .ok();
+        }
+        if exhausted {
+            metrics.increment_counter(metric_names::CONNECTION_POOL_EXHAUSTED, 1, None).await.ok();
+        }
+    }
+
+    /// Get a connection from the pool, waiting at most `wait` for one
+    pub async fn get_connection_timeout(&self, wait: Duration) -> Result<PooledConnection> {
         let start_time = std::time::Instant::now();
-        
-        // Update stats
+
+        // Enqueue as a waiter
         {
             let mut stats = self.stats.lock().await;
             stats.connection_requests += 1;
+            stats.waiting_requests += 1;
+            stats.max_waiting_requests = stats.max_waiting_requests.max(stats.waiting_requests);
         }
 
-        // Try to get an existing connection first
+        // The semaphore hands out permits in request order, so waiters cannot starve
+        let permit = timeout(wait, self.semaphore.clone().acquire_owned()).await;
+
+        let permit = {
+            let mut stats = self.stats.lock().await;
+            stats.waiting_requests = stats.waiting_requests.saturating_sub(1);
+            match permit {
+                Ok(Ok(permit)) => {
+                    stats.record_wait(start_time.elapsed());
+                    permit
+                }
+                Ok(Err(_)) => {
+                    return Err(Error::ConnectionPool("Connection pool is closed".to_string()));
+                }
+                Err(_) => {
+                    stats.connection_timeouts += 1;
+                    warn!("Timed out after {:?} waiting for a pooled connection", wait);
+                    return Err(Error::Timeout(wait));
+                }
+            }
+        };
+
+        self.report_acquire(start_time.elapsed(), 0, false).await;
+        self.checkout(permit).await
+    }
+
+    /// Hand out a connection under an acquired permit
+    async fn checkout(&self, permit: OwnedSemaphorePermit) -> Result<PooledConnection> {
+        // Try to reuse an existing connection first
         if let Some(conn) = self.try_get_existing_connection().await? {
-            return Ok(conn);
+            return Ok(PooledConnection {
+                connection: Some(conn),
+                pool: self.clone(),
+                permit: Some(permit),
+            });
         }
 
-        // Wait for a permit to create a new connection
-        let permit = timeout(
-            self.options.pool_acquire_timeout,
-            self.semaphore.acquire()
-        ).await
-            .map_err(|_| Error::Timeout(self.options.pool_acquire_timeout))?
-            .map_err(|_| Error::Timeout(self.options.pool_acquire_timeout))?;
-
         // Create a new connection
         let conn = self.create_connection().await?;
-        
+
         // Update stats
         {
             let mut stats = self.stats.lock().await;
             stats.total_connections += 1;
             stats.active_connections += 1;
-            stats.total_wait_time += start_time.elapsed();
         }
 
-        // Drop the permit immediately since we don't need to hold onto it
-        drop(permit);
-        
         Ok(PooledConnection {
             connection: Some(conn),
             pool: self.clone(),
-            _permit: None,
+            permit: Some(permit),
         })
     }
 
     /// Try to get an existing connection from the pool
-    async fn try_get_existing_connection(&self) -> Result<Option<PooledConnection>> {
+    async fn try_get_existing_connection(&self) -> Result<Option<Connection>> {
         let mut available = self.available.lock().await;
         
         while let Some(mut conn) = available.pop_front() {
             // Check if the connection is still valid
-            if conn.is_connected() && !conn.is_idle(self.options.idle_timeout) {
+            let discard = self.discard_reason(&conn);
+            if discard.is_none() {
                 // Update stats
                 {
                     let mut stats = self.stats.lock().await;
                     stats.idle_connections = stats.idle_connections.saturating_sub(1);
                     stats.active_connections += 1;
                 }
-                
-                // We can't store the permit here due to lifetime issues
-                // Instead, we'll create a connection without a permit
-                return Ok(Some(PooledConnection {
-                    connection: Some(conn),
-                    pool: self.clone(),
-                    _permit: None,
-                }));
+
+                return Ok(Some(conn));
             } else {
                 // Connection is invalid or idle, drop it
                 if let Err(e) = conn.disconnect().await {
@@ -213,7 +511,11 @@ impl ConnectionPool {
                 // Update stats
                 {
                     let mut stats = self.stats.lock().await;
+                    stats.idle_connections = stats.idle_connections.saturating_sub(1);
                     stats.total_connections = stats.total_connections.saturating_sub(1);
+                    if let Some(reason) = discard {
+                        stats.record_discard(reason);
+                    }
                 }
             }
         }
@@ -222,47 +524,80 @@ impl ConnectionPool {
     }
 
     /// Create a new connection
+    ///
+    /// New connections always start with an empty session; session state is
+    /// only restored when the connection that recorded it reconnects.
     async fn create_connection(&self) -> Result<Connection> {
         let mut conn = Connection::new(self.options.clone());
         conn.connect().await?;
         Ok(conn)
     }
 
+    /// Determine why a connection cannot be reused, if it cannot
+    fn discard_reason(&self, conn: &Connection) -> Option<DiscardReason> {
+        if conn.has_pending_query() {
+            Some(DiscardReason::QueryAbandoned)
+        } else if conn.is_broken() {
+            Some(DiscardReason::Broken)
+        } else if !conn.is_connected() {
+            Some(DiscardReason::Disconnected)
+        } else if conn.is_idle(self.options.idle_timeout) {
+            Some(DiscardReason::IdleTimeout)
+        } else if conn.has_stale_tls() {
+            Some(DiscardReason::TlsReloaded)
+        } else {
+            None
+        }
+    }
+
     /// Return a connection to the pool
     async fn return_connection(&self, mut conn: Connection) {
-        // Check if the connection is still valid
-        if conn.is_connected() && !conn.is_idle(self.options.idle_timeout) {
-            let mut available = self.available.lock().await;
-            
-            // Only add back if we haven't exceeded max connections
-            if available.len() < self.options.max_connections {
-                available.push_back(conn);
-                
-                // Update stats
-                {
-                    let mut stats = self.stats.lock().await;
-                    stats.active_connections = stats.active_connections.saturating_sub(1);
-                    stats.idle_connections += 1;
+        let reason = match self.discard_reason(&conn) {
+            Some(DiscardReason::QueryAbandoned) => {
+                // A query future was dropped mid-flight; the stream cannot be reused
+                warn!("Connection {} returned with a query in flight, cancelling", conn.id());
+                if let Err(e) = conn.cancel_pending_query().await {
+                    warn!("Failed to cancel abandoned query: {}", e);
                 }
-                
-                debug!("Returned connection to pool");
-                return;
+                DiscardReason::QueryAbandoned
             }
-        }
-        
+            Some(reason) => reason,
+            None => {
+                let mut available = self.available.lock().await;
+
+                // Only add back if we haven't exceeded max connections
+                if available.len() < self.options.max_connections {
+                    available.push_back(conn);
+
+                    // Update stats
+                    {
+                        let mut stats = self.stats.lock().await;
+                        stats.active_connections = stats.active_connections.saturating_sub(1);
+                        stats.idle_connections += 1;
+                    }
+
+                    debug!("Returned connection to pool");
+                    return;
+                }
+
+                DiscardReason::PoolFull
+            }
+        };
+
         // Connection is invalid or pool is full, drop it
         if let Err(e) = conn.disconnect().await {
             warn!("Failed to disconnect connection: {}", e);
         }
-        
+
         // Update stats
         {
             let mut stats = self.stats.lock().await;
             stats.total_connections = stats.total_connections.saturating_sub(1);
             stats.active_connections = stats.active_connections.saturating_sub(1);
+            stats.record_discard(reason);
         }
-        
-        debug!("Dropped connection");
+
+        debug!("Dropped connection ({})", reason.as_str());
     }
 
     /// Get pool statistics
@@ -275,6 +610,11 @@ impl ConnectionPool {
         self.available.lock().await.len()
     }
 
+    /// Get the number of callers currently waiting for a connection
+    pub async fn waiting_requests(&self) -> usize {
+        self.stats.lock().await.waiting_requests
+    }
+
     /// Get the number of active connections
     pub async fn active_connections(&self) -> usize {
         self.stats.lock().await.active_connections
@@ -339,6 +679,7 @@ impl ConnectionPool {
                     let mut stats = self.stats.lock().await;
                     stats.idle_connections = stats.idle_connections.saturating_sub(1);
                     stats.total_connections = stats.total_connections.saturating_sub(1);
+                    stats.record_discard(DiscardReason::IdleTimeout);
                 }
             }
         }
@@ -358,6 +699,7 @@ impl Clone for ConnectionPool {
             available: Arc::clone(&self.available),
             semaphore: Arc::clone(&self.semaphore),
             stats: Arc::clone(&self.stats),
+            metrics: self.metrics.clone(),
         }
     }
 }
@@ -368,8 +710,8 @@ pub struct PooledConnection {
     connection: Option<Connection>,
     /// Reference to the pool
     pool: ConnectionPool,
-    /// Semaphore permit (optional for connections from existing pool)
-    _permit: Option<tokio::sync::SemaphorePermit<'static>>,
+    /// Semaphore permit, released once the connection is back in the pool
+    permit: Option<OwnedSemaphorePermit>,
 }
 
 impl PooledConnection {
@@ -411,10 +753,13 @@ impl std::ops::DerefMut for PooledConnection {
 impl Drop for PooledConnection {
     fn drop(&mut self) {
         if let Some(conn) = self.connection.take() {
-            // Return the connection to the pool
+            // Return the connection to the pool before releasing the permit,
+            // so the next waiter in line picks up the returned connection
             let pool = self.pool.clone();
+            let permit = self.permit.take();
             tokio::spawn(async move {
                 pool.return_connection(conn).await;
+                drop(permit);
             });
         }
     }
@@ -434,6 +779,222 @@ mod tests {
     use super::*;
     use std::time::Duration;
 
+    async fn local_pool(max_connections: usize) -> (ConnectionPool, tokio::net::TcpListener) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ClientOptions::new()
+            .host("127.0.0.1")
+            .port(port)
+            .max_connections(max_connections)
+            .min_connections(0);
+        (ConnectionPool::new(options).unwrap(), listener)
+    }
+
+    #[test]
+    fn test_pool_stats_wait_percentiles() {
+        let mut stats = PoolStats::new();
+        assert_eq!(stats.wait_time_percentile(0.99), Duration::from_secs(0));
+
+        for ms in 1..=100 {
+            stats.record_wait(Duration::from_millis(ms));
+        }
+        assert_eq!(stats.wait_time_percentile(0.0), Duration::from_millis(1));
+        assert_eq!(stats.wait_time_percentile(0.5), Duration::from_millis(51));
+        assert_eq!(stats.wait_time_percentile(1.0), Duration::from_millis(100));
+
+        for _ in 0..WAIT_TIME_SAMPLE_SIZE {
+            stats.record_wait(Duration::from_millis(5));
+        }
+        assert_eq!(stats.recent_wait_times.len(), WAIT_TIME_SAMPLE_SIZE);
+        assert_eq!(stats.wait_time_percentile(1.0), Duration::from_millis(5));
+    }
+
+    #[tokio::test]
+    async fn test_pool_acquire_deadline() {
+        let (pool, _listener) = local_pool(1).await;
+
+        let conn = pool.get_connection().await.unwrap();
+        let result = pool.get_connection_timeout(Duration::from_millis(20)).await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+
+        let stats = pool.stats().await;
+        assert_eq!(stats.connection_timeouts, 1);
+        assert_eq!(stats.waiting_requests, 0);
+        assert_eq!(stats.max_waiting_requests, 1);
+
+        drop(conn);
+        let conn = pool.get_connection_timeout(Duration::from_secs(5)).await;
+        assert!(conn.is_ok());
+    }
+
+    #[test]
+    fn test_pool_retry_backoff() {
+        let policy = PoolRetryPolicy::new(Duration::from_secs(1))
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(300));
+        for (attempt, ceiling) in [(0, 100), (1, 200), (2, 300), (40, 300)] {
+            let backoff = policy.backoff(attempt);
+            assert!(backoff <= Duration::from_millis(ceiling), "{:?}", backoff);
+            assert!(backoff >= Duration::from_millis(ceiling / 2), "{:?}", backoff);
+        }
+        assert!(policy.validate().is_ok());
+        assert!(PoolRetryPolicy::new(Duration::ZERO).validate().is_err());
+        assert!(policy.max_backoff(Duration::from_millis(1)).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pool_retry_exhausted() {
+        let (pool, _listener) = local_pool(1).await;
+        let metrics = Arc::new(MetricsRegistry::new("test".to_string()));
+        let pool = pool.with_metrics(metrics.clone());
+        let policy = PoolRetryPolicy::new(Duration::from_millis(50)).initial_backoff(Duration::from_millis(5));
+
+        let conn = pool.get_connection_with_retry(&policy).await.unwrap();
+        let err = pool.get_connection_with_retry(&policy).await.unwrap_err();
+        let Error::PoolExhausted { waited, attempts, stats } = &err else {
+            panic!("Expected PoolExhausted, got {}", err);
+        };
+        assert!(*waited >= Duration::from_millis(50));
+        assert!(*attempts > 1);
+        assert_eq!(stats.active_connections, 1);
+        assert!(err.is_retryable());
+        assert!(err.to_string().contains("1 active"), "{}", err);
+
+        let pool_stats = pool.stats().await;
+        assert_eq!(pool_stats.exhausted_requests, 1);
+        assert_eq!(pool_stats.acquire_retries, *attempts - 1);
+        assert_eq!(pool_stats.waiting_requests, 0);
+        assert!(metrics.get_metric(metric_names::CONNECTION_POOL_EXHAUSTED).await.is_some());
+        assert!(metrics.get_metric(metric_names::CONNECTION_POOL_ACQUIRE_RETRIES).await.is_some());
+
+        let waiter = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.get_connection_with_retry(&PoolRetryPolicy::new(Duration::from_secs(5))).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(conn);
+        assert!(waiter.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pool_fifo_waiters() {
+        let (pool, _listener) = local_pool(1).await;
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let conn = pool.get_connection().await.unwrap();
+        let mut handles = Vec::new();
+        for i in 0..3 {
+            let waiter_pool = pool.clone();
+            let order = order.clone();
+            handles.push(tokio::spawn(async move {
+                let _conn = waiter_pool.get_connection().await.unwrap();
+                order.lock().await.push(i);
+            }));
+            // Make sure waiter i is queued before waiter i + 1
+            while pool.waiting_requests().await < i + 1 {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        drop(conn);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(*order.lock().await, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_pool_discards_abandoned_query() {
+        use tokio::io::AsyncReadExt;
+
+        let (pool, listener) = local_pool(1).await;
+
+        let mut conn = pool.get_connection().await.unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        let query_id = conn.start_query().unwrap().to_string();
+        drop(conn);
+
+        // The server sees a ClientCancel packet for the abandoned query
+        let mut packet = Vec::new();
+        server.read_to_end(&mut packet).await.unwrap();
+        assert_eq!(u64::from_le_bytes(packet[0..8].try_into().unwrap()), 3);
+        assert!(packet.ends_with(query_id.as_bytes()));
+
+        // Stats are updated once the returned connection has been dropped
+        while pool.stats().await.discarded(DiscardReason::QueryAbandoned) == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pool.stats().await.idle_connections, 0);
+        assert_eq!(pool.available_connections().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_pool_does_not_hand_session_to_other_callers() {
+        let (pool, _listener) = local_pool(1).await;
+
+        let mut conn = pool.get_connection().await.unwrap();
+        let replaced = std::mem::replace(&mut *conn, Connection::new(ClientOptions::new()));
+        *conn = replaced.with_session({
+            let mut session = crate::client::SessionState::new();
+            session.track("SET ROLE admin");
+            session
+        });
+        conn.start_query().unwrap();
+        let _ = conn.finish_request::<()>(Err(Error::Protocol("unexpected packet".to_string())));
+        drop(conn);
+        while pool.stats().await.discarded(DiscardReason::Broken) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // The replacement starts clean rather than inheriting the role
+        let conn = pool.get_connection().await.unwrap();
+        assert!(conn.session().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pool_discards_broken_connection() {
+        let (pool, _listener) = local_pool(1).await;
+
+        let mut conn = pool.get_connection().await.unwrap();
+        let first_id = conn.id().to_string();
+        // Native queries are not implemented, so fail one the way a dropped socket would
+        conn.start_query().unwrap();
+        let result: Result<()> = conn.finish_request(Err(Error::Protocol("unexpected packet".to_string())));
+        assert!(result.is_err());
+        assert!(conn.is_broken());
+        drop(conn);
+
+        while pool.stats().await.discarded(DiscardReason::Broken) == 0 {
+            tokio::task::yield_now().await;
+        }
+        let conn = pool.get_connection().await.unwrap();
+        assert_ne!(conn.id(), first_id);
+        assert!(conn.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_pool_lazy_and_fail_fast_startup() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ClientOptions::new().host("127.0.0.1").port(port).min_connections(2);
+
+        let lazy = ConnectionPool::new(options.clone().lazy_connect(true)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(lazy.stats().await.total_connections, 0);
+
+        let pool = ConnectionPool::new(options.clone().fail_fast_on_startup(true)).unwrap();
+        pool.initialize_pool(true).await.unwrap();
+        pool.initialize_pool(true).await.unwrap();
+        assert_eq!(pool.stats().await.total_connections, 2);
+        assert_eq!(pool.available_connections().await, 2);
+
+        drop(listener);
+        let closed = options.fail_fast_on_startup(true);
+        assert!(crate::Client::connect(closed.clone()).await.is_err());
+        assert!(crate::Client::connect(closed.fail_fast_on_startup(false).lazy_connect(true)).await.is_ok());
+        assert!(ClientOptions::new().lazy_connect(true).fail_fast_on_startup(true).validate().is_err());
+    }
+
     #[tokio::test]
     #[ignore = "This test requires a running ClickHouse server at localhost:9000 and can hang if server is unavailable"]
     async fn test_pool_creation() {
@@ -0,0 +1,199 @@
+//! Text tables for query results
+//!
+//! [`QueryResult::format_table`] renders rows the way `clickhouse-client`
+//! does with the `PrettyCompact` format, for debugging and command-line
+//! tools.
+
+use crate::client::QueryResult;
+use crate::types::Value;
+
+/// How NULL values are shown, as in `clickhouse-client`
+const NULL_MARKER: &str = "ᴺᵁᴸᴸ";
+
+/// Marker appended to values cut at the maximum width
+const TRUNCATED_MARKER: char = '⋯';
+
+struct Cell {
+    text: String,
+    numeric: bool,
+}
+
+impl Cell {
+    fn new(value: Option<&Value>, max_width: usize) -> Self {
+        let value = match value {
+            Some(Value::Nullable(Some(inner))) => Some(&**inner),
+            other => other,
+        };
+        let (text, numeric) = match value {
+            None | Some(Value::Null) | Some(Value::Nullable(None)) => (NULL_MARKER.to_string(), false),
+            Some(value) => (escape(&value.to_string()), is_numeric(value)),
+        };
+        Self {
+            text: truncate(text, max_width),
+            numeric,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+fn is_numeric(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::UInt8(_)
+            | Value::UInt16(_)
+            | Value::UInt32(_)
+            | Value::UInt64(_)
+            | Value::UInt128(_)
+            | Value::UInt256(_)
+            | Value::Int8(_)
+            | Value::Int16(_)
+            | Value::Int32(_)
+            | Value::Int64(_)
+            | Value::Int128(_)
+            | Value::Int256(_)
+            | Value::Float32(_)
+            | Value::Float64(_)
+            | Value::Decimal32(_)
+            | Value::Decimal64(_)
+            | Value::Decimal128(_)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t").replace('\r', "\\r")
+}
+
+fn truncate(text: String, max_width: usize) -> String {
+    if max_width == 0 || text.chars().count() <= max_width {
+        return text;
+    }
+    let mut cut: String = text.chars().take(max_width.saturating_sub(1)).collect();
+    cut.push(TRUNCATED_MARKER);
+    cut
+}
+
+impl QueryResult {
+    /// Render the result as a `PrettyCompact` table
+    ///
+    /// At most `max_rows` rows are shown, followed by a note with the total.
+    /// Values longer than `max_width` characters are cut and end with `⋯`;
+    /// 0 disables either limit.
+    pub fn format_table(&self, max_rows: usize, max_width: usize) -> String {
+        let names: Vec<String> = if self.metadata.column_names.is_empty() {
+            self.first_block()
+                .map(|block| block.columns().map(|column| column.name.clone()).collect())
+                .unwrap_or_default()
+        } else {
+            self.metadata.column_names.clone()
+        };
+        if names.is_empty() {
+            return String::new();
+        }
+
+        let total = self.row_count();
+        let shown = if max_rows == 0 { total } else { total.min(max_rows) };
+        let rows: Vec<Vec<Cell>> = self
+            .rows()
+            .take(shown)
+            .map(|row| (0..names.len()).map(|i| Cell::new(row.get(i).and_then(Option::as_ref), max_width)).collect())
+            .collect();
+        let headers: Vec<String> = names.iter().map(|name| truncate(name.clone(), max_width)).collect();
+
+        let widths: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| {
+                rows.iter().map(|row| row[i].width()).chain([header.chars().count()]).max().unwrap_or(0)
+            })
+            .collect();
+
+        let mut out = String::new();
+        out.push('┌');
+        for (i, header) in headers.iter().enumerate() {
+            if i > 0 {
+                out.push('┬');
+            }
+            out.push('─');
+            out.push_str(header);
+            out.push_str(&"─".repeat(widths[i] - header.chars().count() + 1));
+        }
+        out.push_str("┐\n");
+
+        for row in &rows {
+            out.push('│');
+            for (i, cell) in row.iter().enumerate() {
+                if i > 0 {
+                    out.push('│');
+                }
+                let padding = " ".repeat(widths[i] - cell.width());
+                out.push(' ');
+                if cell.numeric {
+                    out.push_str(&padding);
+                    out.push_str(&cell.text);
+                } else {
+                    out.push_str(&cell.text);
+                    out.push_str(&padding);
+                }
+                out.push(' ');
+            }
+            out.push_str("│\n");
+        }
+
+        out.push('└');
+        for (i, width) in widths.iter().enumerate() {
+            if i > 0 {
+                out.push('┴');
+            }
+            out.push_str(&"─".repeat(width + 2));
+        }
+        out.push_str("┘\n");
+
+        if shown < total {
+            out.push_str(&format!("Showed first {} of {} rows.\n", shown, total));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{QueryMetadata, QueryResult, QueryStats};
+    use crate::types::{Block, Column, ColumnData, Value};
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_table() {
+        let mut block = Block::new();
+        block.add_column("id", Column::new("id", "UInt64", ColumnData::UInt64(vec![1, 20, 300])));
+        block.add_column(
+            "name",
+            Column::new(
+                "name",
+                "Nullable(String)",
+                ColumnData::Nullable(vec![
+                    Some(Value::String("alice".to_string())),
+                    None,
+                    Some(Value::String("a very\tlong name".to_string())),
+                ]),
+            ),
+        );
+        let result = QueryResult::new(
+            QueryMetadata::new(vec!["id".into(), "name".into()], vec!["UInt64".into(), "Nullable(String)".into()]),
+            vec![block],
+            QueryStats::new(0, 0, Duration::ZERO),
+        );
+
+        assert_eq!(
+            result.format_table(0, 8),
+            "┌─id──┬─name─────┐\n\
+             │   1 │ alice    │\n\
+             │  20 │ ᴺᵁᴸᴸ     │\n\
+             │ 300 │ a very\\⋯ │\n\
+             └─────┴──────────┘\n"
+        );
+        assert!(result.format_table(2, 0).ends_with("└────┴───────┘\nShowed first 2 of 3 rows.\n"));
+    }
+}
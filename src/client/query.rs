@@ -1,8 +1,16 @@
 //! Query execution and results for ClickHouse
 
+use crate::client::block_size::auto_block_size;
+use crate::client::in_list::InListStrategy;
+use crate::client::limit_guard::{inject_limit, DEFAULT_INTERACTIVE_LIMIT};
+use crate::client::options::CompressionMethod;
+use crate::protocol::{DecodeMode, LogLevel, ServerPartUUIDs, ServerProfileInfo, ServerProgress, ValidationMode};
 use crate::error::{Error, Result};
-use crate::types::{Block, Value};
-use std::collections::HashMap;
+use crate::types::{column_timezone, parse_timezone, Block, ColumnLookup, DateTime, DateTime64, TypeDescriptor, Value};
+use chrono_tz::Tz;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
 use std::time::Duration;
 
 /// Query settings for ClickHouse
@@ -14,6 +22,12 @@ pub struct QuerySettings {
     pub max_memory_usage: Option<u64>,
     /// Maximum block size
     pub max_block_size: Option<u64>,
+    /// Bytes per result block that the block size is picked for, unless `max_block_size` is set (client side only)
+    pub block_memory_target: Option<usize>,
+    /// Maximum number of query processing threads
+    pub max_threads: Option<u64>,
+    /// Query priority (lower value runs first, 0 disables priorities)
+    pub priority: Option<u64>,
     /// Whether to use async insert
     pub async_insert: Option<bool>,
     /// Whether to wait for async insert
@@ -22,6 +36,40 @@ pub struct QuerySettings {
     pub async_insert_busy_timeout_ms: Option<u64>,
     /// Async insert max data size
     pub async_insert_max_data_size: Option<u64>,
+    /// Number of replicas to read from in parallel
+    pub parallel_replicas: Option<u64>,
+    /// Cluster used for parallel replicas reading
+    pub parallel_replicas_cluster: Option<String>,
+    /// Array length above which IN-list parameters are rewritten (client side only)
+    pub in_list_threshold: Option<usize>,
+    /// How IN-list parameters above the threshold are sent (client side only)
+    pub in_list_strategy: Option<InListStrategy>,
+    /// How result columns of undecodable types are handled (client side only)
+    pub decode_mode: Option<DecodeMode>,
+    /// How invalid values in result columns are handled
+    pub validation: Option<ValidationMode>,
+    /// Maximum number of result rows received before the query is aborted (client side only)
+    pub max_result_rows: Option<u64>,
+    /// Maximum number of result bytes received before the query is aborted (client side only)
+    pub max_result_bytes: Option<u64>,
+    /// Server memory use in bytes above which the query is aborted (client side only)
+    pub max_query_memory: Option<u64>,
+    /// Lowest severity of server log messages sent to the client
+    pub send_logs_level: Option<LogLevel>,
+    /// How long results are kept in the server query cache, if it is used
+    pub query_cache_ttl: Option<Duration>,
+    /// Whether the filesystem cache of remote disks is used
+    pub enable_filesystem_cache: Option<bool>,
+    /// Whether the local replica is preferred for distributed queries
+    pub prefer_localhost_replica: Option<bool>,
+    /// Compression of the query's data blocks, overriding the client's (client side only)
+    pub compression: Option<CompressionMethod>,
+    /// Whether SELECTs without a LIMIT get one appended (client side only)
+    pub interactive_safe: Option<bool>,
+    /// Rows a SELECT without a LIMIT is limited to under `interactive_safe` (client side only)
+    pub interactive_limit: Option<u64>,
+    /// Tags sent as a JSON object in `log_comment`
+    pub tags: BTreeMap<String, String>,
     /// Custom settings
     pub custom: HashMap<String, String>,
 }
@@ -33,10 +81,30 @@ impl QuerySettings {
             timeout: None,
             max_memory_usage: None,
             max_block_size: None,
+            block_memory_target: None,
+            max_threads: None,
+            priority: None,
             async_insert: None,
             wait_for_async_insert: None,
             async_insert_busy_timeout_ms: None,
             async_insert_max_data_size: None,
+            parallel_replicas: None,
+            parallel_replicas_cluster: None,
+            in_list_threshold: None,
+            in_list_strategy: None,
+            decode_mode: None,
+            validation: None,
+            max_result_rows: None,
+            max_result_bytes: None,
+            max_query_memory: None,
+            send_logs_level: None,
+            query_cache_ttl: None,
+            enable_filesystem_cache: None,
+            prefer_localhost_replica: None,
+            compression: None,
+            interactive_safe: None,
+            interactive_limit: None,
+            tags: BTreeMap::new(),
             custom: HashMap::new(),
         }
     }
@@ -59,6 +127,36 @@ impl QuerySettings {
         self
     }
 
+    /// Pick the block size that keeps result blocks near `target_bytes`
+    ///
+    /// The rows per block follow from the width of the result rows; see
+    /// [`auto_block_size`](crate::client::auto_block_size). An explicit
+    /// [`max_block_size`](Self::max_block_size) takes precedence.
+    pub fn auto_block_size(mut self, target_bytes: usize) -> Self {
+        self.block_memory_target = Some(target_bytes);
+        self
+    }
+
+    /// Get the rows per block for rows of `row_width` bytes, if limited
+    pub fn block_size_for(&self, row_width: Option<usize>) -> Option<u64> {
+        self.max_block_size.or_else(|| {
+            let target = self.block_memory_target?;
+            Some(auto_block_size(row_width?, target))
+        })
+    }
+
+    /// Set maximum number of query processing threads
+    pub fn max_threads(mut self, max_threads: u64) -> Self {
+        self.max_threads = Some(max_threads);
+        self
+    }
+
+    /// Set query priority
+    pub fn priority(mut self, priority: u64) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
     /// Enable async insert
     pub fn async_insert(mut self, enabled: bool) -> Self {
         self.async_insert = Some(enabled);
@@ -83,6 +181,165 @@ impl QuerySettings {
         self
     }
 
+    /// Read from up to `replicas` replicas in parallel, coordinated by the server
+    ///
+    /// Values of 0 or 1 disable parallel reading.
+    pub fn parallel_replicas(mut self, replicas: u64) -> Self {
+        self.parallel_replicas = Some(replicas);
+        self
+    }
+
+    /// Set the cluster whose replicas are used for parallel reading
+    pub fn parallel_replicas_cluster(mut self, cluster: impl Into<String>) -> Self {
+        self.parallel_replicas_cluster = Some(cluster.into());
+        self
+    }
+
+    /// Set the array length above which IN-list parameters are rewritten
+    pub fn in_list_threshold(mut self, threshold: usize) -> Self {
+        self.in_list_threshold = Some(threshold);
+        self
+    }
+
+    /// Set how IN-list parameters above the threshold are sent
+    pub fn in_list_strategy(mut self, strategy: InListStrategy) -> Self {
+        self.in_list_strategy = Some(strategy);
+        self
+    }
+
+    /// Set how result columns of undecodable types are handled
+    pub fn decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.decode_mode = Some(mode);
+        self
+    }
+
+    /// Drop result columns of undecodable types instead of failing
+    pub fn skip_unknown_columns(self) -> Self {
+        self.decode_mode(DecodeMode::SkipUnknownColumns)
+    }
+
+    /// Set how invalid values in result columns are handled
+    ///
+    /// Under [`ValidationMode::Lenient`] invalid values are replaced and
+    /// reported by [`QueryResult::warnings`].
+    pub fn validation(mut self, mode: ValidationMode) -> Self {
+        self.validation = Some(mode);
+        self
+    }
+
+    /// Abort the query once more than `rows` result rows are received
+    ///
+    /// The limit is enforced while decoding, so an oversized result fails
+    /// with [`Error::ResultTooLarge`](crate::error::Error::ResultTooLarge)
+    /// and the query is cancelled on the server instead of being buffered.
+    pub fn max_result_rows(mut self, rows: u64) -> Self {
+        self.max_result_rows = Some(rows);
+        self
+    }
+
+    /// Abort the query once more than `bytes` of result data are received
+    pub fn max_result_bytes(mut self, bytes: u64) -> Self {
+        self.max_result_bytes = Some(bytes);
+        self
+    }
+
+    /// Abort the query once the server reports more than `bytes` of memory use
+    ///
+    /// Memory use is read from progress packets, so the query is cancelled
+    /// with [`Error::MemoryLimitExceeded`](crate::error::Error::MemoryLimitExceeded)
+    /// before it reaches the server's own limit.
+    pub fn max_query_memory(mut self, bytes: u64) -> Self {
+        self.max_query_memory = Some(bytes);
+        self
+    }
+
+    /// Receive server log messages of at least this severity
+    ///
+    /// The messages are emitted as `tracing` events under the
+    /// `clickhouse::server` target.
+    pub fn send_logs_level(mut self, level: LogLevel) -> Self {
+        self.send_logs_level = Some(level);
+        self
+    }
+
+    /// Serve the result from the server query cache, caching it for `ttl`
+    ///
+    /// Whether the cache was hit is reported by [`QueryResult::query_cache_hit`].
+    pub fn use_query_cache(mut self, ttl: Duration) -> Self {
+        self.query_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Set whether the filesystem cache of remote disks is used
+    pub fn enable_filesystem_cache(mut self, enabled: bool) -> Self {
+        self.enable_filesystem_cache = Some(enabled);
+        self
+    }
+
+    /// Set whether the local replica is preferred for distributed queries
+    pub fn prefer_localhost_replica(mut self, prefer: bool) -> Self {
+        self.prefer_localhost_replica = Some(prefer);
+        self
+    }
+
+    /// Compress the data blocks of this query or insert with `method`
+    ///
+    /// This overrides the client's compression, in either direction: large
+    /// analytical selects benefit from ZSTD while small point lookups are
+    /// faster uncompressed. [`CompressionMethod::None`] disables it.
+    pub fn compression(mut self, method: CompressionMethod) -> Self {
+        self.compression = Some(method);
+        self
+    }
+
+    /// Send and receive the data blocks of this query uncompressed
+    pub fn disable_compression(self) -> Self {
+        self.compression(CompressionMethod::None)
+    }
+
+    /// Append a LIMIT to SELECTs that have none
+    ///
+    /// Protects ad-hoc tooling from runaway result sets. The limit defaults to
+    /// [`DEFAULT_INTERACTIVE_LIMIT`](crate::client::DEFAULT_INTERACTIVE_LIMIT)
+    /// rows and is set with [`interactive_limit`](Self::interactive_limit).
+    pub fn interactive_safe(mut self, enabled: bool) -> Self {
+        self.interactive_safe = Some(enabled);
+        self
+    }
+
+    /// Set the LIMIT appended under [`interactive_safe`](Self::interactive_safe)
+    pub fn interactive_limit(mut self, rows: u64) -> Self {
+        self.interactive_limit = Some(rows);
+        self
+    }
+
+    /// Apply the interactive LIMIT guard to a query, if enabled
+    pub fn guard_sql<'a>(&self, sql: &'a str) -> Cow<'a, str> {
+        if self.interactive_safe != Some(true) {
+            return Cow::Borrowed(sql);
+        }
+        let limit = self.interactive_limit.unwrap_or(DEFAULT_INTERACTIVE_LIMIT);
+        inject_limit(sql, limit).map_or(Cow::Borrowed(sql), Cow::Owned)
+    }
+
+    /// Tag the query, e.g. with the feature or team it runs for
+    ///
+    /// Tags are collected into a JSON object sent as the `log_comment`
+    /// setting, so they show up in `system.query_log` and can be filtered with
+    /// [`SystemTables::query_log`](crate::client::SystemTables::query_log).
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Get the `log_comment` carrying the tags, if any are set
+    pub fn log_comment(&self) -> Option<String> {
+        if self.tags.is_empty() {
+            return None;
+        }
+        serde_json::to_string(&self.tags).ok()
+    }
+
     /// Add a custom setting
     pub fn custom_setting(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.custom.insert(key.into(), value.into());
@@ -105,6 +362,14 @@ impl QuerySettings {
             settings.push(format!("max_block_size={}", max_block_size));
         }
 
+        if let Some(max_threads) = self.max_threads {
+            settings.push(format!("max_threads={}", max_threads));
+        }
+
+        if let Some(priority) = self.priority {
+            settings.push(format!("priority={}", priority));
+        }
+
         if let Some(async_insert) = self.async_insert {
             settings.push(format!("async_insert={}", if async_insert { 1 } else { 0 }));
         }
@@ -124,6 +389,43 @@ impl QuerySettings {
             settings.push(format!("async_insert_max_data_size={}", max_size));
         }
 
+        if let Some(replicas) = self.parallel_replicas {
+            if replicas > 1 {
+                settings.push("allow_experimental_parallel_reading_from_replicas=1".to_string());
+                settings.push(format!("max_parallel_replicas={}", replicas));
+                // Hedged requests cannot be combined with parallel replicas
+                settings.push("use_hedged_requests=0".to_string());
+            } else {
+                settings.push("allow_experimental_parallel_reading_from_replicas=0".to_string());
+            }
+        }
+
+        if let Some(cluster) = &self.parallel_replicas_cluster {
+            settings.push(format!("cluster_for_parallel_replicas='{}'", cluster.replace('\'', "\\'")));
+        }
+
+        if let Some(level) = self.send_logs_level {
+            settings.push(format!("send_logs_level='{}'", level.setting_value()));
+        }
+
+        if let Some(ttl) = self.query_cache_ttl {
+            settings.push("use_query_cache=1".to_string());
+            // The server only accepts whole seconds
+            settings.push(format!("query_cache_ttl={}", ttl.as_secs().max(1)));
+        }
+
+        if let Some(enabled) = self.enable_filesystem_cache {
+            settings.push(format!("enable_filesystem_cache={}", if enabled { 1 } else { 0 }));
+        }
+
+        if let Some(prefer) = self.prefer_localhost_replica {
+            settings.push(format!("prefer_localhost_replica={}", if prefer { 1 } else { 0 }));
+        }
+
+        if let Some(comment) = self.log_comment() {
+            settings.push(format!("log_comment='{}'", comment.replace('\\', "\\\\").replace('\'', "\\'")));
+        }
+
         // Add custom settings
         for (key, value) in &self.custom {
             settings.push(format!("{}={}", key, value));
@@ -133,6 +435,100 @@ impl QuerySettings {
     }
 }
 
+/// Names of the built-in settings profiles
+pub const BUILTIN_PROFILES: [&str; 3] = ["olap_heavy", "low_latency", "etl"];
+
+impl QuerySettings {
+    /// Profile for long-running analytical queries over large data
+    ///
+    /// Uses many threads and a large memory budget, spills GROUP BY and ORDER BY
+    /// to disk before hitting the limit, and runs at reduced priority.
+    pub fn profile_olap_heavy() -> Self {
+        Self::new()
+            .timeout(Duration::from_secs(3600))
+            .max_threads(16)
+            .max_memory_usage(32 * GIB)
+            .priority(10)
+            .custom_setting("max_bytes_before_external_group_by", (16 * GIB).to_string())
+            .custom_setting("max_bytes_before_external_sort", (16 * GIB).to_string())
+    }
+
+    /// Profile for short interactive queries
+    ///
+    /// Caps execution time and resources, and runs ahead of background work.
+    pub fn profile_low_latency() -> Self {
+        Self::new()
+            .timeout(Duration::from_secs(5))
+            .max_threads(4)
+            .max_memory_usage(2 * GIB)
+            .priority(1)
+            .custom_setting("max_execution_time", "5")
+            .custom_setting("use_uncompressed_cache", "1")
+    }
+
+    /// Profile for bulk loading
+    ///
+    /// Batches small inserts on the server with async inserts and waits for
+    /// them to be flushed so failures are reported.
+    pub fn profile_etl() -> Self {
+        Self::new()
+            .timeout(Duration::from_secs(1800))
+            .max_threads(8)
+            .max_memory_usage(8 * GIB)
+            .priority(5)
+            .async_insert(true)
+            .wait_for_async_insert(true)
+            .custom_setting("max_insert_block_size", "1048576")
+    }
+
+    /// Get a built-in profile by name
+    pub fn profile(name: &str) -> Option<Self> {
+        match name {
+            "olap_heavy" => Some(Self::profile_olap_heavy()),
+            "low_latency" => Some(Self::profile_low_latency()),
+            "etl" => Some(Self::profile_etl()),
+            _ => None,
+        }
+    }
+
+    /// Overlay the settings set in `other` on top of these
+    pub fn merge(mut self, other: &QuerySettings) -> Self {
+        self.timeout = other.timeout.or(self.timeout);
+        self.max_memory_usage = other.max_memory_usage.or(self.max_memory_usage);
+        self.max_block_size = other.max_block_size.or(self.max_block_size);
+        self.block_memory_target = other.block_memory_target.or(self.block_memory_target);
+        self.max_threads = other.max_threads.or(self.max_threads);
+        self.priority = other.priority.or(self.priority);
+        self.async_insert = other.async_insert.or(self.async_insert);
+        self.wait_for_async_insert = other.wait_for_async_insert.or(self.wait_for_async_insert);
+        self.async_insert_busy_timeout_ms = other.async_insert_busy_timeout_ms.or(self.async_insert_busy_timeout_ms);
+        self.async_insert_max_data_size = other.async_insert_max_data_size.or(self.async_insert_max_data_size);
+        self.parallel_replicas = other.parallel_replicas.or(self.parallel_replicas);
+        if other.parallel_replicas_cluster.is_some() {
+            self.parallel_replicas_cluster = other.parallel_replicas_cluster.clone();
+        }
+        self.in_list_threshold = other.in_list_threshold.or(self.in_list_threshold);
+        self.in_list_strategy = other.in_list_strategy.or(self.in_list_strategy);
+        self.decode_mode = other.decode_mode.or(self.decode_mode);
+        self.validation = other.validation.or(self.validation);
+        self.max_result_rows = other.max_result_rows.or(self.max_result_rows);
+        self.max_result_bytes = other.max_result_bytes.or(self.max_result_bytes);
+        self.max_query_memory = other.max_query_memory.or(self.max_query_memory);
+        self.send_logs_level = other.send_logs_level.or(self.send_logs_level);
+        self.query_cache_ttl = other.query_cache_ttl.or(self.query_cache_ttl);
+        self.enable_filesystem_cache = other.enable_filesystem_cache.or(self.enable_filesystem_cache);
+        self.prefer_localhost_replica = other.prefer_localhost_replica.or(self.prefer_localhost_replica);
+        self.compression = other.compression.or(self.compression);
+        self.interactive_safe = other.interactive_safe.or(self.interactive_safe);
+        self.interactive_limit = other.interactive_limit.or(self.interactive_limit);
+        self.tags.extend(other.tags.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.custom.extend(other.custom.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self
+    }
+}
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
 impl Default for QuerySettings {
     fn default() -> Self {
         Self::new()
@@ -146,17 +542,217 @@ pub struct QueryResult {
     pub metadata: QueryMetadata,
     /// Data blocks
     pub blocks: Vec<Block>,
+    /// Overflow blocks from aggregation `WITH TOTALS`, kept out of the data
+    pub overflows: Vec<Block>,
     /// Statistics
     pub stats: QueryStats,
+    /// Timezone of the server session that produced the result
+    pub server_timezone: Option<Tz>,
+    /// Values replaced while decoding under lenient validation
+    pub warnings: Vec<String>,
+    /// UUIDs of the data parts the query read, for deduplication across replicas
+    pub part_uuids: Vec<uuid::Uuid>,
+    /// How columns are matched when looked up by name
+    column_lookup: ColumnLookup,
+    /// Column positions already looked up by name
+    column_indexes: Mutex<HashMap<String, Option<usize>>>,
 }
 
 impl QueryResult {
     /// Create a new query result
+    ///
+    /// Overflow blocks are moved out of `blocks` into `overflows`.
     pub fn new(metadata: QueryMetadata, blocks: Vec<Block>, stats: QueryStats) -> Self {
+        let (overflows, blocks) = blocks.into_iter().partition(|block| block.is_overflows());
         Self {
             metadata,
             blocks,
+            overflows,
             stats,
+            server_timezone: None,
+            warnings: Vec::new(),
+            part_uuids: Vec::new(),
+            column_lookup: ColumnLookup::default(),
+            column_indexes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record the part UUIDs sent by the server
+    pub fn apply_part_uuids(&mut self, packet: &ServerPartUUIDs) {
+        self.part_uuids.extend_from_slice(packet.uuids());
+    }
+
+    /// Set how columns are matched when looked up by name
+    pub fn with_column_lookup(mut self, lookup: ColumnLookup) -> Self {
+        self.column_lookup = lookup;
+        self.column_indexes = Mutex::new(HashMap::new());
+        self
+    }
+
+    /// Get how columns are matched when looked up by name
+    pub fn column_lookup(&self) -> &ColumnLookup {
+        &self.column_lookup
+    }
+
+    /// Get the position of a column by name
+    ///
+    /// Names are matched using the result's [`ColumnLookup`], and positions
+    /// are cached so that mapping every row by name stays cheap.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        let mut cache = self.column_indexes.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = cache.get(name) {
+            return *index;
+        }
+        let index = match self.blocks.first() {
+            Some(block) => block.column_position(name, &self.column_lookup),
+            None => self
+                .column_lookup
+                .position(name, self.metadata.column_names.iter().map(String::as_str)),
+        };
+        cache.insert(name.to_string(), index);
+        index
+    }
+
+    /// Attach decode warnings, such as those of a [`BlockDecoder`]
+    ///
+    /// [`BlockDecoder`]: crate::protocol::BlockDecoder
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings.extend(warnings);
+        self
+    }
+
+    /// Get the warnings about values replaced while decoding
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Get the overflow blocks
+    ///
+    /// These hold the aggregate of all keys past `max_rows_to_group_by` when
+    /// `group_by_overflow_mode = 'any'` is used together with `WITH TOTALS`.
+    pub fn overflows(&self) -> &[Block] {
+        &self.overflows
+    }
+
+    /// Check if the server sent overflow blocks
+    pub fn has_overflows(&self) -> bool {
+        !self.overflows.is_empty()
+    }
+
+    /// Group data blocks by two-level aggregation bucket
+    ///
+    /// Blocks that do not belong to a bucket are not included.
+    pub fn buckets(&self) -> BTreeMap<i32, Vec<&Block>> {
+        let mut buckets: BTreeMap<i32, Vec<&Block>> = BTreeMap::new();
+        for block in &self.blocks {
+            if let Some(bucket) = block.info.bucket() {
+                buckets.entry(bucket).or_default().push(block);
+            }
+        }
+        buckets
+    }
+
+    /// Get structured metadata for the result columns
+    ///
+    /// DateTime columns without an explicit timezone report the server timezone.
+    pub fn schema(&self) -> Result<Vec<ColumnSchema>> {
+        let columns: Vec<(&str, &str)> = if self.metadata.column_names.is_empty() {
+            self.first_block()
+                .map(|block| block.columns.iter().map(|c| (c.name.as_str(), c.type_name.as_str())).collect())
+                .unwrap_or_default()
+        } else {
+            self.metadata
+                .column_names
+                .iter()
+                .zip(&self.metadata.column_types)
+                .map(|(name, type_name)| (name.as_str(), type_name.as_str()))
+                .collect()
+        };
+
+        columns
+            .into_iter()
+            .map(|(name, type_name)| {
+                let descriptor = TypeDescriptor::parse(type_name)?;
+                let timezone = match descriptor.timezone() {
+                    Some(tz) => Some(tz.to_string()),
+                    None if descriptor.is_datetime() => self.server_timezone.map(|tz| tz.name().to_string()),
+                    None => None,
+                };
+                Ok(ColumnSchema {
+                    name: name.to_string(),
+                    type_name: type_name.to_string(),
+                    nullable: descriptor.is_nullable(),
+                    decimal: descriptor.decimal(),
+                    timezone,
+                    descriptor,
+                })
+            })
+            .collect()
+    }
+
+    /// Reorder data blocks by bucket number
+    ///
+    /// Buckets hold disjoint keys, so the merged result is the blocks in bucket
+    /// order. Blocks without a bucket come first, in their original order.
+    pub fn merge_buckets(&mut self) {
+        self.blocks.sort_by_key(|block| block.info.bucket());
+    }
+
+    /// Set the server session timezone
+    pub fn with_server_timezone(mut self, timezone: Tz) -> Self {
+        self.server_timezone = Some(timezone);
+        self
+    }
+
+    /// Get the server session timezone, if known
+    pub fn server_timezone(&self) -> Option<Tz> {
+        self.server_timezone
+    }
+
+    /// Check if the result was served from the server query cache
+    ///
+    /// `None` when the server did not report query cache profile events.
+    pub fn query_cache_hit(&self) -> Option<bool> {
+        self.stats.query_cache_hit
+    }
+
+    /// Get the timezone used for a column's DateTime values
+    ///
+    /// An explicit timezone in the column type wins over the server timezone.
+    pub fn column_timezone(&self, column: &str) -> Option<Tz> {
+        let explicit = self
+            .metadata
+            .get_column_index(column)
+            .and_then(|index| self.metadata.get_column_type(index))
+            .or_else(|| self.get_column(column).map(|c| c.type_name()))
+            .and_then(column_timezone);
+
+        match explicit {
+            Some(name) => parse_timezone(name).ok(),
+            None => self.server_timezone,
+        }
+    }
+
+    /// Get a DateTime value localized to the column or server timezone
+    ///
+    /// Falls back to UTC when neither timezone is known.
+    pub fn get_datetime(&self, row: usize, column: &str) -> Result<Option<chrono::DateTime<Tz>>> {
+        let tz = self.column_timezone(column).unwrap_or(Tz::UTC);
+        let index = self
+            .metadata
+            .get_column_index(column)
+            .or_else(|| self.first_block()?.columns.iter().position(|c| c.name == column))
+            .ok_or_else(|| Error::InvalidData(format!("Column not found: {}", column)))?;
+
+        match self.get_row(row).and_then(|r| r.get(index).cloned()).flatten() {
+            Some(Value::DateTime(dt)) => Ok(Some(DateTime(dt).in_timezone(tz))),
+            Some(Value::DateTime64(dt)) => Ok(Some(DateTime64(dt).in_timezone(tz))),
+            Some(Value::Null) | None => Ok(None),
+            Some(other) => Err(Error::TypeConversion(format!(
+                "Column {} is {}, not a DateTime",
+                column,
+                other.type_name()
+            ))),
         }
     }
 
@@ -198,7 +794,7 @@ impl QueryResult {
     /// Get a specific column by name
     pub fn get_column(&self, name: &str) -> Option<&crate::types::Column> {
         for block in &self.blocks {
-            if let Some(col) = block.get_column(name) {
+            if let Some(col) = block.find_column(name, &self.column_lookup) {
                 return Some(col);
             }
         }
@@ -228,6 +824,23 @@ impl QueryResult {
     }
 }
 
+/// Structured metadata of a result column
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSchema {
+    /// Column name
+    pub name: String,
+    /// Type name as sent by the server
+    pub type_name: String,
+    /// Parsed type
+    pub descriptor: TypeDescriptor,
+    /// Whether values can be NULL
+    pub nullable: bool,
+    /// Precision and scale of decimal columns
+    pub decimal: Option<(u8, u8)>,
+    /// Timezone of DateTime columns, if known
+    pub timezone: Option<String>,
+}
+
 /// Query metadata
 #[derive(Debug, Clone)]
 pub struct QueryMetadata {
@@ -277,7 +890,7 @@ impl QueryMetadata {
 }
 
 /// Query statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct QueryStats {
     /// Number of rows read
     pub rows_read: u64,
@@ -289,6 +902,8 @@ pub struct QueryStats {
     pub rows_written: Option<u64>,
     /// Bytes written
     pub bytes_written: Option<u64>,
+    /// Whether the result came from the server query cache
+    pub query_cache_hit: Option<bool>,
 }
 
 impl QueryStats {
@@ -300,6 +915,7 @@ impl QueryStats {
             elapsed,
             rows_written: None,
             bytes_written: None,
+            query_cache_hit: None,
         }
     }
 
@@ -315,6 +931,18 @@ impl QueryStats {
         self
     }
 
+    /// Set whether the result came from the server query cache
+    pub fn with_query_cache_hit(mut self, hit: bool) -> Self {
+        self.query_cache_hit = Some(hit);
+        self
+    }
+
+    /// Take the query cache outcome from the server profile events
+    pub fn with_profile_info(mut self, info: &ServerProfileInfo) -> Self {
+        self.query_cache_hit = info.query_cache_hit().or(self.query_cache_hit);
+        self
+    }
+
     /// Get the query performance in rows per second
     pub fn rows_per_second(&self) -> f64 {
         if self.elapsed.as_secs_f64() > 0.0 {
@@ -334,6 +962,64 @@ impl QueryStats {
     }
 }
 
+/// Outcome of an insert, as reported by the server
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InsertResult {
+    /// Number of rows written
+    pub rows_written: u64,
+    /// Number of uncompressed bytes written
+    pub bytes_written: u64,
+    /// Time from sending the query to the end of the stream
+    pub duration: Duration,
+    /// ID of the insert query
+    pub query_id: String,
+    /// UUIDs of the data parts the server reported for the insert
+    pub part_uuids: Vec<uuid::Uuid>,
+}
+
+impl InsertResult {
+    /// Create an empty result for a query
+    pub fn new(query_id: impl Into<String>) -> Self {
+        Self {
+            query_id: query_id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Add the written counts of a progress packet
+    ///
+    /// Progress packets carry increments since the previous packet.
+    pub fn add_progress(&mut self, progress: &ServerProgress) {
+        self.rows_written += progress.written_rows;
+        self.bytes_written += progress.written_bytes;
+    }
+
+    /// Take the written totals of the final profile packet
+    ///
+    /// Totals lower than the accumulated progress are ignored.
+    pub fn apply_profile_info(&mut self, info: &ServerProfileInfo) {
+        self.rows_written = self.rows_written.max(info.rows_written);
+        self.bytes_written = self.bytes_written.max(info.bytes_written);
+    }
+
+    /// Record the part UUIDs sent by the server
+    ///
+    /// Pipelines with their own deduplication can store them to recognize
+    /// parts that a retried insert would duplicate.
+    pub fn apply_part_uuids(&mut self, packet: &ServerPartUUIDs) {
+        self.part_uuids.extend_from_slice(packet.uuids());
+    }
+
+    /// Get the insert throughput in rows per second
+    pub fn rows_per_second(&self) -> f64 {
+        if self.duration.as_secs_f64() > 0.0 {
+            self.rows_written as f64 / self.duration.as_secs_f64()
+        } else {
+            0.0
+        }
+    }
+}
+
 /// Query builder for constructing complex queries
 #[derive(Clone)]
 pub struct Query {
@@ -403,6 +1089,60 @@ impl Query {
         self
     }
 
+    /// Read from up to `replicas` replicas in parallel
+    pub fn parallel_replicas(mut self, replicas: u64) -> Self {
+        self.settings = self.settings.parallel_replicas(replicas);
+        self
+    }
+
+    /// Set how result columns of undecodable types are handled
+    pub fn decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.settings = self.settings.decode_mode(mode);
+        self
+    }
+
+    /// Set how invalid values in result columns are handled
+    pub fn validation(mut self, mode: ValidationMode) -> Self {
+        self.settings = self.settings.validation(mode);
+        self
+    }
+
+    /// Abort the query once more than `rows` result rows are received
+    pub fn max_result_rows(mut self, rows: u64) -> Self {
+        self.settings = self.settings.max_result_rows(rows);
+        self
+    }
+
+    /// Abort the query once more than `bytes` of result data are received
+    pub fn max_result_bytes(mut self, bytes: u64) -> Self {
+        self.settings = self.settings.max_result_bytes(bytes);
+        self
+    }
+
+    /// Abort the query once the server reports more than `bytes` of memory use
+    pub fn max_query_memory(mut self, bytes: u64) -> Self {
+        self.settings = self.settings.max_query_memory(bytes);
+        self
+    }
+
+    /// Serve the result from the server query cache, caching it for `ttl`
+    pub fn use_query_cache(mut self, ttl: Duration) -> Self {
+        self.settings = self.settings.use_query_cache(ttl);
+        self
+    }
+
+    /// Append a LIMIT to the query if it is a SELECT without one
+    pub fn interactive_safe(mut self, enabled: bool) -> Self {
+        self.settings = self.settings.interactive_safe(enabled);
+        self
+    }
+
+    /// Tag the query for `system.query_log`
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings = self.settings.tag(key, value);
+        self
+    }
+
     /// Add a custom setting
     pub fn custom_setting(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.settings = self.settings.custom_setting(key, value);
@@ -478,6 +1218,7 @@ impl std::fmt::Debug for Query {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::ProfileEvent;
     use std::time::Duration;
 
     #[test]
@@ -495,6 +1236,153 @@ mod tests {
         assert!(settings_str.contains("async_insert=1"));
         assert!(settings_str.contains("wait_for_async_insert=1"));
         assert!(settings_str.contains("max_threads=4"));
+
+        let logs = QuerySettings::new().send_logs_level(LogLevel::Notice).build_settings_string();
+        assert_eq!(logs, "send_logs_level='information'");
+    }
+
+    #[test]
+    fn test_query_settings_cache_hints() {
+        let settings = QuerySettings::new()
+            .use_query_cache(Duration::from_secs(300))
+            .enable_filesystem_cache(false)
+            .prefer_localhost_replica(true);
+        assert_eq!(
+            settings.build_settings_string(),
+            "use_query_cache=1, query_cache_ttl=300, enable_filesystem_cache=0, prefer_localhost_replica=1"
+        );
+
+        let mut info = ServerProfileInfo::new(0, 0, 0, 0, 0);
+        let stats = QueryStats::new(0, 0, Duration::ZERO);
+        assert_eq!(stats.clone().with_profile_info(&info).query_cache_hit, None);
+        info.add_profile_event(ProfileEvent::new("QueryCacheMisses".to_string(), 1, 0));
+        assert_eq!(stats.clone().with_profile_info(&info).query_cache_hit, Some(false));
+        info.add_profile_event(ProfileEvent::new("QueryCacheHits".to_string(), 1, 0));
+        let result = QueryResult::new(QueryMetadata::new(Vec::new(), Vec::new()), Vec::new(), stats.with_profile_info(&info));
+        assert_eq!(result.query_cache_hit(), Some(true));
+    }
+
+    #[test]
+    fn test_query_settings_interactive_safe() {
+        let sql = "SELECT * FROM events";
+        assert_eq!(QuerySettings::new().guard_sql(sql), sql);
+        assert_eq!(QuerySettings::new().interactive_safe(true).guard_sql(sql), "SELECT * FROM events LIMIT 1000");
+        let settings = QuerySettings::new().interactive_safe(true).merge(&QuerySettings::new().interactive_limit(10));
+        assert_eq!(settings.guard_sql(sql), "SELECT * FROM events LIMIT 10");
+        assert!(settings.build_settings_string().is_empty());
+    }
+
+    #[test]
+    fn test_query_settings_tags() {
+        let settings = QuerySettings::new().tag("team", "growth").tag("feature", "it's");
+        assert_eq!(settings.log_comment().unwrap(), r#"{"feature":"it's","team":"growth"}"#);
+        assert_eq!(
+            settings.build_settings_string(),
+            r#"log_comment='{"feature":"it\'s","team":"growth"}'"#
+        );
+
+        let merged = settings.merge(&QuerySettings::new().tag("team", "search"));
+        assert_eq!(merged.tags["team"], "search");
+        assert_eq!(merged.tags["feature"], "it's");
+        assert_eq!(QuerySettings::new().log_comment(), None);
+    }
+
+    #[test]
+    fn test_query_settings_result_limits() {
+        let settings = QuerySettings::new().max_result_rows(1_000);
+        assert!(settings.build_settings_string().is_empty());
+
+        let merged = settings.merge(&QuerySettings::new().max_result_bytes(1 << 20));
+        assert_eq!(merged.max_result_rows, Some(1_000));
+        assert_eq!(merged.max_result_bytes, Some(1 << 20));
+
+        let merged = merged.merge(&QuerySettings::new().max_query_memory(1 << 30));
+        assert_eq!(merged.max_query_memory, Some(1 << 30));
+        assert!(merged.build_settings_string().is_empty());
+    }
+
+    #[test]
+    fn test_query_settings_block_size() {
+        let settings = QuerySettings::new().auto_block_size(1 << 20);
+        assert!(settings.build_settings_string().is_empty());
+        assert_eq!(settings.block_size_for(None), None);
+        assert_eq!(settings.block_size_for(Some(64)), Some(16_384));
+
+        let merged = settings.merge(&QuerySettings::new().max_block_size(500));
+        assert_eq!(merged.block_memory_target, Some(1 << 20));
+        assert_eq!(merged.block_size_for(Some(64)), Some(500));
+        assert_eq!(merged.build_settings_string(), "max_block_size=500");
+    }
+
+    #[test]
+    fn test_insert_result_from_packets() {
+        let mut result = InsertResult::new("q1");
+        let mut progress = ServerProgress::new();
+        progress.written_rows = 600;
+        progress.written_bytes = 4800;
+        result.add_progress(&progress);
+        result.add_progress(&progress);
+        assert_eq!((result.rows_written, result.bytes_written), (1200, 9600));
+
+        result.apply_profile_info(&ServerProfileInfo::new(0, 0, 0, 1000, 8000));
+        assert_eq!(result.rows_written, 1200);
+        result.apply_profile_info(&ServerProfileInfo::new(0, 0, 0, 1500, 12000));
+        assert_eq!((result.rows_written, result.bytes_written), (1500, 12000));
+
+        result.duration = Duration::from_millis(500);
+        assert_eq!(result.rows_per_second(), 3000.0);
+        assert_eq!(result.query_id, "q1");
+
+        let part = uuid::Uuid::from_u64_pair(7, 9);
+        result.apply_part_uuids(&ServerPartUUIDs::new(vec![part]));
+        result.apply_part_uuids(&ServerPartUUIDs::new(vec![]));
+        assert_eq!(result.part_uuids, [part]);
+    }
+
+    #[test]
+    fn test_query_settings_parallel_replicas() {
+        let settings = QuerySettings::new()
+            .parallel_replicas(3)
+            .parallel_replicas_cluster("replicated")
+            .build_settings_string();
+        assert!(settings.contains("allow_experimental_parallel_reading_from_replicas=1"));
+        assert!(settings.contains("max_parallel_replicas=3"));
+        assert!(settings.contains("use_hedged_requests=0"));
+        assert!(settings.contains("cluster_for_parallel_replicas='replicated'"));
+
+        let disabled = QuerySettings::new().parallel_replicas(1).build_settings_string();
+        assert_eq!(disabled, "allow_experimental_parallel_reading_from_replicas=0");
+    }
+
+    #[test]
+    fn test_query_settings_profiles() {
+        let olap = QuerySettings::profile_olap_heavy().build_settings_string();
+        assert!(olap.contains("max_threads=16"));
+        assert!(olap.contains("priority=10"));
+        assert!(olap.contains("max_bytes_before_external_group_by="));
+
+        let low_latency = QuerySettings::profile_low_latency();
+        assert_eq!(low_latency.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(low_latency.priority, Some(1));
+
+        let etl = QuerySettings::profile_etl();
+        assert_eq!(etl.async_insert, Some(true));
+        assert_eq!(etl.wait_for_async_insert, Some(true));
+
+        for name in BUILTIN_PROFILES {
+            assert!(QuerySettings::profile(name).is_some());
+        }
+        assert!(QuerySettings::profile("unknown").is_none());
+    }
+
+    #[test]
+    fn test_query_settings_merge() {
+        let overrides = QuerySettings::new().max_threads(2).custom_setting("max_execution_time", "1");
+        let merged = QuerySettings::profile_low_latency().merge(&overrides);
+
+        assert_eq!(merged.max_threads, Some(2));
+        assert_eq!(merged.priority, Some(1));
+        assert_eq!(merged.custom.get("max_execution_time").map(String::as_str), Some("1"));
     }
 
     #[test]
@@ -536,4 +1424,53 @@ mod tests {
         assert_eq!(stats.rows_written, Some(500));
         assert_eq!(stats.bytes_written, Some(512 * 1024));
     }
+
+    #[test]
+    fn test_query_result_overflows_and_buckets() {
+        use crate::types::{BlockInfo, Column, ColumnData};
+
+        let block = |values: Vec<u64>, is_overflows: bool, bucket_num: i32| {
+            Block::with_columns(vec![Column::new("n", "UInt64", ColumnData::UInt64(values))]).with_info(BlockInfo {
+                is_overflows,
+                bucket_num,
+                ..BlockInfo::default()
+            })
+        };
+        let mut result = QueryResult::new(
+            QueryMetadata::new(vec!["n".to_string()], vec!["UInt64".to_string()]),
+            vec![block(vec![3], false, 1), block(vec![99], true, -1), block(vec![1, 2], false, 0)],
+            QueryStats::new(0, 0, Duration::ZERO),
+        );
+
+        assert!(result.has_overflows());
+        assert_eq!(result.overflows()[0].row_count, 1);
+        assert_eq!(result.row_count(), 3);
+        assert_eq!(result.buckets().keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+
+        result.merge_buckets();
+        assert_eq!(result.first_row().unwrap().get(0), Some(&Some(Value::UInt64(1))));
+    }
+
+    #[test]
+    fn test_query_result_column_index() {
+        use crate::types::{Column, ColumnData};
+
+        let block = Block::with_columns(vec![
+            Column::new("Id", "UInt64", ColumnData::UInt64(vec![1])),
+            Column::new("B", "UInt64", ColumnData::UInt64(vec![2])),
+        ]);
+        let metadata = QueryMetadata::new(vec!["Id".to_string(), "B".to_string()], vec!["UInt64".to_string(); 2]);
+        let result = QueryResult::new(metadata.clone(), vec![block], QueryStats::new(0, 0, Duration::ZERO));
+        assert_eq!(result.column_index("B"), Some(1));
+        assert_eq!(result.column_index("id"), None);
+
+        let result = result.with_column_lookup(ColumnLookup::case_insensitive().alias("a", "B"));
+        assert_eq!(result.column_index("id"), Some(0));
+        assert_eq!(result.column_index("A"), Some(1));
+        assert_eq!(result.get_column("ID").unwrap().name, "Id");
+
+        let empty = QueryResult::new(metadata, vec![], QueryStats::new(0, 0, Duration::ZERO))
+            .with_column_lookup(ColumnLookup::case_insensitive());
+        assert_eq!(empty.column_index("b"), Some(1));
+    }
 }
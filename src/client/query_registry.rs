@@ -0,0 +1,374 @@
+//! Named query templates registered at startup
+//!
+//! ```rust
+//! # async fn example() -> clickhouse_rs::error::Result<()> {
+//! use clickhouse_rs::client::QueryRegistry;
+//! use clickhouse_rs::types::Value;
+//! use clickhouse_rs::{Client, ClientOptions};
+//! use std::collections::HashMap;
+//!
+//! let queries = QueryRegistry::new();
+//! queries.register("find_user", "SELECT id, name FROM users WHERE id = {id:UInt64}")?;
+//! queries.load_dir("sql")?;
+//!
+//! let options = ClientOptions::default().queries(queries).strict_queries(true);
+//! let client = Client::connect(options).await?;
+//! let params = HashMap::from([("id".to_string(), Value::UInt64(42))]);
+//! let user = client.run("find_user", params).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A file holds one template named after its stem, or several that each start
+//! with a `-- name: <name>` line. Templates are checked for their parameters
+//! when run. With strict queries, [`Client::connect`](crate::Client::connect)
+//! also has the server `EXPLAIN` every template, binding the default value of
+//! each declared type, so a typo or a dropped column fails the startup rather
+//! than the first call.
+
+use crate::client::{PreparedStatement, QuerySettings, StatementParameter};
+use crate::error::{Error, Result};
+use crate::types::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Extension of the files [`QueryRegistry::load_dir`] reads
+pub const QUERY_FILE_EXTENSION: &str = "sql";
+
+const NAME_MARKER: &str = "-- name:";
+
+/// Parameterized SQL registered under a name
+#[derive(Debug, Clone)]
+pub struct QueryTemplate {
+    /// Name the template is run by
+    pub name: String,
+    /// SQL as registered
+    pub sql: String,
+    /// Placeholders in order of first appearance
+    pub parameters: Vec<StatementParameter>,
+    /// Settings the template runs with, if any
+    pub settings: Option<QuerySettings>,
+}
+
+impl QueryTemplate {
+    /// Parse the placeholders of a template
+    pub fn new(name: impl Into<String>, sql: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        let sql = sql.into();
+        if !is_query_name(&name) {
+            return Err(Error::Configuration(format!("Invalid query name: {:?}", name)));
+        }
+        if sql.trim().trim_end_matches(';').trim().is_empty() {
+            return Err(Error::Configuration(format!("Query {} has no SQL", name)));
+        }
+        let parameters = PreparedStatement::parse(&sql).parameters;
+        Ok(Self { name, sql, parameters, settings: None })
+    }
+
+    /// Run the template with these settings
+    pub fn with_settings(mut self, settings: QuerySettings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// Check that `params` has a value for every placeholder and nothing else
+    pub fn check_params(&self, params: &HashMap<String, Value>) -> Result<()> {
+        if let Some(missing) = self.parameters.iter().find(|p| !params.contains_key(&p.name)) {
+            return Err(Error::Configuration(format!(
+                "Missing value for parameter {} of query {}",
+                missing.name, self.name
+            )));
+        }
+        let mut unknown: Vec<&str> = params
+            .keys()
+            .filter(|key| !self.parameters.iter().any(|p| &p.name == *key))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort_unstable();
+            return Err(Error::Configuration(format!(
+                "Query {} has no parameters {}",
+                self.name,
+                unknown.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Get the SQL with `{name:Type}` placeholders reduced to `{name}` for binding
+    pub fn bindable_sql(&self) -> String {
+        substitute(&self.sql, |parameter| format!("{{{}}}", parameter.name))
+    }
+
+    /// Get the SQL with every placeholder replaced by the default value of its type
+    ///
+    /// Placeholders without a declared type become `NULL`.
+    pub fn sample_sql(&self) -> String {
+        substitute(&self.sql, |parameter| match &parameter.type_name {
+            Some(type_name) => format!("defaultValueOfTypeName('{}')", type_name.replace('\'', "\\'")),
+            None => "NULL".to_string(),
+        })
+    }
+
+    /// Check whether the server can plan the template rather than only parse it
+    pub fn is_plannable(&self) -> bool {
+        let sql = self.sql.trim_start().trim_start_matches('(');
+        let keyword = sql.split_whitespace().next().unwrap_or_default();
+        keyword.eq_ignore_ascii_case("SELECT") || keyword.eq_ignore_ascii_case("WITH")
+    }
+}
+
+/// Named query templates
+///
+/// Clones share the same templates.
+#[derive(Clone, Default)]
+pub struct QueryRegistry {
+    templates: Arc<RwLock<HashMap<String, Arc<QueryTemplate>>>>,
+}
+
+impl QueryRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a template, replacing any with the same name
+    pub fn register(&self, name: impl Into<String>, sql: impl Into<String>) -> Result<()> {
+        self.insert(QueryTemplate::new(name, sql)?);
+        Ok(())
+    }
+
+    /// Register a template running with its own settings
+    pub fn register_with_settings(
+        &self,
+        name: impl Into<String>,
+        sql: impl Into<String>,
+        settings: QuerySettings,
+    ) -> Result<()> {
+        self.insert(QueryTemplate::new(name, sql)?.with_settings(settings));
+        Ok(())
+    }
+
+    /// Register a parsed template, replacing any with the same name
+    pub fn insert(&self, template: QueryTemplate) {
+        let mut templates = self.templates.write().unwrap_or_else(|e| e.into_inner());
+        templates.insert(template.name.clone(), Arc::new(template));
+    }
+
+    /// Register the templates in a text, naming a lone unmarked one `default_name`
+    ///
+    /// Returns the number of templates registered.
+    pub fn load_str(&self, text: &str, default_name: &str) -> Result<usize> {
+        let templates = split_templates(text, default_name)?;
+        let count = templates.len();
+        templates.into_iter().for_each(|template| self.insert(template));
+        Ok(count)
+    }
+
+    /// Register the templates in a file, naming a lone unmarked one after the file stem
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::Configuration(format!("Failed to read queries from {}: {}", path.display(), e)))?;
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        self.load_str(&text, stem).map_err(|e| match e {
+            Error::Configuration(message) => Error::Configuration(format!("{}: {}", path.display(), message)),
+            e => e,
+        })
+    }
+
+    /// Register the templates of every `.sql` file in a directory
+    pub fn load_dir(&self, dir: impl AsRef<Path>) -> Result<usize> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| Error::Configuration(format!("Failed to list queries in {}: {}", dir.display(), e)))?;
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == QUERY_FILE_EXTENSION))
+            .collect();
+        paths.sort();
+        paths.iter().map(|path| self.load_file(path)).sum()
+    }
+
+    /// Get a template by name
+    pub fn get(&self, name: &str) -> Option<Arc<QueryTemplate>> {
+        self.templates.read().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+    }
+
+    /// Remove a template, returning it if it was registered
+    pub fn remove(&self, name: &str) -> Option<Arc<QueryTemplate>> {
+        self.templates.write().unwrap_or_else(|e| e.into_inner()).remove(name)
+    }
+
+    /// Get the registered names in order
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.templates.read().unwrap_or_else(|e| e.into_inner()).keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Get every template, ordered by name
+    pub fn templates(&self) -> Vec<Arc<QueryTemplate>> {
+        let mut templates: Vec<_> = self.templates.read().unwrap_or_else(|e| e.into_inner()).values().cloned().collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates
+    }
+
+    /// Get the number of templates
+    pub fn len(&self) -> usize {
+        self.templates.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Check whether no template is registered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for QueryRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryRegistry").field("names", &self.names()).finish()
+    }
+}
+
+fn is_query_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+/// Split a text into the templates following `-- name:` markers
+fn split_templates(text: &str, default_name: &str) -> Result<Vec<QueryTemplate>> {
+    let mut named: Vec<(String, String)> = Vec::new();
+    let mut preamble = String::new();
+    for line in text.lines() {
+        if let Some(name) = line.trim_start().strip_prefix(NAME_MARKER) {
+            named.push((name.trim().to_string(), String::new()));
+            continue;
+        }
+        let sql = named.last_mut().map_or(&mut preamble, |(_, sql)| sql);
+        sql.push_str(line);
+        sql.push('\n');
+    }
+
+    if named.is_empty() {
+        return Ok(vec![QueryTemplate::new(default_name, preamble.trim())?]);
+    }
+    if preamble.lines().any(|line| !line.trim().is_empty() && !line.trim_start().starts_with("--")) {
+        return Err(Error::Configuration("SQL before the first `-- name:` marker".to_string()));
+    }
+    let mut templates: Vec<QueryTemplate> = Vec::with_capacity(named.len());
+    for (name, sql) in named {
+        if templates.iter().any(|template| template.name == name) {
+            return Err(Error::Configuration(format!("Query {} is defined twice", name)));
+        }
+        templates.push(QueryTemplate::new(name, sql.trim())?);
+    }
+    Ok(templates)
+}
+
+/// Replace each placeholder outside string literals with `replacement`
+fn substitute(sql: &str, replacement: impl Fn(&StatementParameter) -> String) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut quoted = false;
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        if c == '\'' {
+            quoted = !quoted;
+        } else if c == '{' && !quoted {
+            if let Some(len) = rest.find('}') {
+                let inner = &rest[1..len];
+                let (name, type_name) = match inner.split_once(':') {
+                    Some((name, ty)) => (name.trim(), Some(ty.trim().to_string())),
+                    None => (inner.trim(), None),
+                };
+                if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    out.push_str(&replacement(&StatementParameter { name: name.to_string(), type_name }));
+                    rest = &rest[len + 1..];
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_template() {
+        let template = QueryTemplate::new(
+            "find_user",
+            "SELECT id FROM users WHERE id = {id:UInt64} AND name != '{id}' AND tag = {tag}",
+        )
+        .unwrap();
+        assert_eq!(template.parameters.len(), 2);
+        assert!(template.is_plannable());
+        assert_eq!(
+            template.bindable_sql(),
+            "SELECT id FROM users WHERE id = {id} AND name != '{id}' AND tag = {tag}"
+        );
+        assert_eq!(
+            template.sample_sql(),
+            "SELECT id FROM users WHERE id = defaultValueOfTypeName('UInt64') AND name != '{id}' AND tag = NULL"
+        );
+
+        let mut params = HashMap::from([("id".to_string(), Value::UInt64(1))]);
+        let err = template.check_params(&params).unwrap_err();
+        assert!(err.to_string().contains("Missing value for parameter tag"), "{}", err);
+        params.insert("tag".to_string(), Value::String("a".into()));
+        assert!(template.check_params(&params).is_ok());
+        params.insert("limit".to_string(), Value::UInt64(1));
+        assert!(template.check_params(&params).unwrap_err().to_string().contains("no parameters limit"));
+
+        assert!(QueryTemplate::new("find user", "SELECT 1").is_err());
+        assert!(QueryTemplate::new("empty", " ; ").is_err());
+        assert!(!QueryTemplate::new("purge", "ALTER TABLE t DELETE WHERE 1").unwrap().is_plannable());
+    }
+
+    #[test]
+    fn test_query_registry_load() {
+        let registry = QueryRegistry::new();
+        let shared = registry.clone();
+        let text = "-- queries for users\n-- name: count_users\nSELECT count() FROM users;\n\n-- name: find_user\nSELECT * FROM users\nWHERE id = {id:UInt64}\n";
+        assert_eq!(registry.load_str(text, "users").unwrap(), 2);
+        assert_eq!(shared.names(), ["count_users", "find_user"]);
+        assert_eq!(shared.get("find_user").unwrap().sql, "SELECT * FROM users\nWHERE id = {id:UInt64}");
+
+        assert_eq!(registry.load_str("SELECT 1\n", "ping").unwrap(), 1);
+        assert_eq!(registry.get("ping").unwrap().sql, "SELECT 1");
+        assert!(registry.load_str("SELECT 1\n-- name: two\nSELECT 2", "x").is_err());
+        assert!(registry.load_str("-- name: a\nSELECT 1\n-- name: a\nSELECT 2", "x").is_err());
+
+        let dir = std::env::temp_dir().join(format!("clickhouse-queries-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("top_events.sql"), "SELECT * FROM events LIMIT {n:UInt32}").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a query").unwrap();
+        let loaded = registry.load_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap(), 1);
+        assert_eq!(registry.get("top_events").unwrap().parameters[0].type_name.as_deref(), Some("UInt32"));
+        assert_eq!(registry.len(), 4);
+        assert!(registry.remove("ping").is_some());
+        assert!(registry.load_dir(dir).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_run_checks_template() {
+        use crate::client::{Client, ClientOptions};
+
+        let queries = QueryRegistry::new();
+        let client = Client::new(ClientOptions::default().min_connections(0).queries(queries.clone())).unwrap();
+        client.queries().register("find_user", "SELECT * FROM users WHERE id = {id:UInt64}").unwrap();
+        assert_eq!(queries.names(), ["find_user"]);
+
+        let err = client.run("find_users", HashMap::new()).await.unwrap_err();
+        assert!(err.to_string().contains("Unknown query: find_users"), "{}", err);
+        let err = client.clone().run("find_user", HashMap::new()).await.unwrap_err();
+        assert!(err.to_string().contains("Missing value for parameter id"), "{}", err);
+    }
+}
@@ -0,0 +1,91 @@
+//! Changes the server pushes in the middle of a session
+//!
+//! ```rust
+//! # fn example() -> clickhouse_rs::error::Result<()> {
+//! use clickhouse_rs::client::ServerEvent;
+//! use clickhouse_rs::{Client, ClientOptions};
+//!
+//! let client = Client::new(ClientOptions::default().on_server_event(|event: &ServerEvent| {
+//!     if let ServerEvent::TimezoneChanged { timezone, .. } = event {
+//!         println!("session timezone is now {}", timezone);
+//!     }
+//! }))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Besides the packets answering a query, the server may send a timezone
+//! update after `SET session_timezone`, logs, profile events, table columns
+//! and part UUIDs at any point of a stream. Connections consume these instead
+//! of failing the stream: a timezone update switches the timezone results are
+//! decoded with, and every one of them is passed to the handler.
+
+use crate::protocol::{Frame, PacketType};
+use chrono_tz::Tz;
+use std::fmt;
+use std::sync::Arc;
+
+/// Change pushed by the server
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    /// The session timezone changed
+    TimezoneChanged {
+        /// Timezone before the update, if one was known
+        previous: Option<Tz>,
+        /// New session timezone
+        timezone: Tz,
+    },
+    /// A packet the query stream does not handle itself
+    Packet(Frame),
+}
+
+impl ServerEvent {
+    /// Get the type of the packet behind the event
+    pub fn packet_type(&self) -> u64 {
+        match self {
+            ServerEvent::TimezoneChanged { .. } => PacketType::ServerTimezoneUpdate.to_u64(),
+            ServerEvent::Packet(frame) => frame.packet_type,
+        }
+    }
+}
+
+/// Check whether a packet is pushed by the server rather than part of a query answer
+pub fn is_server_pushed(packet_type: u64) -> bool {
+    matches!(
+        PacketType::from_u64(packet_type),
+        Some(
+            PacketType::ServerTimezoneUpdate
+                | PacketType::ServerLog
+                | PacketType::ServerProfileEvents
+                | PacketType::ServerTableColumns
+                | PacketType::ServerPartUUIDs
+        ) | None
+    )
+}
+
+/// Callback receiving [`ServerEvent`]s, shared by all connections
+#[derive(Clone)]
+pub struct ServerEventHandler {
+    callback: Arc<dyn Fn(&ServerEvent) + Send + Sync>,
+}
+
+impl ServerEventHandler {
+    /// Create a handler calling `callback` for every event
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&ServerEvent) + Send + Sync + 'static,
+    {
+        Self { callback: Arc::new(callback) }
+    }
+
+    /// Pass an event to the callback
+    pub fn notify(&self, event: &ServerEvent) {
+        (self.callback)(event)
+    }
+}
+
+impl fmt::Debug for ServerEventHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerEventHandler").finish_non_exhaustive()
+    }
+}
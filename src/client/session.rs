@@ -0,0 +1,193 @@
+//! Session state tracking and restore on reconnect
+//!
+//! `SET` statements, `SET ROLE` and temporary tables only live as long as the
+//! server session. A connection records them as they succeed so that, when it
+//! has to reconnect, the [`SessionRestorePolicy`] decides whether the state is
+//! replayed, dropped or reported as an error.
+
+use serde::{Deserialize, Serialize};
+
+/// What a connection does with its session state after reconnecting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionRestorePolicy {
+    /// Replay settings and role; a failed replay or lost temporary tables fail the request once
+    #[default]
+    Restore,
+    /// Start with a fresh session, logging what was dropped
+    Discard,
+    /// Fail the request if any session state was lost
+    Fail,
+}
+
+/// Session state applied on a connection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionState {
+    settings: Vec<(String, String)>,
+    role: Option<String>,
+    temporary_tables: Vec<String>,
+}
+
+impl SessionState {
+    /// Create an empty session state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the effect of a statement that ran successfully
+    pub fn track(&mut self, sql: &str) {
+        let sql = sql.trim().trim_end_matches(';').trim();
+        let words: Vec<&str> = sql.split_whitespace().collect();
+        let keyword = |i: usize, expected: &str| words.get(i).is_some_and(|w| w.eq_ignore_ascii_case(expected));
+
+        if keyword(0, "SET") && keyword(1, "ROLE") {
+            let role = words[2..].join(" ");
+            self.role = (!role.is_empty()).then_some(role);
+        } else if keyword(0, "SET") && !keyword(1, "DEFAULT") {
+            for assignment in split_assignments(&sql[3..]) {
+                if let Some((key, value)) = assignment.split_once('=') {
+                    self.set(key.trim(), value.trim());
+                }
+            }
+        } else if keyword(0, "CREATE") && keyword(1, "TEMPORARY") && keyword(2, "TABLE") {
+            let name = if keyword(3, "IF") { words.get(6) } else { words.get(3) };
+            if let Some(name) = name {
+                let name = table_name(name);
+                if !self.temporary_tables.contains(&name) {
+                    self.temporary_tables.push(name);
+                }
+            }
+        } else if keyword(0, "DROP") && (keyword(1, "TABLE") || keyword(1, "TEMPORARY")) {
+            let first = if keyword(1, "TEMPORARY") { 3 } else { 2 };
+            let index = if keyword(first, "IF") { first + 2 } else { first };
+            if let Some(name) = words.get(index) {
+                let name = table_name(name);
+                self.temporary_tables.retain(|table| *table != name);
+            }
+        }
+    }
+
+    /// Record a setting
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.settings.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => self.settings.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// Get the recorded settings in the order they were first applied
+    pub fn settings(&self) -> &[(String, String)] {
+        &self.settings
+    }
+
+    /// Get the active role
+    pub fn role(&self) -> Option<&str> {
+        self.role.as_deref()
+    }
+
+    /// Get the temporary tables created in the session
+    pub fn temporary_tables(&self) -> &[String] {
+        &self.temporary_tables
+    }
+
+    /// Forget the temporary tables, returning their names
+    pub fn take_temporary_tables(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.temporary_tables)
+    }
+
+    /// Check if no state was recorded
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty() && self.role.is_none() && self.temporary_tables.is_empty()
+    }
+
+    /// Statements that re-apply the settings and role
+    ///
+    /// Temporary tables cannot be restored, since their contents are gone.
+    pub fn restore_statements(&self) -> Vec<String> {
+        let mut statements: Vec<String> = self.role.iter().map(|role| format!("SET ROLE {}", role)).collect();
+        statements.extend(self.settings.iter().map(|(key, value)| format!("SET {} = {}", key, value)));
+        statements
+    }
+
+    /// Describe the recorded state for log and error messages
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.settings.is_empty() {
+            let keys: Vec<&str> = self.settings.iter().map(|(key, _)| key.as_str()).collect();
+            parts.push(format!("settings {}", keys.join(", ")));
+        }
+        if let Some(role) = &self.role {
+            parts.push(format!("role {}", role));
+        }
+        if !self.temporary_tables.is_empty() {
+            parts.push(format!("temporary tables {}", self.temporary_tables.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+/// Split `a = 1, b = 'x,y'` on commas outside quotes
+fn split_assignments(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+pub(super) fn table_name(word: &str) -> String {
+    word.split('(').next().unwrap_or(word).trim_matches('`').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_session_statements() {
+        let mut session = SessionState::new();
+        session.track("SET max_threads = 4, format_csv_delimiter = ','");
+        session.track("set max_threads=8;");
+        session.track("SET ROLE analyst");
+        session.track("CREATE TEMPORARY TABLE IF NOT EXISTS ids (id UInt64) ENGINE = Memory");
+        session.track("CREATE TEMPORARY TABLE `tmp`(x UInt8)");
+        session.track("DROP TEMPORARY TABLE IF EXISTS tmp");
+        session.track("SET DEFAULT ROLE admin TO bob");
+        session.track("SELECT 1");
+
+        assert_eq!(
+            session.settings(),
+            &[
+                ("max_threads".to_string(), "8".to_string()),
+                ("format_csv_delimiter".to_string(), "','".to_string()),
+            ]
+        );
+        assert_eq!(session.role(), Some("analyst"));
+        assert_eq!(session.temporary_tables(), &["ids".to_string()]);
+        assert_eq!(
+            session.restore_statements(),
+            vec!["SET ROLE analyst", "SET max_threads = 8", "SET format_csv_delimiter = ','"]
+        );
+        assert_eq!(
+            session.describe(),
+            "settings max_threads, format_csv_delimiter; role analyst; temporary tables ids"
+        );
+    }
+
+    #[test]
+    fn test_empty_session() {
+        let session = SessionState::new();
+        assert!(session.is_empty());
+        assert!(session.restore_statements().is_empty());
+        assert_eq!(SessionRestorePolicy::default(), SessionRestorePolicy::Restore);
+    }
+}
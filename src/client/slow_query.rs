@@ -0,0 +1,96 @@
+//! Slow query reports with sampled `EXPLAIN` capture
+//!
+//! ```rust
+//! # fn example() -> clickhouse_rs::error::Result<()> {
+//! use clickhouse_rs::{Client, ClientOptions};
+//! use std::time::Duration;
+//!
+//! let client = Client::new(
+//!     ClientOptions::default()
+//!         .slow_query_threshold(Duration::from_secs(2))
+//!         .slow_query_explain_rate(0.1),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A query running longer than the threshold is logged with its stats and
+//! reported as [`ClientWarning::SlowQuery`]. With an explain rate, that share
+//! of slow SELECTs is then explained in the background, off the caller's
+//! path, and the plan follows as [`ClientWarning::SlowQueryPlan`].
+
+use crate::client::{Client, ClientWarning, ExplainKind, QueryStats};
+use std::time::Duration;
+
+/// Query that ran longer than the slow query threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowQuery {
+    /// ID of the query
+    pub query_id: String,
+    /// Text of the query
+    pub sql: String,
+    /// How long it ran
+    pub elapsed: Duration,
+    /// Threshold it exceeded
+    pub threshold: Duration,
+    /// Rows and bytes the query read
+    pub stats: QueryStats,
+}
+
+impl SlowQuery {
+    /// Check whether the query can be run under `EXPLAIN`
+    pub fn is_explainable(&self) -> bool {
+        let sql = self.sql.trim_start().trim_start_matches('(');
+        let keyword = sql.split_whitespace().next().unwrap_or_default();
+        keyword.eq_ignore_ascii_case("SELECT") || keyword.eq_ignore_ascii_case("WITH")
+    }
+}
+
+/// Pick a share `rate` of events
+fn sampled(rate: f64) -> bool {
+    rate >= 1.0 || (rate > 0.0 && rand::random::<f64>() < rate)
+}
+
+impl Client {
+    /// Explain a slow query in the background if it is sampled
+    pub(crate) fn explain_slow_query(&self, slow: SlowQuery) {
+        if !slow.is_explainable() || !sampled(self.options.slow_query_explain_rate) {
+            return;
+        }
+        let client = self.clone();
+        tokio::spawn(async move {
+            match client.explain(&slow.sql, ExplainKind::Plan).await {
+                Ok(plan) => client.options.warnings.emit(ClientWarning::SlowQueryPlan {
+                    query_id: slow.query_id,
+                    plan,
+                }),
+                Err(e) => tracing::debug!("Failed to explain slow query {}: {}", slow.query_id, e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slow_query_sampling() {
+        let slow = |sql: &str| SlowQuery {
+            query_id: "q".to_string(),
+            sql: sql.to_string(),
+            elapsed: Duration::from_secs(3),
+            threshold: Duration::from_secs(1),
+            stats: QueryStats::new(10, 100, Duration::from_secs(3)),
+        };
+        assert!(slow("SELECT 1").is_explainable());
+        assert!(slow("  with x AS (SELECT 1) SELECT * FROM x").is_explainable());
+        assert!(slow("(SELECT 1) UNION ALL (SELECT 2)").is_explainable());
+        assert!(!slow("INSERT INTO t VALUES (1)").is_explainable());
+        assert!(!slow("EXPLAIN SELECT 1").is_explainable());
+
+        assert!(sampled(1.0));
+        assert!(!sampled(0.0));
+        assert!(!sampled(f64::NAN));
+    }
+}
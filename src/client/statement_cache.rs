@@ -0,0 +1,384 @@
+//! Per-connection cache of prepared statements and table schemas
+//!
+//! Hot query paths send the same parameterized texts over and over. A
+//! [`StatementCache`] keys them by their normalized text and keeps the parsed
+//! parameter placeholders and, once the statement has run, its result columns.
+//! It also keeps `DESCRIBE TABLE` results for insert targets, so metadata is
+//! only fetched from the server once per connection.
+//!
+//! Entries are dropped when the connection runs DDL on a table, when it
+//! reconnects, or on explicit invalidation.
+
+use crate::client::session::table_name;
+use crate::client::TableColumn;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Default number of statements cached per connection
+pub const DEFAULT_STATEMENT_CACHE_SIZE: usize = 256;
+
+/// Collapse whitespace outside string literals and drop a trailing `;`
+///
+/// Texts that differ only in formatting share one cache entry.
+pub fn normalize_query(sql: &str) -> String {
+    let mut normalized = String::with_capacity(sql.len());
+    let mut quoted = false;
+    let mut pending_space = false;
+    for c in sql.trim().trim_end_matches(';').trim_end().chars() {
+        if !quoted && c.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        if pending_space {
+            normalized.push(' ');
+            pending_space = false;
+        }
+        if c == '\'' {
+            quoted = !quoted;
+        }
+        normalized.push(c);
+    }
+    normalized
+}
+
+/// A `{name}` or `{name:Type}` placeholder of a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementParameter {
+    /// Parameter name
+    pub name: String,
+    /// Declared type, if the placeholder has one
+    pub type_name: Option<String>,
+}
+
+/// A statement parsed once and reused for every execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedStatement {
+    /// Normalized query text
+    pub sql: String,
+    /// Placeholders in order of first appearance
+    pub parameters: Vec<StatementParameter>,
+    /// Result columns, known once the statement has run
+    pub result_columns: Option<Vec<TableColumn>>,
+}
+
+impl PreparedStatement {
+    /// Normalize a statement and parse its placeholders
+    pub fn parse(sql: &str) -> Self {
+        let sql = normalize_query(sql);
+        let mut parameters: Vec<StatementParameter> = Vec::new();
+        let mut quoted = false;
+        let mut pos = 0;
+        while let Some(c) = sql[pos..].chars().next() {
+            if c == '\'' {
+                quoted = !quoted;
+            } else if c == '{' && !quoted {
+                let Some(len) = sql[pos..].find('}') else {
+                    break;
+                };
+                let inner = &sql[pos + 1..pos + len];
+                let (name, type_name) = match inner.split_once(':') {
+                    Some((name, ty)) => (name.trim(), Some(ty.trim().to_string())),
+                    None => (inner.trim(), None),
+                };
+                if is_identifier(name) && !parameters.iter().any(|p| p.name == name) {
+                    parameters.push(StatementParameter {
+                        name: name.to_string(),
+                        type_name,
+                    });
+                }
+                pos += len + 1;
+                continue;
+            }
+            pos += c.len_utf8();
+        }
+        Self {
+            sql,
+            parameters,
+            result_columns: None,
+        }
+    }
+
+    /// Get a placeholder by name
+    pub fn parameter(&self, name: &str) -> Option<&StatementParameter> {
+        self.parameters.iter().find(|p| p.name == name)
+    }
+
+    /// Check whether the statement mentions a table
+    fn mentions(&self, table: &str) -> bool {
+        self.sql.contains(table)
+    }
+}
+
+/// Check whether braces hold a parameter name rather than e.g. a map literal
+fn is_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Counters of a [`StatementCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatementCacheStats {
+    /// Statements found in the cache
+    pub hits: u64,
+    /// Statements parsed because they were not cached
+    pub misses: u64,
+    /// Statements dropped to make room for others
+    pub evictions: u64,
+    /// Table schemas found in the cache
+    pub schema_hits: u64,
+    /// Table schemas that had to be read from the server
+    pub schema_misses: u64,
+    /// Statements and schemas dropped because they may be stale
+    pub invalidations: u64,
+}
+
+impl StatementCacheStats {
+    /// Counters accumulated since an earlier snapshot
+    pub fn since(&self, earlier: &StatementCacheStats) -> StatementCacheStats {
+        StatementCacheStats {
+            hits: self.hits.saturating_sub(earlier.hits),
+            misses: self.misses.saturating_sub(earlier.misses),
+            evictions: self.evictions.saturating_sub(earlier.evictions),
+            schema_hits: self.schema_hits.saturating_sub(earlier.schema_hits),
+            schema_misses: self.schema_misses.saturating_sub(earlier.schema_misses),
+            invalidations: self.invalidations.saturating_sub(earlier.invalidations),
+        }
+    }
+
+    /// Share of statement lookups served from the cache
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// Prepared statements and table schemas of one connection
+///
+/// Statements are evicted least recently used first. A capacity of zero
+/// disables statement caching; table schemas are cached regardless.
+#[derive(Debug)]
+pub struct StatementCache {
+    capacity: usize,
+    statements: HashMap<String, (Arc<PreparedStatement>, u64)>,
+    tables: HashMap<String, Vec<TableColumn>>,
+    clock: u64,
+    stats: StatementCacheStats,
+}
+
+impl StatementCache {
+    /// Create a cache holding at most `capacity` statements
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            statements: HashMap::new(),
+            tables: HashMap::new(),
+            clock: 0,
+            stats: StatementCacheStats::default(),
+        }
+    }
+
+    /// Get the maximum number of cached statements
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of cached statements
+    pub fn len(&self) -> usize {
+        self.statements.len()
+    }
+
+    /// Check if no statements are cached
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    /// Get the counters accumulated over the life of the cache
+    pub fn stats(&self) -> StatementCacheStats {
+        self.stats
+    }
+
+    /// Get a cached statement, parsing and caching it on a miss
+    pub fn prepare(&mut self, sql: &str) -> Arc<PreparedStatement> {
+        let key = normalize_query(sql);
+        self.clock += 1;
+        if let Some((statement, last_used)) = self.statements.get_mut(&key) {
+            *last_used = self.clock;
+            self.stats.hits += 1;
+            return statement.clone();
+        }
+
+        self.stats.misses += 1;
+        let statement = Arc::new(PreparedStatement::parse(sql));
+        if self.capacity == 0 {
+            return statement;
+        }
+        if self.statements.len() >= self.capacity {
+            self.evict_oldest();
+        }
+        self.statements.insert(key, (statement.clone(), self.clock));
+        statement
+    }
+
+    /// Get a cached statement without counting a lookup
+    pub fn get(&self, sql: &str) -> Option<Arc<PreparedStatement>> {
+        self.statements.get(&normalize_query(sql)).map(|(statement, _)| statement.clone())
+    }
+
+    /// Record the result columns of a cached statement
+    pub fn set_result_columns(&mut self, sql: &str, columns: Vec<TableColumn>) {
+        if let Some((statement, _)) = self.statements.get_mut(&normalize_query(sql)) {
+            Arc::make_mut(statement).result_columns = Some(columns);
+        }
+    }
+
+    /// Get the cached schema of a table
+    pub fn table_schema(&mut self, table: &str) -> Option<Vec<TableColumn>> {
+        match self.tables.get(&table_name(table)) {
+            Some(schema) => {
+                self.stats.schema_hits += 1;
+                Some(schema.clone())
+            }
+            None => {
+                self.stats.schema_misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache the schema of a table
+    pub fn set_table_schema(&mut self, table: &str, schema: Vec<TableColumn>) {
+        self.tables.insert(table_name(table), schema);
+    }
+
+    /// Drop a cached statement
+    pub fn invalidate_statement(&mut self, sql: &str) {
+        if self.statements.remove(&normalize_query(sql)).is_some() {
+            self.stats.invalidations += 1;
+        }
+    }
+
+    /// Drop the schema of a table and the statements that mention it
+    pub fn invalidate_table(&mut self, table: &str) {
+        let table = table_name(table);
+        let before = self.statements.len() + self.tables.len();
+        self.tables.remove(&table);
+        self.statements.retain(|_, (statement, _)| !statement.mentions(&table));
+        self.stats.invalidations += (before - self.statements.len() - self.tables.len()) as u64;
+    }
+
+    /// Drop all statements and schemas
+    pub fn clear(&mut self) {
+        self.stats.invalidations += (self.statements.len() + self.tables.len()) as u64;
+        self.statements.clear();
+        self.tables.clear();
+    }
+
+    /// Invalidate what a statement that ran successfully may have made stale
+    ///
+    /// DDL on a named table invalidates that table; renames and exchanges
+    /// touch several tables and drop every cached schema.
+    pub fn observe(&mut self, sql: &str) {
+        let sql = normalize_query(sql);
+        let words: Vec<&str> = sql.split(' ').collect();
+        let keyword = |i: usize, expected: &str| words.get(i).is_some_and(|w| w.eq_ignore_ascii_case(expected));
+
+        let target = if keyword(0, "ALTER") && keyword(1, "TABLE") {
+            Some(2)
+        } else if (keyword(0, "DROP") || keyword(0, "TRUNCATE")) && keyword(1, "TABLE") {
+            Some(if keyword(2, "IF") { 4 } else { 2 })
+        } else if keyword(0, "TRUNCATE") {
+            Some(1)
+        } else if keyword(0, "CREATE") && keyword(1, "OR") && keyword(2, "REPLACE") && keyword(3, "TABLE") {
+            Some(4)
+        } else if keyword(0, "REPLACE") && keyword(1, "TABLE") {
+            Some(2)
+        } else if keyword(0, "RENAME") || keyword(0, "EXCHANGE") || (keyword(0, "DROP") && keyword(1, "DATABASE")) {
+            self.clear();
+            return;
+        } else {
+            None
+        };
+
+        if let Some(table) = target.and_then(|i| words.get(i)) {
+            self.invalidate_table(table);
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .statements
+            .iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            self.statements.remove(&key);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+impl Default for StatementCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATEMENT_CACHE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepared_statement_parse() {
+        let statement = PreparedStatement::parse(
+            "SELECT *\n  FROM t WHERE id = {id:UInt64} AND s = '{not}' OR id IN {ids}  AND x = {id:UInt64} AND m = {'k':1};",
+        );
+        assert_eq!(
+            statement.sql,
+            "SELECT * FROM t WHERE id = {id:UInt64} AND s = '{not}' OR id IN {ids} AND x = {id:UInt64} AND m = {'k':1}"
+        );
+        let names: Vec<&str> = statement.parameters.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["id", "ids"]);
+        assert_eq!(statement.parameter("id").unwrap().type_name.as_deref(), Some("UInt64"));
+        assert_eq!(statement.parameter("ids").unwrap().type_name, None);
+        assert_eq!(normalize_query("SELECT  'a  b'"), "SELECT 'a  b'");
+    }
+
+    #[test]
+    fn test_statement_cache_eviction_and_invalidation() {
+        let mut cache = StatementCache::new(2);
+        cache.prepare("SELECT a FROM t1 WHERE id = {id:UInt64}");
+        cache.prepare("SELECT  a FROM t1 WHERE id = {id:UInt64}");
+        cache.prepare("SELECT b FROM t2");
+        cache.prepare("SELECT a FROM t1 WHERE id = {id:UInt64}");
+        cache.prepare("SELECT c FROM t3");
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("SELECT b FROM t2").is_none());
+
+        cache.set_result_columns("SELECT a FROM t1 WHERE id = {id:UInt64}", vec![TableColumn::new("a", "String")]);
+        let cached = cache.prepare("SELECT a FROM t1 WHERE id = {id:UInt64}");
+        assert_eq!(cached.result_columns.as_ref().unwrap()[0].name, "a");
+
+        cache.set_table_schema("`t1`", vec![TableColumn::new("a", "String")]);
+        assert!(cache.table_schema("t1").is_some());
+        cache.observe("ALTER TABLE t1 ADD COLUMN b UInt8");
+        assert!(cache.table_schema("t1").is_none());
+        assert_eq!(cache.len(), 1);
+
+        cache.set_table_schema("t3", vec![]);
+        cache.observe("RENAME TABLE t3 TO t4");
+        assert!(cache.is_empty());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (3, 3, 1));
+        assert_eq!((stats.schema_hits, stats.schema_misses, stats.invalidations), (1, 1, 4));
+        assert_eq!(stats.since(&StatementCacheStats { hits: 1, ..stats }).hits, 2);
+
+        let mut disabled = StatementCache::new(0);
+        disabled.prepare("SELECT 1");
+        assert!(disabled.is_empty());
+    }
+}
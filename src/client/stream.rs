@@ -0,0 +1,863 @@
+//! Block-by-block query streaming with resume on connection loss and flow control
+
+use crate::client::pool::PooledConnection;
+use crate::client::{
+    auto_block_size, chunk_block, BlockSizes, Connection, InFlightGuard, MemoryBudget, MemoryReservation, QuerySettings,
+};
+use crate::error::{Error, ErrorCategory, Result};
+use crate::types::{Block, Value};
+use futures::future::BoxFuture;
+use futures::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Strategy for continuing a streaming SELECT after the connection drops
+///
+/// The query must return rows ordered ascending by `cursor_column`, and the
+/// column values must be unique. After a network failure the query is re-issued
+/// with a `cursor_column > last_value` predicate instead of restarting.
+#[derive(Debug, Clone)]
+pub struct ResumeStrategy {
+    cursor_column: String,
+    max_resumes: usize,
+}
+
+impl ResumeStrategy {
+    /// Create a resume strategy keyed on the given column
+    pub fn new(cursor_column: impl Into<String>) -> Self {
+        Self {
+            cursor_column: cursor_column.into(),
+            max_resumes: 3,
+        }
+    }
+
+    /// Set how many times the stream may be resumed
+    pub fn max_resumes(mut self, max_resumes: usize) -> Self {
+        self.max_resumes = max_resumes;
+        self
+    }
+
+    /// Get the cursor column
+    pub fn cursor_column(&self) -> &str {
+        &self.cursor_column
+    }
+
+    /// Rewrite a query to continue after the last received cursor value
+    pub fn resume_query(&self, sql: &str, last: &Value) -> Result<String> {
+        let sql = sql.trim().trim_end_matches(';');
+        Ok(format!(
+            "SELECT * FROM ({}) WHERE `{}` > {} ORDER BY `{}`",
+            sql,
+            self.cursor_column.replace('`', "\\`"),
+            sql_literal(last)?,
+            self.cursor_column.replace('`', "\\`")
+        ))
+    }
+}
+
+/// Format a scalar value as a SQL literal
+pub(crate) fn sql_literal(value: &Value) -> Result<String> {
+    let quote = |s: &str| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+    Ok(match value {
+        Value::UInt8(_)
+        | Value::UInt16(_)
+        | Value::UInt32(_)
+        | Value::UInt64(_)
+        | Value::UInt128(_)
+        | Value::Int8(_)
+        | Value::Int16(_)
+        | Value::Int32(_)
+        | Value::Int64(_)
+        | Value::Int128(_)
+        | Value::Float32(_)
+        | Value::Float64(_) => value.to_string(),
+        Value::String(s) => quote(s),
+        Value::Date(d) => quote(&d.format("%Y-%m-%d").to_string()),
+        Value::DateTime(dt) => quote(&dt.format("%Y-%m-%d %H:%M:%S").to_string()),
+        Value::DateTime64(dt) => quote(&dt.format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+        Value::UUID(u) => quote(&u.to_string()),
+        other => {
+            return Err(Error::Unsupported(format!(
+                "Cannot use a {} value as a SQL literal",
+                other.type_name()
+            )))
+        }
+    })
+}
+
+#[derive(Debug, Default)]
+struct ControlState {
+    paused: AtomicBool,
+    cancelled: AtomicBool,
+    changed: Notify,
+}
+
+/// Handle pausing, resuming or cancelling a [`QueryStream`] from anywhere
+///
+/// While paused, the stream hands out the blocks it already holds but reads
+/// nothing more from its connection, so further results wait in the
+/// connection rather than in client memory. Once the socket buffers fill,
+/// the server stops sending until the stream is resumed. A cancelled stream
+/// drops its buffered blocks and ends.
+#[derive(Debug, Clone, Default)]
+pub struct StreamControl {
+    state: Arc<ControlState>,
+}
+
+impl StreamControl {
+    /// Create a handle for a running stream
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop reading from the server once the buffered blocks are consumed
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::Release);
+        self.state.changed.notify_waiters();
+    }
+
+    /// Continue reading from the server
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::Release);
+        self.state.changed.notify_waiters();
+    }
+
+    /// End the stream, discarding the blocks not yet returned
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Release);
+        self.state.changed.notify_waiters();
+    }
+
+    /// Check whether the stream is paused
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Acquire)
+    }
+
+    /// Check whether the stream was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Wait until the stream is resumed or cancelled
+    async fn wait_while_paused(&self) {
+        loop {
+            let changed = self.state.changed.notified();
+            if !self.is_paused() || self.is_cancelled() {
+                return;
+            }
+            tracing::debug!("Query stream paused");
+            changed.await;
+        }
+    }
+}
+
+/// Blocks of one run of a query, read one at a time
+pub(crate) trait BlockSource: Send {
+    /// Read the next block, or `None` once the result is complete
+    fn next_block(&mut self) -> BoxFuture<'_, Result<Option<Block>>>;
+}
+
+impl BlockSource for Connection {
+    fn next_block(&mut self) -> BoxFuture<'_, Result<Option<Block>>> {
+        Box::pin(Connection::next_block(self))
+    }
+}
+
+/// Pooled connection reading the result of a client's query
+///
+/// The client counts the query as in flight until the stream lets go of the
+/// connection. A connection dropped mid-result is cancelled by the pool.
+pub(crate) struct PooledBlocks {
+    connection: PooledConnection,
+    _in_flight: InFlightGuard,
+}
+
+impl PooledBlocks {
+    pub(crate) fn new(connection: PooledConnection, in_flight: InFlightGuard) -> Self {
+        Self { connection, _in_flight: in_flight }
+    }
+}
+
+impl BlockSource for PooledBlocks {
+    fn next_block(&mut self) -> BoxFuture<'_, Result<Option<Block>>> {
+        Box::pin(self.connection.as_mut().next_block())
+    }
+}
+
+type OpenFn<'a> = Box<dyn FnMut(String) -> BoxFuture<'a, Result<Box<dyn BlockSource + 'a>>> + Send + 'a>;
+
+/// Stream of result blocks for a SELECT query
+///
+/// Blocks are read from the connection one at a time, when the caller asks
+/// for the next one. Before each read the stream reserves the size of the
+/// largest block so far from the client's [`MemoryBudget`], waiting while the
+/// budget is exhausted. Between calls a stream only holds memory for the
+/// chunks left over from a block cut down to the block size, so streams
+/// polled in turn from one task do not hold each other up.
+/// Reading can also be paused and cancelled through a [`StreamControl`].
+pub struct QueryStream<'a> {
+    sql: String,
+    resume: Option<ResumeStrategy>,
+    open: OpenFn<'a>,
+    source: Option<Box<dyn BlockSource + 'a>>,
+    budget: MemoryBudget,
+    pending: VecDeque<(Block, MemoryReservation)>,
+    overflows: Vec<Block>,
+    control: StreamControl,
+    last_cursor: Option<Value>,
+    rows_received: u64,
+    bytes_received: u64,
+    max_block_size: Option<u64>,
+    block_memory_target: Option<usize>,
+    block_sizes: BlockSizes,
+    largest_block: usize,
+    resumes: usize,
+    finished: bool,
+}
+
+impl<'a> QueryStream<'a> {
+    /// Create a stream that runs queries through `open`
+    ///
+    /// `open` sends a query and returns the source its blocks are read from;
+    /// it is called again with a rewritten query to resume.
+    pub(crate) fn new(
+        sql: impl Into<String>,
+        resume: Option<ResumeStrategy>,
+        open: impl FnMut(String) -> BoxFuture<'a, Result<Box<dyn BlockSource + 'a>>> + Send + 'a,
+    ) -> Self {
+        Self {
+            sql: sql.into(),
+            resume,
+            open: Box::new(open),
+            source: None,
+            budget: MemoryBudget::default(),
+            pending: VecDeque::new(),
+            overflows: Vec::new(),
+            control: StreamControl::new(),
+            last_cursor: None,
+            rows_received: 0,
+            bytes_received: 0,
+            max_block_size: None,
+            block_memory_target: None,
+            block_sizes: BlockSizes::default(),
+            largest_block: 0,
+            resumes: 0,
+            finished: false,
+        }
+    }
+
+    /// Charge buffered blocks to a shared memory budget
+    pub(crate) fn with_budget(mut self, budget: MemoryBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Cut blocks down to the block size of the settings
+    pub(crate) fn with_block_size(mut self, settings: &QuerySettings) -> Self {
+        self.max_block_size = settings.max_block_size;
+        self.block_memory_target = settings.block_memory_target;
+        self
+    }
+
+    /// Get the next block, or `None` once the result is exhausted
+    ///
+    /// Overflow blocks are not returned; see [`QueryStream::overflows`]. While
+    /// the stream is paused and has no buffered blocks left, this waits for
+    /// [`StreamControl::resume`].
+    pub async fn next_block(&mut self) -> Result<Option<Block>> {
+        loop {
+            if self.control.is_cancelled() {
+                self.pending.clear();
+                self.source = None;
+                self.finished = true;
+                return Ok(None);
+            }
+
+            if let Some((block, _reservation)) = self.pending.pop_front() {
+                if block.is_overflows() {
+                    self.overflows.push(block);
+                    continue;
+                }
+                self.track_cursor(&block)?;
+                self.rows_received += block.row_count as u64;
+                return Ok(Some(block));
+            }
+
+            if self.finished {
+                return Ok(None);
+            }
+
+            if self.control.is_paused() {
+                self.control.wait_while_paused().await;
+                continue;
+            }
+
+            let _reservation = self.budget.acquire(self.largest_block).await;
+            let read = match &mut self.source {
+                Some(source) => source.next_block().await,
+                None => {
+                    let sql = match (&self.resume, &self.last_cursor) {
+                        (Some(resume), Some(last)) => resume.resume_query(&self.sql, last)?,
+                        _ => self.sql.clone(),
+                    };
+                    match (self.open)(sql).await {
+                        Ok(source) => {
+                            self.source = Some(source);
+                            continue;
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+            };
+            match read {
+                Ok(Some(block)) => self.receive(block),
+                Ok(None) => {
+                    self.source = None;
+                    self.finished = true;
+                }
+                Err(e) if self.can_resume(&e) => {
+                    self.source = None;
+                    self.resumes += 1;
+                    tracing::warn!(
+                        "Query stream interrupted after {} rows, resuming (attempt {}): {}",
+                        self.rows_received,
+                        self.resumes,
+                        e
+                    );
+                }
+                Err(e) => {
+                    self.source = None;
+                    self.finished = true;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Get a handle to pause, resume or cancel the stream
+    pub fn control(&self) -> StreamControl {
+        self.control.clone()
+    }
+
+    /// Stop reading from the server once the buffered blocks are consumed
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    /// Continue reading from the server
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    /// Check whether the stream is paused
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    /// Get the number of rows received so far
+    pub fn rows_received(&self) -> u64 {
+        self.rows_received
+    }
+
+    /// Get the in-memory size of all blocks received so far
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Get the sizes of the data blocks received from the server, before chunking
+    pub fn block_sizes(&self) -> BlockSizes {
+        self.block_sizes
+    }
+
+    /// Get the number of blocks received but not yet returned
+    pub fn buffered_blocks(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Get the overflow blocks received so far
+    ///
+    /// The server sends them with the totals, after the regular data.
+    pub fn overflows(&self) -> &[Block] {
+        &self.overflows
+    }
+
+    /// Get the memory held by blocks received but not yet returned
+    pub fn buffered_memory(&self) -> usize {
+        self.pending.iter().map(|(_, reservation)| reservation.bytes()).sum()
+    }
+
+    /// Get the number of times the stream was resumed
+    pub fn resumes(&self) -> usize {
+        self.resumes
+    }
+
+    /// Get the cursor value of the last received row
+    pub fn last_cursor(&self) -> Option<&Value> {
+        self.last_cursor.as_ref()
+    }
+
+    /// Convert into a `futures::Stream` of blocks
+    pub fn into_stream(self) -> impl Stream<Item = Result<Block>> + 'a {
+        futures::stream::unfold(self, |mut stream| async move {
+            match stream.next_block().await {
+                Ok(Some(block)) => Some((Ok(block), stream)),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), stream)),
+            }
+        })
+    }
+
+    /// Fold every block into an accumulator without keeping the blocks
+    ///
+    /// Each block is dropped, and its memory released, before the next one is
+    /// read from the connection; only the chunks left of a block cut down to
+    /// the block size are held in the meantime.
+    pub async fn fold_blocks<B, F>(mut self, init: B, mut f: F) -> Result<B>
+    where
+        F: FnMut(B, Block) -> Result<B>,
+    {
+        let mut acc = init;
+        while let Some(block) = self.next_block().await? {
+            acc = f(acc, block)?;
+        }
+        Ok(acc)
+    }
+
+    /// Convert into a stream of values computed from each block
+    ///
+    /// A block is read only when the next value is polled, and dropped once
+    /// `f` returns.
+    pub fn map_blocks<T, F>(self, mut f: F) -> impl Stream<Item = Result<T>> + 'a
+    where
+        T: 'a,
+        F: FnMut(Block) -> Result<T> + 'a,
+    {
+        self.into_stream().map(move |block| block.and_then(&mut f))
+    }
+
+    /// Collect one column of every block into a vector
+    ///
+    /// Values are converted with `TryFrom<Value>`; NULLs fail the conversion
+    /// unless the target type accepts them. Only the converted values are
+    /// kept, as with [`QueryStream::fold_blocks`].
+    pub async fn collect_column<T>(self, name: &str) -> Result<Vec<T>>
+    where
+        T: TryFrom<Value>,
+        T::Error: Display,
+    {
+        self.fold_blocks(Vec::new(), |mut values, block| {
+            let column = block
+                .get_column(name)
+                .ok_or_else(|| Error::InvalidData(format!("Column '{}' is not in the query result", name)))?;
+            values.reserve(column.len());
+            for row in 0..column.len() {
+                let value = column.get_value(row).unwrap_or(Value::Null);
+                let value = T::try_from(value)
+                    .map_err(|e| Error::TypeConversion(format!("Column '{}', row {}: {}", name, row, e)))?;
+                values.push(value);
+            }
+            Ok(values)
+        })
+        .await
+    }
+
+    fn can_resume(&self, error: &Error) -> bool {
+        match &self.resume {
+            Some(resume) => self.resumes < resume.max_resumes && error.is_category(ErrorCategory::Network),
+            None => false,
+        }
+    }
+
+    /// Buffer a received block, cut into chunks of the block size
+    fn receive(&mut self, block: Block) {
+        let bytes = block.memory_usage();
+        self.bytes_received += bytes as u64;
+        self.largest_block = self.largest_block.max(bytes);
+        if block.is_overflows() {
+            self.pending.push_back((block, self.budget.reserve(bytes)));
+            return;
+        }
+        self.block_sizes.record(block.row_count as u64);
+        let max_rows = self.max_block_size.or_else(|| {
+            let row_width = bytes.checked_div(block.row_count)?;
+            Some(auto_block_size(row_width, self.block_memory_target?))
+        });
+        let chunks = match max_rows {
+            Some(max_rows) => chunk_block(block, max_rows as usize),
+            None => vec![block],
+        };
+        for chunk in chunks {
+            let reservation = self.budget.reserve(chunk.memory_usage());
+            self.pending.push_back((chunk, reservation));
+        }
+    }
+
+    fn track_cursor(&mut self, block: &Block) -> Result<()> {
+        let Some(resume) = &self.resume else {
+            return Ok(());
+        };
+        if block.row_count == 0 {
+            return Ok(());
+        }
+
+        let column = block.get_column(&resume.cursor_column).ok_or_else(|| {
+            Error::Configuration(format!("Cursor column '{}' is not in the query result", resume.cursor_column))
+        })?;
+        self.last_cursor = column.get_value(block.row_count - 1);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+    use crate::protocol::{EndReason, ProtocolWriter, ServerData, ServerEndOfStream};
+    use crate::types::{Column, ColumnData};
+    use futures::{FutureExt, TryStreamExt};
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn block(ids: Vec<u64>) -> Block {
+        Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(ids))])
+    }
+
+    /// Source handing out prepared blocks, then failing with `error` if one is given
+    struct Blocks {
+        blocks: VecDeque<Block>,
+        error: Option<Error>,
+    }
+
+    impl BlockSource for Blocks {
+        fn next_block(&mut self) -> BoxFuture<'_, Result<Option<Block>>> {
+            let next = match self.blocks.pop_front() {
+                Some(block) => Ok(Some(block)),
+                None => self.error.take().map_or(Ok(None), Err),
+            };
+            async move { next }.boxed()
+        }
+    }
+
+    fn source<'a>(blocks: Vec<Block>, error: Option<Error>) -> BoxFuture<'a, Result<Box<dyn BlockSource + 'a>>> {
+        let source: Box<dyn BlockSource> = Box::new(Blocks { blocks: blocks.into(), error });
+        async move { Ok(source) }.boxed()
+    }
+
+    fn reset() -> Error {
+        Error::Network(std::io::ErrorKind::ConnectionReset.into())
+    }
+
+    fn ids(block: &Block) -> Vec<u64> {
+        let column = block.get_column("id").unwrap();
+        (0..column.len()).map(|i| u64::try_from(column.get_value(i).unwrap()).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_resume_query_rewrite() {
+        let strategy = ResumeStrategy::new("id");
+        assert_eq!(
+            strategy.resume_query("SELECT id FROM t ORDER BY id;", &Value::UInt64(42)).unwrap(),
+            "SELECT * FROM (SELECT id FROM t ORDER BY id) WHERE `id` > 42 ORDER BY `id`"
+        );
+        assert_eq!(
+            strategy.resume_query("SELECT name FROM t", &Value::String("o'k".to_string())).unwrap(),
+            "SELECT * FROM (SELECT name FROM t) WHERE `id` > 'o\\'k' ORDER BY `id`"
+        );
+        assert!(strategy.resume_query("SELECT 1", &Value::Null).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_resumes_from_cursor() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let seen = queries.clone();
+        let mut calls = 0;
+
+        // The first run drops after two blocks; the retry continues after the last row delivered
+        let mut stream = QueryStream::new("SELECT id FROM t ORDER BY id", Some(ResumeStrategy::new("id")), move |sql| {
+            seen.lock().unwrap().push(sql);
+            calls += 1;
+            match calls {
+                1 => source(vec![block(vec![1, 2]), block(vec![3])], Some(reset())),
+                _ => source(vec![block(vec![4, 5])], None),
+            }
+        });
+
+        let mut received = Vec::new();
+        while let Some(block) = stream.next_block().await.unwrap() {
+            received.extend(ids(&block));
+        }
+        assert_eq!(received, [1, 2, 3, 4, 5]);
+        assert_eq!(stream.resumes(), 1);
+        assert_eq!(stream.last_cursor(), Some(&Value::UInt64(5)));
+        let queries = queries.lock().unwrap();
+        assert_eq!(queries.len(), 2);
+        assert!(queries[1].ends_with("WHERE `id` > 3 ORDER BY `id`"), "{}", queries[1]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_gives_up_after_max_resumes() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let seen = queries.clone();
+        let mut stream = QueryStream::new("SELECT id FROM t ORDER BY id", Some(ResumeStrategy::new("id")), move |sql| {
+            let mut seen = seen.lock().unwrap();
+            seen.push(sql);
+            match seen.len() {
+                1 => source(vec![block(vec![10])], Some(reset())),
+                _ => async { Err(Error::Network(std::io::ErrorKind::BrokenPipe.into())) }.boxed(),
+            }
+        });
+
+        assert_eq!(ids(&stream.next_block().await.unwrap().unwrap()), [10]);
+        assert!(stream.next_block().await.is_err());
+        assert_eq!(stream.resumes(), 3);
+        let queries = queries.lock().unwrap();
+        assert_eq!(queries.len(), 4);
+        assert!(queries[1..].iter().all(|sql| sql.ends_with("WHERE `id` > 10 ORDER BY `id`")));
+    }
+
+    /// Connection whose server answers the query with blocks of `ids`
+    ///
+    /// Without `end` the server hangs up after the data instead of ending the stream.
+    async fn serve(sql: String, ids: Vec<u64>, end: bool) -> Result<Box<dyn BlockSource>> {
+        let mut response = Vec::new();
+        let mut writer = ProtocolWriter::new(&mut response);
+        writer.write_packet(&ServerData::new(block(ids))).unwrap();
+        if end {
+            writer.write_packet(&ServerEndOfStream::new(EndReason::Normal)).unwrap();
+        }
+
+        let (client, mut server) = tokio::io::duplex(1 << 16);
+        tokio::spawn(async move {
+            let mut header = [0u8; 16];
+            server.read_exact(&mut header).await.unwrap();
+            let mut query = vec![0u8; u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize];
+            server.read_exact(&mut query).await.unwrap();
+            server.write_all(&response).await.unwrap();
+            if end {
+                let _ = server.read_to_end(&mut Vec::new()).await;
+            }
+        });
+
+        let mut connection = Connection::new(ClientOptions::new()).with_transport(client);
+        connection.send_query(&sql, &QuerySettings::default()).await?;
+        Ok(Box::new(connection))
+    }
+
+    #[tokio::test]
+    async fn test_stream_resumes_over_connection() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let seen = queries.clone();
+        let stream = QueryStream::new("SELECT id FROM t ORDER BY id", Some(ResumeStrategy::new("id")), move |sql| {
+            let mut seen = seen.lock().unwrap();
+            seen.push(sql.clone());
+            match seen.len() {
+                1 => serve(sql, vec![1, 2], false).boxed(),
+                _ => serve(sql, vec![3], true).boxed(),
+            }
+        });
+
+        let received = stream.fold_blocks(Vec::new(), |mut received, block| {
+            received.extend(ids(&block));
+            Ok(received)
+        });
+        assert_eq!(received.await.unwrap(), [1, 2, 3]);
+        assert!(queries.lock().unwrap()[1].ends_with("WHERE `id` > 2 ORDER BY `id`"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_stream_is_kept_alive() {
+        use crate::protocol::{Framing, ServerPong};
+
+        let (client, mut server) = tokio::io::duplex(1 << 16);
+        let server = tokio::spawn(async move {
+            let mut header = [0u8; 16];
+            server.read_exact(&mut header).await.unwrap();
+            let mut query = vec![0u8; u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize];
+            server.read_exact(&mut query).await.unwrap();
+
+            // The query takes a while, so the client pings before any data arrives
+            let mut ping = vec![0u8; Framing::Headers.ping().len()];
+            server.read_exact(&mut ping).await.unwrap();
+            assert_eq!(ping, Framing::Headers.ping());
+            let mut response = Vec::new();
+            let mut writer = ProtocolWriter::new(&mut response);
+            writer.write_packet(&ServerPong::new(0, 0, "24.8", "node-1")).unwrap();
+            writer.write_packet(&ServerData::new(block(vec![1]))).unwrap();
+            writer.write_packet(&ServerEndOfStream::new(EndReason::Normal)).unwrap();
+            server.write_all(&response).await.unwrap();
+            server
+        });
+
+        let options = ClientOptions::new().keepalive_ping_interval(std::time::Duration::from_millis(20));
+        let mut connection = Connection::new(options).with_transport(client);
+        connection.send_query("SELECT id FROM t", &QuerySettings::default()).await.unwrap();
+        let mut connection = Some(connection);
+        let mut stream = QueryStream::new("SELECT id FROM t", None, move |_| {
+            let source: Box<dyn BlockSource> = Box::new(connection.take().unwrap());
+            async move { Ok(source) }.boxed()
+        });
+
+        // The pong is consumed by the connection and never reaches the stream
+        assert_eq!(ids(&stream.next_block().await.unwrap().unwrap()), [1]);
+        assert!(stream.next_block().await.unwrap().is_none());
+        let _server = server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_keeps_overflows_out_of_data() {
+        use crate::types::BlockInfo;
+
+        let mut stream = QueryStream::new("SELECT id FROM t", None, |_| {
+            let overflow = block(vec![1, 2]).with_info(BlockInfo {
+                is_overflows: true,
+                ..BlockInfo::default()
+            });
+            source(vec![block(vec![1, 2]), overflow], None)
+        });
+
+        assert_eq!(stream.next_block().await.unwrap().unwrap().row_count, 2);
+        assert!(stream.next_block().await.unwrap().is_none());
+        assert_eq!(stream.overflows().len(), 1);
+        assert_eq!(stream.rows_received(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stream_chunks_blocks_to_block_size() {
+        let pages = || QueryStream::new("SELECT id FROM t", None, |_| source(vec![block((0..5_000).collect())], None));
+
+        let mut stream = pages().with_block_size(&QuerySettings::new().max_block_size(2_000));
+        let mut rows = Vec::new();
+        while let Some(block) = stream.next_block().await.unwrap() {
+            rows.push(block.row_count);
+        }
+        assert_eq!(rows, [2_000, 2_000, 1_000]);
+        assert_eq!(stream.block_sizes(), BlockSizes { blocks: 1, rows: 5_000, min_rows: 5_000, max_rows: 5_000 });
+        assert_eq!(stream.rows_received(), 5_000);
+
+        // 8 bytes per row, so a 16 KiB target allows 2048 rows per block
+        let mut stream = pages().with_block_size(&QuerySettings::new().auto_block_size(16 * 1024));
+        assert_eq!(stream.next_block().await.unwrap().unwrap().row_count, 2_048);
+        assert_eq!(stream.buffered_blocks(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stream_without_resume_fails_fast() {
+        let stream = QueryStream::new("SELECT 1", None, |_| source(vec![block(vec![1])], Some(reset())));
+
+        let items: Vec<_> = stream.into_stream().collect().await;
+        assert_eq!(items.len(), 2);
+        assert!(items[0].is_ok());
+        assert!(items[1].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_block_combinators() {
+        let pages = || QueryStream::new("SELECT id FROM t", None, |_| source(vec![block(vec![1, 2]), block(vec![3])], None));
+
+        let sum = pages()
+            .fold_blocks(0u64, |sum, block| Ok(sum + ids(&block).iter().sum::<u64>()))
+            .await
+            .unwrap();
+        assert_eq!(sum, 6);
+
+        let sizes: Vec<usize> = pages().map_blocks(|block| Ok(block.row_count)).try_collect().await.unwrap();
+        assert_eq!(sizes, [2, 1]);
+
+        assert_eq!(pages().collect_column::<u32>("id").await.unwrap(), [1, 2, 3]);
+        assert!(matches!(pages().collect_column::<String>("id").await, Err(Error::TypeConversion(_))));
+        assert!(matches!(pages().collect_column::<u64>("name").await, Err(Error::InvalidData(_))));
+    }
+
+    #[tokio::test]
+    async fn test_stream_charges_buffered_blocks_to_budget() {
+        let budget = MemoryBudget::new(Some(1));
+        let settings = QuerySettings::new().max_block_size(1);
+        let mut first = QueryStream::new("SELECT id FROM t", None, |_| source(vec![block(vec![1, 2])], None))
+            .with_block_size(&settings)
+            .with_budget(budget.clone());
+
+        assert!(first.next_block().await.unwrap().is_some());
+        assert!(first.buffered_memory() > 0);
+        assert_eq!(budget.usage(), first.buffered_memory());
+
+        // A second stream waits until the first hands out its buffered chunk
+        let mut second = QueryStream::new("SELECT id FROM t", None, |_| source(vec![block(vec![3])], None))
+            .with_budget(budget.clone());
+        assert!(second.next_block().now_or_never().is_none());
+
+        assert!(first.next_block().await.unwrap().is_some());
+        assert_eq!(budget.usage(), 0);
+        assert!(second.next_block().await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_unchunked_streams_share_budget_in_one_task() {
+        let budget = MemoryBudget::new(Some(1));
+        let stream = || {
+            QueryStream::new("SELECT id FROM t", None, |_| source(vec![block(vec![1, 2]), block(vec![3])], None))
+                .with_budget(budget.clone())
+        };
+        let (mut first, mut second) = (stream(), stream());
+
+        // Neither stream holds a reservation between calls, so polling them in turn never waits
+        for _ in 0..2 {
+            assert!(first.next_block().now_or_never().unwrap().unwrap().is_some());
+            assert!(second.next_block().now_or_never().unwrap().unwrap().is_some());
+            assert_eq!(budget.usage(), 0);
+        }
+        assert!(first.next_block().await.unwrap().is_none());
+
+        // A stream waits before reading while the budget is held elsewhere
+        let held = budget.reserve(1);
+        assert!(second.next_block().now_or_never().is_none());
+        drop(held);
+        assert!(second.next_block().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_pause_resume_cancel() {
+        let opened = Arc::new(Mutex::new(0));
+        let counter = opened.clone();
+        let stream = || {
+            let counter = counter.clone();
+            QueryStream::new("SELECT id FROM t", None, move |_| {
+                *counter.lock().unwrap() += 1;
+                source(vec![block(vec![1, 2]), block(vec![3])], None)
+            })
+            .with_block_size(&QuerySettings::new().max_block_size(1))
+        };
+
+        // Nothing is read from the server while paused
+        let mut paused = stream();
+        let control = paused.control();
+        control.pause();
+        assert!(paused.next_block().now_or_never().is_none());
+        assert_eq!(*opened.lock().unwrap(), 0);
+
+        control.resume();
+        assert_eq!(paused.next_block().await.unwrap().unwrap().row_count, 1);
+        assert_eq!(paused.buffered_blocks(), 1);
+        assert!(paused.bytes_received() > 0);
+
+        // Buffered blocks are still handed out while paused
+        paused.pause();
+        assert!(paused.is_paused());
+        assert_eq!(paused.next_block().await.unwrap().unwrap().row_count, 1);
+        assert_eq!(paused.buffered_blocks(), 0);
+        assert!(paused.next_block().now_or_never().is_none());
+
+        // The unread block is still waiting in the source
+        control.resume();
+        assert_eq!(ids(&paused.next_block().await.unwrap().unwrap()), [3]);
+        assert_eq!(*opened.lock().unwrap(), 1);
+
+        let mut cancelled = stream();
+        assert!(cancelled.next_block().await.unwrap().is_some());
+        cancelled.control().cancel();
+        assert!(cancelled.next_block().await.unwrap().is_none());
+        assert_eq!(cancelled.buffered_memory(), 0);
+    }
+}
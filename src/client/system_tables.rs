@@ -0,0 +1,945 @@
+//! Typed accessors for frequently used system tables
+//!
+//! Each struct lists the columns it reads, so the generated SELECT only pulls
+//! what it needs and works across server versions that add new columns.
+
+use crate::client::{Client, KafkaConsumerInfo, QueryResult};
+use crate::error::{Error, Result};
+use crate::types::{Block, CodecRegistry, FromValue, Histogram, Quantiles, Value};
+use chrono::NaiveDateTime;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// A row type backed by a system table
+pub trait SystemTableRow: Sized {
+    /// Fully qualified table name
+    const TABLE: &'static str;
+    /// Columns read from the table
+    const COLUMNS: &'static [&'static str];
+
+    /// Build the row from a result row
+    fn from_row(row: &RowReader<'_>) -> Result<Self>;
+}
+
+/// Column access by name for a single row of a block
+pub struct RowReader<'a> {
+    block: &'a Block,
+    index: usize,
+    codecs: Option<&'a CodecRegistry>,
+}
+
+impl<'a> RowReader<'a> {
+    /// Create a reader for a row of a block
+    pub fn new(block: &'a Block, index: usize) -> Self {
+        Self {
+            block,
+            index,
+            codecs: None,
+        }
+    }
+
+    /// Use registered codecs for [`RowReader::decode`]
+    pub fn with_codecs(mut self, codecs: &'a CodecRegistry) -> Self {
+        self.codecs = Some(codecs);
+        self
+    }
+
+    /// Get the raw value of a column
+    pub fn value(&self, column: &str) -> Result<Value> {
+        self.block
+            .get_column(column)
+            .ok_or_else(|| Error::InvalidData(format!("Column '{}' is missing from the result", column)))?
+            .get_value(self.index)
+            .ok_or_else(|| Error::InvalidData(format!("Row {} is out of range", self.index)))
+    }
+
+    /// Read a string column
+    pub fn string(&self, column: &str) -> Result<String> {
+        match self.value(column)? {
+            Value::String(s) => Ok(s),
+            Value::FixedString(s) => Ok(s.to_string()),
+            Value::LowCardinality(lc) => Ok(lc.get(0).cloned().unwrap_or_default()),
+            other => Err(mismatch(column, "String", &other)),
+        }
+    }
+
+    /// Read an unsigned integer column
+    pub fn u64(&self, column: &str) -> Result<u64> {
+        let value = self.value(column)?;
+        let converted = match &value {
+            Value::UInt8(v) => Some(*v as u64),
+            Value::UInt16(v) => Some(*v as u64),
+            Value::UInt32(v) => Some(*v as u64),
+            Value::UInt64(v) => Some(*v),
+            Value::Int8(v) => u64::try_from(*v).ok(),
+            Value::Int16(v) => u64::try_from(*v).ok(),
+            Value::Int32(v) => u64::try_from(*v).ok(),
+            Value::Int64(v) => u64::try_from(*v).ok(),
+            _ => None,
+        };
+        converted.ok_or_else(|| mismatch(column, "UInt64", &value))
+    }
+
+    /// Read a signed integer column
+    pub fn i64(&self, column: &str) -> Result<i64> {
+        let value = self.value(column)?;
+        let converted = match &value {
+            Value::Int8(v) => Some(*v as i64),
+            Value::Int16(v) => Some(*v as i64),
+            Value::Int32(v) => Some(*v as i64),
+            Value::Int64(v) => Some(*v),
+            Value::UInt8(v) => Some(*v as i64),
+            Value::UInt16(v) => Some(*v as i64),
+            Value::UInt32(v) => Some(*v as i64),
+            Value::UInt64(v) => i64::try_from(*v).ok(),
+            _ => None,
+        };
+        converted.ok_or_else(|| mismatch(column, "Int64", &value))
+    }
+
+    /// Read a floating point column
+    pub fn f64(&self, column: &str) -> Result<f64> {
+        match self.value(column)? {
+            Value::Float32(v) => Ok(v as f64),
+            Value::Float64(v) => Ok(v),
+            other => Err(mismatch(column, "Float64", &other)),
+        }
+    }
+
+    /// Read a `UInt8` flag column
+    pub fn bool(&self, column: &str) -> Result<bool> {
+        Ok(self.u64(column)? != 0)
+    }
+
+    /// Read an `Array` column
+    pub fn array(&self, column: &str) -> Result<Vec<Value>> {
+        match self.value(column)? {
+            Value::Array(values) => Ok(values),
+            other => Err(mismatch(column, "Array", &other)),
+        }
+    }
+
+    /// Read a `quantiles(...)` column computed for `levels`
+    pub fn quantiles(&self, column: &str, levels: &[f64]) -> Result<Quantiles> {
+        Quantiles::from_value(levels, self.value(column)?).map_err(Error::TypeConversion)
+    }
+
+    /// Read a `histogram(...)` column
+    pub fn histogram(&self, column: &str) -> Result<Histogram> {
+        Histogram::try_from(self.value(column)?).map_err(Error::TypeConversion)
+    }
+
+    /// Read a `DateTime` column
+    pub fn datetime(&self, column: &str) -> Result<NaiveDateTime> {
+        match self.value(column)? {
+            Value::DateTime(dt) | Value::DateTime64(dt) => Ok(dt),
+            other => Err(mismatch(column, "DateTime", &other)),
+        }
+    }
+
+    /// Read a column into any type that converts from [`Value`]
+    pub fn get<T: FromValue>(&self, column: &str) -> Result<T> {
+        T::from_value(self.value(column)?).map_err(|e| Error::TypeConversion(format!("Column '{}': {}", column, e)))
+    }
+
+    /// Read a column into a type with a codec in the attached registry
+    pub fn decode<T: 'static>(&self, column: &str) -> Result<T> {
+        let codecs = self
+            .codecs
+            .ok_or_else(|| Error::Configuration("Row reader has no codec registry".to_string()))?;
+        codecs
+            .decode(self.value(column)?)
+            .map_err(|e| Error::TypeConversion(format!("Column '{}': {}", column, e)))
+    }
+}
+
+fn mismatch(column: &str, expected: &str, value: &Value) -> Error {
+    Error::TypeConversion(format!(
+        "Column '{}' has type {}, expected {}",
+        column,
+        value.type_name(),
+        expected
+    ))
+}
+
+/// Convert every row of a result into a typed row
+pub fn rows_from_result<T: SystemTableRow>(result: &QueryResult) -> Result<Vec<T>> {
+    let mut rows = Vec::with_capacity(result.row_count());
+    for block in &result.blocks {
+        for index in 0..block.row_count {
+            rows.push(T::from_row(&RowReader::new(block, index))?);
+        }
+    }
+    Ok(rows)
+}
+
+/// Data part from `system.parts`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartInfo {
+    /// Database name
+    pub database: String,
+    /// Table name
+    pub table: String,
+    /// Partition ID expression
+    pub partition: String,
+    /// Part name
+    pub name: String,
+    /// Whether the part is used by queries
+    pub active: bool,
+    /// Number of rows
+    pub rows: u64,
+    /// Size of all part files in bytes
+    pub bytes_on_disk: u64,
+    /// Time the part directory was last modified
+    pub modification_time: NaiveDateTime,
+}
+
+impl SystemTableRow for PartInfo {
+    const TABLE: &'static str = "system.parts";
+    const COLUMNS: &'static [&'static str] = &[
+        "database",
+        "table",
+        "partition",
+        "name",
+        "active",
+        "rows",
+        "bytes_on_disk",
+        "modification_time",
+    ];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        Ok(Self {
+            database: row.string("database")?,
+            table: row.string("table")?,
+            partition: row.string("partition")?,
+            name: row.string("name")?,
+            active: row.bool("active")?,
+            rows: row.u64("rows")?,
+            bytes_on_disk: row.u64("bytes_on_disk")?,
+            modification_time: row.datetime("modification_time")?,
+        })
+    }
+}
+
+/// Running merge from `system.merges`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeInfo {
+    /// Database name
+    pub database: String,
+    /// Table name
+    pub table: String,
+    /// Seconds since the start
+    pub elapsed: f64,
+    /// Fraction of work done, from 0 to 1
+    pub progress: f64,
+    /// Number of parts being merged
+    pub num_parts: u64,
+    /// Name of the part produced by the merge
+    pub result_part_name: String,
+    /// Compressed size of the merged parts
+    pub total_size_bytes_compressed: u64,
+    /// Memory used in bytes
+    pub memory_usage: u64,
+}
+
+impl MergeInfo {
+    /// Estimate the seconds left from the elapsed time and progress
+    pub fn estimated_remaining(&self) -> Option<f64> {
+        (self.progress > 0.0).then(|| self.elapsed * (1.0 - self.progress) / self.progress)
+    }
+}
+
+impl SystemTableRow for MergeInfo {
+    const TABLE: &'static str = "system.merges";
+    const COLUMNS: &'static [&'static str] = &[
+        "database",
+        "table",
+        "elapsed",
+        "progress",
+        "num_parts",
+        "result_part_name",
+        "total_size_bytes_compressed",
+        "memory_usage",
+    ];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        Ok(Self {
+            database: row.string("database")?,
+            table: row.string("table")?,
+            elapsed: row.f64("elapsed")?,
+            progress: row.f64("progress")?,
+            num_parts: row.u64("num_parts")?,
+            result_part_name: row.string("result_part_name")?,
+            total_size_bytes_compressed: row.u64("total_size_bytes_compressed")?,
+            memory_usage: row.u64("memory_usage")?,
+        })
+    }
+}
+
+/// Replica state from `system.replicas`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicaInfo {
+    /// Database name
+    pub database: String,
+    /// Table name
+    pub table: String,
+    /// Replica name in ZooKeeper
+    pub replica_name: String,
+    /// Whether the replica is a leader
+    pub is_leader: bool,
+    /// Whether the replica is in read-only mode
+    pub is_readonly: bool,
+    /// Whether the ZooKeeper session expired
+    pub is_session_expired: bool,
+    /// Size of the replication queue
+    pub queue_size: u64,
+    /// Inserts waiting to be fetched
+    pub inserts_in_queue: u64,
+    /// Merges waiting to be made
+    pub merges_in_queue: u64,
+    /// Replication lag in seconds
+    pub absolute_delay: u64,
+    /// Number of known replicas
+    pub total_replicas: u64,
+    /// Number of replicas with a live session
+    pub active_replicas: u64,
+}
+
+impl ReplicaInfo {
+    /// Check if the replica is writable, connected and has all peers active
+    pub fn is_healthy(&self) -> bool {
+        !self.is_readonly && !self.is_session_expired && self.active_replicas == self.total_replicas
+    }
+}
+
+impl SystemTableRow for ReplicaInfo {
+    const TABLE: &'static str = "system.replicas";
+    const COLUMNS: &'static [&'static str] = &[
+        "database",
+        "table",
+        "replica_name",
+        "is_leader",
+        "is_readonly",
+        "is_session_expired",
+        "queue_size",
+        "inserts_in_queue",
+        "merges_in_queue",
+        "absolute_delay",
+        "total_replicas",
+        "active_replicas",
+    ];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        Ok(Self {
+            database: row.string("database")?,
+            table: row.string("table")?,
+            replica_name: row.string("replica_name")?,
+            is_leader: row.bool("is_leader")?,
+            is_readonly: row.bool("is_readonly")?,
+            is_session_expired: row.bool("is_session_expired")?,
+            queue_size: row.u64("queue_size")?,
+            inserts_in_queue: row.u64("inserts_in_queue")?,
+            merges_in_queue: row.u64("merges_in_queue")?,
+            absolute_delay: row.u64("absolute_delay")?,
+            total_replicas: row.u64("total_replicas")?,
+            active_replicas: row.u64("active_replicas")?,
+        })
+    }
+}
+
+/// Running query from `system.processes`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessInfo {
+    /// Query ID
+    pub query_id: String,
+    /// User that ran the query
+    pub user: String,
+    /// Query text
+    pub query: String,
+    /// Seconds since the start
+    pub elapsed: f64,
+    /// Rows read so far
+    pub read_rows: u64,
+    /// Bytes read so far
+    pub read_bytes: u64,
+    /// Memory used in bytes
+    pub memory_usage: i64,
+    /// Whether the query came from a client rather than another server
+    pub is_initial_query: bool,
+}
+
+impl SystemTableRow for ProcessInfo {
+    const TABLE: &'static str = "system.processes";
+    const COLUMNS: &'static [&'static str] = &[
+        "query_id",
+        "user",
+        "query",
+        "elapsed",
+        "read_rows",
+        "read_bytes",
+        "memory_usage",
+        "is_initial_query",
+    ];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        Ok(Self {
+            query_id: row.string("query_id")?,
+            user: row.string("user")?,
+            query: row.string("query")?,
+            elapsed: row.f64("elapsed")?,
+            read_rows: row.u64("read_rows")?,
+            read_bytes: row.u64("read_bytes")?,
+            memory_usage: row.i64("memory_usage")?,
+            is_initial_query: row.bool("is_initial_query")?,
+        })
+    }
+}
+
+/// Finished query from `system.query_log`
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryLogEntry {
+    /// Query ID
+    pub query_id: String,
+    /// User that ran the query
+    pub user: String,
+    /// Query text
+    pub query: String,
+    /// When the query finished
+    pub event_time: NaiveDateTime,
+    /// Duration in milliseconds
+    pub query_duration_ms: u64,
+    /// Rows read
+    pub read_rows: u64,
+    /// Bytes read
+    pub read_bytes: u64,
+    /// Rows in the result
+    pub result_rows: u64,
+    /// Peak memory used in bytes
+    pub memory_usage: u64,
+    /// Tags parsed from the JSON `log_comment`, empty for untagged queries
+    pub tags: BTreeMap<String, String>,
+}
+
+impl SystemTableRow for QueryLogEntry {
+    const TABLE: &'static str = "system.query_log";
+    const COLUMNS: &'static [&'static str] = &[
+        "query_id",
+        "user",
+        "query",
+        "event_time",
+        "query_duration_ms",
+        "read_rows",
+        "read_bytes",
+        "result_rows",
+        "memory_usage",
+        "log_comment",
+    ];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        // Comments not written by `QuerySettings::tag` carry no tags
+        let tags = serde_json::from_str(&row.string("log_comment")?).unwrap_or_default();
+        Ok(Self {
+            query_id: row.string("query_id")?,
+            user: row.string("user")?,
+            query: row.string("query")?,
+            event_time: row.datetime("event_time")?,
+            query_duration_ms: row.u64("query_duration_ms")?,
+            read_rows: row.u64("read_rows")?,
+            read_bytes: row.u64("read_bytes")?,
+            result_rows: row.u64("result_rows")?,
+            memory_usage: row.u64("memory_usage")?,
+            tags,
+        })
+    }
+}
+
+/// Engine, storage policy and TTL of a table from `system.tables`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStorage {
+    /// Database name
+    pub database: String,
+    /// Table name
+    pub name: String,
+    /// Engine name
+    pub engine: String,
+    /// Full engine clause, including PARTITION BY, ORDER BY, TTL and SETTINGS
+    pub engine_full: String,
+    /// Storage policy, empty for tables without one
+    pub storage_policy: String,
+}
+
+impl TableStorage {
+    /// Get the table TTL expression from the engine clause, if the table has one
+    pub fn ttl(&self) -> Option<&str> {
+        let (_, rest) = self.engine_full.split_once(" TTL ")?;
+        let ttl = rest.split_once(" SETTINGS ").map_or(rest, |(ttl, _)| ttl).trim();
+        (!ttl.is_empty()).then_some(ttl)
+    }
+}
+
+impl SystemTableRow for TableStorage {
+    const TABLE: &'static str = "system.tables";
+    const COLUMNS: &'static [&'static str] = &["database", "name", "engine", "engine_full", "storage_policy"];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        Ok(Self {
+            database: row.string("database")?,
+            name: row.string("name")?,
+            engine: row.string("engine")?,
+            engine_full: row.string("engine_full")?,
+            storage_policy: row.string("storage_policy")?,
+        })
+    }
+}
+
+/// Volume of a storage policy from `system.storage_policies`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoragePolicyVolume {
+    /// Storage policy name
+    pub policy_name: String,
+    /// Volume name
+    pub volume_name: String,
+    /// Position of the volume in the policy, starting at 1
+    pub volume_priority: u64,
+    /// Disks of the volume
+    pub disks: Vec<String>,
+    /// Largest part stored on the volume in bytes, 0 for no limit
+    pub max_data_part_size: u64,
+    /// Share of free space below which parts move to the next volume
+    pub move_factor: f64,
+}
+
+impl SystemTableRow for StoragePolicyVolume {
+    const TABLE: &'static str = "system.storage_policies";
+    const COLUMNS: &'static [&'static str] = &[
+        "policy_name",
+        "volume_name",
+        "volume_priority",
+        "disks",
+        "max_data_part_size",
+        "move_factor",
+    ];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        let disks = row
+            .array("disks")?
+            .into_iter()
+            .map(String::from_value)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| Error::TypeConversion(format!("Column 'disks': {}", e)))?;
+        Ok(Self {
+            policy_name: row.string("policy_name")?,
+            volume_name: row.string("volume_name")?,
+            volume_priority: row.u64("volume_priority")?,
+            disks,
+            max_data_part_size: row.u64("max_data_part_size")?,
+            move_factor: row.f64("move_factor")?,
+        })
+    }
+}
+
+/// Disk from `system.disks`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskInfo {
+    /// Disk name
+    pub name: String,
+    /// Mount path
+    pub path: String,
+    /// Disk type, such as `Local` or `S3`
+    pub disk_type: String,
+    /// Free space in bytes
+    pub free_space: u64,
+    /// Total space in bytes
+    pub total_space: u64,
+    /// Space kept free in bytes
+    pub keep_free_space: u64,
+}
+
+impl DiskInfo {
+    /// Get the share of the disk in use, from 0 to 1
+    pub fn used_fraction(&self) -> f64 {
+        if self.total_space == 0 {
+            return 0.0;
+        }
+        1.0 - self.free_space as f64 / self.total_space as f64
+    }
+}
+
+impl SystemTableRow for DiskInfo {
+    const TABLE: &'static str = "system.disks";
+    const COLUMNS: &'static [&'static str] = &["name", "path", "type", "free_space", "total_space", "keep_free_space"];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        Ok(Self {
+            name: row.string("name")?,
+            path: row.string("path")?,
+            disk_type: row.string("type")?,
+            free_space: row.u64("free_space")?,
+            total_space: row.u64("total_space")?,
+            keep_free_space: row.u64("keep_free_space")?,
+        })
+    }
+}
+
+/// On-disk size and compression of a table column from `system.columns`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStorage {
+    /// Database name
+    pub database: String,
+    /// Table name
+    pub table: String,
+    /// Column name
+    pub name: String,
+    /// Column type
+    pub column_type: String,
+    /// Position of the column in the table, starting at 1
+    pub position: u64,
+    /// Codec declared for the column, empty for the table default
+    pub compression_codec: String,
+    /// Compressed size of the column data in active parts
+    pub data_compressed_bytes: u64,
+    /// Uncompressed size of the column data in active parts
+    pub data_uncompressed_bytes: u64,
+    /// Size of the column's marks
+    pub marks_bytes: u64,
+}
+
+impl ColumnStorage {
+    /// Get the bytes the column takes on disk, data and marks
+    pub fn bytes_on_disk(&self) -> u64 {
+        self.data_compressed_bytes + self.marks_bytes
+    }
+
+    /// Get the uncompressed to compressed size ratio, if the column has data
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.data_compressed_bytes > 0)
+            .then(|| self.data_uncompressed_bytes as f64 / self.data_compressed_bytes as f64)
+    }
+}
+
+impl SystemTableRow for ColumnStorage {
+    const TABLE: &'static str = "system.columns";
+    const COLUMNS: &'static [&'static str] = &[
+        "database",
+        "table",
+        "name",
+        "type",
+        "position",
+        "compression_codec",
+        "data_compressed_bytes",
+        "data_uncompressed_bytes",
+        "marks_bytes",
+    ];
+
+    fn from_row(row: &RowReader<'_>) -> Result<Self> {
+        Ok(Self {
+            database: row.string("database")?,
+            table: row.string("table")?,
+            name: row.string("name")?,
+            column_type: row.string("type")?,
+            position: row.u64("position")?,
+            compression_codec: row.string("compression_codec")?,
+            data_compressed_bytes: row.u64("data_compressed_bytes")?,
+            data_uncompressed_bytes: row.u64("data_uncompressed_bytes")?,
+            marks_bytes: row.u64("marks_bytes")?,
+        })
+    }
+}
+
+/// Typed system table queries bound to a client
+pub struct SystemTables<'a> {
+    client: &'a Client,
+}
+
+impl<'a> SystemTables<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Self { client }
+    }
+
+    /// Read all rows of a system table, optionally filtered by a WHERE clause
+    pub async fn fetch<T: SystemTableRow>(&self, filter: Option<&str>) -> Result<Vec<T>> {
+        let result = self.client.query(&select_sql::<T>(filter)).await?;
+        rows_from_result(&result)
+    }
+
+    /// Get the active parts of a table
+    pub async fn parts(&self, database: &str, table: &str) -> Result<Vec<PartInfo>> {
+        let filter = format!("active AND {}", table_filter(database, table));
+        self.fetch(Some(&filter)).await
+    }
+
+    /// Get all running merges
+    pub async fn merges(&self) -> Result<Vec<MergeInfo>> {
+        self.fetch(None).await
+    }
+
+    /// Get the state of all replicated tables
+    pub async fn replicas(&self) -> Result<Vec<ReplicaInfo>> {
+        self.fetch(None).await
+    }
+
+    /// Get all running queries
+    pub async fn processes(&self) -> Result<Vec<ProcessInfo>> {
+        self.fetch(None).await
+    }
+
+    /// Get the consumers of a Kafka engine table
+    pub async fn kafka_consumers(&self, database: &str, table: &str) -> Result<Vec<KafkaConsumerInfo>> {
+        self.fetch(Some(&table_filter(database, table))).await
+    }
+
+    /// Get the engine, storage policy and TTL of a table
+    pub async fn table_storage(&self, database: &str, table: &str) -> Result<Option<TableStorage>> {
+        let filter = format!("database = {} AND name = {}", quote(database), quote(table));
+        Ok(self.fetch(Some(&filter)).await?.into_iter().next())
+    }
+
+    /// Get the volumes of all storage policies, in policy and priority order
+    pub async fn storage_policies(&self) -> Result<Vec<StoragePolicyVolume>> {
+        let mut volumes: Vec<StoragePolicyVolume> = self.fetch(None).await?;
+        volumes.sort_by(|a, b| (&a.policy_name, a.volume_priority).cmp(&(&b.policy_name, b.volume_priority)));
+        Ok(volumes)
+    }
+
+    /// Get the on-disk size and compression of each column of a table, in table order
+    pub async fn column_storage(&self, database: &str, table: &str) -> Result<Vec<ColumnStorage>> {
+        let mut columns: Vec<ColumnStorage> = self.fetch(Some(&table_filter(database, table))).await?;
+        columns.sort_by_key(|column| column.position);
+        Ok(columns)
+    }
+
+    /// Get all disks
+    pub async fn disks(&self) -> Result<Vec<DiskInfo>> {
+        self.fetch(None).await
+    }
+
+    /// Get the queries finished in the last `since` that carry all of `tags`
+    ///
+    /// Tags are set with [`QuerySettings::tag`](crate::client::QuerySettings::tag).
+    /// Summing the resources of the entries attributes cost per feature or team.
+    pub async fn query_log(&self, tags: &[(&str, &str)], since: Duration) -> Result<Vec<QueryLogEntry>> {
+        self.fetch(Some(&query_log_filter(tags, since))).await
+    }
+}
+
+/// Build the `system.query_log` filter for finished queries carrying `tags`
+pub fn query_log_filter(tags: &[(&str, &str)], since: Duration) -> String {
+    let mut filter = format!(
+        "type = 'QueryFinish' AND event_time >= now() - INTERVAL {} SECOND",
+        since.as_secs().max(1)
+    );
+    for (key, value) in tags {
+        filter.push_str(&format!(
+            " AND JSONExtractString(log_comment, {}) = {}",
+            quote(key),
+            quote(value)
+        ));
+    }
+    filter
+}
+
+/// Build the SELECT for a system table row type
+pub fn select_sql<T: SystemTableRow>(filter: Option<&str>) -> String {
+    let mut sql = format!("SELECT {} FROM {}", T::COLUMNS.join(", "), T::TABLE);
+    if let Some(filter) = filter {
+        sql.push_str(" WHERE ");
+        sql.push_str(filter);
+    }
+    sql
+}
+
+pub(crate) fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn table_filter(database: &str, table: &str) -> String {
+    format!("database = {} AND table = {}", quote(database), quote(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{QueryMetadata, QueryStats};
+    use crate::types::{Column, ColumnData};
+    use std::time::Duration;
+
+    fn replicas_result() -> QueryResult {
+        let mut block = Block::new();
+        let strings = |v: &[&str]| ColumnData::String(v.iter().map(|s| s.to_string()).collect());
+        let flags = |v: Vec<u8>| ColumnData::UInt8(v);
+        let counts = |v: Vec<u64>| ColumnData::UInt64(v);
+        block.add_column("database", Column::new("database", "String", strings(&["db", "db"])));
+        block.add_column("table", Column::new("table", "String", strings(&["events", "users"])));
+        block.add_column("replica_name", Column::new("replica_name", "String", strings(&["r1", "r1"])));
+        block.add_column("is_leader", Column::new("is_leader", "UInt8", flags(vec![1, 0])));
+        block.add_column("is_readonly", Column::new("is_readonly", "UInt8", flags(vec![0, 1])));
+        block.add_column("is_session_expired", Column::new("is_session_expired", "UInt8", flags(vec![0, 0])));
+        block.add_column("queue_size", Column::new("queue_size", "UInt32", ColumnData::UInt32(vec![3, 0])));
+        block.add_column("inserts_in_queue", Column::new("inserts_in_queue", "UInt32", ColumnData::UInt32(vec![1, 0])));
+        block.add_column("merges_in_queue", Column::new("merges_in_queue", "UInt32", ColumnData::UInt32(vec![2, 0])));
+        block.add_column("absolute_delay", Column::new("absolute_delay", "UInt64", counts(vec![5, 0])));
+        block.add_column("total_replicas", Column::new("total_replicas", "UInt8", flags(vec![2, 2])));
+        block.add_column("active_replicas", Column::new("active_replicas", "UInt8", flags(vec![2, 1])));
+        QueryResult::new(
+            QueryMetadata::new(ReplicaInfo::COLUMNS.iter().map(|c| c.to_string()).collect(), Vec::new()),
+            vec![block],
+            QueryStats::new(0, 0, Duration::ZERO),
+        )
+    }
+
+    #[test]
+    fn test_replicas_from_result() {
+        let replicas: Vec<ReplicaInfo> = rows_from_result(&replicas_result()).unwrap();
+        assert_eq!(replicas.len(), 2);
+        assert_eq!(replicas[0].table, "events");
+        assert!(replicas[0].is_leader);
+        assert_eq!(replicas[0].queue_size, 3);
+        assert!(replicas[0].is_healthy());
+        assert!(!replicas[1].is_healthy());
+    }
+
+    #[test]
+    fn test_missing_or_mismatched_column() {
+        let mut block = Block::new();
+        block.add_column("query_id", Column::new("query_id", "UInt64", ColumnData::UInt64(vec![1])));
+        let row = RowReader::new(&block, 0);
+        assert!(matches!(row.string("query_id"), Err(Error::TypeConversion(_))));
+        assert!(matches!(row.string("user"), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_row_reader_codecs() {
+        #[derive(Debug, PartialEq)]
+        struct Cents(u64);
+
+        let mut block = Block::new();
+        block.add_column("price", Column::new("price", "UInt64", ColumnData::UInt64(vec![250])));
+        let codecs = CodecRegistry::new();
+        assert!(matches!(RowReader::new(&block, 0).decode::<Cents>("price"), Err(Error::Configuration(_))));
+
+        codecs.register_fn(|value| u64::try_from(value).map(Cents), |cents: &Cents| Ok(Value::UInt64(cents.0)));
+        let row = RowReader::new(&block, 0).with_codecs(&codecs);
+        assert_eq!(row.decode::<Cents>("price").unwrap(), Cents(250));
+        assert!(matches!(row.decode::<Cents>("missing"), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_select_sql() {
+        assert_eq!(
+            select_sql::<ProcessInfo>(Some("is_initial_query")),
+            "SELECT query_id, user, query, elapsed, read_rows, read_bytes, memory_usage, is_initial_query \
+             FROM system.processes WHERE is_initial_query"
+        );
+        assert_eq!(table_filter("db", "it's"), "database = 'db' AND table = 'it\\'s'");
+    }
+
+    #[test]
+    fn test_query_log_tags() {
+        assert_eq!(
+            query_log_filter(&[("team", "growth")], Duration::from_secs(3600)),
+            "type = 'QueryFinish' AND event_time >= now() - INTERVAL 3600 SECOND \
+             AND JSONExtractString(log_comment, 'team') = 'growth'"
+        );
+
+        let at = chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(3, 4, 5).unwrap();
+        let strings = |v: &str| ColumnData::String(vec![v.to_string()]);
+        let mut block = Block::new();
+        for (name, value) in [("query_id", "q1"), ("user", "etl"), ("query", "SELECT 1")] {
+            block.add_column(name, Column::new(name, "String", strings(value)));
+        }
+        block.add_column("event_time", Column::new("event_time", "DateTime", ColumnData::DateTime(vec![at])));
+        for name in ["query_duration_ms", "read_rows", "read_bytes", "result_rows", "memory_usage"] {
+            block.add_column(name, Column::new(name, "UInt64", ColumnData::UInt64(vec![7])));
+        }
+        block.add_column("log_comment", Column::new("log_comment", "String", strings(r#"{"team":"growth"}"#)));
+        let entry = QueryLogEntry::from_row(&RowReader::new(&block, 0)).unwrap();
+        assert_eq!(entry.tags["team"], "growth");
+        assert_eq!(entry.read_rows, 7);
+    }
+
+    #[test]
+    fn test_storage_rows() {
+        let strings = |v: &str| ColumnData::String(vec![v.to_string()]);
+        let mut block = Block::new();
+        for (name, value) in [
+            ("database", "db"),
+            ("name", "events"),
+            ("engine", "MergeTree"),
+            ("engine_full", "MergeTree ORDER BY id TTL ts + toIntervalDay(30) SETTINGS storage_policy = 'tiered'"),
+            ("storage_policy", "tiered"),
+            ("policy_name", "tiered"),
+            ("volume_name", "hot"),
+        ] {
+            block.add_column(name, Column::new(name, "String", strings(value)));
+        }
+        block.add_column("volume_priority", Column::new("volume_priority", "UInt64", ColumnData::UInt64(vec![1])));
+        block.add_column("max_data_part_size", Column::new("max_data_part_size", "UInt64", ColumnData::UInt64(vec![0])));
+        block.add_column("move_factor", Column::new("move_factor", "Float32", ColumnData::Float32(vec![0.1])));
+        let disks = ColumnData::Array(vec![vec![Value::String("ssd".to_string()), Value::String("nvme".to_string())]]);
+        block.add_column("disks", Column::new("disks", "Array(String)", disks));
+
+        let table = TableStorage::from_row(&RowReader::new(&block, 0)).unwrap();
+        assert_eq!(table.ttl(), Some("ts + toIntervalDay(30)"));
+        assert_eq!(table.storage_policy, "tiered");
+        let volume = StoragePolicyVolume::from_row(&RowReader::new(&block, 0)).unwrap();
+        assert_eq!(volume.disks, ["ssd", "nvme"]);
+        assert_eq!(volume.volume_priority, 1);
+
+        let plain = TableStorage { engine_full: "MergeTree ORDER BY id".to_string(), ..table };
+        assert_eq!(plain.ttl(), None);
+        let disk = DiskInfo {
+            name: "default".to_string(),
+            path: "/var/lib/clickhouse/".to_string(),
+            disk_type: "Local".to_string(),
+            free_space: 25,
+            total_space: 100,
+            keep_free_space: 0,
+        };
+        assert_eq!(disk.used_fraction(), 0.75);
+    }
+
+    #[test]
+    fn test_column_storage() {
+        let mut block = Block::new();
+        for (name, value) in [
+            ("database", "db"),
+            ("table", "events"),
+            ("name", "payload"),
+            ("type", "String"),
+            ("compression_codec", "CODEC(ZSTD(3))"),
+        ] {
+            block.add_column(name, Column::new(name, "String", ColumnData::String(vec![value.to_string()])));
+        }
+        for (name, value) in [
+            ("position", 2),
+            ("data_compressed_bytes", 250),
+            ("data_uncompressed_bytes", 1000),
+            ("marks_bytes", 10),
+        ] {
+            block.add_column(name, Column::new(name, "UInt64", ColumnData::UInt64(vec![value])));
+        }
+
+        let column = ColumnStorage::from_row(&RowReader::new(&block, 0)).unwrap();
+        assert_eq!(column.column_type, "String");
+        assert_eq!(column.compression_codec, "CODEC(ZSTD(3))");
+        assert_eq!(column.bytes_on_disk(), 260);
+        assert_eq!(column.compression_ratio(), Some(4.0));
+        let empty = ColumnStorage { data_compressed_bytes: 0, data_uncompressed_bytes: 0, ..column };
+        assert_eq!(empty.compression_ratio(), None);
+    }
+
+    #[test]
+    fn test_merge_estimated_remaining() {
+        let merge = MergeInfo {
+            database: "db".to_string(),
+            table: "events".to_string(),
+            elapsed: 10.0,
+            progress: 0.25,
+            num_parts: 4,
+            result_part_name: "all_1_4_1".to_string(),
+            total_size_bytes_compressed: 0,
+            memory_usage: 0,
+        };
+        assert_eq!(merge.estimated_remaining(), Some(30.0));
+    }
+}
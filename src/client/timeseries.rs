@@ -0,0 +1,279 @@
+//! Time-series queries bucketed by a fixed step
+//!
+//! ```rust,no_run
+//! # async fn example(client: &clickhouse_rs::Client) -> clickhouse_rs::Result<()> {
+//! use clickhouse_rs::client::{avg, Fill};
+//! use chrono::{Duration, Utc};
+//! use std::time::Duration as StdDuration;
+//!
+//! let end = Utc::now();
+//! let series = client
+//!     .timeseries("metrics")
+//!     .select(avg("value"))
+//!     .between(end - Duration::hours(1), end)
+//!     .step(StdDuration::from_secs(60))
+//!     .fill(Fill::Zero)
+//!     .fetch()
+//!     .await?;
+//! for (timestamp, value) in series {
+//!     println!("{} {}", timestamp, value);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::system_tables::RowReader;
+use crate::client::Client;
+use crate::error::{Error, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use std::time::Duration;
+
+/// Aggregate computed for each bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregation {
+    expr: String,
+}
+
+impl Aggregation {
+    /// Use an arbitrary aggregate expression, such as `quantile(0.9)(latency)`
+    pub fn new(expr: impl Into<String>) -> Self {
+        Self { expr: expr.into() }
+    }
+
+    /// Get the aggregate expression
+    pub fn expr(&self) -> &str {
+        &self.expr
+    }
+}
+
+/// Average of a column
+pub fn avg(column: &str) -> Aggregation {
+    Aggregation::new(format!("avg({})", column))
+}
+
+/// Sum of a column
+pub fn sum(column: &str) -> Aggregation {
+    Aggregation::new(format!("sum({})", column))
+}
+
+/// Minimum of a column
+pub fn min(column: &str) -> Aggregation {
+    Aggregation::new(format!("min({})", column))
+}
+
+/// Maximum of a column
+pub fn max(column: &str) -> Aggregation {
+    Aggregation::new(format!("max({})", column))
+}
+
+/// Number of rows
+pub fn count() -> Aggregation {
+    Aggregation::new("count()")
+}
+
+/// How buckets without rows are filled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fill {
+    /// Leave them out of the series
+    #[default]
+    None,
+    /// Report them with a value of zero
+    Zero,
+    /// Repeat the value of the previous bucket
+    Previous,
+}
+
+/// Builder for a bucketed time-series query
+pub struct TimeSeriesQuery<'a> {
+    client: &'a Client,
+    table: String,
+    time_column: String,
+    aggregation: Option<Aggregation>,
+    range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    step: Duration,
+    fill: Fill,
+    filter: Option<String>,
+}
+
+impl<'a> TimeSeriesQuery<'a> {
+    /// Create a query over a table, bucketing `timestamp` into one-minute steps
+    pub fn new(client: &'a Client, table: impl Into<String>) -> Self {
+        Self {
+            client,
+            table: table.into(),
+            time_column: "timestamp".to_string(),
+            aggregation: None,
+            range: None,
+            step: Duration::from_secs(60),
+            fill: Fill::None,
+            filter: None,
+        }
+    }
+
+    /// Set the `DateTime` column the buckets are based on
+    pub fn time_column(mut self, column: impl Into<String>) -> Self {
+        self.time_column = column.into();
+        self
+    }
+
+    /// Set the aggregate computed for each bucket
+    pub fn select(mut self, aggregation: Aggregation) -> Self {
+        self.aggregation = Some(aggregation);
+        self
+    }
+
+    /// Restrict the series to `[start, end)`
+    pub fn between(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.range = Some((start, end));
+        self
+    }
+
+    /// Set the bucket width, in whole seconds
+    pub fn step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Set how empty buckets are filled
+    pub fn fill(mut self, fill: Fill) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Add a `WHERE` predicate
+    pub fn filter(mut self, predicate: impl Into<String>) -> Self {
+        self.filter = Some(predicate.into());
+        self
+    }
+
+    /// Build the query
+    ///
+    /// The bucket and value are aliased `__bucket` and `__value`, so they do
+    /// not shadow source columns used by the aggregation or filter.
+    pub fn to_sql(&self) -> Result<String> {
+        let aggregation = self
+            .aggregation
+            .as_ref()
+            .ok_or_else(|| Error::Configuration("Time series query needs an aggregation".to_string()))?;
+        let step = self.step.as_secs();
+        if step == 0 || self.step.subsec_nanos() != 0 {
+            return Err(Error::Configuration(format!(
+                "Time series step must be a whole number of seconds, got {:?}",
+                self.step
+            )));
+        }
+        let interval = format!("INTERVAL {} SECOND", step);
+
+        let mut conditions = Vec::new();
+        if let Some((start, end)) = &self.range {
+            if start >= end {
+                return Err(Error::Configuration("Time series range start must be before its end".to_string()));
+            }
+            conditions.push(format!("{} >= toDateTime({})", self.time_column, start.timestamp()));
+            conditions.push(format!("{} < toDateTime({})", self.time_column, end.timestamp()));
+        }
+        if let Some(filter) = &self.filter {
+            conditions.push(format!("({})", filter));
+        }
+
+        let mut sql = format!(
+            "SELECT toStartOfInterval({}, {}) AS __bucket, toFloat64({}) AS __value FROM {}",
+            self.time_column,
+            interval,
+            aggregation.expr(),
+            self.table
+        );
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        sql.push_str(" GROUP BY __bucket ORDER BY __bucket");
+
+        if self.fill != Fill::None {
+            sql.push_str(" WITH FILL");
+            if let Some((start, end)) = &self.range {
+                sql.push_str(&format!(
+                    " FROM toStartOfInterval(toDateTime({}), {}) TO toDateTime({})",
+                    start.timestamp(),
+                    interval,
+                    end.timestamp()
+                ));
+            }
+            sql.push_str(&format!(" STEP {}", interval));
+            if self.fill == Fill::Previous {
+                sql.push_str(" INTERPOLATE (__value AS __value)");
+            }
+        }
+        Ok(sql)
+    }
+
+    /// Run the query and return `(bucket start, value)` pairs in time order
+    pub async fn fetch(&self) -> Result<Vec<(DateTime<Utc>, f64)>> {
+        let result = self.client.query(&self.to_sql()?).await?;
+        let mut series = Vec::with_capacity(result.row_count());
+        for block in &result.blocks {
+            for index in 0..block.row_count {
+                let row = RowReader::new(block, index);
+                series.push((Utc.from_utc_datetime(&row.datetime("__bucket")?), row.f64("__value")?));
+            }
+        }
+        Ok(series)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+
+    #[tokio::test]
+    async fn test_timeseries_sql() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        let start = Utc.timestamp_opt(1_700_000_030, 0).unwrap();
+        let end = Utc.timestamp_opt(1_700_003_600, 0).unwrap();
+
+        let sql = client
+            .timeseries("metrics")
+            .select(avg("value"))
+            .between(start, end)
+            .step(Duration::from_secs(60))
+            .fill(Fill::Zero)
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT toStartOfInterval(timestamp, INTERVAL 60 SECOND) AS __bucket, toFloat64(avg(value)) AS __value \
+             FROM metrics WHERE timestamp >= toDateTime(1700000030) AND timestamp < toDateTime(1700003600) \
+             GROUP BY __bucket ORDER BY __bucket WITH FILL FROM toStartOfInterval(toDateTime(1700000030), INTERVAL 60 SECOND) \
+             TO toDateTime(1700003600) STEP INTERVAL 60 SECOND"
+        );
+
+        let sql = client
+            .timeseries("metrics")
+            .time_column("ts")
+            .select(count())
+            .filter("host = 'a'")
+            .fill(Fill::Previous)
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT toStartOfInterval(ts, INTERVAL 60 SECOND) AS __bucket, toFloat64(count()) AS __value FROM metrics \
+             WHERE (host = 'a') GROUP BY __bucket ORDER BY __bucket WITH FILL STEP INTERVAL 60 SECOND \
+             INTERPOLATE (__value AS __value)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeseries_validation() {
+        let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+        assert!(client.timeseries("metrics").to_sql().is_err());
+        assert!(client
+            .timeseries("metrics")
+            .select(sum("bytes"))
+            .step(Duration::from_millis(1500))
+            .to_sql()
+            .is_err());
+        let now = Utc::now();
+        assert!(client.timeseries("metrics").select(max("v")).between(now, now).to_sql().is_err());
+    }
+}
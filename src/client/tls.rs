@@ -0,0 +1,345 @@
+//! Client certificates that can be replaced while the client runs
+//!
+//! ```rust,no_run
+//! # fn example() -> clickhouse_rs::error::Result<()> {
+//! use clickhouse_rs::{Client, ClientOptions};
+//! use clickhouse_rs::client::TlsReloader;
+//! use std::time::Duration;
+//!
+//! let tls = TlsReloader::from_files(Some("/run/spiffe/svid.pem"), Some("/run/spiffe/svid_key.pem"), Some("/run/spiffe/bundle.pem"))?;
+//! let client = Client::new(
+//!     ClientOptions::default()
+//!         .enable_tls()
+//!         .tls_reloader(tls)
+//!         .tls_reload_interval(Duration::from_secs(30)),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Short-lived certificates from SPIFFE or Vault are rewritten on disk, or
+//! handed out by an agent, well before they expire. A [`TlsReloader`] loads
+//! them from files or from a [`TlsProvider`] callback and bumps its generation
+//! whenever they change. New connections use the current certificates, and
+//! the pool closes idle connections of an older generation when they come
+//! back, so the pool turns over gradually instead of being drained.
+
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+/// PEM-encoded client certificate, key and CA bundle
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct TlsIdentity {
+    /// Client certificate chain
+    pub cert_pem: Option<Vec<u8>>,
+    /// PKCS#8 private key of the client certificate
+    pub key_pem: Option<Vec<u8>>,
+    /// CA certificates trusted for the server
+    pub ca_pem: Option<Vec<u8>>,
+}
+
+impl TlsIdentity {
+    /// Create an empty identity
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the client certificate chain
+    pub fn cert(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.cert_pem = Some(pem.into());
+        self
+    }
+
+    /// Set the private key of the client certificate
+    pub fn key(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.key_pem = Some(pem.into());
+        self
+    }
+
+    /// Set the CA bundle
+    pub fn ca(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.ca_pem = Some(pem.into());
+        self
+    }
+
+    /// Read an identity from PEM files
+    pub fn from_files(cert: Option<&Path>, key: Option<&Path>, ca: Option<&Path>) -> Result<Self> {
+        let identity = Self {
+            cert_pem: cert.map(read_pem).transpose()?,
+            key_pem: key.map(read_pem).transpose()?,
+            ca_pem: ca.map(read_pem).transpose()?,
+        };
+        identity.validate()?;
+        Ok(identity)
+    }
+
+    /// Check that the certificate and key come together
+    pub fn validate(&self) -> Result<()> {
+        match (&self.cert_pem, &self.key_pem) {
+            (Some(_), None) => Err(Error::Tls("Client certificate without a private key".to_string())),
+            (None, Some(_)) => Err(Error::Tls("Private key without a client certificate".to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Build a native TLS connector presenting this identity
+    #[cfg(feature = "native-tls")]
+    pub fn native_connector(&self, verify: bool) -> Result<native_tls::TlsConnector> {
+        let mut builder = native_tls::TlsConnector::builder();
+        if let (Some(cert), Some(key)) = (&self.cert_pem, &self.key_pem) {
+            let identity = native_tls::Identity::from_pkcs8(cert, key)
+                .map_err(|e| Error::Tls(format!("Invalid client certificate: {}", e)))?;
+            builder.identity(identity);
+        }
+        if let Some(ca) = &self.ca_pem {
+            let ca = native_tls::Certificate::from_pem(ca).map_err(|e| Error::Tls(format!("Invalid CA bundle: {}", e)))?;
+            builder.add_root_certificate(ca);
+        }
+        builder.danger_accept_invalid_certs(!verify);
+        builder.build().map_err(|e| Error::Tls(e.to_string()))
+    }
+}
+
+impl std::fmt::Debug for TlsIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsIdentity")
+            .field("cert_pem", &self.cert_pem.as_ref().map(Vec::len))
+            .field("key_pem", &self.key_pem.as_ref().map(|_| "[redacted]"))
+            .field("ca_pem", &self.ca_pem.as_ref().map(Vec::len))
+            .finish()
+    }
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| Error::Tls(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+/// Source of the current TLS identity, such as a SPIFFE or Vault agent
+pub trait TlsProvider: Send + Sync {
+    /// Get the identity to use for new connections
+    fn load(&self) -> Result<TlsIdentity>;
+}
+
+impl<F> TlsProvider for F
+where
+    F: Fn() -> Result<TlsIdentity> + Send + Sync,
+{
+    fn load(&self) -> Result<TlsIdentity> {
+        self()
+    }
+}
+
+enum TlsSource {
+    Files(Vec<Option<PathBuf>>),
+    Provider(Box<dyn TlsProvider>),
+}
+
+struct ReloaderInner {
+    source: TlsSource,
+    identity: RwLock<Arc<TlsIdentity>>,
+    generation: AtomicU64,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+}
+
+/// TLS identity that is reloaded from files or a provider
+///
+/// Clones share the same identity.
+#[derive(Clone)]
+pub struct TlsReloader {
+    inner: Arc<ReloaderInner>,
+}
+
+impl TlsReloader {
+    /// Load the identity from PEM files, which are re-read when they change
+    pub fn from_files(cert: Option<impl AsRef<Path>>, key: Option<impl AsRef<Path>>, ca: Option<impl AsRef<Path>>) -> Result<Self> {
+        let paths = vec![
+            cert.map(|p| p.as_ref().to_path_buf()),
+            key.map(|p| p.as_ref().to_path_buf()),
+            ca.map(|p| p.as_ref().to_path_buf()),
+        ];
+        Self::with_source(TlsSource::Files(paths))
+    }
+
+    /// Load the identity from a provider, which is asked again on every reload
+    pub fn from_provider(provider: impl TlsProvider + 'static) -> Result<Self> {
+        Self::with_source(TlsSource::Provider(Box::new(provider)))
+    }
+
+    fn with_source(source: TlsSource) -> Result<Self> {
+        let modified = Mutex::new(modified_times(&source));
+        let identity = load(&source)?;
+        Ok(Self {
+            inner: Arc::new(ReloaderInner {
+                source,
+                identity: RwLock::new(Arc::new(identity)),
+                generation: AtomicU64::new(0),
+                modified,
+            }),
+        })
+    }
+
+    /// Get the identity for new connections
+    pub fn identity(&self) -> Arc<TlsIdentity> {
+        self.inner.identity.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Get the number of times the identity has changed
+    pub fn generation(&self) -> u64 {
+        self.inner.generation.load(Ordering::Acquire)
+    }
+
+    /// Load the identity again, returning whether it changed
+    ///
+    /// On error the current identity stays in use.
+    pub fn reload(&self) -> Result<bool> {
+        *self.inner.modified.lock().unwrap_or_else(|e| e.into_inner()) = modified_times(&self.inner.source);
+        let identity = load(&self.inner.source)?;
+        let mut current = self.inner.identity.write().unwrap_or_else(|e| e.into_inner());
+        if **current == identity {
+            return Ok(false);
+        }
+        *current = Arc::new(identity);
+        let generation = self.inner.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        tracing::info!("Reloaded TLS identity, generation {}", generation);
+        Ok(true)
+    }
+
+    /// Reload if a file changed since the last load; providers are always asked
+    pub fn reload_if_modified(&self) -> Result<bool> {
+        if let TlsSource::Files(_) = &self.inner.source {
+            let modified = modified_times(&self.inner.source);
+            if *self.inner.modified.lock().unwrap_or_else(|e| e.into_inner()) == modified {
+                return Ok(false);
+            }
+        }
+        self.reload()
+    }
+
+    /// Check for changes every `interval` in the background
+    ///
+    /// The task ends once every clone of the reloader is dropped.
+    pub fn watch(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let inner: Weak<ReloaderInner> = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                if let Err(e) = (TlsReloader { inner }).reload_if_modified() {
+                    tracing::warn!("Failed to reload TLS identity, keeping the current one: {}", e);
+                }
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for TlsReloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match &self.inner.source {
+            TlsSource::Files(paths) => format!("files {:?}", paths.iter().flatten().collect::<Vec<_>>()),
+            TlsSource::Provider(_) => "provider".to_string(),
+        };
+        f.debug_struct("TlsReloader")
+            .field("source", &source)
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+fn load(source: &TlsSource) -> Result<TlsIdentity> {
+    match source {
+        TlsSource::Files(paths) => {
+            TlsIdentity::from_files(paths[0].as_deref(), paths[1].as_deref(), paths[2].as_deref())
+        }
+        TlsSource::Provider(provider) => {
+            let identity = provider.load()?;
+            identity.validate()?;
+            Ok(identity)
+        }
+    }
+}
+
+fn modified_times(source: &TlsSource) -> Vec<Option<SystemTime>> {
+    match source {
+        TlsSource::Files(paths) => paths
+            .iter()
+            .map(|path| path.as_ref().and_then(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok()))
+            .collect(),
+        TlsSource::Provider(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Client, ClientOptions};
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_reload_from_provider() {
+        let version = Arc::new(AtomicUsize::new(1));
+        let tls = TlsReloader::from_provider({
+            let version = version.clone();
+            move || {
+                Ok(match version.load(Ordering::SeqCst) {
+                    0 => TlsIdentity::new().cert("cert"),
+                    n => TlsIdentity::new().cert(format!("cert {}", n)).key("secret"),
+                })
+            }
+        })
+        .unwrap();
+        assert_eq!(tls.generation(), 0);
+        assert_eq!(tls.identity().cert_pem.as_deref(), Some(&b"cert 1"[..]));
+
+        assert!(!tls.reload_if_modified().unwrap());
+        version.store(2, Ordering::SeqCst);
+        assert!(tls.clone().reload().unwrap());
+        assert_eq!(tls.generation(), 1);
+        assert_eq!(tls.identity().cert_pem.as_deref(), Some(&b"cert 2"[..]));
+
+        // A broken identity keeps the previous one in use
+        version.store(0, Ordering::SeqCst);
+        assert!(matches!(tls.reload(), Err(Error::Tls(_))));
+        assert_eq!(tls.generation(), 1);
+        assert!(!format!("{:?}", tls.identity()).contains("secret"));
+    }
+
+    #[test]
+    fn test_reload_from_files() {
+        let dir = std::env::temp_dir().join(format!("clickhouse-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ca = dir.join("ca.pem");
+        std::fs::write(&ca, "first").unwrap();
+
+        let tls = TlsReloader::from_files(None::<&Path>, None::<&Path>, Some(&ca)).unwrap();
+        assert!(!tls.reload_if_modified().unwrap());
+        std::fs::write(&ca, "second").unwrap();
+        assert!(tls.reload().unwrap());
+        assert_eq!(tls.identity().ca_pem.as_deref(), Some(&b"second"[..]));
+
+        std::fs::remove_file(&ca).unwrap();
+        assert!(tls.reload_if_modified().is_err());
+        assert_eq!(tls.generation(), 1);
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(TlsReloader::from_files(Some(&ca), None::<&Path>, None::<&Path>).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_reload_tls() {
+        let options = ClientOptions::default().min_connections(0);
+        let client = Client::new(options.clone()).unwrap();
+        assert!(client.tls().is_none());
+        assert!(matches!(client.reload_tls(), Err(Error::Configuration(_))));
+
+        let tls = TlsReloader::from_provider(|| Ok(TlsIdentity::new().ca("bundle"))).unwrap();
+        let client = Client::new(options.enable_tls().tls_reloader(tls).tls_reload_interval(Duration::from_secs(60))).unwrap();
+        assert!(!client.reload_tls().unwrap());
+        assert_eq!(client.clone().tls().unwrap().identity().ca_pem.as_deref(), Some(&b"bundle"[..]));
+    }
+}
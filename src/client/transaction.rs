@@ -0,0 +1,185 @@
+//! Experimental transaction support for ClickHouse
+//!
+//! ClickHouse provides experimental `BEGIN TRANSACTION` / `COMMIT` / `ROLLBACK`
+//! statements. A transaction is bound to the session it was started on, so a
+//! [`Transaction`] keeps a single pooled connection for its whole lifetime.
+
+use crate::client::drain::InFlightGuard;
+use crate::client::pool::PooledConnection;
+use crate::client::{InsertResult, QueryResult, QuerySettings};
+use crate::error::{Error, Result};
+use crate::types::Block;
+use tracing::{debug, warn};
+
+/// Transaction state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionState {
+    /// Transaction is open and accepts statements
+    Active,
+    /// Transaction was committed
+    Committed,
+    /// Transaction was rolled back
+    RolledBack,
+}
+
+/// A transaction pinned to a single connection
+///
+/// If the transaction is dropped while still active it is rolled back in the
+/// background. Connections whose rollback fails are disconnected so that the
+/// pool discards them instead of reusing a session with an open transaction.
+/// A transaction begun by the client counts as in flight until it is
+/// committed or rolled back, so draining waits for it.
+pub struct Transaction {
+    /// Connection the transaction runs on
+    connection: Option<PooledConnection>,
+    /// Current state
+    state: TransactionState,
+    /// Keeps the client from finishing a drain while the transaction is open
+    in_flight: Option<InFlightGuard>,
+}
+
+impl Transaction {
+    /// Begin a new transaction on the given connection
+    pub(crate) async fn begin(mut connection: PooledConnection) -> Result<Self> {
+        connection.execute("BEGIN TRANSACTION").await?;
+        debug!("Started transaction on connection {}", connection.id());
+
+        Ok(Self {
+            connection: Some(connection),
+            state: TransactionState::Active,
+            in_flight: None,
+        })
+    }
+
+    /// Count the transaction as in flight until it ends
+    pub(crate) fn with_in_flight(mut self, guard: InFlightGuard) -> Self {
+        self.in_flight = Some(guard);
+        self
+    }
+
+    /// Get the transaction state
+    pub fn state(&self) -> TransactionState {
+        self.state
+    }
+
+    /// Check if the transaction is still active
+    pub fn is_active(&self) -> bool {
+        self.state == TransactionState::Active
+    }
+
+    /// Get the connection of an active transaction
+    fn connection(&mut self) -> Result<&mut PooledConnection> {
+        if !self.is_active() {
+            return Err(Error::QueryExecution(format!(
+                "Transaction is no longer active ({:?})",
+                self.state
+            )));
+        }
+
+        self.connection
+            .as_mut()
+            .ok_or_else(|| Error::Internal("Transaction has no connection".to_string()))
+    }
+
+    /// Execute a query inside the transaction
+    pub async fn query(&mut self, sql: &str) -> Result<QueryResult> {
+        self.connection()?.query(sql).await
+    }
+
+    /// Execute a query with settings inside the transaction
+    pub async fn query_with_settings(&mut self, sql: &str, settings: QuerySettings) -> Result<QueryResult> {
+        self.connection()?.query_with_settings(sql, settings).await
+    }
+
+    /// Execute a statement inside the transaction
+    pub async fn execute(&mut self, sql: &str) -> Result<()> {
+        self.connection()?.execute(sql).await
+    }
+
+    /// Insert a block inside the transaction
+    pub async fn insert(&mut self, table: &str, block: Block) -> Result<InsertResult> {
+        self.connection()?.insert(table, block).await
+    }
+
+    /// Commit the transaction
+    pub async fn commit(mut self) -> Result<()> {
+        let result = self.connection()?.execute("COMMIT").await;
+        self.finish(result, TransactionState::Committed).await
+    }
+
+    /// Roll back the transaction
+    pub async fn rollback(mut self) -> Result<()> {
+        let result = self.connection()?.execute("ROLLBACK").await;
+        self.finish(result, TransactionState::RolledBack).await
+    }
+
+    /// Record the outcome of COMMIT/ROLLBACK
+    async fn finish(&mut self, result: Result<()>, state: TransactionState) -> Result<()> {
+        let _in_flight = self.in_flight.take();
+        match result {
+            Ok(()) => {
+                self.state = state;
+                Ok(())
+            }
+            Err(e) => {
+                // The session state is unknown; make sure the connection is not reused
+                self.state = TransactionState::RolledBack;
+                if let Some(mut connection) = self.connection.take() {
+                    let _ = connection.disconnect().await;
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.state != TransactionState::Active {
+            return;
+        }
+
+        if let Some(mut connection) = self.connection.take() {
+            warn!("Transaction dropped without commit, rolling back");
+            // Draining also waits for the rollback
+            let in_flight = self.in_flight.take();
+            tokio::spawn(async move {
+                let _in_flight = in_flight;
+                if let Err(e) = connection.execute("ROLLBACK").await {
+                    warn!("Rollback on drop failed, discarding connection: {}", e);
+                    let _ = connection.disconnect().await;
+                }
+            });
+        }
+    }
+}
+
+impl std::fmt::Debug for Transaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transaction")
+            .field("connection", &self.connection.as_ref().map(|c| c.id().to_string()))
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::drain::DrainController;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_transaction_holds_in_flight_guard() {
+        let drain = Arc::new(DrainController::new());
+        let transaction = Transaction {
+            connection: None,
+            state: TransactionState::Active,
+            in_flight: None,
+        }
+        .with_in_flight(drain.enter().unwrap());
+        assert_eq!(drain.in_flight(), 1);
+        drop(transaction);
+        assert_eq!(drain.in_flight(), 0);
+    }
+}
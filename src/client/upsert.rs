@@ -0,0 +1,111 @@
+//! Upserts on `ReplacingMergeTree` tables
+//!
+//! ClickHouse has no in-place update. The usual pattern is a
+//! `ReplacingMergeTree(version)` table ordered by the row key: every write is a
+//! plain insert, and when parts merge only the row with the highest version is
+//! kept for each key. Until a merge happens, duplicates are visible, so reads
+//! that must see one row per key use `SELECT ... FINAL`, and `OPTIMIZE ... FINAL`
+//! forces the merge.
+//!
+//! ```rust
+//! use clickhouse_rs::client::{ColumnDef, CreateTable};
+//!
+//! let sql = CreateTable::new("users")
+//!     .column(ColumnDef::new("id", "UInt64"))
+//!     .column(ColumnDef::new("name", "String"))
+//!     .column(ColumnDef::new("version", "UInt64"))
+//!     .engine("ReplacingMergeTree(version)")
+//!     .order_by("id")
+//!     .build()
+//!     .unwrap();
+//! assert!(sql.contains("ENGINE = ReplacingMergeTree(version)"));
+//! ```
+
+use crate::error::{Error, Result};
+use crate::types::{Block, Column, ColumnData};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static LAST_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Next row version: microseconds since the Unix epoch, strictly increasing
+/// within the process
+pub fn next_version() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as u64)
+        .unwrap_or(0);
+    let mut last = LAST_VERSION.load(Ordering::Relaxed);
+    loop {
+        let next = now.max(last + 1);
+        match LAST_VERSION.compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return next,
+            Err(current) => last = current,
+        }
+    }
+}
+
+/// Add a `UInt64` version column to a block
+///
+/// A block that already has the column is returned unchanged, so callers can
+/// supply their own versions.
+pub fn with_version(mut block: Block, version_column: &str, version: u64) -> Result<Block> {
+    if version_column.trim().is_empty() {
+        return Err(Error::Configuration("Version column name cannot be empty".to_string()));
+    }
+    if block.get_column(version_column).is_none() {
+        let data = ColumnData::UInt64(vec![version; block.row_count]);
+        block.add_column(version_column, Column::new(version_column, "UInt64", data));
+    }
+    Ok(block)
+}
+
+/// Build an `OPTIMIZE TABLE ... FINAL` statement
+pub fn optimize_final_sql(table: &str) -> String {
+    format!("OPTIMIZE TABLE {} FINAL", table)
+}
+
+/// Build a `SELECT * ... FINAL` query with an optional predicate
+pub fn select_final_sql(table: &str, predicate: Option<&str>) -> String {
+    match predicate.map(|p| p.trim().trim_end_matches(';').trim()).filter(|p| !p.is_empty()) {
+        Some(predicate) => format!("SELECT * FROM {} FINAL WHERE {}", table, predicate),
+        None => format!("SELECT * FROM {} FINAL", table),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+
+    fn users() -> Block {
+        let mut block = Block::new();
+        block.add_column("id", Column::new("id", "UInt64", ColumnData::UInt64(vec![1, 2])));
+        block
+    }
+
+    #[test]
+    fn test_with_version() {
+        let block = with_version(users(), "version", 42).unwrap();
+        let column = block.get_column("version").unwrap();
+        assert_eq!(column.type_name(), "UInt64");
+        assert_eq!(column.data.get_value(1), Some(Value::UInt64(42)));
+
+        let block = with_version(block, "version", 43).unwrap();
+        assert_eq!(block.column_count(), 2);
+        assert_eq!(block.get_column("version").unwrap().data.get_value(0), Some(Value::UInt64(42)));
+
+        assert!(with_version(users(), " ", 1).is_err());
+        assert!(next_version() < next_version());
+    }
+
+    #[test]
+    fn test_final_sql() {
+        assert_eq!(optimize_final_sql("db.users"), "OPTIMIZE TABLE db.users FINAL");
+        assert_eq!(select_final_sql("users", None), "SELECT * FROM users FINAL");
+        assert_eq!(
+            select_final_sql("users", Some("id = 1;")),
+            "SELECT * FROM users FINAL WHERE id = 1"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Structured warnings about client health
+//!
+//! ```rust
+//! # async fn example(client: clickhouse_rs::Client) {
+//! use clickhouse_rs::client::ClientWarning;
+//!
+//! let mut warnings = client.warnings();
+//! tokio::spawn(async move {
+//!     while let Ok(warning) = warnings.recv().await {
+//!         if let ClientWarning::SlowQuery(slow) = &warning {
+//!             println!("slow query took {:?}", slow.elapsed);
+//!         }
+//!         println!("{}: {}", warning.kind(), warning);
+//!     }
+//! });
+//! # }
+//! ```
+//!
+//! Warnings go to every subscriber on a bounded broadcast channel. A
+//! subscriber that falls behind loses the oldest warnings and gets
+//! `RecvError::Lagged`; nothing is sent while no one is subscribed.
+
+use crate::client::{QueryPlan, SchemaDrift, SlowQuery};
+use std::fmt;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Warnings kept for a subscriber that falls behind
+pub const DEFAULT_WARNING_CAPACITY: usize = 256;
+
+/// Setting the server ignores or has replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeprecatedSetting {
+    /// Name of the setting
+    pub name: &'static str,
+    /// What to use instead, if anything
+    pub replacement: Option<&'static str>,
+}
+
+/// Settings that are obsolete in current server versions
+pub const DEPRECATED_SETTINGS: &[DeprecatedSetting] = &[
+    DeprecatedSetting { name: "allow_experimental_bigint_types", replacement: None },
+    DeprecatedSetting { name: "allow_experimental_data_skipping_indices", replacement: None },
+    DeprecatedSetting { name: "allow_experimental_database_atomic", replacement: None },
+    DeprecatedSetting { name: "allow_experimental_geo_types", replacement: None },
+    DeprecatedSetting { name: "allow_experimental_low_cardinality_type", replacement: None },
+    DeprecatedSetting { name: "allow_experimental_map_type", replacement: None },
+    DeprecatedSetting { name: "allow_experimental_projection_optimization", replacement: Some("optimize_use_projections") },
+    DeprecatedSetting { name: "allow_experimental_window_functions", replacement: None },
+    DeprecatedSetting { name: "enable_debug_queries", replacement: Some("EXPLAIN") },
+    DeprecatedSetting { name: "experimental_use_processors", replacement: None },
+    DeprecatedSetting { name: "max_memory_usage_for_all_queries", replacement: Some("max_server_memory_usage") },
+    DeprecatedSetting { name: "multiple_joins_rewriter_version", replacement: None },
+    DeprecatedSetting { name: "partial_merge_join", replacement: Some("join_algorithm") },
+];
+
+/// Look up a setting in [`DEPRECATED_SETTINGS`]
+pub fn deprecated_setting(name: &str) -> Option<&'static DeprecatedSetting> {
+    DEPRECATED_SETTINGS.iter().find(|setting| setting.name.eq_ignore_ascii_case(name))
+}
+
+/// Event about the client's health
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientWarning {
+    /// A query was sent with an obsolete setting
+    DeprecatedSetting {
+        /// Name of the setting
+        name: String,
+        /// What to use instead, if anything
+        replacement: Option<String>,
+    },
+    /// The columns of an insert target changed
+    SchemaDrift {
+        /// Table whose columns changed
+        table: String,
+        /// Columns added and removed
+        drift: SchemaDrift,
+    },
+    /// The circuit breaker opened and rejects requests
+    CircuitOpened {
+        /// Failures that opened it
+        failures: usize,
+        /// How long it stays open
+        open_for: Duration,
+    },
+    /// A query ran longer than the slow query threshold
+    SlowQuery(SlowQuery),
+    /// Plan of a sampled slow query, captured after it finished
+    SlowQueryPlan {
+        /// ID of the slow query
+        query_id: String,
+        /// Its `EXPLAIN PLAN` output
+        plan: QueryPlan,
+    },
+}
+
+impl ClientWarning {
+    /// Get the name of the warning kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientWarning::DeprecatedSetting { .. } => "deprecated_setting",
+            ClientWarning::SchemaDrift { .. } => "schema_drift",
+            ClientWarning::CircuitOpened { .. } => "circuit_opened",
+            ClientWarning::SlowQuery(_) => "slow_query",
+            ClientWarning::SlowQueryPlan { .. } => "slow_query_plan",
+        }
+    }
+}
+
+impl fmt::Display for ClientWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientWarning::DeprecatedSetting { name, replacement: Some(replacement) } => {
+                write!(f, "Setting {} is deprecated, use {} instead", name, replacement)
+            }
+            ClientWarning::DeprecatedSetting { name, replacement: None } => {
+                write!(f, "Setting {} is deprecated and has no effect", name)
+            }
+            ClientWarning::SchemaDrift { table, drift } => write!(
+                f,
+                "Schema of {} changed: added {:?}, removed {:?}",
+                table,
+                drift.added.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+                drift.removed
+            ),
+            ClientWarning::CircuitOpened { failures, open_for } => {
+                write!(f, "Circuit breaker opened after {} failures for {:?}", failures, open_for)
+            }
+            ClientWarning::SlowQuery(slow) => write!(
+                f,
+                "Query {} took {:?}, over the {:?} threshold, reading {} rows and {} bytes",
+                slow.query_id, slow.elapsed, slow.threshold, slow.stats.rows_read, slow.stats.bytes_read
+            ),
+            ClientWarning::SlowQueryPlan { query_id, plan } => {
+                write!(f, "Plan of slow query {} has {} steps", query_id, plan.steps().len())
+            }
+        }
+    }
+}
+
+/// Broadcast channel of [`ClientWarning`]s
+///
+/// Clones send to the same subscribers.
+#[derive(Clone)]
+pub struct WarningChannel {
+    sender: broadcast::Sender<ClientWarning>,
+}
+
+impl WarningChannel {
+    /// Create a channel keeping up to `capacity` warnings per subscriber
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    /// Subscribe to the warnings sent from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ClientWarning> {
+        self.sender.subscribe()
+    }
+
+    /// Get the number of subscribers
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Send a warning to the subscribers
+    pub fn emit(&self, warning: ClientWarning) {
+        // Without subscribers the warning is dropped, which is fine
+        let _ = self.sender.send(warning);
+    }
+}
+
+impl Default for WarningChannel {
+    fn default() -> Self {
+        Self::new(DEFAULT_WARNING_CAPACITY)
+    }
+}
+
+impl fmt::Debug for WarningChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WarningChannel").field("subscribers", &self.subscribers()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Client, ClientOptions, CircuitBreakerBuilder};
+    use crate::error::Error;
+
+    #[tokio::test]
+    async fn test_warning_channel() {
+        let channel = WarningChannel::new(2);
+        channel.emit(ClientWarning::CircuitOpened { failures: 1, open_for: Duration::from_secs(1) });
+
+        let mut warnings = channel.subscribe();
+        let setting = deprecated_setting("Partial_Merge_Join").unwrap();
+        channel.clone().emit(ClientWarning::DeprecatedSetting {
+            name: setting.name.to_string(),
+            replacement: setting.replacement.map(str::to_string),
+        });
+        let warning = warnings.recv().await.unwrap();
+        assert_eq!(warning.kind(), "deprecated_setting");
+        assert_eq!(warning.to_string(), "Setting partial_merge_join is deprecated, use join_algorithm instead");
+        assert!(warnings.try_recv().is_err());
+        assert!(deprecated_setting("max_threads").is_none());
+
+        let breaker = CircuitBreakerBuilder::new().failure_threshold(1).warnings(channel.clone()).build();
+        let _ = breaker.execute(|| async { Err::<(), _>(Error::Internal("down".to_string())) }).await;
+        assert!(matches!(warnings.recv().await.unwrap(), ClientWarning::CircuitOpened { failures: 1, .. }));
+
+        let client = Client::new(ClientOptions::default().min_connections(0).warnings(channel.clone())).unwrap();
+        let _client_warnings = client.warnings();
+        assert_eq!(channel.subscribers(), 2);
+    }
+}
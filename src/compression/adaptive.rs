@@ -0,0 +1,176 @@
+//! Adaptive per-block compression method selection
+
+use super::CompressionMethod;
+use std::collections::VecDeque;
+
+/// Bytes inspected when estimating payload entropy
+const ENTROPY_SAMPLE_SIZE: usize = 4096;
+
+/// Tuning for adaptive compression
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveConfig {
+    /// Number of recent compression ratios to average
+    pub window: usize,
+    /// Entropy in bits per byte at or above which blocks are sent uncompressed
+    pub skip_entropy: f64,
+    /// Average ratio at or above which compression is paused
+    pub skip_ratio: f64,
+    /// Blocks sent uncompressed for a poor ratio before compression is retried
+    pub probe_interval: usize,
+    /// Entropy at or below which large blocks use ZSTD instead of LZ4
+    pub zstd_max_entropy: f64,
+    /// Minimum block size for ZSTD
+    pub zstd_min_size: usize,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            window: 16,
+            skip_entropy: 7.5,
+            skip_ratio: 0.95,
+            probe_interval: 8,
+            zstd_max_entropy: 5.0,
+            zstd_min_size: 64 * 1024,
+        }
+    }
+}
+
+/// Why a method was chosen for a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdaptiveDecision {
+    /// Block is smaller than the compression threshold
+    BelowThreshold,
+    /// Block looks already compressed or random
+    HighEntropy,
+    /// Recent blocks did not shrink enough to be worth compressing
+    PoorRatio,
+    /// Large redundant block, compressed with ZSTD
+    Redundant,
+    /// Compressed with LZ4
+    Default,
+}
+
+/// Counters for adaptive compression decisions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdaptiveStats {
+    /// Blocks sent uncompressed
+    pub uncompressed_blocks: u64,
+    /// Blocks compressed with LZ4
+    pub lz4_blocks: u64,
+    /// Blocks compressed with ZSTD
+    pub zstd_blocks: u64,
+    /// Blocks skipped because of high entropy
+    pub skipped_high_entropy: u64,
+    /// Blocks skipped because of a poor recent ratio
+    pub skipped_poor_ratio: u64,
+    /// Bytes before compression
+    pub bytes_in: u64,
+    /// Bytes after compression
+    pub bytes_out: u64,
+}
+
+impl AdaptiveStats {
+    /// Get the overall compression ratio
+    pub fn compression_ratio(&self) -> f64 {
+        if self.bytes_in == 0 {
+            1.0
+        } else {
+            self.bytes_out as f64 / self.bytes_in as f64
+        }
+    }
+}
+
+/// Running state of the adaptive selector
+#[derive(Debug, Default)]
+pub(crate) struct AdaptiveState {
+    pub(crate) config: AdaptiveConfig,
+    recent: VecDeque<f64>,
+    paused_blocks: usize,
+    pub(crate) stats: AdaptiveStats,
+}
+
+impl AdaptiveState {
+    pub(crate) fn new(config: AdaptiveConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Pick the method for a block at or above the threshold
+    pub(crate) fn choose(&mut self, data: &[u8]) -> (CompressionMethod, AdaptiveDecision) {
+        let entropy = shannon_entropy(&data[..data.len().min(ENTROPY_SAMPLE_SIZE)]);
+        if entropy >= self.config.skip_entropy {
+            return (CompressionMethod::None, AdaptiveDecision::HighEntropy);
+        }
+
+        if self.recent_ratio().is_some_and(|ratio| ratio >= self.config.skip_ratio) {
+            self.paused_blocks += 1;
+            if self.paused_blocks <= self.config.probe_interval {
+                return (CompressionMethod::None, AdaptiveDecision::PoorRatio);
+            }
+            self.paused_blocks = 0;
+        }
+
+        if entropy <= self.config.zstd_max_entropy && data.len() >= self.config.zstd_min_size {
+            (CompressionMethod::ZSTD, AdaptiveDecision::Redundant)
+        } else {
+            (CompressionMethod::LZ4, AdaptiveDecision::Default)
+        }
+    }
+
+    /// Record the outcome of a block
+    pub(crate) fn record(&mut self, decision: AdaptiveDecision, method: CompressionMethod, original: usize, compressed: usize) {
+        let stats = &mut self.stats;
+        stats.bytes_in += original as u64;
+        stats.bytes_out += compressed as u64;
+        match method {
+            CompressionMethod::LZ4 => stats.lz4_blocks += 1,
+            CompressionMethod::ZSTD => stats.zstd_blocks += 1,
+            _ => stats.uncompressed_blocks += 1,
+        }
+        match decision {
+            AdaptiveDecision::HighEntropy => stats.skipped_high_entropy += 1,
+            AdaptiveDecision::PoorRatio => stats.skipped_poor_ratio += 1,
+            _ => {}
+        }
+
+        // Only attempted compressions say anything about the payload
+        if matches!(decision, AdaptiveDecision::Default | AdaptiveDecision::Redundant) && original > 0 {
+            if self.recent.len() == self.config.window.max(1) {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(compressed as f64 / original as f64);
+        }
+    }
+
+    /// Average ratio of recent compressed blocks
+    pub(crate) fn recent_ratio(&self) -> Option<f64> {
+        if self.recent.is_empty() {
+            None
+        } else {
+            Some(self.recent.iter().sum::<f64>() / self.recent.len() as f64)
+        }
+    }
+}
+
+/// Shannon entropy of a byte slice in bits per byte
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -1,8 +1,16 @@
 //! Compression utilities for ClickHouse
 
+mod adaptive;
+mod frame;
+
+pub use adaptive::{shannon_entropy, AdaptiveConfig, AdaptiveDecision, AdaptiveStats};
+pub use frame::{checksum, decode_frame, encode_frame, frame_size};
+
 use crate::error::{Error, Result};
+use adaptive::AdaptiveState;
 use bytes::{Buf, BufMut, BytesMut};
 use std::io::{self, Write};
+use std::sync::Mutex;
 
 /// Compression methods supported by ClickHouse
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -187,34 +195,106 @@ pub struct CompressionManager {
     threshold: usize,
     /// Compressor instance
     compressor: Box<dyn Compressor>,
+    /// Per-block method selection, when adaptive mode is on
+    adaptive: Option<Mutex<AdaptiveState>>,
+}
+
+/// Create the compressor for a method
+fn compressor_for(method: CompressionMethod) -> Result<Box<dyn Compressor>> {
+    Ok(match method {
+        CompressionMethod::None => Box::new(NoCompressor),
+        CompressionMethod::LZ4 => Box::new(Lz4Compressor),
+        CompressionMethod::ZSTD => Box::new(ZstdCompressor),
+        CompressionMethod::GZIP => {
+            return Err(Error::Unsupported("GZIP compression not yet implemented".to_string()));
+        }
+        CompressionMethod::BZIP2 => {
+            return Err(Error::Unsupported("BZIP2 compression not yet implemented".to_string()));
+        }
+        CompressionMethod::XZ => {
+            return Err(Error::Unsupported("XZ compression not yet implemented".to_string()));
+        }
+    })
 }
 
 impl CompressionManager {
     /// Create a new compression manager
     pub fn new(method: CompressionMethod, level: CompressionLevel, threshold: usize) -> Result<Self> {
-        let compressor: Box<dyn Compressor> = match method {
-            CompressionMethod::None => Box::new(NoCompressor),
-            CompressionMethod::LZ4 => Box::new(Lz4Compressor),
-            CompressionMethod::ZSTD => Box::new(ZstdCompressor),
-            CompressionMethod::GZIP => {
-                return Err(Error::Unsupported("GZIP compression not yet implemented".to_string()));
-            }
-            CompressionMethod::BZIP2 => {
-                return Err(Error::Unsupported("BZIP2 compression not yet implemented".to_string()));
-            }
-            CompressionMethod::XZ => {
-                return Err(Error::Unsupported("XZ compression not yet implemented".to_string()));
-            }
-        };
+        let compressor = compressor_for(method)?;
 
         Ok(Self {
             method,
             level,
             threshold,
             compressor,
+            adaptive: None,
         })
     }
 
+    /// Create a manager that picks None, LZ4 or ZSTD per block
+    ///
+    /// Blocks that look incompressible are sent as is, compression pauses
+    /// while recent ratios are poor, and large redundant blocks use ZSTD.
+    pub fn adaptive(level: CompressionLevel, threshold: usize) -> Result<Self> {
+        Self::new(CompressionMethod::LZ4, level, threshold).map(|m| m.with_adaptive(AdaptiveConfig::default()))
+    }
+
+    /// Enable adaptive method selection with the given tuning
+    pub fn with_adaptive(mut self, config: AdaptiveConfig) -> Self {
+        self.adaptive = Some(Mutex::new(AdaptiveState::new(config)));
+        self
+    }
+
+    /// Check if adaptive method selection is enabled
+    pub fn is_adaptive(&self) -> bool {
+        self.adaptive.is_some()
+    }
+
+    /// Get adaptive decision counters
+    pub fn adaptive_stats(&self) -> Option<AdaptiveStats> {
+        self.adaptive
+            .as_ref()
+            .map(|state| state.lock().unwrap_or_else(|e| e.into_inner()).stats.clone())
+    }
+
+    /// Get the average ratio of recently compressed blocks in adaptive mode
+    pub fn recent_compression_ratio(&self) -> Option<f64> {
+        self.adaptive
+            .as_ref()
+            .and_then(|state| state.lock().unwrap_or_else(|e| e.into_inner()).recent_ratio())
+    }
+
+    fn compress_adaptive(&self, state: &Mutex<AdaptiveState>, data: &[u8]) -> Result<CompressedData> {
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        let (method, decision) = if data.len() < self.threshold {
+            (CompressionMethod::None, AdaptiveDecision::BelowThreshold)
+        } else {
+            state.choose(data)
+        };
+
+        let result = match method {
+            CompressionMethod::None => CompressedData::new(data.to_vec(), CompressionMethod::None, data.len()),
+            method => {
+                let compressed = compressor_for(method)?.compress(data, self.level)?;
+                if compressed.len() < data.len() {
+                    CompressedData::new(compressed, method, data.len())
+                } else {
+                    CompressedData::new(data.to_vec(), CompressionMethod::None, data.len())
+                }
+            }
+        };
+
+        // Report the attempted size so poor ratios pause compression
+        let attempted = if result.method == CompressionMethod::None && method.is_enabled() {
+            data.len()
+        } else {
+            result.compressed_size
+        };
+        state.record(decision, result.method, data.len(), attempted);
+        tracing::trace!("Adaptive compression chose {:?} ({:?}) for {} bytes", result.method, decision, data.len());
+        Ok(result)
+    }
+
     /// Create a new compression manager with default settings
     pub fn default() -> Result<Self> {
         Self::new(
@@ -226,6 +306,10 @@ impl CompressionManager {
 
     /// Compress data if it meets the threshold
     pub fn compress_if_needed(&self, data: &[u8]) -> Result<CompressedData> {
+        if let Some(state) = &self.adaptive {
+            return self.compress_adaptive(state, data);
+        }
+
         if data.len() < self.threshold || !self.method.is_enabled() {
             return Ok(CompressedData {
                 data: data.to_vec(),
@@ -263,6 +347,9 @@ impl CompressionManager {
             return Ok(data.data.clone());
         }
 
+        if data.method != self.method {
+            return compressor_for(data.method)?.decompress(&data.data);
+        }
         self.compressor.decompress(&data.data)
     }
 
@@ -283,20 +370,7 @@ impl CompressionManager {
 
     /// Set the compression method
     pub fn set_method(&mut self, method: CompressionMethod) -> Result<()> {
-        let compressor: Box<dyn Compressor> = match method {
-            CompressionMethod::None => Box::new(NoCompressor),
-            CompressionMethod::LZ4 => Box::new(Lz4Compressor),
-            CompressionMethod::ZSTD => Box::new(ZstdCompressor),
-            CompressionMethod::GZIP => {
-                return Err(Error::Unsupported("GZIP compression not yet implemented".to_string()));
-            }
-            CompressionMethod::BZIP2 => {
-                return Err(Error::Unsupported("BZIP2 compression not yet implemented".to_string()));
-            }
-            CompressionMethod::XZ => {
-                return Err(Error::Unsupported("XZ compression not yet implemented".to_string()));
-            }
-        };
+        let compressor = compressor_for(method)?;
 
         self.method = method;
         self.compressor = compressor;
@@ -530,4 +604,78 @@ mod tests {
         assert_eq!(data.space_savings(), 90.0);
         assert!(data.is_compressed());
     }
+
+    fn pseudo_random(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_adaptive_skips_high_entropy() {
+        let manager = CompressionManager::adaptive(CompressionLevel::default(), 64).unwrap();
+        assert!(shannon_entropy(&pseudo_random(4096)) > 7.5);
+
+        let compressed = manager.compress_if_needed(&pseudo_random(4096)).unwrap();
+        assert!(!compressed.is_compressed());
+
+        let small = manager.compress_if_needed(b"tiny").unwrap();
+        assert!(!small.is_compressed());
+
+        let stats = manager.adaptive_stats().unwrap();
+        assert_eq!(stats.skipped_high_entropy, 1);
+        assert_eq!(stats.uncompressed_blocks, 2);
+    }
+
+    #[test]
+    fn test_adaptive_picks_method_by_payload() {
+        let manager = CompressionManager::adaptive(CompressionLevel::default(), 64).unwrap();
+        let text = b"timestamp=2024-01-01 level=info msg=ok ".repeat(64);
+        let lz4 = manager.compress_if_needed(&text).unwrap();
+        assert_eq!(lz4.method, CompressionMethod::LZ4);
+
+        let large = vec![7u8; 128 * 1024];
+        let zstd = manager.compress_if_needed(&large).unwrap();
+        assert_eq!(zstd.method, CompressionMethod::ZSTD);
+
+        // The manager decompresses whichever method a block used
+        assert_eq!(manager.decompress(&zstd).unwrap(), large);
+        assert_eq!(manager.decompress(&lz4).unwrap(), text);
+
+        let stats = manager.adaptive_stats().unwrap();
+        assert_eq!((stats.lz4_blocks, stats.zstd_blocks), (1, 1));
+        assert!(stats.compression_ratio() < 0.1);
+        assert!(CompressionManager::default().unwrap().adaptive_stats().is_none());
+    }
+
+    #[test]
+    fn test_adaptive_pauses_on_poor_ratio() {
+        let config = AdaptiveConfig {
+            window: 2,
+            skip_entropy: 8.1,
+            probe_interval: 2,
+            ..AdaptiveConfig::default()
+        };
+        let manager = CompressionManager::adaptive(CompressionLevel::default(), 64)
+            .unwrap()
+            .with_adaptive(config);
+        let noise = pseudo_random(2048);
+
+        manager.compress_if_needed(&noise).unwrap();
+        assert!(manager.recent_compression_ratio().unwrap() >= 0.95);
+
+        // Paused for probe_interval blocks, then compression is retried
+        for _ in 0..3 {
+            manager.compress_if_needed(&noise).unwrap();
+        }
+        let stats = manager.adaptive_stats().unwrap();
+        assert_eq!(stats.skipped_poor_ratio, 2);
+        assert_eq!(stats.uncompressed_blocks, 4);
+    }
 }
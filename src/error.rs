@@ -1,3 +1,6 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::PoolStats;
+use crate::protocol::ServerException;
 use std::fmt;
 use thiserror::Error;
 
@@ -23,6 +26,10 @@ pub enum Error {
     #[error("Query execution failed: {0}")]
     QueryExecution(String),
 
+    /// Exception reported by the server
+    #[error("Server exception: {0}")]
+    Server(Box<ServerException>),
+
     /// Data type conversion errors
     #[error("Data type conversion failed: {0}")]
     TypeConversion(String),
@@ -74,25 +81,352 @@ pub enum Error {
     /// Custom errors
     #[error("Custom error: {0}")]
     Custom(String),
+
+    /// The client is draining and refuses new queries
+    #[error("Client is draining and not accepting new queries")]
+    Draining,
+
+    /// Session state could not be carried over to a new connection
+    #[error("Session lost: {0}")]
+    SessionLost(String),
+
+    /// The query result exceeded a client-side size limit
+    #[error("Result size limit exceeded: {0}")]
+    ResultTooLarge(String),
+
+    /// The server reported more memory use than the query's client-side bound
+    #[error("Query memory limit exceeded: {0}")]
+    MemoryLimitExceeded(String),
+
+    /// A received block failed its checksum, so the connection is reset
+    #[error("Integrity check failed: {0}")]
+    IntegrityCheck(String),
+
+    /// No pooled connection became free within the acquisition retry budget
+    #[error(
+        "Connection pool exhausted after {attempts} attempts over {waited:?} ({} active, {} idle, {} waiting)",
+        .stats.active_connections,
+        .stats.idle_connections,
+        .stats.waiting_requests
+    )]
+    #[cfg(not(target_arch = "wasm32"))]
+    PoolExhausted {
+        /// How long the caller waited
+        waited: std::time::Duration,
+        /// Acquisition attempts made
+        attempts: usize,
+        /// Pool stats when the caller gave up
+        stats: Box<PoolStats>,
+    },
+
+    /// An error annotated with where it happened
+    #[error("{source} ({context})")]
+    Context {
+        context: Box<ErrorContext>,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+/// Stable numeric error codes
+///
+/// Each variant has an explicit discriminant that is part of the public API:
+/// codes are never renumbered or reused, and new codes are only appended within
+/// their range. Ranges: 1xxx network, 2xxx server, 3xxx data, 4xxx client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    Network = 1000,
+    Protocol = 1001,
+    Timeout = 1002,
+    Tls = 1003,
+    WebSocket = 1004,
+    Http = 1005,
+    SessionLost = 1006,
+    IntegrityCheck = 1007,
+    Authentication = 2000,
+    QueryExecution = 2001,
+    Server = 2002,
+    TypeConversion = 3000,
+    Serialization = 3001,
+    Compression = 3002,
+    InvalidData = 3003,
+    ConnectionPool = 4000,
+    Configuration = 4001,
+    Unsupported = 4002,
+    Internal = 4003,
+    Custom = 4004,
+    Draining = 4005,
+    ResultTooLarge = 4006,
+    MemoryLimitExceeded = 4007,
+    PoolExhausted = 4008,
+}
+
+impl ErrorCode {
+    /// Every defined code
+    pub const ALL: [ErrorCode; 24] = [
+        ErrorCode::Network,
+        ErrorCode::Protocol,
+        ErrorCode::Timeout,
+        ErrorCode::Tls,
+        ErrorCode::WebSocket,
+        ErrorCode::Http,
+        ErrorCode::SessionLost,
+        ErrorCode::Authentication,
+        ErrorCode::QueryExecution,
+        ErrorCode::Server,
+        ErrorCode::TypeConversion,
+        ErrorCode::Serialization,
+        ErrorCode::Compression,
+        ErrorCode::InvalidData,
+        ErrorCode::ConnectionPool,
+        ErrorCode::Configuration,
+        ErrorCode::Unsupported,
+        ErrorCode::Internal,
+        ErrorCode::Custom,
+        ErrorCode::Draining,
+        ErrorCode::ResultTooLarge,
+        ErrorCode::IntegrityCheck,
+        ErrorCode::MemoryLimitExceeded,
+        ErrorCode::PoolExhausted,
+    ];
+
+    /// Get the numeric value of the code
+    pub fn as_u32(self) -> u32 {
+        self as u32
+    }
+
+    /// Look up a code by its numeric value
+    pub fn from_u32(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_u32() == code)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CH{:04}", self.as_u32())
+    }
+}
+
+/// ClickHouse server exception codes used by the error helpers
+pub mod server_codes {
+    pub const NOT_FOUND_COLUMN_IN_BLOCK: u32 = 10;
+    pub const DUPLICATE_COLUMN: u32 = 15;
+    pub const NO_SUCH_COLUMN_IN_TABLE: u32 = 16;
+    pub const ILLEGAL_COLUMN: u32 = 44;
+    pub const UNKNOWN_IDENTIFIER: u32 = 47;
+    pub const UNKNOWN_TYPE: u32 = 50;
+    pub const TYPE_MISMATCH: u32 = 53;
+    pub const TABLE_ALREADY_EXISTS: u32 = 57;
+    pub const UNKNOWN_TABLE: u32 = 60;
+    pub const UNKNOWN_DATABASE: u32 = 81;
+    pub const DATABASE_ALREADY_EXISTS: u32 = 82;
+    pub const READONLY: u32 = 164;
+    pub const UNKNOWN_USER: u32 = 192;
+    pub const WRONG_PASSWORD: u32 = 193;
+    pub const REQUIRED_PASSWORD: u32 = 194;
+    pub const ACCESS_DENIED: u32 = 497;
+    pub const AUTHENTICATION_FAILED: u32 = 516;
+
+    /// Codes raised for failed logins or missing privileges
+    pub const AUTH: [u32; 5] = [UNKNOWN_USER, WRONG_PASSWORD, REQUIRED_PASSWORD, ACCESS_DENIED, AUTHENTICATION_FAILED];
+
+    /// Codes raised when a query does not match the table schema
+    pub const SCHEMA: [u32; 11] = [
+        NOT_FOUND_COLUMN_IN_BLOCK,
+        DUPLICATE_COLUMN,
+        NO_SUCH_COLUMN_IN_TABLE,
+        ILLEGAL_COLUMN,
+        UNKNOWN_IDENTIFIER,
+        UNKNOWN_TYPE,
+        TYPE_MISMATCH,
+        TABLE_ALREADY_EXISTS,
+        UNKNOWN_TABLE,
+        UNKNOWN_DATABASE,
+        DATABASE_ALREADY_EXISTS,
+    ];
+}
+
+/// Broad error category for branching on failures without matching messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// Transport failures: connection, TLS, timeouts, wire protocol
+    Network,
+    /// Errors reported by the server
+    Server,
+    /// Client misuse, configuration or internal failures
+    Client,
+    /// Malformed or unconvertible data
+    Data,
 }
 
 impl Error {
+    /// Attach context to the error, keeping it as the source
+    pub fn context(self, context: ErrorContext) -> Self {
+        Error::Context {
+            context: Box::new(context),
+            source: Box::new(self),
+        }
+    }
+
+    /// Get the outermost context attached to the error
+    pub fn context_info(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Get the underlying error with all context removed
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    /// Get the categories the error belongs to
+    pub fn categories(&self) -> &'static [ErrorCategory] {
+        use ErrorCategory::*;
+        match self.root() {
+            Error::Network(_) | Error::Timeout(_) | Error::Tls(_) | Error::WebSocket(_) | Error::SessionLost(_) => {
+                &[Network]
+            }
+            Error::Protocol(_) | Error::IntegrityCheck(_) => &[Network, Data],
+            Error::Http { .. } => &[Network, Server],
+            Error::Authentication(_) | Error::QueryExecution(_) | Error::Server(_) => &[Server],
+            Error::TypeConversion(_) | Error::Serialization(_) | Error::Compression(_) | Error::InvalidData(_) => {
+                &[Data]
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::PoolExhausted { .. } => &[Client],
+            Error::ConnectionPool(_)
+            | Error::Configuration(_)
+            | Error::Unsupported(_)
+            | Error::Internal(_)
+            | Error::Custom(_)
+            | Error::Draining
+            | Error::ResultTooLarge(_)
+            | Error::MemoryLimitExceeded(_)
+            | Error::Context { .. } => &[Client],
+        }
+    }
+
+    /// Check if the error belongs to a category
+    pub fn is_category(&self, category: ErrorCategory) -> bool {
+        self.categories().contains(&category)
+    }
+
+    /// Get the stable error code, looking through any context
+    pub fn code(&self) -> ErrorCode {
+        match self.root() {
+            Error::Network(_) => ErrorCode::Network,
+            Error::Protocol(_) => ErrorCode::Protocol,
+            Error::Authentication(_) => ErrorCode::Authentication,
+            Error::QueryExecution(_) => ErrorCode::QueryExecution,
+            Error::Server(_) => ErrorCode::Server,
+            Error::TypeConversion(_) => ErrorCode::TypeConversion,
+            Error::Serialization(_) => ErrorCode::Serialization,
+            Error::Compression(_) => ErrorCode::Compression,
+            Error::ConnectionPool(_) => ErrorCode::ConnectionPool,
+            Error::Configuration(_) => ErrorCode::Configuration,
+            Error::Timeout(_) => ErrorCode::Timeout,
+            Error::Tls(_) => ErrorCode::Tls,
+            Error::Http { .. } => ErrorCode::Http,
+            Error::WebSocket(_) => ErrorCode::WebSocket,
+            Error::InvalidData(_) => ErrorCode::InvalidData,
+            Error::Unsupported(_) => ErrorCode::Unsupported,
+            Error::Internal(_) | Error::Context { .. } => ErrorCode::Internal,
+            Error::Custom(_) => ErrorCode::Custom,
+            Error::Draining => ErrorCode::Draining,
+            Error::SessionLost(_) => ErrorCode::SessionLost,
+            Error::ResultTooLarge(_) => ErrorCode::ResultTooLarge,
+            Error::IntegrityCheck(_) => ErrorCode::IntegrityCheck,
+            Error::MemoryLimitExceeded(_) => ErrorCode::MemoryLimitExceeded,
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::PoolExhausted { .. } => ErrorCode::PoolExhausted,
+        }
+    }
+
+    /// Get the server exception, if the server reported one
+    pub fn server_exception(&self) -> Option<&ServerException> {
+        match self.root() {
+            Error::Server(exception) => Some(exception),
+            _ => None,
+        }
+    }
+
+    /// Get the ClickHouse exception code reported by the server
+    pub fn server_error_code(&self) -> Option<u32> {
+        self.server_exception().map(|e| e.code)
+    }
+
+    /// Check if the error is an authentication or authorization failure
+    pub fn is_auth_error(&self) -> bool {
+        match self.root() {
+            Error::Authentication(_) => true,
+            Error::Http { status, .. } => matches!(status, 401 | 403),
+            Error::Server(e) => server_codes::AUTH.contains(&e.code),
+            _ => false,
+        }
+    }
+
+    /// Check if the error is caused by a missing or mismatched table, column or type
+    pub fn is_schema_error(&self) -> bool {
+        self.server_error_code()
+            .is_some_and(|code| server_codes::SCHEMA.contains(&code))
+    }
+
+    /// Find a ClickHouse error in an error's source chain
+    ///
+    /// Works with `anyhow::Error` and other wrappers via `find_in(&*err)`.
+    pub fn find_in<'a>(err: &'a (dyn std::error::Error + 'static)) -> Option<&'a Error> {
+        let mut current = Some(err);
+        while let Some(err) = current {
+            if let Some(found) = err.downcast_ref::<Error>() {
+                return Some(found);
+            }
+            current = err.source();
+        }
+        None
+    }
+
     /// Check if the error is retryable
     pub fn is_retryable(&self) -> bool {
-        matches!(
-            self,
-            Error::Network(_) | Error::Timeout(_) | Error::ConnectionPool(_)
-        )
+        match self.root() {
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::PoolExhausted { .. } => true,
+            other => matches!(
+                other,
+                Error::Network(_) | Error::Timeout(_) | Error::ConnectionPool(_) | Error::IntegrityCheck(_)
+            ),
+        }
     }
 
     /// Check if the error is a connection error
     pub fn is_connection_error(&self) -> bool {
         matches!(
-            self,
+            self.root(),
             Error::Network(_) | Error::Authentication(_) | Error::Tls(_)
         )
     }
 
+    /// Check if the error leaves the connection in an unknown protocol state
+    pub fn poisons_connection(&self) -> bool {
+        matches!(
+            self.root(),
+            Error::Network(_)
+                | Error::Protocol(_)
+                | Error::Timeout(_)
+                | Error::Tls(_)
+                | Error::WebSocket(_)
+                | Error::Compression(_)
+                | Error::InvalidData(_)
+                | Error::IntegrityCheck(_)
+        )
+    }
+
     /// Get a user-friendly error message
     pub fn user_message(&self) -> String {
         match self {
@@ -102,11 +436,18 @@ impl Error {
             Error::TypeConversion(msg) => format!("Data type error: {}", msg),
             Error::Timeout(duration) => format!("Operation timed out after {:?}", duration),
             Error::Http { status, message } => format!("HTTP error {}: {}", status, message),
+            Error::Context { source, .. } => source.user_message(),
             _ => self.to_string(),
         }
     }
 }
 
+impl From<ServerException> for Error {
+    fn from(exception: ServerException) -> Self {
+        Error::Server(Box::new(exception))
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
         Error::Serialization(err.to_string())
@@ -131,6 +472,7 @@ impl From<tokio::time::error::Elapsed> for Error {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl From<tungstenite::Error> for Error {
     fn from(err: tungstenite::Error) -> Self {
         Error::WebSocket(err.to_string())
@@ -205,11 +547,18 @@ impl From<anyhow::Error> for Error {
     }
 }
 
+/// Maximum length of the SQL snippet kept in an error context
+pub const SQL_SNIPPET_LEN: usize = 256;
+
 /// Error context for adding additional information
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ErrorContext {
     pub operation: String,
     pub details: Option<String>,
+    pub host: Option<String>,
+    pub query_id: Option<String>,
+    /// SQL text, truncated to [`SQL_SNIPPET_LEN`] bytes
+    pub sql: Option<String>,
     pub source: Option<Box<dyn std::error::Error + Send + Sync>>,
 }
 
@@ -217,8 +566,7 @@ impl ErrorContext {
     pub fn new(operation: impl Into<String>) -> Self {
         Self {
             operation: operation.into(),
-            details: None,
-            source: None,
+            ..Default::default()
         }
     }
 
@@ -227,15 +575,52 @@ impl ErrorContext {
         self
     }
 
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    pub fn with_query_id(mut self, query_id: impl Into<String>) -> Self {
+        self.query_id = Some(query_id.into());
+        self
+    }
+
+    pub fn with_sql(mut self, sql: &str) -> Self {
+        self.sql = Some(truncate_sql(sql));
+        self
+    }
+
     pub fn with_source(mut self, source: Box<dyn std::error::Error + Send + Sync>) -> Self {
         self.source = Some(source);
         self
     }
 }
 
+/// Truncate SQL to [`SQL_SNIPPET_LEN`] bytes on a character boundary
+fn truncate_sql(sql: &str) -> String {
+    let sql = sql.trim();
+    if sql.len() <= SQL_SNIPPET_LEN {
+        return sql.to_string();
+    }
+    let mut end = SQL_SNIPPET_LEN;
+    while !sql.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &sql[..end])
+}
+
 impl fmt::Display for ErrorContext {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Operation: {}", self.operation)?;
+        if let Some(host) = &self.host {
+            write!(f, " - Host: {}", host)?;
+        }
+        if let Some(query_id) = &self.query_id {
+            write!(f, " - Query ID: {}", query_id)?;
+        }
+        if let Some(sql) = &self.sql {
+            write!(f, " - SQL: {}", sql)?;
+        }
         if let Some(details) = &self.details {
             write!(f, " - Details: {}", details)?;
         }
@@ -251,3 +636,15 @@ impl std::error::Error for ErrorContext {
         self.source.as_ref().map(|e| e.as_ref() as _)
     }
 }
+
+/// Extension trait for attaching context to results
+pub trait ResultExt<T> {
+    /// Attach context to the error, if any
+    fn context_with(self, context: impl FnOnce() -> ErrorContext) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for Result<T> {
+    fn context_with(self, context: impl FnOnce() -> ErrorContext) -> Result<T> {
+        self.map_err(|e| e.context(context()))
+    }
+}
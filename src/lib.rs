@@ -10,6 +10,9 @@
 //! - **Connection Pooling**: Efficient connection management
 //! - **Batch Operations**: Optimized for bulk data operations
 //! - **Error Handling**: Comprehensive error types with context
+//! - **Blocking API**: A synchronous client behind the `blocking` feature
+//! - **Web Services**: axum and actix-web extractors and health endpoints behind the `web` feature
+//! - **Browser Support**: An HTTP client for `wasm32` behind the `wasm` feature
 //!
 //! ## Quick Start
 //!
@@ -67,13 +70,26 @@
 //!
 //! Licensed under the Apache License, Version 2.0.
 
+#[cfg(not(target_arch = "wasm32"))]
 pub mod client;
 pub mod types;
 pub mod protocol;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod compression;
 pub mod error;
+pub mod secret;
+pub mod schema;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(any(feature = "web-axum", feature = "web-actix"))]
+pub mod web;
 
 // Re-export main types for convenience
+#[cfg(not(target_arch = "wasm32"))]
 pub use client::{Client, ClientOptions, Connection, ConnectionPool};
 pub use types::{
     Block, Column, Row, Value,
@@ -90,7 +106,8 @@ pub use types::{
     // Geometric types
     Point, Ring, Polygon, MultiPolygon,
 };
-pub use error::{Error, Result};
+pub use error::{Error, ErrorCategory, ErrorCode, Result};
+pub use secret::Secret;
 
 // Re-export async traits
 pub use async_trait::async_trait;
@@ -1,6 +1,6 @@
 //! Client Data message for ClickHouse native protocol
 
-use super::{Packet, PacketType};
+use super::{read_block, write_block, DecodeMode, Packet, PacketType};
 use crate::error::{Error, Result};
 use crate::types::Block;
 use bytes::{Buf, BufMut, BytesMut};
@@ -135,11 +135,8 @@ impl Packet for ClientData {
             buf.put_u8(0);
         }
 
-        // Write block (simplified for now)
-        // For now, just write a placeholder for the block
-        buf.put_u64_le(0); // Block size placeholder
-
-        Ok(())
+        // Write block
+        write_block(buf, &self.block)
     }
 
     fn deserialize(buf: &mut BytesMut) -> Result<Self> {
@@ -188,9 +185,8 @@ impl Packet for ClientData {
             None
         };
 
-        // Read block (simplified for now)
-        let _block_size = buf.get_u64_le(); // Skip block size for now
-        let block = Block::default(); // Placeholder
+        // Read block
+        let block = read_block(buf, DecodeMode::Strict)?;
 
         Ok(Self {
             block,
@@ -287,5 +283,10 @@ mod tests {
         assert_eq!(original.database_name, deserialized.database_name);
         assert_eq!(original.compression_method, deserialized.compression_method);
         assert_eq!(original.compression_level, deserialized.compression_level);
+        assert_eq!(deserialized.row_count(), 5);
+        assert_eq!(
+            deserialized.block().get_column("test_column").unwrap().get_value(4),
+            Some(Value::UInt8(5))
+        );
     }
 }
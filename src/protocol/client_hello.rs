@@ -1,10 +1,14 @@
 //! Client Hello message for ClickHouse native protocol
 
+use super::varint::{read_var_string, read_varuint, write_var_string, write_varuint};
 use super::{Packet, PacketType};
-use crate::error::{Error, Result};
-use bytes::{Buf, BufMut, BytesMut};
+use crate::error::Result;
+use crate::secret::Secret;
+use bytes::BytesMut;
 use serde::{Deserialize, Serialize};
-use std::io;
+
+/// First protocol revision whose handshake ends with an addendum (the quota key)
+pub const HELLO_ADDENDUM_REVISION: u64 = 54458;
 
 /// Client Hello message sent when establishing a connection
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,7 +28,7 @@ pub struct ClientHello {
     /// Username
     pub username: String,
     /// Password
-    pub password: String,
+    pub password: Secret,
     /// Protocol version
     pub protocol_version: u64,
     /// Client query info
@@ -151,7 +155,7 @@ impl ClientHello {
             client_revision: 1,
             database: database.into(),
             username: username.into(),
-            password: password.into(),
+            password: Secret::new(password.into()),
             protocol_version: super::constants::DEFAULT_PROTOCOL_VERSION,
             client_query_info: None,
             client_query_info_version: None,
@@ -548,6 +552,22 @@ impl ClientHello {
     pub fn protocol_version_string(&self) -> String {
         format!("{}", self.protocol_version)
     }
+
+    /// Write the fields sent after the server hello for the negotiated revision
+    pub fn serialize_addendum(&self, buf: &mut BytesMut, revision: u64) {
+        if revision >= HELLO_ADDENDUM_REVISION {
+            write_var_string(buf, self.client_query_info_quota_key.as_deref().unwrap_or_default());
+        }
+    }
+
+    /// Read the fields written by [`ClientHello::serialize_addendum`]
+    pub fn deserialize_addendum(&mut self, buf: &mut BytesMut, revision: u64) -> Result<()> {
+        if revision >= HELLO_ADDENDUM_REVISION {
+            let quota_key = read_var_string(buf)?;
+            self.client_query_info_quota_key = (!quota_key.is_empty()).then_some(quota_key);
+        }
+        Ok(())
+    }
 }
 
 impl Packet for ClientHello {
@@ -555,103 +575,42 @@ impl Packet for ClientHello {
         PacketType::ClientHello
     }
 
+    /// Write the handshake in the order the server reads it
+    ///
+    /// Only the major and minor client versions are sent; the revision on the
+    /// wire is the protocol revision. Fields that depend on the negotiated
+    /// revision follow separately in [`ClientHello::serialize_addendum`].
     fn serialize(&self, buf: &mut BytesMut) -> Result<()> {
-        // Write client name
-        buf.put_u64_le(self.client_name.len() as u64);
-        buf.extend_from_slice(self.client_name.as_bytes());
-
-        // Write client version
-        buf.put_u64_le(self.client_version_major);
-        buf.put_u64_le(self.client_version_minor);
-        buf.put_u64_le(self.client_version_patch);
-        buf.put_u64_le(self.client_revision);
-
-        // Write database
-        buf.put_u64_le(self.database.len() as u64);
-        buf.extend_from_slice(self.database.as_bytes());
-
-        // Write username
-        buf.put_u64_le(self.username.len() as u64);
-        buf.extend_from_slice(self.username.as_bytes());
-
-        // Write password
-        buf.put_u64_le(self.password.len() as u64);
-        buf.extend_from_slice(self.password.as_bytes());
-
-        // Write protocol version
-        buf.put_u64_le(self.protocol_version);
-
-        // Write client query info (simplified for now)
-        if let Some(ref info) = self.client_query_info {
-            buf.put_u64_le(info.len() as u64);
-            buf.extend_from_slice(info.as_bytes());
-        } else {
-            buf.put_u64_le(0);
-        }
-
+        write_var_string(buf, &self.client_name);
+        write_varuint(buf, self.client_version_major);
+        write_varuint(buf, self.client_version_minor);
+        write_varuint(buf, self.protocol_version);
+        write_var_string(buf, &self.database);
+        write_var_string(buf, &self.username);
+        write_var_string(buf, self.password.as_str());
         Ok(())
     }
 
     fn deserialize(buf: &mut BytesMut) -> Result<Self> {
-        // Read client name
-        let name_len = buf.get_u64_le() as usize;
-        if buf.remaining() < name_len {
-            return Err(Error::Protocol("Insufficient data for client name".to_string()));
-        }
-        let client_name = String::from_utf8_lossy(&buf.copy_to_bytes(name_len)).to_string();
-
-        // Read client version
-        let client_version_major = buf.get_u64_le();
-        let client_version_minor = buf.get_u64_le();
-        let client_version_patch = buf.get_u64_le();
-        let client_revision = buf.get_u64_le();
-
-        // Read database
-        let db_len = buf.get_u64_le() as usize;
-        if buf.remaining() < db_len {
-            return Err(Error::Protocol("Insufficient data for database".to_string()));
-        }
-        let database = String::from_utf8_lossy(&buf.copy_to_bytes(db_len)).to_string();
-
-        // Read username
-        let user_len = buf.get_u64_le() as usize;
-        if buf.remaining() < user_len {
-            return Err(Error::Protocol("Insufficient data for username".to_string()));
-        }
-        let username = String::from_utf8_lossy(&buf.copy_to_bytes(user_len)).to_string();
-
-        // Read password
-        let pass_len = buf.get_u64_le() as usize;
-        if buf.remaining() < pass_len {
-            return Err(Error::Protocol("Insufficient data for password".to_string()));
-        }
-        let password = String::from_utf8_lossy(&buf.copy_to_bytes(pass_len)).to_string();
-
-        // Read protocol version
-        let protocol_version = buf.get_u64_le();
-
-        // Read client query info (simplified for now)
-        let info_len = buf.get_u64_le() as usize;
-        let client_query_info = if info_len > 0 {
-            if buf.remaining() < info_len {
-                return Err(Error::Protocol("Insufficient data for client query info".to_string()));
-            }
-            Some(String::from_utf8_lossy(&buf.copy_to_bytes(info_len)).to_string())
-        } else {
-            None
-        };
+        let client_name = read_var_string(buf)?;
+        let client_version_major = read_varuint(buf)?;
+        let client_version_minor = read_varuint(buf)?;
+        let protocol_version = read_varuint(buf)?;
+        let database = read_var_string(buf)?;
+        let username = read_var_string(buf)?;
+        let password = Secret::new(read_var_string(buf)?);
 
         Ok(Self {
             client_name,
             client_version_major,
             client_version_minor,
-            client_version_patch,
-            client_revision,
+            client_version_patch: 0,
+            client_revision: protocol_version,
             database,
             username,
             password,
             protocol_version,
-            client_query_info,
+            client_query_info: None,
             client_query_info_version: None,
             client_query_info_kind: None,
             client_query_info_initial_user: None,
@@ -786,14 +745,36 @@ mod tests {
         assert_eq!(original.client_name, deserialized.client_name);
         assert_eq!(original.client_version_major, deserialized.client_version_major);
         assert_eq!(original.client_version_minor, deserialized.client_version_minor);
-        assert_eq!(original.client_version_patch, deserialized.client_version_patch);
-        assert_eq!(original.client_revision, deserialized.client_revision);
+        assert_eq!(deserialized.client_revision, original.protocol_version);
         assert_eq!(original.database, deserialized.database);
         assert_eq!(original.username, deserialized.username);
         assert_eq!(original.password, deserialized.password);
         assert_eq!(original.protocol_version, deserialized.protocol_version);
     }
 
+    #[test]
+    fn test_client_hello_wire_layout() {
+        let hello = ClientHello::new("rs", "db", "u", "p")
+            .with_version(2, 1, 3, 42)
+            .with_protocol_version(54466)
+            .with_quota_key("team-a");
+
+        let mut buf = BytesMut::new();
+        Packet::serialize(&hello, &mut buf).unwrap();
+        assert_eq!(&buf[..], b"\x02rs\x02\x01\xc2\xa9\x03\x02db\x01u\x01p");
+
+        hello.serialize_addendum(&mut buf, HELLO_ADDENDUM_REVISION - 1);
+        assert_eq!(buf.len(), 15);
+        hello.serialize_addendum(&mut buf, 54466);
+        assert_eq!(&buf[15..], b"\x06team-a");
+
+        let mut read = <ClientHello as Packet>::deserialize(&mut buf).unwrap();
+        assert_eq!(read.client_query_info_quota_key, None);
+        read.deserialize_addendum(&mut buf, 54466).unwrap();
+        assert_eq!(read.client_query_info_quota_key.as_deref(), Some("team-a"));
+        assert!(buf.is_empty());
+    }
+
     #[test]
     fn test_client_hello_default() {
         let hello = ClientHello::default();
@@ -0,0 +1,224 @@
+//! Client identification sent with the handshake and every query
+
+use crate::protocol::constants::DEFAULT_CLIENT_NAME;
+use crate::protocol::{ClientHello, ClientQuery};
+use serde::{Deserialize, Serialize};
+
+/// Client information reported to the server and recorded in `system.query_log`
+///
+/// Fields left as `None` are detected from the environment by [`ClientInfo::resolve`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientInfo {
+    /// Operating system user running the client
+    pub os_user: Option<String>,
+    /// Hostname of the machine running the client
+    pub client_hostname: Option<String>,
+    /// Client name
+    pub client_name: Option<String>,
+    /// Client version as (major, minor, patch)
+    pub client_version: Option<(u64, u64, u64)>,
+    /// Interface kind (`TCP`, `HTTP`, `gRPC`, ...)
+    pub interface: Option<String>,
+    /// Key that shares a quota between connections of the same user
+    pub quota_key: Option<String>,
+}
+
+impl ClientInfo {
+    /// Create client info with nothing set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Detect all fields from the environment
+    pub fn from_environment() -> Self {
+        Self::new().resolve()
+    }
+
+    /// Set the OS user
+    pub fn os_user(mut self, os_user: impl Into<String>) -> Self {
+        self.os_user = Some(os_user.into());
+        self
+    }
+
+    /// Set the client hostname
+    pub fn client_hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.client_hostname = Some(hostname.into());
+        self
+    }
+
+    /// Set the client name
+    pub fn client_name(mut self, name: impl Into<String>) -> Self {
+        self.client_name = Some(name.into());
+        self
+    }
+
+    /// Set the client version
+    pub fn client_version(mut self, major: u64, minor: u64, patch: u64) -> Self {
+        self.client_version = Some((major, minor, patch));
+        self
+    }
+
+    /// Set the interface kind
+    pub fn interface(mut self, interface: impl Into<String>) -> Self {
+        self.interface = Some(interface.into());
+        self
+    }
+
+    /// Set the quota key
+    pub fn quota_key(mut self, quota_key: impl Into<String>) -> Self {
+        self.quota_key = Some(quota_key.into());
+        self
+    }
+
+    /// Fill unset fields from the environment, keeping explicit overrides
+    pub fn resolve(&self) -> Self {
+        Self {
+            os_user: self.os_user.clone().or_else(detect_os_user),
+            client_hostname: self.client_hostname.clone().or_else(detect_hostname),
+            client_name: self.client_name.clone().or_else(|| Some(DEFAULT_CLIENT_NAME.to_string())),
+            client_version: self.client_version.or_else(|| Some(crate_version())),
+            interface: self.interface.clone().or_else(|| Some("TCP".to_string())),
+            quota_key: self.quota_key.clone(),
+        }
+    }
+
+    /// Get the version as a dotted string
+    pub fn version_string(&self) -> Option<String> {
+        self.client_version
+            .map(|(major, minor, patch)| format!("{}.{}.{}", major, minor, patch))
+    }
+
+    /// Copy the set fields into a client hello packet
+    pub fn apply_to_hello(&self, mut hello: ClientHello) -> ClientHello {
+        if let Some(os_user) = &self.os_user {
+            hello = hello.with_os_user(os_user.as_str());
+        }
+        if let Some(hostname) = &self.client_hostname {
+            hello = hello.with_client_hostname(hostname.as_str());
+        }
+        if let Some(name) = &self.client_name {
+            hello.client_name = name.clone();
+            hello = hello.with_client_name(name.as_str());
+        }
+        if let (Some((major, minor, patch)), Some(version)) = (self.client_version, self.version_string()) {
+            let revision = hello.client_revision;
+            hello = hello
+                .with_version(major, minor, patch, revision)
+                .with_client_version(version)
+                .with_client_version_numbers(major, minor, patch, revision);
+        }
+        if let Some(interface) = &self.interface {
+            hello = hello.with_interface(interface.as_str());
+        }
+        if let Some(quota_key) = &self.quota_key {
+            hello = hello.with_quota_key(quota_key.as_str());
+        }
+        hello
+    }
+
+    /// Copy the set fields into a query packet
+    pub fn apply_to_query(&self, mut query: ClientQuery) -> ClientQuery {
+        if let Some(os_user) = &self.os_user {
+            query = query.with_os_user(os_user.as_str());
+        }
+        if let Some(hostname) = &self.client_hostname {
+            query = query.with_client_hostname(hostname.as_str());
+        }
+        if let Some(name) = &self.client_name {
+            query = query.with_client_name(name.as_str());
+        }
+        if let (Some((major, minor, patch)), Some(version)) = (self.client_version, self.version_string()) {
+            let revision = query.client_revision.unwrap_or_default();
+            query = query
+                .with_client_version(version)
+                .with_client_version_numbers(major, minor, patch, revision);
+        }
+        if let Some(interface) = &self.interface {
+            query = query.with_interface(interface.as_str());
+        }
+        if let Some(quota_key) = &self.quota_key {
+            query = query.with_quota_key(quota_key.as_str());
+        }
+        query
+    }
+}
+
+/// Version of this crate
+fn crate_version() -> (u64, u64, u64) {
+    let part = |s: &str| s.parse().unwrap_or_default();
+    (
+        part(env!("CARGO_PKG_VERSION_MAJOR")),
+        part(env!("CARGO_PKG_VERSION_MINOR")),
+        part(env!("CARGO_PKG_VERSION_PATCH")),
+    )
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn detect_os_user() -> Option<String> {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().and_then(non_empty))
+}
+
+fn detect_hostname() -> Option<String> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().and_then(non_empty))
+        .or_else(|| {
+            ["/proc/sys/kernel/hostname", "/etc/hostname"]
+                .iter()
+                .find_map(|path| std::fs::read_to_string(path).ok().and_then(non_empty))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_info_resolve_keeps_overrides() {
+        let info = ClientInfo::new()
+            .os_user("etl")
+            .client_name("nightly-loader")
+            .resolve();
+
+        assert_eq!(info.os_user.as_deref(), Some("etl"));
+        assert_eq!(info.client_name.as_deref(), Some("nightly-loader"));
+        assert_eq!(info.client_version, Some(crate_version()));
+        assert_eq!(info.interface.as_deref(), Some("TCP"));
+    }
+
+    #[test]
+    fn test_client_info_from_environment() {
+        let info = ClientInfo::from_environment();
+        assert_eq!(info.client_name.as_deref(), Some(DEFAULT_CLIENT_NAME));
+        assert_eq!(info.version_string().unwrap(), env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_client_info_apply_to_packets() {
+        let info = ClientInfo::new()
+            .os_user("alice")
+            .client_hostname("worker-1")
+            .client_version(2, 3, 4)
+            .interface("HTTP")
+            .quota_key("tenant-7");
+
+        let query = info.apply_to_query(ClientQuery::new("SELECT 1"));
+        assert_eq!(query.os_user.as_deref(), Some("alice"));
+        assert_eq!(query.client_hostname.as_deref(), Some("worker-1"));
+        assert_eq!(query.client_version.as_deref(), Some("2.3.4"));
+        assert_eq!(query.interface.as_deref(), Some("HTTP"));
+        assert_eq!(query.quota_key.as_deref(), Some("tenant-7"));
+
+        let hello = info.apply_to_hello(ClientHello::new("clickhouse-rs", "default", "default", ""));
+        assert_eq!(hello.client_query_info_os_user.as_deref(), Some("alice"));
+        assert_eq!(hello.client_query_info_client_version_major, Some(2));
+        assert_eq!(hello.client_version_major, 2);
+        assert_eq!(hello.client_query_info_quota_key.as_deref(), Some("tenant-7"));
+    }
+}
@@ -1,11 +1,13 @@
 //! Client Query message for ClickHouse native protocol
 
-use super::{Packet, PacketType};
+use super::settings::{read_settings, write_settings};
+use super::{constants, Packet, PacketType};
 use crate::error::{Error, Result};
+use crate::secret::Secret;
 use crate::types::{Block, Value};
 use bytes::{Buf, BufMut, BytesMut};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Client Query message for executing SQL queries
 #[derive(Debug, Clone)]
@@ -71,7 +73,7 @@ pub struct ClientQuery {
     /// Forwarded username
     pub forwarded_username: Option<String>,
     /// Forwarded password
-    pub forwarded_password: Option<String>,
+    pub forwarded_password: Option<Secret>,
     /// Forwarded auth
     pub forwarded_auth: Option<String>,
     /// Forwarded cert
@@ -120,6 +122,10 @@ pub struct ClientQuery {
     pub sql: String,
     /// Query settings
     pub settings: HashMap<String, Value>,
+    /// Settings the server must reject the query for if it does not know them
+    pub important_settings: HashSet<String>,
+    /// Protocol revision that decides the settings wire format
+    pub revision: u64,
     /// Stage
     pub stage: QueryProcessingStage,
     /// Compression
@@ -251,6 +257,8 @@ impl ClientQuery {
             forwarded_ssl_session_ticket_lifetime_hint_years: None,
             sql: sql.into(),
             settings: HashMap::new(),
+            important_settings: HashSet::new(),
+            revision: constants::DEFAULT_PROTOCOL_VERSION,
             stage: QueryProcessingStage::Complete,
             compression: false,
             data: None,
@@ -430,7 +438,7 @@ impl ClientQuery {
 
     /// Set forwarded password
     pub fn with_forwarded_password(mut self, password: impl Into<String>) -> Self {
-        self.forwarded_password = Some(password.into());
+        self.forwarded_password = Some(Secret::new(password.into()));
         self
     }
 
@@ -572,6 +580,20 @@ impl ClientQuery {
         self
     }
 
+    /// Add a setting the server must know, or fail the query
+    pub fn with_important_setting(mut self, key: impl Into<String>, value: Value) -> Self {
+        let key = key.into();
+        self.important_settings.insert(key.clone());
+        self.settings.insert(key, value);
+        self
+    }
+
+    /// Set the negotiated protocol revision
+    pub fn with_revision(mut self, revision: u64) -> Self {
+        self.revision = revision;
+        self
+    }
+
     /// Set stage
     pub fn with_stage(mut self, stage: QueryProcessingStage) -> Self {
         self.stage = stage;
@@ -666,16 +688,8 @@ impl Packet for ClientQuery {
         buf.put_u64_le(self.sql.len() as u64);
         buf.extend_from_slice(self.sql.as_bytes());
 
-        // Write settings (simplified for now)
-        buf.put_u64_le(self.settings.len() as u64);
-        for (key, value) in &self.settings {
-            buf.put_u64_le(key.len() as u64);
-            buf.extend_from_slice(key.as_bytes());
-            // For now, just write the value as a string
-            let value_str = format!("{:?}", value);
-            buf.put_u64_le(value_str.len() as u64);
-            buf.extend_from_slice(value_str.as_bytes());
-        }
+        // Write settings
+        write_settings(buf, &self.settings, &self.important_settings, self.revision)?;
 
         // Write data (if present)
         if let Some(ref _data) = self.data {
@@ -690,6 +704,13 @@ impl Packet for ClientQuery {
     }
 
     fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        Self::deserialize_with_revision(buf, constants::DEFAULT_PROTOCOL_VERSION)
+    }
+}
+
+impl ClientQuery {
+    /// Deserialize a query whose settings were written for `revision`
+    pub fn deserialize_with_revision(buf: &mut BytesMut, revision: u64) -> Result<Self> {
         // Read query ID
         let query_id_len = buf.get_u64_le() as usize;
         let query_id = if query_id_len > 0 {
@@ -741,24 +762,8 @@ impl Packet for ClientQuery {
         }
         let sql = String::from_utf8_lossy(&buf.copy_to_bytes(sql_len)).to_string();
 
-        // Read settings (simplified for now)
-        let settings_len = buf.get_u64_le() as usize;
-        let mut settings = HashMap::new();
-        for _ in 0..settings_len {
-            let key_len = buf.get_u64_le() as usize;
-            if buf.remaining() < key_len {
-                return Err(Error::Protocol("Insufficient data for setting key".to_string()));
-            }
-            let key = String::from_utf8_lossy(&buf.copy_to_bytes(key_len)).to_string();
-
-            let value_len = buf.get_u64_le() as usize;
-            if buf.remaining() < value_len {
-                return Err(Error::Protocol("Insufficient data for setting value".to_string()));
-            }
-            let value_str = String::from_utf8_lossy(&buf.copy_to_bytes(value_len)).to_string();
-            // For now, just use a placeholder value
-            settings.insert(key, Value::String(value_str));
-        }
+        // Read settings
+        let (settings, important_settings) = read_settings(buf, revision)?;
 
         // Read data (simplified for now)
         let has_data = buf.get_u64_le() != 0;
@@ -825,6 +830,8 @@ impl Packet for ClientQuery {
             forwarded_ssl_session_ticket_lifetime_hint_years: None,
             sql,
             settings,
+            important_settings,
+            revision,
             stage,
             compression,
             data,
@@ -835,7 +842,7 @@ impl Packet for ClientQuery {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::protocol::Packet;
+    use crate::protocol::{Packet, SETTINGS_AS_STRINGS_REVISION};
     use crate::types::Value;
 
     #[test]
@@ -925,13 +932,15 @@ mod tests {
             .with_query_kind(QueryKind::Secondary)
             .with_stage(QueryProcessingStage::FetchColumns)
             .with_compression(true)
-            .with_setting("max_memory_usage", Value::UInt64(1000000));
+            .with_setting("max_memory_usage", Value::UInt64(1000000))
+            .with_revision(SETTINGS_AS_STRINGS_REVISION);
 
         let mut buf = BytesMut::new();
         Packet::serialize(&original, &mut buf).unwrap();
 
         let mut read_buf = buf;
-        let deserialized = <ClientQuery as Packet>::deserialize(&mut read_buf).unwrap();
+        let deserialized =
+            ClientQuery::deserialize_with_revision(&mut read_buf, SETTINGS_AS_STRINGS_REVISION).unwrap();
 
         assert_eq!(original.sql, deserialized.sql);
         assert_eq!(original.query_id, deserialized.query_id);
@@ -940,4 +949,25 @@ mod tests {
         assert_eq!(original.compression, deserialized.compression);
         assert_eq!(original.settings.len(), deserialized.settings.len());
     }
+
+    #[test]
+    fn test_client_query_settings_follow_revision() {
+        let query = ClientQuery::new("SELECT 1")
+            .with_important_setting("max_threads", Value::UInt64(8))
+            .with_revision(SETTINGS_AS_STRINGS_REVISION);
+        let mut buf = BytesMut::new();
+        Packet::serialize(&query, &mut buf).unwrap();
+        let settings = b"\x0bmax_threads\x01\x018\x00";
+        assert!(buf.windows(settings.len()).any(|w| w == settings));
+
+        let deserialized =
+            ClientQuery::deserialize_with_revision(&mut buf, SETTINGS_AS_STRINGS_REVISION).unwrap();
+        assert_eq!(deserialized.settings.get("max_threads"), Some(&Value::String("8".to_string())));
+        assert!(deserialized.important_settings.contains("max_threads"));
+
+        let mut legacy = BytesMut::new();
+        Packet::serialize(&query.with_revision(54428), &mut legacy).unwrap();
+        let settings = b"\x0bmax_threads\x08\x00";
+        assert!(legacy.windows(settings.len()).any(|w| w == settings));
+    }
 }
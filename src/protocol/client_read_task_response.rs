@@ -0,0 +1,113 @@
+//! Client read task response packet implementation
+
+use crate::error::{Error, Result};
+use crate::protocol::{Packet, PacketType};
+use bytes::{Buf, BufMut, BytesMut};
+
+/// Client read task response packet
+///
+/// Answers a [`ServerReadTaskRequest`](crate::protocol::ServerReadTaskRequest)
+/// with the next task. An empty task tells the server that all work has been
+/// handed out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientReadTaskResponse {
+    /// Read task protocol version
+    pub protocol_version: u64,
+    /// Next task, `None` once all tasks are handed out
+    pub task: Option<String>,
+}
+
+impl ClientReadTaskResponse {
+    /// Create a new read task response
+    pub fn new(protocol_version: u64, task: Option<String>) -> Self {
+        Self { protocol_version, task }
+    }
+
+    /// Get the task
+    pub fn task(&self) -> Option<&str> {
+        self.task.as_deref()
+    }
+
+    /// Check if this response ends the task exchange
+    pub fn is_finished(&self) -> bool {
+        self.task.is_none()
+    }
+}
+
+impl Packet for ClientReadTaskResponse {
+    fn packet_type(&self) -> PacketType {
+        PacketType::ClientReadTaskResponse
+    }
+
+    fn serialize(&self, buf: &mut BytesMut) -> Result<()> {
+        buf.put_u64_le(self.protocol_version);
+
+        // An empty task marks the end of the exchange
+        let task = self.task.as_deref().unwrap_or_default().as_bytes();
+        buf.put_u64_le(task.len() as u64);
+        buf.extend_from_slice(task);
+
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        if buf.len() < 16 {
+            return Err(Error::Protocol("Insufficient data for ClientReadTaskResponse packet".to_string()));
+        }
+
+        let protocol_version = buf.get_u64_le();
+        let task_len = buf.get_u64_le() as usize;
+
+        if task_len > buf.len() {
+            return Err(Error::Protocol(format!(
+                "Invalid task length: {} (available: {})",
+                task_len,
+                buf.len()
+            )));
+        }
+
+        let task_bytes = buf.copy_to_bytes(task_len);
+        let task = String::from_utf8(task_bytes.to_vec())
+            .map_err(|e| Error::Protocol(format!("Invalid UTF-8 in read task: {}", e)))?;
+
+        Ok(ClientReadTaskResponse {
+            protocol_version,
+            task: if task.is_empty() { None } else { Some(task) },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_read_task_response_roundtrip() {
+        let response = ClientReadTaskResponse::new(1, Some("s3://bucket/part-0.parquet".to_string()));
+        let mut buf = BytesMut::new();
+        response.serialize(&mut buf).unwrap();
+
+        let decoded = ClientReadTaskResponse::deserialize(&mut buf).unwrap();
+        assert_eq!(decoded, response);
+        assert_eq!(decoded.packet_type(), PacketType::ClientReadTaskResponse);
+    }
+
+    #[test]
+    fn test_client_read_task_response_finished() {
+        let response = ClientReadTaskResponse::new(1, None);
+        let mut buf = BytesMut::new();
+        response.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 16);
+
+        let decoded = ClientReadTaskResponse::deserialize(&mut buf).unwrap();
+        assert!(decoded.is_finished());
+    }
+
+    #[test]
+    fn test_client_read_task_response_invalid_length() {
+        let mut buf = BytesMut::new();
+        buf.put_u64_le(1);
+        buf.put_u64_le(100);
+        assert!(ClientReadTaskResponse::deserialize(&mut buf).is_err());
+    }
+}
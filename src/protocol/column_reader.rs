@@ -0,0 +1,918 @@
+//! Decoding of column data in server blocks
+//!
+//! Newer servers can return column types this client does not know how to
+//! decode. By default such a column fails the whole block; the lenient
+//! [`DecodeMode`]s keep the rest of the result readable by either surfacing
+//! the raw values as [`Value::Unsupported`] or dropping the column. Either
+//! way the size of the column's payload must be known, from a fixed width or
+//! from the length prefixes and offsets of strings, arrays and maps;
+//! otherwise the following columns cannot be located and the block is cut
+//! short before the column.
+//!
+//! [`Value::Unsupported`]: crate::types::Value::Unsupported
+
+use crate::error::{Error, Result};
+use crate::types::{
+    Block, Column, ColumnData, Decimal128, Decimal32, Decimal64, Enum16, Enum8, EnumDefinition, FixedString, IPv4,
+    IPv6, Int256, TypeDescriptor, UInt256,
+};
+use bytes::{Buf, BytesMut};
+use chrono::{NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+/// How columns of types the client cannot decode are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecodeMode {
+    /// Fail the block
+    #[default]
+    Strict,
+    /// Keep the column with the raw bytes of each value
+    Lenient,
+    /// Drop the column from the block
+    SkipUnknownColumns,
+}
+
+/// How decoded values are checked against their column type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValidationMode {
+    /// Fail the block on an invalid value
+    #[default]
+    Strict,
+    /// Replace invalid values and record a warning
+    Lenient,
+}
+
+/// Options for decoding server blocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// How columns of undecodable types are handled
+    pub mode: DecodeMode,
+    /// How invalid values are handled
+    pub validation: ValidationMode,
+    /// Maximum number of rows decoded before the result is rejected
+    pub max_rows: Option<u64>,
+    /// Maximum number of column data bytes decoded before the result is rejected
+    pub max_bytes: Option<u64>,
+}
+
+/// Decoder for server blocks that collects validation warnings
+///
+/// Values are checked against their type: strings must be UTF-8, `Date32`
+/// and `DateTime64` must fall within 1900-2299, decimals must fit their
+/// precision and enum values must be declared. Under
+/// [`ValidationMode::Lenient`] invalid values are replaced (lossy UTF-8, the
+/// nearest valid date or decimal, the first declared enum value) and one
+/// warning is recorded per column and kind of problem.
+///
+/// The decoder keeps running totals of the rows and bytes it has decoded and
+/// fails with [`Error::ResultTooLarge`] once they pass the configured limits.
+#[derive(Debug, Default)]
+pub struct BlockDecoder {
+    options: DecodeOptions,
+    warnings: Vec<String>,
+    replaced: Vec<(&'static str, usize)>,
+    unsupported: Vec<(String, bool)>,
+    partial: bool,
+    rows_read: u64,
+    bytes_read: u64,
+}
+
+impl BlockDecoder {
+    /// Create a decoder
+    pub fn new(options: DecodeOptions) -> Self {
+        Self {
+            options,
+            warnings: Vec::new(),
+            replaced: Vec::new(),
+            unsupported: Vec::new(),
+            partial: false,
+            rows_read: 0,
+            bytes_read: 0,
+        }
+    }
+
+    /// Read a block of columns
+    ///
+    /// The block starts with the column count; a count of zero is an empty
+    /// block. Otherwise the row count follows, then the name, type and data of
+    /// each column.
+    ///
+    /// The row limit is checked before any column is decoded, the byte limit
+    /// once the block has been read.
+    ///
+    /// Outside [`DecodeMode::Strict`], a column whose payload size cannot be
+    /// worked out ends the block early: the columns before it are returned, a
+    /// warning is recorded and [`BlockDecoder::is_partial`] is set. The rest
+    /// of the block is left in `buf` for the caller to discard.
+    pub fn read_block(&mut self, buf: &mut BytesMut) -> Result<Block> {
+        let mut block = Block::new();
+        let start = buf.len();
+        self.partial = false;
+        let columns = read_u64(buf)?;
+        if columns == 0 {
+            return Ok(block);
+        }
+        let rows = read_u64(buf)? as usize;
+
+        self.rows_read += rows as u64;
+        if let Some(max_rows) = self.options.max_rows.filter(|max| self.rows_read > *max) {
+            return Err(Error::ResultTooLarge(format!(
+                "{} rows read, max_result_rows is {}",
+                self.rows_read, max_rows
+            )));
+        }
+
+        for index in 0..columns {
+            let name = read_string(buf)?;
+            let type_name = read_string(buf)?;
+            let data = match self.read_column(buf, &name, &type_name, rows) {
+                Ok(data) => data,
+                Err(Error::Unsupported(reason)) if self.options.mode != DecodeMode::Strict => {
+                    self.warnings.push(format!(
+                        "Column {}: {}; kept the {} columns before it and dropped the {} after it",
+                        name,
+                        reason,
+                        block.column_count(),
+                        columns - index - 1
+                    ));
+                    self.partial = true;
+                    break;
+                }
+                Err(e) => return Err(Error::Protocol(format!("Failed to read column {}: {}", name, e))),
+            };
+            match data {
+                Some(data) => block.add_column(name.clone(), Column::new(name, type_name, data)),
+                None => tracing::debug!("Skipped column {} of unsupported type {}", name, type_name),
+            }
+        }
+        block.row_count = rows;
+
+        self.bytes_read += (start - buf.len()) as u64;
+        if let Some(max_bytes) = self.options.max_bytes.filter(|max| self.bytes_read > *max) {
+            return Err(Error::ResultTooLarge(format!(
+                "{} bytes read, max_result_bytes is {}",
+                self.bytes_read, max_bytes
+            )));
+        }
+        Ok(block)
+    }
+
+    /// Check whether the last block was cut short at a column that could not be skipped
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+
+    /// Get the number of rows decoded so far
+    pub fn rows_read(&self) -> u64 {
+        self.rows_read
+    }
+
+    /// Get the number of bytes decoded so far
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Read the data of one column
+    ///
+    /// Returns `None` when the column was skipped under
+    /// [`DecodeMode::SkipUnknownColumns`].
+    pub fn read_column(
+        &mut self,
+        buf: &mut BytesMut,
+        name: &str,
+        type_name: &str,
+        rows: usize,
+    ) -> Result<Option<ColumnData>> {
+        let descriptor = TypeDescriptor::parse(type_name)?;
+        self.replaced.clear();
+        self.unsupported.clear();
+        let data = self.read_values(buf, &descriptor, rows, &[]);
+        for (what, count) in std::mem::take(&mut self.replaced) {
+            self.warnings.push(format!("Column {}: replaced {} invalid {}", name, count, what));
+        }
+        if data.is_ok() {
+            for (unsupported, kept) in std::mem::take(&mut self.unsupported) {
+                let action = if kept { "kept raw values of" } else { "skipped" };
+                self.warnings.push(format!("Column {}: {} unsupported type {}", name, action, unsupported));
+            }
+        }
+        data
+    }
+
+    /// Get the warnings recorded so far
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Take the warnings recorded so far
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Read or skip values the client cannot decode
+    ///
+    /// Fixed-width values are kept raw under [`DecodeMode::Lenient`]; any
+    /// other payload of known size is skipped.
+    fn read_unsupported(
+        &mut self,
+        buf: &mut BytesMut,
+        descriptor: &TypeDescriptor,
+        rows: usize,
+    ) -> Result<Option<ColumnData>> {
+        if self.options.mode == DecodeMode::Strict {
+            return Err(Error::Unsupported(format!("Cannot decode column type {}", descriptor)));
+        }
+        let size = encoded_size(buf, descriptor, rows)?.ok_or_else(|| {
+            Error::Unsupported(format!("Cannot skip column type {}: its encoded size is unknown", descriptor))
+        })?;
+        ensure(buf, size)?;
+
+        let kept = match encoded_width(descriptor) {
+            Some(width) if self.options.mode == DecodeMode::Lenient => Some(ColumnData::Unsupported {
+                type_name: descriptor.to_string(),
+                values: (0..rows).map(|_| buf.copy_to_bytes(width).to_vec()).collect(),
+            }),
+            _ => {
+                buf.advance(size);
+                None
+            }
+        };
+        self.unsupported.push((descriptor.to_string(), kept.is_some()));
+        Ok(kept)
+    }
+
+    /// Fail on an invalid value, or count it for a warning
+    fn invalid(&mut self, what: &'static str, detail: impl FnOnce() -> String) -> Result<()> {
+        if self.options.validation == ValidationMode::Strict {
+            return Err(Error::InvalidData(format!("Invalid {}: {}", what, detail())));
+        }
+        match self.replaced.iter_mut().find(|(w, _)| *w == what) {
+            Some((_, count)) => *count += 1,
+            None => self.replaced.push((what, 1)),
+        }
+        Ok(())
+    }
+
+    fn read_values(
+        &mut self,
+        buf: &mut BytesMut,
+        descriptor: &TypeDescriptor,
+        rows: usize,
+        nulls: &[u8],
+    ) -> Result<Option<ColumnData>> {
+        macro_rules! fixed {
+            ($variant:ident, $width:expr, $get:expr) => {{
+                ensure(buf, rows * $width)?;
+                ColumnData::$variant((0..rows).map(|_| $get(&mut *buf)).collect())
+            }};
+        }
+        let is_null = |row: usize| nulls.get(row).is_some_and(|null| *null != 0);
+
+        let data = match descriptor {
+            TypeDescriptor::Simple(name) => match name.as_str() {
+                "UInt8" | "Bool" => fixed!(UInt8, 1, |b: &mut BytesMut| b.get_u8()),
+                "UInt16" => fixed!(UInt16, 2, |b: &mut BytesMut| b.get_u16_le()),
+                "UInt32" => fixed!(UInt32, 4, |b: &mut BytesMut| b.get_u32_le()),
+                "UInt64" => fixed!(UInt64, 8, |b: &mut BytesMut| b.get_u64_le()),
+                "UInt128" => fixed!(UInt128, 16, |b: &mut BytesMut| b.get_u128_le()),
+                "UInt256" => fixed!(UInt256, 32, |b: &mut BytesMut| {
+                    let mut bytes = [0; 32];
+                    b.copy_to_slice(&mut bytes);
+                    UInt256::from_le_bytes(bytes)
+                }),
+                "Int8" => fixed!(Int8, 1, |b: &mut BytesMut| b.get_i8()),
+                "Int16" => fixed!(Int16, 2, |b: &mut BytesMut| b.get_i16_le()),
+                "Int32" => fixed!(Int32, 4, |b: &mut BytesMut| b.get_i32_le()),
+                "Int64" => fixed!(Int64, 8, |b: &mut BytesMut| b.get_i64_le()),
+                "Int128" => fixed!(Int128, 16, |b: &mut BytesMut| b.get_i128_le()),
+                "Int256" => fixed!(Int256, 32, |b: &mut BytesMut| {
+                    let mut bytes = [0; 32];
+                    b.copy_to_slice(&mut bytes);
+                    Int256::from_le_bytes(bytes)
+                }),
+                "Float32" => fixed!(Float32, 4, |b: &mut BytesMut| b.get_f32_le()),
+                "Float64" => fixed!(Float64, 8, |b: &mut BytesMut| b.get_f64_le()),
+                "UUID" => fixed!(UUID, 16, |b: &mut BytesMut| {
+                    let high = b.get_u64_le();
+                    uuid::Uuid::from_u64_pair(high, b.get_u64_le())
+                }),
+                "Date" => {
+                    ensure(buf, rows * 2)?;
+                    ColumnData::Date(
+                        (0..rows)
+                            .map(|_| unix_epoch().date() + chrono::Duration::days(buf.get_u16_le() as i64))
+                            .collect(),
+                    )
+                }
+                "Date32" => {
+                    ensure(buf, rows * 4)?;
+                    let (min, max) = (min_datetime().date(), max_datetime().date());
+                    let mut dates = Vec::with_capacity(rows);
+                    for row in 0..rows {
+                        let days = buf.get_i32_le();
+                        let date = unix_epoch().date() + chrono::Duration::days(days as i64);
+                        if (date < min || date > max) && !is_null(row) {
+                            self.invalid("dates", || {
+                                format!("{} days since epoch is outside 1900-2299", days)
+                            })?;
+                            dates.push(date.clamp(min, max));
+                        } else {
+                            dates.push(date);
+                        }
+                    }
+                    ColumnData::Date(dates)
+                }
+                "IPv4" => fixed!(IPv4, 4, |b: &mut BytesMut| IPv4::from_u32(b.get_u32_le())),
+                "IPv6" => fixed!(IPv6, 16, |b: &mut BytesMut| {
+                    let mut octets = [0; 16];
+                    b.copy_to_slice(&mut octets);
+                    IPv6::from_octets(octets)
+                }),
+                "String" => {
+                    let mut strings = Vec::with_capacity(rows);
+                    for _ in 0..rows {
+                        let len = read_u64(buf)? as usize;
+                        ensure(buf, len)?;
+                        strings.push(match String::from_utf8(buf.copy_to_bytes(len).to_vec()) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                self.invalid("UTF-8 strings", || e.to_string())?;
+                                String::from_utf8_lossy(e.as_bytes()).into_owned()
+                            }
+                        });
+                    }
+                    ColumnData::String(strings)
+                }
+                _ => return self.read_unsupported(buf, descriptor, rows),
+            },
+            TypeDescriptor::FixedString(length) => {
+                ensure(buf, rows * length)?;
+                ColumnData::FixedString(
+                    (0..rows)
+                        .map(|_| FixedString::from_bytes(&buf.copy_to_bytes(*length), *length))
+                        .collect(),
+                )
+            }
+            TypeDescriptor::DateTime { .. } => {
+                ensure(buf, rows * 4)?;
+                let datetimes = (0..rows)
+                    .map(|_| {
+                        chrono::DateTime::from_timestamp(buf.get_u32_le() as i64, 0)
+                            .map(|datetime| datetime.naive_utc())
+                            .ok_or_else(|| Error::Protocol("DateTime out of range".to_string()))
+                    })
+                    .collect::<Result<_>>()?;
+                ColumnData::DateTime(datetimes)
+            }
+            TypeDescriptor::DateTime64 { precision, .. } if *precision <= 9 => {
+                ensure(buf, rows * 8)?;
+                let scale = 10i64.pow(*precision as u32);
+                let (min, max) = (min_datetime(), max_datetime());
+                let mut datetimes = Vec::with_capacity(rows);
+                for row in 0..rows {
+                    let ticks = buf.get_i64_le();
+                    let nanos = ticks.rem_euclid(scale) * 10i64.pow(9 - *precision as u32);
+                    let datetime = chrono::DateTime::from_timestamp(ticks.div_euclid(scale), nanos as u32)
+                        .map(|datetime| datetime.naive_utc())
+                        .filter(|datetime| (min..=max).contains(datetime));
+                    match datetime {
+                        Some(datetime) => datetimes.push(datetime),
+                        None if is_null(row) => datetimes.push(unix_epoch()),
+                        None => {
+                            self.invalid("datetimes", || format!("{} ticks is outside 1900-2299", ticks))?;
+                            datetimes.push(if ticks < 0 { min } else { max });
+                        }
+                    }
+                }
+                ColumnData::DateTime64(datetimes)
+            }
+            TypeDescriptor::Decimal { precision, scale } if *precision <= 38 => {
+                let precision = (*precision).max(1);
+                let scale = if *scale > precision {
+                    self.invalid("decimal scales", || {
+                        format!("scale {} exceeds precision {}", scale, precision)
+                    })?;
+                    precision
+                } else {
+                    *scale
+                };
+                let limit = 10i128.pow(precision as u32) - 1;
+                let width = encoded_width(descriptor).unwrap_or(16);
+                ensure(buf, rows * width)?;
+                let mut values = Vec::with_capacity(rows);
+                for row in 0..rows {
+                    let value = match width {
+                        4 => buf.get_i32_le() as i128,
+                        8 => buf.get_i64_le() as i128,
+                        _ => buf.get_i128_le(),
+                    };
+                    if value.abs() > limit && !is_null(row) {
+                        self.invalid("decimals", || {
+                            format!("{} does not fit precision {}", value, precision)
+                        })?;
+                        values.push(value.clamp(-limit, limit));
+                    } else {
+                        values.push(value);
+                    }
+                }
+                match width {
+                    4 => ColumnData::Decimal32(
+                        values.into_iter().map(|v| Decimal32::new(v as i32, scale)).collect(),
+                    ),
+                    8 => ColumnData::Decimal64(
+                        values.into_iter().map(|v| Decimal64::new(v as i64, scale)).collect(),
+                    ),
+                    _ => ColumnData::Decimal128(
+                        values.into_iter().map(|v| Decimal128::new(v, scale)).collect(),
+                    ),
+                }
+            }
+            TypeDescriptor::Enum { bits, values } => {
+                let Some((_, fallback)) = values.first() else {
+                    return Err(Error::TypeConversion(format!("Enum type {} has no values", descriptor)));
+                };
+                let mut definition = EnumDefinition::new(descriptor.to_string());
+                for (name, value) in values {
+                    definition.add_value(name.clone(), *value).map_err(Error::TypeConversion)?;
+                }
+                let width = *bits as usize / 8;
+                ensure(buf, rows * width)?;
+                let mut raw = Vec::with_capacity(rows);
+                for row in 0..rows {
+                    let value = if width == 1 { buf.get_i8() as i16 } else { buf.get_i16_le() };
+                    if definition.has_value(value) || is_null(row) {
+                        raw.push(value);
+                    } else {
+                        self.invalid("enum values", || {
+                            format!("{} is not declared in {}", value, descriptor)
+                        })?;
+                        raw.push(*fallback);
+                    }
+                }
+                // Values under NULLs may be undeclared; they are never read
+                let enum_value = |value: i16| if definition.has_value(value) { value } else { *fallback };
+                if width == 1 {
+                    ColumnData::Enum8(
+                        raw.into_iter()
+                            .map(|v| Enum8::new(enum_value(v) as i8, definition.clone()))
+                            .collect::<std::result::Result<_, _>>()
+                            .map_err(Error::TypeConversion)?,
+                    )
+                } else {
+                    ColumnData::Enum16(
+                        raw.into_iter()
+                            .map(|v| Enum16::new(enum_value(v), definition.clone()))
+                            .collect::<std::result::Result<_, _>>()
+                            .map_err(Error::TypeConversion)?,
+                    )
+                }
+            }
+            TypeDescriptor::Nullable(inner) => {
+                ensure(buf, rows)?;
+                let nulls = buf.copy_to_bytes(rows);
+                let Some(values) = self.read_values(buf, inner, rows, &nulls)? else {
+                    return Ok(None);
+                };
+                ColumnData::Nullable(
+                    nulls
+                        .iter()
+                        .enumerate()
+                        .map(|(index, null)| if *null != 0 { None } else { values.get_value(index) })
+                        .collect(),
+                )
+            }
+            TypeDescriptor::Array(inner) => {
+                ensure(buf, rows * 8)?;
+                let offsets: Vec<usize> = (0..rows).map(|_| buf.get_u64_le() as usize).collect();
+                if offsets.windows(2).any(|pair| pair[0] > pair[1]) {
+                    return Err(Error::Protocol("Array offsets are not ascending".to_string()));
+                }
+                let total = offsets.last().copied().unwrap_or(0);
+                let Some(elements) = self.read_values(buf, inner, total, &[])? else {
+                    return Ok(None);
+                };
+                let mut start = 0;
+                ColumnData::Array(
+                    offsets
+                        .into_iter()
+                        .map(|end| {
+                            let items = (start..end).filter_map(|index| elements.get_value(index)).collect();
+                            start = end;
+                            items
+                        })
+                        .collect(),
+                )
+            }
+            TypeDescriptor::Tuple(elements) => {
+                let mut columns = Vec::with_capacity(elements.len());
+                for (_, element) in elements {
+                    columns.push(self.read_values(buf, element, rows, &[])?);
+                }
+                // A skipped element drops the whole tuple, after consuming all of it
+                let Some(columns) = columns.into_iter().collect::<Option<Vec<_>>>() else {
+                    return Ok(None);
+                };
+                ColumnData::Tuple(
+                    (0..rows)
+                        .map(|row| columns.iter().filter_map(|column| column.get_value(row)).collect())
+                        .collect(),
+                )
+            }
+            _ => return self.read_unsupported(buf, descriptor, rows),
+        };
+        Ok(Some(data))
+    }
+}
+
+/// Read a block of columns, validating values strictly
+pub fn read_block(buf: &mut BytesMut, mode: DecodeMode) -> Result<Block> {
+    BlockDecoder::new(DecodeOptions { mode, ..DecodeOptions::default() }).read_block(buf)
+}
+
+/// Read the data of one column, validating values strictly
+///
+/// Returns `None` when the column was skipped under
+/// [`DecodeMode::SkipUnknownColumns`].
+pub fn read_column(
+    buf: &mut BytesMut,
+    type_name: &str,
+    rows: usize,
+    mode: DecodeMode,
+) -> Result<Option<ColumnData>> {
+    BlockDecoder::new(DecodeOptions { mode, ..DecodeOptions::default() })
+        .read_column(buf, "", type_name, rows)
+}
+
+pub(super) fn unix_epoch() -> NaiveDateTime {
+    NaiveDateTime::default()
+}
+
+/// Earliest `Date32` and `DateTime64` value
+fn min_datetime() -> NaiveDateTime {
+    chrono::NaiveDate::from_ymd_opt(1900, 1, 1).unwrap_or_default().and_time(NaiveTime::MIN)
+}
+
+/// Latest `Date32` and `DateTime64` value
+fn max_datetime() -> NaiveDateTime {
+    chrono::NaiveDate::from_ymd_opt(2299, 12, 31)
+        .and_then(|date| date.and_hms_nano_opt(23, 59, 59, 999_999_999))
+        .unwrap_or_default()
+}
+
+/// Size in bytes of the values of a column, read from the front of `bytes`
+///
+/// Besides fixed-width types this follows the length prefixes of strings and
+/// the offsets of arrays and maps. Returns `None` for layouts it does not know.
+fn encoded_size(bytes: &[u8], descriptor: &TypeDescriptor, rows: usize) -> Result<Option<usize>> {
+    if let Some(width) = encoded_width(descriptor) {
+        return Ok(Some(rows * width));
+    }
+    match descriptor {
+        TypeDescriptor::Simple(name) if name == "String" => {
+            let mut offset = 0usize;
+            for _ in 0..rows {
+                let end = offset.saturating_add(8).saturating_add(peek_u64(bytes, offset)? as usize);
+                if end > bytes.len() {
+                    return Err(insufficient(bytes, end));
+                }
+                offset = end;
+            }
+            Ok(Some(offset))
+        }
+        TypeDescriptor::Nullable(inner) => {
+            let values = bytes.get(rows..).ok_or_else(|| insufficient(bytes, rows))?;
+            Ok(encoded_size(values, inner, rows)?.map(|size| rows + size))
+        }
+        TypeDescriptor::Array(inner) => sequence_size(bytes, rows * 8, &[inner.as_ref()], last_offset(bytes, rows)?),
+        TypeDescriptor::Map(key, value) => {
+            sequence_size(bytes, rows * 8, &[key.as_ref(), value.as_ref()], last_offset(bytes, rows)?)
+        }
+        TypeDescriptor::Tuple(elements) => {
+            let elements: Vec<&TypeDescriptor> = elements.iter().map(|(_, element)| element).collect();
+            sequence_size(bytes, 0, &elements, rows)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// End of consecutive columns of `rows` values each, starting at `offset`
+fn sequence_size(bytes: &[u8], offset: usize, elements: &[&TypeDescriptor], rows: usize) -> Result<Option<usize>> {
+    let mut offset = offset;
+    for element in elements {
+        let rest = bytes.get(offset..).ok_or_else(|| insufficient(bytes, offset))?;
+        match encoded_size(rest, element, rows)? {
+            Some(size) => offset += size,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(offset))
+}
+
+/// Number of elements of an array or map column, from its last offset
+fn last_offset(bytes: &[u8], rows: usize) -> Result<usize> {
+    if rows == 0 {
+        return Ok(0);
+    }
+    Ok(peek_u64(bytes, (rows - 1) * 8)? as usize)
+}
+
+fn peek_u64(bytes: &[u8], offset: usize) -> Result<u64> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|word| u64::from_le_bytes(word.try_into().expect("slice of 8 bytes")))
+        .ok_or_else(|| insufficient(bytes, offset + 8))
+}
+
+fn insufficient(bytes: &[u8], needed: usize) -> Error {
+    Error::Protocol(format!("Insufficient data: need {} bytes, have {}", needed, bytes.len()))
+}
+
+/// Size in bytes of each value of a fixed-width type
+pub(crate) fn encoded_width(descriptor: &TypeDescriptor) -> Option<usize> {
+    Some(match descriptor {
+        TypeDescriptor::Simple(name) => match name.as_str() {
+            "UInt8" | "Int8" | "Bool" => 1,
+            "UInt16" | "Int16" | "Date" | "BFloat16" => 2,
+            "UInt32" | "Int32" | "Float32" | "Date32" | "IPv4" | "Time" => 4,
+            "UInt64" | "Int64" | "Float64" | "Time64" => 8,
+            "UInt128" | "Int128" | "UUID" | "IPv6" => 16,
+            "UInt256" | "Int256" => 32,
+            _ => return None,
+        },
+        TypeDescriptor::FixedString(length) => *length,
+        TypeDescriptor::Decimal { precision, .. } => match precision {
+            0..=9 => 4,
+            10..=18 => 8,
+            19..=38 => 16,
+            _ => 32,
+        },
+        TypeDescriptor::DateTime { .. } => 4,
+        TypeDescriptor::DateTime64 { .. } => 8,
+        TypeDescriptor::Enum { bits, .. } => *bits as usize / 8,
+        TypeDescriptor::Other(name) if name.starts_with("Time64(") => 8,
+        _ => return None,
+    })
+}
+
+fn ensure(buf: &BytesMut, needed: usize) -> Result<()> {
+    if buf.remaining() < needed {
+        return Err(Error::Protocol(format!(
+            "Insufficient data: need {} bytes, have {}",
+            needed,
+            buf.remaining()
+        )));
+    }
+    Ok(())
+}
+
+fn read_u64(buf: &mut BytesMut) -> Result<u64> {
+    ensure(buf, 8)?;
+    Ok(buf.get_u64_le())
+}
+
+fn read_string(buf: &mut BytesMut) -> Result<String> {
+    let len = read_u64(buf)? as usize;
+    ensure(buf, len)?;
+    String::from_utf8(buf.copy_to_bytes(len).to_vec()).map_err(|e| Error::Protocol(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+    use bytes::BufMut;
+
+    fn put_string(buf: &mut BytesMut, s: &str) {
+        buf.put_u64_le(s.len() as u64);
+        buf.put_slice(s.as_bytes());
+    }
+
+    /// Block with an `id UInt32`, a `t Time` and a `name String` column
+    fn block_with_unknown_type() -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u64_le(3);
+        buf.put_u64_le(2);
+        put_string(&mut buf, "id");
+        put_string(&mut buf, "UInt32");
+        buf.put_u32_le(1);
+        buf.put_u32_le(2);
+        put_string(&mut buf, "t");
+        put_string(&mut buf, "Time");
+        buf.put_i32_le(3600);
+        buf.put_i32_le(-1);
+        put_string(&mut buf, "name");
+        put_string(&mut buf, "String");
+        put_string(&mut buf, "a");
+        put_string(&mut buf, "b");
+        buf
+    }
+
+    #[test]
+    fn test_decode_modes() {
+        let err = read_block(&mut block_with_unknown_type(), DecodeMode::Strict).unwrap_err();
+        assert!(err.to_string().contains("Time"));
+
+        let block = read_block(&mut block_with_unknown_type(), DecodeMode::Lenient).unwrap();
+        assert_eq!(block.row_count, 2);
+        assert_eq!(block.column_count(), 3);
+        assert_eq!(
+            block.get_column("t").unwrap().data.get_value(0),
+            Some(Value::Unsupported(3600i32.to_le_bytes().to_vec(), "Time".to_string()))
+        );
+        assert_eq!(
+            block.get_column("name").unwrap().data.get_value(1),
+            Some(Value::String("b".to_string()))
+        );
+
+        let block = read_block(&mut block_with_unknown_type(), DecodeMode::SkipUnknownColumns).unwrap();
+        assert_eq!(block.column_count(), 2);
+        assert!(block.get_column("t").is_none());
+        assert_eq!(block.get_column("id").unwrap().data.get_value(1), Some(Value::UInt32(2)));
+    }
+
+    #[test]
+    fn test_nullable_and_variable_width_unknown_types() {
+        let mut buf = BytesMut::new();
+        buf.put_slice(&[0, 1]);
+        buf.put_u16_le(7);
+        buf.put_u16_le(0);
+        let data = read_column(&mut buf, "Nullable(BFloat16)", 2, DecodeMode::Lenient).unwrap().unwrap();
+        assert_eq!(
+            data.get_value(0),
+            Some(Value::Nullable(Some(Box::new(Value::Unsupported(vec![7, 0], "BFloat16".to_string())))))
+        );
+        assert_eq!(data.get_value(1), Some(Value::Nullable(None)));
+
+        let mut buf = BytesMut::from(&[0u8; 16][..]);
+        let err = read_column(&mut buf, "JSON", 2, DecodeMode::Lenient).unwrap_err();
+        assert!(err.to_string().contains("encoded size is unknown"));
+
+        let mut buf = BytesMut::new();
+        buf.put_u64_le(0);
+        assert_eq!(read_block(&mut buf, DecodeMode::Strict).unwrap().column_count(), 0);
+    }
+
+    #[test]
+    fn test_skipped_columns_and_partial_blocks() {
+        // `id UInt32`, `tags Map(String, UInt64)`, `name String`, `doc JSON`, `n UInt8`
+        let mut buf = BytesMut::new();
+        buf.put_u64_le(5);
+        buf.put_u64_le(2);
+        put_string(&mut buf, "id");
+        put_string(&mut buf, "UInt32");
+        buf.put_u32_le(1);
+        buf.put_u32_le(2);
+        put_string(&mut buf, "tags");
+        put_string(&mut buf, "Map(String, UInt64)");
+        buf.put_u64_le(1);
+        buf.put_u64_le(3);
+        for key in ["a", "bb", "ccc"] {
+            put_string(&mut buf, key);
+        }
+        for value in [1, 2, 3] {
+            buf.put_u64_le(value);
+        }
+        put_string(&mut buf, "name");
+        put_string(&mut buf, "String");
+        put_string(&mut buf, "x");
+        put_string(&mut buf, "y");
+        put_string(&mut buf, "doc");
+        put_string(&mut buf, "JSON");
+        buf.put_slice(&[0xde, 0xad]);
+        put_string(&mut buf, "n");
+        put_string(&mut buf, "UInt8");
+        buf.put_slice(&[1, 2]);
+
+        let err = read_block(&mut buf.clone(), DecodeMode::Strict).unwrap_err();
+        assert!(err.to_string().contains("Map(String, UInt64)"));
+
+        for mode in [DecodeMode::Lenient, DecodeMode::SkipUnknownColumns] {
+            let mut decoder = BlockDecoder::new(DecodeOptions { mode, ..DecodeOptions::default() });
+            let block = decoder.read_block(&mut buf.clone()).unwrap();
+            assert!(decoder.is_partial());
+            assert_eq!(block.row_count, 2);
+            assert_eq!(block.columns().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["id", "name"]);
+            assert_eq!(block.get_column("name").unwrap().data.get_value(1), Some(Value::String("y".into())));
+            assert_eq!(
+                decoder.warnings(),
+                [
+                    "Column tags: skipped unsupported type Map(String, UInt64)",
+                    "Column doc: Cannot skip column type JSON: its encoded size is unknown; \
+                     kept the 2 columns before it and dropped the 1 after it",
+                ]
+            );
+        }
+
+        let mut decoder = BlockDecoder::new(DecodeOptions {
+            mode: DecodeMode::Lenient,
+            ..DecodeOptions::default()
+        });
+        decoder.read_block(&mut block_with_unknown_type()).unwrap();
+        assert!(!decoder.is_partial());
+        assert_eq!(decoder.warnings(), ["Column t: kept raw values of unsupported type Time"]);
+    }
+
+    fn lenient() -> BlockDecoder {
+        BlockDecoder::new(DecodeOptions {
+            validation: ValidationMode::Lenient,
+            ..DecodeOptions::default()
+        })
+    }
+
+    #[test]
+    fn test_utf8_validation() {
+        let strings = || {
+            let mut buf = BytesMut::new();
+            put_string(&mut buf, "ok");
+            buf.put_u64_le(2);
+            buf.put_slice(&[0x66, 0xff]);
+            buf
+        };
+        let err = read_column(&mut strings(), "String", 2, DecodeMode::Strict).unwrap_err();
+        assert!(matches!(err, Error::InvalidData(_)));
+
+        let mut decoder = lenient();
+        let data = decoder.read_column(&mut strings(), "s", "String", 2).unwrap().unwrap();
+        assert_eq!(data.get_value(1), Some(Value::String("f\u{fffd}".to_string())));
+        assert_eq!(decoder.warnings(), ["Column s: replaced 1 invalid UTF-8 strings"]);
+        assert_eq!(decoder.take_warnings().len(), 1);
+        assert!(decoder.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_date_range_validation() {
+        let mut buf = BytesMut::new();
+        buf.put_i32_le(19_000);
+        buf.put_i32_le(-40_000);
+        assert!(read_column(&mut buf.clone(), "Date32", 2, DecodeMode::Strict).is_err());
+        let data = lenient().read_column(&mut buf, "d", "Date32", 2).unwrap().unwrap();
+        assert_eq!(data.get_value(1), Some(Value::Date(min_datetime().date())));
+
+        let mut buf = BytesMut::new();
+        buf.put_i64_le(1_700_000_000_123);
+        buf.put_i64_le(i64::MAX);
+        let mut decoder = lenient();
+        let data = decoder.read_column(&mut buf, "t", "DateTime64(3)", 2).unwrap().unwrap();
+        let expected = chrono::DateTime::from_timestamp_millis(1_700_000_000_123).unwrap().naive_utc();
+        assert_eq!(data.get_value(0), Some(Value::DateTime64(expected)));
+        assert_eq!(data.get_value(1), Some(Value::DateTime64(max_datetime())));
+        assert_eq!(decoder.warnings(), ["Column t: replaced 1 invalid datetimes"]);
+
+        // Values under NULLs are not validated
+        let mut buf = BytesMut::new();
+        buf.put_u8(1);
+        buf.put_i32_le(-40_000);
+        let data = read_column(&mut buf, "Nullable(Date32)", 1, DecodeMode::Strict).unwrap().unwrap();
+        assert_eq!(data.get_value(0), Some(Value::Nullable(None)));
+    }
+
+    #[test]
+    fn test_decimal_and_enum_validation() {
+        let mut buf = BytesMut::new();
+        buf.put_i32_le(12_345);
+        buf.put_i32_le(-100_000);
+        assert!(read_column(&mut buf.clone(), "Decimal(5, 2)", 2, DecodeMode::Strict).is_err());
+        let mut decoder = lenient();
+        let data = decoder.read_column(&mut buf, "price", "Decimal(5, 2)", 2).unwrap().unwrap();
+        assert_eq!(data.get_value(0), Some(Value::Decimal32(Decimal32::new(12_345, 2))));
+        assert_eq!(data.get_value(1), Some(Value::Decimal32(Decimal32::new(-99_999, 2))));
+        assert_eq!(decoder.warnings(), ["Column price: replaced 1 invalid decimals"]);
+
+        let enum_type = "Enum8('a' = 1, 'b' = 2)";
+        let mut buf = BytesMut::from(&[2u8, 5][..]);
+        let err = read_column(&mut buf.clone(), enum_type, 2, DecodeMode::Strict).unwrap_err();
+        assert!(err.to_string().contains("not declared"));
+        let mut decoder = lenient();
+        let data = decoder.read_column(&mut buf, "e", enum_type, 2).unwrap().unwrap();
+        let Some(Value::Enum8(value)) = data.get_value(1) else {
+            panic!("expected an Enum8");
+        };
+        assert_eq!(value.name().map(String::as_str), Some("a"));
+        assert_eq!(decoder.warnings(), ["Column e: replaced 1 invalid enum values"]);
+    }
+
+    #[test]
+    fn test_result_size_limits() {
+        let uint_block = |rows: u32| {
+            let mut buf = BytesMut::new();
+            buf.put_u64_le(1);
+            buf.put_u64_le(rows as u64);
+            put_string(&mut buf, "n");
+            put_string(&mut buf, "UInt32");
+            (0..rows).for_each(|n| buf.put_u32_le(n));
+            buf
+        };
+
+        let mut decoder = BlockDecoder::new(DecodeOptions { max_rows: Some(5), ..DecodeOptions::default() });
+        decoder.read_block(&mut uint_block(3)).unwrap();
+        let err = decoder.read_block(&mut uint_block(3)).unwrap_err();
+        assert!(matches!(err, Error::ResultTooLarge(_)));
+        assert_eq!(decoder.rows_read(), 6);
+
+        let mut decoder = BlockDecoder::new(DecodeOptions { max_bytes: Some(64), ..DecodeOptions::default() });
+        decoder.read_block(&mut uint_block(4)).unwrap();
+        assert_eq!(decoder.bytes_read(), 16 + 9 + 14 + 16);
+        let err = decoder.read_block(&mut uint_block(1)).unwrap_err();
+        assert!(err.to_string().contains("max_result_bytes is 64"));
+    }
+}
@@ -0,0 +1,348 @@
+//! Encoding of column data in client blocks
+//!
+//! The layout mirrors [`read_block`](super::read_block). Nested types are
+//! written the way the server expects them: an `Array` column is its row
+//! offsets followed by one column of all elements, a `Tuple` column is one
+//! column per element, and a `Nullable` column is its null map followed by the
+//! inner column. A `Nullable` inside an `Array` or `Tuple` therefore gets a
+//! null map covering only the elements at that level. Rows under a null are
+//! written as a zero placeholder of the inner type.
+
+use super::column_reader::{encoded_width, unix_epoch};
+use crate::error::{Error, Result};
+use crate::types::{Block, ColumnData, IPv4, IPv6, Int256, TypeDescriptor, UInt256, Value};
+use bytes::{BufMut, BytesMut};
+
+/// Write a block of columns
+pub fn write_block(buf: &mut BytesMut, block: &Block) -> Result<()> {
+    buf.put_u64_le(block.columns.len() as u64);
+    if block.columns.is_empty() {
+        return Ok(());
+    }
+    buf.put_u64_le(block.row_count as u64);
+
+    for column in &block.columns {
+        if column.len() != block.row_count {
+            return Err(Error::InvalidData(format!(
+                "Column {} has {} rows, block has {}",
+                column.name,
+                column.len(),
+                block.row_count
+            )));
+        }
+        write_string(buf, &column.name);
+        write_string(buf, &column.type_name);
+        write_column(buf, &column.type_name, &column.data)
+            .map_err(|e| Error::Protocol(format!("Failed to write column {}: {}", column.name, e)))?;
+    }
+    Ok(())
+}
+
+/// Write the data of one column
+pub fn write_column(buf: &mut BytesMut, type_name: &str, data: &ColumnData) -> Result<()> {
+    let descriptor = TypeDescriptor::parse(type_name)?;
+    let values: Vec<Value> = (0..data.len()).filter_map(|index| data.get_value(index)).collect();
+    let values: Vec<Option<&Value>> = values.iter().map(Some).collect();
+    write_values(buf, &descriptor, &values)
+}
+
+/// Write one column of values, where `None` marks a placeholder under a null
+fn write_values(buf: &mut BytesMut, descriptor: &TypeDescriptor, values: &[Option<&Value>]) -> Result<()> {
+    macro_rules! fixed {
+        ($variant:ident, $zero:expr, $put:expr) => {
+            for value in values {
+                match value {
+                    Some(Value::$variant(v)) => $put(&mut *buf, v),
+                    None => $put(&mut *buf, &$zero),
+                    Some(other) => return Err(mismatch(descriptor, other)),
+                }
+            }
+        };
+    }
+
+    match descriptor {
+        TypeDescriptor::Simple(name) => match name.as_str() {
+            "UInt8" | "Bool" => fixed!(UInt8, 0, |b: &mut BytesMut, v: &u8| b.put_u8(*v)),
+            "UInt16" => fixed!(UInt16, 0, |b: &mut BytesMut, v: &u16| b.put_u16_le(*v)),
+            "UInt32" => fixed!(UInt32, 0, |b: &mut BytesMut, v: &u32| b.put_u32_le(*v)),
+            "UInt64" => fixed!(UInt64, 0, |b: &mut BytesMut, v: &u64| b.put_u64_le(*v)),
+            "UInt128" => fixed!(UInt128, 0, |b: &mut BytesMut, v: &u128| b.put_u128_le(*v)),
+            "UInt256" => fixed!(UInt256, UInt256::ZERO, |b: &mut BytesMut, v: &UInt256| b.put_slice(&v.to_le_bytes())),
+            "Int8" => fixed!(Int8, 0, |b: &mut BytesMut, v: &i8| b.put_i8(*v)),
+            "Int16" => fixed!(Int16, 0, |b: &mut BytesMut, v: &i16| b.put_i16_le(*v)),
+            "Int32" => fixed!(Int32, 0, |b: &mut BytesMut, v: &i32| b.put_i32_le(*v)),
+            "Int64" => fixed!(Int64, 0, |b: &mut BytesMut, v: &i64| b.put_i64_le(*v)),
+            "Int128" => fixed!(Int128, 0, |b: &mut BytesMut, v: &i128| b.put_i128_le(*v)),
+            "Int256" => fixed!(Int256, Int256::ZERO, |b: &mut BytesMut, v: &Int256| b.put_slice(&v.to_le_bytes())),
+            "Float32" => fixed!(Float32, 0.0, |b: &mut BytesMut, v: &f32| b.put_f32_le(*v)),
+            "Float64" => fixed!(Float64, 0.0, |b: &mut BytesMut, v: &f64| b.put_f64_le(*v)),
+            "UUID" => fixed!(UUID, uuid::Uuid::nil(), |b: &mut BytesMut, v: &uuid::Uuid| {
+                let (high, low) = v.as_u64_pair();
+                b.put_u64_le(high);
+                b.put_u64_le(low);
+            }),
+            "IPv4" => fixed!(IPv4, IPv4::default(), |b: &mut BytesMut, v: &IPv4| b.put_u32_le(v.to_u32())),
+            "IPv6" => fixed!(IPv6, IPv6::default(), |b: &mut BytesMut, v: &IPv6| b.put_slice(&v.to_octets())),
+            "String" => fixed!(String, String::new(), |b: &mut BytesMut, v: &String| write_string(b, v)),
+            "Date" | "Date32" => {
+                for value in values {
+                    let days = match value {
+                        Some(Value::Date(date)) => (*date - unix_epoch().date()).num_days(),
+                        None => 0,
+                        Some(other) => return Err(mismatch(descriptor, other)),
+                    };
+                    if name == "Date" {
+                        let days = u16::try_from(days)
+                            .map_err(|_| Error::InvalidData(format!("{} days since epoch does not fit Date", days)))?;
+                        buf.put_u16_le(days);
+                    } else {
+                        buf.put_i32_le(days as i32);
+                    }
+                }
+            }
+            _ => write_unsupported(buf, descriptor, values)?,
+        },
+        TypeDescriptor::FixedString(length) => {
+            for value in values {
+                match value {
+                    Some(Value::FixedString(v)) if v.as_bytes().len() == *length => buf.put_slice(v.as_bytes()),
+                    None => buf.put_bytes(0, *length),
+                    Some(other) => return Err(mismatch(descriptor, other)),
+                }
+            }
+        }
+        TypeDescriptor::DateTime { .. } => {
+            for value in values {
+                let seconds = match value {
+                    Some(Value::DateTime(v)) => v.and_utc().timestamp(),
+                    None => 0,
+                    Some(other) => return Err(mismatch(descriptor, other)),
+                };
+                let seconds = u32::try_from(seconds)
+                    .map_err(|_| Error::InvalidData(format!("{} seconds since epoch does not fit DateTime", seconds)))?;
+                buf.put_u32_le(seconds);
+            }
+        }
+        TypeDescriptor::DateTime64 { precision, .. } if *precision <= 9 => {
+            let scale = 10i64.pow(*precision as u32);
+            for value in values {
+                let ticks = match value {
+                    Some(Value::DateTime64(v)) => {
+                        let datetime = v.and_utc();
+                        datetime.timestamp() * scale
+                            + datetime.timestamp_subsec_nanos() as i64 / 10i64.pow(9 - *precision as u32)
+                    }
+                    None => 0,
+                    Some(other) => return Err(mismatch(descriptor, other)),
+                };
+                buf.put_i64_le(ticks);
+            }
+        }
+        TypeDescriptor::Decimal { precision, .. } if *precision <= 38 => {
+            let width = encoded_width(descriptor).unwrap_or(16);
+            for value in values {
+                match (width, value) {
+                    (4, Some(Value::Decimal32(v))) => buf.put_i32_le(v.value()),
+                    (8, Some(Value::Decimal64(v))) => buf.put_i64_le(v.value()),
+                    (16, Some(Value::Decimal128(v))) => buf.put_i128_le(v.value()),
+                    (_, None) => buf.put_bytes(0, width),
+                    (_, Some(other)) => return Err(mismatch(descriptor, other)),
+                }
+            }
+        }
+        TypeDescriptor::Enum { bits, .. } => {
+            for value in values {
+                match (bits, value) {
+                    (8, Some(Value::Enum8(v))) => buf.put_i8(v.value()),
+                    (16, Some(Value::Enum16(v))) => buf.put_i16_le(v.value()),
+                    (_, None) => buf.put_bytes(0, *bits as usize / 8),
+                    (_, Some(other)) => return Err(mismatch(descriptor, other)),
+                }
+            }
+        }
+        TypeDescriptor::Nullable(inner) => {
+            let inner_values: Vec<Option<&Value>> = values
+                .iter()
+                .map(|value| match value {
+                    None | Some(Value::Null) | Some(Value::Nullable(None)) => None,
+                    Some(Value::Nullable(Some(v))) => Some(&**v),
+                    Some(v) => Some(*v),
+                })
+                .collect();
+            for value in &inner_values {
+                buf.put_u8(value.is_none() as u8);
+            }
+            write_values(buf, inner, &inner_values)?;
+        }
+        TypeDescriptor::Array(inner) => {
+            let mut elements = Vec::new();
+            for value in values {
+                match value {
+                    Some(Value::Array(items)) => elements.extend(items.iter().map(Some)),
+                    None => {}
+                    Some(other) => return Err(mismatch(descriptor, other)),
+                }
+                buf.put_u64_le(elements.len() as u64);
+            }
+            write_values(buf, inner, &elements)?;
+        }
+        TypeDescriptor::Tuple(elements) => {
+            for value in values {
+                match value {
+                    Some(Value::Tuple(fields)) if fields.len() == elements.len() => {}
+                    None => {}
+                    Some(other) => return Err(mismatch(descriptor, other)),
+                }
+            }
+            for (index, (_, element)) in elements.iter().enumerate() {
+                let column: Vec<Option<&Value>> = values
+                    .iter()
+                    .map(|value| match value {
+                        Some(Value::Tuple(fields)) => Some(&fields[index]),
+                        _ => None,
+                    })
+                    .collect();
+                write_values(buf, element, &column)?;
+            }
+        }
+        _ => write_unsupported(buf, descriptor, values)?,
+    }
+    Ok(())
+}
+
+/// Write raw values of a type the client cannot encode
+fn write_unsupported(buf: &mut BytesMut, descriptor: &TypeDescriptor, values: &[Option<&Value>]) -> Result<()> {
+    let width = encoded_width(descriptor);
+    for value in values {
+        match (value, width) {
+            (Some(Value::Unsupported(bytes, _)), Some(width)) if bytes.len() == width => buf.put_slice(bytes),
+            (None, Some(width)) => buf.put_bytes(0, width),
+            _ => return Err(Error::Unsupported(format!("Cannot encode column type {}", descriptor))),
+        }
+    }
+    Ok(())
+}
+
+fn mismatch(descriptor: &TypeDescriptor, value: &Value) -> Error {
+    Error::TypeConversion(format!("Cannot write {} value as {}", value.type_name(), descriptor))
+}
+
+fn write_string(buf: &mut BytesMut, s: &str) {
+    buf.put_u64_le(s.len() as u64);
+    buf.put_slice(s.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{read_block, read_column, DecodeMode};
+    use crate::types::Column;
+
+    fn nullable(value: Option<Value>) -> Value {
+        Value::Nullable(value.map(Box::new))
+    }
+
+    fn round_trip(type_name: &str, data: ColumnData) -> ColumnData {
+        let rows = data.len();
+        let mut buf = BytesMut::new();
+        write_column(&mut buf, type_name, &data).unwrap();
+        let decoded = read_column(&mut buf, type_name, rows, DecodeMode::Strict).unwrap().unwrap();
+        assert!(buf.is_empty());
+        decoded
+    }
+
+    #[test]
+    fn test_wide_integers() {
+        let data = ColumnData::UInt256(vec![UInt256::MAX, UInt256::from(7u8)]);
+        let decoded = round_trip("UInt256", data.clone());
+        assert_eq!(decoded.get_value(0), data.get_value(0));
+        assert_eq!(decoded.get_value(1), data.get_value(1));
+
+        let data = ColumnData::Int256(vec![Int256::MIN, Int256::from(-1i8)]);
+        let mut buf = BytesMut::new();
+        write_column(&mut buf, "Int256", &data).unwrap();
+        assert_eq!(&buf[32..], &[0xff; 32]);
+        let decoded = read_column(&mut buf, "Int256", 2, DecodeMode::Strict).unwrap().unwrap();
+        assert_eq!(decoded.get_value(0), Some(Value::Int256(Int256::MIN)));
+    }
+
+    #[test]
+    fn test_array_of_nullable() {
+        let data = ColumnData::Array(vec![
+            vec![nullable(Some(Value::String("a".into()))), nullable(None)],
+            Vec::new(),
+            vec![nullable(None)],
+        ]);
+        let decoded = round_trip("Array(Nullable(String))", data.clone());
+        for row in 0..3 {
+            assert_eq!(decoded.get_value(row), data.get_value(row));
+        }
+
+        // Offsets are cumulative and the null map covers only the elements
+        let mut buf = BytesMut::new();
+        write_column(&mut buf, "Array(Nullable(UInt8))", &ColumnData::Array(vec![
+            vec![nullable(Some(Value::UInt8(7))), nullable(None)],
+            vec![nullable(Some(Value::UInt8(9)))],
+        ]))
+        .unwrap();
+        let mut expected = BytesMut::new();
+        expected.put_u64_le(2);
+        expected.put_u64_le(3);
+        expected.put_slice(&[0, 1, 0, 7, 0, 9]);
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn test_tuple_with_nullable_elements() {
+        let data = ColumnData::Tuple(vec![
+            vec![Value::UInt8(1), nullable(Some(Value::Int32(-5))), Value::Array(vec![nullable(None)])],
+            vec![Value::UInt8(2), nullable(None), Value::Array(Vec::new())],
+        ]);
+        let type_name = "Tuple(id UInt8, delta Nullable(Int32), tags Array(Nullable(String)))";
+        let decoded = round_trip(type_name, data.clone());
+        assert_eq!(decoded.get_value(0), data.get_value(0));
+        assert_eq!(decoded.get_value(1), data.get_value(1));
+
+        let mut buf = BytesMut::new();
+        let err = write_column(&mut buf, "Tuple(UInt8, String)", &ColumnData::Tuple(vec![vec![Value::UInt8(1)]]));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_ip_addresses() {
+        let mut buf = BytesMut::new();
+        buf.put_u32_le(0xC0A8_0001);
+        buf.put_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 10, 0, 0, 1]);
+        let v4 = read_column(&mut buf, "IPv4", 1, DecodeMode::Strict).unwrap().unwrap();
+        assert_eq!(v4.get_value(0).unwrap().to_string(), "192.168.0.1");
+        let v6 = read_column(&mut buf, "IPv6", 1, DecodeMode::Strict).unwrap().unwrap();
+        assert_eq!(v6.get_value(0).unwrap().to_string(), "::ffff:10.0.0.1");
+
+        let data = ColumnData::Nullable(vec![Some(Value::IPv6(IPv6::from_str("2001:db8::1").unwrap())), None]);
+        let decoded = round_trip("Nullable(IPv6)", data.clone());
+        assert_eq!(decoded.get_value(0), data.get_value(0));
+        assert_eq!(decoded.get_value(1), Some(nullable(None)));
+        let data = ColumnData::IPv4(vec![IPv4::from_octets(10, 1, 2, 3)]);
+        assert_eq!(round_trip("IPv4", data.clone()).get_value(0), data.get_value(0));
+    }
+
+    #[test]
+    fn test_block_round_trip() {
+        let block = Block::with_columns(vec![
+            Column::new("id", "UInt64", ColumnData::UInt64(vec![1, 2])),
+            Column::new(
+                "scores",
+                "Array(Nullable(Float64))",
+                ColumnData::Array(vec![vec![nullable(Some(Value::Float64(0.5)))], vec![nullable(None)]]),
+            ),
+        ]);
+        let mut buf = BytesMut::new();
+        write_block(&mut buf, &block).unwrap();
+        let decoded = read_block(&mut buf, DecodeMode::Strict).unwrap();
+        assert_eq!(decoded.row_count, 2);
+        assert_eq!(
+            decoded.get_column("scores").unwrap().get_value(1),
+            Some(Value::Array(vec![nullable(None)]))
+        );
+    }
+}
@@ -0,0 +1,744 @@
+//! Incremental framing of server packets
+//!
+//! On the wire a server packet is a VarUInt packet type followed by a payload
+//! whose length is not sent: it follows from the structure of the packet. A
+//! [`FrameDecoder`] buffers whatever bytes arrive, and once a packet type is
+//! known it walks the payload structure to find where the packet ends. Until
+//! all of it has arrived [`FrameDecoder::decode`] returns `None` and keeps its
+//! state, so packets may be split across any number of reads.
+//!
+//! Data blocks are walked column by column, which needs the encoded size of
+//! every column type. Types whose size cannot be derived, and compressed
+//! blocks, are reported as unsupported.
+//!
+//! With [`Framing::Headers`] the decoder instead reads the 16-byte type and
+//! size header that [`ProtocolWriter`](super::ProtocolWriter) puts in front of
+//! each packet, so one decoder serves both formats.
+
+use super::column_reader::encoded_width;
+use super::constants::MAX_PACKET_SIZE;
+use super::{varint_len, ClientPing, Packet, PacketDirection, PacketTracer, PacketType, ProtocolStats, PACKET_HEADER_SIZE};
+use crate::error::{Error, Result};
+use crate::types::TypeDescriptor;
+use bytes::BytesMut;
+
+/// Revision from which the server hello carries a timezone
+pub const TIMEZONE_REVISION: u64 = 54058;
+/// Revision from which the server hello carries a display name
+pub const DISPLAY_NAME_REVISION: u64 = 54372;
+/// Revision from which the server hello carries the version patch
+pub const VERSION_PATCH_REVISION: u64 = 54401;
+/// Revision from which progress packets carry written rows and bytes
+pub const CLIENT_WRITE_INFO_REVISION: u64 = 54420;
+/// Revision from which block columns carry a custom serialization flag
+pub const CUSTOM_SERIALIZATION_REVISION: u64 = 54454;
+/// Revision from which progress packets carry the elapsed time
+pub const PROGRESS_ELAPSED_REVISION: u64 = 54460;
+/// Revision from which the server hello carries password complexity rules
+pub const PASSWORD_RULES_REVISION: u64 = 54461;
+/// Revision from which the server hello carries an interserver nonce
+pub const INTERSERVER_NONCE_REVISION: u64 = 54462;
+/// Revision from which progress packets carry the total bytes to read
+pub const PROGRESS_TOTAL_BYTES_REVISION: u64 = 54463;
+/// Revision from which profile info carries rows before aggregation
+pub const ROWS_BEFORE_AGGREGATION_REVISION: u64 = 54469;
+
+/// How packets are delimited on the stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// VarUInt packet type; the payload length follows from its structure
+    #[default]
+    Native,
+    /// Little-endian u64 packet type and payload size ahead of the payload
+    Headers,
+}
+
+impl Framing {
+    /// Encode a ping packet
+    pub fn ping(&self) -> Vec<u8> {
+        let packet_type = PacketType::ClientPing.to_u64();
+        match self {
+            // Packet codes below 128 are a single VarUInt byte, and a ping has no payload
+            Framing::Native => vec![packet_type as u8],
+            Framing::Headers => {
+                let mut payload = BytesMut::new();
+                ClientPing::new().serialize(&mut payload).expect("ping serializes into memory");
+                let mut packet = Vec::with_capacity(PACKET_HEADER_SIZE + payload.len());
+                packet.extend_from_slice(&packet_type.to_le_bytes());
+                packet.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+                packet.extend_from_slice(&payload);
+                packet
+            }
+        }
+    }
+
+    /// Get the size of the bytes in front of the payload of a packet
+    pub fn header_len(&self, packet_type: u64) -> usize {
+        match self {
+            Framing::Native => varint_len(packet_type),
+            Framing::Headers => PACKET_HEADER_SIZE,
+        }
+    }
+}
+
+/// What a [`FrameDecoder`] is waiting for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameState {
+    /// The VarUInt packet type
+    PacketType,
+    /// The rest of the payload of a packet of the given type
+    Payload {
+        /// Packet type code
+        packet_type: u64,
+    },
+}
+
+/// A complete server packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Packet type code
+    pub packet_type: u64,
+    /// Payload bytes, without the packet type
+    pub payload: BytesMut,
+}
+
+impl Frame {
+    /// Get the packet type, if it is a known one
+    pub fn kind(&self) -> Option<PacketType> {
+        PacketType::from_u64(self.packet_type)
+    }
+}
+
+/// State machine that cuts a byte stream into server packets
+///
+/// Every packet taken out of the decoder is traced and counted with the
+/// tracer and stats it was given.
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    state: FrameState,
+    buffer: BytesMut,
+    revision: u64,
+    compressed: bool,
+    framing: Framing,
+    tracer: Option<PacketTracer>,
+    stats: Option<ProtocolStats>,
+}
+
+impl FrameDecoder {
+    /// Create a decoder for a connection using protocol `revision`
+    pub fn new(revision: u64) -> Self {
+        Self {
+            state: FrameState::PacketType,
+            buffer: BytesMut::new(),
+            revision,
+            compressed: false,
+            framing: Framing::Native,
+            tracer: None,
+            stats: None,
+        }
+    }
+
+    /// Set how packets are delimited
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Get how packets are delimited
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Trace every packet decoded with the given tracer
+    pub fn with_tracer(mut self, tracer: PacketTracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Count every packet decoded in the given connection stats
+    pub fn with_stats(mut self, stats: ProtocolStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Set whether data blocks are compressed
+    pub fn with_compression(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Get the protocol revision that decides which fields are present
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Set the protocol revision
+    pub fn set_revision(&mut self, revision: u64) {
+        self.revision = revision;
+    }
+
+    /// Get what the decoder is waiting for
+    pub fn state(&self) -> FrameState {
+        self.state
+    }
+
+    /// Get the number of bytes received but not yet returned in a frame
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Append bytes received from the server
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Take the next complete packet, if all of it has arrived
+    ///
+    /// A server hello lowers the revision to the one the server announced,
+    /// so later packets are framed with the negotiated revision.
+    pub fn decode(&mut self) -> Result<Option<Frame>> {
+        let frame = match self.framing {
+            Framing::Native => self.decode_native()?,
+            Framing::Headers => self.decode_headers()?,
+        };
+        if let Some(frame) = &frame {
+            self.record(frame);
+        }
+        Ok(frame)
+    }
+
+    /// Take the next packet behind a type and size header
+    fn decode_headers(&mut self) -> Result<Option<Frame>> {
+        if self.buffer.len() < PACKET_HEADER_SIZE {
+            return Ok(None);
+        }
+        let packet_type = u64::from_le_bytes(self.buffer[0..8].try_into().unwrap());
+        let size = u64::from_le_bytes(self.buffer[8..16].try_into().unwrap()) as usize;
+        if size > MAX_PACKET_SIZE {
+            return Err(Error::Protocol(format!("Packet size {} exceeds {} bytes", size, MAX_PACKET_SIZE)));
+        }
+        self.state = FrameState::Payload { packet_type };
+        if self.buffer.len() < PACKET_HEADER_SIZE + size {
+            return Ok(None);
+        }
+
+        let _ = self.buffer.split_to(PACKET_HEADER_SIZE);
+        let payload = self.buffer.split_to(size);
+        self.state = FrameState::PacketType;
+        Ok(Some(Frame { packet_type, payload }))
+    }
+
+    /// Trace and count a decoded packet
+    fn record(&self, frame: &Frame) {
+        if let Some(tracer) = &self.tracer {
+            let mut header = [0u8; PACKET_HEADER_SIZE];
+            header[0..8].copy_from_slice(&frame.packet_type.to_le_bytes());
+            header[8..16].copy_from_slice(&(frame.payload.len() as u64).to_le_bytes());
+            tracer.trace(PacketDirection::Received, &header, &frame.payload);
+        }
+        if let Some(stats) = &self.stats {
+            let size = self.framing.header_len(frame.packet_type) + frame.payload.len();
+            stats.record_packet(PacketDirection::Received, frame.packet_type, size);
+        }
+    }
+
+    /// Take the next packet in the native wire format
+    fn decode_native(&mut self) -> Result<Option<Frame>> {
+        if self.state == FrameState::PacketType {
+            let mut scan = Scan::new(&self.buffer);
+            let packet_type = match scan.varuint() {
+                Ok(packet_type) => packet_type,
+                Err(Incomplete::More) => return Ok(None),
+                Err(Incomplete::Invalid(e)) => return Err(e),
+            };
+            let _ = self.buffer.split_to(scan.pos);
+            self.state = FrameState::Payload { packet_type };
+        }
+
+        let FrameState::Payload { packet_type } = self.state else {
+            return Ok(None);
+        };
+        let length = match payload_length(packet_type, &self.buffer, self.revision, self.compressed) {
+            Ok(length) => length,
+            Err(Incomplete::More) if self.buffer.len() > MAX_PACKET_SIZE => {
+                return Err(Error::Protocol(format!(
+                    "Packet of type {} exceeds {} bytes",
+                    packet_type, MAX_PACKET_SIZE
+                )));
+            }
+            Err(Incomplete::More) => return Ok(None),
+            Err(Incomplete::Invalid(e)) => return Err(e),
+        };
+
+        let payload = self.buffer.split_to(length);
+        self.state = FrameState::PacketType;
+        if packet_type == PacketType::ServerHello.to_u64() {
+            if let Some(server_revision) = hello_revision(&payload) {
+                self.revision = self.revision.min(server_revision);
+            }
+        }
+        Ok(Some(Frame { packet_type, payload }))
+    }
+}
+
+/// Why a payload could not be measured
+enum Incomplete {
+    /// More bytes are needed
+    More,
+    /// The payload cannot be framed
+    Invalid(Error),
+}
+
+impl From<Error> for Incomplete {
+    fn from(e: Error) -> Self {
+        Incomplete::Invalid(e)
+    }
+}
+
+type ScanResult<T> = std::result::Result<T, Incomplete>;
+
+/// Cursor that walks a payload without decoding it
+struct Scan<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scan<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> ScanResult<&'a [u8]> {
+        let end = self.pos.checked_add(len).ok_or(Incomplete::More)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(Incomplete::More)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> ScanResult<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> ScanResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> ScanResult<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn varuint(&mut self) -> ScanResult<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::Protocol("VarUInt is longer than 10 bytes".to_string()).into())
+    }
+
+    fn string(&mut self) -> ScanResult<&'a str> {
+        let len = self.varuint()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|e| Error::Protocol(e.to_string()).into())
+    }
+
+    fn skip_strings(&mut self, count: usize) -> ScanResult<()> {
+        for _ in 0..count {
+            let len = self.varuint()? as usize;
+            self.skip(len)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the server revision from a complete server hello payload
+fn hello_revision(payload: &[u8]) -> Option<u64> {
+    let mut scan = Scan::new(payload);
+    scan.string().ok()?;
+    scan.varuint().ok()?;
+    scan.varuint().ok()?;
+    scan.varuint().ok()
+}
+
+/// Measure the payload of a packet at the start of `buf`
+fn payload_length(packet_type: u64, buf: &[u8], revision: u64, compressed: bool) -> ScanResult<usize> {
+    let mut scan = Scan::new(buf);
+    match PacketType::from_u64(packet_type) {
+        Some(PacketType::ServerHello) => {
+            scan.skip_strings(1)?;
+            scan.varuint()?;
+            scan.varuint()?;
+            let revision = revision.min(scan.varuint()?);
+            if revision >= TIMEZONE_REVISION {
+                scan.skip_strings(1)?;
+            }
+            if revision >= DISPLAY_NAME_REVISION {
+                scan.skip_strings(1)?;
+            }
+            if revision >= VERSION_PATCH_REVISION {
+                scan.varuint()?;
+            }
+            if revision >= PASSWORD_RULES_REVISION {
+                let rules = scan.varuint()? as usize;
+                scan.skip_strings(rules * 2)?;
+            }
+            if revision >= INTERSERVER_NONCE_REVISION {
+                scan.skip(8)?;
+            }
+        }
+        Some(PacketType::ServerException) => loop {
+            scan.skip(4)?;
+            scan.skip_strings(3)?;
+            if scan.u8()? == 0 {
+                break;
+            }
+        },
+        Some(PacketType::ServerProgress) => {
+            let fields = 3
+                + if revision >= CLIENT_WRITE_INFO_REVISION { 2 } else { 0 }
+                + usize::from(revision >= PROGRESS_ELAPSED_REVISION)
+                + usize::from(revision >= PROGRESS_TOTAL_BYTES_REVISION);
+            for _ in 0..fields {
+                scan.varuint()?;
+            }
+        }
+        Some(PacketType::ServerProfileInfo) => {
+            for _ in 0..3 {
+                scan.varuint()?;
+            }
+            scan.skip(1)?;
+            scan.varuint()?;
+            scan.skip(1)?;
+            if revision >= ROWS_BEFORE_AGGREGATION_REVISION {
+                scan.skip(1)?;
+                scan.varuint()?;
+            }
+        }
+        Some(PacketType::ServerPong | PacketType::ServerEndOfStream | PacketType::ServerReadTaskRequest) => {}
+        Some(PacketType::ServerTimezoneUpdate) => scan.skip_strings(1)?,
+        Some(PacketType::ServerTableColumns) => scan.skip_strings(2)?,
+        Some(PacketType::ServerPartUUIDs) => {
+            let count = scan.varuint()? as usize;
+            scan.skip(count.saturating_mul(16))?;
+        }
+        // Logs and profile events are never compressed
+        Some(PacketType::ServerLog | PacketType::ServerProfileEvents) => {
+            scan.skip_strings(1)?;
+            scan_block(&mut scan, revision)?;
+        }
+        Some(PacketType::ServerData | PacketType::ServerTotals | PacketType::ServerExtremes) => {
+            scan.skip_strings(1)?;
+            if compressed {
+                return Err(Error::Unsupported("Framing compressed data blocks is not supported".to_string()).into());
+            }
+            scan_block(&mut scan, revision)?;
+        }
+        _ => {
+            return Err(Error::Protocol(format!("Cannot frame packet type {}", packet_type)).into());
+        }
+    }
+    Ok(scan.pos)
+}
+
+/// Walk a native block: block info, then the name, type and data of each column
+fn scan_block(scan: &mut Scan<'_>, revision: u64) -> ScanResult<()> {
+    if revision > 0 {
+        loop {
+            match scan.varuint()? {
+                0 => break,
+                1 => scan.skip(1)?,
+                2 => scan.skip(4)?,
+                field => {
+                    return Err(Error::Protocol(format!("Unknown block info field {}", field)).into());
+                }
+            }
+        }
+    }
+
+    let columns = scan.varuint()?;
+    let rows = scan.varuint()? as usize;
+    for _ in 0..columns {
+        scan.skip_strings(1)?;
+        let type_name = scan.string()?;
+        if revision >= CUSTOM_SERIALIZATION_REVISION && scan.u8()? != 0 {
+            return Err(Error::Unsupported(format!("Custom serialization of {} columns", type_name)).into());
+        }
+        if rows > 0 {
+            let descriptor = TypeDescriptor::parse(type_name)?;
+            scan_prefix(scan, &descriptor)?;
+            scan_values(scan, &descriptor, rows)?;
+        }
+    }
+    Ok(())
+}
+
+/// Skip the state prefixes written ahead of the data of a column
+fn scan_prefix(scan: &mut Scan<'_>, descriptor: &TypeDescriptor) -> ScanResult<()> {
+    match descriptor {
+        TypeDescriptor::LowCardinality(_) => scan.skip(8),
+        TypeDescriptor::Nullable(inner) | TypeDescriptor::Array(inner) => scan_prefix(scan, inner),
+        TypeDescriptor::Tuple(elements) => elements.iter().try_for_each(|(_, element)| scan_prefix(scan, element)),
+        TypeDescriptor::Map(key, value) => {
+            scan_prefix(scan, key)?;
+            scan_prefix(scan, value)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Skip `rows` values of a column
+fn scan_values(scan: &mut Scan<'_>, descriptor: &TypeDescriptor, rows: usize) -> ScanResult<()> {
+    match descriptor {
+        TypeDescriptor::Simple(name) if name == "String" => scan.skip_strings(rows),
+        TypeDescriptor::Nullable(inner) => {
+            scan.skip(rows)?;
+            scan_values(scan, inner, rows)
+        }
+        TypeDescriptor::Array(inner) => {
+            let total = last_offset(scan, rows)?;
+            scan_values(scan, inner, total)
+        }
+        TypeDescriptor::Map(key, value) => {
+            let total = last_offset(scan, rows)?;
+            scan_values(scan, key, total)?;
+            scan_values(scan, value, total)
+        }
+        TypeDescriptor::Tuple(elements) => {
+            elements.iter().try_for_each(|(_, element)| scan_values(scan, element, rows))
+        }
+        TypeDescriptor::LowCardinality(inner) => {
+            let flags = scan.u64()?;
+            let index_width = 1usize << (flags & 0xff);
+            let keys = scan.u64()? as usize;
+            // Dictionary keys of a nullable type are stored without the null map
+            let key_type = match inner.as_ref() {
+                TypeDescriptor::Nullable(key_type) => key_type.as_ref(),
+                key_type => key_type,
+            };
+            scan_values(scan, key_type, keys)?;
+            let indexes = scan.u64()? as usize;
+            scan.skip(indexes.saturating_mul(index_width))
+        }
+        _ => {
+            let width = encoded_width(descriptor).ok_or_else(|| {
+                Error::Unsupported(format!("Cannot frame column type {}: its encoded size is unknown", descriptor))
+            })?;
+            scan.skip(rows.saturating_mul(width))
+        }
+    }
+}
+
+/// Read array offsets and return the number of nested values
+fn last_offset(scan: &mut Scan<'_>, rows: usize) -> ScanResult<usize> {
+    let mut last = 0;
+    for _ in 0..rows {
+        last = scan.u64()?;
+    }
+    Ok(last as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::varint::{write_var_string, write_varuint};
+    use bytes::BufMut;
+
+    const REVISION: u64 = 54460;
+
+    fn packet(packet_type: PacketType, payload: impl FnOnce(&mut BytesMut)) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        write_varuint(&mut buf, packet_type.to_u64());
+        payload(&mut buf);
+        buf.to_vec()
+    }
+
+    fn data_packet() -> Vec<u8> {
+        packet(PacketType::ServerData, |buf| {
+            write_var_string(buf, "");
+            write_varuint(buf, 1);
+            buf.put_u8(0);
+            write_varuint(buf, 2);
+            buf.put_i32_le(-1);
+            write_varuint(buf, 0);
+            write_varuint(buf, 4);
+            write_varuint(buf, 2);
+            for (name, type_name) in [("id", "UInt32"), ("s", "Nullable(String)")] {
+                write_var_string(buf, name);
+                write_var_string(buf, type_name);
+                buf.put_u8(0);
+                if type_name == "UInt32" {
+                    buf.put_u32_le(1);
+                    buf.put_u32_le(2);
+                } else {
+                    buf.put_slice(&[0, 1]);
+                    write_var_string(buf, "a");
+                    write_var_string(buf, "");
+                }
+            }
+            write_var_string(buf, "tags");
+            write_var_string(buf, "Array(LowCardinality(String))");
+            buf.put_u8(0);
+            buf.put_u64_le(1);
+            buf.put_u64_le(1);
+            buf.put_u64_le(3);
+            buf.put_u64_le(0x200);
+            buf.put_u64_le(2);
+            write_var_string(buf, "x");
+            write_var_string(buf, "yz");
+            buf.put_u64_le(3);
+            buf.put_slice(&[0, 1, 1]);
+            write_var_string(buf, "m");
+            write_var_string(buf, "Map(String, UInt8)");
+            buf.put_u8(0);
+            buf.put_u64_le(1);
+            buf.put_u64_le(1);
+            write_var_string(buf, "k");
+            buf.put_u8(7);
+        })
+    }
+
+    fn stream() -> Vec<u8> {
+        let mut bytes = packet(PacketType::ServerHello, |buf| {
+            write_var_string(buf, "ClickHouse");
+            write_varuint(buf, 24);
+            write_varuint(buf, 8);
+            write_varuint(buf, 54458);
+            write_var_string(buf, "UTC");
+            write_var_string(buf, "node-1");
+            write_varuint(buf, 3);
+        });
+        bytes.extend(data_packet());
+        bytes.extend(packet(PacketType::ServerProgress, |buf| {
+            // 54458 has written rows and bytes but no elapsed time
+            for value in [10, 1000, 300, 0, 0] {
+                write_varuint(buf, value);
+            }
+        }));
+        bytes.extend(packet(PacketType::ServerException, |buf| {
+            for nested in [1u8, 0] {
+                buf.put_i32_le(60);
+                write_var_string(buf, "DB::Exception");
+                write_var_string(buf, "Table does not exist");
+                write_var_string(buf, "");
+                buf.put_u8(nested);
+            }
+        }));
+        bytes.extend(packet(PacketType::ServerEndOfStream, |_| {}));
+        bytes
+    }
+
+    fn decode_all(decoder: &mut FrameDecoder, frames: &mut Vec<Frame>) {
+        while let Some(frame) = decoder.decode().unwrap() {
+            frames.push(frame);
+        }
+    }
+
+    #[test]
+    fn test_frame_decoder_split_buffers() {
+        let bytes = stream();
+        let expected = {
+            let mut decoder = FrameDecoder::new(REVISION);
+            decoder.extend(&bytes);
+            let mut frames = Vec::new();
+            decode_all(&mut decoder, &mut frames);
+            assert_eq!(decoder.buffered(), 0);
+            assert_eq!(decoder.revision(), 54458);
+            frames
+        };
+        let kinds: Vec<_> = expected.iter().map(|f| f.kind().unwrap()).collect();
+        assert_eq!(
+            kinds,
+            [
+                PacketType::ServerHello,
+                PacketType::ServerData,
+                PacketType::ServerProgress,
+                PacketType::ServerException,
+                PacketType::ServerEndOfStream
+            ]
+        );
+
+        // Every split point, and one byte at a time
+        for split in 1..bytes.len() {
+            let mut decoder = FrameDecoder::new(REVISION);
+            let mut frames = Vec::new();
+            for chunk in [&bytes[..split], &bytes[split..]] {
+                decoder.extend(chunk);
+                decode_all(&mut decoder, &mut frames);
+            }
+            assert_eq!(frames, expected, "split at {}", split);
+        }
+        let mut decoder = FrameDecoder::new(REVISION);
+        let mut frames = Vec::new();
+        for byte in &bytes {
+            decoder.extend(std::slice::from_ref(byte));
+            decode_all(&mut decoder, &mut frames);
+        }
+        assert_eq!(frames, expected);
+    }
+
+    #[test]
+    fn test_frame_decoder_state_and_errors() {
+        let data = data_packet();
+        let mut decoder = FrameDecoder::new(REVISION);
+        decoder.extend(&data[..5]);
+        assert!(decoder.decode().unwrap().is_none());
+        assert_eq!(
+            decoder.state(),
+            FrameState::Payload {
+                packet_type: PacketType::ServerData.to_u64()
+            }
+        );
+
+        let mut compressed = FrameDecoder::new(REVISION).with_compression(true);
+        compressed.extend(&data);
+        assert!(matches!(compressed.decode(), Err(Error::Unsupported(_))));
+
+        let mut unknown = FrameDecoder::new(REVISION);
+        unknown.extend(&[99, 0]);
+        assert!(matches!(unknown.decode(), Err(Error::Protocol(_))));
+
+        let mut json = FrameDecoder::new(REVISION);
+        json.extend(&packet(PacketType::ServerData, |buf| {
+            write_var_string(buf, "");
+            write_varuint(buf, 0);
+            write_varuint(buf, 1);
+            write_varuint(buf, 1);
+            write_var_string(buf, "j");
+            write_var_string(buf, "JSON");
+            buf.put_u8(0);
+        }));
+        assert!(matches!(json.decode(), Err(Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_frame_decoder_headers() {
+        let mut bytes = Vec::new();
+        let mut writer = crate::protocol::ProtocolWriter::new(&mut bytes);
+        writer.write_packet(&crate::protocol::ServerPong::new(1, 2, "24.8", "node-1")).unwrap();
+        writer.write_packet(&ClientPing::new()).unwrap();
+        drop(writer);
+
+        let stats = ProtocolStats::new();
+        let mut decoder = FrameDecoder::new(REVISION).with_framing(Framing::Headers).with_stats(stats.clone());
+        decoder.extend(&bytes[..20]);
+        assert!(decoder.decode().unwrap().is_none());
+        assert_eq!(decoder.state(), FrameState::Payload { packet_type: PacketType::ServerPong.to_u64() });
+        decoder.extend(&bytes[20..]);
+        assert_eq!(decoder.decode().unwrap().unwrap().kind(), Some(PacketType::ServerPong));
+
+        // A ping encoded for the framing is what the writer produces
+        let ping = decoder.decode().unwrap().unwrap();
+        assert_eq!(ping.kind(), Some(PacketType::ClientPing));
+        assert_eq!(Framing::Headers.ping(), bytes[bytes.len() - 24..]);
+        assert_eq!(Framing::Native.ping(), [PacketType::ClientPing.to_u64() as u8]);
+        assert_eq!(decoder.buffered(), 0);
+        assert_eq!(stats.snapshot().bytes_received, bytes.len() as u64);
+
+        let mut oversized = FrameDecoder::new(REVISION).with_framing(Framing::Headers);
+        oversized.extend(&[PacketType::ServerData.to_u64() as u8, 0, 0, 0, 0, 0, 0, 0]);
+        oversized.extend(&u64::MAX.to_le_bytes());
+        assert!(matches!(oversized.decode(), Err(Error::Protocol(_))));
+    }
+}
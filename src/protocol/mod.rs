@@ -1,10 +1,12 @@
 //! ClickHouse native protocol implementation
 
 mod client_hello;
+mod client_info;
 mod client_query;
 mod client_data;
 mod client_ping;
 mod client_cancel;
+mod client_read_task_response;
 mod server_hello;
 mod server_data;
 mod server_exception;
@@ -16,23 +18,55 @@ mod version_negotiation;
 mod server_totals;
 mod server_extremes;
 mod server_log;
-
-pub use client_hello::ClientHello;
+mod server_read_task_request;
+mod server_timezone_update;
+mod server_query_plan;
+mod server_table_columns;
+mod server_part_uuids;
+mod tracer;
+mod replay;
+mod stats;
+mod settings;
+mod varint;
+mod framing;
+mod column_reader;
+mod column_writer;
+
+pub use client_hello::{ClientHello, HELLO_ADDENDUM_REVISION};
+pub use client_info::ClientInfo;
 pub use client_query::ClientQuery;
 pub use client_data::ClientData;
 pub use client_ping::ClientPing;
 pub use client_cancel::ClientCancel;
+pub use client_read_task_response::ClientReadTaskResponse;
 pub use server_hello::ServerHello;
 pub use server_data::ServerData;
 pub use server_exception::ServerException;
 pub use server_progress::ServerProgress;
 pub use server_pong::ServerPong;
-pub use server_end_of_stream::ServerEndOfStream;
-pub use server_profile_info::ServerProfileInfo;
+pub use server_end_of_stream::{EndReason, ServerEndOfStream};
+pub use server_profile_info::{ProfileEvent, ServerProfileInfo};
 pub use version_negotiation::{ProtocolVersion, ClientVersionNegotiation, ServerVersionNegotiation};
 pub use server_totals::ServerTotals;
 pub use server_extremes::ServerExtremes;
 pub use server_log::{ServerLog, LogLevel};
+pub use server_read_task_request::{ServerReadTaskRequest, READ_TASK_PROTOCOL_VERSION};
+pub use server_timezone_update::ServerTimezoneUpdate;
+pub use server_query_plan::ServerQueryPlan;
+pub use server_table_columns::{ColumnDescription, ServerTableColumns};
+pub use server_part_uuids::ServerPartUUIDs;
+pub use tracer::{PacketTracer, PacketDirection, TraceRecord, hexdump, read_trace_file};
+pub use replay::ReplayTransport;
+pub use stats::{ConnectionStats, ProtocolStats};
+pub use settings::{SETTINGS_AS_STRINGS_REVISION, SETTING_FLAG_CUSTOM, SETTING_FLAG_IMPORTANT};
+pub use column_reader::{BlockDecoder, DecodeMode, DecodeOptions, ValidationMode, read_block, read_column};
+pub(crate) use column_reader::encoded_width;
+pub use column_writer::{write_block, write_column};
+pub use framing::{
+    Frame, FrameDecoder, FrameState, Framing, CLIENT_WRITE_INFO_REVISION, CUSTOM_SERIALIZATION_REVISION, DISPLAY_NAME_REVISION,
+    INTERSERVER_NONCE_REVISION, PASSWORD_RULES_REVISION, PROGRESS_ELAPSED_REVISION, PROGRESS_TOTAL_BYTES_REVISION,
+    ROWS_BEFORE_AGGREGATION_REVISION, TIMEZONE_REVISION, VERSION_PATCH_REVISION,
+};
 
 use crate::error::{Error, Result};
 use crate::types::{Block, Value};
@@ -61,8 +95,8 @@ pub enum PacketType {
     ClientScp = 7,
     /// Client query with external tables packet
     ClientQueryWithExternalTables = 8,
-    /// Client query with external tables packet
-    ClientQueryWithExternalTables2 = 9,
+    /// Client read task response packet
+    ClientReadTaskResponse = 9,
     /// Server hello packet
     ServerHello = 100,
     /// Server data packet
@@ -114,7 +148,7 @@ impl PacketType {
             6 => Some(PacketType::ClientKeepAlive),
             7 => Some(PacketType::ClientScp),
             8 => Some(PacketType::ClientQueryWithExternalTables),
-            9 => Some(PacketType::ClientQueryWithExternalTables2),
+            9 => Some(PacketType::ClientReadTaskResponse),
             100 => Some(PacketType::ServerHello),
             101 => Some(PacketType::ServerData),
             102 => Some(PacketType::ServerException),
@@ -149,7 +183,7 @@ impl PacketType {
             PacketType::ClientKeepAlive => 6,
             PacketType::ClientScp => 7,
             PacketType::ClientQueryWithExternalTables => 8,
-            PacketType::ClientQueryWithExternalTables2 => 9,
+            PacketType::ClientReadTaskResponse => 9,
             PacketType::ServerHello => 100,
             PacketType::ServerData => 101,
             PacketType::ServerException => 102,
@@ -186,10 +220,22 @@ pub trait Packet {
         Self: Sized;
 }
 
+/// Size of each read from the underlying stream
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Length of the header [`ProtocolWriter`] puts in front of each packet
+const PACKET_HEADER_SIZE: usize = 16;
+
 /// Protocol reader for reading packets from a stream
+///
+/// Bytes read from the stream are buffered until a whole packet has arrived,
+/// so short reads are fine, and a read failing with `WouldBlock` or
+/// `Interrupted` can simply be retried without losing data.
 pub struct ProtocolReader<R> {
     reader: R,
     buffer: BytesMut,
+    packets: FrameDecoder,
+    frames: FrameDecoder,
 }
 
 impl<R> ProtocolReader<R>
@@ -201,21 +247,76 @@ where
         Self {
             reader,
             buffer: BytesMut::new(),
+            packets: FrameDecoder::new(constants::DEFAULT_PROTOCOL_VERSION).with_framing(Framing::Headers),
+            frames: FrameDecoder::new(constants::DEFAULT_PROTOCOL_VERSION),
         }
     }
 
-    /// Read a packet from the stream
-    pub fn read_packet(&mut self) -> Result<Box<dyn Packet>> {
-        // Read packet header (type + size)
-        let mut header = [0u8; 16];
-        self.reader.read_exact(&mut header)?;
+    /// Frame server packets with the given protocol revision
+    pub fn with_revision(mut self, revision: u64) -> Self {
+        self.frames.set_revision(revision);
+        self
+    }
+
+    /// Set whether server data blocks are compressed
+    pub fn with_compression(mut self, compressed: bool) -> Self {
+        self.frames = self.frames.with_compression(compressed);
+        self
+    }
 
-        let packet_type = u64::from_le_bytes(header[0..8].try_into().unwrap());
-        let packet_size = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    /// Get the decoder that frames server packets
+    pub fn frame_decoder(&self) -> &FrameDecoder {
+        &self.frames
+    }
 
-        // Read packet body
-        self.buffer.resize(packet_size as usize, 0);
-        self.reader.read_exact(&mut self.buffer[..packet_size as usize])?;
+    /// Trace every packet read with the given tracer
+    pub fn with_tracer(mut self, tracer: PacketTracer) -> Self {
+        self.packets = self.packets.with_tracer(tracer.clone());
+        self.frames = self.frames.with_tracer(tracer);
+        self
+    }
+
+    /// Count every packet read in the given connection stats
+    pub fn with_stats(mut self, stats: ProtocolStats) -> Self {
+        self.packets = self.packets.with_stats(stats.clone());
+        self.frames = self.frames.with_stats(stats);
+        self
+    }
+
+    /// Feed the stream into `decoder` until it has a whole packet
+    ///
+    /// `Interrupted` reads are retried; the end of the stream is an error,
+    /// since the caller is in the middle of a packet.
+    fn next_frame(reader: &mut R, decoder: &mut FrameDecoder) -> Result<Frame> {
+        let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+        loop {
+            if let Some(frame) = decoder.decode()? {
+                return Ok(frame);
+            }
+            match reader.read(&mut chunk) {
+                Ok(0) => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed mid-packet").into())
+                }
+                Ok(n) => decoder.extend(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Read the next server packet in the native wire format
+    ///
+    /// The packet type is a VarUInt and the payload length follows from the
+    /// packet structure, see [`FrameDecoder`].
+    pub fn read_frame(&mut self) -> Result<Frame> {
+        Self::next_frame(&mut self.reader, &mut self.frames)
+    }
+
+    /// Read a packet from the stream
+    pub fn read_packet(&mut self) -> Result<Box<dyn Packet>> {
+        let frame = Self::next_frame(&mut self.reader, &mut self.packets)?;
+        let packet_type = frame.packet_type;
+        self.buffer = frame.payload;
 
         // Deserialize packet based on type
         let packet: Box<dyn Packet> = match PacketType::from_u64(packet_type) {
@@ -237,6 +338,33 @@ where
             Some(PacketType::ServerEndOfStream) => {
                 Box::new(ServerEndOfStream::deserialize(&mut self.buffer)?)
             }
+            Some(PacketType::ServerReadTaskRequest) => {
+                Box::new(ServerReadTaskRequest::deserialize(&mut self.buffer)?)
+            }
+            Some(PacketType::ServerTimezoneUpdate) => {
+                Box::new(ServerTimezoneUpdate::deserialize(&mut self.buffer)?)
+            }
+            Some(PacketType::ServerQueryPlan) => {
+                Box::new(ServerQueryPlan::deserialize(&mut self.buffer)?)
+            }
+            Some(PacketType::ServerTableColumns) => {
+                Box::new(ServerTableColumns::deserialize(&mut self.buffer)?)
+            }
+            Some(PacketType::ServerPartUUIDs) => {
+                Box::new(ServerPartUUIDs::deserialize(&mut self.buffer)?)
+            }
+            Some(PacketType::ServerProfileInfo) => {
+                Box::new(ServerProfileInfo::deserialize(&mut self.buffer)?)
+            }
+            Some(PacketType::ServerTotals) => {
+                Box::new(ServerTotals::deserialize(&mut self.buffer)?)
+            }
+            Some(PacketType::ServerExtremes) => {
+                Box::new(ServerExtremes::deserialize(&mut self.buffer)?)
+            }
+            Some(PacketType::ServerLog) => {
+                Box::new(ServerLog::deserialize(&mut self.buffer)?)
+            }
             _ => {
                 return Err(Error::Protocol(format!(
                     "Unknown packet type: {}",
@@ -249,10 +377,17 @@ where
     }
 }
 
+/// Number of bytes a VarUInt takes on the wire
+fn varint_len(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize).div_ceil(7).max(1)
+}
+
 /// Protocol writer for writing packets to a stream
 pub struct ProtocolWriter<W> {
     writer: W,
     buffer: BytesMut,
+    tracer: Option<PacketTracer>,
+    stats: Option<ProtocolStats>,
 }
 
 impl<W> ProtocolWriter<W>
@@ -264,9 +399,23 @@ where
         Self {
             writer,
             buffer: BytesMut::new(),
+            tracer: None,
+            stats: None,
         }
     }
 
+    /// Trace every packet written with the given tracer
+    pub fn with_tracer(mut self, tracer: PacketTracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Count every packet written in the given connection stats
+    pub fn with_stats(mut self, stats: ProtocolStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
     /// Write a packet to the stream
     pub fn write_packet(&mut self, packet: &dyn Packet) -> Result<()> {
         // Clear buffer
@@ -279,8 +428,17 @@ where
         let packet_type = packet.packet_type().to_u64();
         let packet_size = self.buffer.len() as u64;
 
-        self.writer.write_all(&packet_type.to_le_bytes())?;
-        self.writer.write_all(&packet_size.to_le_bytes())?;
+        let mut header = [0u8; 16];
+        header[0..8].copy_from_slice(&packet_type.to_le_bytes());
+        header[8..16].copy_from_slice(&packet_size.to_le_bytes());
+        self.writer.write_all(&header)?;
+
+        if let Some(tracer) = &self.tracer {
+            tracer.trace(PacketDirection::Sent, &header, &self.buffer);
+        }
+        if let Some(stats) = &self.stats {
+            stats.record_packet(PacketDirection::Sent, packet_type, header.len() + self.buffer.len());
+        }
 
         // Write packet body
         self.writer.write_all(&self.buffer)?;
@@ -304,6 +462,9 @@ pub mod constants {
     /// Default password
     pub const DEFAULT_PASSWORD: &str = "";
     
+    /// Username sent in place of a user when authenticating with a JWT
+    pub const JWT_AUTHENTICATION_MARKER: &str = " JWT AUTHENTICATION ";
+    
     /// Default client name
     pub const DEFAULT_CLIENT_NAME: &str = "clickhouse-rs";
     
@@ -346,7 +507,7 @@ mod tests {
         assert_eq!(PacketType::ClientKeepAlive.to_u64(), 6);
         assert_eq!(PacketType::ClientScp.to_u64(), 7);
         assert_eq!(PacketType::ClientQueryWithExternalTables.to_u64(), 8);
-        assert_eq!(PacketType::ClientQueryWithExternalTables2.to_u64(), 9);
+        assert_eq!(PacketType::ClientReadTaskResponse.to_u64(), 9);
 
         // Test all server packet types
         assert_eq!(PacketType::ServerHello.to_u64(), 100);
@@ -378,7 +539,7 @@ mod tests {
         assert_eq!(PacketType::from_u64(6), Some(PacketType::ClientKeepAlive));
         assert_eq!(PacketType::from_u64(7), Some(PacketType::ClientScp));
         assert_eq!(PacketType::from_u64(8), Some(PacketType::ClientQueryWithExternalTables));
-        assert_eq!(PacketType::from_u64(9), Some(PacketType::ClientQueryWithExternalTables2));
+        assert_eq!(PacketType::from_u64(9), Some(PacketType::ClientReadTaskResponse));
         assert_eq!(PacketType::from_u64(100), Some(PacketType::ServerHello));
         assert_eq!(PacketType::from_u64(101), Some(PacketType::ServerData));
         assert_eq!(PacketType::from_u64(102), Some(PacketType::ServerException));
@@ -744,6 +905,91 @@ mod tests {
         }
     }
 
+    /// Reader that returns one byte per read and fails every other read with `WouldBlock`
+    struct TrickleReader {
+        data: Vec<u8>,
+        pos: usize,
+        block: bool,
+    }
+
+    impl TrickleReader {
+        fn new(data: Vec<u8>) -> Self {
+            Self { data, pos: 0, block: false }
+        }
+    }
+
+    impl io::Read for TrickleReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.block = !self.block;
+            if self.block {
+                return Err(io::Error::new(ErrorKind::WouldBlock, "no data yet"));
+            }
+            let Some(byte) = self.data.get(self.pos) else {
+                return Ok(0);
+            };
+            buf[0] = *byte;
+            self.pos += 1;
+            Ok(1)
+        }
+    }
+
+    /// Call `read` until it stops failing with `WouldBlock`
+    fn retry<T>(mut read: impl FnMut() -> Result<T>) -> Result<T> {
+        loop {
+            match read() {
+                Err(Error::Network(e)) if e.kind() == ErrorKind::WouldBlock => continue,
+                result => return result,
+            }
+        }
+    }
+
+    fn is_eof<T>(result: Result<T>) -> bool {
+        matches!(result, Err(Error::Network(e)) if e.kind() == ErrorKind::UnexpectedEof)
+    }
+
+    #[test]
+    fn test_protocol_reader_partial_reads() {
+        let mut data = Vec::new();
+        for _ in 0..2 {
+            data.extend_from_slice(&104u64.to_le_bytes());
+            data.extend_from_slice(&41u64.to_le_bytes());
+            data.extend_from_slice(&1000000000u64.to_le_bytes());
+            data.extend_from_slice(&3600u64.to_le_bytes());
+            data.extend_from_slice(&5u64.to_le_bytes());
+            data.extend_from_slice(b"1.0.0");
+            data.extend_from_slice(&4u64.to_le_bytes());
+            data.extend_from_slice(b"Test");
+        }
+        let mut reader = ProtocolReader::new(TrickleReader::new(data));
+        for _ in 0..2 {
+            let packet = retry(|| reader.read_packet()).unwrap();
+            assert_eq!(packet.packet_type(), PacketType::ServerPong);
+        }
+        assert!(is_eof(retry(|| reader.read_packet())));
+    }
+
+    #[test]
+    fn test_protocol_reader_read_frame() {
+        // ServerTimezoneUpdate, ServerPong, then a ServerTableColumns cut short
+        let data = vec![115, 3, b'U', b'T', b'C', 104, 111, 1, b't'];
+        let stats = ProtocolStats::new();
+        let mut reader = ProtocolReader::new(TrickleReader::new(data)).with_stats(stats.clone());
+
+        let frame = retry(|| reader.read_frame()).unwrap();
+        assert_eq!(frame.kind(), Some(PacketType::ServerTimezoneUpdate));
+        assert_eq!(&frame.payload[..], b"\x03UTC");
+        assert_eq!(retry(|| reader.read_frame()).unwrap().kind(), Some(PacketType::ServerPong));
+        assert_eq!(stats.snapshot().bytes_received, 6);
+
+        assert!(is_eof(retry(|| reader.read_frame())));
+        assert_eq!(
+            reader.frame_decoder().state(),
+            FrameState::Payload {
+                packet_type: PacketType::ServerTableColumns.to_u64()
+            }
+        );
+    }
+
     struct FailingReader;
 
     impl io::Read for FailingReader {
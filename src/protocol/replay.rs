@@ -0,0 +1,273 @@
+//! Record and replay of protocol sessions
+//!
+//! Sessions are recorded with a [`PacketTracer`] created by
+//! [`PacketTracer::record_to`], which captures whole packets. A
+//! [`ReplayTransport`] built from the recording then plays the server side
+//! back, so protocol code can be tested deterministically without a server.
+
+use crate::error::{Error, Result};
+use crate::protocol::tracer::{read_trace_file, PacketDirection, TraceRecord};
+use crate::protocol::PacketTracer;
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+impl PacketTracer {
+    /// Create an enabled tracer that records complete packets for replay
+    pub fn record_to(path: impl AsRef<Path>) -> Result<Self> {
+        let tracer = Self::with_file(path)?;
+        tracer.set_capture_bytes(usize::MAX);
+        Ok(tracer)
+    }
+}
+
+/// In-memory transport that replays a recorded session
+///
+/// Reads return the recorded server packets in order. Writes are framed into
+/// packets and, unless verification is disabled, checked against the packets
+/// the client sent during recording.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    /// Framed server packets still to be read
+    incoming: VecDeque<u8>,
+    /// Client packets expected from the code under test
+    expected: VecDeque<TraceRecord>,
+    /// Written bytes that do not form a complete packet yet
+    written: Vec<u8>,
+    /// Whether written packets are checked against the recording
+    verify_writes: bool,
+}
+
+impl ReplayTransport {
+    /// Create a replay transport from recorded packets
+    pub fn from_records(records: Vec<TraceRecord>) -> Result<Self> {
+        let mut incoming = VecDeque::new();
+        let mut expected = VecDeque::new();
+
+        for record in records {
+            if !record.is_complete() {
+                return Err(Error::InvalidData(format!(
+                    "Packet of type {} was truncated to {} of {} bytes; record with PacketTracer::record_to",
+                    record.packet_type,
+                    record.data.len(),
+                    record.size
+                )));
+            }
+
+            match record.direction {
+                PacketDirection::Received => {
+                    incoming.extend(record.packet_type.to_le_bytes());
+                    incoming.extend(record.size.to_le_bytes());
+                    incoming.extend(record.data);
+                }
+                PacketDirection::Sent => expected.push_back(record),
+            }
+        }
+
+        Ok(Self {
+            incoming,
+            expected,
+            written: Vec::new(),
+            verify_writes: true,
+        })
+    }
+
+    /// Create a replay transport from a trace file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_records(read_trace_file(path)?)
+    }
+
+    /// Set whether written packets are checked against the recording
+    pub fn verify_writes(mut self, verify: bool) -> Self {
+        self.verify_writes = verify;
+        self
+    }
+
+    /// Get the number of server bytes not read yet
+    pub fn remaining_server_bytes(&self) -> usize {
+        self.incoming.len()
+    }
+
+    /// Get the number of recorded client packets not written yet
+    pub fn remaining_client_packets(&self) -> usize {
+        self.expected.len()
+    }
+
+    /// Check if the whole session has been replayed
+    pub fn is_exhausted(&self) -> bool {
+        self.incoming.is_empty() && self.expected.is_empty()
+    }
+
+    /// Match complete written packets against the recording
+    fn consume_written(&mut self) -> io::Result<()> {
+        while self.written.len() >= 16 {
+            let packet_type = u64::from_le_bytes(self.written[0..8].try_into().unwrap());
+            let size = u64::from_le_bytes(self.written[8..16].try_into().unwrap()) as usize;
+            if self.written.len() < 16 + size {
+                break;
+            }
+
+            let body: Vec<u8> = self.written.drain(..16 + size).skip(16).collect();
+            let expected = self.expected.pop_front();
+            if !self.verify_writes {
+                continue;
+            }
+
+            match expected {
+                Some(record) if record.packet_type == packet_type && record.data == body => {}
+                Some(record) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Replay mismatch: expected packet type {} ({} bytes), got type {} ({} bytes)",
+                            record.packet_type, record.size, packet_type, size
+                        ),
+                    ));
+                }
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Replay mismatch: unexpected packet type {} after end of recording", packet_type),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl io::Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.incoming.len());
+        for (dst, src) in buf.iter_mut().zip(self.incoming.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl io::Write for ReplayTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        self.consume_written()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncRead for ReplayTransport {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let n = buf.remaining().min(this.incoming.len());
+        let bytes: Vec<u8> = this.incoming.drain(..n).collect();
+        buf.put_slice(&bytes);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ReplayTransport {
+    fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Poll::Ready(io::Write::write(self.get_mut(), buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{ClientCancel, ClientPing, PacketType, ProtocolReader, ProtocolWriter, ServerPong};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Record a ping/pong exchange and return the trace file path
+    fn record_ping_session() -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("clickhouse-replay-{}.bin", uuid::Uuid::new_v4()));
+        let tracer = PacketTracer::record_to(&path).unwrap();
+
+        let mut sent = Vec::new();
+        ProtocolWriter::new(&mut sent)
+            .with_tracer(tracer.clone())
+            .write_packet(&ClientPing::new())
+            .unwrap();
+
+        let mut server = Vec::new();
+        ProtocolWriter::new(&mut server)
+            .write_packet(&ServerPong::new(1, 2, "24.3", "replay"))
+            .unwrap();
+        ProtocolReader::new(io::Cursor::new(server))
+            .with_tracer(tracer.clone())
+            .read_packet()
+            .unwrap();
+
+        tracer.close_output().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_replay_session() {
+        let path = record_ping_session();
+        let mut transport = ReplayTransport::from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(transport.remaining_client_packets(), 1);
+
+        ProtocolWriter::new(&mut transport).write_packet(&ClientPing::new()).unwrap();
+        let packet = ProtocolReader::new(&mut transport).read_packet().unwrap();
+
+        assert_eq!(packet.packet_type(), PacketType::ServerPong);
+        assert!(transport.is_exhausted());
+    }
+
+    #[test]
+    fn test_replay_detects_unexpected_writes() {
+        let path = record_ping_session();
+        let mut transport = ReplayTransport::from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let result = ProtocolWriter::new(&mut transport).write_packet(&ClientCancel::new("q".to_string()));
+        assert!(result.is_err());
+
+        let path = record_ping_session();
+        let mut transport = ReplayTransport::from_file(&path).unwrap().verify_writes(false);
+        std::fs::remove_file(&path).ok();
+        assert!(ProtocolWriter::new(&mut transport).write_packet(&ClientCancel::new("q".to_string())).is_ok());
+    }
+
+    #[test]
+    fn test_replay_rejects_truncated_recording() {
+        let record = TraceRecord {
+            timestamp_micros: 0,
+            direction: PacketDirection::Received,
+            packet_type: 4,
+            size: 10,
+            data: vec![0; 4],
+        };
+        assert!(ReplayTransport::from_records(vec![record]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_async_transport() {
+        let path = record_ping_session();
+        let mut transport = ReplayTransport::from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let mut ping = Vec::new();
+        ProtocolWriter::new(&mut ping).write_packet(&ClientPing::new()).unwrap();
+        transport.write_all(&ping).await.unwrap();
+
+        let mut header = [0u8; 16];
+        transport.read_exact(&mut header).await.unwrap();
+        assert_eq!(u64::from_le_bytes(header[0..8].try_into().unwrap()), PacketType::ServerPong.to_u64());
+    }
+}
@@ -1,6 +1,6 @@
 //! Server Data message for ClickHouse native protocol
 
-use super::{Packet, PacketType};
+use super::{write_block, BlockDecoder, DecodeMode, DecodeOptions, Packet, PacketType};
 use crate::error::{Error, Result};
 use crate::types::Block;
 use bytes::{Buf, BufMut, BytesMut};
@@ -79,6 +79,16 @@ impl Default for BlockInfo {
     }
 }
 
+impl From<&BlockInfo> for crate::types::BlockInfo {
+    fn from(info: &BlockInfo) -> Self {
+        Self {
+            is_overflows: info.is_overflows,
+            bucket_num: info.bucket_num().unwrap_or(-1),
+            ..Self::default()
+        }
+    }
+}
+
 impl ServerData {
     /// Create a new Server Data message
     pub fn new(block: Block) -> Self {
@@ -90,8 +100,9 @@ impl ServerData {
         }
     }
 
-    /// Set block info
+    /// Set block info, also recording it on the block
     pub fn with_block_info(mut self, block_info: BlockInfo) -> Self {
+        self.block.info = (&block_info).into();
         self.block_info = Some(block_info);
         self
     }
@@ -191,14 +202,24 @@ impl Packet for ServerData {
             buf.put_u8(0);
         }
 
-        // Write block (simplified for now)
-        // For now, just write a placeholder for the block
-        buf.put_u64_le(0); // Block size placeholder
-
-        Ok(())
+        // Write block
+        write_block(buf, &self.block)
     }
 
     fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        Self::deserialize_with_mode(buf, DecodeMode::Strict)
+    }
+}
+
+impl ServerData {
+    /// Deserialize the packet, handling undecodable column types per `mode`
+    pub fn deserialize_with_mode(buf: &mut BytesMut, mode: DecodeMode) -> Result<Self> {
+        let mut decoder = BlockDecoder::new(DecodeOptions { mode, ..DecodeOptions::default() });
+        Self::deserialize_with_decoder(buf, &mut decoder)
+    }
+
+    /// Deserialize the packet, validating values and collecting warnings in `decoder`
+    pub fn deserialize_with_decoder(buf: &mut BytesMut, decoder: &mut BlockDecoder) -> Result<Self> {
         // Read block info
         let is_overflows = buf.get_u8() != 0;
         let bucket_num = buf.get_i32_le();
@@ -237,9 +258,11 @@ impl Packet for ServerData {
             None
         };
 
-        // Read block (simplified for now)
-        let _block_size = buf.get_u64_le(); // Skip block size for now
-        let block = Block::default(); // Placeholder
+        // Read block
+        let mut block = decoder.read_block(buf)?;
+        if let Some(info) = &block_info {
+            block.info = info.into();
+        }
 
         Ok(Self {
             block,
@@ -292,6 +315,8 @@ mod tests {
         assert!(data.block_info().is_some());
         assert!(data.has_overflows());
         assert_eq!(data.bucket_number(), Some(42));
+        assert!(data.block().is_overflows());
+        assert_eq!(data.block().info.bucket(), Some(42));
     }
 
     #[test]
@@ -360,6 +385,8 @@ mod tests {
                    deserialized.block_info.as_ref().unwrap().bucket_num);
         assert_eq!(original.block_info.as_ref().unwrap().is_bucket_number, 
                    deserialized.block_info.as_ref().unwrap().is_bucket_number);
+        assert!(deserialized.block.is_overflows());
+        assert_eq!(deserialized.block.info.bucket(), Some(42));
         assert_eq!(original.compression_method, deserialized.compression_method);
         assert_eq!(original.compression_level, deserialized.compression_level);
     }
@@ -61,6 +61,29 @@ impl LogLevel {
         }
     }
 
+    /// Get the value of the `send_logs_level` setting for this level
+    pub fn setting_value(self) -> &'static str {
+        match self {
+            LogLevel::Fatal | LogLevel::Critical => "fatal",
+            LogLevel::Error => "error",
+            LogLevel::Warning => "warning",
+            LogLevel::Notice | LogLevel::Information => "information",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    /// Get the closest `tracing` level
+    pub fn tracing_level(self) -> tracing::Level {
+        match self {
+            LogLevel::Fatal | LogLevel::Critical | LogLevel::Error => tracing::Level::ERROR,
+            LogLevel::Warning => tracing::Level::WARN,
+            LogLevel::Notice | LogLevel::Information => tracing::Level::INFO,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Trace => tracing::Level::TRACE,
+        }
+    }
+
     /// Check if this is an error level
     pub fn is_error(self) -> bool {
         matches!(self, LogLevel::Fatal | LogLevel::Critical | LogLevel::Error)
@@ -158,6 +181,33 @@ impl ServerLog {
     pub fn timestamp(&self) -> u64 {
         self.timestamp_ns
     }
+
+    /// Emit the message as a `tracing` event under the `clickhouse::server` target
+    ///
+    /// The event level follows the server severity, which is also kept in the
+    /// `server_level` field along with the source component and query ID.
+    pub fn emit(&self) {
+        macro_rules! emit {
+            ($level:expr) => {
+                tracing::event!(
+                    target: "clickhouse::server",
+                    $level,
+                    server_level = self.level.as_str(),
+                    source = %self.source,
+                    query_id = self.get_metadata("query_id").map(String::as_str).unwrap_or(""),
+                    "{}",
+                    self.message
+                )
+            };
+        }
+        match self.level.tracing_level() {
+            tracing::Level::ERROR => emit!(tracing::Level::ERROR),
+            tracing::Level::WARN => emit!(tracing::Level::WARN),
+            tracing::Level::INFO => emit!(tracing::Level::INFO),
+            tracing::Level::DEBUG => emit!(tracing::Level::DEBUG),
+            _ => emit!(tracing::Level::TRACE),
+        }
+    }
 }
 
 impl Packet for ServerLog {
@@ -305,6 +355,15 @@ mod tests {
         assert_eq!(LogLevel::Warning.to_string(), "Warning");
     }
 
+    #[test]
+    fn test_log_level_mapping() {
+        assert_eq!(LogLevel::Critical.setting_value(), "fatal");
+        assert_eq!(LogLevel::Debug.setting_value(), "debug");
+        assert_eq!(LogLevel::Fatal.tracing_level(), tracing::Level::ERROR);
+        assert_eq!(LogLevel::Notice.tracing_level(), tracing::Level::INFO);
+        assert_eq!(LogLevel::Trace.tracing_level(), tracing::Level::TRACE);
+    }
+
     #[test]
     fn test_log_level_checks() {
         assert!(LogLevel::Fatal.is_error());
@@ -313,6 +372,43 @@ mod tests {
         assert!(LogLevel::Debug.is_debug());
     }
 
+    #[derive(Clone, Default)]
+    struct Captured(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_server_log_emit() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let mut log = ServerLog::new(LogLevel::Warning, "Slow read".to_string(), "MergeTreeReader".to_string());
+        log.add_metadata("query_id".to_string(), "q1".to_string());
+        tracing::subscriber::with_default(subscriber, || log.emit());
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("WARN"));
+        assert!(output.contains("clickhouse::server"));
+        assert!(output.contains("server_level=\"Warning\""));
+        assert!(output.contains("source=MergeTreeReader"));
+        assert!(output.contains("query_id=\"q1\""));
+        assert!(output.contains("Slow read"));
+    }
+
     #[test]
     fn test_server_log_new() {
         let log = ServerLog::new(
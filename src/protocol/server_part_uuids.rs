@@ -0,0 +1,89 @@
+//! Server part UUIDs packet implementation
+
+use crate::error::{Error, Result};
+use crate::protocol::{Packet, PacketType};
+use bytes::{Buf, BufMut, BytesMut};
+use uuid::Uuid;
+
+/// Server part UUIDs packet
+///
+/// Lists the UUIDs of the data parts a query touched. With
+/// `allow_experimental_query_deduplication`, replicas of a cluster use them
+/// to skip parts that were already read from another replica.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ServerPartUUIDs {
+    /// UUIDs of the parts
+    pub uuids: Vec<Uuid>,
+}
+
+impl ServerPartUUIDs {
+    /// Create a new part UUIDs packet
+    pub fn new(uuids: Vec<Uuid>) -> Self {
+        Self { uuids }
+    }
+
+    /// Get the part UUIDs
+    pub fn uuids(&self) -> &[Uuid] {
+        &self.uuids
+    }
+}
+
+impl Packet for ServerPartUUIDs {
+    fn packet_type(&self) -> PacketType {
+        PacketType::ServerPartUUIDs
+    }
+
+    fn serialize(&self, buf: &mut BytesMut) -> Result<()> {
+        buf.put_u64_le(self.uuids.len() as u64);
+        for uuid in &self.uuids {
+            let (high, low) = uuid.as_u64_pair();
+            buf.put_u64_le(high);
+            buf.put_u64_le(low);
+        }
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        if buf.len() < 8 {
+            return Err(Error::Protocol("Insufficient data for ServerPartUUIDs packet".to_string()));
+        }
+
+        let count = buf.get_u64_le() as usize;
+        if count > buf.len() / 16 {
+            return Err(Error::Protocol(format!(
+                "Invalid part UUID count: {} (available: {} bytes)",
+                count,
+                buf.len()
+            )));
+        }
+        let uuids = (0..count)
+            .map(|_| {
+                let high = buf.get_u64_le();
+                Uuid::from_u64_pair(high, buf.get_u64_le())
+            })
+            .collect();
+
+        Ok(ServerPartUUIDs { uuids })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_part_uuids_roundtrip() {
+        let packet = ServerPartUUIDs::new(vec![Uuid::from_u64_pair(1, 2), Uuid::new_v4()]);
+        let mut buf = BytesMut::new();
+        packet.serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 8 + 2 * 16);
+
+        let decoded = ServerPartUUIDs::deserialize(&mut buf).unwrap();
+        assert_eq!(decoded, packet);
+
+        let mut truncated = BytesMut::new();
+        truncated.put_u64_le(2);
+        truncated.put_u64_le(1);
+        assert!(ServerPartUUIDs::deserialize(&mut truncated).is_err());
+    }
+}
@@ -71,6 +71,21 @@ impl ServerProfileInfo {
         self.profile_events.insert(event.event_type.clone(), event);
     }
 
+    /// Check if the result was served from the query cache
+    ///
+    /// Based on the `QueryCacheHits` and `QueryCacheMisses` events; `None`
+    /// when the query cache was not consulted.
+    pub fn query_cache_hit(&self) -> Option<bool> {
+        let count = |name: &str| self.profile_events.get(name).map_or(0, |event| event.count);
+        if count("QueryCacheHits") > 0 {
+            Some(true)
+        } else if count("QueryCacheMisses") > 0 {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
     /// Get execution time in milliseconds
     pub fn execution_time_ms(&self) -> f64 {
         self.execution_time_ns as f64 / 1_000_000.0
@@ -0,0 +1,96 @@
+//! Server query plan packet implementation
+
+use crate::error::{Error, Result};
+use crate::protocol::{Packet, PacketType};
+use bytes::{Buf, BufMut, BytesMut};
+
+/// Server query plan packet
+///
+/// Carries the text lines of a query plan, in the same indented format as
+/// the result of an `EXPLAIN` query.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ServerQueryPlan {
+    /// Plan lines, indented by nesting depth
+    pub lines: Vec<String>,
+}
+
+impl ServerQueryPlan {
+    /// Create a new query plan packet
+    pub fn new(lines: Vec<String>) -> Self {
+        Self { lines }
+    }
+
+    /// Get the plan lines
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+impl Packet for ServerQueryPlan {
+    fn packet_type(&self) -> PacketType {
+        PacketType::ServerQueryPlan
+    }
+
+    fn serialize(&self, buf: &mut BytesMut) -> Result<()> {
+        buf.put_u64_le(self.lines.len() as u64);
+        for line in &self.lines {
+            buf.put_u64_le(line.len() as u64);
+            buf.extend_from_slice(line.as_bytes());
+        }
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        if buf.len() < 8 {
+            return Err(Error::Protocol("Insufficient data for ServerQueryPlan packet".to_string()));
+        }
+
+        let count = buf.get_u64_le() as usize;
+        let mut lines = Vec::with_capacity(count.min(buf.len() / 8));
+        for _ in 0..count {
+            if buf.len() < 8 {
+                return Err(Error::Protocol("Insufficient data for query plan line length".to_string()));
+            }
+            let len = buf.get_u64_le() as usize;
+            if len > buf.len() {
+                return Err(Error::Protocol(format!(
+                    "Invalid query plan line length: {} (available: {})",
+                    len,
+                    buf.len()
+                )));
+            }
+            let bytes = buf.copy_to_bytes(len);
+            let line = String::from_utf8(bytes.to_vec())
+                .map_err(|e| Error::Protocol(format!("Invalid UTF-8 in query plan: {}", e)))?;
+            lines.push(line);
+        }
+
+        Ok(ServerQueryPlan { lines })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_query_plan_roundtrip() {
+        let plan = ServerQueryPlan::new(vec![
+            "Expression ((Projection + Before ORDER BY))".to_string(),
+            "  ReadFromMergeTree (default.events)".to_string(),
+        ]);
+        let mut buf = BytesMut::new();
+        plan.serialize(&mut buf).unwrap();
+
+        let decoded = ServerQueryPlan::deserialize(&mut buf).unwrap();
+        assert_eq!(decoded, plan);
+    }
+
+    #[test]
+    fn test_server_query_plan_truncated() {
+        let mut buf = BytesMut::new();
+        buf.put_u64_le(2);
+        buf.put_u64_le(100);
+        assert!(ServerQueryPlan::deserialize(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Server read task request packet implementation
+
+use crate::error::{Error, Result};
+use crate::protocol::{ClientReadTaskResponse, Packet, PacketType};
+use bytes::{Buf, BufMut, BytesMut};
+
+/// Version of the read task exchange spoken by this client
+pub const READ_TASK_PROTOCOL_VERSION: u64 = 1;
+
+/// Server read task request packet
+///
+/// Sent by the server during distributed or parallel replica reads to ask the
+/// initiating client for the next unit of work. The client must answer with a
+/// [`ClientReadTaskResponse`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerReadTaskRequest {
+    /// Read task protocol version used by the server
+    pub protocol_version: u64,
+}
+
+impl ServerReadTaskRequest {
+    /// Create a new read task request
+    pub fn new() -> Self {
+        Self {
+            protocol_version: READ_TASK_PROTOCOL_VERSION,
+        }
+    }
+
+    /// Build the response handing out `task`, or signalling that no tasks are left
+    pub fn respond(&self, task: Option<String>) -> ClientReadTaskResponse {
+        ClientReadTaskResponse::new(self.protocol_version.min(READ_TASK_PROTOCOL_VERSION), task)
+    }
+}
+
+impl Default for ServerReadTaskRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Packet for ServerReadTaskRequest {
+    fn packet_type(&self) -> PacketType {
+        PacketType::ServerReadTaskRequest
+    }
+
+    fn serialize(&self, buf: &mut BytesMut) -> Result<()> {
+        buf.put_u64_le(self.protocol_version);
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        if buf.len() < 8 {
+            return Err(Error::Protocol("Insufficient data for ServerReadTaskRequest packet".to_string()));
+        }
+
+        Ok(ServerReadTaskRequest {
+            protocol_version: buf.get_u64_le(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_read_task_request_roundtrip() {
+        let request = ServerReadTaskRequest::new();
+        let mut buf = BytesMut::new();
+        request.serialize(&mut buf).unwrap();
+
+        let decoded = ServerReadTaskRequest::deserialize(&mut buf).unwrap();
+        assert_eq!(decoded, request);
+        assert_eq!(decoded.packet_type(), PacketType::ServerReadTaskRequest);
+    }
+
+    #[test]
+    fn test_server_read_task_request_respond() {
+        let request = ServerReadTaskRequest { protocol_version: 7 };
+        let response = request.respond(Some("part_1".to_string()));
+        assert_eq!(response.protocol_version, READ_TASK_PROTOCOL_VERSION);
+        assert_eq!(response.task(), Some("part_1"));
+    }
+
+    #[test]
+    fn test_server_read_task_request_insufficient_data() {
+        let mut buf = BytesMut::from(&[1u8, 2][..]);
+        assert!(matches!(
+            ServerReadTaskRequest::deserialize(&mut buf).unwrap_err(),
+            Error::Protocol(_)
+        ));
+    }
+}
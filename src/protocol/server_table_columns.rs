@@ -0,0 +1,276 @@
+//! Server table columns packet implementation
+
+use crate::error::{Error, Result};
+use crate::protocol::{Packet, PacketType};
+use crate::types::Block;
+use bytes::{Buf, BufMut, BytesMut};
+
+/// First line of a columns description
+const DESCRIPTION_VERSION: &str = "columns format version: 1";
+
+/// Kinds of default expression a column can have
+const DEFAULT_KINDS: [&str; 4] = ["DEFAULT", "MATERIALIZED", "ALIAS", "EPHEMERAL"];
+
+/// Column of the table an insert goes to
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ColumnDescription {
+    /// Column name
+    pub name: String,
+    /// Column type
+    pub type_name: String,
+    /// `DEFAULT`, `MATERIALIZED`, `ALIAS` or `EPHEMERAL`; empty for plain columns
+    pub default_kind: String,
+    /// Default expression; empty for plain columns
+    pub default_expression: String,
+}
+
+impl ColumnDescription {
+    /// Create a plain column
+    pub fn new(name: impl Into<String>, type_name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            type_name: type_name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the default expression
+    pub fn default_expression(mut self, kind: impl Into<String>, expression: impl Into<String>) -> Self {
+        self.default_kind = kind.into();
+        self.default_expression = expression.into();
+        self
+    }
+}
+
+/// Server table columns packet
+///
+/// Sent before the server asks for the data of an insert. It describes the
+/// columns of the target table, including their default expressions.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ServerTableColumns {
+    /// Name of the external table the description is for; empty for the insert target
+    pub table_name: String,
+    /// Columns of the table, in table order
+    pub columns: Vec<ColumnDescription>,
+}
+
+impl ServerTableColumns {
+    /// Create a new table columns packet
+    pub fn new(columns: Vec<ColumnDescription>) -> Self {
+        Self {
+            table_name: String::new(),
+            columns,
+        }
+    }
+
+    /// Get the columns
+    pub fn columns(&self) -> &[ColumnDescription] {
+        &self.columns
+    }
+
+    /// Get a column by name
+    pub fn column(&self, name: &str) -> Option<&ColumnDescription> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// Check that a block can be inserted into the table
+    ///
+    /// Every block column must exist in the table with the same type and must
+    /// not be `MATERIALIZED` or `ALIAS`.
+    pub fn validate_block(&self, block: &Block) -> Result<()> {
+        for column in &block.columns {
+            let table_column = self.column(&column.name).ok_or_else(|| {
+                Error::InvalidData(format!("Column {} does not exist in the table", column.name))
+            })?;
+            if matches!(table_column.default_kind.as_str(), "MATERIALIZED" | "ALIAS") {
+                return Err(Error::InvalidData(format!(
+                    "Column {} is {} and cannot be inserted",
+                    column.name, table_column.default_kind
+                )));
+            }
+            if table_column.type_name != column.type_name {
+                return Err(Error::InvalidData(format!(
+                    "Column {} is {} in the block but {} in the table",
+                    column.name, column.type_name, table_column.type_name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the server's text description of the columns
+    ///
+    /// Column comments, codecs, TTLs and settings are skipped.
+    pub fn parse_description(description: &str) -> Result<Vec<ColumnDescription>> {
+        let invalid = |what: &str| Error::Protocol(format!("Invalid columns description: {}", what));
+        let mut lines = description.lines();
+        if lines.next() != Some(DESCRIPTION_VERSION) {
+            return Err(invalid("unknown format version"));
+        }
+        let count: usize = lines
+            .next()
+            .and_then(|line| line.strip_suffix(" columns:"))
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(|| invalid("missing column count"))?;
+
+        let mut columns = Vec::with_capacity(count.min(description.len()));
+        for line in lines.take(count) {
+            let (name, rest) = parse_back_quoted(line).ok_or_else(|| invalid(line))?;
+            let mut fields = rest.strip_prefix(' ').ok_or_else(|| invalid(line))?.split('\t');
+            let mut column = ColumnDescription::new(name, unescape(fields.next().unwrap_or_default()));
+            while let Some(field) = fields.next() {
+                if DEFAULT_KINDS.contains(&field) {
+                    let expression = fields.next().ok_or_else(|| invalid(line))?;
+                    column = column.default_expression(field, unescape(expression));
+                }
+            }
+            columns.push(column);
+        }
+        if columns.len() != count {
+            return Err(invalid(&format!("expected {} columns, got {}", count, columns.len())));
+        }
+        Ok(columns)
+    }
+
+    /// Format the columns as the server's text description
+    pub fn description(&self) -> String {
+        let mut description = format!("{}\n{} columns:\n", DESCRIPTION_VERSION, self.columns.len());
+        for column in &self.columns {
+            description.push('`');
+            description.push_str(&column.name.replace('\\', "\\\\").replace('`', "\\`"));
+            description.push_str("` ");
+            description.push_str(&escape(&column.type_name));
+            if !column.default_kind.is_empty() {
+                description.push('\t');
+                description.push_str(&column.default_kind);
+                description.push('\t');
+                description.push_str(&escape(&column.default_expression));
+            }
+            description.push('\n');
+        }
+        description
+    }
+}
+
+/// Split a back-quoted name off the start of a line
+fn parse_back_quoted(line: &str) -> Option<(String, &str)> {
+    let rest = line.strip_prefix('`')?;
+    let mut name = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '`' => return Some((name, &rest[i + 1..])),
+            '\\' => name.push(chars.next()?.1),
+            _ => name.push(c),
+        }
+    }
+    None
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+impl Packet for ServerTableColumns {
+    fn packet_type(&self) -> PacketType {
+        PacketType::ServerTableColumns
+    }
+
+    fn serialize(&self, buf: &mut BytesMut) -> Result<()> {
+        for s in [self.table_name.as_str(), self.description().as_str()] {
+            buf.put_u64_le(s.len() as u64);
+            buf.extend_from_slice(s.as_bytes());
+        }
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        let mut strings = Vec::with_capacity(2);
+        for field in ["table name", "columns description"] {
+            if buf.len() < 8 {
+                return Err(Error::Protocol(format!(
+                    "Insufficient data for ServerTableColumns {}",
+                    field
+                )));
+            }
+            let len = buf.get_u64_le() as usize;
+            if len > buf.len() {
+                return Err(Error::Protocol(format!(
+                    "Invalid {} length: {} (available: {})",
+                    field,
+                    len,
+                    buf.len()
+                )));
+            }
+            let bytes = buf.copy_to_bytes(len);
+            strings.push(
+                String::from_utf8(bytes.to_vec())
+                    .map_err(|e| Error::Protocol(format!("Invalid UTF-8 in {}: {}", field, e)))?,
+            );
+        }
+
+        let columns = Self::parse_description(&strings[1])?;
+        Ok(ServerTableColumns {
+            table_name: strings.swap_remove(0),
+            columns,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Column, ColumnData};
+
+    #[test]
+    fn test_server_table_columns() {
+        let description = "columns format version: 1\n4 columns:\n`id` UInt64\n`we\\`ird` String\tDEFAULT\t\\'a\\\\tb\\'\n\
+                           `day` Date\tMATERIALIZED\ttoDate(ts)\tCOMMENT \\'day\\'\n`ts` DateTime\tCODEC Delta\n";
+        let columns = ServerTableColumns::parse_description(description).unwrap();
+        assert_eq!(
+            columns,
+            vec![
+                ColumnDescription::new("id", "UInt64"),
+                ColumnDescription::new("we`ird", "String").default_expression("DEFAULT", "'a\\tb'"),
+                ColumnDescription::new("day", "Date").default_expression("MATERIALIZED", "toDate(ts)"),
+                ColumnDescription::new("ts", "DateTime"),
+            ]
+        );
+
+        let packet = ServerTableColumns::new(columns);
+        let mut buf = BytesMut::new();
+        packet.serialize(&mut buf).unwrap();
+        let decoded = ServerTableColumns::deserialize(&mut buf).unwrap();
+        assert_eq!(decoded, packet);
+        assert_eq!(decoded.column("day").unwrap().default_kind, "MATERIALIZED");
+
+        let block = |name: &str, type_name: &str| {
+            Block::with_columns(vec![Column::new(name, type_name, ColumnData::UInt64(vec![1]))])
+        };
+        assert!(decoded.validate_block(&block("id", "UInt64")).is_ok());
+        assert!(decoded.validate_block(&block("id", "UInt32")).is_err());
+        assert!(decoded.validate_block(&block("day", "UInt64")).is_err());
+        assert!(decoded.validate_block(&block("missing", "UInt64")).is_err());
+
+        assert!(ServerTableColumns::parse_description("columns format version: 1\n2 columns:\n`id` UInt64\n").is_err());
+        assert!(ServerTableColumns::parse_description("columns format version: 2\n0 columns:\n").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Server timezone update packet implementation
+
+use super::varint::read_var_string;
+use crate::error::{Error, Result};
+use crate::protocol::{Packet, PacketType};
+use crate::types::parse_timezone;
+use bytes::{Buf, BufMut, BytesMut};
+use chrono_tz::Tz;
+
+/// Server timezone update packet
+///
+/// Sent by the server when the session timezone changes, e.g. after
+/// `SET session_timezone = ...`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerTimezoneUpdate {
+    /// IANA name of the new timezone
+    pub timezone: String,
+}
+
+impl ServerTimezoneUpdate {
+    /// Create a new timezone update packet
+    pub fn new(timezone: impl Into<String>) -> Self {
+        Self {
+            timezone: timezone.into(),
+        }
+    }
+
+    /// Get the timezone name
+    pub fn timezone(&self) -> &str {
+        &self.timezone
+    }
+
+    /// Resolve the timezone
+    pub fn tz(&self) -> Result<Tz> {
+        parse_timezone(&self.timezone)
+    }
+
+    /// Decode the payload of a native protocol frame
+    pub fn from_frame_payload(payload: &[u8]) -> Result<Self> {
+        let timezone = read_var_string(&mut BytesMut::from(payload))?;
+        Ok(ServerTimezoneUpdate { timezone })
+    }
+}
+
+impl Packet for ServerTimezoneUpdate {
+    fn packet_type(&self) -> PacketType {
+        PacketType::ServerTimezoneUpdate
+    }
+
+    fn serialize(&self, buf: &mut BytesMut) -> Result<()> {
+        let timezone = self.timezone.as_bytes();
+        buf.put_u64_le(timezone.len() as u64);
+        buf.extend_from_slice(timezone);
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        if buf.len() < 8 {
+            return Err(Error::Protocol("Insufficient data for ServerTimezoneUpdate packet".to_string()));
+        }
+
+        let timezone_len = buf.get_u64_le() as usize;
+        if timezone_len > buf.len() {
+            return Err(Error::Protocol(format!(
+                "Invalid timezone length: {} (available: {})",
+                timezone_len,
+                buf.len()
+            )));
+        }
+
+        let timezone_bytes = buf.copy_to_bytes(timezone_len);
+        let timezone = String::from_utf8(timezone_bytes.to_vec())
+            .map_err(|e| Error::Protocol(format!("Invalid UTF-8 in timezone: {}", e)))?;
+
+        Ok(ServerTimezoneUpdate { timezone })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_timezone_update_roundtrip() {
+        let update = ServerTimezoneUpdate::new("Europe/Berlin");
+        let mut buf = BytesMut::new();
+        update.serialize(&mut buf).unwrap();
+
+        let decoded = ServerTimezoneUpdate::deserialize(&mut buf).unwrap();
+        assert_eq!(decoded, update);
+        assert_eq!(decoded.tz().unwrap(), chrono_tz::Europe::Berlin);
+    }
+
+    #[test]
+    fn test_server_timezone_update_from_frame_payload() {
+        let update = ServerTimezoneUpdate::from_frame_payload(b"\x0aAsia/Tokyo").unwrap();
+        assert_eq!(update.timezone(), "Asia/Tokyo");
+        assert!(ServerTimezoneUpdate::from_frame_payload(b"\x0aAsia").is_err());
+    }
+
+    #[test]
+    fn test_server_timezone_update_unknown_zone() {
+        assert!(ServerTimezoneUpdate::new("Mars/Olympus").tz().is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Query settings as sent in the `ClientQuery` packet
+//!
+//! From revision [`SETTINGS_AS_STRINGS_REVISION`] on, each setting is its
+//! name, a VarUInt of flags and the value as a string; custom settings carry
+//! the value in the server's field dump format (`UInt64_1`, `'text'`). Older
+//! revisions send the value in the binary form of the setting's type:
+//! integers as VarUInt, everything else as a string. Either way the list ends
+//! with an empty name.
+
+use super::varint::{read_var_string, read_varuint, write_var_string, write_varuint};
+use crate::error::{Error, Result};
+use crate::types::Value;
+use bytes::BytesMut;
+use std::collections::{HashMap, HashSet};
+
+/// First protocol revision that serializes settings as strings with flags
+pub const SETTINGS_AS_STRINGS_REVISION: u64 = 54429;
+
+/// The server must fail the query if it does not know the setting
+pub const SETTING_FLAG_IMPORTANT: u64 = 0x01;
+
+/// The setting is a user-defined custom setting
+pub const SETTING_FLAG_CUSTOM: u64 = 0x02;
+
+/// Prefix the server accepts for custom settings by default
+const CUSTOM_SETTING_PREFIX: &str = "custom_";
+
+/// Write settings in the format of the given protocol revision
+///
+/// Settings are written in name order so the packet is deterministic.
+pub(crate) fn write_settings(
+    buf: &mut BytesMut,
+    settings: &HashMap<String, Value>,
+    important: &HashSet<String>,
+    revision: u64,
+) -> Result<()> {
+    let mut names: Vec<&String> = settings.keys().collect();
+    names.sort();
+
+    for name in names {
+        let value = &settings[name];
+        write_var_string(buf, name);
+        if revision >= SETTINGS_AS_STRINGS_REVISION {
+            let custom = name.starts_with(CUSTOM_SETTING_PREFIX);
+            let mut flags = 0;
+            if important.contains(name) {
+                flags |= SETTING_FLAG_IMPORTANT;
+            }
+            if custom {
+                flags |= SETTING_FLAG_CUSTOM;
+            }
+            write_varuint(buf, flags);
+            let text = if custom { dump_field(name, value)? } else { setting_string(name, value)? };
+            write_var_string(buf, &text);
+        } else {
+            write_legacy_value(buf, name, value)?;
+        }
+    }
+    write_var_string(buf, "");
+    Ok(())
+}
+
+/// Read settings written by [`write_settings`], with their important flags
+///
+/// Only the string format can be read back: the binary format of older
+/// revisions depends on the server's type of each setting.
+pub(crate) fn read_settings(
+    buf: &mut BytesMut,
+    revision: u64,
+) -> Result<(HashMap<String, Value>, HashSet<String>)> {
+    let mut settings = HashMap::new();
+    let mut important = HashSet::new();
+    loop {
+        let name = read_var_string(buf)?;
+        if name.is_empty() {
+            return Ok((settings, important));
+        }
+        if revision < SETTINGS_AS_STRINGS_REVISION {
+            return Err(Error::Unsupported(format!(
+                "Cannot decode setting {} in the binary format of revision {}",
+                name, revision
+            )));
+        }
+
+        let flags = read_varuint(buf)?;
+        let text = read_var_string(buf)?;
+        let value = if flags & SETTING_FLAG_CUSTOM != 0 {
+            restore_field(&text)
+        } else {
+            Value::String(text)
+        };
+        if flags & SETTING_FLAG_IMPORTANT != 0 {
+            important.insert(name.clone());
+        }
+        settings.insert(name, value);
+    }
+}
+
+fn unsupported(name: &str, value: &Value) -> Error {
+    Error::Unsupported(format!("Cannot send a {} value as setting {}", value.type_name(), name))
+}
+
+/// Format a value the way the server parses regular settings
+fn setting_string(name: &str, value: &Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Nullable(Some(inner)) => setting_string(name, inner),
+        v if is_number(v) => Ok(v.to_string()),
+        other => Err(unsupported(name, other)),
+    }
+}
+
+/// Format a value in the server's field dump format
+fn dump_field(name: &str, value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::UInt8(_) | Value::UInt16(_) | Value::UInt32(_) | Value::UInt64(_) => format!("UInt64_{}", value),
+        Value::Int8(_) | Value::Int16(_) | Value::Int32(_) | Value::Int64(_) => format!("Int64_{}", value),
+        Value::Float32(_) | Value::Float64(_) => format!("Float64_{}", value),
+        Value::String(s) => format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+        Value::Nullable(Some(inner)) => return dump_field(name, inner),
+        other => return Err(unsupported(name, other)),
+    })
+}
+
+/// Parse a value from the server's field dump format
+fn restore_field(text: &str) -> Value {
+    let number = |prefix: &str| text.strip_prefix(prefix);
+    if let Some(v) = number("UInt64_").and_then(|v| v.parse().ok()) {
+        return Value::UInt64(v);
+    }
+    if let Some(v) = number("Int64_").and_then(|v| v.parse().ok()) {
+        return Value::Int64(v);
+    }
+    if let Some(v) = number("Float64_").and_then(|v| v.parse().ok()) {
+        return Value::Float64(v);
+    }
+    match text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        Some(quoted) => Value::String(quoted.replace("\\'", "'").replace("\\\\", "\\")),
+        None => Value::String(text.to_string()),
+    }
+}
+
+/// Write a value in the binary format used before string settings
+fn write_legacy_value(buf: &mut BytesMut, name: &str, value: &Value) -> Result<()> {
+    let unsigned = match value {
+        Value::UInt8(v) => Some(*v as u64),
+        Value::UInt16(v) => Some(*v as u64),
+        Value::UInt32(v) => Some(*v as u64),
+        Value::UInt64(v) => Some(*v),
+        Value::Int8(v) => u64::try_from(*v).ok(),
+        Value::Int16(v) => u64::try_from(*v).ok(),
+        Value::Int32(v) => u64::try_from(*v).ok(),
+        Value::Int64(v) => u64::try_from(*v).ok(),
+        Value::Nullable(Some(inner)) => return write_legacy_value(buf, name, inner),
+        _ => None,
+    };
+    match unsigned {
+        Some(v) => write_varuint(buf, v),
+        None if is_integer(value) => return Err(unsupported(name, value)),
+        None => write_var_string(buf, &setting_string(name, value)?),
+    }
+    Ok(())
+}
+
+fn is_integer(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::UInt8(_)
+            | Value::UInt16(_)
+            | Value::UInt32(_)
+            | Value::UInt64(_)
+            | Value::Int8(_)
+            | Value::Int16(_)
+            | Value::Int32(_)
+            | Value::Int64(_)
+    )
+}
+
+fn is_number(value: &Value) -> bool {
+    is_integer(value) || matches!(value, Value::Float32(_) | Value::Float64(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(entries: &[(&str, Value)]) -> HashMap<String, Value> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn test_string_settings_format() {
+        let values = settings(&[
+            ("max_threads", Value::UInt64(4)),
+            ("custom_tag", Value::String("it's".to_string())),
+        ]);
+        let important = HashSet::from(["max_threads".to_string()]);
+
+        let mut buf = BytesMut::new();
+        write_settings(&mut buf, &values, &important, SETTINGS_AS_STRINGS_REVISION).unwrap();
+        let mut expected = BytesMut::new();
+        write_var_string(&mut expected, "custom_tag");
+        write_varuint(&mut expected, SETTING_FLAG_CUSTOM);
+        write_var_string(&mut expected, "'it\\'s'");
+        write_var_string(&mut expected, "max_threads");
+        write_varuint(&mut expected, SETTING_FLAG_IMPORTANT);
+        write_var_string(&mut expected, "4");
+        write_var_string(&mut expected, "");
+        assert_eq!(buf, expected);
+
+        let (read, read_important) = read_settings(&mut buf, SETTINGS_AS_STRINGS_REVISION).unwrap();
+        assert_eq!(read.get("max_threads"), Some(&Value::String("4".to_string())));
+        assert_eq!(read.get("custom_tag"), Some(&Value::String("it's".to_string())));
+        assert_eq!(read_important, important);
+        assert_eq!(restore_field("UInt64_7"), Value::UInt64(7));
+    }
+
+    #[test]
+    fn test_legacy_settings_format() {
+        let values = settings(&[
+            ("max_block_size", Value::UInt64(300)),
+            ("load_balancing", Value::String("random".to_string())),
+        ]);
+        let mut buf = BytesMut::new();
+        write_settings(&mut buf, &values, &HashSet::new(), 54428).unwrap();
+        let mut expected = BytesMut::new();
+        write_var_string(&mut expected, "load_balancing");
+        write_var_string(&mut expected, "random");
+        write_var_string(&mut expected, "max_block_size");
+        write_varuint(&mut expected, 300);
+        write_var_string(&mut expected, "");
+        assert_eq!(buf, expected);
+        assert!(matches!(read_settings(&mut buf, 54428), Err(Error::Unsupported(_))));
+
+        let negative = settings(&[("max_threads", Value::Int64(-1))]);
+        assert!(write_settings(&mut BytesMut::new(), &negative, &HashSet::new(), 54428).is_err());
+        let array = settings(&[("x", Value::Array(vec![]))]);
+        assert!(write_settings(&mut BytesMut::new(), &array, &HashSet::new(), 54429).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Per-connection protocol counters
+//!
+//! A [`ProtocolStats`] handle counts the bytes and packets exchanged on a
+//! connection, plus the sizes going in and out of compression. Handles are
+//! cheap to clone and share their counters, so readers and writers created
+//! for a single exchange all feed the same connection totals.
+
+use crate::protocol::{PacketDirection, PacketType};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+struct Counters {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    uncompressed_sent: AtomicU64,
+    compressed_sent: AtomicU64,
+    uncompressed_received: AtomicU64,
+    compressed_received: AtomicU64,
+    packets_sent: Mutex<BTreeMap<u64, u64>>,
+    packets_received: Mutex<BTreeMap<u64, u64>>,
+}
+
+/// Shared byte, packet and compression counters for one connection
+#[derive(Debug, Clone, Default)]
+pub struct ProtocolStats {
+    counters: Arc<Counters>,
+}
+
+impl ProtocolStats {
+    /// Create a new set of counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a packet of the given type code and total wire size
+    pub fn record_packet(&self, direction: PacketDirection, packet_type: u64, bytes: usize) {
+        let c = &self.counters;
+        let (total, packets) = match direction {
+            PacketDirection::Sent => (&c.bytes_sent, &c.packets_sent),
+            PacketDirection::Received => (&c.bytes_received, &c.packets_received),
+        };
+        total.fetch_add(bytes as u64, Ordering::Relaxed);
+        *packets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(packet_type)
+            .or_insert(0) += 1;
+    }
+
+    /// Record a payload passing through compression
+    pub fn record_compression(&self, direction: PacketDirection, uncompressed: usize, compressed: usize) {
+        let c = &self.counters;
+        let (raw, packed) = match direction {
+            PacketDirection::Sent => (&c.uncompressed_sent, &c.compressed_sent),
+            PacketDirection::Received => (&c.uncompressed_received, &c.compressed_received),
+        };
+        raw.fetch_add(uncompressed as u64, Ordering::Relaxed);
+        packed.fetch_add(compressed as u64, Ordering::Relaxed);
+    }
+
+    /// Take a point-in-time copy of the counters
+    pub fn snapshot(&self) -> ConnectionStats {
+        let c = &self.counters;
+        let packets = |map: &Mutex<BTreeMap<u64, u64>>| {
+            map.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .map(|(code, count)| (packet_type_name(*code), *count))
+                .collect()
+        };
+        ConnectionStats {
+            bytes_sent: c.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: c.bytes_received.load(Ordering::Relaxed),
+            packets_sent: packets(&c.packets_sent),
+            packets_received: packets(&c.packets_received),
+            uncompressed_bytes_sent: c.uncompressed_sent.load(Ordering::Relaxed),
+            compressed_bytes_sent: c.compressed_sent.load(Ordering::Relaxed),
+            uncompressed_bytes_received: c.uncompressed_received.load(Ordering::Relaxed),
+            compressed_bytes_received: c.compressed_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn packet_type_name(code: u64) -> String {
+    match PacketType::from_u64(code) {
+        Some(packet_type) => format!("{:?}", packet_type),
+        None => format!("Unknown({})", code),
+    }
+}
+
+/// Snapshot of the protocol counters of a connection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// Bytes written to the server, headers included
+    pub bytes_sent: u64,
+    /// Bytes read from the server, headers included
+    pub bytes_received: u64,
+    /// Packets sent, by packet type name
+    pub packets_sent: BTreeMap<String, u64>,
+    /// Packets received, by packet type name
+    pub packets_received: BTreeMap<String, u64>,
+    /// Payload bytes handed to the compressor
+    pub uncompressed_bytes_sent: u64,
+    /// Payload bytes produced by the compressor
+    pub compressed_bytes_sent: u64,
+    /// Payload bytes produced by the decompressor
+    pub uncompressed_bytes_received: u64,
+    /// Payload bytes handed to the decompressor
+    pub compressed_bytes_received: u64,
+}
+
+impl ConnectionStats {
+    /// Total number of packets sent
+    pub fn total_packets_sent(&self) -> u64 {
+        self.packets_sent.values().sum()
+    }
+
+    /// Total number of packets received
+    pub fn total_packets_received(&self) -> u64 {
+        self.packets_received.values().sum()
+    }
+
+    /// Compression ratio (uncompressed / compressed) of outgoing data
+    pub fn compression_ratio_sent(&self) -> Option<f64> {
+        ratio(self.uncompressed_bytes_sent, self.compressed_bytes_sent)
+    }
+
+    /// Compression ratio (uncompressed / compressed) of incoming data
+    pub fn compression_ratio_received(&self) -> Option<f64> {
+        ratio(self.uncompressed_bytes_received, self.compressed_bytes_received)
+    }
+
+    /// Counters accumulated since an earlier snapshot of the same connection
+    pub fn since(&self, earlier: &ConnectionStats) -> ConnectionStats {
+        let delta = |now: &BTreeMap<String, u64>, then: &BTreeMap<String, u64>| {
+            now.iter()
+                .map(|(name, count)| {
+                    (name.clone(), count.saturating_sub(then.get(name).copied().unwrap_or(0)))
+                })
+                .filter(|(_, count)| *count > 0)
+                .collect()
+        };
+        ConnectionStats {
+            bytes_sent: self.bytes_sent.saturating_sub(earlier.bytes_sent),
+            bytes_received: self.bytes_received.saturating_sub(earlier.bytes_received),
+            packets_sent: delta(&self.packets_sent, &earlier.packets_sent),
+            packets_received: delta(&self.packets_received, &earlier.packets_received),
+            uncompressed_bytes_sent: self
+                .uncompressed_bytes_sent
+                .saturating_sub(earlier.uncompressed_bytes_sent),
+            compressed_bytes_sent: self.compressed_bytes_sent.saturating_sub(earlier.compressed_bytes_sent),
+            uncompressed_bytes_received: self
+                .uncompressed_bytes_received
+                .saturating_sub(earlier.uncompressed_bytes_received),
+            compressed_bytes_received: self
+                .compressed_bytes_received
+                .saturating_sub(earlier.compressed_bytes_received),
+        }
+    }
+}
+
+fn ratio(uncompressed: u64, compressed: u64) -> Option<f64> {
+    if compressed == 0 {
+        None
+    } else {
+        Some(uncompressed as f64 / compressed as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_deltas() {
+        let stats = ProtocolStats::new();
+        let shared = stats.clone();
+        shared.record_packet(PacketDirection::Sent, PacketType::ClientQuery.to_u64(), 40);
+        shared.record_packet(PacketDirection::Received, PacketType::ServerData.to_u64(), 100);
+        let before = stats.snapshot();
+
+        stats.record_packet(PacketDirection::Received, PacketType::ServerData.to_u64(), 60);
+        stats.record_packet(PacketDirection::Received, 999, 16);
+        stats.record_compression(PacketDirection::Received, 300, 100);
+
+        let after = stats.snapshot();
+        assert_eq!(after.bytes_received, 176);
+        assert_eq!(after.packets_received.get("ServerData"), Some(&2));
+        assert_eq!(after.packets_received.get("Unknown(999)"), Some(&1));
+        assert_eq!(after.compression_ratio_received(), Some(3.0));
+        assert_eq!(after.compression_ratio_sent(), None);
+
+        let delta = after.since(&before);
+        assert_eq!(delta.bytes_sent, 0);
+        assert_eq!(delta.bytes_received, 76);
+        assert!(delta.packets_sent.is_empty());
+        assert_eq!(delta.total_packets_received(), 2);
+    }
+}
@@ -0,0 +1,439 @@
+//! Wire-level packet tracing
+//!
+//! A [`PacketTracer`] logs every packet exchanged on a connection (type,
+//! size and a hexdump of the header) and can optionally capture the packets
+//! to a trace file. Tracers are cheap to clone and share their state, so
+//! tracing can be switched on and off at runtime for live connections.
+//! Passwords in handshake packets are masked before they are logged or
+//! captured unless redaction is turned off.
+
+use super::varint::{read_var_string, read_varuint};
+use crate::error::{Error, Result};
+use crate::protocol::PacketType;
+use bytes::BytesMut;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Magic bytes at the start of a trace file
+pub const TRACE_FILE_MAGIC: &[u8; 8] = b"CHTRACE1";
+
+/// Default number of body bytes included in hexdumps and captures
+pub const DEFAULT_CAPTURE_BYTES: usize = 64;
+
+/// Direction of a traced packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    /// Packet sent by the client
+    Sent,
+    /// Packet received from the server
+    Received,
+}
+
+impl PacketDirection {
+    /// Get the direction name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PacketDirection::Sent => "sent",
+            PacketDirection::Received => "received",
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            PacketDirection::Sent => 0,
+            PacketDirection::Received => 1,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PacketDirection::Sent),
+            1 => Some(PacketDirection::Received),
+            _ => None,
+        }
+    }
+}
+
+/// A single packet captured by the tracer
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    /// Capture time in microseconds since the Unix epoch
+    pub timestamp_micros: u64,
+    /// Packet direction
+    pub direction: PacketDirection,
+    /// Raw packet type
+    pub packet_type: u64,
+    /// Full packet body size in bytes
+    pub size: u64,
+    /// Captured body bytes (may be truncated to the capture limit)
+    pub data: Vec<u8>,
+}
+
+impl TraceRecord {
+    /// Get the packet type, if known
+    pub fn packet_type(&self) -> Option<PacketType> {
+        PacketType::from_u64(self.packet_type)
+    }
+
+    /// Check if the whole packet body was captured
+    pub fn is_complete(&self) -> bool {
+        self.data.len() as u64 == self.size
+    }
+
+    /// Write the record in trace file format
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.timestamp_micros.to_le_bytes())?;
+        writer.write_all(&[self.direction.to_u8()])?;
+        writer.write_all(&self.packet_type.to_le_bytes())?;
+        writer.write_all(&self.size.to_le_bytes())?;
+        writer.write_all(&(self.data.len() as u64).to_le_bytes())?;
+        writer.write_all(&self.data)?;
+        Ok(())
+    }
+
+    /// Read the next record, returning `None` at end of stream
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Option<Self>> {
+        let mut timestamp = [0u8; 8];
+        match reader.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut header = [0u8; 25];
+        reader.read_exact(&mut header)?;
+
+        let direction = PacketDirection::from_u8(header[0])
+            .ok_or_else(|| Error::InvalidData(format!("Invalid packet direction: {}", header[0])))?;
+        let packet_type = u64::from_le_bytes(header[1..9].try_into().unwrap());
+        let size = u64::from_le_bytes(header[9..17].try_into().unwrap());
+        let captured = u64::from_le_bytes(header[17..25].try_into().unwrap());
+
+        if captured > size {
+            return Err(Error::InvalidData(format!(
+                "Captured length {} exceeds packet size {}",
+                captured, size
+            )));
+        }
+
+        let mut data = vec![0u8; captured as usize];
+        reader.read_exact(&mut data)?;
+
+        Ok(Some(Self {
+            timestamp_micros: u64::from_le_bytes(timestamp),
+            direction,
+            packet_type,
+            size,
+            data,
+        }))
+    }
+}
+
+/// Read all records from a trace file
+pub fn read_trace_file(path: impl AsRef<Path>) -> Result<Vec<TraceRecord>> {
+    let mut reader = io::BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != TRACE_FILE_MAGIC {
+        return Err(Error::InvalidData("Not a packet trace file".to_string()));
+    }
+
+    let mut records = Vec::new();
+    while let Some(record) = TraceRecord::read_from(&mut reader)? {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Format bytes as a hexdump with offsets and an ASCII column
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("{:08x}  {:<47}  |{}|", i * 16, hex.join(" "), ascii));
+    }
+    out
+}
+
+/// Mask the password in a client hello body with `*` bytes
+///
+/// A body that cannot be parsed as a hello is masked entirely.
+fn redact_hello(body: &[u8]) -> Vec<u8> {
+    let password_range = || -> Result<(usize, usize)> {
+        let mut rest = BytesMut::from(body);
+        read_var_string(&mut rest)?;
+        for _ in 0..3 {
+            read_varuint(&mut rest)?;
+        }
+        read_var_string(&mut rest)?;
+        read_var_string(&mut rest)?;
+        let len = read_varuint(&mut rest)? as usize;
+        let start = body.len() - rest.len();
+        Ok((start, body.len().min(start + len)))
+    };
+
+    let mut redacted = body.to_vec();
+    let (start, end) = password_range().unwrap_or((0, body.len()));
+    redacted[start..end].fill(b'*');
+    redacted
+}
+
+struct TracerInner {
+    enabled: AtomicBool,
+    redact_secrets: AtomicBool,
+    capture_bytes: AtomicUsize,
+    sink: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+/// Opt-in packet tracer shared by all connections created from the same options
+#[derive(Clone)]
+pub struct PacketTracer {
+    inner: Arc<TracerInner>,
+}
+
+impl PacketTracer {
+    /// Create a new, disabled tracer that only logs
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(TracerInner {
+                enabled: AtomicBool::new(false),
+                redact_secrets: AtomicBool::new(true),
+                capture_bytes: AtomicUsize::new(DEFAULT_CAPTURE_BYTES),
+                sink: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Create an enabled tracer that also captures packets to a trace file
+    pub fn with_file(path: impl AsRef<Path>) -> Result<Self> {
+        let tracer = Self::new();
+        tracer.set_output(BufWriter::new(File::create(path)?))?;
+        tracer.enable();
+        Ok(tracer)
+    }
+
+    /// Capture packets to the given writer in trace file format
+    pub fn set_output<W: Write + Send + 'static>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(TRACE_FILE_MAGIC)?;
+        *self.lock_sink() = Some(Box::new(writer));
+        Ok(())
+    }
+
+    /// Stop capturing packets, flushing the current output
+    pub fn close_output(&self) -> Result<()> {
+        if let Some(mut writer) = self.lock_sink().take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Set the number of body bytes hexdumped and captured per packet
+    pub fn set_capture_bytes(&self, bytes: usize) {
+        self.inner.capture_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Get the number of body bytes hexdumped and captured per packet
+    pub fn capture_bytes(&self) -> usize {
+        self.inner.capture_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Set whether passwords are masked in logged and captured packets (on by default)
+    pub fn set_redact_secrets(&self, redact: bool) {
+        self.inner.redact_secrets.store(redact, Ordering::Relaxed);
+    }
+
+    /// Check whether passwords are masked in logged and captured packets
+    pub fn redacts_secrets(&self) -> bool {
+        self.inner.redact_secrets.load(Ordering::Relaxed)
+    }
+
+    /// Enable tracing
+    pub fn enable(&self) {
+        self.inner.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Disable tracing
+    pub fn disable(&self) {
+        self.inner.enabled.store(false, Ordering::Relaxed);
+    }
+
+    /// Check if tracing is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Trace a packet given its 16-byte header and body
+    pub fn trace(&self, direction: PacketDirection, header: &[u8], body: &[u8]) {
+        if !self.is_enabled() || header.len() < 16 {
+            return;
+        }
+
+        let packet_type = u64::from_le_bytes(header[0..8].try_into().unwrap());
+        let size = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let body = if self.redacts_secrets() && packet_type == PacketType::ClientHello.to_u64() {
+            Cow::Owned(redact_hello(body))
+        } else {
+            Cow::Borrowed(body)
+        };
+        let captured = &body[..body.len().min(self.capture_bytes())];
+
+        let type_name = match PacketType::from_u64(packet_type) {
+            Some(packet_type) => format!("{:?}", packet_type),
+            None => format!("Unknown({})", packet_type),
+        };
+        tracing::debug!(
+            "{} packet {} ({} bytes)\n{}\n{}",
+            direction.as_str(),
+            type_name,
+            size,
+            hexdump(header),
+            hexdump(captured)
+        );
+
+        let mut sink = self.lock_sink();
+        if let Some(writer) = sink.as_mut() {
+            let record = TraceRecord {
+                timestamp_micros: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_micros() as u64)
+                    .unwrap_or(0),
+                direction,
+                packet_type,
+                size,
+                data: captured.to_vec(),
+            };
+            if let Err(e) = record.write_to(writer).and_then(|_| writer.flush().map_err(Error::from)) {
+                tracing::warn!("Failed to write packet trace, closing output: {}", e);
+                *sink = None;
+            }
+        }
+    }
+
+    fn lock_sink(&self) -> std::sync::MutexGuard<'_, Option<Box<dyn Write + Send>>> {
+        self.inner.sink.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for PacketTracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for PacketTracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PacketTracer")
+            .field("enabled", &self.is_enabled())
+            .field("redact_secrets", &self.redacts_secrets())
+            .field("capture_bytes", &self.capture_bytes())
+            .field("has_output", &self.lock_sink().is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{ClientHello, ClientPing, Packet, ProtocolWriter};
+
+    fn header(packet_type: u64, size: u64) -> Vec<u8> {
+        let mut header = packet_type.to_le_bytes().to_vec();
+        header.extend_from_slice(&size.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(hexdump(b""), "");
+        assert_eq!(
+            hexdump(b"SELECT 1\x00"),
+            "00000000  53 45 4c 45 43 54 20 31 00                       |SELECT 1.|"
+        );
+        assert_eq!(hexdump(&[0u8; 17]).lines().count(), 2);
+    }
+
+    #[test]
+    fn test_trace_file_roundtrip() {
+        let path = std::env::temp_dir().join(format!("clickhouse-trace-{}.bin", uuid::Uuid::new_v4()));
+        let tracer = PacketTracer::with_file(&path).unwrap();
+
+        tracer.trace(PacketDirection::Sent, &header(4, 3), b"abc");
+        tracer.set_capture_bytes(2);
+        tracer.trace(PacketDirection::Received, &header(99, 5), b"hello");
+
+        // Disabled tracers record nothing
+        tracer.disable();
+        tracer.trace(PacketDirection::Sent, &header(4, 0), b"");
+        tracer.close_output().unwrap();
+
+        let records = read_trace_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, PacketDirection::Sent);
+        assert_eq!(records[0].packet_type(), Some(PacketType::ClientPing));
+        assert_eq!(records[0].data, b"abc");
+        assert!(records[0].is_complete());
+
+        assert_eq!(records[1].direction, PacketDirection::Received);
+        assert_eq!(records[1].packet_type(), None);
+        assert_eq!(records[1].size, 5);
+        assert_eq!(records[1].data, b"he");
+        assert!(!records[1].is_complete());
+    }
+
+    #[test]
+    fn test_protocol_writer_traces_packets() {
+        let path = std::env::temp_dir().join(format!("clickhouse-trace-{}.bin", uuid::Uuid::new_v4()));
+        let tracer = PacketTracer::with_file(&path).unwrap();
+
+        let mut out = Vec::new();
+        ProtocolWriter::new(&mut out)
+            .with_tracer(tracer.clone())
+            .write_packet(&ClientPing::new())
+            .unwrap();
+        tracer.close_output().unwrap();
+
+        let records = read_trace_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].packet_type(), Some(PacketType::ClientPing));
+        assert_eq!(records[0].data, &out[16..]);
+    }
+
+    #[test]
+    fn test_hello_password_redacted() {
+        let path = std::env::temp_dir().join(format!("clickhouse-trace-{}.bin", uuid::Uuid::new_v4()));
+        let tracer = PacketTracer::with_file(&path).unwrap();
+        let mut body = BytesMut::new();
+        ClientHello::new("rs", "db", "user", "hunter2").serialize(&mut body).unwrap();
+
+        tracer.trace(PacketDirection::Sent, &header(0, body.len() as u64), &body);
+        tracer.trace(PacketDirection::Sent, &header(0, 3), b"\xff\xff\xff");
+        tracer.set_redact_secrets(false);
+        tracer.trace(PacketDirection::Sent, &header(0, body.len() as u64), &body);
+        tracer.close_output().unwrap();
+
+        let records = read_trace_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(records[0].data.ends_with(b"user\x07*******"));
+        assert_eq!(records[1].data, b"***");
+        assert_eq!(records[2].data, &body[..]);
+        assert!(!format!("{:?}", ClientHello::new("rs", "db", "user", "hunter2")).contains("hunter2"));
+    }
+}
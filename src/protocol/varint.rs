@@ -0,0 +1,70 @@
+//! LEB128 integers and length-prefixed strings used by the ClickHouse handshake and settings
+
+use crate::error::{Error, Result};
+use bytes::{Buf, BufMut, BytesMut};
+
+/// Write an unsigned LEB128 integer
+pub(crate) fn write_varuint(buf: &mut BytesMut, mut value: u64) {
+    while value >= 0x80 {
+        buf.put_u8((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+/// Read an unsigned LEB128 integer
+pub(crate) fn read_varuint(buf: &mut BytesMut) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        if !buf.has_remaining() {
+            return Err(Error::Protocol("Unexpected end of data in VarUInt".to_string()));
+        }
+        let byte = buf.get_u8();
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::Protocol("VarUInt is longer than 10 bytes".to_string()))
+}
+
+/// Write a string prefixed with its VarUInt length
+pub(crate) fn write_var_string(buf: &mut BytesMut, s: &str) {
+    write_varuint(buf, s.len() as u64);
+    buf.put_slice(s.as_bytes());
+}
+
+/// Read a string prefixed with its VarUInt length
+pub(crate) fn read_var_string(buf: &mut BytesMut) -> Result<String> {
+    let len = read_varuint(buf)? as usize;
+    if buf.remaining() < len {
+        return Err(Error::Protocol(format!(
+            "Expected {} string bytes, only {} remaining",
+            len,
+            buf.remaining()
+        )));
+    }
+    String::from_utf8(buf.split_to(len).to_vec()).map_err(|e| Error::Protocol(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varuint_round_trip() {
+        let mut buf = BytesMut::new();
+        for value in [0, 127, 128, 300, u64::MAX] {
+            write_varuint(&mut buf, value);
+        }
+        assert_eq!(&buf[..4], &[0x00, 0x7f, 0x80, 0x01]);
+        for value in [0, 127, 128, 300, u64::MAX] {
+            assert_eq!(read_varuint(&mut buf).unwrap(), value);
+        }
+        assert!(read_varuint(&mut buf).is_err());
+
+        write_var_string(&mut buf, "héllo");
+        assert_eq!(buf[0], 6);
+        assert_eq!(read_var_string(&mut buf).unwrap(), "héllo");
+    }
+}
@@ -0,0 +1,444 @@
+//! Schema snapshots for compile-time checked queries
+//!
+//! A [`SchemaSnapshot`] records the columns of selected tables in a JSON file
+//! that is committed next to the code. The `clickhouse-macros` crate reads it
+//! at build time to check the columns of a `SELECT` and generate a typed row
+//! struct, so builds need no server. Refresh the file with
+//! [`Client::schema_snapshot`] or the CLI:
+//!
+//! ```text
+//! cargo run --features cli --bin clickhouse-rs -- schema snapshot \
+//!     --dsn clickhouse://localhost/analytics users events
+//! ```
+//!
+//! Only the select list and the table are checked: each item must be a
+//! column of the table, `*`, `count()` or have an explicit type through
+//! `CAST(expr AS Type)` or `expr::Type`. The rest of the query is passed
+//! through as written.
+
+use crate::error::{Error, Result};
+use crate::types::TypeDescriptor;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::Client;
+
+/// Default snapshot file name, relative to the crate root
+pub const SCHEMA_SNAPSHOT_FILE: &str = "clickhouse-schema.json";
+
+/// Column recorded in a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotColumn {
+    /// Column name
+    pub name: String,
+    /// Column type
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
+impl SnapshotColumn {
+    /// Create a column
+    pub fn new(name: impl Into<String>, type_name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            type_name: type_name.into(),
+        }
+    }
+}
+
+/// Columns of a set of tables, keyed by table name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+    /// Tables by the name they were captured under, such as `events` or `db.events`
+    pub tables: BTreeMap<String, Vec<SnapshotColumn>>,
+}
+
+impl SchemaSnapshot {
+    /// Create an empty snapshot
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace a table
+    pub fn add_table(&mut self, table: impl Into<String>, columns: Vec<SnapshotColumn>) {
+        self.tables.insert(table.into(), columns);
+    }
+
+    /// Get the columns of a table
+    ///
+    /// An unqualified name also matches a table captured as `db.table` when
+    /// only one database has it.
+    pub fn table(&self, table: &str) -> Option<&[SnapshotColumn]> {
+        if let Some(columns) = self.tables.get(table) {
+            return Some(columns);
+        }
+        if table.contains('.') {
+            return None;
+        }
+        let mut matches = self
+            .tables
+            .iter()
+            .filter(|(name, _)| name.rsplit_once('.').is_some_and(|(_, name)| name == table));
+        match (matches.next(), matches.next()) {
+            (Some((_, columns)), None) => Some(columns),
+            _ => None,
+        }
+    }
+
+    /// Parse a snapshot from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Serialize the snapshot as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Read a snapshot file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| Error::Configuration(format!("Cannot read schema snapshot {}: {}", path.display(), e)))?;
+        Self::from_json(&json)
+    }
+
+    /// Write the snapshot to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut json = self.to_json()?;
+        json.push('\n');
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Check the select list of a query and get its output columns
+    pub fn check_select(&self, sql: &str) -> Result<Vec<SnapshotColumn>> {
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+        let body = strip_keyword(sql, "SELECT")
+            .ok_or_else(|| invalid(format!("Expected a SELECT query, got '{}'", sql)))?;
+        let body = strip_keyword(body, "DISTINCT").unwrap_or(body);
+
+        let from = find_keyword(body, "FROM").ok_or_else(|| invalid("Query has no FROM clause".to_string()))?;
+        let (list, rest) = (&body[..from], body[from + 4..].trim_start());
+        let table = table_name(rest)?;
+        let columns = self
+            .table(&table)
+            .ok_or_else(|| invalid(format!("Table '{}' is not in the schema snapshot", table)))?;
+
+        let mut output = Vec::new();
+        for item in split_top_level(list, ',') {
+            let item = item.trim();
+            let (expr, alias) = match find_last_keyword(item, "AS") {
+                Some(at) => (item[..at].trim(), Some(unquote(item[at + 2..].trim()))),
+                None => (item, None),
+            };
+            if expr.is_empty() {
+                return Err(invalid(format!("Empty item in the select list of '{}'", sql)));
+            }
+            if expr == "*" {
+                if alias.is_some() {
+                    return Err(invalid("'*' cannot have an alias".to_string()));
+                }
+                output.extend(columns.iter().cloned());
+                continue;
+            }
+
+            let (name, type_name) = match explicit_type(expr) {
+                Some(type_name) => {
+                    TypeDescriptor::parse(&type_name)?;
+                    (alias.unwrap_or_else(|| expr.to_string()), type_name)
+                }
+                None => {
+                    let column = column_reference(expr).ok_or_else(|| {
+                        invalid(format!(
+                            "Cannot infer the type of '{}'; select a column, count() or CAST(expr AS Type)",
+                            expr
+                        ))
+                    })?;
+                    let type_name = columns
+                        .iter()
+                        .find(|c| c.name == column)
+                        .map(|c| c.type_name.clone())
+                        .ok_or_else(|| invalid(format!("Column '{}' does not exist in table '{}'", column, table)))?;
+                    (alias.unwrap_or(column), type_name)
+                }
+            };
+            output.push(SnapshotColumn::new(name, type_name));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
+    /// Capture the columns of tables into a snapshot
+    ///
+    /// Tables are recorded under the names given, so use `db.table` for
+    /// tables outside the default database.
+    pub async fn schema_snapshot(&self, tables: &[&str]) -> Result<SchemaSnapshot> {
+        let mut snapshot = SchemaSnapshot::new();
+        for table in tables {
+            let columns = self
+                .describe_table(table)
+                .await?
+                .into_iter()
+                .map(|column| SnapshotColumn::new(column.name, column.type_name))
+                .collect();
+            snapshot.add_table(*table, columns);
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Get the Rust type a column of this type converts into
+///
+/// Types without a dedicated conversion map to [`crate::types::Value`].
+pub fn rust_type(type_name: &str) -> Result<String> {
+    Ok(rust_type_of(&TypeDescriptor::parse(type_name)?))
+}
+
+fn rust_type_of(descriptor: &TypeDescriptor) -> String {
+    let path = |name: &str| format!("::clickhouse_rs::types::{}", name);
+    match descriptor {
+        TypeDescriptor::Simple(name) => match name.as_str() {
+            "UInt8" => "u8".to_string(),
+            "UInt16" => "u16".to_string(),
+            "UInt32" => "u32".to_string(),
+            "UInt64" => "u64".to_string(),
+            "Int8" => "i8".to_string(),
+            "Int16" => "i16".to_string(),
+            "Int32" => "i32".to_string(),
+            "Int64" => "i64".to_string(),
+            "Float32" => "f32".to_string(),
+            "Float64" => "f64".to_string(),
+            "Bool" => "bool".to_string(),
+            "String" => "String".to_string(),
+            "Date" | "UUID" | "UInt256" | "Int256" => path(name),
+            _ => path("Value"),
+        },
+        TypeDescriptor::FixedString(_) | TypeDescriptor::Enum { .. } => "String".to_string(),
+        TypeDescriptor::DateTime { .. } => path("DateTime"),
+        TypeDescriptor::DateTime64 { .. } => path("DateTime64"),
+        TypeDescriptor::LowCardinality(inner) => rust_type_of(inner),
+        TypeDescriptor::Nullable(inner) => format!("{}<{}>", path("Nullable"), rust_type_of(inner)),
+        TypeDescriptor::Array(inner) => format!("{}<{}>", path("Array"), rust_type_of(inner)),
+        _ => path("Value"),
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidData(message)
+}
+
+/// Mark the bytes outside quotes and parentheses
+fn top_level_mask(s: &str) -> Vec<bool> {
+    let mut mask = Vec::with_capacity(s.len());
+    let mut depth = 0usize;
+    let mut quote: Option<u8> = None;
+    let mut escaped = false;
+    for &b in s.as_bytes() {
+        let top = quote.is_none() && depth == 0;
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if b == b'\\' => escaped = true,
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None => match b {
+                b'\'' | b'"' | b'`' => quote = Some(b),
+                b'(' | b'[' => depth += 1,
+                b')' | b']' => depth = depth.saturating_sub(1),
+                _ => {}
+            },
+        }
+        mask.push(top && quote.is_none() && depth == 0);
+    }
+    mask
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+fn keyword_positions(s: &str, keyword: &str) -> Vec<usize> {
+    let mask = top_level_mask(s);
+    let bytes = s.as_bytes();
+    (0..s.len().saturating_sub(keyword.len() - 1))
+        .filter(|&i| {
+            mask[i]
+                && s.is_char_boundary(i)
+                && s.get(i..i + keyword.len()).is_some_and(|w| w.eq_ignore_ascii_case(keyword))
+                && (i == 0 || !is_word_byte(bytes[i - 1]))
+                && bytes.get(i + keyword.len()).is_none_or(|&b| !is_word_byte(b))
+        })
+        .collect()
+}
+
+fn find_keyword(s: &str, keyword: &str) -> Option<usize> {
+    keyword_positions(s, keyword).first().copied()
+}
+
+fn find_last_keyword(s: &str, keyword: &str) -> Option<usize> {
+    keyword_positions(s, keyword).last().copied()
+}
+
+fn strip_keyword<'a>(s: &'a str, keyword: &str) -> Option<&'a str> {
+    (find_keyword(s, keyword) == Some(0)).then(|| s[keyword.len()..].trim_start())
+}
+
+fn split_top_level(s: &str, separator: char) -> Vec<&str> {
+    let mask = top_level_mask(s);
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == separator && mask[i] {
+            parts.push(&s[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Remove backticks or double quotes around an identifier
+fn unquote(s: &str) -> String {
+    for q in ['`', '"'] {
+        if let Some(inner) = s.strip_prefix(q).and_then(|s| s.strip_suffix(q)) {
+            return inner.to_string();
+        }
+    }
+    s.to_string()
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse `name`, `` `name` `` or `table.name`, returning the column name
+fn column_reference(expr: &str) -> Option<String> {
+    let parts: Vec<String> = split_top_level(expr, '.').into_iter().map(|p| unquote(p.trim())).collect();
+    let quoted = |part: &str| expr.contains(&format!("`{}`", part)) || expr.contains(&format!("\"{}\"", part));
+    if parts.len() > 2 || parts.iter().any(|p| p.is_empty() || !(is_identifier(p) || quoted(p))) {
+        return None;
+    }
+    parts.last().cloned()
+}
+
+/// Get the type of `CAST(expr AS Type)`, `CAST(expr, 'Type')`, `expr::Type` or `count()`
+fn explicit_type(expr: &str) -> Option<String> {
+    let compact: String = expr.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.eq_ignore_ascii_case("count()") || compact.eq_ignore_ascii_case("count(*)") {
+        return Some("UInt64".to_string());
+    }
+
+    let mask = top_level_mask(expr);
+    if let Some(at) = expr.match_indices("::").map(|(i, _)| i).filter(|&i| mask[i]).last() {
+        return Some(expr[at + 2..].trim().to_string());
+    }
+
+    let open = expr.find('(')?;
+    if !expr[..open].trim().eq_ignore_ascii_case("CAST") || !expr.ends_with(')') {
+        return None;
+    }
+    let args = &expr[open + 1..expr.len() - 1];
+    if let Some(at) = find_last_keyword(args, "AS") {
+        return Some(args[at + 2..].trim().to_string());
+    }
+    match split_top_level(args, ',').as_slice() {
+        [_, type_name] => type_name.trim().strip_prefix('\'')?.strip_suffix('\'').map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Parse the table after `FROM`
+fn table_name(rest: &str) -> Result<String> {
+    let end = rest
+        .char_indices()
+        .scan(None, |quote: &mut Option<char>, (i, c)| {
+            match *quote {
+                Some(q) if c == q => *quote = None,
+                Some(_) => {}
+                None if c == '`' || c == '"' => *quote = Some(c),
+                None if c.is_whitespace() || c == '(' || c == ';' => return Some(Some(i)),
+                None => {}
+            }
+            Some(None)
+        })
+        .flatten()
+        .next()
+        .unwrap_or(rest.len());
+    let name = &rest[..end];
+    if name.is_empty() || rest[end..].starts_with('(') {
+        return Err(invalid(
+            "Only queries reading a table are supported, not subqueries or table functions".to_string(),
+        ));
+    }
+    Ok(split_top_level(name, '.').into_iter().map(unquote).collect::<Vec<_>>().join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> SchemaSnapshot {
+        let mut snapshot = SchemaSnapshot::new();
+        snapshot.add_table(
+            "analytics.events",
+            vec![
+                SnapshotColumn::new("id", "UInt64"),
+                SnapshotColumn::new("user", "LowCardinality(String)"),
+                SnapshotColumn::new("score", "Nullable(Float64)"),
+                SnapshotColumn::new("event time", "DateTime('UTC')"),
+            ],
+        );
+        snapshot
+    }
+
+    #[test]
+    fn test_check_select() {
+        let snapshot = SchemaSnapshot::from_json(&snapshot().to_json().unwrap()).unwrap();
+        assert_eq!(snapshot, self::snapshot());
+
+        let columns = snapshot
+            .check_select(
+                "select e.id, `event time` AS at, CAST(score, 'Float32') AS s, count() AS n, \
+                 toString(id)::String AS text FROM events AS e WHERE user = 'a,b' GROUP BY id;",
+            )
+            .unwrap();
+        let columns: Vec<_> = columns.iter().map(|c| (c.name.as_str(), c.type_name.as_str())).collect();
+        assert_eq!(
+            columns,
+            [("id", "UInt64"), ("at", "DateTime('UTC')"), ("s", "Float32"), ("n", "UInt64"), ("text", "String")]
+        );
+        assert_eq!(snapshot.check_select("SELECT DISTINCT * FROM analytics.events").unwrap().len(), 4);
+
+        for sql in [
+            "SELECT missing FROM events",
+            "SELECT id FROM other",
+            "SELECT id + 1 FROM events",
+            "SELECT id FROM (SELECT 1)",
+            "SELECT id FROM numbers(10)",
+            "SELECT CAST(id AS Nope() FROM events",
+            "INSERT INTO events VALUES",
+            "SELECT 1",
+        ] {
+            assert!(snapshot.check_select(sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_rust_type() {
+        assert_eq!(rust_type("UInt64").unwrap(), "u64");
+        assert_eq!(rust_type("LowCardinality(String)").unwrap(), "String");
+        assert_eq!(
+            rust_type("Array(Nullable(Int32))").unwrap(),
+            "::clickhouse_rs::types::Array<::clickhouse_rs::types::Nullable<i32>>"
+        );
+        assert_eq!(rust_type("DateTime64(3, 'UTC')").unwrap(), "::clickhouse_rs::types::DateTime64");
+        assert_eq!(rust_type("Map(String, UInt8)").unwrap(), "::clickhouse_rs::types::Value");
+        assert!(rust_type("Array(").is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! Wrapper for credentials that must not leak into logs
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Text shown in place of a secret value
+pub const REDACTED: &str = "***";
+
+/// A value such as a password or JWT that is redacted when printed
+///
+/// `Debug` and `Serialize` output show [`REDACTED`] instead of the value;
+/// deserializing reads the plain value. Use [`Secret::expose`] where the
+/// value is actually needed, e.g. when writing it to the wire.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Secret<T = String>(T);
+
+impl<T> Secret<T> {
+    /// Wrap a secret value
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Get the secret value
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Unwrap the secret value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl Secret<String> {
+    /// Get the secret as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Check whether the secret is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+impl From<String> for Secret<String> {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl PartialEq<str> for Secret<String> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Secret<String> {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Secret<String> {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_redaction() {
+        let secret = Secret::from("hunter2");
+        assert_eq!(format!("{:?}", secret), "Secret(***)");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"***\"");
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(secret, "hunter2");
+
+        let parsed: Secret = serde_json::from_str("\"hunter2\"").unwrap();
+        assert_eq!(parsed, secret);
+    }
+}
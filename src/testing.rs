@@ -0,0 +1,271 @@
+//! Test helpers backed by a disposable ClickHouse container
+//!
+//! Available with the `testing` feature. Requires a local Docker daemon.
+//!
+//! ```rust,no_run
+//! use clickhouse_rs::testing::ClickHouseContainer;
+//!
+//! # async fn example() -> clickhouse_rs::Result<()> {
+//! let clickhouse = ClickHouseContainer::start().await?;
+//! clickhouse
+//!     .bootstrap_schema("CREATE TABLE events (id UInt64) ENGINE = Memory")
+//!     .await?;
+//! let client = clickhouse.client();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::{http_ping, Client, ClientOptions};
+use crate::error::{Error, Result};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use testcontainers::core::{ExecCommand, IntoContainerPort};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, GenericImage, ImageExt};
+
+/// Default ClickHouse server image
+pub const DEFAULT_IMAGE: &str = "clickhouse/clickhouse-server";
+
+/// Default ClickHouse server image tag
+pub const DEFAULT_TAG: &str = "24.3";
+
+/// Native protocol port inside the container
+pub const NATIVE_PORT: u16 = 9000;
+
+/// HTTP interface port inside the container
+pub const HTTP_PORT: u16 = 8123;
+
+/// Builder for [`ClickHouseContainer`]
+#[derive(Debug, Clone)]
+pub struct ClickHouseContainerBuilder {
+    image: String,
+    tag: String,
+    username: String,
+    password: String,
+    database: String,
+    startup_timeout: Duration,
+}
+
+impl ClickHouseContainerBuilder {
+    /// Create a builder with default settings
+    pub fn new() -> Self {
+        Self {
+            image: DEFAULT_IMAGE.to_string(),
+            tag: DEFAULT_TAG.to_string(),
+            username: "default".to_string(),
+            password: "clickhouse".to_string(),
+            database: "default".to_string(),
+            startup_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Set the server image
+    pub fn image(mut self, image: impl Into<String>) -> Self {
+        self.image = image.into();
+        self
+    }
+
+    /// Set the server image tag
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = tag.into();
+        self
+    }
+
+    /// Set the username
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.username = username.into();
+        self
+    }
+
+    /// Set the password
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = password.into();
+        self
+    }
+
+    /// Set the database created on startup
+    pub fn database(mut self, database: impl Into<String>) -> Self {
+        self.database = database.into();
+        self
+    }
+
+    /// Set how long to wait for the server to become ready
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Start the container and wait until the server answers
+    pub async fn start(self) -> Result<ClickHouseContainer> {
+        let container = GenericImage::new(self.image.as_str(), self.tag.as_str())
+            .with_exposed_port(NATIVE_PORT.tcp())
+            .with_exposed_port(HTTP_PORT.tcp())
+            .with_env_var("CLICKHOUSE_USER", self.username.as_str())
+            .with_env_var("CLICKHOUSE_PASSWORD", self.password.as_str())
+            .with_env_var("CLICKHOUSE_DB", self.database.as_str())
+            .with_env_var("CLICKHOUSE_DEFAULT_ACCESS_MANAGEMENT", "1")
+            .with_startup_timeout(self.startup_timeout)
+            .start()
+            .await
+            .map_err(container_error)?;
+
+        let host = container.get_host().await.map_err(container_error)?.to_string();
+        let native_port = container.get_host_port_ipv4(NATIVE_PORT).await.map_err(container_error)?;
+        let http_port = container.get_host_port_ipv4(HTTP_PORT).await.map_err(container_error)?;
+
+        wait_until_ready(&host, http_port, self.startup_timeout).await?;
+
+        let options = ClientOptions::new()
+            .host(host.as_str())
+            .port(native_port)
+            .database(self.database.as_str())
+            .username(self.username.as_str())
+            .password(self.password.as_str());
+        let client = Client::new(options)?;
+
+        tracing::debug!("ClickHouse container {} ready at {}:{}", container.id(), host, native_port);
+
+        Ok(ClickHouseContainer {
+            container,
+            client,
+            host,
+            native_port,
+            http_port,
+            username: self.username,
+            password: self.password,
+            database: self.database,
+        })
+    }
+}
+
+impl Default for ClickHouseContainerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A running ClickHouse server container with a connected client
+///
+/// The container is stopped and removed when this value is dropped.
+pub struct ClickHouseContainer {
+    container: ContainerAsync<GenericImage>,
+    client: Client,
+    host: String,
+    native_port: u16,
+    http_port: u16,
+    username: String,
+    password: String,
+    database: String,
+}
+
+impl ClickHouseContainer {
+    /// Start a container with default settings
+    pub async fn start() -> Result<Self> {
+        ClickHouseContainerBuilder::new().start().await
+    }
+
+    /// Create a builder for a customized container
+    pub fn builder() -> ClickHouseContainerBuilder {
+        ClickHouseContainerBuilder::new()
+    }
+
+    /// Get the client connected to the container
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Get client options pointing at the container
+    pub fn options(&self) -> &ClientOptions {
+        self.client.options()
+    }
+
+    /// Get the host the container ports are mapped on
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Get the mapped native protocol port
+    pub fn native_port(&self) -> u16 {
+        self.native_port
+    }
+
+    /// Get the mapped HTTP interface port
+    pub fn http_port(&self) -> u16 {
+        self.http_port
+    }
+
+    /// Get the database created on startup
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    /// Get the container ID
+    pub fn id(&self) -> &str {
+        self.container.id()
+    }
+
+    /// Run SQL statements (separated by `;`) with `clickhouse-client` inside the container
+    pub async fn bootstrap_schema(&self, sql: &str) -> Result<()> {
+        let command = ExecCommand::new([
+            "clickhouse-client",
+            "--user",
+            self.username.as_str(),
+            "--password",
+            self.password.as_str(),
+            "--database",
+            self.database.as_str(),
+            "--multiquery",
+            "--query",
+            sql,
+        ]);
+
+        let mut result = self.container.exec(command).await.map_err(container_error)?;
+        let stderr = result.stderr_to_vec().await.map_err(container_error)?;
+
+        match result.exit_code().await.map_err(container_error)? {
+            Some(0) | None => Ok(()),
+            Some(code) => Err(Error::QueryExecution(format!(
+                "Schema bootstrap failed with exit code {}: {}",
+                code,
+                String::from_utf8_lossy(&stderr).trim()
+            ))),
+        }
+    }
+
+    /// Run the SQL statements of a file inside the container
+    pub async fn bootstrap_schema_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let sql = std::fs::read_to_string(path)?;
+        self.bootstrap_schema(&sql).await
+    }
+}
+
+impl std::fmt::Debug for ClickHouseContainer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClickHouseContainer")
+            .field("id", &self.container.id())
+            .field("host", &self.host)
+            .field("native_port", &self.native_port)
+            .field("http_port", &self.http_port)
+            .field("database", &self.database)
+            .finish()
+    }
+}
+
+/// Poll the HTTP `/ping` endpoint until the server answers
+async fn wait_until_ready(host: &str, http_port: u16, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match http_ping(host, http_port, "/ping").await {
+            Ok(()) => return Ok(()),
+            Err(e) if Instant::now() >= deadline => {
+                tracing::warn!("ClickHouse container not ready after {:?}: {}", timeout, e);
+                return Err(Error::Timeout(timeout));
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(250)).await,
+        }
+    }
+}
+
+fn container_error(e: testcontainers::TestcontainersError) -> Error {
+    Error::Custom(format!("Test container error: {}", e))
+}
@@ -0,0 +1,174 @@
+//! Typed results of the `quantiles` and `histogram` aggregate functions
+
+use super::Value;
+
+/// Result of `quantiles(level, ...)(expr)`, one value per requested level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantiles {
+    /// Requested levels, from 0 to 1
+    pub levels: Vec<f64>,
+    /// Quantile values, in the order of `levels`
+    pub values: Vec<f64>,
+}
+
+impl Quantiles {
+    /// Pair an `Array` result with the levels it was computed for
+    pub fn from_value(levels: &[f64], value: Value) -> Result<Self, String> {
+        let values = match value {
+            Value::Array(values) => values.iter().map(number).collect::<Result<Vec<_>, _>>()?,
+            other => return Err(format!("Cannot convert {} to quantiles", other.type_name())),
+        };
+        if values.len() != levels.len() {
+            return Err(format!(
+                "Got {} quantiles for {} levels",
+                values.len(),
+                levels.len()
+            ));
+        }
+        Ok(Self {
+            levels: levels.to_vec(),
+            values,
+        })
+    }
+
+    /// Get the value at a level
+    pub fn get(&self, level: f64) -> Option<f64> {
+        self.levels
+            .iter()
+            .position(|l| (l - level).abs() < f64::EPSILON)
+            .map(|index| self.values[index])
+    }
+
+    /// Iterate over `(level, value)` pairs
+    pub fn iter(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.levels.iter().copied().zip(self.values.iter().copied())
+    }
+}
+
+/// Build a `quantiles(level, ...)(expr)` call
+pub fn quantiles_sql(levels: &[f64], expr: &str) -> String {
+    let levels: Vec<String> = levels.iter().map(|level| level.to_string()).collect();
+    format!("quantiles({})({})", levels.join(", "), expr)
+}
+
+/// Bucket of a `histogram(n)(expr)` result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistogramBucket {
+    /// Lower bound
+    pub lower: f64,
+    /// Upper bound
+    pub upper: f64,
+    /// Estimated number of values in the bucket
+    pub height: f64,
+}
+
+impl HistogramBucket {
+    /// Get the bucket width
+    pub fn width(&self) -> f64 {
+        self.upper - self.lower
+    }
+}
+
+/// Result of `histogram(n)(expr)`: adaptive buckets in ascending order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// Buckets
+    pub buckets: Vec<HistogramBucket>,
+}
+
+impl Histogram {
+    /// Get the sum of all bucket heights
+    pub fn total(&self) -> f64 {
+        self.buckets.iter().map(|bucket| bucket.height).sum()
+    }
+
+    /// Get the tallest bucket
+    pub fn max_bucket(&self) -> Option<&HistogramBucket> {
+        self.buckets
+            .iter()
+            .max_by(|a, b| a.height.total_cmp(&b.height))
+    }
+
+    /// Get the bucket containing a value
+    pub fn bucket_for(&self, value: f64) -> Option<&HistogramBucket> {
+        self.buckets
+            .iter()
+            .find(|bucket| bucket.lower <= value && value <= bucket.upper)
+    }
+
+    /// Check if there are no buckets
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+impl TryFrom<Value> for Histogram {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let Value::Array(items) = value else {
+            return Err(format!("Cannot convert {} to Histogram", value.type_name()));
+        };
+        let buckets = items
+            .iter()
+            .map(|item| match item {
+                Value::Tuple(fields) if fields.len() == 3 => Ok(HistogramBucket {
+                    lower: number(&fields[0])?,
+                    upper: number(&fields[1])?,
+                    height: number(&fields[2])?,
+                }),
+                other => Err(format!("Expected a (lower, upper, height) tuple, got {}", other.type_name())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { buckets })
+    }
+}
+
+fn number(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Float64(v) => Ok(*v),
+        Value::Float32(v) => Ok(*v as f64),
+        Value::UInt8(v) => Ok(*v as f64),
+        Value::UInt16(v) => Ok(*v as f64),
+        Value::UInt32(v) => Ok(*v as f64),
+        Value::UInt64(v) => Ok(*v as f64),
+        Value::Int8(v) => Ok(*v as f64),
+        Value::Int16(v) => Ok(*v as f64),
+        Value::Int32(v) => Ok(*v as f64),
+        Value::Int64(v) => Ok(*v as f64),
+        Value::Nullable(Some(v)) => number(v),
+        other => Err(format!("Cannot convert {} to a number", other.type_name())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles() {
+        let value = Value::Array(vec![Value::Float64(12.0), Value::Float64(80.5), Value::UInt64(120)]);
+        let quantiles = Quantiles::from_value(&[0.5, 0.9, 0.99], value.clone()).unwrap();
+        assert_eq!(quantiles.get(0.9), Some(80.5));
+        assert_eq!(quantiles.get(0.99), Some(120.0));
+        assert_eq!(quantiles.get(0.75), None);
+        assert_eq!(quantiles.iter().next(), Some((0.5, 12.0)));
+
+        assert!(Quantiles::from_value(&[0.5], value).is_err());
+        assert_eq!(quantiles_sql(&[0.5, 0.99], "latency"), "quantiles(0.5, 0.99)(latency)");
+    }
+
+    #[test]
+    fn test_histogram() {
+        let bucket = |l: f64, u: f64, h: f64| Value::Tuple(vec![Value::Float64(l), Value::Float64(u), Value::Float64(h)]);
+        let histogram = Histogram::try_from(Value::Array(vec![bucket(0.0, 10.0, 4.0), bucket(10.0, 25.0, 6.5)])).unwrap();
+        assert_eq!(histogram.buckets.len(), 2);
+        assert_eq!(histogram.total(), 10.5);
+        assert_eq!(histogram.max_bucket().unwrap().width(), 15.0);
+        assert_eq!(histogram.bucket_for(3.0).unwrap().height, 4.0);
+        assert!(histogram.bucket_for(30.0).is_none());
+
+        assert!(Histogram::try_from(Value::Array(vec![Value::Float64(1.0)])).is_err());
+        assert!(Histogram::try_from(Value::Array(Vec::new())).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+//! User-registered conversions between [`Value`] and domain types
+//!
+//! `TryFrom<Value>` cannot be implemented for a type from another crate, and
+//! one Rust type may be stored differently in different schemas. A
+//! [`CodecRegistry`] holds a [`ValueCodec`] per Rust type instead, looked up
+//! at run time by row readers and insert builders.
+//!
+//! ```rust
+//! use clickhouse_rs::types::{CodecRegistry, Value};
+//!
+//! #[derive(Debug, PartialEq)]
+//! struct Cents(i64);
+//!
+//! let codecs = CodecRegistry::new();
+//! codecs.register_fn(
+//!     |value| i64::try_from(value).map(Cents),
+//!     |cents: &Cents| Ok(Value::Int64(cents.0)),
+//! );
+//! assert_eq!(codecs.decode::<Cents>(Value::Int64(250)).unwrap(), Cents(250));
+//! ```
+
+use super::{Column, Value};
+use crate::error::{Error, Result};
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Conversion between a [`Value`] and a Rust type
+pub trait ValueCodec<T>: Send + Sync {
+    /// Convert a column value into the Rust type
+    fn decode(&self, value: Value) -> std::result::Result<T, String>;
+
+    /// Convert the Rust type into a column value
+    fn encode(&self, value: &T) -> std::result::Result<Value, String>;
+}
+
+/// Codec made of a pair of closures
+struct FnCodec<D, E> {
+    decode: D,
+    encode: E,
+}
+
+impl<T, D, E> ValueCodec<T> for FnCodec<D, E>
+where
+    D: Fn(Value) -> std::result::Result<T, String> + Send + Sync,
+    E: Fn(&T) -> std::result::Result<Value, String> + Send + Sync,
+{
+    fn decode(&self, value: Value) -> std::result::Result<T, String> {
+        (self.decode)(value)
+    }
+
+    fn encode(&self, value: &T) -> std::result::Result<Value, String> {
+        (self.encode)(value)
+    }
+}
+
+/// Codecs keyed by the Rust type they convert
+///
+/// Clones share the same codecs, so a codec registered on a client's
+/// registry is seen by every clone of the client.
+#[derive(Clone, Default)]
+pub struct CodecRegistry {
+    // Each entry is an `Arc<dyn ValueCodec<T>>` for the `T` of its key
+    codecs: Arc<RwLock<HashMap<TypeId, Box<dyn Any + Send + Sync>>>>,
+}
+
+impl CodecRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the codec of `T`, replacing any previous one
+    pub fn register<T, C>(&self, codec: C)
+    where
+        T: 'static,
+        C: ValueCodec<T> + 'static,
+    {
+        let codec: Arc<dyn ValueCodec<T>> = Arc::new(codec);
+        self.codecs
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(TypeId::of::<T>(), Box::new(codec));
+    }
+
+    /// Register the codec of `T` from a pair of closures
+    pub fn register_fn<T, D, E>(&self, decode: D, encode: E)
+    where
+        T: 'static,
+        D: Fn(Value) -> std::result::Result<T, String> + Send + Sync + 'static,
+        E: Fn(&T) -> std::result::Result<Value, String> + Send + Sync + 'static,
+    {
+        self.register::<T, _>(FnCodec { decode, encode });
+    }
+
+    /// Check whether `T` has a codec
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.codecs
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(&TypeId::of::<T>())
+    }
+
+    /// Get the codec of `T`
+    pub fn get<T: 'static>(&self) -> Option<Arc<dyn ValueCodec<T>>> {
+        self.codecs
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&TypeId::of::<T>())
+            .and_then(|codec| codec.downcast_ref::<Arc<dyn ValueCodec<T>>>())
+            .cloned()
+    }
+
+    /// Convert a value with the codec of `T`
+    pub fn decode<T: 'static>(&self, value: Value) -> Result<T> {
+        self.codec::<T>()?
+            .decode(value)
+            .map_err(|e| Error::TypeConversion(format!("{}: {}", type_name::<T>(), e)))
+    }
+
+    /// Convert a `T` into a value with its codec
+    pub fn encode<T: 'static>(&self, value: &T) -> Result<Value> {
+        self.codec::<T>()?
+            .encode(value)
+            .map_err(|e| Error::TypeConversion(format!("{}: {}", type_name::<T>(), e)))
+    }
+
+    /// Append values to a column, converting each with the codec of `T`
+    pub fn encode_into<'v, T, I>(&self, column: &mut Column, values: I) -> Result<()>
+    where
+        T: 'static,
+        I: IntoIterator<Item = &'v T>,
+    {
+        let codec = self.codec::<T>()?;
+        for value in values {
+            let value = codec
+                .encode(value)
+                .map_err(|e| Error::TypeConversion(format!("{}: {}", type_name::<T>(), e)))?;
+            column
+                .push(value)
+                .map_err(|e| Error::TypeConversion(format!("Column '{}': {}", column.name, e)))?;
+        }
+        Ok(())
+    }
+
+    fn codec<T: 'static>(&self) -> Result<Arc<dyn ValueCodec<T>>> {
+        self.get::<T>()
+            .ok_or_else(|| Error::Configuration(format!("No codec registered for {}", type_name::<T>())))
+    }
+}
+
+impl std::fmt::Debug for CodecRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = self.codecs.read().map(|codecs| codecs.len()).unwrap_or_default();
+        f.debug_struct("CodecRegistry").field("codecs", &count).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ColumnData;
+
+    #[derive(Debug, PartialEq)]
+    enum Status {
+        Active,
+        Disabled,
+    }
+
+    struct StatusCodec;
+
+    impl ValueCodec<Status> for StatusCodec {
+        fn decode(&self, value: Value) -> std::result::Result<Status, String> {
+            match String::try_from(value)?.as_str() {
+                "active" => Ok(Status::Active),
+                "disabled" => Ok(Status::Disabled),
+                other => Err(format!("unknown status {}", other)),
+            }
+        }
+
+        fn encode(&self, value: &Status) -> std::result::Result<Value, String> {
+            let name = match value {
+                Status::Active => "active",
+                Status::Disabled => "disabled",
+            };
+            Ok(Value::String(name.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_codec_registry() {
+        let codecs = CodecRegistry::new();
+        assert!(matches!(codecs.decode::<Status>(Value::String("active".into())), Err(Error::Configuration(_))));
+
+        codecs.clone().register(StatusCodec);
+        assert!(codecs.contains::<Status>());
+        assert_eq!(codecs.decode::<Status>(Value::String("disabled".into())).unwrap(), Status::Disabled);
+        assert!(matches!(codecs.decode::<Status>(Value::String("x".into())), Err(Error::TypeConversion(_))));
+        assert_eq!(codecs.encode(&Status::Active).unwrap(), Value::String("active".into()));
+
+        let mut column = Column::new("status", "String", ColumnData::String(Vec::new()));
+        codecs.encode_into(&mut column, &[Status::Active, Status::Disabled]).unwrap();
+        assert_eq!(column.get_value(1), Some(Value::String("disabled".into())));
+
+        let mut numbers = Column::new("n", "UInt64", ColumnData::UInt64(Vec::new()));
+        assert!(codecs.encode_into(&mut numbers, &[Status::Active]).is_err());
+    }
+}
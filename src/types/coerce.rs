@@ -0,0 +1,664 @@
+//! Coercion of loosely typed rows into blocks
+//!
+//! Rows parsed from JSON or CSV rarely match the types of the target table:
+//! numbers arrive as strings, timestamps as text or epoch seconds, and some
+//! values do not fit at all. [`BlockCoercer`] converts such rows to a target
+//! schema column by column, applies a [`CoercionPolicy`] to each value that
+//! does not fit and records what it did in a [`CoercionReport`].
+
+use super::{Block, Column, ColumnData, Decimal128, Decimal32, Decimal64, FixedString, LowCardinality, TypeDescriptor, Value};
+use crate::error::{Error, Result};
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use std::collections::HashMap;
+
+/// How a value that does not fit its column is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoercionPolicy {
+    /// Reject the row
+    #[default]
+    Strict,
+    /// Clamp numbers to the type's range and drop extra digits, bytes and
+    /// sub-second precision; reject the row if the value still does not fit
+    Truncate,
+    /// Store NULL, or the type's default value in a non-Nullable column
+    NullOnError,
+    /// Store the type's default value
+    DefaultOnError,
+}
+
+/// What was done to a value that did not fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoercionAction {
+    /// The value was clamped or shortened
+    Truncated,
+    /// NULL was stored instead
+    Nulled,
+    /// The type's default value was stored instead
+    Defaulted,
+    /// The row was rejected
+    Rejected,
+}
+
+/// A value that did not coerce cleanly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoercionIssue {
+    /// Index of the row among all pushed rows
+    pub row: usize,
+    /// Column name
+    pub column: String,
+    /// What was done
+    pub action: CoercionAction,
+    /// Why the value did not fit
+    pub message: String,
+}
+
+/// Summary of the rows pushed to a [`BlockCoercer`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoercionReport {
+    /// Rows added to the block
+    pub rows_accepted: usize,
+    /// Rows rejected
+    pub rows_rejected: usize,
+    /// Values that did not coerce cleanly, in order
+    pub issues: Vec<CoercionIssue>,
+}
+
+impl CoercionReport {
+    /// Count the issues handled with an action
+    pub fn count(&self, action: CoercionAction) -> usize {
+        self.issues.iter().filter(|issue| issue.action == action).count()
+    }
+
+    /// Check if every value coerced without changes
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+struct Field {
+    name: String,
+    type_name: String,
+    descriptor: TypeDescriptor,
+    data: ColumnData,
+}
+
+/// Builds a block of a target schema from loosely typed rows
+pub struct BlockCoercer {
+    fields: Vec<Field>,
+    default_policy: CoercionPolicy,
+    policies: HashMap<String, CoercionPolicy>,
+    report: CoercionReport,
+}
+
+impl BlockCoercer {
+    /// Create a coercer for columns given as `(name, type)` pairs
+    ///
+    /// Supports integers, floats, Bool, String, FixedString, Decimal, Date,
+    /// DateTime, DateTime64 and UUID, and Nullable, Array and
+    /// LowCardinality(String) of those.
+    pub fn new(schema: &[(&str, &str)]) -> Result<Self> {
+        let fields = schema
+            .iter()
+            .map(|&(name, type_name)| {
+                let descriptor = TypeDescriptor::parse(type_name)?;
+                let data = empty_column_data(&descriptor)
+                    .ok_or_else(|| Error::Unsupported(format!("Cannot coerce values to {}", type_name)))?;
+                Ok(Field {
+                    name: name.to_string(),
+                    type_name: type_name.to_string(),
+                    descriptor,
+                    data,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            fields,
+            default_policy: CoercionPolicy::default(),
+            policies: HashMap::new(),
+            report: CoercionReport::default(),
+        })
+    }
+
+    /// Set the policy of columns without their own
+    pub fn default_policy(mut self, policy: CoercionPolicy) -> Self {
+        self.default_policy = policy;
+        self
+    }
+
+    /// Set the policy of a column
+    pub fn policy(mut self, column: impl Into<String>, policy: CoercionPolicy) -> Self {
+        self.policies.insert(column.into(), policy);
+        self
+    }
+
+    /// Coerce a row with values in schema order and add it to the block
+    ///
+    /// Missing trailing values are treated as NULL. A rejected row is not
+    /// added, is recorded in the report and fails with
+    /// [`Error::TypeConversion`].
+    pub fn push_row(&mut self, row: Vec<Value>) -> Result<()> {
+        let index = self.report.rows_accepted + self.report.rows_rejected;
+        let mut values = row.into_iter();
+        let mut coerced = Vec::with_capacity(self.fields.len());
+        let mut issues = Vec::new();
+        for field in &self.fields {
+            let policy = self.policies.get(&field.name).copied().unwrap_or(self.default_policy);
+            let value = values.next().unwrap_or(Value::Null);
+            let issue = |action, message| CoercionIssue {
+                row: index,
+                column: field.name.clone(),
+                action,
+                message,
+            };
+            match coerce(value, &field.descriptor, policy == CoercionPolicy::Truncate) {
+                Ok((value, note)) => {
+                    if let Some(message) = note {
+                        issues.push(issue(CoercionAction::Truncated, message));
+                    }
+                    coerced.push(value);
+                }
+                Err(message) => match policy {
+                    CoercionPolicy::Strict | CoercionPolicy::Truncate => {
+                        let error = format!("Row {}, column '{}': {}", index, field.name, message);
+                        self.report.rows_rejected += 1;
+                        self.report.issues.push(issue(CoercionAction::Rejected, message));
+                        return Err(Error::TypeConversion(error));
+                    }
+                    CoercionPolicy::NullOnError if field.descriptor.is_nullable() => {
+                        issues.push(issue(CoercionAction::Nulled, message));
+                        coerced.push(Value::Nullable(None));
+                    }
+                    CoercionPolicy::NullOnError | CoercionPolicy::DefaultOnError => {
+                        issues.push(issue(CoercionAction::Defaulted, message));
+                        coerced.push(default_value(&field.descriptor));
+                    }
+                },
+            }
+        }
+
+        for (field, value) in self.fields.iter_mut().zip(coerced) {
+            field
+                .data
+                .push(value)
+                .map_err(|e| Error::Internal(format!("Column '{}': {}", field.name, e)))?;
+        }
+        self.report.rows_accepted += 1;
+        self.report.issues.extend(issues);
+        Ok(())
+    }
+
+    /// Coerce a JSON object, reading each column from the field of its name
+    pub fn push_json(&mut self, object: &serde_json::Value) -> Result<()> {
+        let object = object
+            .as_object()
+            .ok_or_else(|| Error::InvalidData(format!("Expected a JSON object, got {}", object)))?;
+        let row = self
+            .fields
+            .iter()
+            .map(|field| object.get(&field.name).map_or(Value::Null, json_value))
+            .collect();
+        self.push_row(row)
+    }
+
+    /// Get the report of the rows pushed so far
+    pub fn report(&self) -> &CoercionReport {
+        &self.report
+    }
+
+    /// Get the number of rows in the block
+    pub fn len(&self) -> usize {
+        self.report.rows_accepted
+    }
+
+    /// Check if no rows were added
+    pub fn is_empty(&self) -> bool {
+        self.report.rows_accepted == 0
+    }
+
+    /// Build the block and the report
+    pub fn finish(self) -> (Block, CoercionReport) {
+        let columns = self
+            .fields
+            .into_iter()
+            .map(|field| Column::new(field.name, field.type_name, field.data))
+            .collect();
+        (Block::with_columns(columns), self.report)
+    }
+}
+
+/// Coerced value and a note if it was truncated
+type Coerced = std::result::Result<(Value, Option<String>), String>;
+
+fn coerce(value: Value, descriptor: &TypeDescriptor, truncate: bool) -> Coerced {
+    match (descriptor, value) {
+        (TypeDescriptor::Nullable(_), Value::Null | Value::Nullable(None)) => Ok((Value::Nullable(None), None)),
+        (_, Value::Nullable(Some(value))) => coerce(*value, descriptor, truncate),
+        (_, Value::Null | Value::Nullable(None)) => Err("NULL in a non-Nullable column".to_string()),
+        (TypeDescriptor::Nullable(inner), value) => {
+            let (value, note) = coerce(value, inner, truncate)?;
+            Ok((Value::Nullable(Some(Box::new(value))), note))
+        }
+        (TypeDescriptor::LowCardinality(inner), value) => coerce(value, inner, truncate),
+        (TypeDescriptor::Array(inner), value) => {
+            let items = match value {
+                Value::Array(items) => items,
+                Value::String(s) => match serde_json::from_str(&s) {
+                    Ok(serde_json::Value::Array(items)) => items.iter().map(json_value).collect(),
+                    _ => return Err(format!("'{}' is not an array", s)),
+                },
+                other => return Err(format!("{} is not an array", other)),
+            };
+            let mut note = None;
+            let mut coerced = Vec::with_capacity(items.len());
+            for item in items {
+                let (item, item_note) = coerce(item, inner, truncate)?;
+                note = note.or(item_note);
+                coerced.push(item);
+            }
+            Ok((Value::Array(coerced), note))
+        }
+        (TypeDescriptor::Simple(name), value) => coerce_simple(value, name, truncate),
+        (TypeDescriptor::FixedString(length), value) => {
+            let text = text(value)?;
+            let bytes = text.as_bytes();
+            if bytes.len() <= *length {
+                return Ok((Value::FixedString(FixedString::from_bytes(bytes, *length)), None));
+            }
+            if !truncate {
+                return Err(format!("'{}' is longer than {} bytes", text, length));
+            }
+            let note = format!("'{}' truncated to {} bytes", text, length);
+            Ok((Value::FixedString(FixedString::from_bytes(bytes, *length)), Some(note)))
+        }
+        (TypeDescriptor::Decimal { precision, scale }, value) => decimal(value, *precision, *scale, truncate),
+        (TypeDescriptor::DateTime { .. }, value) => {
+            let datetime = datetime(value)?;
+            let seconds = datetime.with_nanosecond(0).unwrap_or(datetime);
+            if seconds == datetime {
+                Ok((Value::DateTime(datetime), None))
+            } else if truncate {
+                Ok((Value::DateTime(seconds), Some(format!("{} truncated to whole seconds", datetime))))
+            } else {
+                Err(format!("{} has sub-second precision", datetime))
+            }
+        }
+        (TypeDescriptor::DateTime64 { .. }, value) => Ok((Value::DateTime64(datetime(value)?), None)),
+        (descriptor, _) => Err(format!("Cannot coerce values to {:?}", descriptor)),
+    }
+}
+
+fn coerce_simple(value: Value, type_name: &str, truncate: bool) -> Coerced {
+    macro_rules! int {
+        ($variant:ident, $ty:ty) => {
+            integer(value, type_name, <$ty>::MIN as i128, <$ty>::MAX as i128, truncate)
+                .map(|(n, note)| (Value::$variant(n as $ty), note))
+        };
+    }
+    match type_name {
+        "UInt8" => int!(UInt8, u8),
+        "UInt16" => int!(UInt16, u16),
+        "UInt32" => int!(UInt32, u32),
+        "UInt64" => int!(UInt64, u64),
+        "UInt128" => integer(value, type_name, 0, i128::MAX, truncate).map(|(n, note)| (Value::UInt128(n as u128), note)),
+        "Int8" => int!(Int8, i8),
+        "Int16" => int!(Int16, i16),
+        "Int32" => int!(Int32, i32),
+        "Int64" => int!(Int64, i64),
+        "Int128" => int!(Int128, i128),
+        "Float32" => float(&value).map(|f| (Value::Float32(f as f32), None)),
+        "Float64" => float(&value).map(|f| (Value::Float64(f), None)),
+        "Bool" => {
+            let flag = match &value {
+                Value::String(s) if s.trim().eq_ignore_ascii_case("true") => true,
+                Value::String(s) if s.trim().eq_ignore_ascii_case("false") => false,
+                _ => match parse_number(&value) {
+                    Some(Number::Signed(0) | Number::Unsigned(0)) => false,
+                    Some(Number::Signed(1) | Number::Unsigned(1)) => true,
+                    _ => return Err(format!("{} is not a boolean", value)),
+                },
+            };
+            Ok((Value::UInt8(flag as u8), None))
+        }
+        "String" => text(value).map(|s| (Value::String(s), None)),
+        "UUID" => match value {
+            Value::UUID(uuid) => Ok((Value::UUID(uuid), None)),
+            Value::String(s) => uuid::Uuid::parse_str(s.trim())
+                .map(|uuid| (Value::UUID(uuid), None))
+                .map_err(|e| format!("'{}' is not a UUID: {}", s, e)),
+            other => Err(format!("{} is not a UUID", other)),
+        },
+        "Date" => {
+            let date = datetime(value)?;
+            if date.time() == chrono::NaiveTime::MIN {
+                Ok((Value::Date(date.date()), None))
+            } else if truncate {
+                Ok((Value::Date(date.date()), Some(format!("{} truncated to a date", date))))
+            } else {
+                Err(format!("{} has a time of day", date))
+            }
+        }
+        _ => Err(format!("Cannot coerce values to {}", type_name)),
+    }
+}
+
+/// Value of any numeric type
+pub(super) enum Number {
+    Signed(i128),
+    Unsigned(u128),
+    Float(f64),
+}
+
+/// Get the value of an integer or float, without reading strings
+pub(super) fn number(value: &Value) -> Option<Number> {
+    Some(match *value {
+        Value::UInt8(v) => Number::Unsigned(v.into()),
+        Value::UInt16(v) => Number::Unsigned(v.into()),
+        Value::UInt32(v) => Number::Unsigned(v.into()),
+        Value::UInt64(v) => Number::Unsigned(v.into()),
+        Value::UInt128(v) => Number::Unsigned(v),
+        Value::Int8(v) => Number::Signed(v.into()),
+        Value::Int16(v) => Number::Signed(v.into()),
+        Value::Int32(v) => Number::Signed(v.into()),
+        Value::Int64(v) => Number::Signed(v.into()),
+        Value::Int128(v) => Number::Signed(v),
+        Value::Float32(v) => Number::Float(v.into()),
+        Value::Float64(v) => Number::Float(v),
+        _ => return None,
+    })
+}
+
+/// Get the value of a number or of a string holding one
+fn parse_number(value: &Value) -> Option<Number> {
+    let Value::String(s) = value else {
+        return number(value);
+    };
+    let s = s.trim();
+    Some(match s.parse::<i128>() {
+        Ok(n) => Number::Signed(n),
+        Err(_) => match s.parse::<u128>() {
+            Ok(n) => Number::Unsigned(n),
+            Err(_) => Number::Float(s.parse().ok()?),
+        },
+    })
+}
+
+fn integer(value: Value, type_name: &str, min: i128, max: i128, truncate: bool) -> std::result::Result<(i128, Option<String>), String> {
+    let (n, fractional) = match parse_number(&value) {
+        Some(Number::Signed(n)) => (n, false),
+        // Beyond every target's range, so only kept when truncating
+        Some(Number::Unsigned(n)) => i128::try_from(n).map_or((i128::MAX, true), |n| (n, false)),
+        Some(Number::Float(f)) if f.is_finite() => (f.trunc() as i128, f.fract() != 0.0),
+        _ => return Err(format!("{} is not an integer", value)),
+    };
+    let clamped = n.clamp(min, max);
+    if clamped == n && !fractional {
+        Ok((n, None))
+    } else if truncate {
+        Ok((clamped, Some(format!("{} truncated to {}", value, clamped))))
+    } else {
+        Err(format!("{} does not fit into {}", value, type_name))
+    }
+}
+
+fn float(value: &Value) -> std::result::Result<f64, String> {
+    match parse_number(value) {
+        Some(Number::Signed(n)) => Ok(n as f64),
+        Some(Number::Unsigned(n)) => Ok(n as f64),
+        Some(Number::Float(f)) => Ok(f),
+        None => Err(format!("{} is not a number", value)),
+    }
+}
+
+fn text(value: Value) -> std::result::Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::FixedString(s) => Ok(s.as_str().trim_end_matches('\0').to_string()),
+        Value::Unsupported(..) => Err(format!("{} cannot be converted to a string", value)),
+        other => Ok(other.to_string()),
+    }
+}
+
+fn decimal(value: Value, precision: u8, scale: u8, truncate: bool) -> Coerced {
+    let text = match &value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Decimal32(_) | Value::Decimal64(_) | Value::Decimal128(_) => value.to_string(),
+        Value::Float32(f) if !f.is_finite() => return Err(format!("{} is not a decimal", value)),
+        Value::Float64(f) if !f.is_finite() => return Err(format!("{} is not a decimal", value)),
+        _ if number(&value).is_some() => value.to_string(),
+        _ => return Err(format!("{} is not a decimal", value)),
+    };
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(&text)),
+    };
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if (int_part.is_empty() && frac_part.is_empty()) || !is_digits(int_part) || !is_digits(frac_part) {
+        return Err(format!("'{}' is not a decimal", text));
+    }
+
+    let mut note = None;
+    let frac_part = if frac_part.len() > scale as usize {
+        if !truncate {
+            return Err(format!("'{}' has more than {} decimal places", text, scale));
+        }
+        note = Some(format!("'{}' truncated to {} decimal places", text, scale));
+        &frac_part[..scale as usize]
+    } else {
+        frac_part
+    };
+    let int_part = int_part.trim_start_matches('0');
+    if int_part.len() + scale as usize > precision as usize {
+        return Err(format!("'{}' does not fit into Decimal({}, {})", text, precision, scale));
+    }
+    let unscaled: i128 = format!("0{}{:0<width$}", int_part, frac_part, width = scale as usize)
+        .parse()
+        .map_err(|_| format!("'{}' does not fit into Decimal({}, {})", text, precision, scale))?;
+    let unscaled = if negative { -unscaled } else { unscaled };
+
+    let value = match precision {
+        0..=9 => Value::Decimal32(Decimal32::new(unscaled as i32, scale)),
+        10..=18 => Value::Decimal64(Decimal64::new(unscaled as i64, scale)),
+        _ => Value::Decimal128(Decimal128::new(unscaled, scale)),
+    };
+    Ok((value, note))
+}
+
+fn datetime(value: Value) -> std::result::Result<NaiveDateTime, String> {
+    const FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+    let from_epoch = |seconds: f64| {
+        chrono::DateTime::from_timestamp(seconds.floor() as i64, (seconds.fract() * 1e9).round() as u32 % 1_000_000_000)
+            .map(|datetime| datetime.naive_utc())
+    };
+    let parsed = match &value {
+        Value::DateTime(datetime) | Value::DateTime64(datetime) => Some(*datetime),
+        Value::Date(date) => Some(date.and_time(chrono::NaiveTime::MIN)),
+        Value::String(s) => {
+            let s = s.trim();
+            FORMATS
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+                .or_else(|| chrono::DateTime::parse_from_rfc3339(s).ok().map(|datetime| datetime.naive_utc()))
+                .or_else(|| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok().map(|date| date.and_time(chrono::NaiveTime::MIN)))
+                .or_else(|| s.parse::<f64>().ok().filter(|f| f.is_finite()).and_then(from_epoch))
+        }
+        _ => match number(&value) {
+            Some(Number::Signed(seconds)) => i64::try_from(seconds)
+                .ok()
+                .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+                .map(|datetime| datetime.naive_utc()),
+            Some(Number::Unsigned(seconds)) => i64::try_from(seconds)
+                .ok()
+                .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+                .map(|datetime| datetime.naive_utc()),
+            Some(Number::Float(seconds)) if seconds.is_finite() => from_epoch(seconds),
+            _ => None,
+        },
+    };
+    parsed.ok_or_else(|| format!("{} is not a date and time", value))
+}
+
+/// Create empty column data for a type, if coercion to it is supported
+fn empty_column_data(descriptor: &TypeDescriptor) -> Option<ColumnData> {
+    Some(match descriptor {
+        TypeDescriptor::Simple(name) => match name.as_str() {
+            "UInt8" | "Bool" => ColumnData::UInt8(Vec::new()),
+            "UInt16" => ColumnData::UInt16(Vec::new()),
+            "UInt32" => ColumnData::UInt32(Vec::new()),
+            "UInt64" => ColumnData::UInt64(Vec::new()),
+            "UInt128" => ColumnData::UInt128(Vec::new()),
+            "Int8" => ColumnData::Int8(Vec::new()),
+            "Int16" => ColumnData::Int16(Vec::new()),
+            "Int32" => ColumnData::Int32(Vec::new()),
+            "Int64" => ColumnData::Int64(Vec::new()),
+            "Int128" => ColumnData::Int128(Vec::new()),
+            "Float32" => ColumnData::Float32(Vec::new()),
+            "Float64" => ColumnData::Float64(Vec::new()),
+            "String" => ColumnData::String(Vec::new()),
+            "UUID" => ColumnData::UUID(Vec::new()),
+            "Date" => ColumnData::Date(Vec::new()),
+            _ => return None,
+        },
+        TypeDescriptor::FixedString(_) => ColumnData::FixedString(Vec::new()),
+        TypeDescriptor::Decimal { precision, .. } => match precision {
+            0..=9 => ColumnData::Decimal32(Vec::new()),
+            10..=18 => ColumnData::Decimal64(Vec::new()),
+            19..=38 => ColumnData::Decimal128(Vec::new()),
+            _ => return None,
+        },
+        TypeDescriptor::DateTime { .. } => ColumnData::DateTime(Vec::new()),
+        TypeDescriptor::DateTime64 { .. } => ColumnData::DateTime64(Vec::new()),
+        TypeDescriptor::Nullable(inner) => {
+            empty_column_data(inner)?;
+            ColumnData::Nullable(Vec::new())
+        }
+        TypeDescriptor::LowCardinality(inner) if matches!(inner.as_ref(), TypeDescriptor::Simple(name) if name == "String") => {
+            ColumnData::LowCardinality(LowCardinality::new())
+        }
+        TypeDescriptor::Array(inner) => {
+            empty_column_data(inner)?;
+            ColumnData::Array(Vec::new())
+        }
+        _ => return None,
+    })
+}
+
+/// Get the default value of a type supported by [`empty_column_data`]
+fn default_value(descriptor: &TypeDescriptor) -> Value {
+    match descriptor {
+        TypeDescriptor::Simple(name) => match name.as_str() {
+            "UInt16" => Value::UInt16(0),
+            "UInt32" => Value::UInt32(0),
+            "UInt64" => Value::UInt64(0),
+            "UInt128" => Value::UInt128(0),
+            "Int8" => Value::Int8(0),
+            "Int16" => Value::Int16(0),
+            "Int32" => Value::Int32(0),
+            "Int64" => Value::Int64(0),
+            "Int128" => Value::Int128(0),
+            "Float32" => Value::Float32(0.0),
+            "Float64" => Value::Float64(0.0),
+            "String" => Value::String(String::new()),
+            "UUID" => Value::UUID(uuid::Uuid::nil()),
+            "Date" => Value::Date(NaiveDate::default()),
+            _ => Value::UInt8(0),
+        },
+        TypeDescriptor::FixedString(length) => Value::FixedString(FixedString::new(*length)),
+        TypeDescriptor::Decimal { precision, scale } => match precision {
+            0..=9 => Value::Decimal32(Decimal32::new(0, *scale)),
+            10..=18 => Value::Decimal64(Decimal64::new(0, *scale)),
+            _ => Value::Decimal128(Decimal128::new(0, *scale)),
+        },
+        TypeDescriptor::DateTime { .. } => Value::DateTime(NaiveDateTime::default()),
+        TypeDescriptor::DateTime64 { .. } => Value::DateTime64(NaiveDateTime::default()),
+        TypeDescriptor::Nullable(_) => Value::Nullable(None),
+        TypeDescriptor::LowCardinality(_) => Value::String(String::new()),
+        _ => Value::Array(Vec::new()),
+    }
+}
+
+fn json_value(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(flag) => Value::UInt8(*flag as u8),
+        serde_json::Value::Number(n) => n
+            .as_u64()
+            .map(Value::UInt64)
+            .or_else(|| n.as_i64().map(Value::Int64))
+            .unwrap_or_else(|| Value::Float64(n.as_f64().unwrap_or(f64::NAN))),
+        serde_json::Value::String(s) => Value::String(s.clone()),
+        serde_json::Value::Array(items) => Value::Array(items.iter().map(json_value).collect()),
+        serde_json::Value::Object(_) => Value::String(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_coercer_policies() {
+        let mut coercer = BlockCoercer::new(&[
+            ("id", "UInt8"),
+            ("price", "Decimal(9, 2)"),
+            ("code", "FixedString(2)"),
+            ("at", "Nullable(DateTime)"),
+            ("tags", "Array(UInt16)"),
+        ])
+        .unwrap()
+        .policy("id", CoercionPolicy::Truncate)
+        .policy("code", CoercionPolicy::Truncate)
+        .policy("at", CoercionPolicy::NullOnError)
+        .policy("tags", CoercionPolicy::DefaultOnError);
+
+        coercer
+            .push_json(&serde_json::json!({"id": "7", "price": 1.5, "code": "ab", "at": 1700000000, "tags": "[1, 2]"}))
+            .unwrap();
+        coercer
+            .push_row(vec![
+                Value::Int64(300),
+                Value::String("2.25".into()),
+                Value::String("abc".into()),
+                Value::String("yesterday".into()),
+                Value::String("oops".into()),
+            ])
+            .unwrap();
+        let rejected = coercer.push_row(vec![Value::UInt8(1), Value::String("0.125".into())]);
+        assert!(matches!(rejected, Err(Error::TypeConversion(_))));
+        assert_eq!(coercer.len(), 2);
+
+        let (block, report) = coercer.finish();
+        assert_eq!(block.row_count(), 2);
+        let column = |index: usize| &block.columns[index];
+        assert_eq!(column(0).get_value(0), Some(Value::UInt8(7)));
+        assert_eq!(column(0).get_value(1), Some(Value::UInt8(255)));
+        assert_eq!(column(1).get_value(1), Some(Value::Decimal32(Decimal32::new(225, 2))));
+        assert_eq!(column(2).get_value(1), Some(Value::FixedString(FixedString::from_bytes(b"ab", 2))));
+        assert_eq!(
+            column(3).get_value(0),
+            Some(Value::Nullable(Some(Box::new(Value::DateTime(
+                chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap().naive_utc()
+            )))))
+        );
+        assert_eq!(column(3).get_value(1), Some(Value::Nullable(None)));
+        assert_eq!(column(4).get_value(0), Some(Value::Array(vec![Value::UInt16(1), Value::UInt16(2)])));
+        assert_eq!(column(4).get_value(1), Some(Value::Array(Vec::new())));
+
+        assert_eq!(report.rows_accepted, 2);
+        assert_eq!(report.rows_rejected, 1);
+        assert_eq!(report.count(CoercionAction::Truncated), 2);
+        assert_eq!(report.count(CoercionAction::Nulled), 1);
+        assert_eq!(report.count(CoercionAction::Defaulted), 1);
+        assert_eq!(report.issues.last().unwrap().column, "price");
+        assert!(BlockCoercer::new(&[("p", "Point")]).is_err());
+    }
+
+    #[test]
+    fn test_coerce_wide_unsigned() {
+        assert!(coerce_simple(Value::UInt128(u128::MAX), "Int128", false).is_err());
+        let (value, note) = coerce_simple(Value::String(u128::MAX.to_string()), "Int128", true).unwrap();
+        assert_eq!(value, Value::Int128(i128::MAX));
+        assert!(note.is_some());
+        assert_eq!(coerce_simple(Value::UInt128(1), "Bool", false).unwrap().0, Value::UInt8(1));
+    }
+}
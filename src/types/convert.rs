@@ -0,0 +1,401 @@
+//! Conversions from [`Value`] into plain Rust types and typed maps
+
+use super::{complex, datetime, Int256, Row, UInt256, Value};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::hash::Hash;
+use std::str::FromStr;
+
+macro_rules! impl_try_from_value_for_int {
+    ($($target:ty),*) => {$(
+        impl TryFrom<Value> for $target {
+            type Error = String;
+
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                let wide = match value {
+                    Value::UInt8(v) => v as i128,
+                    Value::UInt16(v) => v as i128,
+                    Value::UInt32(v) => v as i128,
+                    Value::UInt64(v) => v as i128,
+                    Value::UInt128(v) => i128::try_from(v).map_err(|e| e.to_string())?,
+                    Value::Int8(v) => v as i128,
+                    Value::Int16(v) => v as i128,
+                    Value::Int32(v) => v as i128,
+                    Value::Int64(v) => v as i128,
+                    Value::Int128(v) => v,
+                    Value::Nullable(Some(v)) => return Self::try_from(*v),
+                    other => {
+                        return Err(format!("Cannot convert {} to {}", other.type_name(), stringify!($target)))
+                    }
+                };
+                <$target>::try_from(wide)
+                    .map_err(|_| format!("Value {} is out of range for {}", wide, stringify!($target)))
+            }
+        }
+    )*};
+}
+
+impl_try_from_value_for_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+macro_rules! impl_try_from_value_for_float {
+    ($($target:ty),*) => {$(
+        impl TryFrom<Value> for $target {
+            type Error = String;
+
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                Ok(match value {
+                    Value::Float32(v) => v as $target,
+                    Value::Float64(v) => v as $target,
+                    Value::UInt8(v) => v as $target,
+                    Value::UInt16(v) => v as $target,
+                    Value::UInt32(v) => v as $target,
+                    Value::UInt64(v) => v as $target,
+                    Value::Int8(v) => v as $target,
+                    Value::Int16(v) => v as $target,
+                    Value::Int32(v) => v as $target,
+                    Value::Int64(v) => v as $target,
+                    Value::Nullable(Some(v)) => return Self::try_from(*v),
+                    other => {
+                        return Err(format!("Cannot convert {} to {}", other.type_name(), stringify!($target)))
+                    }
+                })
+            }
+        }
+    )*};
+}
+
+impl_try_from_value_for_float!(f32, f64);
+
+impl TryFrom<Value> for bool {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::UInt8(0) => Ok(false),
+            Value::UInt8(1) => Ok(true),
+            Value::UInt8(v) => Err(format!("Value {} is not a Bool", v)),
+            Value::Nullable(Some(v)) => Self::try_from(*v),
+            other => Err(format!("Cannot convert {} to bool", other.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(s) => Ok(s),
+            Value::FixedString(s) => String::from_utf8(s.as_bytes().to_vec()).map_err(|e| e.to_string()),
+            Value::Enum8(v) => v.name().cloned().ok_or_else(|| format!("Enum8 value {} has no name", v.value())),
+            Value::Enum16(v) => v.name().cloned().ok_or_else(|| format!("Enum16 value {} has no name", v.value())),
+            Value::Nullable(Some(v)) => Self::try_from(*v),
+            other => Err(format!("Cannot convert {} to String", other.type_name())),
+        }
+    }
+}
+
+/// Conversion from a [`Value`] into the Rust type of a column
+///
+/// Unlike `TryFrom<Value>`, this is implemented for the types behind the
+/// `Nullable`, `Array`, `Date`, `DateTime` and `UUID` aliases: `Option<T>`
+/// (NULL becomes `None`), `Vec<T>`, the chrono types and `Uuid`.
+pub trait FromValue: Sized {
+    /// Convert the value
+    fn from_value(value: Value) -> Result<Self, String>;
+}
+
+macro_rules! impl_from_value_via_try_from {
+    ($($target:ty),*) => {$(
+        impl FromValue for $target {
+            fn from_value(value: Value) -> Result<Self, String> {
+                Self::try_from(value)
+            }
+        }
+    )*};
+}
+
+impl_from_value_via_try_from!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, bool, String, UInt256, Int256);
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Null | Value::Nullable(None) => Ok(None),
+            Value::Nullable(Some(inner)) => T::from_value(*inner).map(Some),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Array(values) => values.into_iter().map(T::from_value).collect(),
+            Value::Nullable(Some(inner)) => Self::from_value(*inner),
+            other => Err(format!("Cannot convert {} to Array", other.type_name())),
+        }
+    }
+}
+
+impl FromValue for NaiveDate {
+    fn from_value(value: Value) -> Result<Self, String> {
+        datetime::Date::try_from(value).map(|date| date.0)
+    }
+}
+
+impl FromValue for NaiveDateTime {
+    fn from_value(value: Value) -> Result<Self, String> {
+        datetime::DateTime::try_from(value).map(|datetime| datetime.0)
+    }
+}
+
+impl FromValue for uuid::Uuid {
+    fn from_value(value: Value) -> Result<Self, String> {
+        complex::UUID::try_from(value).map(|uuid| uuid.0)
+    }
+}
+
+/// Conversion from a result row into a scalar or a tuple
+///
+/// Scalars take a row of one column; tuples of up to 16 [`FromValue`]
+/// elements take one column per element, in order.
+pub trait FromRow: Sized {
+    /// Number of columns the row must have
+    const WIDTH: usize;
+
+    /// Convert the values of the row
+    fn from_row(values: Vec<Option<Value>>) -> Result<Self, String>;
+}
+
+fn check_width(values: &[Option<Value>], width: usize) -> Result<(), String> {
+    if values.len() == width {
+        Ok(())
+    } else {
+        Err(format!("Expected {} columns, got {}", width, values.len()))
+    }
+}
+
+fn column_value<T: FromValue>(value: Option<Value>, index: usize) -> Result<T, String> {
+    T::from_value(value.unwrap_or(Value::Null)).map_err(|e| format!("Column {}: {}", index, e))
+}
+
+impl<T: FromValue> FromRow for T {
+    const WIDTH: usize = 1;
+
+    fn from_row(values: Vec<Option<Value>>) -> Result<Self, String> {
+        check_width(&values, Self::WIDTH)?;
+        column_value(values.into_iter().next().flatten(), 0)
+    }
+}
+
+macro_rules! impl_from_row_for_tuple {
+    ($width:expr; $($element:ident $index:tt),+) => {
+        impl<$($element: FromValue),+> FromRow for ($($element,)+) {
+            const WIDTH: usize = $width;
+
+            fn from_row(values: Vec<Option<Value>>) -> Result<Self, String> {
+                check_width(&values, Self::WIDTH)?;
+                let mut values = values.into_iter();
+                Ok(($(column_value::<$element>(values.next().flatten(), $index)?,)+))
+            }
+        }
+    };
+}
+
+impl_from_row_for_tuple!(1; A 0);
+impl_from_row_for_tuple!(2; A 0, B 1);
+impl_from_row_for_tuple!(3; A 0, B 1, C 2);
+impl_from_row_for_tuple!(4; A 0, B 1, C 2, D 3);
+impl_from_row_for_tuple!(5; A 0, B 1, C 2, D 3, E 4);
+impl_from_row_for_tuple!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+impl_from_row_for_tuple!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_from_row_for_tuple!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_from_row_for_tuple!(9; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_from_row_for_tuple!(10; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_from_row_for_tuple!(11; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_from_row_for_tuple!(12; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+impl_from_row_for_tuple!(13; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12);
+impl_from_row_for_tuple!(14; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13);
+impl_from_row_for_tuple!(15; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14);
+impl_from_row_for_tuple!(16; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14, P 15);
+
+impl Value {
+    /// Convert a `Map` value into a typed `HashMap`
+    ///
+    /// Keys are parsed from their string form and values converted with
+    /// `TryFrom<Value>`. The error lists every entry that failed.
+    pub fn as_map<K, V>(&self) -> Result<HashMap<K, V>, String>
+    where
+        K: FromStr + Eq + Hash,
+        K::Err: Display,
+        V: TryFrom<Value>,
+        V::Error: Display,
+    {
+        self.convert_map()
+    }
+
+    /// Convert a `Map` value into a typed `BTreeMap`
+    pub fn as_btree_map<K, V>(&self) -> Result<BTreeMap<K, V>, String>
+    where
+        K: FromStr + Ord,
+        K::Err: Display,
+        V: TryFrom<Value>,
+        V::Error: Display,
+    {
+        self.convert_map()
+    }
+
+    fn convert_map<K, V, M>(&self) -> Result<M, String>
+    where
+        K: FromStr,
+        K::Err: Display,
+        V: TryFrom<Value>,
+        V::Error: Display,
+        M: FromIterator<(K, V)>,
+    {
+        let map = match self {
+            Value::Map(map) => map,
+            Value::Nullable(Some(inner)) => return inner.convert_map(),
+            other => return Err(format!("Cannot convert {} to a map", other.type_name())),
+        };
+
+        let mut entries = Vec::with_capacity(map.len());
+        let mut errors = Vec::new();
+        for (key, value) in map {
+            let typed_key = key.parse::<K>().map_err(|e| format!("invalid key: {}", e));
+            let typed_value = V::try_from(value.clone()).map_err(|e| e.to_string());
+            match (typed_key, typed_value) {
+                (Ok(k), Ok(v)) => entries.push((k, v)),
+                (Err(e), _) | (_, Err(e)) => errors.push(format!("'{}': {}", key, e)),
+            }
+        }
+        if !errors.is_empty() {
+            errors.sort();
+            return Err(format!("Cannot convert map entries {}", errors.join("; ")));
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl Row {
+    /// Convert the row into a scalar or a tuple
+    pub fn decode<T: FromRow>(self) -> Result<T, String> {
+        T::from_row(self.values)
+    }
+
+    /// Get a `Map` value by index as a typed `HashMap`
+    pub fn get_map<K, V>(&self, index: usize) -> Result<HashMap<K, V>, String>
+    where
+        K: FromStr + Eq + Hash,
+        K::Err: Display,
+        V: TryFrom<Value>,
+        V::Error: Display,
+    {
+        self.map_value(index)?.as_map()
+    }
+
+    /// Get a `Map` value by index as a typed `BTreeMap`
+    pub fn get_btree_map<K, V>(&self, index: usize) -> Result<BTreeMap<K, V>, String>
+    where
+        K: FromStr + Ord,
+        K::Err: Display,
+        V: TryFrom<Value>,
+        V::Error: Display,
+    {
+        self.map_value(index)?.as_btree_map()
+    }
+
+    fn map_value(&self, index: usize) -> Result<&Value, String> {
+        self.get(index)
+            .and_then(|v| v.as_ref())
+            .ok_or_else(|| "Value not found or null".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, Value)]) -> Value {
+        Value::Map(entries.iter().map(|(k, v)| (k.to_string(), v.clone())).collect())
+    }
+
+    #[test]
+    fn test_typed_maps() {
+        let value = map(&[("1", Value::UInt32(10)), ("2", Value::UInt64(20))]);
+        let typed: HashMap<u16, u64> = value.as_map().unwrap();
+        assert_eq!(typed.get(&2), Some(&20));
+
+        let sorted = value.as_btree_map::<String, f64>().unwrap();
+        assert_eq!(sorted.keys().collect::<Vec<_>>(), ["1", "2"]);
+
+        let row = Row::new(vec![Some(Value::Nullable(Some(Box::new(value)))), None]);
+        assert_eq!(row.get_map::<u8, i32>(0).unwrap().len(), 2);
+        assert!(row.get_btree_map::<u8, i32>(1).is_err());
+        assert!(Value::String("x".into()).as_map::<String, String>().is_err());
+    }
+
+    #[test]
+    fn test_typed_map_entry_errors() {
+        let value = map(&[
+            ("a", Value::String("x".into())),
+            ("b", Value::Int64(-1)),
+            ("300", Value::UInt8(1)),
+        ]);
+        let err = value.as_map::<u8, u32>().unwrap_err();
+        assert!(err.contains("'a': invalid key"));
+        assert!(err.contains("'b': invalid key"));
+        assert!(err.contains("'300': invalid key"));
+
+        let err = value.as_map::<String, u32>().unwrap_err();
+        assert_eq!(
+            err,
+            "Cannot convert map entries 'a': Cannot convert String to u32; 'b': Value -1 is out of range for u32"
+        );
+    }
+
+    #[test]
+    fn test_primitive_conversions() {
+        assert_eq!(u8::try_from(Value::Int64(200)), Ok(200));
+        assert!(u8::try_from(Value::Int64(256)).is_err());
+        assert_eq!(f64::try_from(Value::Int32(-3)), Ok(-3.0));
+        assert_eq!(bool::try_from(Value::UInt8(1)), Ok(true));
+        assert_eq!(String::try_from(Value::String("s".into())), Ok("s".to_string()));
+        assert_eq!(Row::new(vec![Some(Value::UInt16(7))]).get_typed::<u64>(0), Ok(7));
+    }
+
+    #[test]
+    fn test_from_value() {
+        assert_eq!(Option::<u32>::from_value(Value::Nullable(None)), Ok(None));
+        assert_eq!(Option::<u32>::from_value(Value::Nullable(Some(Box::new(Value::UInt8(4))))), Ok(Some(4)));
+        assert_eq!(
+            Vec::<Option<String>>::from_value(Value::Array(vec![Value::String("a".into()), Value::Null])),
+            Ok(vec![Some("a".to_string()), None])
+        );
+        let day = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        assert_eq!(NaiveDate::from_value(Value::Date(day)), Ok(day));
+        assert!(Vec::<u8>::from_value(Value::UInt8(1)).is_err());
+    }
+
+    #[test]
+    fn test_from_row() {
+        let row = Row::new(vec![Some(Value::UInt64(1)), Some(Value::String("a".into())), None]);
+        assert_eq!(
+            row.clone().decode::<(u64, String, Option<f64>)>(),
+            Ok((1, "a".to_string(), None))
+        );
+        assert_eq!(row.clone().decode::<(u64, String)>(), Err("Expected 2 columns, got 3".to_string()));
+        assert!(row.decode::<(u64, String, f64)>().unwrap_err().starts_with("Column 2: "));
+
+        assert_eq!(Row::new(vec![Some(Value::UInt8(5))]).decode::<u32>(), Ok(5));
+        assert_eq!(Row::new(vec![Some(Value::UInt8(5))]).decode::<(i64,)>(), Ok((5,)));
+        assert_eq!(Row::new(vec![None]).decode::<Option<String>>(), Ok(None));
+    }
+}
@@ -2,6 +2,7 @@
 
 use super::Value;
 use chrono::{DateTime as ChronoDateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -159,6 +160,11 @@ impl DateTime {
         DateTime(self.0 - chrono::Duration::seconds(seconds))
     }
 
+    /// Convert the UTC instant to wall-clock time in `tz`
+    pub fn in_timezone(&self, tz: Tz) -> ChronoDateTime<Tz> {
+        Utc.from_utc_datetime(&self.0).with_timezone(&tz)
+    }
+
     /// Convert to UTC DateTime
     pub fn with_timezone<Tz: TimeZone>(&self, tz: Tz) -> chrono::DateTime<Tz> {
         tz.from_local_datetime(&self.0).earliest().unwrap_or_else(|| {
@@ -229,6 +235,26 @@ impl DateTime64 {
     pub fn sub_nanoseconds(&self, nanoseconds: i64) -> Self {
         DateTime64(self.0 - chrono::Duration::nanoseconds(nanoseconds))
     }
+
+    /// Convert the UTC instant to wall-clock time in `tz`
+    pub fn in_timezone(&self, tz: Tz) -> ChronoDateTime<Tz> {
+        Utc.from_utc_datetime(&self.0).with_timezone(&tz)
+    }
+}
+
+/// Resolve an IANA timezone name such as `Europe/Berlin`
+pub fn parse_timezone(name: &str) -> crate::error::Result<Tz> {
+    name.parse::<Tz>()
+        .map_err(|_| crate::error::Error::TypeConversion(format!("Unknown timezone: {}", name)))
+}
+
+/// Get the explicit timezone of a `DateTime('tz')` or `DateTime64(p, 'tz')` column type
+pub fn column_timezone(type_name: &str) -> Option<&str> {
+    let start = type_name.find("DateTime")?;
+    let rest = &type_name[start..];
+    let open = rest.find('\'')?;
+    let close = rest[open + 1..].find('\'')?;
+    Some(&rest[open + 1..open + 1 + close])
 }
 
 // Implement Display for all datetime types
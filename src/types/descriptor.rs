@@ -0,0 +1,310 @@
+//! Parsed ClickHouse type names
+
+use crate::error::{Error, Result};
+use std::fmt;
+
+/// Structured form of a ClickHouse type name such as `Nullable(Decimal(18, 4))`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeDescriptor {
+    /// Type without parameters, such as `UInt64`, `String` or `UUID`
+    Simple(String),
+    /// `FixedString(N)`
+    FixedString(usize),
+    /// `Decimal(P, S)` and the `Decimal32/64/128/256(S)` shorthands
+    Decimal {
+        /// Total number of digits
+        precision: u8,
+        /// Digits after the decimal point
+        scale: u8,
+    },
+    /// `DateTime` with an optional timezone
+    DateTime {
+        /// Explicit timezone
+        timezone: Option<String>,
+    },
+    /// `DateTime64(P)` with an optional timezone
+    DateTime64 {
+        /// Sub-second digits
+        precision: u8,
+        /// Explicit timezone
+        timezone: Option<String>,
+    },
+    /// `Enum8` or `Enum16` with its named values
+    Enum {
+        /// Storage width in bits
+        bits: u8,
+        /// Names and values
+        values: Vec<(String, i16)>,
+    },
+    /// `Nullable(T)`
+    Nullable(Box<TypeDescriptor>),
+    /// `LowCardinality(T)`
+    LowCardinality(Box<TypeDescriptor>),
+    /// `Array(T)`
+    Array(Box<TypeDescriptor>),
+    /// `Tuple(...)` with optional element names
+    Tuple(Vec<(Option<String>, TypeDescriptor)>),
+    /// `Map(K, V)`
+    Map(Box<TypeDescriptor>, Box<TypeDescriptor>),
+    /// Any other parameterized type, kept verbatim
+    Other(String),
+}
+
+impl TypeDescriptor {
+    /// Parse a type name
+    pub fn parse(type_name: &str) -> Result<Self> {
+        let type_name = type_name.trim();
+        let invalid = || Error::TypeConversion(format!("Invalid type name: {}", type_name));
+
+        let Some(open) = type_name.find('(') else {
+            if !is_identifier(type_name) {
+                return Err(invalid());
+            }
+            return Ok(match type_name {
+                "DateTime" => TypeDescriptor::DateTime { timezone: None },
+                _ => TypeDescriptor::Simple(type_name.to_string()),
+            });
+        };
+        let name = &type_name[..open];
+        if !is_identifier(name) || !type_name.ends_with(')') {
+            return Err(invalid());
+        }
+        let args = split_args(&type_name[open + 1..type_name.len() - 1]).ok_or_else(invalid)?;
+        let single = || match args.as_slice() {
+            [arg] => TypeDescriptor::parse(arg).map(Box::new),
+            _ => Err(invalid()),
+        };
+        let number = |arg: &str| arg.trim().parse::<u8>().map_err(|_| invalid());
+
+        Ok(match (name, args.as_slice()) {
+            ("Nullable", _) => TypeDescriptor::Nullable(single()?),
+            ("LowCardinality", _) => TypeDescriptor::LowCardinality(single()?),
+            ("Array", _) => TypeDescriptor::Array(single()?),
+            ("Map", [key, value]) => TypeDescriptor::Map(Box::new(Self::parse(key)?), Box::new(Self::parse(value)?)),
+            ("Tuple", elements) => TypeDescriptor::Tuple(
+                elements
+                    .iter()
+                    .map(|element| parse_tuple_element(element))
+                    .collect::<Result<_>>()?,
+            ),
+            ("FixedString", [length]) => TypeDescriptor::FixedString(length.trim().parse().map_err(|_| invalid())?),
+            ("Decimal", [precision, scale]) => TypeDescriptor::Decimal {
+                precision: number(precision)?,
+                scale: number(scale)?,
+            },
+            ("Decimal32", [scale]) => TypeDescriptor::Decimal { precision: 9, scale: number(scale)? },
+            ("Decimal64", [scale]) => TypeDescriptor::Decimal { precision: 18, scale: number(scale)? },
+            ("Decimal128", [scale]) => TypeDescriptor::Decimal { precision: 38, scale: number(scale)? },
+            ("Decimal256", [scale]) => TypeDescriptor::Decimal { precision: 76, scale: number(scale)? },
+            ("DateTime", [timezone]) => TypeDescriptor::DateTime {
+                timezone: Some(unquote(timezone).ok_or_else(invalid)?),
+            },
+            ("DateTime64", [precision]) => TypeDescriptor::DateTime64 { precision: number(precision)?, timezone: None },
+            ("DateTime64", [precision, timezone]) => TypeDescriptor::DateTime64 {
+                precision: number(precision)?,
+                timezone: Some(unquote(timezone).ok_or_else(invalid)?),
+            },
+            ("Enum8" | "Enum16", values) => TypeDescriptor::Enum {
+                bits: if name == "Enum8" { 8 } else { 16 },
+                values: values
+                    .iter()
+                    .map(|value| parse_enum_value(value).ok_or_else(invalid))
+                    .collect::<Result<_>>()?,
+            },
+            ("Map" | "FixedString" | "Decimal" | "Decimal32" | "Decimal64" | "Decimal128" | "Decimal256" | "DateTime"
+            | "DateTime64", _) => return Err(invalid()),
+            _ => TypeDescriptor::Other(type_name.to_string()),
+        })
+    }
+
+    /// Check if values can be NULL
+    pub fn is_nullable(&self) -> bool {
+        match self {
+            TypeDescriptor::Nullable(_) => true,
+            TypeDescriptor::LowCardinality(inner) => inner.is_nullable(),
+            _ => false,
+        }
+    }
+
+    /// Get the value type without `Nullable` and `LowCardinality` wrappers
+    pub fn base(&self) -> &TypeDescriptor {
+        match self {
+            TypeDescriptor::Nullable(inner) | TypeDescriptor::LowCardinality(inner) => inner.base(),
+            other => other,
+        }
+    }
+
+    /// Get the precision and scale of a decimal type
+    pub fn decimal(&self) -> Option<(u8, u8)> {
+        match self.base() {
+            TypeDescriptor::Decimal { precision, scale } => Some((*precision, *scale)),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a `DateTime` or `DateTime64` type
+    pub fn is_datetime(&self) -> bool {
+        matches!(self.base(), TypeDescriptor::DateTime { .. } | TypeDescriptor::DateTime64 { .. })
+    }
+
+    /// Get the explicit timezone of a `DateTime` or `DateTime64` type
+    pub fn timezone(&self) -> Option<&str> {
+        match self.base() {
+            TypeDescriptor::DateTime { timezone } | TypeDescriptor::DateTime64 { timezone, .. } => timezone.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TypeDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quote = |s: &str| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+        match self {
+            TypeDescriptor::Simple(name) | TypeDescriptor::Other(name) => write!(f, "{}", name),
+            TypeDescriptor::FixedString(length) => write!(f, "FixedString({})", length),
+            TypeDescriptor::Decimal { precision, scale } => write!(f, "Decimal({}, {})", precision, scale),
+            TypeDescriptor::DateTime { timezone: None } => write!(f, "DateTime"),
+            TypeDescriptor::DateTime { timezone: Some(tz) } => write!(f, "DateTime({})", quote(tz)),
+            TypeDescriptor::DateTime64 { precision, timezone: None } => write!(f, "DateTime64({})", precision),
+            TypeDescriptor::DateTime64 { precision, timezone: Some(tz) } => {
+                write!(f, "DateTime64({}, {})", precision, quote(tz))
+            }
+            TypeDescriptor::Enum { bits, values } => {
+                let values: Vec<String> = values.iter().map(|(name, value)| format!("{} = {}", quote(name), value)).collect();
+                write!(f, "Enum{}({})", bits, values.join(", "))
+            }
+            TypeDescriptor::Nullable(inner) => write!(f, "Nullable({})", inner),
+            TypeDescriptor::LowCardinality(inner) => write!(f, "LowCardinality({})", inner),
+            TypeDescriptor::Array(inner) => write!(f, "Array({})", inner),
+            TypeDescriptor::Tuple(elements) => {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|(name, ty)| match name {
+                        Some(name) => format!("{} {}", name, ty),
+                        None => ty.to_string(),
+                    })
+                    .collect();
+                write!(f, "Tuple({})", elements.join(", "))
+            }
+            TypeDescriptor::Map(key, value) => write!(f, "Map({}, {})", key, value),
+        }
+    }
+}
+
+impl std::str::FromStr for TypeDescriptor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Split on commas outside parentheses and quotes
+fn split_args(args: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '\'' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth = depth.checked_sub(1)?,
+            ',' if !quoted && depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if quoted || depth != 0 {
+        return None;
+    }
+    parts.push(args[start..].trim());
+    Some(parts)
+}
+
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.trim().strip_prefix('\'')?.strip_suffix('\'')?;
+    Some(inner.replace("\\'", "'").replace("\\\\", "\\"))
+}
+
+fn parse_tuple_element(element: &str) -> Result<(Option<String>, TypeDescriptor)> {
+    if let Ok(ty) = TypeDescriptor::parse(element) {
+        return Ok((None, ty));
+    }
+    match element.split_once(char::is_whitespace) {
+        Some((name, ty)) if is_identifier(name) => Ok((Some(name.to_string()), TypeDescriptor::parse(ty)?)),
+        _ => Err(Error::TypeConversion(format!("Invalid tuple element: {}", element))),
+    }
+}
+
+fn parse_enum_value(value: &str) -> Option<(String, i16)> {
+    let (name, number) = value.rsplit_once('=')?;
+    Some((unquote(name)?, number.trim().parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wrappers_and_parameters() {
+        let ty = TypeDescriptor::parse("LowCardinality(Nullable(String))").unwrap();
+        assert!(ty.is_nullable());
+        assert_eq!(ty.base(), &TypeDescriptor::Simple("String".to_string()));
+
+        assert_eq!(TypeDescriptor::parse("Nullable(Decimal64(4))").unwrap().decimal(), Some((18, 4)));
+        assert_eq!(TypeDescriptor::parse("Decimal(10, 2)").unwrap().decimal(), Some((10, 2)));
+        assert_eq!(TypeDescriptor::parse("DateTime64(3, 'Asia/Tokyo')").unwrap().timezone(), Some("Asia/Tokyo"));
+        assert!(TypeDescriptor::parse("DateTime").unwrap().is_datetime());
+        assert_eq!(TypeDescriptor::parse("FixedString(16)").unwrap(), TypeDescriptor::FixedString(16));
+    }
+
+    #[test]
+    fn test_parse_composite_types() {
+        let ty = TypeDescriptor::parse("Map(String, Array(Tuple(id UInt64, Nullable(String))))").unwrap();
+        let TypeDescriptor::Map(key, value) = &ty else {
+            panic!("Expected a map: {:?}", ty);
+        };
+        assert_eq!(**key, TypeDescriptor::Simple("String".to_string()));
+        let TypeDescriptor::Array(element) = value.as_ref() else {
+            panic!("Expected an array: {:?}", value);
+        };
+        let TypeDescriptor::Tuple(elements) = element.as_ref() else {
+            panic!("Expected a tuple: {:?}", element);
+        };
+        assert_eq!(elements[0].0.as_deref(), Some("id"));
+        assert!(elements[1].1.is_nullable());
+
+        let enum_type = TypeDescriptor::parse("Enum8('a,b' = 1, 'it\\'s' = -2)").unwrap();
+        assert_eq!(
+            enum_type,
+            TypeDescriptor::Enum {
+                bits: 8,
+                values: vec![("a,b".to_string(), 1), ("it's".to_string(), -2)],
+            }
+        );
+        assert_eq!(enum_type.to_string(), "Enum8('a,b' = 1, 'it\\'s' = -2)");
+        assert_eq!(ty.to_string(), "Map(String, Array(Tuple(id UInt64, Nullable(String))))");
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_types() {
+        assert!(TypeDescriptor::parse("Nullable(String").is_err());
+        assert!(TypeDescriptor::parse("Decimal(10)").is_err());
+        assert!(TypeDescriptor::parse("Array(String, UInt8)").is_err());
+        assert!(TypeDescriptor::parse("").is_err());
+        assert!(matches!(
+            TypeDescriptor::parse("AggregateFunction(sum, UInt64)").unwrap(),
+            TypeDescriptor::Other(_)
+        ));
+    }
+}
@@ -4,8 +4,16 @@
 //! and truncating longer strings to fit the specified size.
 
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt;
 
+/// Get the length `N` of a `FixedString(N)` column type
+pub fn fixed_string_length(type_name: &str) -> Option<usize> {
+    let start = type_name.find("FixedString(")? + "FixedString(".len();
+    let end = type_name[start..].find(')')?;
+    type_name[start..start + end].trim().parse().ok()
+}
+
 /// FixedString type that stores strings of a fixed length
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FixedString {
@@ -34,6 +42,32 @@ impl FixedString {
         Self { length, data }
     }
 
+    /// Create a FixedString of exactly `length` bytes, padding with null bytes
+    ///
+    /// Fails instead of truncating when the string is longer than `length`.
+    pub fn from_str_padded(s: &str, length: usize) -> Result<Self, String> {
+        Self::from_bytes_padded(s.as_bytes(), length)
+    }
+
+    /// Create a FixedString of exactly `length` bytes from bytes, padding with null bytes
+    pub fn from_bytes_padded(bytes: &[u8], length: usize) -> Result<Self, String> {
+        if bytes.len() > length {
+            return Err(format!(
+                "Value of {} bytes does not fit into FixedString({})",
+                bytes.len(),
+                length
+            ));
+        }
+        Ok(Self::from_bytes(bytes, length))
+    }
+
+    /// Get a copy padded or trimmed to exactly `length` bytes
+    ///
+    /// Only trailing null bytes may be trimmed.
+    pub fn to_length(&self, length: usize) -> Result<Self, String> {
+        Self::from_bytes_padded(&self.data[..self.actual_length()], length)
+    }
+
     /// Create a FixedString from bytes, padding or truncating as needed
     pub fn from_bytes(bytes: &[u8], length: usize) -> Self {
         let mut data = vec![0; length];
@@ -53,30 +87,14 @@ impl FixedString {
         &self.data
     }
 
-    /// Get the string data as a string slice, trimming null bytes
-    pub fn as_str(&self) -> &str {
-        // Find the first non-null byte
-        let start = self.data
-            .iter()
-            .position(|&b| b != 0)
-            .unwrap_or(self.length);
-        
-        // Find the last non-null byte
-        let end = self.data
-            .iter()
-            .rposition(|&b| b != 0)
-            .map(|pos| pos + 1)
-            .unwrap_or(start);
-        
-        if start >= end {
-            return "";
-        }
-        
-        std::str::from_utf8(&self.data[start..end])
-            .unwrap_or("") // Return empty string on invalid UTF-8
+    /// Get the string data as text, trimming trailing null bytes
+    ///
+    /// Invalid UTF-8 sequences are replaced with U+FFFD.
+    pub fn as_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.data[..self.actual_length()])
     }
 
-    /// Get the string data as a string, trimming null bytes
+    /// Get the string data as a string, trimming trailing null bytes
     pub fn to_string(&self) -> String {
         self.as_str().to_string()
     }
@@ -228,6 +246,30 @@ impl From<FixedString> for Vec<u8> {
     }
 }
 
+impl PartialEq<str> for FixedString {
+    fn eq(&self, other: &str) -> bool {
+        self.data[..self.actual_length()] == *other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for FixedString {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<FixedString> for str {
+    fn eq(&self, other: &FixedString) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<FixedString> for &str {
+    fn eq(&self, other: &FixedString) -> bool {
+        other == *self
+    }
+}
+
 impl fmt::Display for FixedString {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.as_str())
@@ -362,7 +404,8 @@ mod tests {
         
         fs.pad_left(15);
         assert_eq!(fs.length(), 15);
-        assert_eq!(fs.as_str(), "hello");
+        // Leading null bytes are data, only trailing ones are padding
+        assert_eq!(fs.as_str(), "\0\0\0\0\0hello");
         assert_eq!(fs.as_bytes().len(), 15);
         
         // Check that the string content is preserved in the middle (bytes 5-10)
@@ -392,4 +435,23 @@ mod tests {
         let bytes: &[u8] = &fs;
         assert_eq!(bytes, b"hello");
     }
+
+    #[test]
+    fn test_fixedstring_padded_and_equality() {
+        let fs = FixedString::from_str_padded("abc", 8).unwrap();
+        assert_eq!(fs.length(), 8);
+        assert_eq!(fs.as_bytes(), b"abc\0\0\0\0\0");
+        assert!(fs == "abc");
+        assert!("abc" == fs);
+        assert!(fs != "abc\0");
+        assert!(FixedString::from_str_padded("too long", 3).is_err());
+
+        assert_eq!(fs.to_length(3).unwrap().as_bytes(), b"abc");
+        assert!(fs.to_length(2).is_err());
+
+        let invalid = FixedString::from_bytes(&[0xff, b'a', 0], 3);
+        assert_eq!(invalid.as_str(), "\u{fffd}a");
+        assert_eq!(fixed_string_length("Nullable(FixedString(16))"), Some(16));
+        assert_eq!(fixed_string_length("String"), None);
+    }
 }
@@ -0,0 +1,106 @@
+//! Column lookup by name with optional case folding and aliases
+
+use super::{Block, Column};
+use std::collections::HashMap;
+
+/// How column names are matched when looking columns up by name
+///
+/// The default is an exact, case-sensitive match. An exact match always wins
+/// over a case-insensitive one, so blocks holding both `id` and `ID` stay
+/// unambiguous.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnLookup {
+    /// Match names regardless of ASCII case
+    pub case_insensitive: bool,
+    /// Requested names mapped to the result column names they stand for
+    pub aliases: HashMap<String, String>,
+}
+
+impl ColumnLookup {
+    /// Create an exact, case-sensitive lookup
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a lookup that ignores ASCII case
+    pub fn case_insensitive() -> Self {
+        Self::new().ignore_case(true)
+    }
+
+    /// Set whether ASCII case is ignored
+    pub fn ignore_case(mut self, ignore: bool) -> Self {
+        self.case_insensitive = ignore;
+        self
+    }
+
+    /// Look `name` up as the result column `column`, e.g. for `SELECT a AS B`
+    pub fn alias(mut self, name: impl Into<String>, column: impl Into<String>) -> Self {
+        self.aliases.insert(name.into(), column.into());
+        self
+    }
+
+    /// Resolve an alias to the column name it stands for
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        if let Some(column) = self.aliases.get(name) {
+            return column;
+        }
+        if self.case_insensitive {
+            if let Some((_, column)) = self.aliases.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(name)) {
+                return column;
+            }
+        }
+        name
+    }
+
+    /// Find the position of `name` among column names
+    pub fn position<'a, I>(&self, name: &str, names: I) -> Option<usize>
+    where
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: Clone,
+    {
+        let name = self.resolve(name);
+        let names = names.into_iter();
+        names.clone().position(|n| n == name).or_else(|| {
+            self.case_insensitive
+                .then(|| names.clone().position(|n| n.eq_ignore_ascii_case(name)))
+                .flatten()
+        })
+    }
+}
+
+impl Block {
+    /// Get the position of a column, matching names as `lookup` says
+    pub fn column_position(&self, name: &str, lookup: &ColumnLookup) -> Option<usize> {
+        lookup.position(name, self.columns.iter().map(|c| c.name.as_str()))
+    }
+
+    /// Get a column by name, matching names as `lookup` says
+    pub fn find_column(&self, name: &str, lookup: &ColumnLookup) -> Option<&Column> {
+        self.column_position(name, lookup).map(|i| &self.columns[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ColumnData;
+
+    #[test]
+    fn test_column_lookup() {
+        let mut block = Block::new();
+        for name in ["id", "ID", "UserName"] {
+            block.add_column(name, Column::new(name, "UInt8", ColumnData::UInt8(vec![])));
+        }
+
+        let exact = ColumnLookup::new();
+        assert_eq!(block.column_position("ID", &exact), Some(1));
+        assert_eq!(block.column_position("username", &exact), None);
+
+        let folded = ColumnLookup::case_insensitive().alias("user", "UserName");
+        assert_eq!(block.column_position("id", &folded), Some(0));
+        assert_eq!(block.column_position("ID", &folded), Some(1));
+        assert_eq!(block.column_position("USERNAME", &folded), Some(2));
+        assert_eq!(block.find_column("User", &folded).unwrap().name, "UserName");
+        assert!(block.find_column("missing", &folded).is_none());
+    }
+}
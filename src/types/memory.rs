@@ -0,0 +1,146 @@
+//! Memory accounting for blocks, columns and values
+//!
+//! Sizes are estimates of what the allocator holds: vectors are counted by
+//! capacity rather than length, and every heap allocation owned by a value
+//! (strings, nested values, enum definitions) is included.
+
+use super::{Block, Column, ColumnData, EnumDefinition, LowCardinality, Row, Value};
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// Bytes held by a vector of plain values
+fn vec_usage<T>(v: &Vec<T>) -> usize {
+    v.capacity() * size_of::<T>()
+}
+
+/// Bytes held by a vector of values that own heap memory
+fn nested_usage<T>(v: &Vec<T>, heap: impl Fn(&T) -> usize) -> usize {
+    vec_usage(v) + v.iter().map(heap).sum::<usize>()
+}
+
+fn map_usage<K, V>(map: &HashMap<K, V>, heap: impl Fn(&K, &V) -> usize) -> usize {
+    map.capacity() * (size_of::<K>() + size_of::<V>()) + map.iter().map(|(k, v)| heap(k, v)).sum::<usize>()
+}
+
+fn low_cardinality_usage(lc: &LowCardinality<String>) -> usize {
+    lc.dictionary().iter().map(|s| size_of::<String>() + s.capacity()).sum::<usize>()
+        + std::mem::size_of_val(lc.indices())
+}
+
+fn enum_heap_usage(definition: &EnumDefinition) -> usize {
+    definition.name.capacity()
+        + map_usage(&definition.values, |name, _| name.capacity())
+        + map_usage(&definition.names, |_, name| name.capacity())
+}
+
+impl Value {
+    /// Estimated memory held by the value, including its inline size
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Value>() + self.heap_usage()
+    }
+
+    /// Estimated heap memory owned by the value
+    fn heap_usage(&self) -> usize {
+        match self {
+            Value::String(s) => s.capacity(),
+            Value::FixedString(s) => s.as_bytes().len(),
+            Value::LowCardinality(lc) => low_cardinality_usage(lc),
+            Value::Enum8(v) => enum_heap_usage(v.definition()),
+            Value::Enum16(v) => enum_heap_usage(v.definition()),
+            Value::Array(values) | Value::Tuple(values) => nested_usage(values, Value::heap_usage),
+            Value::Nullable(Some(inner)) => inner.memory_usage(),
+            Value::Map(map) => map_usage(map, |k, v| k.capacity() + v.heap_usage()),
+            Value::Unsupported(bytes, type_name) => bytes.capacity() + type_name.capacity(),
+            _ => 0,
+        }
+    }
+}
+
+impl ColumnData {
+    /// Estimated memory held by the column values
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            ColumnData::UInt8(v) => vec_usage(v),
+            ColumnData::UInt16(v) => vec_usage(v),
+            ColumnData::UInt32(v) => vec_usage(v),
+            ColumnData::UInt64(v) => vec_usage(v),
+            ColumnData::UInt128(v) => vec_usage(v),
+            ColumnData::UInt256(v) => vec_usage(v),
+            ColumnData::Int8(v) => vec_usage(v),
+            ColumnData::Int16(v) => vec_usage(v),
+            ColumnData::Int32(v) => vec_usage(v),
+            ColumnData::Int64(v) => vec_usage(v),
+            ColumnData::Int128(v) => vec_usage(v),
+            ColumnData::Int256(v) => vec_usage(v),
+            ColumnData::Float32(v) => vec_usage(v),
+            ColumnData::Float64(v) => vec_usage(v),
+            ColumnData::String(v) => nested_usage(v, |s| s.capacity()),
+            ColumnData::FixedString(v) => nested_usage(v, |s| s.as_bytes().len()),
+            ColumnData::LowCardinality(lc) => low_cardinality_usage(lc),
+            ColumnData::Date(v) => vec_usage(v),
+            ColumnData::DateTime(v) => vec_usage(v),
+            ColumnData::DateTime64(v) => vec_usage(v),
+            ColumnData::UUID(v) => vec_usage(v),
+            ColumnData::IPv4(v) => vec_usage(v),
+            ColumnData::IPv6(v) => vec_usage(v),
+            ColumnData::Decimal32(v) => vec_usage(v),
+            ColumnData::Decimal64(v) => vec_usage(v),
+            ColumnData::Decimal128(v) => vec_usage(v),
+            ColumnData::Enum8(v) => nested_usage(v, |e| enum_heap_usage(e.definition())),
+            ColumnData::Enum16(v) => nested_usage(v, |e| enum_heap_usage(e.definition())),
+            ColumnData::Array(v) | ColumnData::Tuple(v) => {
+                nested_usage(v, |values| nested_usage(values, Value::heap_usage))
+            }
+            ColumnData::Nullable(v) => nested_usage(v, |value| value.as_ref().map_or(0, Value::heap_usage)),
+            ColumnData::Map(v) => nested_usage(v, |map| map_usage(map, |k, v| k.capacity() + v.heap_usage())),
+            ColumnData::Unsupported { type_name, values } => {
+                type_name.capacity() + nested_usage(values, |bytes| bytes.capacity())
+            }
+        }
+    }
+}
+
+impl Column {
+    /// Estimated memory held by the column, including its name and type
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Column>() + self.name.capacity() + self.type_name.capacity() + self.data.memory_usage()
+    }
+}
+
+impl Block {
+    /// Estimated memory held by the block and all of its columns
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Block>()
+            + (self.columns.capacity() - self.columns.len()) * size_of::<Column>()
+            + self.columns.iter().map(Column::memory_usage).sum::<usize>()
+    }
+}
+
+impl Row {
+    /// Estimated memory held by the row values
+    pub fn memory_usage(&self) -> usize {
+        nested_usage(&self.values, |value| value.as_ref().map_or(0, Value::heap_usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_usage() {
+        let numbers = ColumnData::UInt64(Vec::with_capacity(100));
+        assert_eq!(numbers.memory_usage(), 800);
+
+        let strings = ColumnData::String(vec!["abcd".to_string(), String::new()]);
+        assert_eq!(strings.memory_usage(), 2 * size_of::<String>() + 4);
+
+        let nested = Value::Array(vec![Value::String("xyz".to_string())]);
+        assert_eq!(nested.memory_usage(), 2 * size_of::<Value>() + 3);
+
+        let mut block = Block::new();
+        let empty = block.memory_usage();
+        block.add_column("n", Column::new("n", "UInt64", numbers));
+        assert!(block.memory_usage() >= empty + 800 + size_of::<Column>());
+    }
+}
@@ -10,6 +10,19 @@ mod network;
 mod fixed_string;
 mod enum_types;
 mod decimal;
+mod wide;
+mod descriptor;
+mod aggregate;
+mod sort;
+mod convert;
+mod memory;
+mod rows;
+mod lookup;
+mod record;
+mod coerce;
+mod codec;
+mod widen;
+mod stats;
 
 
 pub use numeric::*;
@@ -22,6 +35,17 @@ pub use network::*;
 pub use fixed_string::*;
 pub use enum_types::*;
 pub use decimal::*;
+pub use wide::*;
+pub use descriptor::*;
+pub use aggregate::*;
+pub use rows::*;
+pub use lookup::*;
+pub use record::{NameCase, RowSchema};
+pub use convert::{FromRow, FromValue};
+pub use coerce::{BlockCoercer, CoercionAction, CoercionIssue, CoercionPolicy, CoercionReport};
+pub use codec::{CodecRegistry, ValueCodec};
+pub use widen::ConversionPolicy;
+pub use stats::{ColumnStats, HyperLogLog, HLL_PRECISION};
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -35,6 +59,7 @@ pub struct Block {
     pub columns: Vec<Column>,
     /// Number of rows in the block
     pub row_count: usize,
+    stats_cache: stats::StatsCache,
 }
 
 impl Block {
@@ -44,6 +69,7 @@ impl Block {
             info: BlockInfo::default(),
             columns: Vec::new(),
             row_count: 0,
+            stats_cache: stats::StatsCache::default(),
         }
     }
 
@@ -52,6 +78,17 @@ impl Block {
         self.row_count
     }
 
+    /// Set the block metadata
+    pub fn with_info(mut self, info: BlockInfo) -> Self {
+        self.info = info;
+        self
+    }
+
+    /// Check if this is an overflow block from aggregation with totals
+    pub fn is_overflows(&self) -> bool {
+        self.info.is_overflows
+    }
+
     /// Create a new block with the specified columns
     pub fn with_columns(columns: Vec<Column>) -> Self {
         let row_count = columns.first().map(|col| col.len()).unwrap_or(0);
@@ -59,12 +96,14 @@ impl Block {
             info: BlockInfo::default(),
             columns,
             row_count,
+            stats_cache: stats::StatsCache::default(),
         }
     }
 
     /// Add a column to the block
     pub fn add_column(&mut self, _name: impl Into<String>, column: Column) {
         let column_len = column.len();
+        self.invalidate_stats();
         self.columns.push(column);
         if self.row_count == 0 {
             self.row_count = column_len;
@@ -78,6 +117,7 @@ impl Block {
 
     /// Get a mutable column by name
     pub fn get_column_mut(&mut self, name: &str) -> Option<&mut Column> {
+        self.invalidate_stats();
         self.columns.iter_mut().find(|col| col.name == name)
     }
 
@@ -93,6 +133,7 @@ impl Block {
 
     /// Clear all data from the block
     pub fn clear(&mut self) {
+        self.invalidate_stats();
         self.columns.clear();
         self.row_count = 0;
     }
@@ -136,6 +177,11 @@ impl Default for Block {
 }
 
 /// Block metadata information
+///
+/// Two-level aggregation sends its result split into buckets, each holding a
+/// disjoint set of keys. With `WITH TOTALS` and `group_by_overflow_mode = 'any'`,
+/// rows for keys past `max_rows_to_group_by` are aggregated into a separate
+/// overflow block, which is not part of the regular result.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockInfo {
     /// Whether this is an overflow block
@@ -146,6 +192,13 @@ pub struct BlockInfo {
     pub num_buckets: i32,
 }
 
+impl BlockInfo {
+    /// Get the two-level aggregation bucket, if the block belongs to one
+    pub fn bucket(&self) -> Option<i32> {
+        (self.bucket_num >= 0).then_some(self.bucket_num)
+    }
+}
+
 impl Default for BlockInfo {
     fn default() -> Self {
         Self {
@@ -194,12 +247,36 @@ impl Column {
 
     /// Set a value at the specified index
     pub fn set_value(&mut self, index: usize, value: Value) -> Result<(), String> {
-        self.data.set_value(index, value)
+        self.set_value_with(index, value, ConversionPolicy::default())
+    }
+
+    /// Set a value at the specified index, converting it according to `policy`
+    pub fn set_value_with(&mut self, index: usize, value: Value, policy: ConversionPolicy) -> Result<(), String> {
+        let value = self.fit_value(value)?;
+        self.data.set_value_with(index, value, policy)
     }
 
     /// Append a value to the column
+    ///
+    /// FixedString values are padded to the column's `N` bytes, and rejected if longer.
+    /// Values of smaller types are widened, see [`ConversionPolicy::Widen`].
     pub fn push(&mut self, value: Value) -> Result<(), String> {
-        self.data.push(value)
+        self.push_with(value, ConversionPolicy::default())
+    }
+
+    /// Append a value to the column, converting it according to `policy`
+    pub fn push_with(&mut self, value: Value, policy: ConversionPolicy) -> Result<(), String> {
+        let value = self.fit_value(value)?;
+        self.data.push_with(value, policy)
+    }
+
+    fn fit_value(&self, value: Value) -> Result<Value, String> {
+        match (value, fixed_string_length(&self.type_name)) {
+            (Value::FixedString(v), Some(length)) if v.length() != length => {
+                v.to_length(length).map(Value::FixedString)
+            }
+            (value, _) => Ok(value),
+        }
     }
 
     /// Get the column type
@@ -236,7 +313,7 @@ pub enum ColumnData {
     /// UInt128 values
     UInt128(Vec<u128>),
     /// UInt256 values
-    UInt256(Vec<u256::U256>),
+    UInt256(Vec<UInt256>),
     /// Int8 values
     Int8(Vec<i8>),
     /// Int16 values
@@ -248,7 +325,7 @@ pub enum ColumnData {
     /// Int128 values
     Int128(Vec<i128>),
     /// Int256 values
-    Int256(Vec<i256::I256>),
+    Int256(Vec<Int256>),
     /// Float32 values
     Float32(Vec<f32>),
     /// Float64 values
@@ -289,6 +366,13 @@ pub enum ColumnData {
     Tuple(Vec<Vec<Value>>),
     /// Map values
     Map(Vec<HashMap<String, Value>>),
+    /// Raw values of a type the client cannot decode
+    Unsupported {
+        /// Column type
+        type_name: String,
+        /// Encoded bytes of each value
+        values: Vec<Vec<u8>>,
+    },
 }
 
 impl ColumnData {
@@ -327,6 +411,7 @@ impl ColumnData {
             ColumnData::Nullable(v) => v.len(),
             ColumnData::Tuple(v) => v.len(),
             ColumnData::Map(v) => v.len(),
+            ColumnData::Unsupported { values, .. } => values.len(),
         }
     }
 
@@ -369,14 +454,23 @@ impl ColumnData {
             ColumnData::Nullable(v) => Some(Value::Nullable(v[index].as_ref().map(|val| Box::new(val.clone())))),
             ColumnData::Tuple(v) => Some(Value::Tuple(v[index].clone())),
             ColumnData::Map(v) => Some(Value::Map(v[index].clone())),
+            ColumnData::Unsupported { type_name, values } => {
+                Some(Value::Unsupported(values[index].clone(), type_name.clone()))
+            }
         }
     }
 
-    /// Set a value at the specified index
+    /// Set a value at the specified index, widening it to the column's type if needed
     pub fn set_value(&mut self, index: usize, value: Value) -> Result<(), String> {
+        self.set_value_with(index, value, ConversionPolicy::default())
+    }
+
+    /// Set a value at the specified index, converting it according to `policy`
+    pub fn set_value_with(&mut self, index: usize, value: Value, policy: ConversionPolicy) -> Result<(), String> {
         if index >= self.len() {
             return Err("Index out of bounds".to_string());
         }
+        let value = policy.convert(value, self)?;
 
         match (self, value) {
             (ColumnData::UInt8(v), Value::UInt8(val)) => v[index] = val,
@@ -415,14 +509,23 @@ impl ColumnData {
             (ColumnData::Nullable(v), Value::Nullable(val)) => v[index] = val.map(|val| *val),
             (ColumnData::Tuple(v), Value::Tuple(val)) => v[index] = val,
             (ColumnData::Map(v), Value::Map(val)) => v[index] = val,
+            (ColumnData::Unsupported { type_name, values }, Value::Unsupported(bytes, ty)) if *type_name == ty => {
+                values[index] = bytes
+            }
             _ => return Err("Type mismatch".to_string()),
         }
 
         Ok(())
     }
 
-    /// Push a value to the column
+    /// Push a value to the column, widening it to the column's type if needed
     pub fn push(&mut self, value: Value) -> Result<(), String> {
+        self.push_with(value, ConversionPolicy::default())
+    }
+
+    /// Push a value to the column, converting it according to `policy`
+    pub fn push_with(&mut self, value: Value, policy: ConversionPolicy) -> Result<(), String> {
+        let value = policy.convert(value, self)?;
         match (self, value) {
             (ColumnData::UInt8(v), Value::UInt8(val)) => v.push(val),
             (ColumnData::UInt16(v), Value::UInt16(val)) => v.push(val),
@@ -456,6 +559,9 @@ impl ColumnData {
             (ColumnData::Nullable(v), Value::Nullable(val)) => v.push(val.map(|val| *val)),
             (ColumnData::Tuple(v), Value::Tuple(val)) => v.push(val),
             (ColumnData::Map(v), Value::Map(val)) => v.push(val),
+            (ColumnData::Unsupported { type_name, values }, Value::Unsupported(bytes, ty)) if *type_name == ty => {
+                values.push(bytes)
+            }
             _ => return Err("Type mismatch".to_string()),
         }
 
@@ -543,7 +649,7 @@ pub enum Value {
     /// UInt128 value
     UInt128(u128),
     /// UInt256 value
-    UInt256(u256::U256),
+    UInt256(UInt256),
     /// Int8 value
     Int8(i8),
     /// Int16 value
@@ -555,7 +661,7 @@ pub enum Value {
     /// Int128 value
     Int128(i128),
     /// Int256 value
-    Int256(i256::I256),
+    Int256(Int256),
     /// Float32 value
     Float32(f32),
     /// Float64 value
@@ -596,6 +702,8 @@ pub enum Value {
     Tuple(Vec<Value>),
     /// Map value
     Map(HashMap<String, Value>),
+    /// Encoded bytes and type of a value the client cannot decode
+    Unsupported(Vec<u8>, String),
 }
 
 impl std::fmt::Display for Value {
@@ -606,17 +714,17 @@ impl std::fmt::Display for Value {
             Value::UInt32(v) => write!(f, "{}", v),
             Value::UInt64(v) => write!(f, "{}", v),
             Value::UInt128(v) => write!(f, "{}", v),
-            Value::UInt256(v) => write!(f, "{:?}", v),
+            Value::UInt256(v) => write!(f, "{}", v),
             Value::Int8(v) => write!(f, "{}", v),
             Value::Int16(v) => write!(f, "{}", v),
             Value::Int32(v) => write!(f, "{}", v),
             Value::Int64(v) => write!(f, "{}", v),
             Value::Int128(v) => write!(f, "{}", v),
-            Value::Int256(v) => write!(f, "{:?}", v),
+            Value::Int256(v) => write!(f, "{}", v),
             Value::Float32(v) => write!(f, "{}", v),
             Value::Float64(v) => write!(f, "{}", v),
             Value::String(v) => write!(f, "{}", v),
-            Value::FixedString(v) => write!(f, "{:?}", v),
+            Value::FixedString(v) => write!(f, "{}", v),
             Value::LowCardinality(v) => write!(f, "{:?}", v),
             Value::Date(v) => write!(f, "{}", v),
             Value::DateTime(v) => write!(f, "{}", v),
@@ -664,6 +772,13 @@ impl std::fmt::Display for Value {
             Value::Enum8(v) => write!(f, "{}", v),
             Value::Enum16(v) => write!(f, "{}", v),
             Value::Null => write!(f, "NULL"),
+            Value::Unsupported(bytes, type_name) => {
+                write!(f, "<{} 0x", type_name)?;
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                write!(f, ">")
+            }
         }
     }
 }
@@ -705,8 +820,8 @@ impl From<u128> for Value {
     }
 }
 
-impl From<u256::U256> for Value {
-    fn from(value: u256::U256) -> Self {
+impl From<UInt256> for Value {
+    fn from(value: UInt256) -> Self {
         Value::UInt256(value)
     }
 }
@@ -741,8 +856,8 @@ impl From<i128> for Value {
     }
 }
 
-impl From<i256::I256> for Value {
-    fn from(value: i256::I256) -> Self {
+impl From<Int256> for Value {
+    fn from(value: Int256) -> Self {
         Value::Int256(value)
     }
 }
@@ -891,6 +1006,7 @@ impl Value {
             Value::Enum8(_) => "Enum8",
             Value::Enum16(_) => "Enum16",
             Value::Null => "Null",
+            Value::Unsupported(_, _) => "Unsupported",
 
         }
     }
@@ -902,14 +1018,12 @@ pub type UInt16 = u16;
 pub type UInt32 = u32;
 pub type UInt64 = u64;
 pub type UInt128 = u128;
-pub type UInt256 = u256::U256;
 
 pub type Int8 = i8;
 pub type Int16 = i16;
 pub type Int32 = i32;
 pub type Int64 = i64;
 pub type Int128 = i128;
-pub type Int256 = i256::I256;
 
 pub type Float32 = f32;
 pub type Float64 = f64;
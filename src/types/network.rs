@@ -7,7 +7,10 @@ use std::net::{Ipv4Addr, Ipv6Addr};
 use serde::{Deserialize, Serialize};
 
 /// IPv4 address type for ClickHouse
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+///
+/// On the wire the address is a little-endian `UInt32`. Addresses order
+/// numerically, like the server orders them.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct IPv4(pub Ipv4Addr);
 
 impl IPv4 {
@@ -109,7 +112,11 @@ impl std::str::FromStr for IPv4 {
 }
 
 /// IPv6 address type for ClickHouse
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+///
+/// On the wire the address is its 16 octets in network byte order, like a
+/// `FixedString(16)`. Addresses order by those octets, and display in the
+/// canonical RFC 5952 form, with IPv4-mapped addresses as `::ffff:a.b.c.d`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct IPv6(pub Ipv6Addr);
 
 impl IPv6 {
@@ -140,6 +147,21 @@ impl IPv6 {
         self.0.segments()
     }
 
+    /// Create from octets in network byte order
+    pub fn from_octets(octets: [u8; 16]) -> Self {
+        Self(Ipv6Addr::from(octets))
+    }
+
+    /// Convert to octets in network byte order
+    pub fn to_octets(&self) -> [u8; 16] {
+        self.0.octets()
+    }
+
+    /// Get the IPv4 address of an IPv4-mapped address (`::ffff:a.b.c.d`)
+    pub fn to_ipv4_mapped(&self) -> Option<IPv4> {
+        self.0.to_ipv4_mapped().map(IPv4)
+    }
+
     /// Check if this is a private address
     pub fn is_private(&self) -> bool {
         // IPv6 doesn't have a direct is_private method, so we check for unique local addresses
@@ -191,6 +213,12 @@ impl From<[u16; 8]> for IPv6 {
     }
 }
 
+impl From<[u8; 16]> for IPv6 {
+    fn from(octets: [u8; 16]) -> Self {
+        Self::from_octets(octets)
+    }
+}
+
 impl From<IPv6> for [u16; 8] {
     fn from(ip: IPv6) -> Self {
         ip.0.segments()
@@ -307,6 +335,20 @@ mod tests {
         assert_eq!(ip.to_string(), "0.0.0.0");
     }
 
+    #[test]
+    fn test_canonical_form_and_ordering() {
+        let mapped = IPv6::from_str("0:0:0:0:0:FFFF:0A00:0001").unwrap();
+        assert_eq!(mapped.to_string(), "::ffff:10.0.0.1");
+        assert_eq!(mapped.to_ipv4_mapped(), Some(IPv4::from_octets(10, 0, 0, 1)));
+        assert_eq!(IPv6::from_str("2001:0DB8:0:0:1:0:0:1").unwrap().to_string(), "2001:db8::1:0:0:1");
+        assert_eq!(IPv6::from(mapped.to_octets()), mapped);
+
+        let mut v4 = vec![IPv4::from_octets(10, 0, 0, 2), IPv4::from_octets(9, 255, 255, 255)];
+        v4.sort();
+        assert_eq!(v4[0].to_string(), "9.255.255.255");
+        assert!(IPv6::from_str("::1").unwrap() < IPv6::from_str("fe80::1").unwrap());
+    }
+
     #[test]
     fn test_ipv6_default() {
         let ip = IPv6::default();
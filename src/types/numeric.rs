@@ -24,10 +24,6 @@ pub struct UInt64(pub u64);
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct UInt128(pub u128);
 
-/// UInt256 type (0 to 2^256 - 1)
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct UInt256(pub u256::U256);
-
 /// Int8 type (-128 to 127)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Int8(pub i8);
@@ -48,10 +44,6 @@ pub struct Int64(pub i64);
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Int128(pub i128);
 
-/// Int256 type (-2^255 to 2^255 - 1)
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Int256(pub i256::I256);
-
 /// Float32 type (32-bit floating point)
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Float32(pub f32);
@@ -91,12 +83,6 @@ impl fmt::Display for UInt128 {
     }
 }
 
-impl fmt::Display for UInt256 {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.0)
-    }
-}
-
 impl fmt::Display for Int8 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -127,12 +113,6 @@ impl fmt::Display for Int128 {
     }
 }
 
-impl fmt::Display for Int256 {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
 impl fmt::Display for Float32 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -176,12 +156,6 @@ impl From<u128> for UInt128 {
     }
 }
 
-impl From<u256::U256> for UInt256 {
-    fn from(value: u256::U256) -> Self {
-        UInt256(value)
-    }
-}
-
 impl From<i8> for Int8 {
     fn from(value: i8) -> Self {
         Int8(value)
@@ -212,12 +186,6 @@ impl From<i128> for Int128 {
     }
 }
 
-impl From<i256::I256> for Int256 {
-    fn from(value: i256::I256) -> Self {
-        Int256(value)
-    }
-}
-
 impl From<f32> for Float32 {
     fn from(value: f32) -> Self {
         Float32(value)
@@ -583,12 +551,6 @@ impl Default for UInt128 {
     }
 }
 
-impl Default for UInt256 {
-    fn default() -> Self {
-        UInt256(u256::U256::zero())
-    }
-}
-
 impl Default for Int8 {
     fn default() -> Self {
         Int8(0)
@@ -619,12 +581,6 @@ impl Default for Int128 {
     }
 }
 
-impl Default for Int256 {
-    fn default() -> Self {
-        Int256(i256::I256::min_value())
-    }
-}
-
 impl Default for Float32 {
     fn default() -> Self {
         Float32(0.0)
@@ -0,0 +1,210 @@
+//! Name-keyed views of rows
+//!
+//! A [`Row`] only holds values by position. A [`RowSchema`] carries the
+//! column names of the block the rows came from, optionally converted to
+//! another case, so rows can be turned into maps or JSON objects.
+
+use super::{Block, Row, Value};
+use std::collections::HashMap;
+
+/// Case conversion applied to column names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameCase {
+    /// Keep names as the server sent them
+    #[default]
+    Unchanged,
+    /// `snake_case`
+    Snake,
+    /// `camelCase`
+    Camel,
+}
+
+impl NameCase {
+    /// Convert a column name
+    ///
+    /// Word boundaries are `_`, `-`, spaces and lower-to-upper case changes.
+    pub fn convert(&self, name: &str) -> String {
+        if *self == NameCase::Unchanged {
+            return name.to_string();
+        }
+
+        let mut words: Vec<String> = Vec::new();
+        let mut current = String::new();
+        let mut previous_lower = false;
+        for c in name.chars() {
+            if c == '_' || c == '-' || c == ' ' {
+                words.extend((!current.is_empty()).then(|| std::mem::take(&mut current)));
+                previous_lower = false;
+                continue;
+            }
+            if c.is_uppercase() && previous_lower {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lower = c.is_lowercase() || c.is_ascii_digit();
+            current.extend(c.to_lowercase());
+        }
+        words.extend((!current.is_empty()).then_some(current));
+
+        match self {
+            NameCase::Snake => words.join("_"),
+            _ => {
+                let mut converted = String::with_capacity(name.len());
+                for (i, word) in words.iter().enumerate() {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) if i > 0 => {
+                            converted.extend(first.to_uppercase());
+                            converted.push_str(chars.as_str());
+                        }
+                        _ => converted.push_str(word),
+                    }
+                }
+                converted
+            }
+        }
+    }
+}
+
+/// Column names used to key the values of a row
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowSchema {
+    names: Vec<String>,
+}
+
+impl RowSchema {
+    /// Create a schema from column names
+    pub fn new<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Create a schema from the columns of a block
+    pub fn from_block(block: &Block) -> Self {
+        Self::new(block.columns().map(|column| column.name.clone()))
+    }
+
+    /// Convert the column names to another case
+    pub fn case(mut self, case: NameCase) -> Self {
+        self.names = self.names.iter().map(|name| case.convert(name)).collect();
+        self
+    }
+
+    /// Get the column names
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+impl Row {
+    /// Key the values of the row by column name
+    ///
+    /// NULLs become [`Value::Null`]. Values beyond the schema are skipped.
+    pub fn to_hashmap(&self, schema: &RowSchema) -> HashMap<String, Value> {
+        schema
+            .names
+            .iter()
+            .zip(&self.values)
+            .map(|(name, value)| (name.clone(), value.clone().unwrap_or(Value::Null)))
+            .collect()
+    }
+
+    /// Convert the row into a JSON object keyed by column name
+    pub fn to_json(&self, schema: &RowSchema) -> serde_json::Value {
+        let object = schema
+            .names
+            .iter()
+            .zip(&self.values)
+            .map(|(name, value)| (name.clone(), value.as_ref().map_or(serde_json::Value::Null, Value::to_json)))
+            .collect();
+        serde_json::Value::Object(object)
+    }
+}
+
+impl Value {
+    /// Convert the value into JSON
+    ///
+    /// Integers wider than 64 bits and decimals become strings so no precision
+    /// is lost; dates, UUIDs and addresses use their text form. Non-finite
+    /// floats and values the client could not decode become `null`.
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value as Json;
+
+        match self {
+            Value::Null | Value::Nullable(None) | Value::Unsupported(..) => Json::Null,
+            Value::UInt8(v) => Json::from(*v),
+            Value::UInt16(v) => Json::from(*v),
+            Value::UInt32(v) => Json::from(*v),
+            Value::UInt64(v) => Json::from(*v),
+            Value::Int8(v) => Json::from(*v),
+            Value::Int16(v) => Json::from(*v),
+            Value::Int32(v) => Json::from(*v),
+            Value::Int64(v) => Json::from(*v),
+            Value::Float32(v) => serde_json::Number::from_f64(*v as f64).map_or(Json::Null, Json::Number),
+            Value::Float64(v) => serde_json::Number::from_f64(*v).map_or(Json::Null, Json::Number),
+            Value::String(s) => Json::String(s.clone()),
+            Value::FixedString(s) => Json::String(String::from_utf8_lossy(s.as_bytes()).into_owned()),
+            Value::LowCardinality(values) => {
+                Json::Array((0..values.len()).filter_map(|i| values.get(i)).cloned().map(Json::String).collect())
+            }
+            Value::Enum8(v) => v.name().map_or(Json::from(v.value()), |name| Json::String(name.clone())),
+            Value::Enum16(v) => v.name().map_or(Json::from(v.value()), |name| Json::String(name.clone())),
+            Value::Date(d) => Json::String(d.format("%Y-%m-%d").to_string()),
+            Value::DateTime(dt) => Json::String(dt.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Value::DateTime64(dt) => Json::String(dt.format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+            Value::Array(values) | Value::Tuple(values) => Json::Array(values.iter().map(Value::to_json).collect()),
+            Value::Nullable(Some(inner)) => inner.to_json(),
+            Value::Map(map) => Json::Object(map.iter().map(|(k, v)| (k.clone(), v.to_json())).collect()),
+            Value::UInt128(_)
+            | Value::UInt256(_)
+            | Value::Int128(_)
+            | Value::Int256(_)
+            | Value::UUID(_)
+            | Value::IPv4(_)
+            | Value::IPv6(_)
+            | Value::Decimal32(_)
+            | Value::Decimal64(_)
+            | Value::Decimal128(_) => Json::String(self.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Column, ColumnData};
+    use serde_json::json;
+
+    #[test]
+    fn test_name_case_conversion() {
+        assert_eq!(NameCase::Snake.convert("userID"), "user_id");
+        assert_eq!(NameCase::Snake.convert("EventTime"), "event_time");
+        assert_eq!(NameCase::Camel.convert("event_time"), "eventTime");
+        assert_eq!(NameCase::Camel.convert("count()"), "count()");
+        assert_eq!(NameCase::Unchanged.convert("Event_Time"), "Event_Time");
+    }
+
+    #[test]
+    fn test_row_to_hashmap_and_json() {
+        let block = Block::with_columns(vec![
+            Column::new("user_id", "UInt64", ColumnData::UInt64(vec![7])),
+            Column::new("total_spent", "Int128", ColumnData::Int128(vec![-5])),
+            Column::new("tags", "Array(String)", ColumnData::Array(vec![vec![Value::String("a".into())]])),
+        ]);
+        let row = block.get_row(0).unwrap();
+
+        let map = row.to_hashmap(&RowSchema::from_block(&block));
+        assert_eq!(map["user_id"], Value::UInt64(7));
+        assert_eq!(map.len(), 3);
+
+        let schema = RowSchema::from_block(&block).case(NameCase::Camel);
+        assert_eq!(row.to_json(&schema), json!({"userId": 7, "totalSpent": "-5", "tags": ["a"]}));
+
+        let partial = Row::new(vec![None]);
+        assert_eq!(partial.to_json(&schema), json!({"userId": null}));
+    }
+}
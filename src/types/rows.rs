@@ -0,0 +1,195 @@
+//! Building blocks column by column from Rust tuples
+
+use super::{network, Block, Column, ColumnData, Value};
+
+/// A Rust type stored in a ClickHouse column of an inferred type
+pub trait ColumnType: Sized {
+    /// ClickHouse type name of the column
+    fn type_name() -> String;
+
+    /// Create empty column data with room for `capacity` values
+    fn new_column(capacity: usize) -> ColumnData;
+
+    /// Append the value to column data created by [`ColumnType::new_column`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `column` holds data of another type.
+    fn push_to(self, column: &mut ColumnData);
+}
+
+fn column_mismatch(type_name: &str) -> ! {
+    panic!("Cannot append a {} value to column data of another type", type_name)
+}
+
+macro_rules! impl_column_type {
+    ($($target:ty => $variant:ident, $name:expr;)*) => {$(
+        impl ColumnType for $target {
+            fn type_name() -> String {
+                $name.to_string()
+            }
+
+            fn new_column(capacity: usize) -> ColumnData {
+                ColumnData::$variant(Vec::with_capacity(capacity))
+            }
+
+            fn push_to(self, column: &mut ColumnData) {
+                match column {
+                    ColumnData::$variant(values) => values.push(self.into()),
+                    _ => column_mismatch($name),
+                }
+            }
+        }
+    )*};
+}
+
+impl_column_type! {
+    u8 => UInt8, "UInt8";
+    u16 => UInt16, "UInt16";
+    u32 => UInt32, "UInt32";
+    u64 => UInt64, "UInt64";
+    u128 => UInt128, "UInt128";
+    super::UInt256 => UInt256, "UInt256";
+    i8 => Int8, "Int8";
+    i16 => Int16, "Int16";
+    i32 => Int32, "Int32";
+    i64 => Int64, "Int64";
+    i128 => Int128, "Int128";
+    super::Int256 => Int256, "Int256";
+    f32 => Float32, "Float32";
+    f64 => Float64, "Float64";
+    bool => UInt8, "Bool";
+    String => String, "String";
+    &str => String, "String";
+    chrono::NaiveDate => Date, "Date";
+    chrono::NaiveDateTime => DateTime, "DateTime";
+    uuid::Uuid => UUID, "UUID";
+    network::IPv4 => IPv4, "IPv4";
+    network::IPv6 => IPv6, "IPv6";
+}
+
+impl<T> ColumnType for Option<T>
+where
+    T: ColumnType + Into<Value>,
+{
+    fn type_name() -> String {
+        format!("Nullable({})", T::type_name())
+    }
+
+    fn new_column(capacity: usize) -> ColumnData {
+        ColumnData::Nullable(Vec::with_capacity(capacity))
+    }
+
+    fn push_to(self, column: &mut ColumnData) {
+        match column {
+            ColumnData::Nullable(values) => values.push(self.map(Into::into)),
+            _ => column_mismatch("Nullable"),
+        }
+    }
+}
+
+/// A tuple whose elements become the columns of a block
+///
+/// Implemented for tuples of up to 16 [`ColumnType`] elements.
+pub trait RowTuple {
+    /// Number of columns
+    const WIDTH: usize;
+
+    /// ClickHouse type names of the columns
+    fn type_names() -> Vec<String>;
+
+    /// Create empty column data for every element
+    fn new_columns(capacity: usize) -> Vec<ColumnData>;
+
+    /// Append each element to its column
+    fn push_to(self, columns: &mut [ColumnData]);
+}
+
+macro_rules! impl_row_tuple {
+    ($width:expr; $($element:ident $index:tt),+) => {
+        impl<$($element: ColumnType),+> RowTuple for ($($element,)+) {
+            const WIDTH: usize = $width;
+
+            fn type_names() -> Vec<String> {
+                vec![$($element::type_name()),+]
+            }
+
+            fn new_columns(capacity: usize) -> Vec<ColumnData> {
+                vec![$($element::new_column(capacity)),+]
+            }
+
+            fn push_to(self, columns: &mut [ColumnData]) {
+                $(self.$index.push_to(&mut columns[$index]);)+
+            }
+        }
+    };
+}
+
+impl_row_tuple!(1; A 0);
+impl_row_tuple!(2; A 0, B 1);
+impl_row_tuple!(3; A 0, B 1, C 2);
+impl_row_tuple!(4; A 0, B 1, C 2, D 3);
+impl_row_tuple!(5; A 0, B 1, C 2, D 3, E 4);
+impl_row_tuple!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+impl_row_tuple!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_row_tuple!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_row_tuple!(9; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_row_tuple!(10; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_row_tuple!(11; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_row_tuple!(12; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+impl_row_tuple!(13; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12);
+impl_row_tuple!(14; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13);
+impl_row_tuple!(15; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14);
+impl_row_tuple!(16; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14, P 15);
+
+impl Block {
+    /// Build a block from an iterator of tuples, one tuple per row
+    ///
+    /// Column types are inferred from the tuple element types, and values are
+    /// appended straight to typed column data without going through [`Value`].
+    pub fn from_rows<T, I>(names: &[&str], rows: I) -> Result<Block, String>
+    where
+        T: RowTuple,
+        I: IntoIterator<Item = T>,
+    {
+        if names.len() != T::WIDTH {
+            return Err(format!("Expected {} column names, got {}", T::WIDTH, names.len()));
+        }
+
+        let rows = rows.into_iter();
+        let mut columns = T::new_columns(rows.size_hint().0);
+        for row in rows {
+            row.push_to(&mut columns);
+        }
+
+        let columns = names
+            .iter()
+            .zip(T::type_names())
+            .zip(columns)
+            .map(|((name, type_name), data)| Column::new(*name, type_name, data))
+            .collect();
+        Ok(Block::with_columns(columns))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_from_rows() {
+        let rows = (1..=3u64).map(|id| (id, format!("user{}", id), (id % 2 == 0).then_some(id as f64), id > 1));
+        let block = Block::from_rows(&["id", "name", "score", "active"], rows).unwrap();
+
+        assert_eq!(block.row_count(), 3);
+        let types: Vec<_> = block.columns().map(|c| c.type_name()).collect();
+        assert_eq!(types, ["UInt64", "String", "Nullable(Float64)", "Bool"]);
+        assert!(matches!(&block.get_column("id").unwrap().data, ColumnData::UInt64(v) if v == &[1, 2, 3]));
+        assert_eq!(block.get_column("name").unwrap().get_value(2), Some(Value::String("user3".to_string())));
+        assert_eq!(block.get_column("score").unwrap().get_value(0), Some(Value::Nullable(None)));
+        assert_eq!(block.get_column("active").unwrap().get_value(1), Some(Value::UInt8(1)));
+
+        let err = Block::from_rows(&["id"], vec![(1u8, "a")]).unwrap_err();
+        assert_eq!(err, "Expected 2 column names, got 1");
+    }
+}
@@ -0,0 +1,279 @@
+//! Sorting and deduplication of blocks
+//!
+//! Both operations compare key columns in place and build a row permutation,
+//! which is then applied to every column once, so rows are never materialized.
+
+use super::{Block, ColumnData, Value};
+use std::cell::Cell;
+use std::cmp::Ordering;
+
+impl Block {
+    /// Sort rows by key columns in ascending order, NULLs last
+    ///
+    /// The sort is stable, so rows with equal keys keep their relative order.
+    pub fn sort_by(&mut self, keys: &[&str]) -> Result<(), String> {
+        let permutation = self.sorted_permutation(keys)?;
+        *self = self.take(&permutation);
+        Ok(())
+    }
+
+    /// Keep only the last row of each distinct key, in the original row order
+    ///
+    /// This matches what a `ReplacingMergeTree` without a version column keeps
+    /// after a merge.
+    pub fn dedup_by(&mut self, keys: &[&str]) -> Result<(), String> {
+        let permutation = self.sorted_permutation(keys)?;
+        let columns = self.key_columns(keys)?;
+        let mut kept: Vec<usize> = Vec::with_capacity(permutation.len());
+        for (position, &row) in permutation.iter().enumerate() {
+            let next = permutation.get(position + 1);
+            let last_of_key = next.is_none_or(|&next| compare_rows(&columns, row, next) != Some(Ordering::Equal));
+            if last_of_key {
+                kept.push(row);
+            }
+        }
+        kept.sort_unstable();
+        *self = self.take(&kept);
+        Ok(())
+    }
+
+    /// Build a block from the rows at `indices`, in that order
+    pub fn take(&self, indices: &[usize]) -> Block {
+        let mut block = Block::with_columns(self.columns.iter().map(|column| column.take(indices)).collect());
+        block.row_count = indices.len();
+        block.info = self.info.clone();
+        block
+    }
+
+    fn key_columns(&self, keys: &[&str]) -> Result<Vec<&ColumnData>, String> {
+        keys.iter()
+            .map(|key| {
+                self.get_column(key)
+                    .map(|column| &column.data)
+                    .ok_or_else(|| format!("Column '{}' not found", key))
+            })
+            .collect()
+    }
+
+    fn sorted_permutation(&self, keys: &[&str]) -> Result<Vec<usize>, String> {
+        let columns = self.key_columns(keys)?;
+        let incomparable = Cell::new(None);
+        let mut permutation: Vec<usize> = (0..self.row_count).collect();
+        permutation.sort_by(|&a, &b| {
+            compare_rows(&columns, a, b).unwrap_or_else(|| {
+                incomparable.set(Some((a, b)));
+                Ordering::Equal
+            })
+        });
+        if let Some((a, b)) = incomparable.get() {
+            return Err(format!("Rows {} and {} cannot be compared on keys {}", a, b, keys.join(", ")));
+        }
+        Ok(permutation)
+    }
+}
+
+impl super::Column {
+    /// Build a column from the values at `indices`, in that order
+    pub fn take(&self, indices: &[usize]) -> super::Column {
+        super::Column::new(self.name.clone(), self.type_name.clone(), self.data.take(indices))
+    }
+}
+
+fn compare_rows(columns: &[&ColumnData], a: usize, b: usize) -> Option<Ordering> {
+    for column in columns {
+        match column.compare(a, b)? {
+            Ordering::Equal => continue,
+            ordering => return Some(ordering),
+        }
+    }
+    Some(Ordering::Equal)
+}
+
+impl ColumnData {
+    /// Compare the values at two rows
+    ///
+    /// Returns `None` for types without a natural order, such as arrays and maps.
+    pub fn compare(&self, a: usize, b: usize) -> Option<Ordering> {
+        Some(match self {
+            ColumnData::UInt8(v) => v[a].cmp(&v[b]),
+            ColumnData::UInt16(v) => v[a].cmp(&v[b]),
+            ColumnData::UInt32(v) => v[a].cmp(&v[b]),
+            ColumnData::UInt64(v) => v[a].cmp(&v[b]),
+            ColumnData::UInt128(v) => v[a].cmp(&v[b]),
+            ColumnData::UInt256(v) => v[a].cmp(&v[b]),
+            ColumnData::Int8(v) => v[a].cmp(&v[b]),
+            ColumnData::Int16(v) => v[a].cmp(&v[b]),
+            ColumnData::Int32(v) => v[a].cmp(&v[b]),
+            ColumnData::Int64(v) => v[a].cmp(&v[b]),
+            ColumnData::Int128(v) => v[a].cmp(&v[b]),
+            ColumnData::Int256(v) => v[a].cmp(&v[b]),
+            ColumnData::Float32(v) => v[a].total_cmp(&v[b]),
+            ColumnData::Float64(v) => v[a].total_cmp(&v[b]),
+            ColumnData::String(v) => v[a].cmp(&v[b]),
+            ColumnData::FixedString(v) => v[a].as_bytes().cmp(v[b].as_bytes()),
+            ColumnData::LowCardinality(v) => v.get(a).cmp(&v.get(b)),
+            ColumnData::Date(v) => v[a].cmp(&v[b]),
+            ColumnData::DateTime(v) => v[a].cmp(&v[b]),
+            ColumnData::DateTime64(v) => v[a].cmp(&v[b]),
+            ColumnData::UUID(v) => v[a].cmp(&v[b]),
+            ColumnData::IPv4(v) => v[a].0.cmp(&v[b].0),
+            ColumnData::IPv6(v) => v[a].0.cmp(&v[b].0),
+            ColumnData::Decimal32(v) => v[a].value().cmp(&v[b].value()),
+            ColumnData::Decimal64(v) => v[a].value().cmp(&v[b].value()),
+            ColumnData::Decimal128(v) => v[a].value().cmp(&v[b].value()),
+            ColumnData::Enum8(v) => v[a].value().cmp(&v[b].value()),
+            ColumnData::Enum16(v) => v[a].value().cmp(&v[b].value()),
+            ColumnData::Nullable(v) => match (&v[a], &v[b]) {
+                (Some(x), Some(y)) => compare_values(x, y)?,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+            ColumnData::Array(_) | ColumnData::Tuple(_) | ColumnData::Map(_) | ColumnData::Unsupported { .. } => {
+                return None
+            }
+        })
+    }
+
+    /// Build column data from the values at `indices`, in that order
+    pub fn take(&self, indices: &[usize]) -> ColumnData {
+        macro_rules! take {
+            ($variant:ident, $v:expr) => {
+                ColumnData::$variant(indices.iter().map(|&i| $v[i].clone()).collect())
+            };
+        }
+        match self {
+            ColumnData::UInt8(v) => take!(UInt8, v),
+            ColumnData::UInt16(v) => take!(UInt16, v),
+            ColumnData::UInt32(v) => take!(UInt32, v),
+            ColumnData::UInt64(v) => take!(UInt64, v),
+            ColumnData::UInt128(v) => take!(UInt128, v),
+            ColumnData::UInt256(v) => take!(UInt256, v),
+            ColumnData::Int8(v) => take!(Int8, v),
+            ColumnData::Int16(v) => take!(Int16, v),
+            ColumnData::Int32(v) => take!(Int32, v),
+            ColumnData::Int64(v) => take!(Int64, v),
+            ColumnData::Int128(v) => take!(Int128, v),
+            ColumnData::Int256(v) => take!(Int256, v),
+            ColumnData::Float32(v) => take!(Float32, v),
+            ColumnData::Float64(v) => take!(Float64, v),
+            ColumnData::String(v) => take!(String, v),
+            ColumnData::FixedString(v) => take!(FixedString, v),
+            ColumnData::LowCardinality(v) => ColumnData::LowCardinality(super::LowCardinality::from_vec(
+                indices.iter().map(|&i| v.get(i).cloned().unwrap_or_default()).collect(),
+            )),
+            ColumnData::Date(v) => take!(Date, v),
+            ColumnData::DateTime(v) => take!(DateTime, v),
+            ColumnData::DateTime64(v) => take!(DateTime64, v),
+            ColumnData::UUID(v) => take!(UUID, v),
+            ColumnData::IPv4(v) => take!(IPv4, v),
+            ColumnData::IPv6(v) => take!(IPv6, v),
+            ColumnData::Decimal32(v) => take!(Decimal32, v),
+            ColumnData::Decimal64(v) => take!(Decimal64, v),
+            ColumnData::Decimal128(v) => take!(Decimal128, v),
+            ColumnData::Enum8(v) => take!(Enum8, v),
+            ColumnData::Enum16(v) => take!(Enum16, v),
+            ColumnData::Array(v) => take!(Array, v),
+            ColumnData::Nullable(v) => take!(Nullable, v),
+            ColumnData::Tuple(v) => take!(Tuple, v),
+            ColumnData::Map(v) => take!(Map, v),
+            ColumnData::Unsupported { type_name, values } => ColumnData::Unsupported {
+                type_name: type_name.clone(),
+                values: indices.iter().map(|&i| values[i].clone()).collect(),
+            },
+        }
+    }
+}
+
+/// Compare two scalar values of the same type
+pub(super) fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    Some(match (a, b) {
+        (Value::UInt8(x), Value::UInt8(y)) => x.cmp(y),
+        (Value::UInt16(x), Value::UInt16(y)) => x.cmp(y),
+        (Value::UInt32(x), Value::UInt32(y)) => x.cmp(y),
+        (Value::UInt64(x), Value::UInt64(y)) => x.cmp(y),
+        (Value::UInt128(x), Value::UInt128(y)) => x.cmp(y),
+        (Value::UInt256(x), Value::UInt256(y)) => x.cmp(y),
+        (Value::Int8(x), Value::Int8(y)) => x.cmp(y),
+        (Value::Int16(x), Value::Int16(y)) => x.cmp(y),
+        (Value::Int32(x), Value::Int32(y)) => x.cmp(y),
+        (Value::Int64(x), Value::Int64(y)) => x.cmp(y),
+        (Value::Int128(x), Value::Int128(y)) => x.cmp(y),
+        (Value::Int256(x), Value::Int256(y)) => x.cmp(y),
+        (Value::Float32(x), Value::Float32(y)) => x.total_cmp(y),
+        (Value::Float64(x), Value::Float64(y)) => x.total_cmp(y),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::FixedString(x), Value::FixedString(y)) => x.as_bytes().cmp(y.as_bytes()),
+        (Value::Date(x), Value::Date(y)) => x.cmp(y),
+        (Value::DateTime(x), Value::DateTime(y)) | (Value::DateTime64(x), Value::DateTime64(y)) => x.cmp(y),
+        (Value::UUID(x), Value::UUID(y)) => x.cmp(y),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Column;
+
+    fn events() -> Block {
+        let mut block = Block::new();
+        block.add_column("id", Column::new("id", "UInt64", ColumnData::UInt64(vec![3, 1, 3, 2, 1])));
+        block.add_column(
+            "name",
+            Column::new(
+                "name",
+                "String",
+                ColumnData::String(["c1", "a1", "c2", "b1", "a2"].iter().map(|s| s.to_string()).collect()),
+            ),
+        );
+        block
+    }
+
+    fn names(block: &Block) -> Vec<String> {
+        (0..block.row_count)
+            .map(|i| block.get_column("name").unwrap().data.get_value(i).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let mut block = events();
+        block.sort_by(&["id"]).unwrap();
+        assert_eq!(names(&block), ["a1", "a2", "b1", "c1", "c2"]);
+
+        let mut block = events();
+        block.sort_by(&["id", "name"]).unwrap();
+        assert_eq!(block.get_column("id").unwrap().data.get_value(4), Some(Value::UInt64(3)));
+        assert!(block.sort_by(&["missing"]).is_err());
+    }
+
+    #[test]
+    fn test_dedup_by() {
+        let mut block = events();
+        block.dedup_by(&["id"]).unwrap();
+        assert_eq!(block.row_count, 3);
+        assert_eq!(names(&block), ["c2", "b1", "a2"]);
+    }
+
+    #[test]
+    fn test_nullable_and_unsortable_keys() {
+        let mut block = Block::new();
+        block.add_column(
+            "score",
+            Column::new(
+                "score",
+                "Nullable(Int32)",
+                ColumnData::Nullable(vec![None, Some(Value::Int32(5)), Some(Value::Int32(-1))]),
+            ),
+        );
+        block.add_column("tags", Column::new("tags", "Array(String)", ColumnData::Array(vec![Vec::new(); 3])));
+        block.sort_by(&["score"]).unwrap();
+        assert_eq!(
+            block.get_column("score").unwrap().data.get_value(0),
+            Some(Value::Nullable(Some(Box::new(Value::Int32(-1)))))
+        );
+        assert_eq!(block.get_column("score").unwrap().data.get_value(2), Some(Value::Nullable(None)));
+        assert!(block.sort_by(&["tags"]).is_err());
+    }
+}
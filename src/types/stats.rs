@@ -0,0 +1,323 @@
+//! Client-side column statistics
+//!
+//! [`Column::stats`] scans a column once for its null count, minimum, maximum
+//! and an estimate of its distinct values, e.g. to sanity check a block before
+//! inserting it or to pick a codec. [`Block::column_stats`] caches the result
+//! per column until the block is changed through its methods; code editing
+//! `columns` directly should call [`Block::invalidate_stats`].
+
+use super::sort::compare_values;
+use super::{Block, Column, Value};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// Registers of [`HyperLogLog`] are addressed by this many hash bits
+pub const HLL_PRECISION: u32 = 12;
+
+/// Statistics of the values of a column
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    /// Values in the column
+    pub rows: usize,
+    /// NULL values
+    pub null_count: usize,
+    /// Smallest non-NULL value, if the type is ordered
+    pub min: Option<Value>,
+    /// Largest non-NULL value, if the type is ordered
+    pub max: Option<Value>,
+    /// Estimated distinct non-NULL values
+    pub distinct_estimate: u64,
+}
+
+impl ColumnStats {
+    /// Get the share of NULL values, from 0.0 to 1.0
+    pub fn null_fraction(&self) -> f64 {
+        if self.rows == 0 {
+            0.0
+        } else {
+            self.null_count as f64 / self.rows as f64
+        }
+    }
+
+    /// Check whether every value is NULL
+    pub fn all_null(&self) -> bool {
+        self.null_count == self.rows
+    }
+
+    /// Get the estimated distinct values per non-NULL value, from 0.0 to 1.0
+    ///
+    /// Low ratios suit `LowCardinality` and dictionary-friendly codecs.
+    pub fn cardinality_ratio(&self) -> f64 {
+        let values = self.rows - self.null_count;
+        if values == 0 {
+            0.0
+        } else {
+            (self.distinct_estimate as f64 / values as f64).min(1.0)
+        }
+    }
+}
+
+/// HyperLogLog sketch estimating the number of distinct values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    /// Create an empty sketch with [`HLL_PRECISION`] bits of precision
+    pub fn new() -> Self {
+        Self { registers: vec![0; 1 << HLL_PRECISION] }
+    }
+
+    /// Add a value
+    pub fn insert(&mut self, value: &Value) {
+        let mut hasher = DefaultHasher::new();
+        hash_value(value, &mut hasher);
+        self.insert_hash(hasher.finish());
+    }
+
+    /// Add a value by its 64-bit hash
+    pub fn insert_hash(&mut self, hash: u64) {
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        // The sentinel bit bounds the rank when the remaining bits are all zero
+        let rest = (hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// Add the values of another sketch
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    /// Estimate the number of distinct values added
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hash_value(value: &Value, hasher: &mut impl Hasher) {
+    std::mem::discriminant(value).hash(hasher);
+    match value {
+        Value::UInt8(v) => v.hash(hasher),
+        Value::UInt16(v) => v.hash(hasher),
+        Value::UInt32(v) => v.hash(hasher),
+        Value::UInt64(v) => v.hash(hasher),
+        Value::UInt128(v) => v.hash(hasher),
+        Value::Int8(v) => v.hash(hasher),
+        Value::Int16(v) => v.hash(hasher),
+        Value::Int32(v) => v.hash(hasher),
+        Value::Int64(v) => v.hash(hasher),
+        Value::Int128(v) => v.hash(hasher),
+        Value::Float32(v) => v.to_bits().hash(hasher),
+        Value::Float64(v) => v.to_bits().hash(hasher),
+        Value::String(v) => v.hash(hasher),
+        Value::FixedString(v) => v.as_bytes().hash(hasher),
+        Value::Date(v) => v.hash(hasher),
+        Value::DateTime(v) | Value::DateTime64(v) => v.hash(hasher),
+        Value::UUID(v) => v.hash(hasher),
+        Value::Nullable(Some(inner)) => hash_value(inner, hasher),
+        Value::Array(values) | Value::Tuple(values) => values.iter().for_each(|v| hash_value(v, hasher)),
+        other => format!("{:?}", other).hash(hasher),
+    }
+}
+
+impl Column {
+    /// Compute the null count, minimum, maximum and distinct estimate of the values
+    pub fn stats(&self) -> ColumnStats {
+        let mut stats = ColumnStats {
+            rows: self.len(),
+            null_count: 0,
+            min: None,
+            max: None,
+            distinct_estimate: 0,
+        };
+        let mut sketch = HyperLogLog::new();
+        let mut ordered = true;
+        for index in 0..self.len() {
+            let value = match self.get_value(index) {
+                Some(Value::Nullable(None)) | None => {
+                    stats.null_count += 1;
+                    continue;
+                }
+                Some(Value::Nullable(Some(inner))) => *inner,
+                Some(value) => value,
+            };
+            sketch.insert(&value);
+            if !ordered {
+                continue;
+            }
+            let Some(min) = &stats.min else {
+                stats.min = Some(value.clone());
+                stats.max = Some(value);
+                continue;
+            };
+            match compare_values(&value, min) {
+                Some(Ordering::Less) => stats.min = Some(value),
+                Some(_) => {
+                    if stats.max.as_ref().and_then(|max| compare_values(&value, max)) == Some(Ordering::Greater) {
+                        stats.max = Some(value);
+                    }
+                }
+                None => ordered = false,
+            }
+        }
+        if !ordered {
+            stats.min = None;
+            stats.max = None;
+        }
+        if stats.null_count < stats.rows {
+            stats.distinct_estimate = sketch.estimate().clamp(1, (stats.rows - stats.null_count) as u64);
+        }
+        stats
+    }
+}
+
+/// Column statistics computed for a block, by column name
+///
+/// Clones of a block start with a copy of the cached statistics.
+#[derive(Default)]
+pub(crate) struct StatsCache {
+    columns: Mutex<HashMap<String, Arc<ColumnStats>>>,
+}
+
+impl StatsCache {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<ColumnStats>>> {
+        self.columns.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+impl Clone for StatsCache {
+    fn clone(&self) -> Self {
+        Self { columns: Mutex::new(self.lock().clone()) }
+    }
+}
+
+impl fmt::Debug for StatsCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatsCache").field("columns", &self.lock().len()).finish()
+    }
+}
+
+impl Block {
+    /// Get the statistics of a column, computing them on first use
+    pub fn column_stats(&self, name: &str) -> Option<Arc<ColumnStats>> {
+        let column = self.get_column(name)?;
+        let mut cache = self.stats_cache.lock();
+        match cache.get(name) {
+            // A column grown or shrunk through `columns` is recomputed
+            Some(stats) if stats.rows == column.len() => Some(stats.clone()),
+            _ => {
+                let stats = Arc::new(column.stats());
+                cache.insert(name.to_string(), stats.clone());
+                Some(stats)
+            }
+        }
+    }
+
+    /// Get the statistics of every column, in column order
+    pub fn stats(&self) -> Vec<(String, Arc<ColumnStats>)> {
+        self.columns
+            .iter()
+            .filter_map(|column| Some((column.name.clone(), self.column_stats(&column.name)?)))
+            .collect()
+    }
+
+    /// Drop the cached statistics, e.g. after editing `columns` directly
+    pub fn invalidate_stats(&self) {
+        self.stats_cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ColumnData;
+
+    #[test]
+    fn test_column_stats() {
+        let ids = Column::new("id", "UInt32", ColumnData::UInt32(vec![5, 3, 9, 3, 1]));
+        let stats = ids.stats();
+        assert_eq!((stats.rows, stats.null_count), (5, 0));
+        assert_eq!((stats.min, stats.max), (Some(Value::UInt32(1)), Some(Value::UInt32(9))));
+        assert_eq!(stats.distinct_estimate, 4);
+
+        let names = Column::new(
+            "name",
+            "Nullable(String)",
+            ColumnData::Nullable(vec![None, Some(Value::String("b".into())), Some(Value::String("a".into())), None]),
+        );
+        let stats = names.stats();
+        assert_eq!(stats.null_count, 2);
+        assert_eq!(stats.null_fraction(), 0.5);
+        assert_eq!(stats.min, Some(Value::String("a".into())));
+        assert_eq!(stats.cardinality_ratio(), 1.0);
+
+        let empty = Column::new("x", "Nullable(UInt8)", ColumnData::Nullable(vec![None])).stats();
+        assert!(empty.all_null());
+        assert_eq!((empty.min, empty.distinct_estimate), (None, 0));
+
+        let arrays = Column::new("tags", "Array(UInt8)", ColumnData::Array(vec![vec![], vec![Value::UInt8(1)]]));
+        assert_eq!((arrays.stats().min, arrays.stats().distinct_estimate), (None, 2));
+    }
+
+    #[test]
+    fn test_hyperloglog_estimate() {
+        let mut sketch = HyperLogLog::new();
+        let mut other = HyperLogLog::new();
+        for i in 0..100_000u64 {
+            sketch.insert(&Value::UInt64(i));
+            other.insert(&Value::UInt64(i + 50_000));
+        }
+        let error = |estimate: u64, actual: f64| (estimate as f64 - actual).abs() / actual;
+        assert!(error(sketch.estimate(), 100_000.0) < 0.05, "{}", sketch.estimate());
+        sketch.merge(&other);
+        assert!(error(sketch.estimate(), 150_000.0) < 0.05, "{}", sketch.estimate());
+        assert_eq!(HyperLogLog::default().estimate(), 0);
+    }
+
+    #[test]
+    fn test_block_stats_cache() {
+        let mut block = Block::with_columns(vec![Column::new("id", "UInt64", ColumnData::UInt64(vec![2, 7]))]);
+        let first = block.column_stats("id").unwrap();
+        assert!(Arc::ptr_eq(&first, &block.column_stats("id").unwrap()));
+        assert!(Arc::ptr_eq(&first, &block.clone().column_stats("id").unwrap()));
+        assert!(block.column_stats("missing").is_none());
+
+        block.get_column_mut("id").unwrap().set_value(0, Value::UInt64(9)).unwrap();
+        assert_eq!(block.column_stats("id").unwrap().max, Some(Value::UInt64(9)));
+
+        block.columns[0].push(Value::UInt64(20)).unwrap();
+        assert_eq!(block.column_stats("id").unwrap().max, Some(Value::UInt64(20)));
+        block.columns[0].set_value(2, Value::UInt64(1)).unwrap();
+        block.invalidate_stats();
+        assert_eq!(block.stats()[0].1.min, Some(Value::UInt64(1)));
+    }
+}
@@ -0,0 +1,615 @@
+//! 256-bit integer types for ClickHouse UInt256 and Int256 columns
+//!
+//! Values are stored as four little-endian 64-bit limbs, matching the wire
+//! layout of the native protocol. Signed values use two's complement.
+//! Arithmetic, parsing and formatting are done by [`ethnum`]; convert with
+//! `From` to use its `U256` and `I256` directly.
+
+use super::Value;
+use ethnum::{I256, U256};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::str::FromStr;
+
+/// UInt256 type (0 to 2^256 - 1)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct UInt256(pub [u64; 4]);
+
+/// Int256 type (-2^255 to 2^255 - 1)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Int256(pub [u64; 4]);
+
+impl UInt256 {
+    /// Zero
+    pub const ZERO: Self = UInt256([0; 4]);
+    /// One
+    pub const ONE: Self = UInt256([1, 0, 0, 0]);
+    /// Largest value
+    pub const MAX: Self = UInt256([u64::MAX; 4]);
+
+    /// Create from little-endian bytes
+    pub fn from_le_bytes(bytes: [u8; 32]) -> Self {
+        UInt256::from(U256::from_le_bytes(bytes))
+    }
+
+    /// Convert to little-endian bytes
+    pub fn to_le_bytes(self) -> [u8; 32] {
+        U256::from(self).to_le_bytes()
+    }
+
+    /// Check if the value is zero
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    /// Add, returning `None` on overflow
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        U256::from(self).checked_add(rhs.into()).map(Self::from)
+    }
+
+    /// Subtract, returning `None` on underflow
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        U256::from(self).checked_sub(rhs.into()).map(Self::from)
+    }
+
+    /// Multiply, returning `None` on overflow
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        U256::from(self).checked_mul(rhs.into()).map(Self::from)
+    }
+
+    /// Divide, returning `None` when dividing by zero
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        U256::from(self).checked_div(rhs.into()).map(Self::from)
+    }
+
+    /// Get the remainder, returning `None` when dividing by zero
+    pub fn checked_rem(self, rhs: Self) -> Option<Self> {
+        U256::from(self).checked_rem(rhs.into()).map(Self::from)
+    }
+
+    /// Parse a string in the given radix (2 to 36)
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<Self, String> {
+        if !(2..=36).contains(&radix) {
+            return Err(format!("Invalid radix {}", radix));
+        }
+        if s.is_empty() || s.starts_with(['+', '-']) {
+            return Err(format!("Cannot parse UInt256 from {:?}", s));
+        }
+        U256::from_str_radix(s, radix)
+            .map(Self::from)
+            .map_err(|e| format!("Cannot parse UInt256 from {:?}: {}", s, e))
+    }
+
+    /// Convert to the nearest `f64`
+    pub fn to_f64(self) -> f64 {
+        U256::from(self).as_f64()
+    }
+}
+
+impl Int256 {
+    /// Zero
+    pub const ZERO: Self = Int256([0; 4]);
+    /// One
+    pub const ONE: Self = Int256([1, 0, 0, 0]);
+    /// Smallest value
+    pub const MIN: Self = Int256([0, 0, 0, 1 << 63]);
+    /// Largest value
+    pub const MAX: Self = Int256([u64::MAX, u64::MAX, u64::MAX, u64::MAX >> 1]);
+
+    /// Create from little-endian two's complement bytes
+    pub fn from_le_bytes(bytes: [u8; 32]) -> Self {
+        Int256::from(I256::from_le_bytes(bytes))
+    }
+
+    /// Convert to little-endian two's complement bytes
+    pub fn to_le_bytes(self) -> [u8; 32] {
+        I256::from(self).to_le_bytes()
+    }
+
+    /// Check if the value is zero
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    /// Check if the value is below zero
+    pub fn is_negative(&self) -> bool {
+        self.0[3] >> 63 == 1
+    }
+
+    /// Get the absolute value as an unsigned integer
+    pub fn unsigned_abs(self) -> UInt256 {
+        UInt256::from(I256::from(self).unsigned_abs())
+    }
+
+    /// Negate, returning `None` for `MIN`
+    pub fn checked_neg(self) -> Option<Self> {
+        I256::from(self).checked_neg().map(Self::from)
+    }
+
+    /// Add, returning `None` on overflow
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        I256::from(self).checked_add(rhs.into()).map(Self::from)
+    }
+
+    /// Subtract, returning `None` on overflow
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        I256::from(self).checked_sub(rhs.into()).map(Self::from)
+    }
+
+    /// Multiply, returning `None` on overflow
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        I256::from(self).checked_mul(rhs.into()).map(Self::from)
+    }
+
+    /// Divide rounding toward zero, returning `None` on division by zero or overflow
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        I256::from(self).checked_div(rhs.into()).map(Self::from)
+    }
+
+    /// Get the remainder with the sign of `self`, returning `None` on division by zero or overflow
+    pub fn checked_rem(self, rhs: Self) -> Option<Self> {
+        I256::from(self).checked_rem(rhs.into()).map(Self::from)
+    }
+
+    /// Parse a string in the given radix (2 to 36) with an optional sign
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<Self, String> {
+        let (negative, digits) = split_sign(s);
+        let magnitude = UInt256::from_str_radix(digits, radix)?;
+        Self::from_sign_magnitude(negative, magnitude).ok_or_else(|| format!("Value out of range for Int256: {}", s))
+    }
+
+    /// Convert to the nearest `f64`
+    pub fn to_f64(self) -> f64 {
+        I256::from(self).as_f64()
+    }
+
+    fn from_sign_magnitude(negative: bool, magnitude: UInt256) -> Option<Self> {
+        let magnitude = U256::from(magnitude);
+        if !negative {
+            I256::try_from(magnitude).ok().map(Self::from)
+        } else if magnitude <= I256::MIN.unsigned_abs() {
+            Some(Self::from(magnitude.as_i256().wrapping_neg()))
+        } else {
+            None
+        }
+    }
+}
+
+impl From<U256> for UInt256 {
+    fn from(value: U256) -> Self {
+        let (high, low) = value.into_words();
+        UInt256([low as u64, (low >> 64) as u64, high as u64, (high >> 64) as u64])
+    }
+}
+
+impl From<UInt256> for U256 {
+    fn from(value: UInt256) -> Self {
+        let [a, b, c, d] = value.0;
+        U256::from_words(c as u128 | (d as u128) << 64, a as u128 | (b as u128) << 64)
+    }
+}
+
+impl From<I256> for Int256 {
+    fn from(value: I256) -> Self {
+        Int256(UInt256::from(value.as_u256()).0)
+    }
+}
+
+impl From<Int256> for I256 {
+    fn from(value: Int256) -> Self {
+        U256::from(UInt256(value.0)).as_i256()
+    }
+}
+
+impl Ord for UInt256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        U256::from(*self).cmp(&U256::from(*other))
+    }
+}
+
+impl PartialOrd for UInt256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Int256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        I256::from(*self).cmp(&I256::from(*other))
+    }
+}
+
+impl PartialOrd for Int256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for UInt256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&U256::from(*self), f)
+    }
+}
+
+impl fmt::LowerHex for UInt256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&U256::from(*self), f)
+    }
+}
+
+impl fmt::Display for Int256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&I256::from(*self), f)
+    }
+}
+
+impl FromStr for UInt256 {
+    type Err = String;
+
+    /// Parse a decimal or `0x`-prefixed hexadecimal string
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => UInt256::from_str_radix(hex, 16),
+            None => UInt256::from_str_radix(s.strip_prefix('+').unwrap_or(s), 10),
+        }
+    }
+}
+
+impl FromStr for Int256 {
+    type Err = String;
+
+    /// Parse a signed decimal or `0x`-prefixed hexadecimal string
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = split_sign(s.trim());
+        let magnitude: UInt256 = digits.parse()?;
+        Int256::from_sign_magnitude(negative, magnitude).ok_or_else(|| format!("Value out of range for Int256: {}", s))
+    }
+}
+
+/// Serialized as a decimal string, since JSON numbers lose precision past 2^53
+impl Serialize for UInt256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Serialized as a decimal string, since JSON numbers lose precision past 2^53
+impl Serialize for Int256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts decimal or `0x` strings and integer numbers
+struct WideVisitor<T>(std::marker::PhantomData<T>);
+
+impl<T> Visitor<'_> for WideVisitor<T>
+where
+    T: FromStr<Err = String> + From<u64> + TryFrom<i64>,
+    <T as TryFrom<i64>>::Error: fmt::Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a 256-bit integer as a string or number")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        Ok(T::from(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        T::try_from(v).map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for UInt256 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(WideVisitor(std::marker::PhantomData))
+    }
+}
+
+impl<'de> Deserialize<'de> for Int256 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(WideVisitor(std::marker::PhantomData))
+    }
+}
+
+macro_rules! impl_arithmetic {
+    ($type:ident, $($trait:ident $method:ident $checked:ident $message:literal),*) => {
+        $(
+            impl $trait for $type {
+                type Output = $type;
+
+                fn $method(self, rhs: $type) -> $type {
+                    self.$checked(rhs).expect($message)
+                }
+            }
+        )*
+    };
+}
+
+impl_arithmetic!(
+    UInt256,
+    Add add checked_add "attempt to add with overflow",
+    Sub sub checked_sub "attempt to subtract with overflow",
+    Mul mul checked_mul "attempt to multiply with overflow",
+    Div div checked_div "attempt to divide by zero",
+    Rem rem checked_rem "attempt to calculate the remainder with a divisor of zero"
+);
+
+impl_arithmetic!(
+    Int256,
+    Add add checked_add "attempt to add with overflow",
+    Sub sub checked_sub "attempt to subtract with overflow",
+    Mul mul checked_mul "attempt to multiply with overflow",
+    Div div checked_div "attempt to divide by zero or with overflow",
+    Rem rem checked_rem "attempt to calculate the remainder with a divisor of zero or with overflow"
+);
+
+impl Neg for Int256 {
+    type Output = Int256;
+
+    fn neg(self) -> Int256 {
+        self.checked_neg().expect("attempt to negate with overflow")
+    }
+}
+
+macro_rules! impl_from_unsigned {
+    ($($primitive:ty),*) => {
+        $(
+            impl From<$primitive> for UInt256 {
+                fn from(value: $primitive) -> Self {
+                    UInt256::from(U256::from(value))
+                }
+            }
+
+            impl From<$primitive> for Int256 {
+                fn from(value: $primitive) -> Self {
+                    Int256::from(I256::from(value))
+                }
+            }
+
+            impl TryFrom<UInt256> for $primitive {
+                type Error = String;
+
+                fn try_from(value: UInt256) -> Result<Self, Self::Error> {
+                    <$primitive>::try_from(U256::from(value))
+                        .map_err(|_| format!("Value out of range for {}", stringify!($primitive)))
+                }
+            }
+
+            impl TryFrom<Int256> for $primitive {
+                type Error = String;
+
+                fn try_from(value: Int256) -> Result<Self, Self::Error> {
+                    if value.is_negative() {
+                        return Err(format!("Negative value cannot be converted to {}", stringify!($primitive)));
+                    }
+                    <$primitive>::try_from(UInt256(value.0))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_from_signed {
+    ($($primitive:ty),*) => {
+        $(
+            impl From<$primitive> for Int256 {
+                fn from(value: $primitive) -> Self {
+                    Int256::from(I256::from(value))
+                }
+            }
+
+            impl TryFrom<Int256> for $primitive {
+                type Error = String;
+
+                fn try_from(value: Int256) -> Result<Self, Self::Error> {
+                    <$primitive>::try_from(I256::from(value))
+                        .map_err(|_| format!("Value out of range for {}", stringify!($primitive)))
+                }
+            }
+
+            impl TryFrom<UInt256> for $primitive {
+                type Error = String;
+
+                fn try_from(value: UInt256) -> Result<Self, Self::Error> {
+                    <$primitive>::try_from(U256::from(value))
+                        .map_err(|_| format!("Value out of range for {}", stringify!($primitive)))
+                }
+            }
+
+            impl TryFrom<$primitive> for UInt256 {
+                type Error = String;
+
+                fn try_from(value: $primitive) -> Result<Self, Self::Error> {
+                    U256::try_from(value)
+                        .map(UInt256::from)
+                        .map_err(|_| format!("Negative value cannot be converted to UInt256: {}", value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_unsigned!(u8, u16, u32, u64, u128);
+impl_from_signed!(i8, i16, i32, i64, i128);
+
+impl TryFrom<Int256> for UInt256 {
+    type Error = String;
+
+    fn try_from(value: Int256) -> Result<Self, Self::Error> {
+        if value.is_negative() {
+            Err("Negative value cannot be converted to UInt256".to_string())
+        } else {
+            Ok(UInt256(value.0))
+        }
+    }
+}
+
+impl TryFrom<UInt256> for Int256 {
+    type Error = String;
+
+    fn try_from(value: UInt256) -> Result<Self, Self::Error> {
+        Int256::from_sign_magnitude(false, value).ok_or_else(|| "Value out of range for Int256".to_string())
+    }
+}
+
+impl TryFrom<Value> for UInt256 {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::UInt8(v) => Ok(v.into()),
+            Value::UInt16(v) => Ok(v.into()),
+            Value::UInt32(v) => Ok(v.into()),
+            Value::UInt64(v) => Ok(v.into()),
+            Value::UInt128(v) => Ok(v.into()),
+            Value::UInt256(v) => Ok(v),
+            Value::Int256(v) => UInt256::try_from(v),
+            Value::String(s) => s.parse(),
+            _ => Err(format!("Cannot convert {} to UInt256", value.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Value> for Int256 {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::UInt8(v) => Ok(v.into()),
+            Value::UInt16(v) => Ok(v.into()),
+            Value::UInt32(v) => Ok(v.into()),
+            Value::UInt64(v) => Ok(v.into()),
+            Value::UInt128(v) => Ok(v.into()),
+            Value::Int8(v) => Ok(v.into()),
+            Value::Int16(v) => Ok(v.into()),
+            Value::Int32(v) => Ok(v.into()),
+            Value::Int64(v) => Ok(v.into()),
+            Value::Int128(v) => Ok(v.into()),
+            Value::UInt256(v) => Int256::try_from(v),
+            Value::Int256(v) => Ok(v),
+            Value::String(s) => s.parse(),
+            _ => Err(format!("Cannot convert {} to Int256", value.type_name())),
+        }
+    }
+}
+
+fn split_sign(s: &str) -> (bool, &str) {
+    match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uint256_parse_and_display() {
+        let max: UInt256 = "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+            .parse()
+            .unwrap();
+        assert_eq!(max, UInt256::MAX);
+        assert_eq!(max.to_string().len(), 78);
+        assert_eq!(UInt256::ZERO.to_string(), "0");
+
+        let hex: UInt256 = "0xDE0B6B3A7640000".parse().unwrap();
+        assert_eq!(hex, UInt256::from(1_000_000_000_000_000_000u64));
+        assert_eq!(format!("{:x}", hex), "de0b6b3a7640000");
+        assert_eq!(format!("{:#x}", UInt256::ZERO), "0x0");
+
+        assert!("115792089237316195423570985008687907853269984665640564039457584007913129639936"
+            .parse::<UInt256>()
+            .is_err());
+        assert!("12a".parse::<UInt256>().is_err());
+        assert!("".parse::<UInt256>().is_err());
+    }
+
+    #[test]
+    fn test_uint256_arithmetic() {
+        let wei = UInt256::from(10u64).checked_mul(UInt256::from(u128::MAX)).unwrap();
+        assert_eq!(wei.to_string(), "3402823669209384634633746074317682114550");
+        assert_eq!(wei / UInt256::from(10u8), UInt256::from(u128::MAX));
+        assert_eq!(wei % UInt256::from(u128::MAX), UInt256::ZERO);
+        assert_eq!((wei + UInt256::ONE) % wei, UInt256::ONE);
+        assert_eq!(wei - wei, UInt256::ZERO);
+
+        assert_eq!(UInt256::MAX.checked_add(UInt256::ONE), None);
+        assert_eq!(UInt256::ZERO.checked_sub(UInt256::ONE), None);
+        assert_eq!(UInt256::MAX.checked_mul(UInt256::from(2u8)), None);
+        assert_eq!(UInt256::ONE.checked_div(UInt256::ZERO), None);
+        assert!(UInt256::MAX > wei);
+    }
+
+    #[test]
+    fn test_int256_arithmetic() {
+        let a: Int256 = "-57896044618658097711785492504343953926634992332820282019728792003956564819968"
+            .parse()
+            .unwrap();
+        assert_eq!(a, Int256::MIN);
+        assert_eq!(Int256::MIN.to_string(), "-57896044618658097711785492504343953926634992332820282019728792003956564819968");
+        assert_eq!(Int256::MIN.checked_neg(), None);
+        assert_eq!(Int256::MIN.checked_sub(Int256::ONE), None);
+        assert_eq!(Int256::MAX.checked_add(Int256::ONE), None);
+        assert_eq!(Int256::MIN.checked_div(Int256::from(-1i8)), None);
+
+        let x = Int256::from(-7i64);
+        let y = Int256::from(2i64);
+        assert_eq!(x / y, Int256::from(-3i64));
+        assert_eq!(x % y, Int256::from(-1i64));
+        assert_eq!(x * y, Int256::from(-14i64));
+        assert_eq!(-x, Int256::from(7i64));
+        assert!(x < y && Int256::MIN < x && Int256::MAX > y);
+        assert_eq!("+42".parse::<Int256>().unwrap(), Int256::from(42u8));
+    }
+
+    #[test]
+    fn test_primitive_conversions() {
+        assert_eq!(u64::try_from(UInt256::from(42u64)).unwrap(), 42);
+        assert!(u64::try_from(UInt256::from(u128::MAX)).is_err());
+        assert_eq!(u128::try_from(UInt256::from(u128::MAX)).unwrap(), u128::MAX);
+        assert_eq!(i128::try_from(Int256::from(i128::MIN)).unwrap(), i128::MIN);
+        assert_eq!(i64::try_from(Int256::from(-5i8)).unwrap(), -5);
+        assert!(u64::try_from(Int256::from(-5i8)).is_err());
+        assert!(i128::try_from(Int256::MAX).is_err());
+        assert_eq!(UInt256::from(1u64 << 53).to_f64(), 9007199254740992.0);
+        assert_eq!(Int256::from(-2i8).to_f64(), -2.0);
+
+        let bytes = Int256::from(-1i8).to_le_bytes();
+        assert_eq!(bytes, [0xff; 32]);
+        assert_eq!(Int256::from_le_bytes(bytes), Int256::from(-1i8));
+        assert_eq!(UInt256::try_from(Value::String("7".to_string())).unwrap(), UInt256::from(7u8));
+        assert_eq!(Value::UInt256(UInt256::from(7u8)).to_string(), "7");
+    }
+
+    #[test]
+    fn test_ethnum_and_serde() {
+        let value = UInt256::from(u128::MAX) * UInt256::from(3u8);
+        assert_eq!(UInt256::from(U256::from(value)), value);
+        assert_eq!(U256::from(value), U256::from(u128::MAX) * 3);
+        assert_eq!(I256::from(Int256::MIN), I256::MIN);
+        assert_eq!(Int256::from(I256::new(-5)), Int256::from(-5i8));
+
+        let json = serde_json::to_string(&(value, Int256::from(-42i8))).unwrap();
+        assert_eq!(json, r#"["1020847100762815390390123822295304634365","-42"]"#);
+        let decoded: (UInt256, Int256) = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, (value, Int256::from(-42i8)));
+        assert_eq!(serde_json::from_str::<Int256>("-7").unwrap(), Int256::from(-7i8));
+        assert_eq!(serde_json::from_str::<UInt256>(r#""0xff""#).unwrap(), UInt256::from(255u8));
+        assert!(serde_json::from_str::<UInt256>("-1").is_err());
+        assert!(UInt256::try_from(-1i64).is_err());
+        assert!("-0".parse::<UInt256>().is_err());
+    }
+}
@@ -0,0 +1,270 @@
+//! Conversion of values pushed into a column of another type
+//!
+//! Blocks built from several sources often get a `UInt8` for a `UInt64`
+//! column or a `Date` for a `DateTime` one. [`ColumnData::push`] and
+//! [`ColumnData::set_value`] widen such values when no information is lost,
+//! and [`ColumnData::push_with`] takes a [`ConversionPolicy`] to turn this off
+//! or to also narrow values that fit the smaller type.
+
+use super::coerce::{number, Number};
+use super::{ColumnData, Value};
+use chrono::{NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// Which values of another type a column accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConversionPolicy {
+    /// Only values of the column's type
+    Exact,
+    /// Also values of types the column's type holds without loss: smaller
+    /// integers, integers a float represents exactly, `Float32` into
+    /// `Float64`, `Date` into `DateTime`, and any value into `Nullable`
+    #[default]
+    Widen,
+    /// Also values of other numeric, date and Nullable types that this
+    /// particular value fits, e.g. `UInt64(200)` into `UInt8`
+    CheckedNarrow,
+}
+
+impl ConversionPolicy {
+    /// Convert a value to the type of a column
+    ///
+    /// Values the policy does not convert are returned as they are, so
+    /// pushing them fails as a type mismatch.
+    pub fn convert(self, value: Value, data: &ColumnData) -> Result<Value, String> {
+        let target = column_kind(data);
+        let source = value_kind(&value);
+        if self == ConversionPolicy::Exact || source == target || target == Kind::Other {
+            return Ok(value);
+        }
+        match (value, target) {
+            (Value::Nullable(None), _) => Err(format!("NULL does not fit a {} column", target.name())),
+            (Value::Nullable(Some(inner)), _) if self == ConversionPolicy::CheckedNarrow => self.convert(*inner, data),
+            (value, Kind::Nullable) => Ok(Value::Nullable(Some(Box::new(value)))),
+            (value, _) if self == ConversionPolicy::Widen && !source.widens_to(target) => Ok(value),
+            (value, _) if source == Kind::Other => Ok(value),
+            (value, _) => {
+                let described = value.to_string();
+                cast(value, target).ok_or_else(|| format!("{} does not fit {}", described, target.name()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int { signed: bool, bits: u32 },
+    /// Float with this many mantissa bits
+    Float(u32),
+    Date,
+    DateTime,
+    DateTime64,
+    Nullable,
+    Other,
+}
+
+impl Kind {
+    fn widens_to(self, target: Kind) -> bool {
+        match (self, target) {
+            (Kind::Int { signed: s, bits: b }, Kind::Int { signed: t, bits: c }) => {
+                if s == t {
+                    c >= b
+                } else {
+                    !s && t && c > b
+                }
+            }
+            (Kind::Int { signed, bits }, Kind::Float(mantissa)) => bits - signed as u32 <= mantissa,
+            (Kind::Float(a), Kind::Float(b)) => b >= a,
+            (Kind::Date, Kind::DateTime | Kind::DateTime64) | (Kind::DateTime, Kind::DateTime64) => true,
+            _ => false,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Int { signed: false, bits: 8 } => "UInt8",
+            Kind::Int { signed: false, bits: 16 } => "UInt16",
+            Kind::Int { signed: false, bits: 32 } => "UInt32",
+            Kind::Int { signed: false, bits: 64 } => "UInt64",
+            Kind::Int { signed: false, .. } => "UInt128",
+            Kind::Int { signed: true, bits: 8 } => "Int8",
+            Kind::Int { signed: true, bits: 16 } => "Int16",
+            Kind::Int { signed: true, bits: 32 } => "Int32",
+            Kind::Int { signed: true, bits: 64 } => "Int64",
+            Kind::Int { signed: true, .. } => "Int128",
+            Kind::Float(24) => "Float32",
+            Kind::Float(_) => "Float64",
+            Kind::Date => "Date",
+            Kind::DateTime => "DateTime",
+            Kind::DateTime64 => "DateTime64",
+            Kind::Nullable => "Nullable",
+            Kind::Other => "other",
+        }
+    }
+}
+
+fn column_kind(data: &ColumnData) -> Kind {
+    match data {
+        ColumnData::UInt8(_) => Kind::Int { signed: false, bits: 8 },
+        ColumnData::UInt16(_) => Kind::Int { signed: false, bits: 16 },
+        ColumnData::UInt32(_) => Kind::Int { signed: false, bits: 32 },
+        ColumnData::UInt64(_) => Kind::Int { signed: false, bits: 64 },
+        ColumnData::UInt128(_) => Kind::Int { signed: false, bits: 128 },
+        ColumnData::Int8(_) => Kind::Int { signed: true, bits: 8 },
+        ColumnData::Int16(_) => Kind::Int { signed: true, bits: 16 },
+        ColumnData::Int32(_) => Kind::Int { signed: true, bits: 32 },
+        ColumnData::Int64(_) => Kind::Int { signed: true, bits: 64 },
+        ColumnData::Int128(_) => Kind::Int { signed: true, bits: 128 },
+        ColumnData::Float32(_) => Kind::Float(24),
+        ColumnData::Float64(_) => Kind::Float(53),
+        ColumnData::Date(_) => Kind::Date,
+        ColumnData::DateTime(_) => Kind::DateTime,
+        ColumnData::DateTime64(_) => Kind::DateTime64,
+        ColumnData::Nullable(_) => Kind::Nullable,
+        _ => Kind::Other,
+    }
+}
+
+fn value_kind(value: &Value) -> Kind {
+    match value {
+        Value::UInt8(_) => Kind::Int { signed: false, bits: 8 },
+        Value::UInt16(_) => Kind::Int { signed: false, bits: 16 },
+        Value::UInt32(_) => Kind::Int { signed: false, bits: 32 },
+        Value::UInt64(_) => Kind::Int { signed: false, bits: 64 },
+        Value::UInt128(_) => Kind::Int { signed: false, bits: 128 },
+        Value::Int8(_) => Kind::Int { signed: true, bits: 8 },
+        Value::Int16(_) => Kind::Int { signed: true, bits: 16 },
+        Value::Int32(_) => Kind::Int { signed: true, bits: 32 },
+        Value::Int64(_) => Kind::Int { signed: true, bits: 64 },
+        Value::Int128(_) => Kind::Int { signed: true, bits: 128 },
+        Value::Float32(_) => Kind::Float(24),
+        Value::Float64(_) => Kind::Float(53),
+        Value::Date(_) => Kind::Date,
+        Value::DateTime(_) => Kind::DateTime,
+        Value::DateTime64(_) => Kind::DateTime64,
+        Value::Nullable(_) => Kind::Nullable,
+        _ => Kind::Other,
+    }
+}
+
+/// Convert a value to a kind, if it is represented exactly
+fn cast(value: Value, target: Kind) -> Option<Value> {
+    macro_rules! int {
+        ($variant:ident, $ty:ty) => {
+            match number(&value)? {
+                Number::Signed(v) => <$ty>::try_from(v).ok(),
+                Number::Unsigned(v) => <$ty>::try_from(v).ok(),
+                // Integral floats only; 2^127 and beyond do not fit any target
+                Number::Float(f) if f.fract() == 0.0 && f.abs() < 2f64.powi(127) => <$ty>::try_from(f as i128).ok(),
+                Number::Float(_) => None,
+            }
+            .map(Value::$variant)
+        };
+    }
+    match target {
+        Kind::Int { signed: false, bits: 8 } => int!(UInt8, u8),
+        Kind::Int { signed: false, bits: 16 } => int!(UInt16, u16),
+        Kind::Int { signed: false, bits: 32 } => int!(UInt32, u32),
+        Kind::Int { signed: false, bits: 64 } => int!(UInt64, u64),
+        Kind::Int { signed: false, .. } => int!(UInt128, u128),
+        Kind::Int { signed: true, bits: 8 } => int!(Int8, i8),
+        Kind::Int { signed: true, bits: 16 } => int!(Int16, i16),
+        Kind::Int { signed: true, bits: 32 } => int!(Int32, i32),
+        Kind::Int { signed: true, bits: 64 } => int!(Int64, i64),
+        Kind::Int { signed: true, .. } => int!(Int128, i128),
+        Kind::Float(mantissa) => {
+            let f = match number(&value)? {
+                Number::Signed(v) => Some(v as f64).filter(|f| *f as i128 == v)?,
+                Number::Unsigned(v) => Some(v as f64).filter(|f| *f as u128 == v)?,
+                Number::Float(f) => f,
+            };
+            if mantissa == 24 {
+                let narrowed = f as f32;
+                (f64::from(narrowed) == f || f.is_nan()).then_some(Value::Float32(narrowed))
+            } else {
+                Some(Value::Float64(f))
+            }
+        }
+        Kind::Date => match value {
+            Value::DateTime(dt) | Value::DateTime64(dt) => (dt.time() == NaiveTime::MIN).then(|| Value::Date(dt.date())),
+            _ => None,
+        },
+        Kind::DateTime => match value {
+            Value::Date(date) => Some(Value::DateTime(date.and_time(NaiveTime::MIN))),
+            Value::DateTime64(dt) => (dt.nanosecond() == 0).then_some(Value::DateTime(dt)),
+            _ => None,
+        },
+        Kind::DateTime64 => match value {
+            Value::Date(date) => Some(Value::DateTime64(date.and_time(NaiveTime::MIN))),
+            Value::DateTime(dt) => Some(Value::DateTime64(dt)),
+            _ => None,
+        },
+        Kind::Nullable | Kind::Other => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_widen_on_push() {
+        let mut data = ColumnData::UInt64(Vec::new());
+        data.push(Value::UInt8(7)).unwrap();
+        data.push(Value::UInt32(8)).unwrap();
+        assert!(data.push(Value::Int8(1)).is_err());
+        assert!(data.push(Value::Float64(1.0)).is_err());
+        assert!(data.push_with(Value::UInt8(9), ConversionPolicy::Exact).is_err());
+        data.set_value(0, Value::UInt16(6)).unwrap();
+        assert_eq!(data.get_value(0), Some(Value::UInt64(6)));
+        assert_eq!(data.len(), 2);
+
+        let mut signed = ColumnData::Int32(Vec::new());
+        signed.push(Value::UInt16(65_535)).unwrap();
+        assert!(signed.push(Value::UInt32(1)).is_err());
+
+        let mut floats = ColumnData::Float64(Vec::new());
+        floats.push(Value::Float32(0.5)).unwrap();
+        floats.push(Value::Int32(-3)).unwrap();
+        assert!(floats.push(Value::Int64(1)).is_err());
+        assert_eq!(floats.get_value(1), Some(Value::Float64(-3.0)));
+
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let mut times = ColumnData::DateTime64(Vec::new());
+        times.push(Value::Date(date)).unwrap();
+        assert_eq!(times.get_value(0), Some(Value::DateTime64(date.and_hms_opt(0, 0, 0).unwrap())));
+
+        let mut nullable = ColumnData::Nullable(Vec::new());
+        nullable.push(Value::String("a".into())).unwrap();
+        nullable.push(Value::Nullable(None)).unwrap();
+        assert_eq!(nullable.get_value(0), Some(Value::Nullable(Some(Box::new(Value::String("a".into()))))));
+    }
+
+    #[test]
+    fn test_checked_narrow() {
+        let narrow = ConversionPolicy::CheckedNarrow;
+        let mut data = ColumnData::UInt8(Vec::new());
+        data.push_with(Value::UInt64(200), narrow).unwrap();
+        data.push_with(Value::Float64(3.0), narrow).unwrap();
+        data.push_with(Value::Nullable(Some(Box::new(Value::Int64(4)))), narrow).unwrap();
+        assert_eq!(data.push_with(Value::UInt64(300), narrow).unwrap_err(), "300 does not fit UInt8");
+        assert!(data.push_with(Value::Int8(-1), narrow).is_err());
+        assert!(data.push_with(Value::Float64(1.5), narrow).is_err());
+        assert!(data.push_with(Value::Nullable(None), narrow).unwrap_err().contains("NULL"));
+        assert!(data.push_with(Value::String("1".into()), narrow).is_err());
+        assert_eq!(data.len(), 3);
+
+        let mut floats = ColumnData::Float32(Vec::new());
+        floats.push_with(Value::Float64(0.25), narrow).unwrap();
+        assert!(floats.push_with(Value::Float64(0.1), narrow).is_err());
+        assert!(floats.push_with(Value::Int64((1 << 24) + 1), narrow).is_err());
+
+        let midnight = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let mut dates = ColumnData::Date(Vec::new());
+        dates.push_with(Value::DateTime(midnight), narrow).unwrap();
+        let later = midnight + chrono::Duration::seconds(1);
+        assert!(dates.set_value_with(0, Value::DateTime64(later), narrow).is_err());
+        assert_eq!(dates.get_value(0), Some(Value::Date(midnight.date())));
+    }
+}
@@ -0,0 +1,288 @@
+//! Browser client over the HTTP interface
+//!
+//! The native client needs sockets and a Tokio runtime, neither of which
+//! exists in a browser. [`Client`] instead sends each query with `fetch` to
+//! a ClickHouse HTTP endpoint (which must allow the page's origin through
+//! CORS) and reads the `JSONCompact` output into a [`Block`].
+//!
+//! Build for `wasm32-unknown-unknown` with default features off:
+//!
+//! ```toml
+//! clickhouse-rs = { version = "0.1", default-features = false, features = ["wasm"] }
+//! ```
+//!
+//! Request building and response parsing do not depend on the target, so
+//! they can be used and tested natively; only sending needs `wasm32`.
+
+use crate::error::{Error, Result};
+use crate::secret::Secret;
+use crate::types::{Block, Column, ColumnData, TypeDescriptor, Value};
+
+/// Output format requested from the server
+const OUTPUT_FORMAT: &str = "JSONCompact";
+
+/// ClickHouse client for browsers
+#[derive(Debug, Clone)]
+pub struct Client {
+    url: String,
+    database: Option<String>,
+    username: Option<String>,
+    password: Secret,
+    settings: Vec<(String, String)>,
+}
+
+impl Client {
+    /// Create a client for an HTTP endpoint, such as `https://ch.example.com:8443`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            database: None,
+            username: None,
+            password: Secret::default(),
+            settings: Vec::new(),
+        }
+    }
+
+    /// Set the default database
+    pub fn database(mut self, database: impl Into<String>) -> Self {
+        self.database = Some(database.into());
+        self
+    }
+
+    /// Set the user and password
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = password.into().into();
+        self
+    }
+
+    /// Add a setting sent with every query
+    pub fn setting(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.push((name.into(), value.into()));
+        self
+    }
+
+    /// Get the URL a query is posted to
+    ///
+    /// The query itself goes in the request body.
+    pub fn request_url(&self) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        if let Some(database) = &self.database {
+            query.append_pair("database", database);
+        }
+        query.append_pair("default_format", OUTPUT_FORMAT);
+        for (name, value) in &self.settings {
+            query.append_pair(name, value);
+        }
+        format!("{}/?{}", self.url.trim_end_matches('/'), query.finish())
+    }
+
+    /// Get the headers sent with every request
+    ///
+    /// Credentials go in headers rather than the URL, which browsers may log.
+    pub fn request_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(username) = &self.username {
+            headers.push(("X-ClickHouse-User", username.clone()));
+            headers.push(("X-ClickHouse-Key", self.password.expose().clone()));
+        }
+        headers
+    }
+
+    /// Execute a query and read its rows
+    #[cfg(target_arch = "wasm32")]
+    pub async fn query(&self, sql: &str) -> Result<Block> {
+        parse_json_compact(&self.send(sql).await?)
+    }
+
+    /// Execute a statement that returns no rows
+    #[cfg(target_arch = "wasm32")]
+    pub async fn execute(&self, sql: &str) -> Result<()> {
+        self.send(sql).await.map(drop)
+    }
+
+    /// Check that the server answers
+    #[cfg(target_arch = "wasm32")]
+    pub async fn ping(&self) -> Result<()> {
+        self.execute("SELECT 1").await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn send(&self, sql: &str) -> Result<String> {
+        let fetch_error = |e: gloo_net::Error| Error::Network(std::io::Error::other(e.to_string()));
+        let mut request = gloo_net::http::Request::post(&self.request_url());
+        for (name, value) in self.request_headers() {
+            request = request.header(name, &value);
+        }
+        let response = request.body(sql).map_err(fetch_error)?.send().await.map_err(fetch_error)?;
+        let body = response.text().await.map_err(fetch_error)?;
+        if !response.ok() {
+            return Err(Error::Http {
+                status: response.status(),
+                message: body.trim().to_string(),
+            });
+        }
+        Ok(body)
+    }
+}
+
+/// Read a `JSONCompact` response into a block
+///
+/// Integers, floats, strings and `Bool` keep their types, as do `Nullable`
+/// and `Array` of them. Other types, such as dates, decimals and UUIDs, are
+/// kept in the text form the server writes them in.
+pub fn parse_json_compact(body: &str) -> Result<Block> {
+    let invalid = |what: &str| Error::InvalidData(format!("Invalid JSONCompact response: {}", what));
+    let json: serde_json::Value = serde_json::from_str(body)?;
+    let meta = json.get("meta").and_then(|m| m.as_array()).ok_or_else(|| invalid("missing meta"))?;
+    let rows = json.get("data").and_then(|d| d.as_array()).ok_or_else(|| invalid("missing data"))?;
+
+    let mut columns = Vec::with_capacity(meta.len());
+    for (index, column) in meta.iter().enumerate() {
+        let name = column.get("name").and_then(|n| n.as_str()).ok_or_else(|| invalid("column without name"))?;
+        let type_name = column.get("type").and_then(|t| t.as_str()).ok_or_else(|| invalid("column without type"))?;
+        let descriptor = TypeDescriptor::parse(type_name)?;
+        let mut data = empty_column(&descriptor);
+        for row in rows {
+            let cell = row.get(index).ok_or_else(|| invalid("short row"))?;
+            let value = json_to_value(&descriptor, cell)?;
+            data.push(value)
+                .map_err(|e| Error::TypeConversion(format!("Column '{}': {}", name, e)))?;
+        }
+        columns.push(Column::new(name, type_name, data));
+    }
+    Ok(Block::with_columns(columns))
+}
+
+fn kept_type(descriptor: &TypeDescriptor) -> Option<&str> {
+    match descriptor {
+        TypeDescriptor::Simple(name) => match name.as_str() {
+            "UInt8" | "UInt16" | "UInt32" | "UInt64" | "Int8" | "Int16" | "Int32" | "Int64" | "Float32"
+            | "Float64" | "String" => Some(name),
+            "Bool" => Some("UInt8"),
+            _ => None,
+        },
+        TypeDescriptor::LowCardinality(inner) => kept_type(inner),
+        _ => None,
+    }
+}
+
+fn empty_column(descriptor: &TypeDescriptor) -> ColumnData {
+    match descriptor {
+        TypeDescriptor::Nullable(_) => ColumnData::Nullable(Vec::new()),
+        TypeDescriptor::Array(_) => ColumnData::Array(Vec::new()),
+        _ => match kept_type(descriptor) {
+            Some("UInt8") => ColumnData::UInt8(Vec::new()),
+            Some("UInt16") => ColumnData::UInt16(Vec::new()),
+            Some("UInt32") => ColumnData::UInt32(Vec::new()),
+            Some("UInt64") => ColumnData::UInt64(Vec::new()),
+            Some("Int8") => ColumnData::Int8(Vec::new()),
+            Some("Int16") => ColumnData::Int16(Vec::new()),
+            Some("Int32") => ColumnData::Int32(Vec::new()),
+            Some("Int64") => ColumnData::Int64(Vec::new()),
+            Some("Float32") => ColumnData::Float32(Vec::new()),
+            Some("Float64") => ColumnData::Float64(Vec::new()),
+            _ => ColumnData::String(Vec::new()),
+        },
+    }
+}
+
+fn json_to_value(descriptor: &TypeDescriptor, json: &serde_json::Value) -> Result<Value> {
+    use serde_json::Value as Json;
+
+    let mismatch = || Error::TypeConversion(format!("Cannot read {} as {:?}", json, descriptor));
+    // 64-bit integers arrive quoted by default
+    let number = |json: &Json| -> Option<String> {
+        match json {
+            Json::Number(n) => Some(n.to_string()),
+            Json::String(s) => Some(s.clone()),
+            Json::Bool(b) => Some(u8::from(*b).to_string()),
+            _ => None,
+        }
+    };
+
+    Ok(match descriptor {
+        TypeDescriptor::Nullable(inner) => match json {
+            Json::Null => Value::Nullable(None),
+            _ => Value::Nullable(Some(Box::new(json_to_value(inner, json)?))),
+        },
+        TypeDescriptor::Array(inner) => {
+            let items = json.as_array().ok_or_else(mismatch)?;
+            Value::Array(items.iter().map(|item| json_to_value(inner, item)).collect::<Result<_>>()?)
+        }
+        _ => match kept_type(descriptor) {
+            Some("String") => match json {
+                Json::String(s) => Value::String(s.clone()),
+                _ => return Err(mismatch()),
+            },
+            Some(kept) => {
+                let text = number(json).ok_or_else(mismatch)?;
+                let parsed = match kept {
+                    "UInt8" => text.parse().map(Value::UInt8).ok(),
+                    "UInt16" => text.parse().map(Value::UInt16).ok(),
+                    "UInt32" => text.parse().map(Value::UInt32).ok(),
+                    "UInt64" => text.parse().map(Value::UInt64).ok(),
+                    "Int8" => text.parse().map(Value::Int8).ok(),
+                    "Int16" => text.parse().map(Value::Int16).ok(),
+                    "Int32" => text.parse().map(Value::Int32).ok(),
+                    "Int64" => text.parse().map(Value::Int64).ok(),
+                    "Float32" => text.parse().map(Value::Float32).ok(),
+                    _ => text.parse().map(Value::Float64).ok(),
+                };
+                parsed.ok_or_else(mismatch)?
+            }
+            None => match json {
+                Json::String(s) => Value::String(s.clone()),
+                other => Value::String(other.to_string()),
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasm_request() {
+        let client = Client::new("https://ch.example.com:8443/")
+            .database("web")
+            .credentials("viewer", "s3cret")
+            .setting("max_result_rows", "1000");
+        assert_eq!(
+            client.request_url(),
+            "https://ch.example.com:8443/?database=web&default_format=JSONCompact&max_result_rows=1000"
+        );
+        assert_eq!(
+            client.request_headers(),
+            [("X-ClickHouse-User", "viewer".to_string()), ("X-ClickHouse-Key", "s3cret".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_json_compact() {
+        let body = r#"{
+            "meta": [
+                {"name": "id", "type": "UInt64"},
+                {"name": "score", "type": "Nullable(Float64)"},
+                {"name": "tags", "type": "Array(LowCardinality(String))"},
+                {"name": "day", "type": "Date"}
+            ],
+            "data": [
+                ["18446744073709551615", 1.5, ["a", "b"], "2024-01-02"],
+                ["7", null, [], "2024-01-03"]
+            ],
+            "rows": 2
+        }"#;
+        let block = parse_json_compact(body).unwrap();
+        assert_eq!(block.row_count, 2);
+        let row = block.get_row(0).unwrap();
+        assert_eq!(row.get(0), Some(&Some(Value::UInt64(u64::MAX))));
+        assert_eq!(row.get(3), Some(&Some(Value::String("2024-01-02".to_string()))));
+        assert_eq!(block.get_row(1).unwrap().get(1), Some(&Some(Value::Nullable(None))));
+        assert_eq!(block.get_column("day").unwrap().type_name(), "Date");
+
+        assert!(parse_json_compact(r#"{"meta": [{"name": "id", "type": "UInt8"}], "data": [[300]]}"#).is_err());
+        assert!(parse_json_compact(r#"{"data": []}"#).is_err());
+    }
+}
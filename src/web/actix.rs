@@ -0,0 +1,73 @@
+//! actix-web integration
+//!
+//! Register a [`ClickHouse`] as app data, then take it as a handler
+//! argument:
+//!
+//! ```rust,ignore
+//! async fn count(clickhouse: ClickHouse) -> String {
+//!     let result = clickhouse.query("SELECT count() FROM events").await.unwrap();
+//!     result.format_table(1, 40)
+//! }
+//!
+//! App::new()
+//!     .app_data(clickhouse.clone())
+//!     .configure(configure_health)
+//!     .route("/count", web::get().to(count))
+//! ```
+
+use super::ClickHouse;
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use std::future::{ready, Ready};
+
+/// Path the health handler is mounted at by [`configure_health`]
+pub const HEALTH_PATH: &str = "/health";
+
+impl FromRequest for ClickHouse {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    /// Take the handle from app data, registered either as is or as `web::Data`
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let clickhouse = req
+            .app_data::<ClickHouse>()
+            .cloned()
+            .or_else(|| req.app_data::<web::Data<ClickHouse>>().map(|data| data.get_ref().clone()));
+        ready(clickhouse.ok_or_else(|| {
+            actix_web::error::ErrorInternalServerError("ClickHouse client is not registered as app data")
+        }))
+    }
+}
+
+/// Answer with the client's [`HealthReport`](super::HealthReport)
+pub async fn health(clickhouse: ClickHouse) -> HttpResponse {
+    let report = clickhouse.health().await;
+    let status = StatusCode::from_u16(report.status_code()).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+    HttpResponse::build(status).json(report)
+}
+
+/// Serve [`health`] at [`HEALTH_PATH`], for use with `App::configure`
+pub fn configure_health(config: &mut web::ServiceConfig) {
+    config.route(HEALTH_PATH, web::get().to(health));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, ClientOptions};
+    use actix_web::test::TestRequest;
+
+    #[tokio::test]
+    async fn test_actix_extractor_and_health() {
+        let clickhouse = ClickHouse::new(Client::new(ClientOptions::new().lazy_connect(true)).unwrap());
+
+        let request = TestRequest::default().app_data(web::Data::new(clickhouse.clone())).to_http_request();
+        let extracted = ClickHouse::extract(&request).await.unwrap();
+        assert!(std::sync::Arc::ptr_eq(extracted.client(), clickhouse.client()));
+        assert!(ClickHouse::extract(&TestRequest::default().to_http_request()).await.is_err());
+
+        let response = health(extracted).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,87 @@
+//! axum integration
+//!
+//! Put a [`ClickHouse`] in the router state, or in any state it can be taken
+//! from with [`FromRef`], then take it as a handler argument:
+//!
+//! ```rust,ignore
+//! async fn count(clickhouse: ClickHouse) -> String {
+//!     let result = clickhouse.query("SELECT count() FROM events").await.unwrap();
+//!     result.format_table(1, 40)
+//! }
+//!
+//! let app = Router::new()
+//!     .route("/count", get(count))
+//!     .merge(health_router())
+//!     .with_state(clickhouse);
+//! ```
+
+use super::{ClickHouse, HealthReport};
+use axum::extract::{FromRef, FromRequestParts, State};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use std::convert::Infallible;
+
+/// Path the health handler is mounted at by [`health_router`]
+pub const HEALTH_PATH: &str = "/health";
+
+impl<S> FromRequestParts<S> for ClickHouse
+where
+    ClickHouse: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClickHouse::from_ref(state))
+    }
+}
+
+/// Answer with the client's [`HealthReport`]
+pub async fn health(State(clickhouse): State<ClickHouse>) -> (StatusCode, Json<HealthReport>) {
+    let report = clickhouse.health().await;
+    let status = StatusCode::from_u16(report.status_code()).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+    (status, Json(report))
+}
+
+/// Router serving [`health`] at [`HEALTH_PATH`]
+pub fn health_router<S>() -> Router<S>
+where
+    ClickHouse: FromRef<S>,
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new().route(HEALTH_PATH, get(health))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, ClientOptions};
+
+    #[derive(Clone)]
+    struct AppState {
+        clickhouse: ClickHouse,
+    }
+
+    impl FromRef<AppState> for ClickHouse {
+        fn from_ref(state: &AppState) -> Self {
+            state.clickhouse.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_axum_extractor_and_health() {
+        let clickhouse = ClickHouse::new(Client::new(ClientOptions::new().lazy_connect(true)).unwrap());
+        let state = AppState { clickhouse };
+        let (mut parts, ()) = axum::http::Request::new(()).into_parts();
+        let extracted = ClickHouse::from_request_parts(&mut parts, &state).await.unwrap();
+        assert!(std::sync::Arc::ptr_eq(extracted.client(), state.clickhouse.client()));
+
+        let (status, Json(report)) = health(State(extracted)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!report.healthy);
+
+        let _router: Router<AppState> = health_router();
+    }
+}
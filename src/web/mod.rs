@@ -0,0 +1,144 @@
+//! Web framework integration
+//!
+//! [`ClickHouse`] is a cheaply cloneable handle to a [`Client`] meant to live
+//! in the application state of a web service. With the `web-axum` and
+//! `web-actix` features it can be taken directly as a handler argument, and
+//! each framework gets a ready-made health endpoint backed by
+//! [`Client::health_check`].
+//!
+//! For graceful shutdown, let the server finish its in-flight requests, then
+//! drain the client:
+//!
+//! ```rust,ignore
+//! axum::serve(listener, app).with_graceful_shutdown(shutdown_signal()).await?;
+//! clickhouse.shutdown(Duration::from_secs(30)).await?;
+//! ```
+//!
+//! The health endpoint answers 503 while the client drains, so load
+//! balancers stop routing to the instance.
+
+#[cfg(feature = "web-actix")]
+pub mod actix;
+#[cfg(feature = "web-axum")]
+pub mod axum;
+
+use crate::client::Client;
+use crate::error::Result;
+use serde::Serialize;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use crate::client::shutdown_signal;
+
+/// Shared client handle for web application state
+#[derive(Clone)]
+pub struct ClickHouse {
+    client: Arc<Client>,
+}
+
+impl ClickHouse {
+    /// Wrap a client
+    pub fn new(client: Client) -> Self {
+        Self { client: Arc::new(client) }
+    }
+
+    /// Get the shared client
+    pub fn client(&self) -> &Arc<Client> {
+        &self.client
+    }
+
+    /// Collect a health report
+    pub async fn health(&self) -> HealthReport {
+        let health = self.client.health_check().await;
+        let quiescing = self.client.is_quiescing();
+        HealthReport {
+            healthy: health.is_healthy() && !quiescing,
+            quiescing,
+            summary: health.summary(),
+            circuit_breaker: health.circuit_breaker_health.description(),
+            total_connections: health.pool_stats.total_connections,
+            active_connections: health.pool_stats.active_connections,
+            idle_connections: health.pool_stats.idle_connections,
+            in_flight_queries: self.client.in_flight_queries(),
+        }
+    }
+
+    /// Drain in-flight queries and close the connection pool
+    ///
+    /// Call this after the server has stopped accepting requests.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<()> {
+        self.client.shutdown_on(std::future::ready(()), timeout).await
+    }
+}
+
+impl Deref for ClickHouse {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl From<Client> for ClickHouse {
+    fn from(client: Client) -> Self {
+        Self::new(client)
+    }
+}
+
+impl std::fmt::Debug for ClickHouse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClickHouse").field("host", &self.client.options().host).finish()
+    }
+}
+
+/// Health endpoint response body
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// Whether the instance should receive traffic
+    pub healthy: bool,
+    /// Whether the client is draining for shutdown
+    pub quiescing: bool,
+    /// One-line summary of pool, circuit breaker and metrics
+    pub summary: String,
+    /// Circuit breaker state
+    pub circuit_breaker: String,
+    /// Connections in the pool
+    pub total_connections: usize,
+    /// Connections currently checked out
+    pub active_connections: usize,
+    /// Connections ready for use
+    pub idle_connections: usize,
+    /// Queries running through the client
+    pub in_flight_queries: usize,
+}
+
+impl HealthReport {
+    /// Get the HTTP status to answer with: 200 when healthy, 503 otherwise
+    pub fn status_code(&self) -> u16 {
+        if self.healthy {
+            200
+        } else {
+            503
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClientOptions;
+
+    #[tokio::test]
+    async fn test_health_report_while_draining() {
+        let clickhouse = ClickHouse::new(Client::new(ClientOptions::new().lazy_connect(true)).unwrap());
+        let report = clickhouse.health().await;
+        assert!(!report.quiescing);
+        assert_eq!(report.idle_connections, 0);
+        assert_eq!(report.status_code(), 503);
+
+        clickhouse.shutdown(Duration::from_millis(10)).await.unwrap();
+        let report = clickhouse.health().await;
+        assert!(report.quiescing && !report.healthy);
+    }
+}
@@ -273,3 +273,144 @@ async fn test_error_retryability() {
     let query_error = Error::QueryExecution("Syntax error".to_string());
     assert!(!query_error.is_retryable());
 }
+
+#[test]
+fn test_error_context_and_categories() {
+    use clickhouse_rs::error::{Error, ErrorCategory, ErrorContext, SQL_SNIPPET_LEN};
+
+    let error = Error::Timeout(Duration::from_secs(5)).context(
+        ErrorContext::new("query")
+            .with_host("db1:9000")
+            .with_query_id("abc")
+            .with_sql(&"SELECT 1 ".repeat(100)),
+    );
+
+    // Predicates and categories look through the context
+    assert!(error.is_retryable());
+    assert_eq!(error.categories(), &[ErrorCategory::Network]);
+    assert!(matches!(error.root(), Error::Timeout(_)));
+
+    let context = error.context_info().unwrap();
+    assert_eq!(context.host.as_deref(), Some("db1:9000"));
+    assert_eq!(context.sql.as_ref().unwrap().len(), SQL_SNIPPET_LEN + 3);
+    assert!(error.to_string().contains("Query ID: abc"));
+
+    assert!(Error::QueryExecution("bad".to_string()).is_category(ErrorCategory::Server));
+    assert!(Error::InvalidData("bad".to_string()).is_category(ErrorCategory::Data));
+    assert!(Error::Configuration("bad".to_string()).is_category(ErrorCategory::Client));
+    assert_eq!(
+        Error::Protocol("bad".to_string()).categories(),
+        &[ErrorCategory::Network, ErrorCategory::Data]
+    );
+}
+
+#[test]
+fn test_error_codes_are_stable() {
+    use clickhouse_rs::error::{Error, ErrorCode};
+
+    // These values are part of the public API and must never change
+    assert_eq!(ErrorCode::Network.as_u32(), 1000);
+    assert_eq!(ErrorCode::Http.as_u32(), 1005);
+    assert_eq!(ErrorCode::Server.as_u32(), 2002);
+    assert_eq!(ErrorCode::InvalidData.as_u32(), 3003);
+    assert_eq!(ErrorCode::Custom.as_u32(), 4004);
+    assert_eq!(ErrorCode::Draining.as_u32(), 4005);
+    for code in ErrorCode::ALL {
+        assert_eq!(ErrorCode::from_u32(code.as_u32()), Some(code));
+    }
+    assert_eq!(ErrorCode::from_u32(1), None);
+
+    assert_eq!(Error::Timeout(Duration::from_secs(1)).code(), ErrorCode::Timeout);
+    assert_eq!(ErrorCode::Timeout.to_string(), "CH1002");
+}
+
+#[test]
+fn test_error_server_helpers() {
+    use clickhouse_rs::error::{server_codes, Error, ErrorContext};
+    use clickhouse_rs::protocol::ServerException;
+
+    let unknown_table: Error = ServerException::new("Table default.t doesn't exist", server_codes::UNKNOWN_TABLE, "DB::Exception").into();
+    let error = unknown_table.context(ErrorContext::new("query"));
+    assert_eq!(error.server_error_code(), Some(server_codes::UNKNOWN_TABLE));
+    assert!(error.is_schema_error());
+    assert!(!error.is_auth_error());
+
+    let wrong_password: Error = ServerException::new("Wrong password", server_codes::AUTHENTICATION_FAILED, "DB::Exception").into();
+    assert!(wrong_password.is_auth_error());
+    assert!(Error::Http { status: 401, message: "Unauthorized".to_string() }.is_auth_error());
+    assert_eq!(Error::QueryExecution("failed".to_string()).server_error_code(), None);
+}
+
+#[test]
+fn test_error_find_in_anyhow() {
+    use clickhouse_rs::error::{Error, ErrorCode};
+
+    let err = anyhow::Error::new(Error::Tls("handshake failed".to_string())).context("connecting to replica");
+    let found = Error::find_in(err.as_ref()).expect("ClickHouse error in chain");
+    assert_eq!(found.code(), ErrorCode::Tls);
+
+    let other = anyhow::anyhow!("unrelated");
+    assert!(Error::find_in(other.as_ref()).is_none());
+}
+
+#[tokio::test]
+async fn test_transactions_require_option() {
+    let options = ClientOptions::default().min_connections(0);
+    assert!(!options.use_experimental_transactions);
+
+    let client = Client::new(options).unwrap();
+    let result = client.begin_transaction().await;
+    assert!(matches!(result, Err(Error::Configuration(_))));
+
+    let options = ClientOptions::default().enable_experimental_transactions();
+    assert!(options.use_experimental_transactions);
+}
+
+#[tokio::test]
+async fn test_client_settings_profiles() {
+    use clickhouse_rs::client::QuerySettings;
+
+    let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+    assert_eq!(client.settings_profile("etl").unwrap().async_insert, Some(true));
+    assert!(client.settings_profile("reporting").is_none());
+
+    client.register_settings_profile("reporting", QuerySettings::profile_olap_heavy().max_threads(4));
+    assert_eq!(client.settings_profile("reporting").unwrap().max_threads, Some(4));
+
+    // Registered profiles shadow built-in ones
+    client.register_settings_profile("etl", QuerySettings::new().priority(7));
+    assert_eq!(client.settings_profile("etl").unwrap().async_insert, None);
+
+    let result = client.query_with_profile("SELECT 1", "missing").await;
+    assert!(matches!(result, Err(Error::Configuration(_))));
+}
+
+#[tokio::test]
+async fn test_client_quiesce_rejects_new_queries() {
+    use clickhouse_rs::error::ErrorCode;
+
+    let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+    assert!(!client.is_quiescing());
+
+    client.quiesce(Duration::from_secs(1)).await.unwrap();
+    assert!(client.is_quiescing());
+    assert_eq!(client.in_flight_queries(), 0);
+
+    let result = client.query("SELECT 1").await;
+    assert!(matches!(result, Err(Error::Draining)));
+    assert_eq!(result.unwrap_err().code(), ErrorCode::Draining);
+    assert!(matches!(client.execute("SELECT 1").await, Err(Error::Draining)));
+
+    // Clones share the drain state
+    let clone = client.clone();
+    assert!(clone.is_quiescing());
+    client.resume();
+    assert!(!clone.is_quiescing());
+}
+
+#[tokio::test]
+async fn test_client_shutdown_on_signal() {
+    let client = Client::new(ClientOptions::default().min_connections(0)).unwrap();
+    client.shutdown_on(async {}, Duration::from_secs(1)).await.unwrap();
+    assert!(client.is_quiescing());
+}
@@ -55,6 +55,17 @@ pub fn create_test_websocket_client() -> Result<Client> {
     Client::new(options)
 }
 
+/// Start a disposable ClickHouse server with the test credentials (requires Docker)
+#[cfg(feature = "testing")]
+pub async fn start_test_container() -> Result<clickhouse_rs::testing::ClickHouseContainer> {
+    clickhouse_rs::testing::ClickHouseContainer::builder()
+        .username(TEST_USER)
+        .password(TEST_PASSWORD)
+        .database(TEST_DATABASE)
+        .start()
+        .await
+}
+
 /// Check if ClickHouse server is available
 pub async fn is_clickhouse_available() -> bool {
     match create_test_client().await {
@@ -1065,3 +1065,21 @@ fn test_grpc_client_drop_integration() {
         assert!(!id.is_empty());
     });
 }
+
+#[cfg(feature = "testing")]
+#[tokio::test]
+#[ignore = "This test requires a local Docker daemon"]
+async fn test_container_bootstrap() {
+    let clickhouse = common::start_test_container().await.expect("Failed to start container");
+
+    assert_ne!(clickhouse.native_port(), 0);
+    assert_eq!(clickhouse.options().port, clickhouse.native_port());
+
+    clickhouse
+        .bootstrap_schema("CREATE TABLE IF NOT EXISTS container_test (id UInt64) ENGINE = Memory")
+        .await
+        .expect("Failed to bootstrap schema");
+
+    let result = clickhouse.bootstrap_schema("SELECT * FROM missing_table").await;
+    assert!(matches!(result, Err(Error::QueryExecution(_))));
+}
@@ -547,3 +547,80 @@ fn test_block_with_map_values() {
         panic!("Expected map column data");
     }
 }
+
+#[test]
+fn test_query_result_datetime_timezones() {
+    use chrono::{NaiveDate, Timelike};
+    use clickhouse_rs::client::{QueryMetadata, QueryResult, QueryStats};
+    use clickhouse_rs::types::column_timezone;
+
+    assert_eq!(column_timezone("DateTime('Asia/Tokyo')"), Some("Asia/Tokyo"));
+    assert_eq!(column_timezone("Nullable(DateTime64(3, 'UTC'))"), Some("UTC"));
+    assert_eq!(column_timezone("DateTime"), None);
+
+    let instant = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let mut block = Block::new();
+    block.add_column("plain", Column::new("plain", "DateTime", ColumnData::DateTime(vec![instant])));
+    block.add_column("tokyo", Column::new("tokyo", "DateTime('Asia/Tokyo')", ColumnData::DateTime(vec![instant])));
+
+    let result = QueryResult::new(
+        QueryMetadata::new(
+            vec!["plain".to_string(), "tokyo".to_string()],
+            vec!["DateTime".to_string(), "DateTime('Asia/Tokyo')".to_string()],
+        ),
+        vec![block],
+        QueryStats::new(1, 0, std::time::Duration::ZERO),
+    );
+
+    // Without a server timezone values are read as UTC
+    assert_eq!(result.server_timezone(), None);
+    assert_eq!(result.get_datetime(0, "plain").unwrap().unwrap().hour(), 12);
+
+    let result = result.with_server_timezone(chrono_tz::Europe::Berlin);
+    assert_eq!(result.get_datetime(0, "plain").unwrap().unwrap().hour(), 13);
+    assert_eq!(result.get_datetime(0, "tokyo").unwrap().unwrap().hour(), 21);
+    assert!(result.get_datetime(0, "missing").is_err());
+}
+
+#[test]
+fn test_fixed_string_column_padding() {
+    let mut column = Column::new("code", "FixedString(4)", ColumnData::FixedString(Vec::new()));
+    column.push(Value::FixedString(FixedString::from("ab"))).unwrap();
+    assert!(column.push(Value::FixedString(FixedString::from("abcde"))).is_err());
+
+    match column.get_value(0) {
+        Some(Value::FixedString(code)) => {
+            assert_eq!(code.as_bytes(), b"ab\0\0");
+            assert!(code == "ab");
+        }
+        other => panic!("Unexpected value: {:?}", other),
+    }
+    assert_eq!(column.len(), 1);
+}
+
+#[test]
+fn test_query_result_schema() {
+    use clickhouse_rs::client::{QueryMetadata, QueryResult, QueryStats};
+
+    let result = QueryResult::new(
+        QueryMetadata::new(
+            vec!["price".to_string(), "at".to_string(), "tag".to_string()],
+            vec![
+                "Nullable(Decimal(18, 4))".to_string(),
+                "DateTime".to_string(),
+                "LowCardinality(String)".to_string(),
+            ],
+        ),
+        Vec::new(),
+        QueryStats::new(0, 0, std::time::Duration::ZERO),
+    )
+    .with_server_timezone(chrono_tz::Europe::Berlin);
+
+    let schema = result.schema().unwrap();
+    assert_eq!(schema.len(), 3);
+    assert!(schema[0].nullable);
+    assert_eq!(schema[0].decimal, Some((18, 4)));
+    assert_eq!(schema[1].timezone.as_deref(), Some("Europe/Berlin"));
+    assert!(!schema[2].nullable);
+    assert_eq!(schema[2].timezone, None);
+}